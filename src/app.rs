@@ -10,17 +10,50 @@ use tokio::task::spawn_blocking;
 use crate::app::legacy::*;
 use crate::paths::Paths;
 
+pub mod acme;
+pub mod acoustid;
+pub mod api_key;
+pub mod artist_image;
+pub mod artwork;
+pub mod audit;
 pub mod auth;
 pub mod config;
 pub mod ddns;
+pub mod device_login;
+pub mod dlna;
+pub mod duplicates;
+pub mod events;
+pub mod favorites;
 pub mod formats;
+pub mod history;
+pub mod hooks;
 pub mod index;
+pub mod invite;
+pub mod jukebox;
 pub mod legacy;
+pub mod listenbrainz;
+pub mod lyrics;
+pub mod mpd;
+pub mod musicbrainz;
 pub mod ndb;
+pub mod oidc;
 pub mod peaks;
 pub mod playlist;
+pub mod preferences;
+pub mod quality;
+pub mod queue;
+pub mod radio;
+pub mod ratings;
+pub mod remote;
 pub mod scanner;
+pub mod session;
+pub mod silence;
+pub mod stats;
+pub mod throttle;
 pub mod thumbnail;
+pub mod transcode;
+pub mod transfers;
+pub mod webauthn;
 
 #[cfg(test)]
 pub mod test;
@@ -42,6 +75,8 @@ pub enum Error {
 	Ape(#[from] ape::Error),
 	#[error("ID3 error in `{0}`: `{1}`")]
 	Id3(PathBuf, id3::Error),
+	#[error("Could not parse CUE sheet `{0}`")]
+	CueSheetInvalid(PathBuf),
 	#[error("Metaflac error in `{0}`: `{1}`")]
 	Metaflac(PathBuf, metaflac::Error),
 	#[error("Mp4aMeta error in `{0}`: `{1}`")]
@@ -67,6 +102,8 @@ pub enum Error {
 	MediaPacketError(symphonia::core::errors::Error),
 	#[error(transparent)]
 	MediaProbeError(symphonia::core::errors::Error),
+	#[error("Could not compute audio fingerprint of `{0}`")]
+	AudioFingerprint(PathBuf),
 
 	#[error(transparent)]
 	PeaksSerialization(bitcode::Error),
@@ -93,6 +130,52 @@ pub enum Error {
 	IndexAlbumArtPatternInvalid,
 	#[error("DDNS update URL is invalid")]
 	DDNSUpdateURLInvalid,
+	#[error("Filename pattern is invalid")]
+	FilenamePatternInvalid,
+	#[error("Base path is invalid")]
+	BasePathInvalid,
+	#[error("ACME domain is invalid")]
+	AcmeDomainInvalid,
+	#[error("Alias name cannot be blank")]
+	AliasInvalid,
+	#[error("Post-scan webhook URL is invalid")]
+	PostScanWebhookURLInvalid,
+	#[error("Webhook URL must be an http or https URL")]
+	WebhookURLInvalid,
+	#[error("S3 endpoint must be an http or https URL")]
+	S3EndpointInvalid,
+	#[error("S3 storage requires a bucket name")]
+	S3BucketMissing,
+	#[error("WebDAV URL must be an http or https URL")]
+	WebdavURLInvalid,
+	#[error("SMB server must be an smb:// address")]
+	SmbServerInvalid,
+	#[error("SMB storage requires a share name")]
+	SmbShareMissing,
+	#[error("Limits must be greater than zero")]
+	LimitInvalid,
+	#[error("Token lifetime must be greater than zero")]
+	TokenLifetimeInvalid,
+	#[error("Lockout threshold and duration must be greater than zero")]
+	LockoutInvalid,
+	#[error("OpenID Connect issuer URL must be an http or https URL")]
+	OidcIssuerURLInvalid,
+	#[error("Passkey origin must be an http or https URL without a path")]
+	PasskeyOriginInvalid,
+	#[error(
+		"Passkey relying party ID must be the origin's host name or one of its parent domains"
+	)]
+	PasskeyRelyingPartyInvalid,
+	#[error("Trusted proxy is not a valid IP address or range")]
+	TrustedProxyInvalid,
+	#[error("Scan schedule is not a valid cron expression")]
+	ScanScheduleInvalid,
+	#[error("Exclude pattern is not a valid glob pattern")]
+	ExcludePatternInvalid,
+	#[error("Artist separator cannot be empty")]
+	ArtistSeparatorInvalid,
+	#[error("The fanart.tv artist image provider requires an API key")]
+	FanartTvApiKeyMissing,
 
 	#[error("Could not deserialize configuration: `{0}`")]
 	ConfigDeserialization(toml::de::Error),
@@ -102,6 +185,8 @@ pub enum Error {
 	IndexDeserializationError,
 	#[error("Could not serialize collection")]
 	IndexSerializationError,
+	#[error("Search index error: `{0}`")]
+	SearchIndex(#[from] tantivy::TantivyError),
 
 	#[error("Invalid Directory")]
 	InvalidDirectory(String),
@@ -113,6 +198,16 @@ pub enum Error {
 	UserNotFound,
 	#[error("Directory not found: {0}")]
 	DirectoryNotFound(PathBuf),
+	#[error("No collection scan is in progress")]
+	NoScanInProgress,
+	#[error("Collection scan is paused")]
+	ScanPaused,
+	#[error("Jukebox is not available")]
+	JukeboxUnavailable,
+	#[error("Jukebox queue position is out of range")]
+	JukeboxPositionInvalid,
+	#[error("Could not open audio output: {0}")]
+	AudioOutput(String),
 	#[error("Artist not found")]
 	ArtistNotFound,
 	#[error("Album not found")]
@@ -125,21 +220,109 @@ pub enum Error {
 	SearchQueryParseError,
 	#[error("Playlist not found")]
 	PlaylistNotFound,
+	#[error("Play queue position is out of range")]
+	PlayQueuePositionInvalid,
+	#[error("Radio station not found")]
+	RadioStationNotFound,
+	#[error("Cannot use empty radio station name")]
+	EmptyRadioStationName,
+	#[error("Radio station URL must be an http or https URL")]
+	RadioStationUrlInvalid,
+	#[error("Could not connect to radio station")]
+	RadioStreamUnavailable,
+	#[error("Could not transcode audio: `{0}`")]
+	TranscodingFailed(String),
+	#[error("Remote storage request for `{0}` failed: {1}")]
+	RemoteStorage(PathBuf, String),
+	#[error("Stream bitrate must be between 8 and 320 kbps")]
+	StreamBitrateInvalid,
+	#[error("Uploaded artwork is not a supported image")]
+	ArtworkInvalid,
+	#[error("Artwork override not found")]
+	ArtworkOverrideNotFound,
 	#[error("No embedded artwork was found in `{0}`")]
 	EmbeddedArtworkNotFound(PathBuf),
+	#[error("No lyrics were found for `{0}`")]
+	LyricsNotFound(PathBuf),
+	#[error("No audio fingerprint has been computed for `{0}`")]
+	AudioFingerprintNotFound(PathBuf),
+	#[error("No image was found for this artist")]
+	ArtistImageNotFound,
 
 	#[error("Cannot use empty username")]
 	EmptyUsername,
 	#[error("Cannot use empty password")]
 	EmptyPassword,
+	#[error("Password does not meet the password policy: {0}")]
+	PasswordTooWeak(String),
+	#[error("Password must be changed before signing in")]
+	PasswordChangeRequired,
 	#[error("Username already exists")]
 	DuplicateUsername,
 	#[error("Username does not exist")]
 	IncorrectUsername,
 	#[error("Password does not match username")]
 	IncorrectPassword,
+	#[error("Account is temporarily locked after too many failed logins")]
+	AccountLocked,
+	#[error("Too many failed attempts, retry in {}s", .0.as_secs_f64().ceil())]
+	LoginThrottled(std::time::Duration),
 	#[error("Invalid auth token")]
 	InvalidAuthToken,
+	#[error("Device login code not found or expired")]
+	DeviceLoginNotFound,
+	#[error("Device login expired or was not started by this server")]
+	DeviceLoginExpired,
+	#[error("Device login was polled too often")]
+	DeviceLoginSlowDown,
+	#[error("Too many logins are pending")]
+	TooManyPendingLogins,
+	#[error("OpenID Connect login is not configured")]
+	OidcNotConfigured,
+	#[error("OpenID Connect login expired or was not started by this server")]
+	OidcLoginExpired,
+	#[error("This account of the identity provider is not linked to a Polaris user")]
+	OidcAccountNotLinked,
+	#[error("OpenID Connect return path must be a path on this server")]
+	OidcReturnPathInvalid,
+	#[error("OpenID Connect provider request failed: `{0}`")]
+	OidcProviderFailed(String),
+	#[error("OpenID Connect ID token is invalid: `{0}`")]
+	OidcTokenInvalid(String),
+	#[error("Session not found")]
+	SessionNotFound,
+	#[error("Passkey not found")]
+	PasskeyNotFound,
+	#[error("Passkey is already registered")]
+	PasskeyAlreadyRegistered,
+	#[error("Passkey challenge expired or was not issued by this server")]
+	PasskeyChallengeExpired,
+	#[error("Passkey registration is invalid: `{0}`")]
+	PasskeyRegistrationInvalid(String),
+	#[error("Passkey authentication failed: `{0}`")]
+	PasskeyAuthenticationFailed(String),
+	#[error("Cannot use empty API key name")]
+	EmptyApiKeyName,
+	#[error("API key not found")]
+	ApiKeyNotFound,
+	#[error("Invite not found")]
+	InviteNotFound,
+	#[error("Invalid preference key `{0}`")]
+	PreferenceKeyInvalid(String),
+	#[error("Preferences exceed the maximum size")]
+	PreferencesTooLarge,
+	#[error("Ratings must be between 0 and 5")]
+	RatingInvalid,
+	#[error("ListenBrainz account is not linked")]
+	ListenBrainzNotLinked,
+	#[error("ListenBrainz token is invalid")]
+	ListenBrainzTokenInvalid,
+	#[error("ListenBrainz request failed: `{0}`")]
+	ListenBrainzFailed(String),
+	#[error("Invite is invalid or expired")]
+	InviteInvalid,
+	#[error("Invites must be valid for at least one day")]
+	InviteValidityInvalid,
 	#[error("Incorrect authorization scope")]
 	IncorrectAuthorizationScope,
 	#[error("Failed to hash password")]
@@ -152,15 +335,41 @@ pub enum Error {
 
 #[derive(Clone)]
 pub struct App {
-	pub port: u16,
 	pub web_dir_path: PathBuf,
+	pub acme_manager: acme::Manager,
+	pub acoustid_manager: acoustid::Manager,
+	pub api_key_manager: api_key::Manager,
+	pub artist_image_manager: artist_image::Manager,
+	pub artwork_manager: artwork::Manager,
+	pub audit_manager: audit::Manager,
 	pub ddns_manager: ddns::Manager,
+	pub device_login_manager: device_login::Manager,
+	pub dlna_manager: dlna::Manager,
+	pub duplicates_manager: duplicates::Manager,
+	pub events_manager: events::Manager,
+	pub favorites_manager: favorites::Manager,
+	pub history_manager: history::Manager,
+	pub hooks_manager: hooks::Manager,
 	pub scanner: scanner::Scanner,
 	pub index_manager: index::Manager,
+	pub invite_manager: invite::Manager,
+	pub jukebox_manager: jukebox::Manager,
+	pub listenbrainz_manager: listenbrainz::Manager,
+	pub mpd_manager: mpd::Manager,
 	pub config_manager: config::Manager,
+	pub oidc_manager: oidc::Manager,
 	pub peaks_manager: peaks::Manager,
 	pub playlist_manager: playlist::Manager,
+	pub preferences_manager: preferences::Manager,
+	pub quality_manager: quality::Manager,
+	pub ratings_manager: ratings::Manager,
+	pub queue_manager: queue::Manager,
+	pub radio_manager: radio::Manager,
+	pub session_manager: session::Manager,
+	pub stats_manager: stats::Manager,
 	pub thumbnail_manager: thumbnail::Manager,
+	pub transfers_manager: transfers::Manager,
+	pub webauthn_manager: webauthn::Manager,
 }
 
 impl App {
@@ -174,6 +383,8 @@ impl App {
 		let peaks_dir_path = paths.cache_dir_path.join("peaks");
 		fs::create_dir_all(&peaks_dir_path).map_err(|e| Error::Io(peaks_dir_path.clone(), e))?;
 
+		let artist_images_dir_path = paths.cache_dir_path.join("artist_images");
+		let artwork_dir_path = paths.data_dir_path.join("artwork");
 		let thumbnails_dir_path = paths.cache_dir_path.join("thumbnails");
 		fs::create_dir_all(&thumbnails_dir_path)
 			.map_err(|e| Error::Io(thumbnails_dir_path.clone(), e))?;
@@ -182,25 +393,114 @@ impl App {
 		Self::migrate_legacy_auth_secret(&paths.db_file_path, &auth_secret_file_path).await?;
 		let auth_secret = Self::get_or_create_auth_secret(&auth_secret_file_path).await?;
 
-		let config_manager = config::Manager::new(&paths.config_file_path, auth_secret).await?;
+		let config_manager =
+			config::Manager::new(&paths.config_file_path, auth_secret.clone()).await?;
+		let acme_manager = acme::Manager::new(config_manager.clone());
 		let ddns_manager = ddns::Manager::new(config_manager.clone());
+		let dlna_manager =
+			dlna::Manager::new(&paths.data_dir_path, config_manager.clone(), port).await?;
+		let events_manager = events::Manager::new(config_manager.clone());
+		let hooks_manager = hooks::Manager::new(config_manager.clone());
 		let ndb_manager = ndb::Manager::new(&paths.data_dir_path)?;
 		let index_manager = index::Manager::new(&paths.data_dir_path).await?;
-		let scanner = scanner::Scanner::new(index_manager.clone(), config_manager.clone()).await?;
+		let artwork_manager = artwork::Manager::new(
+			artwork_dir_path,
+			config_manager.clone(),
+			ndb_manager.clone(),
+		);
+		let artist_image_manager = artist_image::Manager::new(
+			artist_images_dir_path,
+			config_manager.clone(),
+			index_manager.clone(),
+			ndb_manager.clone(),
+		);
+		let musicbrainz_manager =
+			musicbrainz::Manager::new(ndb_manager.clone(), index_manager.clone());
+		let scanner = scanner::Scanner::new(
+			index_manager.clone(),
+			config_manager.clone(),
+			hooks_manager.clone(),
+			musicbrainz_manager,
+		)
+		.await?;
+		let acoustid_manager = acoustid::Manager::new(
+			config_manager.clone(),
+			index_manager.clone(),
+			ndb_manager.clone(),
+		);
+		let duplicates_manager =
+			duplicates::Manager::new(acoustid_manager.clone(), index_manager.clone());
+		let jukebox_manager = jukebox::Manager::new(config_manager.clone(), index_manager.clone());
+		let oidc_manager = oidc::Manager::new(config_manager.clone());
+		let device_login_manager = device_login::Manager::new();
 		let peaks_manager = peaks::Manager::new(peaks_dir_path);
-		let playlist_manager = playlist::Manager::new(ndb_manager);
+		let playlist_manager = playlist::Manager::new(ndb_manager.clone());
+		let quality_manager = quality::Manager::new(index_manager.clone());
+		let queue_manager = queue::Manager::new(ndb_manager.clone());
+		let radio_manager = radio::Manager::new(ndb_manager.clone());
+		let api_key_manager = api_key::Manager::new(ndb_manager.clone(), config_manager.clone());
+		let audit_manager = audit::Manager::new(ndb_manager.clone());
+		let session_manager = session::Manager::new(
+			ndb_manager.clone(),
+			audit_manager.clone(),
+			config_manager.clone(),
+			hooks_manager.clone(),
+		);
+		let mpd_manager = mpd::Manager::new(
+			config_manager.clone(),
+			index_manager.clone(),
+			scanner.clone(),
+			session_manager.clone(),
+		);
+		let preferences_manager = preferences::Manager::new(ndb_manager.clone());
+		let favorites_manager = favorites::Manager::new(ndb_manager.clone());
+		let ratings_manager = ratings::Manager::new(ndb_manager.clone());
+		let history_manager = history::Manager::new(ndb_manager.clone());
+		let stats_manager = stats::Manager::new(history_manager.clone(), index_manager.clone());
+		let listenbrainz_manager =
+			listenbrainz::Manager::new(ndb_manager.clone(), index_manager.clone());
+		let invite_manager = invite::Manager::new(ndb_manager.clone(), config_manager.clone());
+		let transfers_manager =
+			transfers::Manager::new(ndb_manager.clone(), config_manager.clone());
 		let thumbnail_manager = thumbnail::Manager::new(thumbnails_dir_path);
+		let webauthn_manager = webauthn::Manager::new(config_manager.clone(), &auth_secret);
 
 		let app = Self {
-			port,
 			web_dir_path: paths.web_dir_path,
+			acme_manager,
+			acoustid_manager,
+			api_key_manager,
+			artist_image_manager,
+			artwork_manager,
+			audit_manager,
 			ddns_manager,
+			device_login_manager,
+			dlna_manager,
+			duplicates_manager,
+			events_manager,
+			favorites_manager,
+			history_manager,
+			hooks_manager,
 			scanner,
 			index_manager,
+			invite_manager,
+			jukebox_manager,
+			listenbrainz_manager,
+			mpd_manager,
 			config_manager,
+			oidc_manager,
 			peaks_manager,
 			playlist_manager,
+			preferences_manager,
+			quality_manager,
+			ratings_manager,
+			queue_manager,
+			radio_manager,
+			session_manager,
+			stats_manager,
 			thumbnail_manager,
+			transfers_manager,
+			webauthn_manager,
 		};
 
 		app.migrate_legacy_db(&paths.db_file_path).await?;
@@ -0,0 +1,76 @@
+use std::io;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use log::info;
+use rustls_acme::caches::DirCache;
+use rustls_acme::futures_rustls::server::TlsStream as RustlsStream;
+use rustls_acme::tokio::{TokioIncoming, TokioIncomingTcpWrapper};
+use rustls_acme::AcmeConfig;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_stream::Stream;
+use tokio_util::compat::Compat;
+
+use crate::app::config;
+
+pub type TlsStream = Compat<RustlsStream<Compat<TcpStream>>>;
+
+pub type Incoming = TokioIncoming<
+	Compat<TcpStream>,
+	io::Error,
+	TokioIncomingTcpWrapper<TcpStream, io::Error, Listeners>,
+	io::Error,
+	io::Error,
+>;
+
+// Accepts connections from several TCP listeners as a single stream
+pub struct Listeners(Vec<TcpListener>);
+
+impl Stream for Listeners {
+	type Item = io::Result<TcpStream>;
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		for listener in &self.0 {
+			if let Poll::Ready(result) = listener.poll_accept(cx) {
+				return Poll::Ready(Some(result.map(|(stream, _)| stream)));
+			}
+		}
+		Poll::Pending
+	}
+}
+
+#[derive(Clone)]
+pub struct Manager {
+	config_manager: config::Manager,
+}
+
+impl Manager {
+	pub fn new(config_manager: config::Manager) -> Self {
+		Self { config_manager }
+	}
+
+	pub async fn get_acme(&self) -> Option<config::Acme> {
+		self.config_manager.get_acme().await
+	}
+
+	// Certificates and account keys are stored next to the config file
+	pub fn get_cache_dir(&self) -> PathBuf {
+		self.config_manager.get_config_dir().join("acme")
+	}
+
+	// Wraps TCP listeners into a stream of TLS connections. Certificates are
+	// provisioned and renewed in the background, using the TLS-ALPN-01 challenge.
+	pub fn incoming(&self, acme: config::Acme, listeners: Vec<TcpListener>) -> Incoming {
+		let cache_dir = self.get_cache_dir();
+		info!(
+			"Serving HTTPS for `{}` with certificates stored in {:#?}",
+			acme.domain, cache_dir
+		);
+		AcmeConfig::new([acme.domain])
+			.contact(acme.email.iter().map(|e| format!("mailto:{e}")))
+			.cache(DirCache::new(cache_dir))
+			.directory_lets_encrypt(!acme.staging)
+			.tokio_incoming(Listeners(listeners), vec![b"http/1.1".to_vec()])
+	}
+}
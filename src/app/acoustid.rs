@@ -0,0 +1,361 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use log::{error, info};
+use native_db::*;
+use native_model::{native_model, Model};
+use rusty_chromaprint::{Configuration, Fingerprinter};
+use serde::{Deserialize, Serialize};
+use symphonia::core::{
+	audio::SampleBuffer,
+	codecs::{DecoderOptions, CODEC_TYPE_NULL},
+	formats::FormatOptions,
+	io::{MediaSourceStream, MediaSourceStreamOptions},
+	meta::MetadataOptions,
+	probe::Hint,
+};
+use tokio::task::spawn_blocking;
+
+use crate::app::{config, index, ndb, Error};
+
+// AcoustID lookups only use the beginning of each recording
+const MAX_FINGERPRINT_DURATION: f64 = 120.0;
+const FINGERPRINTING_INTERVAL: Duration = Duration::from_secs(60 * 15);
+
+// Chromaprint fingerprints of indexed songs, computed in the background when enabled in the
+// configuration. Fingerprints identify recordings by their audio content, regardless of tags.
+#[derive(Clone)]
+pub struct Manager {
+	config_manager: config::Manager,
+	index_manager: index::Manager,
+	db: ndb::Manager,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fingerprint {
+	pub virtual_path: PathBuf,
+	// Duration of the song in seconds, which AcoustID lookups require
+	pub duration: Option<i64>,
+	pub data: Vec<u32>,
+}
+
+pub type AcoustIdFingerprintModel = v1::AcoustIdFingerprintModel;
+
+pub mod v1 {
+
+	use super::*;
+
+	#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+	#[native_model(id = 16, version = 1)]
+	#[native_db]
+	pub struct AcoustIdFingerprintModel {
+		#[primary_key]
+		pub virtual_path: String,
+		// Change detection fingerprint of the file when it was analyzed
+		pub file_fingerprint: Option<u64>,
+		pub duration: Option<i64>,
+		pub data: Vec<u32>,
+	}
+}
+
+impl From<AcoustIdFingerprintModel> for Fingerprint {
+	fn from(m: AcoustIdFingerprintModel) -> Self {
+		Self {
+			virtual_path: PathBuf::from(m.virtual_path),
+			duration: m.duration,
+			data: m.data,
+		}
+	}
+}
+
+impl Manager {
+	pub fn new(
+		config_manager: config::Manager,
+		index_manager: index::Manager,
+		db: ndb::Manager,
+	) -> Self {
+		Self {
+			config_manager,
+			index_manager,
+			db,
+		}
+	}
+
+	pub fn begin_periodic_fingerprinting(&self) {
+		tokio::spawn({
+			let manager = self.clone();
+			async move {
+				loop {
+					if manager.config_manager.get_acoustid_fingerprints().await {
+						if let Err(e) = manager.update_fingerprints().await {
+							error!("Audio fingerprinting error: {e}");
+						}
+					}
+					tokio::time::sleep(FINGERPRINTING_INTERVAL).await;
+				}
+			}
+		});
+	}
+
+	// Fingerprints songs which are new or changed since they were last analyzed, and forgets
+	// songs which are no longer in the collection
+	pub async fn update_fingerprints(&self) -> Result<(), Error> {
+		let songs = self.index_manager.get_all_songs().await;
+
+		let mut known = spawn_blocking({
+			let manager = self.clone();
+			move || -> Result<HashMap<String, Option<u64>>, Error> {
+				let transaction = manager.db.r_transaction()?;
+				let known = transaction
+					.scan()
+					.primary::<AcoustIdFingerprintModel>()?
+					.all()?
+					.filter_map(|m| m.ok())
+					.map(|m| (m.virtual_path, m.file_fingerprint))
+					.collect();
+				Ok(known)
+			}
+		})
+		.await??;
+
+		let mut num_fingerprinted = 0;
+		for song in songs {
+			let virtual_path = song.virtual_path.to_string_lossy().into_owned();
+			let is_up_to_date = known
+				.remove(&virtual_path)
+				.is_some_and(|f| f.is_some() && f == song.fingerprint);
+			// Songs split from CUE sheets share their file with other songs
+			if is_up_to_date || song.segment_start.is_some() {
+				continue;
+			}
+
+			let result = spawn_blocking({
+				let real_path = song.real_path.clone();
+				move || compute_fingerprint(&real_path)
+			})
+			.await?;
+			let data = match result {
+				Ok(data) => data,
+				Err(e) => {
+					error!("Could not fingerprint `{}`: {e}", song.real_path.display());
+					continue;
+				}
+			};
+
+			self.save_fingerprint(AcoustIdFingerprintModel {
+				virtual_path,
+				file_fingerprint: song.fingerprint,
+				duration: song.duration,
+				data,
+			})
+			.await?;
+			num_fingerprinted += 1;
+		}
+
+		let removed = known.into_keys().collect::<Vec<_>>();
+		spawn_blocking({
+			let manager = self.clone();
+			move || -> Result<(), Error> {
+				let transaction = manager.db.rw_transaction()?;
+				for virtual_path in removed {
+					if let Some(model) = transaction
+						.get()
+						.primary::<AcoustIdFingerprintModel>(virtual_path)?
+					{
+						transaction.remove::<AcoustIdFingerprintModel>(model)?;
+					}
+				}
+				transaction.commit()?;
+				Ok(())
+			}
+		})
+		.await??;
+
+		if num_fingerprinted > 0 {
+			info!("Computed audio fingerprints of {num_fingerprinted} songs");
+		}
+
+		Ok(())
+	}
+
+	pub async fn get_fingerprint(&self, virtual_path: &Path) -> Result<Fingerprint, Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			let virtual_path = virtual_path.to_owned();
+			move || {
+				let transaction = manager.db.r_transaction()?;
+				let model = transaction
+					.get()
+					.primary::<AcoustIdFingerprintModel>(virtual_path.to_string_lossy().as_ref())?;
+				model
+					.map(Fingerprint::from)
+					.ok_or(Error::AudioFingerprintNotFound(virtual_path))
+			}
+		})
+		.await?
+	}
+
+	pub async fn get_fingerprints(&self) -> Result<Vec<Fingerprint>, Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			move || {
+				let transaction = manager.db.r_transaction()?;
+				let fingerprints = transaction
+					.scan()
+					.primary::<AcoustIdFingerprintModel>()?
+					.all()?
+					.filter_map(|m| m.ok())
+					.map(Fingerprint::from)
+					.collect();
+				Ok(fingerprints)
+			}
+		})
+		.await?
+	}
+
+	async fn save_fingerprint(&self, model: AcoustIdFingerprintModel) -> Result<(), Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			move || {
+				let transaction = manager.db.rw_transaction()?;
+				transaction.upsert::<AcoustIdFingerprintModel>(model)?;
+				transaction.commit()?;
+				Ok(())
+			}
+		})
+		.await?
+	}
+}
+
+pub fn compute_fingerprint(audio_path: &Path) -> Result<Vec<u32>, Error> {
+	let file = std::fs::File::open(audio_path).map_err(|e| Error::Io(audio_path.to_owned(), e))?;
+	let media_source = MediaSourceStream::new(Box::new(file), MediaSourceStreamOptions::default());
+
+	let mut format = symphonia::default::get_probe()
+		.format(
+			&Hint::new(),
+			media_source,
+			&FormatOptions::default(),
+			&MetadataOptions::default(),
+		)
+		.map_err(Error::MediaProbeError)?
+		.format;
+
+	let track = format
+		.tracks()
+		.iter()
+		.find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+		.ok_or_else(|| Error::MediaEmpty(audio_path.to_owned()))?;
+
+	let track_id = track.id;
+
+	let mut decoder = symphonia::default::get_codecs()
+		.make(&track.codec_params, &DecoderOptions::default())
+		.map_err(Error::MediaDecoderError)?;
+
+	let configuration = Configuration::preset_test2();
+	let mut fingerprinter = Fingerprinter::new(&configuration);
+	let mut started = false;
+	let mut elapsed_seconds = 0.0;
+
+	while elapsed_seconds < MAX_FINGERPRINT_DURATION {
+		let packet = match format.next_packet() {
+			Ok(packet) => packet,
+			Err(symphonia::core::errors::Error::IoError(e))
+				if e.kind() == std::io::ErrorKind::UnexpectedEof =>
+			{
+				break;
+			}
+			Err(e) => return Err(Error::MediaPacketError(e)),
+		};
+
+		if packet.track_id() != track_id {
+			continue;
+		}
+
+		let decoded = match decoder.decode(&packet) {
+			Ok(d) => d,
+			Err(_) => continue,
+		};
+
+		let num_channels = decoded.spec().channels.count();
+		let sample_rate = decoded.spec().rate;
+		if !started {
+			fingerprinter
+				.start(sample_rate, num_channels as u32)
+				.map_err(|_| Error::AudioFingerprint(audio_path.to_owned()))?;
+			started = true;
+		}
+
+		let mut buffer = SampleBuffer::<i16>::new(decoded.capacity() as u64, *decoded.spec());
+		buffer.copy_interleaved_ref(decoded);
+		fingerprinter.consume(buffer.samples());
+		elapsed_seconds += (buffer.samples().len() / num_channels) as f64 / sample_rate as f64;
+	}
+
+	if !started {
+		return Err(Error::MediaEmpty(audio_path.to_owned()));
+	}
+
+	fingerprinter.finish();
+	Ok(fingerprinter.fingerprint().to_vec())
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::app::test;
+	use crate::test_name;
+
+	#[test]
+	fn can_fingerprint_songs() {
+		for extension in ["flac", "mp3", "ogg"] {
+			let file_name = format!("sample.{extension}");
+			let path = PathBuf::from_iter(["test-data", "formats", file_name.as_str()]);
+			assert!(compute_fingerprint(&path).is_ok());
+		}
+	}
+
+	#[tokio::test]
+	async fn fingerprints_indexed_songs() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.mount("root", "test-data/small-collection")
+			.build()
+			.await;
+		ctx.scanner.run_scan().await.unwrap();
+
+		ctx.acoustid_manager.update_fingerprints().await.unwrap();
+
+		let songs = ctx.index_manager.get_all_songs().await;
+		assert!(!songs.is_empty());
+		for song in songs {
+			let fingerprint = ctx
+				.acoustid_manager
+				.get_fingerprint(&song.virtual_path)
+				.await
+				.unwrap();
+			assert_eq!(fingerprint.duration, song.duration);
+		}
+	}
+
+	#[tokio::test]
+	async fn forgets_removed_songs() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.mount("root", "test-data/small-collection")
+			.build()
+			.await;
+		ctx.scanner.run_scan().await.unwrap();
+		ctx.acoustid_manager.update_fingerprints().await.unwrap();
+
+		ctx.config_manager.set_mounts(vec![]).await.unwrap();
+		ctx.scanner.run_scan().await.unwrap();
+		ctx.acoustid_manager.update_fingerprints().await.unwrap();
+
+		let path = PathBuf::from_iter(["root", "Khemmis", "Hunted", "02 - Candlelight.mp3"]);
+		assert!(matches!(
+			ctx.acoustid_manager.get_fingerprint(&path).await,
+			Err(Error::AudioFingerprintNotFound(_))
+		));
+	}
+}
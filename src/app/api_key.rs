@@ -0,0 +1,329 @@
+use native_db::*;
+use native_model::{native_model, Model};
+use rand::distributions::{Alphanumeric, DistString};
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use tokio::task::spawn_blocking;
+
+use crate::app::{auth, config, ndb, Error};
+
+// Distinguishes API keys from login tokens
+pub const KEY_PREFIX: &str = "polaris_";
+
+// Avoids writing to the database on every request
+const LAST_USED_RESOLUTION_SECONDS: i64 = 60;
+
+// Long-lived credentials for scripts and headless clients, which can be revoked individually
+#[derive(Clone)]
+pub struct Manager {
+	db: ndb::Manager,
+	config_manager: config::Manager,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Scope {
+	// Requests which do not modify anything (GET and HEAD)
+	Read,
+	// All other requests, eg. saving playlists or submitting playback
+	Write,
+	// Requests requiring administrator permission, for users who are admins
+	Admin,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ApiKey {
+	pub id: String,
+	pub username: String,
+	pub name: String,
+	pub scopes: Vec<Scope>,
+	pub created_at: i64,
+	pub last_used_at: Option<i64>,
+}
+
+pub type ApiKeyModel = v1::ApiKeyModel;
+type ApiKeyModelKey = v1::ApiKeyModelKey;
+
+pub mod v1 {
+
+	use super::*;
+
+	#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+	#[native_model(id = 6, version = 1)]
+	#[native_db]
+	pub struct ApiKeyModel {
+		#[primary_key]
+		pub id: String,
+		#[secondary_key]
+		pub username: String,
+		pub name: String,
+		pub scopes: Vec<Scope>,
+		// Keys are only shown when created, the database keeps a hash of their secret
+		pub secret_hash: Vec<u8>,
+		pub created_at: i64,
+		pub last_used_at: Option<i64>,
+	}
+}
+
+impl From<ApiKeyModel> for ApiKey {
+	fn from(k: ApiKeyModel) -> Self {
+		Self {
+			id: k.id,
+			username: k.username,
+			name: k.name,
+			scopes: k.scopes,
+			created_at: k.created_at,
+			last_used_at: k.last_used_at,
+		}
+	}
+}
+
+impl Manager {
+	pub fn new(db: ndb::Manager, config_manager: config::Manager) -> Self {
+		Self { db, config_manager }
+	}
+
+	// Returns the new key, along with the secret value clients should authenticate with
+	pub async fn create_api_key(
+		&self,
+		username: &str,
+		name: &str,
+		scopes: Vec<Scope>,
+	) -> Result<(ApiKey, String), Error> {
+		let name = name.trim();
+		if name.is_empty() {
+			return Err(Error::EmptyApiKeyName);
+		}
+		self.config_manager.get_user(username).await?;
+
+		let mut scopes = scopes;
+		scopes.sort();
+		scopes.dedup();
+
+		let id = Alphanumeric.sample_string(&mut OsRng, 16);
+		let secret = Alphanumeric.sample_string(&mut OsRng, 32);
+		let model = ApiKeyModel {
+			id: id.clone(),
+			username: username.to_owned(),
+			name: name.to_owned(),
+			scopes,
+			secret_hash: auth::hash_secret(&secret),
+			created_at: auth::now() as i64,
+			last_used_at: None,
+		};
+
+		spawn_blocking({
+			let manager = self.clone();
+			let model = model.clone();
+			move || -> Result<(), Error> {
+				let transaction = manager.db.rw_transaction()?;
+				transaction.insert::<ApiKeyModel>(model)?;
+				transaction.commit()?;
+				Ok(())
+			}
+		})
+		.await??;
+
+		Ok((model.into(), format!("{KEY_PREFIX}{id}_{secret}")))
+	}
+
+	pub async fn list_api_keys(&self, username: &str) -> Result<Vec<ApiKey>, Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			let username = username.to_owned();
+			move || {
+				let transaction = manager.db.r_transaction()?;
+				let mut keys = transaction
+					.scan()
+					.secondary::<ApiKeyModel>(ApiKeyModelKey::username)?
+					.range(username.as_str()..=username.as_str())?
+					.filter_map(|k| k.ok())
+					.map(ApiKey::from)
+					.collect::<Vec<_>>();
+				keys.sort_by(|a, b| a.name.cmp(&b.name).then(a.created_at.cmp(&b.created_at)));
+				Ok(keys)
+			}
+		})
+		.await?
+	}
+
+	pub async fn revoke_api_key(&self, username: &str, id: &str) -> Result<(), Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			let username = username.to_owned();
+			let id = id.to_owned();
+			move || {
+				let transaction = manager.db.rw_transaction()?;
+				let key = transaction
+					.get()
+					.primary::<ApiKeyModel>(id)?
+					.filter(|k| k.username == username)
+					.ok_or(Error::ApiKeyNotFound)?;
+				transaction.remove::<ApiKeyModel>(key)?;
+				transaction.commit()?;
+				Ok(())
+			}
+		})
+		.await?
+	}
+
+	pub async fn revoke_all_api_keys(&self, username: &str) -> Result<(), Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			let username = username.to_owned();
+			move || {
+				let transaction = manager.db.rw_transaction()?;
+				let keys = transaction
+					.scan()
+					.secondary::<ApiKeyModel>(ApiKeyModelKey::username)?
+					.range(username.as_str()..=username.as_str())?
+					.collect::<Result<Vec<_>, _>>()?;
+				for key in keys {
+					transaction.remove::<ApiKeyModel>(key)?;
+				}
+				transaction.commit()?;
+				Ok(())
+			}
+		})
+		.await?
+	}
+
+	// Looks up the key matching a secret value, and records when it was used
+	pub async fn authenticate(&self, key: &str) -> Result<ApiKey, Error> {
+		let (id, secret) = key
+			.strip_prefix(KEY_PREFIX)
+			.and_then(|k| k.split_once('_'))
+			.ok_or(Error::InvalidAuthToken)?;
+
+		let api_key: ApiKey = spawn_blocking({
+			let manager = self.clone();
+			let id = id.to_owned();
+			let secret_hash = auth::hash_secret(secret);
+			move || {
+				let key = {
+					let transaction = manager.db.r_transaction()?;
+					transaction.get().primary::<ApiKeyModel>(id)?
+				};
+				let Some(key) = key.filter(|k| k.secret_hash == secret_hash) else {
+					return Err(Error::InvalidAuthToken);
+				};
+
+				let now = auth::now() as i64;
+				if key
+					.last_used_at
+					.is_none_or(|t| now - t >= LAST_USED_RESOLUTION_SECONDS)
+				{
+					let transaction = manager.db.rw_transaction()?;
+					transaction.update::<ApiKeyModel>(
+						key.clone(),
+						ApiKeyModel {
+							last_used_at: Some(now),
+							..key.clone()
+						},
+					)?;
+					transaction.commit()?;
+				}
+
+				Ok(key.into())
+			}
+		})
+		.await??;
+
+		// Keys of deleted users stop working
+		self.config_manager
+			.get_user(&api_key.username)
+			.await
+			.map_err(|_| Error::InvalidAuthToken)?;
+
+		Ok(api_key)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::app::test;
+	use crate::test_name;
+
+	const TEST_USER: &str = "test_user";
+	const TEST_PASSWORD: &str = "password";
+
+	#[tokio::test]
+	async fn can_create_and_use_api_key() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.build()
+			.await;
+
+		let (api_key, secret) = ctx
+			.api_key_manager
+			.create_api_key(TEST_USER, " Scrobbler ", vec![Scope::Write, Scope::Read])
+			.await
+			.unwrap();
+		assert_eq!(api_key.name, "Scrobbler");
+		assert_eq!(api_key.scopes, vec![Scope::Read, Scope::Write]);
+		assert!(api_key.last_used_at.is_none());
+		assert!(secret.starts_with(KEY_PREFIX));
+
+		let authenticated = ctx.api_key_manager.authenticate(&secret).await.unwrap();
+		assert_eq!(authenticated.id, api_key.id);
+		assert_eq!(authenticated.username, TEST_USER);
+
+		let keys = ctx.api_key_manager.list_api_keys(TEST_USER).await.unwrap();
+		assert_eq!(keys.len(), 1);
+		assert!(keys[0].last_used_at.is_some());
+
+		let forged = format!("{KEY_PREFIX}{}_garbage", api_key.id);
+		assert!(matches!(
+			ctx.api_key_manager.authenticate(&forged).await,
+			Err(Error::InvalidAuthToken)
+		));
+	}
+
+	#[tokio::test]
+	async fn revoked_api_keys_cannot_authenticate() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.user("other_user", TEST_PASSWORD, false)
+			.build()
+			.await;
+
+		let (api_key, secret) = ctx
+			.api_key_manager
+			.create_api_key(TEST_USER, "Script", vec![Scope::Read])
+			.await
+			.unwrap();
+
+		assert!(matches!(
+			ctx.api_key_manager
+				.revoke_api_key("other_user", &api_key.id)
+				.await,
+			Err(Error::ApiKeyNotFound)
+		));
+
+		ctx.api_key_manager
+			.revoke_api_key(TEST_USER, &api_key.id)
+			.await
+			.unwrap();
+		assert!(ctx.api_key_manager.authenticate(&secret).await.is_err());
+		assert!(ctx
+			.api_key_manager
+			.list_api_keys(TEST_USER)
+			.await
+			.unwrap()
+			.is_empty());
+	}
+
+	#[tokio::test]
+	async fn rejects_empty_name() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.build()
+			.await;
+		assert!(matches!(
+			ctx.api_key_manager
+				.create_api_key(TEST_USER, "  ", vec![Scope::Read])
+				.await,
+			Err(Error::EmptyApiKeyName)
+		));
+	}
+}
@@ -0,0 +1,447 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::error;
+use native_db::*;
+use native_model::{native_model, Model};
+use rand::distributions::{Alphanumeric, DistString};
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use tokio::task::spawn_blocking;
+use unicase::UniCase;
+
+use crate::app::{auth, config, index, ndb, Error};
+
+const DEEZER_API_URL: &str = "https://api.deezer.com";
+const FANART_TV_API_URL: &str = "https://webservice.fanart.tv/v3";
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_IMAGE_SIZE: u64 = 10 * 1024 * 1024;
+// Artists without a portrait on any online provider are looked up again after this delay
+const MISSING_IMAGE_RETRY_DELAY: i64 = 7 * 24 * 60 * 60;
+const LOCAL_IMAGE_NAMES: [&str; 3] = ["artist.jpg", "artist.jpeg", "artist.png"];
+
+// Artist portraits, read from `artist.jpg` files in the collection or downloaded from online
+// providers. Downloaded portraits are cached on disk.
+#[derive(Clone)]
+pub struct Manager {
+	artist_images_dir_path: PathBuf,
+	config_manager: config::Manager,
+	index_manager: index::Manager,
+	db: ndb::Manager,
+	// Online lookups in progress by artist, so that concurrent requests download a portrait once
+	lookups: Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>>,
+}
+
+pub type ArtistImageModel = v1::ArtistImageModel;
+
+pub mod v1 {
+
+	use super::*;
+
+	#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+	#[native_model(id = 18, version = 1)]
+	#[native_db]
+	pub struct ArtistImageModel {
+		// Lowercase artist name
+		#[primary_key]
+		pub artist: String,
+		// Artists without a portrait are also cached, without a file
+		pub file_name: Option<String>,
+		pub fetched_at: i64,
+	}
+}
+
+#[derive(Deserialize)]
+struct DeezerSearch {
+	#[serde(default)]
+	data: Vec<DeezerArtist>,
+}
+
+#[derive(Deserialize)]
+struct DeezerArtist {
+	name: String,
+	picture_xl: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct FanartTvArtist {
+	#[serde(default)]
+	artistthumb: Vec<FanartTvImage>,
+}
+
+#[derive(Deserialize)]
+struct FanartTvImage {
+	url: String,
+}
+
+// Deezer returns a placeholder picture for artists without a portrait, whose URL has no image hash
+fn pick_deezer_picture(search: DeezerSearch, artist: &str) -> Option<String> {
+	search
+		.data
+		.into_iter()
+		.find(|a| UniCase::new(&a.name) == UniCase::new(artist))
+		.and_then(|a| a.picture_xl)
+		.filter(|url| !url.contains("/artist//"))
+}
+
+fn pick_fanart_tv_picture(artist: FanartTvArtist) -> Option<String> {
+	artist.artistthumb.into_iter().next().map(|i| i.url)
+}
+
+fn find_on_deezer(artist: &str) -> Result<Option<String>, ureq::Error> {
+	let search = ureq::get(&format!("{DEEZER_API_URL}/search/artist"))
+		.timeout(HTTP_TIMEOUT)
+		.query("q", artist)
+		.call()?
+		.into_json::<DeezerSearch>()?;
+	Ok(pick_deezer_picture(search, artist))
+}
+
+fn find_on_fanart_tv(mbid: &str, api_key: &str) -> Result<Option<String>, ureq::Error> {
+	// Sent as a header rather than in the URL, which ends up in logged errors
+	let response = ureq::get(&format!("{FANART_TV_API_URL}/music/{mbid}"))
+		.timeout(HTTP_TIMEOUT)
+		.set("api-key", api_key)
+		.call();
+	match response {
+		Ok(r) => Ok(pick_fanart_tv_picture(r.into_json::<FanartTvArtist>()?)),
+		Err(ureq::Error::Status(404, _)) => Ok(None),
+		Err(e) => Err(e),
+	}
+}
+
+fn download(url: &str) -> Result<Vec<u8>, ureq::Error> {
+	let mut data = vec![];
+	ureq::get(url)
+		.timeout(HTTP_TIMEOUT)
+		.call()?
+		.into_reader()
+		.take(MAX_IMAGE_SIZE)
+		.read_to_end(&mut data)?;
+	Ok(data)
+}
+
+fn find_local_image(directory: &Path) -> Option<PathBuf> {
+	std::fs::read_dir(directory)
+		.ok()?
+		.filter_map(|e| e.ok())
+		.find(|e| {
+			let name = e.file_name().to_string_lossy().to_lowercase();
+			LOCAL_IMAGE_NAMES.contains(&name.as_str())
+		})
+		.map(|e| e.path())
+}
+
+impl Manager {
+	pub fn new(
+		artist_images_dir_path: PathBuf,
+		config_manager: config::Manager,
+		index_manager: index::Manager,
+		db: ndb::Manager,
+	) -> Self {
+		Self {
+			artist_images_dir_path,
+			config_manager,
+			index_manager,
+			db,
+			lookups: Arc::default(),
+		}
+	}
+
+	// Returns the path of an image file, from the first provider which has a portrait of this artist
+	pub async fn get_image(&self, name: &str) -> Result<PathBuf, Error> {
+		let artist = self.index_manager.get_artist(name.to_owned()).await?;
+		let config = self.config_manager.get_artist_images().await;
+
+		let mut searched_online = false;
+		for provider in &config.providers {
+			let image = match provider {
+				config::ArtistImageProvider::Local => self.find_local_image(&artist).await,
+				config::ArtistImageProvider::Deezer | config::ArtistImageProvider::FanartTv => {
+					if searched_online {
+						continue;
+					}
+					searched_online = true;
+					self.find_online_image(&artist, &config).await?
+				}
+			};
+			if let Some(image) = image {
+				return Ok(image);
+			}
+		}
+
+		Err(Error::ArtistImageNotFound)
+	}
+
+	// Looks in the directories of albums credited to this artist, and in their parent directories
+	async fn find_local_image(&self, artist: &index::Artist) -> Option<PathBuf> {
+		let mut directories = HashSet::new();
+		let albums = artist.albums.iter().filter(|a| {
+			a.header
+				.artists
+				.iter()
+				.any(|n| UniCase::new(n) == artist.header.name)
+		});
+		for song in albums.flat_map(|a| &a.songs) {
+			let album_directory = song.virtual_path.parent();
+			directories.extend(album_directory);
+			directories.extend(album_directory.and_then(Path::parent));
+		}
+
+		let mut real_directories = vec![];
+		for directory in directories {
+			if let Ok(real_path) = self.config_manager.resolve_virtual_path(directory).await {
+				real_directories.push(real_path);
+			}
+		}
+		real_directories.sort();
+
+		spawn_blocking(move || real_directories.iter().find_map(|d| find_local_image(d)))
+			.await
+			.ok()
+			.flatten()
+	}
+
+	async fn find_online_image(
+		&self,
+		artist: &index::Artist,
+		config: &config::ArtistImages,
+	) -> Result<Option<PathBuf>, Error> {
+		let key = artist.header.name.to_lowercase();
+		let lookup = self
+			.lookups
+			.lock()
+			.unwrap()
+			.entry(key.clone())
+			.or_default()
+			.clone();
+
+		let result = {
+			let _guard = lookup.lock().await;
+			self.lookup_online_image(artist, config, key.clone()).await
+		};
+
+		let mut lookups = self.lookups.lock().unwrap();
+		if Arc::strong_count(&lookup) == 2 {
+			lookups.remove(&key);
+		}
+
+		result
+	}
+
+	// Callers must hold the lock of this artist in `lookups`
+	async fn lookup_online_image(
+		&self,
+		artist: &index::Artist,
+		config: &config::ArtistImages,
+		key: String,
+	) -> Result<Option<PathBuf>, Error> {
+		if let Some(model) = self.read_model(&key).await? {
+			match model.file_name {
+				Some(file_name) => return Ok(Some(self.artist_images_dir_path.join(file_name))),
+				None if auth::now() as i64 - model.fetched_at < MISSING_IMAGE_RETRY_DELAY => {
+					return Ok(None)
+				}
+				None => (),
+			}
+		}
+
+		let name = artist.header.name.to_string();
+		let mbid = artist.header.musicbrainz_id.clone();
+		let providers = config.providers.clone();
+		let fanart_tv_api_key = config.fanart_tv_api_key.clone();
+		let result = spawn_blocking(move || -> Result<Option<Vec<u8>>, ureq::Error> {
+			for provider in providers {
+				let url = match (provider, &mbid, &fanart_tv_api_key) {
+					(config::ArtistImageProvider::Deezer, _, _) => find_on_deezer(&name)?,
+					(config::ArtistImageProvider::FanartTv, Some(mbid), Some(api_key)) => {
+						find_on_fanart_tv(mbid, api_key)?
+					}
+					_ => None,
+				};
+				if let Some(url) = url {
+					return download(&url).map(Some);
+				}
+			}
+			Ok(None)
+		})
+		.await?;
+
+		let data = match result {
+			Ok(data) => data,
+			Err(e) => {
+				// Failed lookups are not cached, so they are attempted again on the next request
+				error!(
+					"Could not download image of artist `{}`: {e}",
+					artist.header.name
+				);
+				return Ok(None);
+			}
+		};
+
+		let extension = data.as_deref().and_then(|d| {
+			image::guess_format(d)
+				.ok()
+				.and_then(|f| f.extensions_str().first().copied())
+				.filter(|_| image::load_from_memory(d).is_ok())
+		});
+		let file_name = match (data, extension) {
+			(Some(data), Some(extension)) => {
+				let file_name =
+					format!("{}.{extension}", Alphanumeric.sample_string(&mut OsRng, 16));
+				tokio::fs::create_dir_all(&self.artist_images_dir_path)
+					.await
+					.map_err(|e| Error::Io(self.artist_images_dir_path.clone(), e))?;
+				let file_path = self.artist_images_dir_path.join(&file_name);
+				tokio::fs::write(&file_path, data)
+					.await
+					.map_err(|e| Error::Io(file_path.clone(), e))?;
+				Some(file_name)
+			}
+			_ => None,
+		};
+
+		let saved = self
+			.save_model(ArtistImageModel {
+				artist: key,
+				file_name: file_name.clone(),
+				fetched_at: auth::now() as i64,
+			})
+			.await;
+		if let (Err(_), Some(file_name)) = (&saved, &file_name) {
+			tokio::fs::remove_file(self.artist_images_dir_path.join(file_name))
+				.await
+				.ok();
+		}
+		saved?;
+
+		Ok(file_name.map(|f| self.artist_images_dir_path.join(f)))
+	}
+
+	async fn read_model(&self, artist: &str) -> Result<Option<ArtistImageModel>, Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			let artist = artist.to_owned();
+			move || {
+				let transaction = manager.db.r_transaction()?;
+				Ok(transaction.get().primary::<ArtistImageModel>(artist)?)
+			}
+		})
+		.await?
+	}
+
+	async fn save_model(&self, model: ArtistImageModel) -> Result<(), Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			move || {
+				let transaction = manager.db.rw_transaction()?;
+				transaction.upsert::<ArtistImageModel>(model)?;
+				transaction.commit()?;
+				Ok(())
+			}
+		})
+		.await?
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::fs;
+
+	use super::*;
+	use crate::app::test;
+	use crate::test_name;
+
+	#[test]
+	fn picks_deezer_picture_of_matching_artist() {
+		let search = serde_json::from_str::<DeezerSearch>(
+			r#"{ "data": [
+				{ "name": "Khemmis Tribute", "picture_xl": "https://e-cdns-images.dzcdn.net/images/artist/1/1000x1000.jpg" },
+				{ "name": "KHEMMIS", "picture_xl": "https://e-cdns-images.dzcdn.net/images/artist/2/1000x1000.jpg" }
+			] }"#,
+		)
+		.unwrap();
+		assert_eq!(
+			pick_deezer_picture(search, "Khemmis"),
+			Some("https://e-cdns-images.dzcdn.net/images/artist/2/1000x1000.jpg".to_owned())
+		);
+	}
+
+	#[test]
+	fn ignores_deezer_placeholder_picture() {
+		let search = serde_json::from_str::<DeezerSearch>(
+			r#"{ "data": [
+				{ "name": "Tobokegao", "picture_xl": "https://e-cdns-images.dzcdn.net/images/artist//1000x1000-000000-80-0-0.jpg" }
+			] }"#,
+		)
+		.unwrap();
+		assert_eq!(pick_deezer_picture(search, "Tobokegao"), None);
+	}
+
+	#[test]
+	fn picks_fanart_tv_picture() {
+		let artist = serde_json::from_str::<FanartTvArtist>(
+			r#"{ "name": "Khemmis", "artistthumb": [
+				{ "id": "1", "url": "https://assets.fanart.tv/fanart/music/a/artistthumb/khemmis.jpg", "likes": "2" }
+			] }"#,
+		)
+		.unwrap();
+		assert_eq!(
+			pick_fanart_tv_picture(artist),
+			Some("https://assets.fanart.tv/fanart/music/a/artistthumb/khemmis.jpg".to_owned())
+		);
+	}
+
+	#[tokio::test]
+	async fn finds_local_artist_image() {
+		let builder = test::ContextBuilder::new(test_name!());
+		let music_directory = builder.test_directory.join("music");
+		let artist_directory = music_directory.join("TEST ALBUM ARTIST");
+		let album_directory = artist_directory.join("TEST ALBUM");
+		fs::create_dir_all(&album_directory).unwrap();
+		fs::copy(
+			PathBuf::from_iter(["test-data", "formats", "sample.mp3"]),
+			album_directory.join("sample.mp3"),
+		)
+		.unwrap();
+		fs::copy(
+			PathBuf::from_iter(["test-data", "artwork", "Folder.png"]),
+			artist_directory.join("Artist.png"),
+		)
+		.unwrap();
+
+		let ctx = builder
+			.mount("root", music_directory.to_str().unwrap())
+			.build()
+			.await;
+		ctx.scanner.run_scan().await.unwrap();
+
+		let image = ctx
+			.artist_image_manager
+			.get_image("TEST ALBUM ARTIST")
+			.await
+			.unwrap();
+		assert_eq!(image, artist_directory.join("Artist.png"));
+	}
+
+	#[tokio::test]
+	async fn artists_without_image_are_not_found() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.mount("root", "test-data/small-collection")
+			.build()
+			.await;
+		ctx.scanner.run_scan().await.unwrap();
+
+		assert!(matches!(
+			ctx.artist_image_manager.get_image("Khemmis").await,
+			Err(Error::ArtistImageNotFound)
+		));
+		assert!(matches!(
+			ctx.artist_image_manager.get_image("Unknown Artist").await,
+			Err(Error::ArtistNotFound)
+		));
+	}
+}
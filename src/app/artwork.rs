@@ -0,0 +1,218 @@
+use std::path::{Path, PathBuf};
+
+use native_db::*;
+use native_model::{native_model, Model};
+use rand::distributions::{Alphanumeric, DistString};
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use tokio::task::spawn_blocking;
+
+use crate::app::{config, ndb, Error};
+
+// Replacement artwork uploaded for collection directories. Overrides are stored outside of
+// music folders, and take precedence over artwork found in the directory they apply to.
+#[derive(Clone)]
+pub struct Manager {
+	artwork_dir_path: PathBuf,
+	config_manager: config::Manager,
+	db: ndb::Manager,
+}
+
+pub type ArtworkOverrideModel = v1::ArtworkOverrideModel;
+
+pub mod v1 {
+
+	use super::*;
+
+	#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+	#[native_model(id = 4, version = 1)]
+	#[native_db]
+	pub struct ArtworkOverrideModel {
+		#[primary_key]
+		pub virtual_path: String,
+		pub file_name: String,
+	}
+}
+
+impl Manager {
+	pub fn new(
+		artwork_dir_path: PathBuf,
+		config_manager: config::Manager,
+		db: ndb::Manager,
+	) -> Self {
+		Self {
+			artwork_dir_path,
+			config_manager,
+			db,
+		}
+	}
+
+	pub async fn set_override(&self, virtual_path: &Path, data: Vec<u8>) -> Result<(), Error> {
+		let real_path = self
+			.config_manager
+			.resolve_virtual_path(virtual_path)
+			.await?;
+		if !real_path.is_dir() {
+			return Err(Error::DirectoryNotFound(virtual_path.to_owned()));
+		}
+
+		let extension = image::guess_format(&data)
+			.ok()
+			.and_then(|f| f.extensions_str().first().copied())
+			.filter(|_| image::load_from_memory(&data).is_ok())
+			.ok_or(Error::ArtworkInvalid)?;
+
+		// Every upload gets a new file name, so thumbnails of previous uploads are never reused
+		let file_name = format!("{}.{extension}", Alphanumeric.sample_string(&mut OsRng, 16));
+		tokio::fs::create_dir_all(&self.artwork_dir_path)
+			.await
+			.map_err(|e| Error::Io(self.artwork_dir_path.clone(), e))?;
+		let file_path = self.artwork_dir_path.join(&file_name);
+		tokio::fs::write(&file_path, data)
+			.await
+			.map_err(|e| Error::Io(file_path.clone(), e))?;
+
+		let previous = spawn_blocking({
+			let manager = self.clone();
+			let virtual_path = virtual_path.to_string_lossy().into_owned();
+			move || -> Result<_, Error> {
+				let transaction = manager.db.rw_transaction()?;
+				let previous =
+					transaction.upsert::<ArtworkOverrideModel>(ArtworkOverrideModel {
+						virtual_path,
+						file_name,
+					})?;
+				transaction.commit()?;
+				Ok(previous)
+			}
+		})
+		.await??;
+
+		if let Some(previous) = previous {
+			self.remove_file(&previous.file_name).await;
+		}
+
+		Ok(())
+	}
+
+	pub async fn delete_override(&self, virtual_path: &Path) -> Result<(), Error> {
+		let removed = spawn_blocking({
+			let manager = self.clone();
+			let virtual_path = virtual_path.to_string_lossy().into_owned();
+			move || -> Result<_, Error> {
+				let transaction = manager.db.rw_transaction()?;
+				let model = transaction
+					.get()
+					.primary::<ArtworkOverrideModel>(virtual_path)?
+					.ok_or(Error::ArtworkOverrideNotFound)?;
+				let removed = transaction.remove::<ArtworkOverrideModel>(model)?;
+				transaction.commit()?;
+				Ok(removed)
+			}
+		})
+		.await??;
+
+		self.remove_file(&removed.file_name).await;
+		Ok(())
+	}
+
+	// Finds the override applying to an artwork path (an image file, or a song with embedded
+	// artwork), based on the directory containing it.
+	pub async fn get_override(&self, artwork_path: &Path) -> Result<Option<PathBuf>, Error> {
+		let Some(directory) = artwork_path.parent() else {
+			return Ok(None);
+		};
+
+		let model = spawn_blocking({
+			let manager = self.clone();
+			let directory = directory.to_string_lossy().into_owned();
+			move || -> Result<_, Error> {
+				let transaction = manager.db.r_transaction()?;
+				Ok(transaction
+					.get()
+					.primary::<ArtworkOverrideModel>(directory)?)
+			}
+		})
+		.await??;
+
+		Ok(model.map(|m| self.artwork_dir_path.join(m.file_name)))
+	}
+
+	async fn remove_file(&self, file_name: &str) {
+		let path = self.artwork_dir_path.join(file_name);
+		tokio::fs::remove_file(path).await.ok();
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::app::test;
+	use crate::test_name;
+
+	const TEST_MOUNT_NAME: &str = "root";
+
+	fn read_image(name: &str) -> Vec<u8> {
+		std::fs::read(PathBuf::from_iter(["test-data", "artwork", name])).unwrap()
+	}
+
+	#[tokio::test]
+	async fn can_override_directory_artwork() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+			.build()
+			.await;
+		let artwork = ctx.artwork_manager;
+
+		let directory = PathBuf::from_iter([TEST_MOUNT_NAME, "Khemmis", "Hunted"]);
+		let artwork_path = directory.join("Folder.jpg");
+		assert_eq!(artwork.get_override(&artwork_path).await.unwrap(), None);
+
+		artwork
+			.set_override(&directory, read_image("Folder.png"))
+			.await
+			.unwrap();
+		let first = artwork.get_override(&artwork_path).await.unwrap().unwrap();
+		assert_eq!(first.extension().unwrap(), "png");
+		assert!(first.is_file());
+
+		artwork
+			.set_override(&directory, read_image("Embedded.png"))
+			.await
+			.unwrap();
+		let second = artwork.get_override(&artwork_path).await.unwrap().unwrap();
+		assert_ne!(first, second);
+		assert!(!first.exists());
+
+		artwork.delete_override(&directory).await.unwrap();
+		assert_eq!(artwork.get_override(&artwork_path).await.unwrap(), None);
+		assert!(!second.exists());
+		assert!(matches!(
+			artwork.delete_override(&directory).await,
+			Err(Error::ArtworkOverrideNotFound)
+		));
+	}
+
+	#[tokio::test]
+	async fn rejects_invalid_overrides() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+			.build()
+			.await;
+		let artwork = ctx.artwork_manager;
+
+		let directory = PathBuf::from_iter([TEST_MOUNT_NAME, "Khemmis", "Hunted"]);
+		assert!(matches!(
+			artwork
+				.set_override(&directory, b"not an image".to_vec())
+				.await,
+			Err(Error::ArtworkInvalid)
+		));
+
+		let song = directory.join("02 - Candlelight.mp3");
+		assert!(matches!(
+			artwork.set_override(&song, read_image("Folder.png")).await,
+			Err(Error::DirectoryNotFound(_))
+		));
+	}
+}
@@ -0,0 +1,224 @@
+use std::net::IpAddr;
+
+use log::error;
+use native_db::*;
+use native_model::{native_model, Model};
+use serde::{Deserialize, Serialize};
+use tokio::task::spawn_blocking;
+
+use crate::app::{auth, ndb, Error};
+
+pub const DEFAULT_QUERY_LIMIT: usize = 100;
+
+// Append-only record of security-relevant events, for accountability on multi-user servers
+#[derive(Clone)]
+pub struct Manager {
+	db: ndb::Manager,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Event {
+	// The actor of login events is the username which was submitted
+	LoginSucceeded,
+	LoginFailed,
+	UserCreated { name: String, admin: bool },
+	UserUpdated { name: String },
+	UserDeleted { name: String },
+	MountDirsChanged,
+	SettingsChanged,
+	PlaylistDeleted { name: String },
+	AliasesChanged,
+	InviteCreated { id: String },
+	InviteRevoked { id: String },
+	UserLoggedOut { name: String },
+	UserUnlocked { name: String },
+	ApiKeyCreated { id: String, name: String },
+	ApiKeyRevoked { id: String },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+	pub id: u64,
+	pub timestamp: i64,
+	pub actor: Option<String>,
+	pub address: Option<IpAddr>,
+	pub event: Event,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Filter {
+	pub actor: Option<String>,
+	pub since: Option<i64>,
+	pub until: Option<i64>,
+	pub limit: usize,
+}
+
+impl Default for Filter {
+	fn default() -> Self {
+		Self {
+			actor: None,
+			since: None,
+			until: None,
+			limit: DEFAULT_QUERY_LIMIT,
+		}
+	}
+}
+
+pub type AuditEntryModel = v1::AuditEntryModel;
+
+pub mod v1 {
+
+	use super::*;
+
+	#[derive(Clone, Debug, Serialize, Deserialize)]
+	#[native_model(id = 8, version = 1)]
+	#[native_db]
+	pub struct AuditEntryModel {
+		// Increases with each entry, so that entries are stored in chronological order
+		#[primary_key]
+		pub id: u64,
+		pub timestamp: i64,
+		pub actor: Option<String>,
+		pub address: Option<IpAddr>,
+		pub event: Event,
+	}
+}
+
+impl From<AuditEntryModel> for Entry {
+	fn from(e: AuditEntryModel) -> Self {
+		Self {
+			id: e.id,
+			timestamp: e.timestamp,
+			actor: e.actor,
+			address: e.address,
+			event: e.event,
+		}
+	}
+}
+
+impl Manager {
+	pub fn new(db: ndb::Manager) -> Self {
+		Self { db }
+	}
+
+	// Failures are logged rather than returned, so they do not fail the action being audited
+	pub async fn record(&self, actor: Option<&str>, address: Option<IpAddr>, event: Event) {
+		let result = spawn_blocking({
+			let manager = self.clone();
+			let actor = actor.map(str::to_owned);
+			move || -> Result<(), Error> {
+				let transaction = manager.db.rw_transaction()?;
+				let last_id = transaction
+					.scan()
+					.primary::<AuditEntryModel>()?
+					.all()?
+					.next_back()
+					.transpose()?
+					.map(|e| e.id);
+				transaction.insert::<AuditEntryModel>(AuditEntryModel {
+					id: last_id.map_or(0, |id| id + 1),
+					timestamp: auth::now() as i64,
+					actor,
+					address,
+					event,
+				})?;
+				transaction.commit()?;
+				Ok(())
+			}
+		})
+		.await;
+
+		match result {
+			Ok(Ok(())) => (),
+			Ok(Err(e)) => error!("Could not record audit log entry: {e}"),
+			Err(e) => error!("Could not record audit log entry: {e}"),
+		}
+	}
+
+	// Returns matching entries, most recent first
+	pub async fn query(&self, filter: Filter) -> Result<Vec<Entry>, Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			move || {
+				let transaction = manager.db.r_transaction()?;
+				let entries = transaction
+					.scan()
+					.primary::<AuditEntryModel>()?
+					.all()?
+					.rev()
+					.filter_map(|e| e.ok())
+					.skip_while(|e| filter.until.is_some_and(|t| e.timestamp > t))
+					.take_while(|e| filter.since.is_none_or(|t| e.timestamp >= t))
+					.filter(|e| filter.actor.is_none() || e.actor == filter.actor)
+					.take(filter.limit)
+					.map(Entry::from)
+					.collect();
+				Ok(entries)
+			}
+		})
+		.await?
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::app::test;
+	use crate::test_name;
+
+	#[tokio::test]
+	async fn can_record_and_query_events() {
+		let ctx = test::ContextBuilder::new(test_name!()).build().await;
+		let audit = ctx.audit_manager;
+		let address = "192.168.1.20".parse().ok();
+
+		audit
+			.record(Some("alice"), address, Event::LoginSucceeded)
+			.await;
+		audit
+			.record(Some("mallory"), None, Event::LoginFailed)
+			.await;
+		audit
+			.record(
+				Some("alice"),
+				address,
+				Event::UserDeleted {
+					name: "bob".to_owned(),
+				},
+			)
+			.await;
+
+		let entries = audit.query(Filter::default()).await.unwrap();
+		assert_eq!(entries.len(), 3);
+		assert_eq!(
+			entries[0].event,
+			Event::UserDeleted {
+				name: "bob".to_owned()
+			}
+		);
+		assert_eq!(entries[2].event, Event::LoginSucceeded);
+		assert_eq!(entries[2].address, address);
+		assert!(entries[0].id > entries[1].id);
+
+		let filter = Filter {
+			actor: Some("alice".to_owned()),
+			limit: 1,
+			..Default::default()
+		};
+		let entries = audit.query(filter).await.unwrap();
+		assert_eq!(entries.len(), 1);
+		assert_eq!(entries[0].actor.as_deref(), Some("alice"));
+
+		let filter = Filter {
+			since: Some(auth::now() as i64 + 60),
+			..Default::default()
+		};
+		assert!(audit.query(filter).await.unwrap().is_empty());
+
+		let filter = Filter {
+			until: Some(auth::now() as i64 - 60),
+			..Default::default()
+		};
+		assert!(audit.query(filter).await.unwrap().is_empty());
+	}
+}
@@ -1,4 +1,4 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use pbkdf2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
 use pbkdf2::Pbkdf2;
@@ -29,12 +29,76 @@ pub struct Token(pub String);
 #[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
 pub enum Scope {
 	PolarisAuth,
+	PlaylistFeed,
+}
+
+// Responsibilities which administrators can delegate to other users. Administrators hold every
+// permission.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+	ManageUsers,
+	ManageMounts,
+	TriggerScan,
+	Download,
+	Share,
+}
+
+impl Permission {
+	pub const ALL: [Permission; 5] = [
+		Permission::ManageUsers,
+		Permission::ManageMounts,
+		Permission::TriggerScan,
+		Permission::Download,
+		Permission::Share,
+	];
+
+	// Held by users whose permissions are not configured
+	pub const DEFAULT: [Permission; 2] = [Permission::Download, Permission::Share];
+
+	// Permissions over the whole server, rather than over the activity of the user
+	pub fn is_administrative(&self) -> bool {
+		matches!(
+			self,
+			Permission::ManageUsers | Permission::ManageMounts | Permission::TriggerScan
+		)
+	}
 }
 
 #[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
 pub struct Authorization {
 	pub username: String,
 	pub scope: Scope,
+	/// Tokens issued before sessions were introduced do not have a session
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub session_id: Option<String>,
+	/// Playlist which can be read with a `PlaylistFeed` token
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub playlist: Option<String>,
+	/// Feed of the playlist which a `PlaylistFeed` token was issued for
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub feed_id: Option<String>,
+	/// Unix timestamp at which the token was issued, missing from tokens issued by older versions
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub issued_at: Option<u64>,
+	/// Token generation of the user when the token was issued, see `config::User`
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub generation: Option<u64>,
+}
+
+pub fn now() -> u64 {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.unwrap_or_default()
+		.as_secs()
+}
+
+// Fast digest for long random secrets, like API keys or invite tokens. Passwords must go through
+// `hash_password` instead.
+pub fn hash_secret(secret: &str) -> Vec<u8> {
+	ring::digest::digest(&ring::digest::SHA256, secret.as_bytes())
+		.as_ref()
+		.to_vec()
 }
 
 pub fn hash_password(password: &str) -> Result<String, Error> {
@@ -66,24 +130,25 @@ pub fn generate_auth_token(
 	branca::encode(
 		serialized_authorization.as_bytes(),
 		auth_secret.as_ref(),
-		SystemTime::now()
-			.duration_since(UNIX_EPOCH)
-			.unwrap_or_default()
-			.as_secs() as u32,
+		now() as u32,
 	)
 	.or(Err(Error::BrancaTokenEncoding))
 	.map(Token)
 }
 
+// Login tokens older than `lifetime` are rejected, other tokens never expire
 pub fn decode_auth_token(
 	auth_token: &Token,
 	scope: Scope,
+	lifetime: Option<Duration>,
 	auth_secret: &Secret,
 ) -> Result<Authorization, Error> {
 	let Token(data) = auth_token;
 	let ttl = match scope {
-		Scope::PolarisAuth => 0, // permanent
-	};
+		Scope::PolarisAuth => lifetime.map(|l| l.as_secs().clamp(1, u32::MAX as u64) as u32),
+		Scope::PlaylistFeed => None,
+	}
+	.unwrap_or(0); // permanent
 	let authorization =
 		branca::decode(data, auth_secret.as_ref(), ttl).map_err(|_| Error::InvalidAuthToken)?;
 	let authorization: Authorization =
@@ -1,4 +1,5 @@
 use std::{
+	num::NonZeroUsize,
 	path::{Path, PathBuf},
 	sync::Arc,
 	time::Duration,
@@ -8,23 +9,538 @@ use log::{error, info};
 use notify::{RecommendedWatcher, RecursiveMode, Watcher};
 use notify_debouncer_full::{Debouncer, FileIdMap};
 use regex::Regex;
-use tokio::sync::{futures::Notified, Notify, RwLock};
+use tokio::sync::{futures::Notified, Mutex, Notify};
 
-use crate::app::Error;
+use crate::app::{formats, scanner, Error};
 
+mod aliases;
 mod mounts;
+mod proxies;
 pub mod storage;
 mod user;
 
+pub use aliases::*;
 pub use mounts::*;
+pub use proxies::*;
+pub use storage::{ArtistImageProvider, ChangeDetection, SymlinkPolicy, WebhookEvent};
 pub use user::*;
 
 use super::auth;
 
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Acme {
+	pub domain: String,
+	pub email: Option<String>,
+	pub staging: bool,
+}
+
+impl TryFrom<storage::Acme> for Acme {
+	type Error = Error;
+
+	fn try_from(a: storage::Acme) -> Result<Self, Self::Error> {
+		let domain = a.domain.trim().to_owned();
+		if domain.is_empty() || domain.contains(['/', ':', ' ']) {
+			return Err(Error::AcmeDomainInvalid);
+		}
+		Ok(Self {
+			domain,
+			email: a.email.filter(|e| !e.trim().is_empty()),
+			staging: a.staging.unwrap_or_default(),
+		})
+	}
+}
+
+impl From<Acme> for storage::Acme {
+	fn from(a: Acme) -> Self {
+		Self {
+			domain: a.domain,
+			email: a.email,
+			staging: a.staging.then_some(true),
+		}
+	}
+}
+
+// External processing to run after every completed collection scan
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PostScanHook {
+	pub command: Option<String>,
+	pub webhook_url: Option<http::Uri>,
+}
+
+impl TryFrom<storage::PostScanHook> for PostScanHook {
+	type Error = Error;
+
+	fn try_from(h: storage::PostScanHook) -> Result<Self, Self::Error> {
+		let webhook_url = match h.webhook_url.map(http::Uri::try_from) {
+			Some(Ok(u)) if u.scheme().is_some() => Some(u),
+			Some(_) => return Err(Error::PostScanWebhookURLInvalid),
+			None => None,
+		};
+		Ok(Self {
+			command: h.command.filter(|c| !c.trim().is_empty()),
+			webhook_url,
+		})
+	}
+}
+
+impl From<PostScanHook> for storage::PostScanHook {
+	fn from(h: PostScanHook) -> Self {
+		Self {
+			command: h.command,
+			webhook_url: h.webhook_url.map(|u| u.to_string()),
+		}
+	}
+}
+
+// Sources of artist portraits, tried in order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArtistImages {
+	pub providers: Vec<ArtistImageProvider>,
+	pub fanart_tv_api_key: Option<String>,
+}
+
+impl Default for ArtistImages {
+	fn default() -> Self {
+		Self {
+			providers: vec![ArtistImageProvider::Local],
+			fanart_tv_api_key: None,
+		}
+	}
+}
+
+impl TryFrom<storage::ArtistImages> for ArtistImages {
+	type Error = Error;
+
+	fn try_from(a: storage::ArtistImages) -> Result<Self, Self::Error> {
+		let providers = a
+			.providers
+			.unwrap_or_else(|| ArtistImages::default().providers);
+		let fanart_tv_api_key = a
+			.fanart_tv_api_key
+			.map(|k| k.trim().to_owned())
+			.filter(|k| !k.is_empty());
+		if providers.contains(&ArtistImageProvider::FanartTv) && fanart_tv_api_key.is_none() {
+			return Err(Error::FanartTvApiKeyMissing);
+		}
+		Ok(Self {
+			providers,
+			fanart_tv_api_key,
+		})
+	}
+}
+
+impl From<ArtistImages> for storage::ArtistImages {
+	fn from(a: ArtistImages) -> Self {
+		Self {
+			providers: (a.providers != ArtistImages::default().providers).then_some(a.providers),
+			fanart_tv_api_key: a.fanart_tv_api_key,
+		}
+	}
+}
+
+// Resource usage limits, protecting small servers from misbehaving clients
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Limits {
+	pub max_connections: Option<usize>,
+	pub max_connections_per_ip: Option<usize>,
+	pub max_streams_per_user: Option<usize>,
+	pub max_request_body_mb: usize,
+}
+
+impl Default for Limits {
+	fn default() -> Self {
+		Self {
+			max_connections: None,
+			max_connections_per_ip: None,
+			max_streams_per_user: None,
+			max_request_body_mb: 10,
+		}
+	}
+}
+
+// Lifetime of login tokens. Tokens never expire when unset.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Auth {
+	pub token_lifetime: Option<Duration>,
+	pub lockout: Option<Lockout>,
+}
+
+// Accounts are locked for `duration` after `threshold` consecutive failed logins
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lockout {
+	pub threshold: u32,
+	pub duration: Duration,
+}
+
+const DEFAULT_LOCKOUT_MINUTES: u64 = 15;
+
+impl TryFrom<storage::Auth> for Auth {
+	type Error = Error;
+
+	fn try_from(a: storage::Auth) -> Result<Self, Self::Error> {
+		if a.token_lifetime_hours == Some(0) {
+			return Err(Error::TokenLifetimeInvalid);
+		}
+		if a.lockout_threshold == Some(0) || a.lockout_minutes == Some(0) {
+			return Err(Error::LockoutInvalid);
+		}
+		Ok(Self {
+			token_lifetime: a
+				.token_lifetime_hours
+				.map(|h| Duration::from_secs(h * 3600)),
+			lockout: a.lockout_threshold.map(|threshold| Lockout {
+				threshold,
+				duration: Duration::from_secs(
+					a.lockout_minutes.unwrap_or(DEFAULT_LOCKOUT_MINUTES) * 60,
+				),
+			}),
+		})
+	}
+}
+
+impl From<Auth> for storage::Auth {
+	fn from(a: Auth) -> Self {
+		Self {
+			token_lifetime_hours: a.token_lifetime.map(|d| d.as_secs() / 3600),
+			lockout_threshold: a.lockout.as_ref().map(|l| l.threshold),
+			lockout_minutes: a
+				.lockout
+				.map(|l| l.duration.as_secs() / 60)
+				.filter(|m| *m != DEFAULT_LOCKOUT_MINUTES),
+		}
+	}
+}
+
+// Requirements for passwords chosen by users or administrators. Passwords set through
+// `initial_password` in the config file are not checked.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PasswordPolicy {
+	pub min_length: usize,
+	pub require_mixed_case: bool,
+	pub require_digit: bool,
+	pub require_symbol: bool,
+	// Users who never changed their password must do so before signing in
+	pub require_initial_password_change: bool,
+}
+
+impl PasswordPolicy {
+	pub fn check(&self, password: &str) -> Result<(), Error> {
+		if password.is_empty() {
+			return Err(Error::EmptyPassword);
+		}
+		let weak = |reason: &str| Err(Error::PasswordTooWeak(reason.to_owned()));
+		if password.chars().count() < self.min_length {
+			return weak(&format!(
+				"must be at least {} characters long",
+				self.min_length
+			));
+		}
+		if self.require_mixed_case
+			&& !(password.chars().any(char::is_lowercase)
+				&& password.chars().any(char::is_uppercase))
+		{
+			return weak("must contain lowercase and uppercase letters");
+		}
+		if self.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+			return weak("must contain a digit");
+		}
+		if self.require_symbol && password.chars().all(char::is_alphanumeric) {
+			return weak("must contain a symbol");
+		}
+		Ok(())
+	}
+}
+
+impl From<storage::PasswordPolicy> for PasswordPolicy {
+	fn from(p: storage::PasswordPolicy) -> Self {
+		Self {
+			min_length: p.min_length.unwrap_or_default(),
+			require_mixed_case: p.require_mixed_case.unwrap_or_default(),
+			require_digit: p.require_digit.unwrap_or_default(),
+			require_symbol: p.require_symbol.unwrap_or_default(),
+			require_initial_password_change: p.require_initial_password_change.unwrap_or_default(),
+		}
+	}
+}
+
+impl From<PasswordPolicy> for storage::PasswordPolicy {
+	fn from(p: PasswordPolicy) -> Self {
+		Self {
+			min_length: (p.min_length != 0).then_some(p.min_length),
+			require_mixed_case: p.require_mixed_case.then_some(true),
+			require_digit: p.require_digit.then_some(true),
+			require_symbol: p.require_symbol.then_some(true),
+			require_initial_password_change: p.require_initial_password_change.then_some(true),
+		}
+	}
+}
+
+// Sign in through an OpenID Connect identity provider
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Oidc {
+	pub issuer_url: String,
+	pub client_id: String,
+	pub client_secret: String,
+	pub username_claim: String,
+	pub admin_group: Option<String>,
+	pub create_users: bool,
+}
+
+impl TryFrom<storage::Oidc> for Oidc {
+	type Error = Error;
+
+	fn try_from(o: storage::Oidc) -> Result<Self, Self::Error> {
+		// Kept as written, since ID tokens must name this exact issuer
+		let issuer_url = o.issuer_url.trim().trim_end_matches('/').to_owned();
+		match http::Uri::try_from(&issuer_url) {
+			Ok(u) if matches!(u.scheme_str(), Some("http" | "https")) => (),
+			_ => return Err(Error::OidcIssuerURLInvalid),
+		};
+		Ok(Self {
+			issuer_url,
+			client_id: o.client_id,
+			client_secret: o.client_secret,
+			username_claim: o
+				.username_claim
+				.unwrap_or_else(|| "preferred_username".to_owned()),
+			admin_group: o.admin_group,
+			create_users: o.create_users.unwrap_or_default(),
+		})
+	}
+}
+
+impl From<Oidc> for storage::Oidc {
+	fn from(o: Oidc) -> Self {
+		Self {
+			issuer_url: o.issuer_url,
+			client_id: o.client_id,
+			client_secret: o.client_secret,
+			username_claim: (o.username_claim != "preferred_username").then_some(o.username_claim),
+			admin_group: o.admin_group,
+			create_users: o.create_users.then_some(true),
+		}
+	}
+}
+
+// Relying party passkeys are bound to, instead of the host name requests are addressed to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Passkeys {
+	pub origin: String,
+	pub rp_id: String,
+}
+
+impl TryFrom<storage::Passkeys> for Passkeys {
+	type Error = Error;
+
+	fn try_from(p: storage::Passkeys) -> Result<Self, Self::Error> {
+		// Browsers report lowercase origins without a path or trailing slash
+		let origin = p.origin.trim().trim_end_matches('/').to_ascii_lowercase();
+		let host = match http::Uri::try_from(&origin) {
+			Ok(u)
+				if matches!(u.scheme_str(), Some("http" | "https"))
+					&& u.path() == "/"
+					&& u.query().is_none() =>
+			{
+				match u.host() {
+					Some(h) => h.to_owned(),
+					None => return Err(Error::PasskeyOriginInvalid),
+				}
+			}
+			_ => return Err(Error::PasskeyOriginInvalid),
+		};
+		// The RP ID may be the origin's host or a parent domain of it
+		let rp_id = match p.rp_id.map(|id| id.trim().to_ascii_lowercase()) {
+			None => host,
+			Some(id) if id == host || (!id.is_empty() && host.ends_with(&format!(".{id}"))) => id,
+			Some(_) => return Err(Error::PasskeyRelyingPartyInvalid),
+		};
+		Ok(Self { origin, rp_id })
+	}
+}
+
+impl From<Passkeys> for storage::Passkeys {
+	fn from(p: Passkeys) -> Self {
+		let host = http::Uri::try_from(&p.origin)
+			.ok()
+			.and_then(|u| u.host().map(str::to_owned));
+		Self {
+			rp_id: (host.as_deref() != Some(p.rp_id.as_str())).then_some(p.rp_id),
+			origin: p.origin,
+		}
+	}
+}
+
+impl TryFrom<storage::Limits> for Limits {
+	type Error = Error;
+
+	fn try_from(l: storage::Limits) -> Result<Self, Self::Error> {
+		let values = [
+			l.max_connections,
+			l.max_connections_per_ip,
+			l.max_streams_per_user,
+			l.max_request_body_mb,
+		];
+		if values.contains(&Some(0)) {
+			return Err(Error::LimitInvalid);
+		}
+		Ok(Self {
+			max_connections: l.max_connections,
+			max_connections_per_ip: l.max_connections_per_ip,
+			max_streams_per_user: l.max_streams_per_user,
+			max_request_body_mb: l
+				.max_request_body_mb
+				.unwrap_or(Limits::default().max_request_body_mb),
+		})
+	}
+}
+
+impl From<Limits> for storage::Limits {
+	fn from(l: Limits) -> Self {
+		Self {
+			max_connections: l.max_connections,
+			max_connections_per_ip: l.max_connections_per_ip,
+			max_streams_per_user: l.max_streams_per_user,
+			max_request_body_mb: (l.max_request_body_mb != Limits::default().max_request_body_mb)
+				.then_some(l.max_request_body_mb),
+		}
+	}
+}
+
+// Endpoint notified about server events. Webhooks without a list of events receive all of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Webhook {
+	pub url: http::Uri,
+	pub events: Vec<WebhookEvent>,
+}
+
+impl Webhook {
+	pub fn accepts(&self, event: WebhookEvent) -> bool {
+		self.events.is_empty() || self.events.contains(&event)
+	}
+}
+
+impl TryFrom<storage::Webhook> for Webhook {
+	type Error = Error;
+
+	fn try_from(w: storage::Webhook) -> Result<Self, Self::Error> {
+		let url = match http::Uri::try_from(w.url.trim()) {
+			Ok(u) if matches!(u.scheme_str(), Some("http" | "https")) => u,
+			_ => return Err(Error::WebhookURLInvalid),
+		};
+		Ok(Self {
+			url,
+			events: w.events,
+		})
+	}
+}
+
+impl From<Webhook> for storage::Webhook {
+	fn from(w: Webhook) -> Self {
+		Self {
+			url: w.url.to_string(),
+			events: w.events,
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dlna {
+	pub friendly_name: String,
+	// Account whose permissions and limits apply to DLNA devices
+	pub user: Option<String>,
+}
+
+impl From<storage::Dlna> for Dlna {
+	fn from(d: storage::Dlna) -> Self {
+		let friendly_name = d.friendly_name.map(|n| n.trim().to_owned());
+		Self {
+			friendly_name: friendly_name
+				.filter(|n| !n.is_empty())
+				.unwrap_or_else(|| "Polaris".to_owned()),
+			user: d.user.filter(|u| !u.trim().is_empty()),
+		}
+	}
+}
+
+impl From<Dlna> for storage::Dlna {
+	fn from(d: Dlna) -> Self {
+		Self {
+			friendly_name: Some(d.friendly_name),
+			user: d.user,
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mpd {
+	pub port: u16,
+}
+
+impl From<storage::Mpd> for Mpd {
+	fn from(m: storage::Mpd) -> Self {
+		Self {
+			port: m.port.unwrap_or(6600),
+		}
+	}
+}
+
+impl From<Mpd> for storage::Mpd {
+	fn from(m: Mpd) -> Self {
+		Self { port: Some(m.port) }
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Jukebox {
+	pub device: Option<String>,
+}
+
+impl From<storage::Jukebox> for Jukebox {
+	fn from(j: storage::Jukebox) -> Self {
+		Self {
+			device: j
+				.device
+				.map(|d| d.trim().to_owned())
+				.filter(|d| !d.is_empty()),
+		}
+	}
+}
+
+impl From<Jukebox> for storage::Jukebox {
+	fn from(j: Jukebox) -> Self {
+		Self { device: j.device }
+	}
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct Config {
 	pub album_art_pattern: Option<Regex>,
+	pub base_path: Option<String>,
 	pub ddns_update_url: Option<http::Uri>,
+	pub detect_silence: bool,
+	pub acoustid_fingerprints: bool,
+	pub musicbrainz_enrichment: bool,
+	pub change_detection: ChangeDetection,
+	pub scan_threads: Option<NonZeroUsize>,
+	pub scan_schedule: Option<scanner::Schedule>,
+	pub filename_pattern: Option<formats::FilenamePattern>,
+	pub artist_separators: Vec<String>,
+	pub acme: Option<Acme>,
+	pub post_scan_hook: Option<PostScanHook>,
+	pub dlna: Option<Dlna>,
+	pub mpd: Option<Mpd>,
+	pub jukebox: Option<Jukebox>,
+	pub artist_images: ArtistImages,
+	pub limits: Limits,
+	pub auth: Auth,
+	pub password_policy: PasswordPolicy,
+	pub oidc: Option<Oidc>,
+	pub passkeys: Option<Passkeys>,
+	pub webhooks: Vec<Webhook>,
+	pub trusted_proxies: Vec<TrustedProxy>,
+	pub artist_aliases: Vec<Alias>,
+	pub album_aliases: Vec<Alias>,
+	pub genre_aliases: Vec<Alias>,
 	pub mount_dirs: Vec<MountDir>,
 	pub users: Vec<User>,
 }
@@ -36,6 +552,9 @@ impl TryFrom<storage::Config> for Config {
 		let mut config = Config::default();
 		config.set_mounts(c.mount_dirs)?;
 		config.set_users(c.users)?;
+		config.set_artist_aliases(c.artist_aliases)?;
+		config.set_album_aliases(c.album_aliases)?;
+		config.set_genre_aliases(c.genre_aliases)?;
 
 		config.album_art_pattern = match c.album_art_pattern.as_deref().map(Regex::new) {
 			Some(Ok(u)) => Some(u),
@@ -43,12 +562,70 @@ impl TryFrom<storage::Config> for Config {
 			None => None,
 		};
 
+		config.base_path = match c.base_path.as_deref().map(sanitize_base_path) {
+			Some(Ok(p)) => p,
+			Some(Err(e)) => return Err(e),
+			None => None,
+		};
+
 		config.ddns_update_url = match c.ddns_update_url.map(http::Uri::try_from) {
 			Some(Ok(u)) => Some(u),
 			Some(Err(_)) => return Err(Error::DDNSUpdateURLInvalid),
 			None => None,
 		};
 
+		config.detect_silence = c.detect_silence.unwrap_or_default();
+		config.acoustid_fingerprints = c.acoustid_fingerprints.unwrap_or_default();
+		config.musicbrainz_enrichment = c.musicbrainz_enrichment.unwrap_or_default();
+		config.change_detection = c.change_detection.unwrap_or_default();
+		config.scan_threads = c.scan_threads;
+		config.scan_schedule = c
+			.scan_schedule
+			.as_deref()
+			.map(scanner::Schedule::new)
+			.transpose()?;
+		config.filename_pattern = c
+			.filename_pattern
+			.as_deref()
+			.map(formats::FilenamePattern::new)
+			.transpose()?;
+		config.set_artist_separators(
+			c.artist_separators
+				.unwrap_or_else(default_artist_separators),
+		)?;
+		config.acme = c.acme.map(Acme::try_from).transpose()?;
+		config.post_scan_hook = c.post_scan_hook.map(PostScanHook::try_from).transpose()?;
+		config.dlna = c.dlna.map(Dlna::from);
+		config.mpd = c.mpd.map(Mpd::from);
+		config.jukebox = c.jukebox.map(Jukebox::from);
+		config.artist_images = c
+			.artist_images
+			.map(ArtistImages::try_from)
+			.transpose()?
+			.unwrap_or_default();
+		config.limits = c
+			.limits
+			.map(Limits::try_from)
+			.transpose()?
+			.unwrap_or_default();
+		config.auth = c.auth.map(Auth::try_from).transpose()?.unwrap_or_default();
+		config.password_policy = c
+			.password_policy
+			.map(PasswordPolicy::from)
+			.unwrap_or_default();
+		config.oidc = c.oidc.map(Oidc::try_from).transpose()?;
+		config.passkeys = c.passkeys.map(Passkeys::try_from).transpose()?;
+		config.webhooks = c
+			.webhooks
+			.into_iter()
+			.map(Webhook::try_from)
+			.collect::<Result<_, _>>()?;
+		config.trusted_proxies = c
+			.trusted_proxies
+			.iter()
+			.map(|p| p.parse())
+			.collect::<Result<_, _>>()?;
+
 		Ok(config)
 	}
 }
@@ -57,17 +634,77 @@ impl From<Config> for storage::Config {
 	fn from(c: Config) -> Self {
 		Self {
 			album_art_pattern: c.album_art_pattern.map(|p| p.as_str().to_owned()),
+			base_path: c.base_path,
 			mount_dirs: c.mount_dirs.into_iter().map(|d| d.into()).collect(),
 			ddns_update_url: c.ddns_update_url.map(|u| u.to_string()),
+			detect_silence: c.detect_silence.then_some(true),
+			acoustid_fingerprints: c.acoustid_fingerprints.then_some(true),
+			musicbrainz_enrichment: c.musicbrainz_enrichment.then_some(true),
+			change_detection: (c.change_detection != ChangeDetection::default())
+				.then_some(c.change_detection),
+			scan_threads: c.scan_threads,
+			scan_schedule: c.scan_schedule.map(|s| s.as_str().to_owned()),
+			filename_pattern: c.filename_pattern.map(|p| p.as_str().to_owned()),
+			artist_separators: (c.artist_separators != default_artist_separators())
+				.then_some(c.artist_separators),
+			acme: c.acme.map(|a| a.into()),
+			post_scan_hook: c.post_scan_hook.map(|h| h.into()),
+			dlna: c.dlna.map(|d| d.into()),
+			mpd: c.mpd.map(|m| m.into()),
+			jukebox: c.jukebox.map(|j| j.into()),
+			artist_images: (c.artist_images != ArtistImages::default())
+				.then(|| c.artist_images.into()),
+			limits: (c.limits != Limits::default()).then(|| c.limits.into()),
+			auth: (c.auth != Auth::default()).then(|| c.auth.into()),
+			password_policy: (c.password_policy != PasswordPolicy::default())
+				.then(|| c.password_policy.into()),
+			oidc: c.oidc.map(|o| o.into()),
+			passkeys: c.passkeys.map(|p| p.into()),
+			webhooks: c.webhooks.into_iter().map(|w| w.into()).collect(),
+			trusted_proxies: c.trusted_proxies.iter().map(|p| p.to_string()).collect(),
+			artist_aliases: c.artist_aliases.into_iter().map(|a| a.into()).collect(),
+			album_aliases: c.album_aliases.into_iter().map(|a| a.into()).collect(),
+			genre_aliases: c.genre_aliases.into_iter().map(|a| a.into()).collect(),
 			users: c.users.into_iter().map(|u| u.into()).collect(),
 		}
 	}
 }
 
+impl Config {
+	pub fn set_artist_separators(&mut self, separators: Vec<String>) -> Result<(), Error> {
+		if separators.iter().any(String::is_empty) {
+			return Err(Error::ArtistSeparatorInvalid);
+		}
+		self.artist_separators = separators;
+		Ok(())
+	}
+}
+
+// Artists tagged as `Artist A; Artist B` or `Artist A feat. Artist B` are indexed separately.
+// A plain `/` is left out so names like `AC/DC` stay whole.
+fn default_artist_separators() -> Vec<String> {
+	[";", "feat.", "／"].map(str::to_owned).into()
+}
+
+// Turns user input like `polaris/` into `/polaris`. Returns `None` when serving from the root.
+fn sanitize_base_path(base_path: &str) -> Result<Option<String>, Error> {
+	let trimmed = base_path.trim().trim_matches('/');
+	if trimmed.is_empty() {
+		return Ok(None);
+	}
+	let base_path = format!("/{trimmed}");
+	match http::uri::PathAndQuery::try_from(base_path.as_str()) {
+		Ok(p) if p.query().is_none() && !base_path.contains("//") => Ok(Some(base_path)),
+		_ => Err(Error::BasePathInvalid),
+	}
+}
+
 #[derive(Clone)]
 pub struct Manager {
 	config_file_path: PathBuf,
-	config: Arc<RwLock<Config>>,
+	// Writes are serialized through this lock, and published to `snapshot` once complete
+	config: Arc<Mutex<Config>>,
+	snapshot: Arc<std::sync::RwLock<Arc<Config>>>,
 	auth_secret: auth::Secret,
 	#[allow(dead_code)]
 	file_watcher: Arc<Debouncer<RecommendedWatcher, FileIdMap>>,
@@ -105,7 +742,8 @@ impl Manager {
 
 		let manager = Self {
 			config_file_path: config_file_path.to_owned(),
-			config: Arc::new(RwLock::new(Config::default())),
+			config: Arc::default(),
+			snapshot: Arc::default(),
 			auth_secret,
 			file_watcher: Arc::new(debouncer),
 			change_notify: Arc::default(),
@@ -130,6 +768,15 @@ impl Manager {
 		Ok(manager)
 	}
 
+	// Returns the current configuration. Readers never wait on configuration changes.
+	pub fn current(&self) -> Arc<Config> {
+		self.snapshot.read().unwrap().clone()
+	}
+
+	fn publish(&self, config: &Config) {
+		*self.snapshot.write().unwrap() = Arc::new(config.clone());
+	}
+
 	pub fn on_config_change(&self) -> Notified {
 		self.change_notify.notified()
 	}
@@ -147,10 +794,9 @@ impl Manager {
 	}
 
 	pub async fn save_config(&self) -> Result<(), Error> {
-		let serialized = toml::ser::to_string_pretty::<storage::Config>(
-			&self.config.read().await.clone().into(),
-		)
-		.map_err(Error::ConfigSerialization)?;
+		let serialized =
+			toml::ser::to_string_pretty::<storage::Config>(&self.current().as_ref().clone().into())
+				.map_err(Error::ConfigSerialization)?;
 		tokio::fs::write(&self.config_file_path, serialized.as_bytes())
 			.await
 			.map_err(|e| Error::Io(self.config_file_path.clone(), e))?;
@@ -158,8 +804,9 @@ impl Manager {
 	}
 
 	pub async fn apply_config(&self, new_config: storage::Config) -> Result<(), Error> {
-		let mut config = self.config.write().await;
+		let mut config = self.config.lock().await;
 		*config = new_config.try_into()?;
+		self.publish(&config);
 		self.change_notify.notify_waiters();
 		Ok(())
 	}
@@ -177,8 +824,9 @@ impl Manager {
 		op: F,
 	) -> Result<(), Error> {
 		{
-			let mut config = self.config.write().await;
+			let mut config = self.config.lock().await;
 			op(&mut config)?;
+			self.publish(&config);
 		}
 		self.change_notify.notify_waiters();
 		self.save_config().await?;
@@ -186,8 +834,7 @@ impl Manager {
 	}
 
 	pub async fn get_index_album_art_pattern(&self) -> Regex {
-		let config = self.config.read().await;
-		let pattern = config.album_art_pattern.clone();
+		let pattern = self.current().album_art_pattern.clone();
 		pattern.unwrap_or_else(|| Regex::new("Folder.(jpeg|jpg|png)").unwrap())
 	}
 
@@ -198,8 +845,12 @@ impl Manager {
 		.await
 	}
 
+	pub async fn get_base_path(&self) -> Option<String> {
+		self.current().base_path.clone()
+	}
+
 	pub async fn get_ddns_update_url(&self) -> Option<http::Uri> {
-		self.config.read().await.ddns_update_url.clone()
+		self.current().ddns_update_url.clone()
 	}
 
 	pub async fn set_ddns_update_url(&self, url: Option<http::Uri>) -> Result<(), Error> {
@@ -209,13 +860,151 @@ impl Manager {
 		.await
 	}
 
+	pub async fn get_detect_silence(&self) -> bool {
+		self.current().detect_silence
+	}
+
+	pub async fn set_detect_silence(&self, detect_silence: bool) -> Result<(), Error> {
+		self.mutate(|c| {
+			c.detect_silence = detect_silence;
+		})
+		.await
+	}
+
+	pub async fn get_acoustid_fingerprints(&self) -> bool {
+		self.current().acoustid_fingerprints
+	}
+
+	pub async fn get_musicbrainz_enrichment(&self) -> bool {
+		self.current().musicbrainz_enrichment
+	}
+
+	pub async fn get_change_detection(&self) -> ChangeDetection {
+		self.current().change_detection
+	}
+
+	pub async fn get_scan_threads(&self) -> Option<NonZeroUsize> {
+		self.current().scan_threads
+	}
+
+	pub async fn get_scan_schedule(&self) -> Option<scanner::Schedule> {
+		self.current().scan_schedule.clone()
+	}
+
+	pub async fn get_filename_pattern(&self) -> Option<formats::FilenamePattern> {
+		self.current().filename_pattern.clone()
+	}
+
+	pub async fn set_filename_pattern(
+		&self,
+		pattern: Option<formats::FilenamePattern>,
+	) -> Result<(), Error> {
+		self.mutate(|c| {
+			c.filename_pattern = pattern;
+		})
+		.await
+	}
+
+	pub async fn get_artist_separators(&self) -> Vec<String> {
+		self.current().artist_separators.clone()
+	}
+
+	pub async fn set_artist_separators(&self, separators: Vec<String>) -> Result<(), Error> {
+		self.mutate_fallible(|c| c.set_artist_separators(separators))
+			.await
+	}
+
+	pub async fn get_artist_aliases(&self) -> Vec<Alias> {
+		self.current().artist_aliases.to_vec()
+	}
+
+	pub async fn get_album_aliases(&self) -> Vec<Alias> {
+		self.current().album_aliases.to_vec()
+	}
+
+	pub async fn get_genre_aliases(&self) -> Vec<Alias> {
+		self.current().genre_aliases.to_vec()
+	}
+
+	pub async fn set_aliases(
+		&self,
+		artists: Vec<storage::Alias>,
+		albums: Vec<storage::Alias>,
+		genres: Vec<storage::Alias>,
+	) -> Result<(), Error> {
+		self.mutate_fallible(|c| c.set_aliases(artists, albums, genres))
+			.await
+	}
+
+	pub async fn get_acme(&self) -> Option<Acme> {
+		self.current().acme.clone()
+	}
+
+	pub async fn get_post_scan_hook(&self) -> Option<PostScanHook> {
+		self.current().post_scan_hook.clone()
+	}
+
+	pub async fn get_dlna(&self) -> Option<Dlna> {
+		self.current().dlna.clone()
+	}
+
+	pub async fn get_mpd(&self) -> Option<Mpd> {
+		self.current().mpd.clone()
+	}
+
+	pub async fn get_jukebox(&self) -> Option<Jukebox> {
+		self.current().jukebox.clone()
+	}
+
+	pub async fn get_artist_images(&self) -> ArtistImages {
+		self.current().artist_images.clone()
+	}
+
+	pub async fn get_limits(&self) -> Limits {
+		self.current().limits.clone()
+	}
+
+	pub async fn get_auth(&self) -> Auth {
+		self.current().auth.clone()
+	}
+
+	pub async fn get_oidc(&self) -> Option<Oidc> {
+		self.current().oidc.clone()
+	}
+
+	pub async fn get_passkeys(&self) -> Option<Passkeys> {
+		self.current().passkeys.clone()
+	}
+
+	pub async fn get_webhooks(&self) -> Vec<Webhook> {
+		self.current().webhooks.to_vec()
+	}
+
+	pub async fn get_trusted_proxies(&self) -> Vec<TrustedProxy> {
+		self.current().trusted_proxies.to_vec()
+	}
+
+	// Verifies that the configuration file can still be read
+	pub async fn check_config_file(&self) -> Result<(), Error> {
+		tokio::fs::File::open(&self.config_file_path)
+			.await
+			.map(|_| ())
+			.map_err(|e| Error::Io(self.config_file_path.clone(), e))
+	}
+
+	pub fn get_config_dir(&self) -> PathBuf {
+		match self.config_file_path.parent() {
+			Some(parent) if parent.components().count() > 0 => parent.to_owned(),
+			_ => PathBuf::from("."),
+		}
+	}
+
 	pub async fn get_users(&self) -> Vec<User> {
-		self.config.read().await.users.to_vec()
+		self.current().users.to_vec()
 	}
 
 	pub async fn get_user(&self, username: &str) -> Result<User, Error> {
-		let config = self.config.read().await;
-		config
+		self.current()
 			.get_user(username)
 			.cloned()
 			.ok_or(Error::UserNotFound)
@@ -231,9 +1020,85 @@ impl Manager {
 			.await
 	}
 
-	pub async fn login(&self, username: &str, password: &str) -> Result<auth::Token, Error> {
-		let config = self.config.read().await;
-		config.login(username, password, &self.auth_secret)
+	// Users registering with an invite pick their own password, which never needs changing
+	pub async fn register_user(&self, username: &str, password: &str) -> Result<(), Error> {
+		self.mutate_fallible(|c| c.register_user(username, password))
+			.await
+	}
+
+	pub async fn login(
+		&self,
+		username: &str,
+		password: &str,
+		session_id: Option<&str>,
+	) -> Result<auth::Token, Error> {
+		self.current()
+			.login(username, password, session_id, &self.auth_secret)
+	}
+
+	// Signs in a user vouched for by an identity provider. Their account is created or updated to
+	// match what the provider reports, if allowed.
+	pub async fn login_external(
+		&self,
+		username: &str,
+		admin: Option<bool>,
+		create_user: bool,
+		session_id: Option<&str>,
+	) -> Result<auth::Token, Error> {
+		let needs_update = match self.current().get_user(username) {
+			Some(user) => admin.is_some_and(|a| a != user.is_admin()),
+			None if create_user => true,
+			None => return Err(Error::IncorrectUsername),
+		};
+		if needs_update {
+			self.mutate_fallible(|c| c.sync_external_user(username, admin))
+				.await?;
+		}
+		self.current()
+			.generate_auth_token(username, session_id, &self.auth_secret)
+	}
+
+	// Finds the user bound to an OpenID Connect account, or creates one named after the account
+	// Returns the user linked to an account of the identity provider, and whether it was just created
+	pub async fn resolve_oidc_user(
+		&self,
+		subject: &str,
+		username: &str,
+		create_user: bool,
+	) -> Result<(String, bool), Error> {
+		let config = self.current();
+		if let Some(user) = config.get_oidc_user(subject) {
+			return Ok((user.name.clone(), false));
+		}
+		if config.exists(username) {
+			return Err(Error::OidcAccountNotLinked);
+		}
+		if !create_user {
+			return Err(Error::IncorrectUsername);
+		}
+		self.mutate_fallible(|c| c.add_oidc_user(username, subject))
+			.await?;
+		Ok((username.to_owned(), true))
+	}
+
+	// Issues a new login token for an existing session, before the current one expires
+	pub async fn refresh_auth_token(
+		&self,
+		username: &str,
+		session_id: Option<&str>,
+	) -> Result<auth::Token, Error> {
+		self.current()
+			.generate_auth_token(username, session_id, &self.auth_secret)
+	}
+
+	pub async fn generate_feed_token(
+		&self,
+		username: &str,
+		playlist: &str,
+		feed_id: &str,
+	) -> Result<auth::Token, Error> {
+		self.current()
+			.generate_feed_token(username, playlist, feed_id, &self.auth_secret)
 	}
 
 	pub async fn set_is_admin(&self, username: &str, is_admin: bool) -> Result<(), Error> {
@@ -241,18 +1106,67 @@ impl Manager {
 			.await
 	}
 
+	pub async fn add_passkey(
+		&self,
+		username: &str,
+		passkey: storage::Passkey,
+	) -> Result<(), Error> {
+		self.mutate_fallible(|c| c.add_passkey(username, passkey))
+			.await
+	}
+
+	pub async fn delete_passkey(&self, username: &str, id: &str) -> Result<(), Error> {
+		self.mutate_fallible(|c| c.delete_passkey(username, id))
+			.await
+	}
+
+	pub async fn find_passkey(&self, id: &str) -> Option<(String, storage::Passkey)> {
+		self.current()
+			.find_passkey(id)
+			.map(|(u, p)| (u.name.clone(), p.clone()))
+	}
+
+	// Invalidates all tokens previously issued to a user
+	pub async fn revoke_tokens(&self, username: &str) -> Result<(), Error> {
+		self.mutate_fallible(|c| c.revoke_tokens(username)).await
+	}
+
+	pub async fn set_is_guest(&self, username: &str, is_guest: bool) -> Result<(), Error> {
+		self.mutate_fallible(|c| c.set_is_guest(username, is_guest))
+			.await
+	}
+
+	pub async fn set_permissions(
+		&self,
+		username: &str,
+		permissions: Option<Vec<auth::Permission>>,
+	) -> Result<(), Error> {
+		self.mutate_fallible(|c| c.set_permissions(username, permissions))
+			.await
+	}
+
 	pub async fn set_password(&self, username: &str, password: &str) -> Result<(), Error> {
 		self.mutate_fallible(|c| c.set_password(username, password))
 			.await
 	}
 
+	pub async fn change_password(
+		&self,
+		username: &str,
+		current_password: &str,
+		new_password: &str,
+	) -> Result<(), Error> {
+		self.mutate_fallible(|c| c.change_password(username, current_password, new_password))
+			.await
+	}
+
 	pub async fn authenticate(
 		&self,
 		auth_token: &auth::Token,
 		scope: auth::Scope,
 	) -> Result<auth::Authorization, Error> {
-		let config = self.config.read().await;
-		config.authenticate(auth_token, scope, &self.auth_secret)
+		self.current()
+			.authenticate(auth_token, scope, &self.auth_secret)
 	}
 
 	pub async fn delete_user(&self, username: &str) -> Result<(), Error> {
@@ -260,16 +1174,18 @@ impl Manager {
 	}
 
 	pub async fn get_mounts(&self) -> Vec<MountDir> {
-		let config = self.config.read().await;
-		config.mount_dirs.to_vec()
+		self.current().mount_dirs.to_vec()
 	}
 
 	pub async fn resolve_virtual_path<P: AsRef<Path>>(
 		&self,
 		virtual_path: P,
 	) -> Result<PathBuf, Error> {
-		let config = self.config.read().await;
-		config.resolve_virtual_path(virtual_path)
+		self.current().resolve_virtual_path(virtual_path)
+	}
+
+	pub async fn get_remote<P: AsRef<Path>>(&self, virtual_path: P) -> Option<Remote> {
+		self.current().get_remote(virtual_path)
 	}
 
 	pub async fn set_mounts(&self, mount_dirs: Vec<storage::MountDir>) -> Result<(), Error> {
@@ -290,7 +1206,7 @@ mod test {
 		let manager = Manager::new(&config_path, auth::Secret([0; 32]))
 			.await
 			.unwrap();
-		let config: storage::Config = manager.config.read().await.clone().into();
+		let config: storage::Config = manager.current().as_ref().clone().into();
 		assert_eq!(config, storage::Config::default());
 	}
 
@@ -300,7 +1216,7 @@ mod test {
 		let manager = Manager::new(&config_path, auth::Secret([0; 32]))
 			.await
 			.unwrap();
-		let config: storage::Config = manager.config.read().await.clone().into();
+		let config: storage::Config = manager.current().as_ref().clone().into();
 
 		assert_eq!(
 			config.album_art_pattern,
@@ -311,6 +1227,10 @@ mod test {
 			vec![storage::MountDir {
 				source: PathBuf::from("test-data/small-collection"),
 				name: "root".to_owned(),
+				exclude_patterns: vec![],
+				symlinks: None,
+				split_artists: None,
+				remote: None,
 			}]
 		);
 		assert_eq!(config.users[0].name, "test_user");
@@ -322,6 +1242,146 @@ mod test {
 		assert!(config.users[0].hashed_password.is_some());
 	}
 
+	#[test]
+	fn can_sanitize_base_path() {
+		assert_eq!(sanitize_base_path("").unwrap(), None);
+		assert_eq!(sanitize_base_path("/").unwrap(), None);
+		assert_eq!(
+			sanitize_base_path("polaris").unwrap(),
+			Some("/polaris".to_owned())
+		);
+		assert_eq!(
+			sanitize_base_path("/music/polaris/").unwrap(),
+			Some("/music/polaris".to_owned())
+		);
+		assert!(sanitize_base_path("/polaris?a=b").is_err());
+		assert!(sanitize_base_path("/music//polaris").is_err());
+	}
+
+	#[tokio::test]
+	async fn rejects_invalid_acme_domain() {
+		let config = storage::Config {
+			acme: Some(storage::Acme {
+				domain: "https://example.com".to_owned(),
+				..Default::default()
+			}),
+			..Default::default()
+		};
+		assert!(matches!(
+			Config::try_from(config),
+			Err(Error::AcmeDomainInvalid)
+		));
+	}
+
+	#[tokio::test]
+	async fn rejects_invalid_post_scan_webhook_url() {
+		let config = storage::Config {
+			post_scan_hook: Some(storage::PostScanHook {
+				webhook_url: Some("not a url".to_owned()),
+				..Default::default()
+			}),
+			..Default::default()
+		};
+		assert!(matches!(
+			Config::try_from(config),
+			Err(Error::PostScanWebhookURLInvalid)
+		));
+	}
+
+	#[test]
+	fn can_read_passkey_relying_party() {
+		let passkeys = |origin: &str, rp_id: Option<&str>| {
+			Passkeys::try_from(storage::Passkeys {
+				origin: origin.to_owned(),
+				rp_id: rp_id.map(str::to_owned),
+			})
+		};
+
+		let p = passkeys("https://Music.example.com/", None).unwrap();
+		assert_eq!(p.origin, "https://music.example.com");
+		assert_eq!(p.rp_id, "music.example.com");
+		assert_eq!(
+			passkeys("https://music.example.com", Some("example.com"))
+				.unwrap()
+				.rp_id,
+			"example.com"
+		);
+
+		assert!(matches!(
+			passkeys("music.example.com", None),
+			Err(Error::PasskeyOriginInvalid)
+		));
+		assert!(matches!(
+			passkeys("https://music.example.com/polaris", None),
+			Err(Error::PasskeyOriginInvalid)
+		));
+		assert!(matches!(
+			passkeys("https://music.example.com", Some("other.com")),
+			Err(Error::PasskeyRelyingPartyInvalid)
+		));
+		assert!(matches!(
+			passkeys("https://music.example.com", Some("le.com")),
+			Err(Error::PasskeyRelyingPartyInvalid)
+		));
+	}
+
+	#[tokio::test]
+	async fn rejects_zero_limits() {
+		let config = storage::Config {
+			limits: Some(storage::Limits {
+				max_streams_per_user: Some(0),
+				..Default::default()
+			}),
+			..Default::default()
+		};
+		assert!(matches!(Config::try_from(config), Err(Error::LimitInvalid)));
+	}
+
+	#[tokio::test]
+	async fn rejects_zero_token_lifetime() {
+		let config = storage::Config {
+			auth: Some(storage::Auth {
+				token_lifetime_hours: Some(0),
+				..Default::default()
+			}),
+			..Default::default()
+		};
+		assert!(matches!(
+			Config::try_from(config),
+			Err(Error::TokenLifetimeInvalid)
+		));
+	}
+
+	#[tokio::test]
+	async fn rejects_zero_lockout_threshold() {
+		let config = storage::Config {
+			auth: Some(storage::Auth {
+				lockout_threshold: Some(0),
+				..Default::default()
+			}),
+			..Default::default()
+		};
+		assert!(matches!(
+			Config::try_from(config),
+			Err(Error::LockoutInvalid)
+		));
+	}
+
+	#[tokio::test]
+	async fn rejects_invalid_webhook_url() {
+		let config = storage::Config {
+			webhooks: vec![storage::Webhook {
+				url: "ftp://example.com".to_owned(),
+				..Default::default()
+			}],
+			..Default::default()
+		};
+		assert!(matches!(
+			Config::try_from(config),
+			Err(Error::WebhookURLInvalid)
+		));
+	}
+
 	#[tokio::test]
 	async fn can_write_config() {
 		let ctx = test::ContextBuilder::new(test_name!()).build().await;
@@ -335,4 +1395,13 @@ mod test {
 			.unwrap();
 		assert!(manager.get_user("Walter").await.is_ok());
 	}
+
+	#[tokio::test]
+	async fn snapshots_are_not_affected_by_changes() {
+		let ctx = test::ContextBuilder::new(test_name!()).build().await;
+		let before = ctx.config_manager.current();
+		ctx.config_manager.set_detect_silence(true).await.unwrap();
+		assert!(!before.detect_silence);
+		assert!(ctx.config_manager.current().detect_silence);
+	}
 }
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use crate::app::Error;
+
+use super::storage;
+use super::Config;
+
+// Alternative spellings of an artist, album or genre name, which are merged into `name` when indexing
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Alias {
+	pub name: String,
+	pub aliases: Vec<String>,
+}
+
+impl TryFrom<storage::Alias> for Alias {
+	type Error = Error;
+
+	fn try_from(alias: storage::Alias) -> Result<Self, Self::Error> {
+		let name = alias.name.trim().to_owned();
+		if name.is_empty() {
+			return Err(Error::AliasInvalid);
+		}
+		Ok(Self {
+			name,
+			aliases: alias
+				.aliases
+				.iter()
+				.map(|a| a.trim().to_owned())
+				.filter(|a| !a.is_empty())
+				.collect(),
+		})
+	}
+}
+
+impl From<Alias> for storage::Alias {
+	fn from(a: Alias) -> Self {
+		Self {
+			name: a.name,
+			aliases: a.aliases,
+		}
+	}
+}
+
+// Case-insensitive lookup from alternative spellings to preferred names
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct AliasTable(HashMap<String, String>);
+
+impl AliasTable {
+	pub fn new(aliases: &[Alias]) -> Self {
+		let mut table = HashMap::new();
+		for alias in aliases {
+			for spelling in &alias.aliases {
+				table.insert(spelling.to_lowercase(), alias.name.clone());
+			}
+		}
+		Self(table)
+	}
+
+	pub fn resolve(&self, name: String) -> String {
+		match self.0.get(&name.trim().to_lowercase()) {
+			Some(preferred) => preferred.clone(),
+			None => name,
+		}
+	}
+}
+
+fn convert_aliases(aliases: Vec<storage::Alias>) -> Result<Vec<Alias>, Error> {
+	aliases.into_iter().map(Alias::try_from).collect()
+}
+
+impl Config {
+	pub fn set_artist_aliases(&mut self, aliases: Vec<storage::Alias>) -> Result<(), Error> {
+		self.artist_aliases = convert_aliases(aliases)?;
+		Ok(())
+	}
+
+	pub fn set_album_aliases(&mut self, aliases: Vec<storage::Alias>) -> Result<(), Error> {
+		self.album_aliases = convert_aliases(aliases)?;
+		Ok(())
+	}
+
+	pub fn set_genre_aliases(&mut self, aliases: Vec<storage::Alias>) -> Result<(), Error> {
+		self.genre_aliases = convert_aliases(aliases)?;
+		Ok(())
+	}
+
+	// Nothing is changed unless every alias is valid
+	pub fn set_aliases(
+		&mut self,
+		artists: Vec<storage::Alias>,
+		albums: Vec<storage::Alias>,
+		genres: Vec<storage::Alias>,
+	) -> Result<(), Error> {
+		let artists = convert_aliases(artists)?;
+		let albums = convert_aliases(albums)?;
+		let genres = convert_aliases(genres)?;
+		self.artist_aliases = artists;
+		self.album_aliases = albums;
+		self.genre_aliases = genres;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn can_resolve_aliases() {
+		let table = AliasTable::new(&[Alias {
+			name: "Sigur Rós".to_owned(),
+			aliases: vec!["Sigur Ros".to_owned(), "Sigur Rós (band)".to_owned()],
+		}]);
+		assert_eq!(table.resolve("Sigur Ros".to_owned()), "Sigur Rós");
+		assert_eq!(table.resolve("sigur ros".to_owned()), "Sigur Rós");
+		assert_eq!(table.resolve("Sigur Rós (band)".to_owned()), "Sigur Rós");
+		assert_eq!(table.resolve("Múm".to_owned()), "Múm");
+	}
+
+	#[test]
+	fn rejects_blank_alias_name() {
+		let alias = storage::Alias {
+			name: " ".to_owned(),
+			aliases: vec!["Sigur Ros".to_owned()],
+		};
+		assert!(Alias::try_from(alias).is_err());
+	}
+}
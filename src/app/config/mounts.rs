@@ -10,10 +10,48 @@ use crate::app::Error;
 use super::storage;
 use super::Config;
 
-#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct MountDir {
 	pub source: PathBuf,
 	pub name: String,
+	pub exclude_patterns: Vec<ExcludePattern>,
+	pub symlinks: storage::SymlinkPolicy,
+	// Whether artist tags of songs in this directory are split using `artist_separators`
+	pub split_artists: bool,
+	// Server holding the files of this directory, in which case `source` is the location of the
+	// directory on that server
+	pub remote: Option<Remote>,
+}
+
+impl Default for MountDir {
+	fn default() -> Self {
+		Self {
+			source: PathBuf::new(),
+			name: String::new(),
+			exclude_patterns: vec![],
+			symlinks: Default::default(),
+			split_artists: true,
+			remote: None,
+		}
+	}
+}
+
+impl MountDir {
+	// Whether a path relative to the mount source is left out of the index
+	pub fn excludes<P: AsRef<Path>>(&self, relative_path: P) -> bool {
+		if self.exclude_patterns.is_empty() {
+			return false;
+		}
+		let relative_path = relative_path
+			.as_ref()
+			.components()
+			.map(|c| c.as_os_str().to_string_lossy())
+			.collect::<Vec<_>>()
+			.join("/");
+		self.exclude_patterns
+			.iter()
+			.any(|p| p.regex.is_match(&relative_path))
+	}
 }
 
 impl TryFrom<storage::MountDir> for MountDir {
@@ -24,6 +62,14 @@ impl TryFrom<storage::MountDir> for MountDir {
 		Ok(Self {
 			source: sanitize_path(&mount_dir.source),
 			name: mount_dir.name,
+			exclude_patterns: mount_dir
+				.exclude_patterns
+				.iter()
+				.map(|p| ExcludePattern::new(p))
+				.collect::<Result<_, _>>()?,
+			symlinks: mount_dir.symlinks.unwrap_or_default(),
+			split_artists: mount_dir.split_artists.unwrap_or(true),
+			remote: mount_dir.remote.map(Remote::try_from).transpose()?,
 		})
 	}
 }
@@ -33,15 +79,303 @@ impl From<MountDir> for storage::MountDir {
 		Self {
 			source: m.source,
 			name: m.name,
+			exclude_patterns: m.exclude_patterns.into_iter().map(|p| p.pattern).collect(),
+			symlinks: (m.symlinks != storage::SymlinkPolicy::default()).then_some(m.symlinks),
+			split_artists: (!m.split_artists).then_some(false),
+			remote: m.remote.map(|r| r.into()),
+		}
+	}
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Remote {
+	S3(S3Bucket),
+	Webdav(WebdavShare),
+	Smb(SmbShare),
+}
+
+impl TryFrom<storage::Remote> for Remote {
+	type Error = Error;
+
+	fn try_from(remote: storage::Remote) -> Result<Self, Self::Error> {
+		match remote {
+			storage::Remote::S3(bucket) => Ok(Self::S3(bucket.try_into()?)),
+			storage::Remote::Webdav(share) => Ok(Self::Webdav(share.try_into()?)),
+			storage::Remote::Smb(share) => Ok(Self::Smb(share.try_into()?)),
+		}
+	}
+}
+
+impl From<Remote> for storage::Remote {
+	fn from(remote: Remote) -> Self {
+		match remote {
+			Remote::S3(bucket) => Self::S3(bucket.into()),
+			Remote::Webdav(share) => Self::Webdav(share.into()),
+			Remote::Smb(share) => Self::Smb(share.into()),
+		}
+	}
+}
+
+// Bucket of an S3-compatible object storage service, like AWS S3 or MinIO
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct S3Bucket {
+	pub endpoint: http::Uri,
+	pub bucket: String,
+	pub region: String,
+	pub access_key_id: String,
+	pub secret_access_key: String,
+	// Whether the bucket name is part of the URL path (as MinIO expects by default), rather than
+	// of the host name
+	pub path_style: bool,
+}
+
+const DEFAULT_S3_REGION: &str = "us-east-1";
+
+impl TryFrom<storage::S3Bucket> for S3Bucket {
+	type Error = Error;
+
+	fn try_from(b: storage::S3Bucket) -> Result<Self, Self::Error> {
+		let endpoint = match http::Uri::try_from(b.endpoint.trim()) {
+			Ok(u) if matches!(u.scheme_str(), Some("http" | "https")) && u.host().is_some() => u,
+			_ => return Err(Error::S3EndpointInvalid),
+		};
+		let bucket = b.bucket.trim().to_owned();
+		if bucket.is_empty() {
+			return Err(Error::S3BucketMissing);
+		}
+		Ok(Self {
+			endpoint,
+			bucket,
+			region: b
+				.region
+				.map(|r| r.trim().to_owned())
+				.filter(|r| !r.is_empty())
+				.unwrap_or_else(|| DEFAULT_S3_REGION.to_owned()),
+			access_key_id: b.access_key_id,
+			secret_access_key: b.secret_access_key,
+			path_style: b.path_style.unwrap_or(true),
+		})
+	}
+}
+
+impl From<S3Bucket> for storage::S3Bucket {
+	fn from(b: S3Bucket) -> Self {
+		Self {
+			endpoint: b.endpoint.to_string(),
+			bucket: b.bucket,
+			region: (b.region != DEFAULT_S3_REGION).then_some(b.region),
+			access_key_id: b.access_key_id,
+			secret_access_key: b.secret_access_key,
+			path_style: (!b.path_style).then_some(false),
+		}
+	}
+}
+
+// Password written in the configuration file, or kept in a separate file (eg. a Docker secret).
+// Files are read whenever the password is needed, so they can be updated without a restart.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Secret {
+	Value(String),
+	File(PathBuf),
+}
+
+impl Secret {
+	fn new(value: Option<String>, file: Option<PathBuf>) -> Option<Self> {
+		match (value, file) {
+			(_, Some(file)) => Some(Self::File(file)),
+			(Some(value), None) => Some(Self::Value(value)),
+			(None, None) => None,
+		}
+	}
+
+	pub fn read(&self) -> Result<String, Error> {
+		match self {
+			Self::Value(value) => Ok(value.clone()),
+			Self::File(path) => std::fs::read_to_string(path)
+				.map(|s| s.trim_end_matches(['\r', '\n']).to_owned())
+				.map_err(|e| Error::Io(path.clone(), e)),
+		}
+	}
+
+	fn into_storage(secret: Option<Self>) -> (Option<String>, Option<PathBuf>) {
+		match secret {
+			Some(Self::Value(value)) => (Some(value), None),
+			Some(Self::File(path)) => (None, Some(path)),
+			None => (None, None),
+		}
+	}
+}
+
+// Collection of files served by a WebDAV server, like Nextcloud or Apache's mod_dav
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WebdavShare {
+	pub url: http::Uri,
+	pub username: Option<String>,
+	pub password: Option<Secret>,
+}
+
+impl TryFrom<storage::WebdavShare> for WebdavShare {
+	type Error = Error;
+
+	fn try_from(s: storage::WebdavShare) -> Result<Self, Self::Error> {
+		let url = match http::Uri::try_from(s.url.trim()) {
+			Ok(u) if matches!(u.scheme_str(), Some("http" | "https")) && u.host().is_some() => u,
+			_ => return Err(Error::WebdavURLInvalid),
+		};
+		Ok(Self {
+			url,
+			username: s.username,
+			password: Secret::new(s.password, s.password_file),
+		})
+	}
+}
+
+impl From<WebdavShare> for storage::WebdavShare {
+	fn from(s: WebdavShare) -> Self {
+		let (password, password_file) = Secret::into_storage(s.password);
+		Self {
+			url: s.url.to_string(),
+			username: s.username,
+			password,
+			password_file,
+		}
+	}
+}
+
+// Shared folder of an SMB server, like Windows file sharing or Samba
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SmbShare {
+	// Address of the server, like `smb://nas.local`
+	pub server: String,
+	// Name of the shared folder, like `/music`
+	pub share: String,
+	pub workgroup: Option<String>,
+	pub username: Option<String>,
+	pub password: Option<Secret>,
+}
+
+impl TryFrom<storage::SmbShare> for SmbShare {
+	type Error = Error;
+
+	fn try_from(s: storage::SmbShare) -> Result<Self, Self::Error> {
+		let server = s.server.trim().trim_end_matches('/');
+		if !server.starts_with("smb://") || server.len() == "smb://".len() {
+			return Err(Error::SmbServerInvalid);
+		}
+		let share = s.share.trim().trim_matches('/');
+		if share.is_empty() {
+			return Err(Error::SmbShareMissing);
+		}
+		Ok(Self {
+			server: server.to_owned(),
+			share: format!("/{share}"),
+			workgroup: s.workgroup,
+			username: s.username,
+			password: Secret::new(s.password, s.password_file),
+		})
+	}
+}
+
+impl From<SmbShare> for storage::SmbShare {
+	fn from(s: SmbShare) -> Self {
+		let (password, password_file) = Secret::into_storage(s.password);
+		Self {
+			server: s.server,
+			share: s.share,
+			workgroup: s.workgroup,
+			username: s.username,
+			password,
+			password_file,
 		}
 	}
 }
 
+// Glob pattern for files and directories to leave out of the index, like `@eaDir` or `*.tmp`.
+// Patterns without a `/` match names at any depth, others match paths relative to the mount
+// source. `*` and `?` do not match across directories, `**` does.
+#[derive(Clone, Debug)]
+pub struct ExcludePattern {
+	pattern: String,
+	regex: Regex,
+}
+
+impl PartialEq for ExcludePattern {
+	fn eq(&self, other: &Self) -> bool {
+		self.pattern == other.pattern
+	}
+}
+
+impl Eq for ExcludePattern {}
+
+impl ExcludePattern {
+	pub fn new(pattern: &str) -> Result<Self, Error> {
+		let glob = pattern.trim().trim_end_matches('/');
+		let anchored = glob.contains('/');
+		let glob = glob.trim_start_matches('/');
+		if glob.is_empty() {
+			return Err(Error::ExcludePatternInvalid);
+		}
+
+		let mut regex = String::from(if anchored { "^" } else { "(^|/)" });
+		let mut chars = glob.chars().peekable();
+		while let Some(c) = chars.next() {
+			match c {
+				'*' if chars.peek() == Some(&'*') => {
+					chars.next();
+					if chars.peek() == Some(&'/') {
+						chars.next();
+						regex.push_str("(.*/)?");
+					} else {
+						regex.push_str(".*");
+					}
+				}
+				'*' => regex.push_str("[^/]*"),
+				'?' => regex.push_str("[^/]"),
+				'[' => {
+					regex.push('[');
+					if chars.next_if_eq(&'!').is_some() {
+						regex.push('^');
+					}
+					loop {
+						match chars.next() {
+							Some(']') => break,
+							Some('\\') => regex.push_str("\\\\"),
+							Some(c) => regex.push(c),
+							None => return Err(Error::ExcludePatternInvalid),
+						}
+					}
+					regex.push(']');
+				}
+				c => regex.push_str(&regex::escape(&c.to_string())),
+			}
+		}
+		regex.push('$');
+
+		Ok(Self {
+			pattern: pattern.to_owned(),
+			regex: Regex::new(&regex).map_err(|_| Error::ExcludePatternInvalid)?,
+		})
+	}
+
+	pub fn as_str(&self) -> &str {
+		&self.pattern
+	}
+}
+
 impl Config {
 	pub fn set_mounts(&mut self, mount_dirs: Vec<storage::MountDir>) -> Result<(), Error> {
 		let mut new_mount_dirs = Vec::new();
 		for mount_dir in mount_dirs {
-			let mount_dir = <storage::MountDir as TryInto<MountDir>>::try_into(mount_dir)?;
+			let mut mount_dir = <storage::MountDir as TryInto<MountDir>>::try_into(mount_dir)?;
+			// Remote storage is only set up in the configuration file, so mounts edited through the
+			// API keep their existing server
+			if mount_dir.remote.is_none() {
+				mount_dir.remote = self
+					.mount_dirs
+					.iter()
+					.find(|m| m.name == mount_dir.name)
+					.and_then(|m| m.remote.clone());
+			}
 			new_mount_dirs.push(mount_dir);
 		}
 		new_mount_dirs.dedup_by(|a, b| a.name == b.name);
@@ -61,6 +395,14 @@ impl Config {
 		}
 		Err(Error::CouldNotMapToRealPath(virtual_path.as_ref().into()))
 	}
+
+	// Server holding a file or directory, for paths within remote mounts
+	pub fn get_remote<P: AsRef<Path>>(&self, virtual_path: P) -> Option<Remote> {
+		self.mount_dirs
+			.iter()
+			.find(|m| virtual_path.as_ref().starts_with(&m.name))
+			.and_then(|m| m.remote.clone())
+	}
 }
 
 fn sanitize_path(source: &Path) -> PathBuf {
@@ -75,6 +417,8 @@ fn sanitize_path(source: &Path) -> PathBuf {
 #[cfg(test)]
 mod test {
 	use super::*;
+	use crate::test::prepare_test_directory;
+	use crate::test_name;
 
 	#[test]
 	fn can_resolve_virtual_paths() {
@@ -82,6 +426,7 @@ mod test {
 			mount_dirs: vec![storage::MountDir {
 				name: "root".to_owned(),
 				source: PathBuf::from("test_dir"),
+				..Default::default()
 			}],
 			..Default::default()
 		};
@@ -138,6 +483,7 @@ mod test {
 				mount_dirs: vec![storage::MountDir {
 					name: "root".to_owned(),
 					source: PathBuf::from(test),
+					..Default::default()
 				}],
 				..Default::default()
 			};
@@ -146,4 +492,180 @@ mod test {
 			assert_eq!(converted_path, correct_path);
 		}
 	}
+
+	#[test]
+	fn validates_s3_buckets() {
+		let valid = storage::S3Bucket {
+			endpoint: "http://localhost:9000".to_owned(),
+			bucket: "music".to_owned(),
+			..Default::default()
+		};
+		let bucket = S3Bucket::try_from(valid.clone()).unwrap();
+		assert_eq!(bucket.region, "us-east-1");
+		assert!(bucket.path_style);
+
+		let invalid = [
+			storage::S3Bucket {
+				endpoint: "localhost:9000".to_owned(),
+				..valid.clone()
+			},
+			storage::S3Bucket {
+				endpoint: "ftp://localhost".to_owned(),
+				..valid.clone()
+			},
+			storage::S3Bucket {
+				bucket: " ".to_owned(),
+				..valid.clone()
+			},
+		];
+		for bucket in invalid {
+			assert!(S3Bucket::try_from(bucket).is_err());
+		}
+	}
+
+	#[test]
+	fn validates_webdav_shares() {
+		let valid = storage::WebdavShare {
+			url: "https://cloud.example.com/remote.php/dav/files/alice".to_owned(),
+			username: Some("alice".to_owned()),
+			password: Some("hunter2".to_owned()),
+			..Default::default()
+		};
+		let share = WebdavShare::try_from(valid.clone()).unwrap();
+		assert_eq!(share.password, Some(Secret::Value("hunter2".to_owned())));
+
+		for url in ["cloud.example.com/dav", "smb://cloud.example.com", ""] {
+			let invalid = storage::WebdavShare {
+				url: url.to_owned(),
+				..valid.clone()
+			};
+			assert!(WebdavShare::try_from(invalid).is_err());
+		}
+	}
+
+	#[test]
+	fn validates_smb_shares() {
+		let valid = storage::SmbShare {
+			server: "smb://nas.local/".to_owned(),
+			share: "music/".to_owned(),
+			password_file: Some(PathBuf::from("/run/secrets/smb")),
+			..Default::default()
+		};
+		let share = SmbShare::try_from(valid.clone()).unwrap();
+		assert_eq!(share.server, "smb://nas.local");
+		assert_eq!(share.share, "/music");
+		assert_eq!(
+			share.password,
+			Some(Secret::File(PathBuf::from("/run/secrets/smb")))
+		);
+
+		let invalid = [
+			storage::SmbShare {
+				server: "nas.local".to_owned(),
+				..valid.clone()
+			},
+			storage::SmbShare {
+				server: "smb://".to_owned(),
+				..valid.clone()
+			},
+			storage::SmbShare {
+				share: "/".to_owned(),
+				..valid.clone()
+			},
+		];
+		for share in invalid {
+			assert!(SmbShare::try_from(share).is_err());
+		}
+	}
+
+	#[test]
+	fn reads_secrets_from_files() {
+		let path = prepare_test_directory(test_name!()).join("password");
+		std::fs::write(&path, "hunter2\n").unwrap();
+		assert_eq!(Secret::File(path.clone()).read().unwrap(), "hunter2");
+		std::fs::remove_file(&path).unwrap();
+		assert!(Secret::File(path).read().is_err());
+	}
+
+	#[test]
+	fn mounts_keep_remote_storage() {
+		let raw_config = storage::Config {
+			mount_dirs: vec![storage::MountDir {
+				name: "root".to_owned(),
+				source: PathBuf::from("flac"),
+				remote: Some(storage::Remote::S3(storage::S3Bucket {
+					endpoint: "http://localhost:9000".to_owned(),
+					bucket: "music".to_owned(),
+					..Default::default()
+				})),
+				..Default::default()
+			}],
+			..Default::default()
+		};
+		let mut config: Config = raw_config.try_into().unwrap();
+		assert!(config.get_remote("root/Khemmis").is_some());
+		assert!(config.get_remote("other/Khemmis").is_none());
+
+		// As done by the mount directories API, which cannot set up remote servers
+		config
+			.set_mounts(vec![storage::MountDir {
+				name: "root".to_owned(),
+				source: PathBuf::from("flac"),
+				..Default::default()
+			}])
+			.unwrap();
+		assert!(matches!(
+			config.get_remote("root"),
+			Some(Remote::S3(b)) if b.bucket == "music"
+		));
+	}
+
+	#[test]
+	fn rejects_invalid_exclude_patterns() {
+		assert!(ExcludePattern::new("").is_err());
+		assert!(ExcludePattern::new("/").is_err());
+		assert!(ExcludePattern::new("[abc").is_err());
+	}
+
+	#[test]
+	fn applies_exclude_patterns() {
+		let mount_dir: MountDir = storage::MountDir {
+			name: "root".to_owned(),
+			source: PathBuf::from("test_dir"),
+			exclude_patterns: vec![
+				"@eaDir".to_owned(),
+				".st*".to_owned(),
+				"Khemmis/lossless masters/".to_owned(),
+				"**/demo?.mp3".to_owned(),
+			],
+			symlinks: None,
+			split_artists: None,
+			remote: None,
+		}
+		.try_into()
+		.unwrap();
+
+		let excluded = [
+			"@eaDir",
+			"Tobokegao/@eaDir",
+			".stfolder",
+			"Khemmis/lossless masters",
+			"demo1.mp3",
+			"Khemmis/Hunted/demo2.mp3",
+		];
+		for path in excluded {
+			assert!(mount_dir.excludes(path), "{path}");
+		}
+
+		let included = [
+			"eaDir",
+			"Tobokegao/@eaDir.mp3",
+			"Tobokegao/lossless masters",
+			"Khemmis/lossless masters.mp3",
+			"demo12.mp3",
+		];
+		for path in included {
+			assert!(!mount_dir.excludes(path), "{path}");
+		}
+	}
 }
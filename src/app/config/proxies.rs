@@ -0,0 +1,109 @@
+use std::{fmt, net::IpAddr, str::FromStr};
+
+use crate::app::Error;
+
+// Range of addresses, like `10.0.0.0/8` or `::1`, whose forwarding headers are trusted
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TrustedProxy {
+	address: IpAddr,
+	prefix_length: u8,
+}
+
+impl TrustedProxy {
+	pub fn contains(&self, ip: IpAddr) -> bool {
+		// Clients connecting over IPv4 to a dual-stack socket appear as IPv4-mapped IPv6 addresses
+		let ip = match ip {
+			IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+			ip => ip,
+		};
+		match (self.address, ip) {
+			(IpAddr::V4(network), IpAddr::V4(ip)) => {
+				let mask = u32::MAX
+					.checked_shl(32 - self.prefix_length as u32)
+					.unwrap_or(0);
+				u32::from(network) & mask == u32::from(ip) & mask
+			}
+			(IpAddr::V6(network), IpAddr::V6(ip)) => {
+				let mask = u128::MAX
+					.checked_shl(128 - self.prefix_length as u32)
+					.unwrap_or(0);
+				u128::from(network) & mask == u128::from(ip) & mask
+			}
+			_ => false,
+		}
+	}
+}
+
+impl FromStr for TrustedProxy {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (address, prefix_length) = match s.trim().split_once('/') {
+			Some((a, p)) => (a, Some(p)),
+			None => (s.trim(), None),
+		};
+		let address = IpAddr::from_str(address).map_err(|_| Error::TrustedProxyInvalid)?;
+		let max_prefix_length = match address {
+			IpAddr::V4(_) => 32,
+			IpAddr::V6(_) => 128,
+		};
+		let prefix_length = match prefix_length.map(u8::from_str) {
+			Some(Ok(p)) if p <= max_prefix_length => p,
+			Some(_) => return Err(Error::TrustedProxyInvalid),
+			None => max_prefix_length,
+		};
+		Ok(Self {
+			address,
+			prefix_length,
+		})
+	}
+}
+
+impl fmt::Display for TrustedProxy {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match (self.address, self.prefix_length) {
+			(IpAddr::V4(a), 32) => write!(f, "{a}"),
+			(IpAddr::V6(a), 128) => write!(f, "{a}"),
+			(a, p) => write!(f, "{a}/{p}"),
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn ip(s: &str) -> IpAddr {
+		IpAddr::from_str(s).unwrap()
+	}
+
+	#[test]
+	fn can_parse_trusted_proxies() {
+		let proxy = TrustedProxy::from_str("10.0.0.0/8").unwrap();
+		assert!(proxy.contains(ip("10.1.2.3")));
+		assert!(!proxy.contains(ip("11.1.2.3")));
+		assert_eq!(proxy.to_string(), "10.0.0.0/8");
+
+		let proxy = TrustedProxy::from_str("127.0.0.1").unwrap();
+		assert!(proxy.contains(ip("127.0.0.1")));
+		assert!(proxy.contains(ip("::ffff:127.0.0.1")));
+		assert!(!proxy.contains(ip("127.0.0.2")));
+		assert_eq!(proxy.to_string(), "127.0.0.1");
+
+		let proxy = TrustedProxy::from_str("fd00::/8").unwrap();
+		assert!(proxy.contains(ip("fd12::1")));
+		assert!(!proxy.contains(ip("10.0.0.1")));
+
+		assert!(TrustedProxy::from_str("0.0.0.0/0")
+			.unwrap()
+			.contains(ip("1.2.3.4")));
+	}
+
+	#[test]
+	fn rejects_invalid_trusted_proxies() {
+		assert!(TrustedProxy::from_str("").is_err());
+		assert!(TrustedProxy::from_str("localhost").is_err());
+		assert!(TrustedProxy::from_str("10.0.0.0/33").is_err());
+		assert!(TrustedProxy::from_str("10.0.0.0/").is_err());
+	}
+}
@@ -1,32 +1,341 @@
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
 
 use serde::{Deserialize, Serialize};
 
+use crate::app::auth::Permission;
+
 #[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
 pub struct User {
 	pub name: String,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub admin: Option<bool>,
 	#[serde(skip_serializing_if = "Option::is_none")]
+	pub guest: Option<bool>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub permissions: Option<Vec<Permission>>,
+	#[serde(skip_serializing_if = "Option::is_none")]
 	pub initial_password: Option<String>,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub hashed_password: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub password_changed_at: Option<u64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub token_generation: Option<u64>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub passkeys: Vec<Passkey>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub stream_quality: Option<StreamQuality>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub transfer_quota: Option<TransferQuota>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub oidc_subject: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Passkey {
+	pub id: String,
+	pub name: String,
+	pub public_key: String,
+	pub created_at: u64,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct StreamQuality {
+	pub max_bitrate_kbps: u32,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub codec: Option<StreamCodec>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub remote_only: Option<bool>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TransferQuota {
+	pub monthly_mb: u64,
+	pub max_bitrate_kbps: u32,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub codec: Option<StreamCodec>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamCodec {
+	#[default]
+	Opus,
+	Mp3,
 }
 
 #[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
 pub struct MountDir {
 	pub source: PathBuf,
 	pub name: String,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub exclude_patterns: Vec<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub symlinks: Option<SymlinkPolicy>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub split_artists: Option<bool>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub remote: Option<Remote>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SymlinkPolicy {
+	// Symlinks to files are indexed, symlinks to directories are skipped
+	#[default]
+	Files,
+	// Symlinks to files and directories are both followed
+	Follow,
+	// Symlinks are skipped
+	Ignore,
+}
+
+// Server holding the files of a mount directory, when they are not on the local file system
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Remote {
+	S3(S3Bucket),
+	Webdav(WebdavShare),
+	Smb(SmbShare),
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct S3Bucket {
+	pub endpoint: String,
+	pub bucket: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub region: Option<String>,
+	pub access_key_id: String,
+	pub secret_access_key: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub path_style: Option<bool>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct WebdavShare {
+	pub url: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub username: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub password: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub password_file: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SmbShare {
+	pub server: String,
+	pub share: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub workgroup: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub username: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub password: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub password_file: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Alias {
+	pub name: String,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub aliases: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Acme {
+	pub domain: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub email: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub staging: Option<bool>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PostScanHook {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub command: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub webhook_url: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Dlna {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub friendly_name: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub user: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Mpd {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub port: Option<u16>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Jukebox {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub device: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtistImageProvider {
+	Local,
+	Deezer,
+	FanartTv,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ArtistImages {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub providers: Option<Vec<ArtistImageProvider>>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub fanart_tv_api_key: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Limits {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub max_connections: Option<usize>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub max_connections_per_ip: Option<usize>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub max_streams_per_user: Option<usize>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub max_request_body_mb: Option<usize>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Auth {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub token_lifetime_hours: Option<u64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub lockout_threshold: Option<u32>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub lockout_minutes: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PasswordPolicy {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub min_length: Option<usize>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub require_mixed_case: Option<bool>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub require_digit: Option<bool>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub require_symbol: Option<bool>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub require_initial_password_change: Option<bool>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Oidc {
+	pub issuer_url: String,
+	pub client_id: String,
+	pub client_secret: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub username_claim: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub admin_group: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub create_users: Option<bool>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Passkeys {
+	pub origin: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub rp_id: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+	ScanCompleted,
+	AlbumAdded,
+	UserCreated,
+	LoginFailed,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Webhook {
+	pub url: String,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub events: Vec<WebhookEvent>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeDetection {
+	#[default]
+	ModificationTime,
+	ContentHash,
+	DirectoryModificationTime,
 }
 
 #[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Config {
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub album_art_pattern: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub base_path: Option<String>,
 	#[serde(default, skip_serializing_if = "Vec::is_empty")]
 	pub mount_dirs: Vec<MountDir>,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub ddns_update_url: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub detect_silence: Option<bool>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub acoustid_fingerprints: Option<bool>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub musicbrainz_enrichment: Option<bool>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub change_detection: Option<ChangeDetection>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub scan_threads: Option<NonZeroUsize>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub scan_schedule: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub filename_pattern: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub artist_separators: Option<Vec<String>>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub acme: Option<Acme>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub post_scan_hook: Option<PostScanHook>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub dlna: Option<Dlna>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub mpd: Option<Mpd>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub jukebox: Option<Jukebox>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub artist_images: Option<ArtistImages>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub limits: Option<Limits>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub auth: Option<Auth>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub password_policy: Option<PasswordPolicy>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub oidc: Option<Oidc>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub passkeys: Option<Passkeys>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub webhooks: Vec<Webhook>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub trusted_proxies: Vec<String>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub artist_aliases: Vec<Alias>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub album_aliases: Vec<Alias>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub genre_aliases: Vec<Alias>,
 	#[serde(default, skip_serializing_if = "Vec::is_empty")]
 	pub users: Vec<User>,
 }
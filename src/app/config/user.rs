@@ -1,20 +1,75 @@
+use rand::distributions::{Alphanumeric, DistString};
+use rand::rngs::OsRng;
+
 use crate::app::{auth, Error};
 
 use super::storage;
 use super::Config;
 
+// Bitrates supported by both the Opus and MP3 encoders
+pub const MIN_STREAM_BITRATE_KBPS: u32 = 8;
+pub const MAX_STREAM_BITRATE_KBPS: u32 = 320;
+
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct User {
 	pub name: String,
 	pub admin: Option<bool>,
+	/// Guests can browse and stream music, but not change anything
+	pub guest: Option<bool>,
+	/// Permissions of users who are not administrators, `auth::Permission::DEFAULT` when unset
+	pub permissions: Option<Vec<auth::Permission>>,
 	pub initial_password: Option<String>,
 	pub hashed_password: String,
+	/// Login tokens issued before this Unix timestamp are rejected
+	pub password_changed_at: Option<u64>,
+	/// Incremented to sign the user out everywhere, tokens from earlier generations are rejected
+	pub token_generation: u64,
+	pub passkeys: Vec<storage::Passkey>,
+	/// Audio streamed to this user is transcoded when this is set
+	pub stream_quality: Option<storage::StreamQuality>,
+	/// Audio streamed to this user is transcoded once they transferred this much in a month
+	pub transfer_quota: Option<storage::TransferQuota>,
+	/// Subject identifier of the OpenID Connect account this user signs in with
+	pub oidc_subject: Option<String>,
 }
 
 impl User {
 	pub fn is_admin(&self) -> bool {
 		self.admin == Some(true)
 	}
+
+	// Administrators are never guests
+	pub fn is_guest(&self) -> bool {
+		self.guest == Some(true) && !self.is_admin()
+	}
+
+	// Caps only apply to clients outside the local network, unless configured otherwise
+	pub fn get_stream_quality(&self, local_client: bool) -> Option<&storage::StreamQuality> {
+		self.stream_quality
+			.as_ref()
+			.filter(|q| !local_client || q.remote_only == Some(false))
+	}
+
+	// Quality of the audio served once the monthly transfer quota is used up, on all networks
+	pub fn get_over_quota_stream_quality(&self) -> Option<storage::StreamQuality> {
+		self.transfer_quota
+			.as_ref()
+			.map(|q| storage::StreamQuality {
+				max_bitrate_kbps: q.max_bitrate_kbps,
+				codec: q.codec,
+				remote_only: Some(false),
+			})
+	}
+
+	pub fn has_permission(&self, permission: auth::Permission) -> bool {
+		if self.is_admin() {
+			return true;
+		}
+		match &self.permissions {
+			Some(permissions) => permissions.contains(&permission),
+			None => auth::Permission::DEFAULT.contains(&permission),
+		}
+	}
 }
 
 impl TryFrom<storage::User> for User {
@@ -27,11 +82,31 @@ impl TryFrom<storage::User> for User {
 			(None, None) => return Err(Error::EmptyPassword),
 		};
 
+		let bitrates = [
+			user.stream_quality.as_ref().map(|q| q.max_bitrate_kbps),
+			user.transfer_quota.as_ref().map(|q| q.max_bitrate_kbps),
+		];
+		if bitrates
+			.into_iter()
+			.flatten()
+			.any(|b| !(MIN_STREAM_BITRATE_KBPS..=MAX_STREAM_BITRATE_KBPS).contains(&b))
+		{
+			return Err(Error::StreamBitrateInvalid);
+		}
+
 		Ok(Self {
 			name: user.name,
 			admin: user.admin,
+			guest: user.guest,
+			permissions: user.permissions,
 			initial_password: user.initial_password,
 			hashed_password,
+			password_changed_at: user.password_changed_at,
+			token_generation: user.token_generation.unwrap_or_default(),
+			passkeys: user.passkeys,
+			stream_quality: user.stream_quality,
+			transfer_quota: user.transfer_quota,
+			oidc_subject: user.oidc_subject,
 		})
 	}
 }
@@ -41,8 +116,16 @@ impl From<User> for storage::User {
 		Self {
 			name: user.name,
 			admin: user.admin,
+			guest: user.guest,
+			permissions: user.permissions,
 			initial_password: user.initial_password,
 			hashed_password: Some(user.hashed_password),
+			password_changed_at: user.password_changed_at,
+			token_generation: (user.token_generation != 0).then_some(user.token_generation),
+			passkeys: user.passkeys,
+			stream_quality: user.stream_quality,
+			transfer_quota: user.transfer_quota,
+			oidc_subject: user.oidc_subject,
 		}
 	}
 }
@@ -73,13 +156,34 @@ impl Config {
 			return Err(Error::DuplicateUsername);
 		}
 
+		self.password_policy.check(password)?;
+		self.add_user(username, password, admin)
+	}
+
+	pub fn register_user(&mut self, username: &str, password: &str) -> Result<(), Error> {
+		self.create_user(username, password, false)?;
+		if let Some(user) = self.get_user_mut(username) {
+			user.password_changed_at = Some(auth::now());
+		}
+		Ok(())
+	}
+
+	fn add_user(&mut self, username: &str, password: &str, admin: bool) -> Result<(), Error> {
 		let password_hash = auth::hash_password(password)?;
 
 		self.users.push(User {
 			name: username.to_owned(),
 			admin: Some(admin),
+			guest: None,
+			permissions: None,
 			initial_password: None,
 			hashed_password: password_hash,
+			password_changed_at: None,
+			token_generation: 0,
+			passkeys: Vec::new(),
+			stream_quality: None,
+			transfer_quota: None,
+			oidc_subject: None,
 		});
 
 		Ok(())
@@ -103,30 +207,127 @@ impl Config {
 		scope: auth::Scope,
 		auth_secret: &auth::Secret,
 	) -> Result<auth::Authorization, Error> {
-		let authorization = auth::decode_auth_token(auth_token, scope, auth_secret)?;
-		if self.exists(&authorization.username) {
-			Ok(authorization)
-		} else {
-			Err(Error::IncorrectUsername)
+		let authorization =
+			auth::decode_auth_token(auth_token, scope, self.auth.token_lifetime, auth_secret)?;
+		let user = self
+			.get_user(&authorization.username)
+			.ok_or(Error::IncorrectUsername)?;
+		if authorization.scope == auth::Scope::PolarisAuth
+			&& user.password_changed_at > authorization.issued_at
+		{
+			return Err(Error::InvalidAuthToken);
+		}
+		if authorization.generation.unwrap_or_default() < user.token_generation {
+			return Err(Error::InvalidAuthToken);
 		}
+		Ok(authorization)
 	}
 
 	pub fn login(
 		&self,
 		username: &str,
 		password: &str,
+		session_id: Option<&str>,
 		auth_secret: &auth::Secret,
 	) -> Result<auth::Token, Error> {
 		let user = self.get_user(username).ok_or(Error::IncorrectUsername)?;
-		if auth::verify_password(&user.hashed_password, password) {
-			let authorization = auth::Authorization {
-				username: username.to_owned(),
-				scope: auth::Scope::PolarisAuth,
-			};
-			auth::generate_auth_token(&authorization, auth_secret)
-		} else {
-			Err(Error::IncorrectPassword)
+		if !auth::verify_password(&user.hashed_password, password) {
+			return Err(Error::IncorrectPassword);
+		}
+		if self.password_policy.require_initial_password_change
+			&& user.password_changed_at.is_none()
+		{
+			return Err(Error::PasswordChangeRequired);
+		}
+		self.generate_auth_token(username, session_id, auth_secret)
+	}
+
+	pub fn generate_auth_token(
+		&self,
+		username: &str,
+		session_id: Option<&str>,
+		auth_secret: &auth::Secret,
+	) -> Result<auth::Token, Error> {
+		let user = self.get_user(username).ok_or(Error::IncorrectUsername)?;
+		let authorization = auth::Authorization {
+			username: username.to_owned(),
+			scope: auth::Scope::PolarisAuth,
+			session_id: session_id.map(str::to_owned),
+			playlist: None,
+			feed_id: None,
+			issued_at: Some(auth::now()),
+			generation: Some(user.token_generation),
+		};
+		auth::generate_auth_token(&authorization, auth_secret)
+	}
+
+	// Maps a user reported by an identity provider to a Polaris user. Missing users are created
+	// with a random password, so they can only sign in through the provider until an
+	// administrator sets one. This password is exempt from the password policy.
+	pub fn sync_external_user(&mut self, username: &str, admin: Option<bool>) -> Result<(), Error> {
+		match self.get_user_mut(username) {
+			Some(user) => {
+				if admin.is_some() {
+					user.admin = admin;
+				}
+				Ok(())
+			}
+			None if username.is_empty() => Err(Error::EmptyUsername),
+			None => {
+				let password = Alphanumeric.sample_string(&mut OsRng, 32);
+				self.add_user(username, &password, admin.unwrap_or_default())
+			}
+		}
+	}
+
+	pub fn get_oidc_user(&self, subject: &str) -> Option<&User> {
+		self.users
+			.iter()
+			.find(|u| u.oidc_subject.as_deref() == Some(subject))
+	}
+
+	// Creates a user bound to an OpenID Connect account. Existing users are never bound
+	// implicitly, since identity providers may let people pick any username.
+	pub fn add_oidc_user(&mut self, username: &str, subject: &str) -> Result<(), Error> {
+		if username.is_empty() {
+			return Err(Error::EmptyUsername);
+		}
+		// Concurrent logins of the same account
+		if self
+			.get_oidc_user(subject)
+			.is_some_and(|u| u.name == username)
+		{
+			return Ok(());
+		}
+		if self.exists(username) {
+			return Err(Error::OidcAccountNotLinked);
 		}
+		let password = Alphanumeric.sample_string(&mut OsRng, 32);
+		self.add_user(username, &password, false)?;
+		if let Some(user) = self.get_user_mut(username) {
+			user.oidc_subject = Some(subject.to_owned());
+		}
+		Ok(())
+	}
+
+	pub fn generate_feed_token(
+		&self,
+		username: &str,
+		playlist: &str,
+		feed_id: &str,
+		auth_secret: &auth::Secret,
+	) -> Result<auth::Token, Error> {
+		let user = self.get_user(username).ok_or(Error::UserNotFound)?;
+		let authorization = auth::Authorization {
+			username: username.to_owned(),
+			scope: auth::Scope::PlaylistFeed,
+			session_id: None,
+			playlist: Some(playlist.to_owned()),
+			feed_id: Some(feed_id.to_owned()),
+			issued_at: Some(auth::now()),
+			generation: Some(user.token_generation),
+		};
+		auth::generate_auth_token(&authorization, auth_secret)
 	}
 
 	pub fn set_is_admin(&mut self, username: &str, is_admin: bool) -> Result<(), Error> {
@@ -135,9 +336,84 @@ impl Config {
 		Ok(())
 	}
 
+	pub fn set_is_guest(&mut self, username: &str, is_guest: bool) -> Result<(), Error> {
+		let user = self.get_user_mut(username).ok_or(Error::UserNotFound)?;
+		user.guest = is_guest.then_some(true);
+		Ok(())
+	}
+
+	// Unset permissions revert to `auth::Permission::DEFAULT`
+	pub fn set_permissions(
+		&mut self,
+		username: &str,
+		permissions: Option<Vec<auth::Permission>>,
+	) -> Result<(), Error> {
+		let user = self.get_user_mut(username).ok_or(Error::UserNotFound)?;
+		user.permissions = permissions.map(|mut p| {
+			p.sort();
+			p.dedup();
+			p
+		});
+		Ok(())
+	}
+
 	pub fn set_password(&mut self, username: &str, password: &str) -> Result<(), Error> {
+		self.password_policy.check(password)?;
 		let user = self.get_user_mut(username).ok_or(Error::UserNotFound)?;
 		user.hashed_password = auth::hash_password(password)?;
+		user.password_changed_at = Some(auth::now());
+		Ok(())
+	}
+
+	// Lets users pick a new password without being signed in, eg. when their initial password
+	// must be changed
+	pub fn change_password(
+		&mut self,
+		username: &str,
+		current_password: &str,
+		new_password: &str,
+	) -> Result<(), Error> {
+		let user = self.get_user(username).ok_or(Error::IncorrectUsername)?;
+		if !auth::verify_password(&user.hashed_password, current_password) {
+			return Err(Error::IncorrectPassword);
+		}
+		if current_password == new_password {
+			return Err(Error::PasswordTooWeak(
+				"must differ from the current password".to_owned(),
+			));
+		}
+		self.set_password(username, new_password)
+	}
+
+	pub fn add_passkey(&mut self, username: &str, passkey: storage::Passkey) -> Result<(), Error> {
+		if self.find_passkey(&passkey.id).is_some() {
+			return Err(Error::PasskeyAlreadyRegistered);
+		}
+		let user = self.get_user_mut(username).ok_or(Error::UserNotFound)?;
+		user.passkeys.push(passkey);
+		Ok(())
+	}
+
+	pub fn delete_passkey(&mut self, username: &str, id: &str) -> Result<(), Error> {
+		let user = self.get_user_mut(username).ok_or(Error::UserNotFound)?;
+		let count = user.passkeys.len();
+		user.passkeys.retain(|p| p.id != id);
+		match user.passkeys.len() == count {
+			true => Err(Error::PasskeyNotFound),
+			false => Ok(()),
+		}
+	}
+
+	// Returns the user owning a passkey, along with the passkey
+	pub fn find_passkey(&self, id: &str) -> Option<(&User, &storage::Passkey)> {
+		self.users
+			.iter()
+			.find_map(|u| u.passkeys.iter().find(|p| p.id == id).map(|p| (u, p)))
+	}
+
+	pub fn revoke_tokens(&mut self, username: &str) -> Result<(), Error> {
+		let user = self.get_user_mut(username).ok_or(Error::UserNotFound)?;
+		user.token_generation += 1;
 		Ok(())
 	}
 
@@ -148,6 +424,7 @@ impl Config {
 
 #[cfg(test)]
 mod test {
+	use crate::app::config::PasswordPolicy;
 	use crate::app::test;
 	use crate::test_name;
 
@@ -185,6 +462,22 @@ mod test {
 		assert_eq!(user_out, user_in);
 	}
 
+	#[test]
+	fn resolves_permissions() {
+		let mut user = User::default();
+		assert!(user.has_permission(auth::Permission::Download));
+		assert!(!user.has_permission(auth::Permission::ManageUsers));
+
+		user.permissions = Some(vec![auth::Permission::TriggerScan]);
+		assert!(user.has_permission(auth::Permission::TriggerScan));
+		assert!(!user.has_permission(auth::Permission::Download));
+
+		user.admin = Some(true);
+		assert!(auth::Permission::ALL
+			.into_iter()
+			.all(|p| user.has_permission(p)));
+	}
+
 	#[tokio::test]
 	async fn create_delete_user_golden_path() {
 		let ctx = test::ContextBuilder::new(test_name!()).build().await;
@@ -213,6 +506,147 @@ mod test {
 		assert!(matches!(result.await.unwrap_err(), Error::EmptyPassword));
 	}
 
+	#[test]
+	fn enforces_password_policy() {
+		let mut config = Config {
+			password_policy: PasswordPolicy {
+				min_length: 8,
+				require_mixed_case: true,
+				require_digit: true,
+				require_symbol: true,
+				..Default::default()
+			},
+			..Default::default()
+		};
+
+		for weak in ["Ab1!", "abcdefg1!", "Abcdefgh!", "Abcdefg12"] {
+			assert!(matches!(
+				config.create_user(TEST_USERNAME, weak, false),
+				Err(Error::PasswordTooWeak(_))
+			));
+		}
+		config
+			.create_user(TEST_USERNAME, "Abcdefg1!", false)
+			.unwrap();
+
+		assert!(matches!(
+			config.set_password(TEST_USERNAME, "password"),
+			Err(Error::PasswordTooWeak(_))
+		));
+		config.set_password(TEST_USERNAME, "Hijklmn2?").unwrap();
+	}
+
+	#[test]
+	fn initial_password_must_be_changed() {
+		let secret = auth::Secret([0; 32]);
+		let mut config = Config {
+			password_policy: PasswordPolicy {
+				require_initial_password_change: true,
+				..Default::default()
+			},
+			..Default::default()
+		};
+		config
+			.set_users(vec![storage::User {
+				name: TEST_USERNAME.to_owned(),
+				initial_password: Some(TEST_PASSWORD.to_owned()),
+				..Default::default()
+			}])
+			.unwrap();
+
+		assert!(matches!(
+			config.login(TEST_USERNAME, TEST_PASSWORD, None, &secret),
+			Err(Error::PasswordChangeRequired)
+		));
+		assert!(matches!(
+			config.change_password(TEST_USERNAME, "not the password", "new password"),
+			Err(Error::IncorrectPassword)
+		));
+		assert!(matches!(
+			config.change_password(TEST_USERNAME, TEST_PASSWORD, TEST_PASSWORD),
+			Err(Error::PasswordTooWeak(_))
+		));
+
+		config
+			.change_password(TEST_USERNAME, TEST_PASSWORD, "new password")
+			.unwrap();
+		assert!(config
+			.login(TEST_USERNAME, "new password", None, &secret)
+			.is_ok());
+	}
+
+	#[test]
+	fn stream_quality_applies_to_remote_clients() {
+		let mut config = Config::default();
+		let quality = storage::StreamQuality {
+			max_bitrate_kbps: 192,
+			codec: Some(storage::StreamCodec::Opus),
+			remote_only: None,
+		};
+		config
+			.set_users(vec![storage::User {
+				name: TEST_USERNAME.to_owned(),
+				initial_password: Some(TEST_PASSWORD.to_owned()),
+				stream_quality: Some(quality.clone()),
+				..Default::default()
+			}])
+			.unwrap();
+		let user = config.get_user(TEST_USERNAME).unwrap();
+		assert_eq!(user.get_stream_quality(false), Some(&quality));
+		assert_eq!(user.get_stream_quality(true), None);
+
+		let mut user = user.clone();
+		user.stream_quality = Some(storage::StreamQuality {
+			remote_only: Some(false),
+			..quality.clone()
+		});
+		assert!(user.get_stream_quality(true).is_some());
+
+		let result = config.set_users(vec![storage::User {
+			name: TEST_USERNAME.to_owned(),
+			initial_password: Some(TEST_PASSWORD.to_owned()),
+			stream_quality: Some(storage::StreamQuality {
+				max_bitrate_kbps: 0,
+				..quality
+			}),
+			..Default::default()
+		}]);
+		assert!(matches!(result, Err(Error::StreamBitrateInvalid)));
+	}
+
+	#[test]
+	fn transfer_quota_bitrate_is_validated() {
+		let mut config = Config::default();
+		let quota = storage::TransferQuota {
+			monthly_mb: 1000,
+			max_bitrate_kbps: 64,
+			codec: None,
+		};
+		let user = storage::User {
+			name: TEST_USERNAME.to_owned(),
+			initial_password: Some(TEST_PASSWORD.to_owned()),
+			transfer_quota: Some(quota.clone()),
+			..Default::default()
+		};
+		config.set_users(vec![user.clone()]).unwrap();
+		let quality = config
+			.get_user(TEST_USERNAME)
+			.unwrap()
+			.get_over_quota_stream_quality()
+			.unwrap();
+		assert_eq!(quality.max_bitrate_kbps, 64);
+		assert_eq!(quality.remote_only, Some(false));
+
+		let result = config.set_users(vec![storage::User {
+			transfer_quota: Some(storage::TransferQuota {
+				max_bitrate_kbps: 1000,
+				..quota
+			}),
+			..user
+		}]);
+		assert!(matches!(result, Err(Error::StreamBitrateInvalid)));
+	}
+
 	#[tokio::test]
 	async fn cannot_create_duplicate_user() {
 		let ctx = test::ContextBuilder::new(test_name!()).build().await;
@@ -239,7 +673,9 @@ mod test {
 			.await
 			.unwrap();
 
-		let result = ctx.config_manager.login(TEST_USERNAME, "not the password");
+		let result = ctx
+			.config_manager
+			.login(TEST_USERNAME, "not the password", None);
 		assert!(matches!(
 			result.await.unwrap_err(),
 			Error::IncorrectPassword
@@ -255,7 +691,7 @@ mod test {
 			.await
 			.unwrap();
 
-		let result = ctx.config_manager.login(TEST_USERNAME, TEST_PASSWORD);
+		let result = ctx.config_manager.login(TEST_USERNAME, TEST_PASSWORD, None);
 		assert!(result.await.is_ok());
 	}
 
@@ -287,7 +723,7 @@ mod test {
 
 		let token = ctx
 			.config_manager
-			.login(TEST_USERNAME, TEST_PASSWORD)
+			.login(TEST_USERNAME, TEST_PASSWORD, None)
 			.await
 			.unwrap();
 
@@ -297,12 +733,234 @@ mod test {
 			.await
 			.unwrap();
 
+		assert!(authorization.issued_at.is_some());
 		assert_eq!(
 			authorization,
 			auth::Authorization {
 				username: TEST_USERNAME.to_owned(),
 				scope: auth::Scope::PolarisAuth,
+				session_id: None,
+				playlist: None,
+				feed_id: None,
+				issued_at: authorization.issued_at,
+				generation: Some(0),
 			}
 		)
 	}
+
+	#[tokio::test]
+	async fn password_change_invalidates_tokens() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USERNAME, TEST_PASSWORD, false)
+			.build()
+			.await;
+
+		let old_token = ctx
+			.config_manager
+			.login(TEST_USERNAME, TEST_PASSWORD, None)
+			.await
+			.unwrap();
+
+		// Timestamps have a resolution of one second
+		tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+		ctx.config_manager
+			.set_password(TEST_USERNAME, "new password")
+			.await
+			.unwrap();
+
+		assert!(matches!(
+			ctx.config_manager
+				.authenticate(&old_token, auth::Scope::PolarisAuth)
+				.await,
+			Err(Error::InvalidAuthToken)
+		));
+
+		let new_token = ctx
+			.config_manager
+			.login(TEST_USERNAME, "new password", None)
+			.await
+			.unwrap();
+		assert!(ctx
+			.config_manager
+			.authenticate(&new_token, auth::Scope::PolarisAuth)
+			.await
+			.is_ok());
+	}
+
+	#[tokio::test]
+	async fn revoking_tokens_signs_user_out_everywhere() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USERNAME, TEST_PASSWORD, false)
+			.build()
+			.await;
+
+		let login_token = ctx
+			.config_manager
+			.login(TEST_USERNAME, TEST_PASSWORD, None)
+			.await
+			.unwrap();
+		let feed_token = ctx
+			.config_manager
+			.generate_feed_token(TEST_USERNAME, "Chill Jazz", "feed_id")
+			.await
+			.unwrap();
+
+		ctx.config_manager
+			.revoke_tokens(TEST_USERNAME)
+			.await
+			.unwrap();
+
+		assert!(matches!(
+			ctx.config_manager
+				.authenticate(&login_token, auth::Scope::PolarisAuth)
+				.await,
+			Err(Error::InvalidAuthToken)
+		));
+		assert!(matches!(
+			ctx.config_manager
+				.authenticate(&feed_token, auth::Scope::PlaylistFeed)
+				.await,
+			Err(Error::InvalidAuthToken)
+		));
+
+		let new_token = ctx
+			.config_manager
+			.login(TEST_USERNAME, TEST_PASSWORD, None)
+			.await
+			.unwrap();
+		assert!(ctx
+			.config_manager
+			.authenticate(&new_token, auth::Scope::PolarisAuth)
+			.await
+			.is_ok());
+		assert_eq!(
+			ctx.config_manager
+				.get_user(TEST_USERNAME)
+				.await
+				.unwrap()
+				.token_generation,
+			1
+		);
+	}
+
+	#[tokio::test]
+	async fn feed_token_is_limited_to_playlist() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USERNAME, TEST_PASSWORD, false)
+			.build()
+			.await;
+
+		let token = ctx
+			.config_manager
+			.generate_feed_token(TEST_USERNAME, "Chill Jazz", "feed_id")
+			.await
+			.unwrap();
+
+		let authorization = ctx
+			.config_manager
+			.authenticate(&token, auth::Scope::PlaylistFeed)
+			.await
+			.unwrap();
+		assert_eq!(authorization.playlist, Some("Chill Jazz".to_owned()));
+		assert_eq!(authorization.feed_id, Some("feed_id".to_owned()));
+
+		assert!(matches!(
+			ctx.config_manager
+				.authenticate(&token, auth::Scope::PolarisAuth)
+				.await,
+			Err(Error::IncorrectAuthorizationScope)
+		));
+	}
+
+	#[tokio::test]
+	async fn external_login_maps_users() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USERNAME, TEST_PASSWORD, false)
+			.build()
+			.await;
+
+		assert!(matches!(
+			ctx.config_manager
+				.login_external("Jesse", None, false, None)
+				.await,
+			Err(Error::IncorrectUsername)
+		));
+
+		let token = ctx
+			.config_manager
+			.login_external("Jesse", Some(true), true, None)
+			.await
+			.unwrap();
+		let authorization = ctx
+			.config_manager
+			.authenticate(&token, auth::Scope::PolarisAuth)
+			.await
+			.unwrap();
+		assert_eq!(authorization.username, "Jesse");
+		assert!(ctx
+			.config_manager
+			.get_user("Jesse")
+			.await
+			.unwrap()
+			.is_admin());
+
+		ctx.config_manager
+			.login_external(TEST_USERNAME, Some(true), false, None)
+			.await
+			.unwrap();
+		let user = ctx.config_manager.get_user(TEST_USERNAME).await.unwrap();
+		assert!(user.is_admin());
+
+		ctx.config_manager
+			.login_external(TEST_USERNAME, None, false, None)
+			.await
+			.unwrap();
+		let user = ctx.config_manager.get_user(TEST_USERNAME).await.unwrap();
+		assert!(user.is_admin());
+	}
+
+	#[tokio::test]
+	async fn oidc_accounts_are_bound_to_subjects() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USERNAME, TEST_PASSWORD, false)
+			.build()
+			.await;
+
+		assert!(matches!(
+			ctx.config_manager
+				.resolve_oidc_user("subject-1", TEST_USERNAME, true)
+				.await,
+			Err(Error::OidcAccountNotLinked)
+		));
+		assert!(matches!(
+			ctx.config_manager
+				.resolve_oidc_user("subject-1", "Jesse", false)
+				.await,
+			Err(Error::IncorrectUsername)
+		));
+
+		let (username, created) = ctx
+			.config_manager
+			.resolve_oidc_user("subject-1", "Jesse", true)
+			.await
+			.unwrap();
+		assert_eq!(username, "Jesse");
+		assert!(created);
+
+		// Renaming the account on the provider does not change the Polaris user
+		let (username, created) = ctx
+			.config_manager
+			.resolve_oidc_user("subject-1", TEST_USERNAME, true)
+			.await
+			.unwrap();
+		assert_eq!(username, "Jesse");
+		assert!(!created);
+
+		assert!(matches!(
+			ctx.config_manager
+				.resolve_oidc_user("subject-2", "Jesse", true)
+				.await,
+			Err(Error::OidcAccountNotLinked)
+		));
+	}
 }
@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rand::distributions::{Alphanumeric, DistString, Slice};
+use rand::rngs::OsRng;
+use rand::Rng;
+
+use crate::app::Error;
+
+// Time users have to approve a device
+pub const LOGIN_TIMEOUT: Duration = Duration::from_secs(600);
+
+// How often devices should check whether their login was approved
+pub const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+// Devices polling sooner than this after their previous attempt are told to slow down.
+// Leaves some slack for devices whose timers are not exact.
+const MIN_POLL_INTERVAL: Duration = Duration::from_secs(4);
+
+// Logins which can be pending at the same time, so unauthenticated devices cannot exhaust memory
+const MAX_PENDING_LOGINS: usize = 1000;
+
+// Letters which cannot be mistaken for one another, or for digits
+const USER_CODE_ALPHABET: &[char] = &[
+	'B', 'C', 'D', 'F', 'G', 'H', 'J', 'K', 'L', 'M', 'N', 'P', 'Q', 'R', 'S', 'T', 'V', 'W', 'X',
+	'Z',
+];
+const USER_CODE_LENGTH: usize = 8;
+
+// Sign in on devices without a convenient keyboard, like TVs or command line tools. The device
+// displays a short code, which users approve from a client where they are already signed in.
+#[derive(Clone, Default)]
+pub struct Manager {
+	pending_logins: Arc<Mutex<PendingLogins>>,
+}
+
+#[derive(Default)]
+struct PendingLogins {
+	by_device_code: HashMap<String, PendingLogin>,
+	// Normalized user codes, and the device code they belong to
+	by_user_code: HashMap<String, String>,
+}
+
+impl PendingLogins {
+	fn remove_expired(&mut self) {
+		self.by_device_code
+			.retain(|_, l| l.started.elapsed() < LOGIN_TIMEOUT);
+		let by_device_code = &self.by_device_code;
+		self.by_user_code
+			.retain(|_, device_code| by_device_code.contains_key(device_code));
+	}
+
+	fn remove(&mut self, device_code: &str) -> Option<PendingLogin> {
+		let login = self.by_device_code.remove(device_code)?;
+		self.by_user_code.remove(&login.user_code);
+		Some(login)
+	}
+}
+
+struct PendingLogin {
+	user_code: String,
+	client_name: Option<String>,
+	approved_by: Option<String>,
+	started: Instant,
+	last_poll: Option<Instant>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceLogin {
+	// Secret used by the device to retrieve its token
+	pub device_code: String,
+	// Code users type to approve the device, formatted as `XXXX-XXXX`
+	pub user_code: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ApprovedLogin {
+	pub username: String,
+	pub client_name: Option<String>,
+}
+
+// Codes are compared without dashes, spaces or case, since users type them by hand
+fn normalize_user_code(user_code: &str) -> String {
+	user_code
+		.chars()
+		.filter(|c| c.is_alphanumeric())
+		.map(|c| c.to_ascii_uppercase())
+		.collect()
+}
+
+fn generate_user_code() -> String {
+	let alphabet = Slice::new(USER_CODE_ALPHABET).unwrap();
+	let code = OsRng
+		.sample_iter(alphabet)
+		.take(USER_CODE_LENGTH)
+		.collect::<String>();
+	let (first, second) = code.split_at(USER_CODE_LENGTH / 2);
+	format!("{first}-{second}")
+}
+
+impl Manager {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn begin_login(&self, client_name: Option<String>) -> Result<DeviceLogin, Error> {
+		let mut pending_logins = self.pending_logins.lock().unwrap();
+		pending_logins.remove_expired();
+		if pending_logins.by_device_code.len() >= MAX_PENDING_LOGINS {
+			return Err(Error::TooManyPendingLogins);
+		}
+
+		let (user_code, normalized) = loop {
+			let user_code = generate_user_code();
+			let normalized = normalize_user_code(&user_code);
+			if !pending_logins.by_user_code.contains_key(&normalized) {
+				break (user_code, normalized);
+			}
+		};
+		let device_code = Alphanumeric.sample_string(&mut OsRng, 32);
+		pending_logins
+			.by_user_code
+			.insert(normalized.clone(), device_code.clone());
+		pending_logins.by_device_code.insert(
+			device_code.clone(),
+			PendingLogin {
+				user_code: normalized,
+				client_name,
+				approved_by: None,
+				started: Instant::now(),
+				last_poll: None,
+			},
+		);
+
+		Ok(DeviceLogin {
+			device_code,
+			user_code,
+		})
+	}
+
+	// Lets the device displaying `user_code` sign in as `username`
+	pub fn approve_login(&self, user_code: &str, username: &str) -> Result<(), Error> {
+		let user_code = normalize_user_code(user_code);
+		let mut pending_logins = self.pending_logins.lock().unwrap();
+		let PendingLogins {
+			by_device_code,
+			by_user_code,
+		} = &mut *pending_logins;
+		let login = by_user_code
+			.get(&user_code)
+			.and_then(|device_code| by_device_code.get_mut(device_code))
+			.filter(|l| l.started.elapsed() < LOGIN_TIMEOUT)
+			.filter(|l| l.approved_by.is_none())
+			.ok_or(Error::DeviceLoginNotFound)?;
+		login.approved_by = Some(username.to_owned());
+		Ok(())
+	}
+
+	// Returns the approved login once, or `None` while users have not approved it yet
+	pub fn poll_login(&self, device_code: &str) -> Result<Option<ApprovedLogin>, Error> {
+		let mut pending_logins = self.pending_logins.lock().unwrap();
+		let login = pending_logins
+			.by_device_code
+			.get_mut(device_code)
+			.filter(|l| l.started.elapsed() < LOGIN_TIMEOUT)
+			.ok_or(Error::DeviceLoginExpired)?;
+		// Approved logins are handed out right away
+		if login.approved_by.is_none() {
+			if login
+				.last_poll
+				.is_some_and(|p| p.elapsed() < MIN_POLL_INTERVAL)
+			{
+				return Err(Error::DeviceLoginSlowDown);
+			}
+			login.last_poll = Some(Instant::now());
+			return Ok(None);
+		}
+		let login = pending_logins.remove(device_code).unwrap();
+		Ok(login.approved_by.map(|username| ApprovedLogin {
+			username,
+			client_name: login.client_name,
+		}))
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	// Pretends the device waited for the poll interval
+	fn rewind_last_poll(manager: &Manager, device_code: &str) {
+		let mut pending_logins = manager.pending_logins.lock().unwrap();
+		let login = pending_logins.by_device_code.get_mut(device_code).unwrap();
+		login.last_poll = login.last_poll.map(|p| p - POLL_INTERVAL);
+	}
+
+	#[test]
+	fn generates_readable_user_codes() {
+		let user_code = generate_user_code();
+		assert_eq!(user_code.len(), USER_CODE_LENGTH + 1);
+		assert_eq!(user_code.chars().nth(USER_CODE_LENGTH / 2), Some('-'));
+		assert!(normalize_user_code(&user_code)
+			.chars()
+			.all(|c| USER_CODE_ALPHABET.contains(&c)));
+	}
+
+	#[test]
+	fn can_approve_device() {
+		let manager = Manager::new();
+		let login = manager
+			.begin_login(Some("Living room TV".to_owned()))
+			.unwrap();
+
+		assert_eq!(manager.poll_login(&login.device_code).unwrap(), None);
+
+		let typed_code = login.user_code.to_lowercase().replace('-', " ");
+		manager.approve_login(&typed_code, "walter").unwrap();
+		assert!(matches!(
+			manager.approve_login(&login.user_code, "jesse"),
+			Err(Error::DeviceLoginNotFound)
+		));
+
+		assert_eq!(
+			manager.poll_login(&login.device_code).unwrap(),
+			Some(ApprovedLogin {
+				username: "walter".to_owned(),
+				client_name: Some("Living room TV".to_owned()),
+			})
+		);
+		assert!(matches!(
+			manager.poll_login(&login.device_code),
+			Err(Error::DeviceLoginExpired)
+		));
+	}
+
+	#[test]
+	fn rejects_unknown_codes() {
+		let manager = Manager::new();
+		manager.begin_login(None).unwrap();
+		assert!(matches!(
+			manager.approve_login("BBBB-BBBB", "walter"),
+			Err(Error::DeviceLoginNotFound)
+		));
+		assert!(matches!(
+			manager.poll_login("not a device code"),
+			Err(Error::DeviceLoginExpired)
+		));
+	}
+
+	#[test]
+	fn devices_polling_too_often_must_slow_down() {
+		let manager = Manager::new();
+		let login = manager.begin_login(None).unwrap();
+		assert_eq!(manager.poll_login(&login.device_code).unwrap(), None);
+		assert!(matches!(
+			manager.poll_login(&login.device_code),
+			Err(Error::DeviceLoginSlowDown)
+		));
+		rewind_last_poll(&manager, &login.device_code);
+		assert_eq!(manager.poll_login(&login.device_code).unwrap(), None);
+	}
+
+	#[test]
+	fn pending_logins_are_capped() {
+		let manager = Manager::new();
+		for _ in 0..MAX_PENDING_LOGINS {
+			manager.begin_login(None).unwrap();
+		}
+		assert!(matches!(
+			manager.begin_login(None),
+			Err(Error::TooManyPendingLogins)
+		));
+	}
+}
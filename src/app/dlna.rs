@@ -0,0 +1,241 @@
+use std::{
+	net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket},
+	path::Path,
+	time::Duration,
+};
+
+use log::{error, info};
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::app::{config, Error};
+
+const SSDP_ADDRESS: Ipv4Addr = Ipv4Addr::new(239, 255, 255, 250);
+const SSDP_PORT: u16 = 1900;
+const MAX_AGE: Duration = Duration::from_secs(30 * 60);
+
+pub const DEVICE_TYPE: &str = "urn:schemas-upnp-org:device:MediaServer:1";
+pub const CONTENT_DIRECTORY: &str = "urn:schemas-upnp-org:service:ContentDirectory:1";
+pub const CONNECTION_MANAGER: &str = "urn:schemas-upnp-org:service:ConnectionManager:1";
+
+// Announces Polaris as a UPnP MediaServer on the local network (SSDP), so DLNA devices like smart TVs
+// can find it. Device descriptions, browsing and playback are served by the HTTP server.
+#[derive(Clone)]
+pub struct Manager {
+	config_manager: config::Manager,
+	device_id: String,
+	port: u16,
+}
+
+impl Manager {
+	pub async fn new(
+		data_dir: &Path,
+		config_manager: config::Manager,
+		port: u16,
+	) -> Result<Self, Error> {
+		let device_id = Self::get_or_create_device_id(&data_dir.join("dlna.uuid")).await?;
+		Ok(Self {
+			config_manager,
+			device_id,
+			port,
+		})
+	}
+
+	// Renderers remember servers by their UUID, so it must not change across restarts
+	async fn get_or_create_device_id(path: &Path) -> Result<String, Error> {
+		match tokio::fs::read_to_string(path).await {
+			Ok(id) if !id.trim().is_empty() => return Ok(id.trim().to_owned()),
+			Ok(_) => (),
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+			Err(e) => return Err(Error::Io(path.to_owned(), e)),
+		};
+
+		let mut bytes: [u8; 16] = rand::random();
+		bytes[6] = (bytes[6] & 0x0f) | 0x40;
+		bytes[8] = (bytes[8] & 0x3f) | 0x80;
+		let n = u128::from_be_bytes(bytes);
+		let id = format!(
+			"{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+			n >> 96,
+			(n >> 80) & 0xffff,
+			(n >> 64) & 0xffff,
+			(n >> 48) & 0xffff,
+			n & 0xffff_ffff_ffff
+		);
+
+		tokio::fs::write(path, &id)
+			.await
+			.map_err(|e| Error::Io(path.to_owned(), e))?;
+		Ok(id)
+	}
+
+	pub fn get_device_id(&self) -> &str {
+		&self.device_id
+	}
+
+	pub async fn get_dlna(&self) -> Option<config::Dlna> {
+		self.config_manager.get_dlna().await
+	}
+
+	pub async fn begin_announcements(&self) {
+		if self.get_dlna().await.is_none() {
+			return;
+		}
+
+		let socket = match bind_ssdp_socket() {
+			Ok(s) => s,
+			Err(e) => {
+				error!("Could not start DLNA discovery: {e}");
+				return;
+			}
+		};
+
+		info!("Announcing DLNA media server on the local network");
+
+		tokio::spawn({
+			let manager = self.clone();
+			async move {
+				let mut buffer = [0; 2048];
+				let mut notify_interval = tokio::time::interval(MAX_AGE / 2);
+				loop {
+					tokio::select! {
+						_ = notify_interval.tick() => {
+							manager.notify_alive(&socket).await;
+						}
+						Ok((length, sender)) = socket.recv_from(&mut buffer) => {
+							let message = String::from_utf8_lossy(&buffer[..length]);
+							manager.answer_search(&socket, &message, sender).await;
+						}
+					}
+				}
+			}
+		});
+	}
+
+	async fn notify_alive(&self, socket: &tokio::net::UdpSocket) {
+		let destination = SocketAddr::V4(SocketAddrV4::new(SSDP_ADDRESS, SSDP_PORT));
+		let Some(location) = self.get_location(destination).await else {
+			return;
+		};
+		for (target, usn) in self.get_targets() {
+			let message = format!(
+				"NOTIFY * HTTP/1.1\r\nHOST: {SSDP_ADDRESS}:{SSDP_PORT}\r\nCACHE-CONTROL: max-age={}\r\nLOCATION: {location}\r\nNT: {target}\r\nNTS: ssdp:alive\r\nSERVER: {}\r\nUSN: {usn}\r\n\r\n",
+				MAX_AGE.as_secs(),
+				server_name(),
+			);
+			socket.send_to(message.as_bytes(), destination).await.ok();
+		}
+	}
+
+	async fn answer_search(
+		&self,
+		socket: &tokio::net::UdpSocket,
+		message: &str,
+		sender: SocketAddr,
+	) {
+		let Some(search_target) = parse_search_target(message) else {
+			return;
+		};
+		let Some(location) = self.get_location(sender).await else {
+			return;
+		};
+		for (target, usn) in self.get_targets() {
+			if search_target != "ssdp:all" && search_target != target {
+				continue;
+			}
+			let message = format!(
+				"HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age={}\r\nEXT:\r\nLOCATION: {location}\r\nSERVER: {}\r\nST: {target}\r\nUSN: {usn}\r\n\r\n",
+				MAX_AGE.as_secs(),
+				server_name(),
+			);
+			socket.send_to(message.as_bytes(), sender).await.ok();
+		}
+	}
+
+	// Notification types along with their unique service names
+	fn get_targets(&self) -> Vec<(String, String)> {
+		let uuid = format!("uuid:{}", self.device_id);
+		let mut targets = vec![(uuid.clone(), uuid.clone())];
+		for target in [
+			"upnp:rootdevice",
+			DEVICE_TYPE,
+			CONTENT_DIRECTORY,
+			CONNECTION_MANAGER,
+		] {
+			targets.push((target.to_owned(), format!("{uuid}::{target}")));
+		}
+		targets
+	}
+
+	// URL of the device description, as reachable from the given address
+	async fn get_location(&self, remote: SocketAddr) -> Option<String> {
+		let local_address = get_local_address(remote)?;
+		let scheme = match self.config_manager.get_acme().await {
+			Some(_) => "https",
+			None => "http",
+		};
+		let base_path = self.config_manager.get_base_path().await;
+		let host = match local_address {
+			IpAddr::V4(a) => a.to_string(),
+			IpAddr::V6(a) => format!("[{a}]"),
+		};
+		Some(format!(
+			"{scheme}://{host}:{}{}/dlna/description.xml",
+			self.port,
+			base_path.unwrap_or_default()
+		))
+	}
+}
+
+fn bind_ssdp_socket() -> std::io::Result<tokio::net::UdpSocket> {
+	// Other media servers on the same machine may also be listening for searches
+	let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+	socket.set_reuse_address(true)?;
+	socket.set_nonblocking(true)?;
+	let address = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, SSDP_PORT);
+	socket.bind(&address.into())?;
+	socket.join_multicast_v4(&SSDP_ADDRESS, &Ipv4Addr::UNSPECIFIED)?;
+	tokio::net::UdpSocket::from_std(socket.into())
+}
+
+// Address of the network interface used to reach a remote address
+fn get_local_address(remote: SocketAddr) -> Option<IpAddr> {
+	let socket = UdpSocket::bind(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0)).ok()?;
+	socket.connect(remote).ok()?;
+	Some(socket.local_addr().ok()?.ip())
+}
+
+fn server_name() -> String {
+	format!(
+		"{}/1.0 UPnP/1.0 Polaris/{}",
+		std::env::consts::OS,
+		env!("CARGO_PKG_VERSION")
+	)
+}
+
+// Reads the search target of `M-SEARCH` requests
+fn parse_search_target(message: &str) -> Option<String> {
+	let mut lines = message.lines();
+	if !lines.next()?.starts_with("M-SEARCH") {
+		return None;
+	}
+	lines.find_map(|line| {
+		let (name, value) = line.split_once(':')?;
+		name.trim()
+			.eq_ignore_ascii_case("ST")
+			.then(|| value.trim().to_owned())
+	})
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn can_parse_search_target() {
+		let message = "M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\nst: urn:schemas-upnp-org:device:MediaServer:1\r\n\r\n";
+		assert_eq!(parse_search_target(message), Some(DEVICE_TYPE.to_owned()));
+
+		let message = "NOTIFY * HTTP/1.1\r\nNT: upnp:rootdevice\r\n\r\n";
+		assert_eq!(parse_search_target(message), None);
+	}
+}
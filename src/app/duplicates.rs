@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+
+use crate::app::{acoustid, index, Error};
+
+// Songs whose durations differ by more than this many seconds are never considered duplicates
+const MAX_DURATION_DIFFERENCE: i64 = 2;
+// Number of leading fingerprint items compared, covering about fifteen seconds of audio
+const FINGERPRINT_COMPARISON_LENGTH: usize = 120;
+// Fraction of differing fingerprint bits under which two songs are considered the same recording
+const MAX_FINGERPRINT_BIT_ERROR_RATE: f64 = 0.1;
+
+// Likely duplicate songs, found by comparing their tags and audio fingerprints
+#[derive(Clone)]
+pub struct Manager {
+	acoustid_manager: acoustid::Manager,
+	index_manager: index::Manager,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reason {
+	// Same title and artists, and a similar duration
+	Tags,
+	// Similar audio content, regardless of tags
+	Fingerprint,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Duplicates {
+	pub reason: Reason,
+	pub songs: Vec<index::Song>,
+}
+
+// Ignores case, punctuation and spacing differences between tags
+fn normalize(s: &str) -> String {
+	s.chars()
+		.filter(|c| c.is_alphanumeric())
+		.flat_map(char::to_lowercase)
+		.collect()
+}
+
+fn tags_key(song: &index::Song) -> Option<(String, String)> {
+	let title = normalize(song.title.as_deref()?);
+	let artists = match song.artists.is_empty() {
+		true => &song.album_artists,
+		false => &song.artists,
+	};
+	let artists = artists.iter().map(|a| normalize(a)).collect::<Vec<_>>();
+	if title.is_empty() || artists.is_empty() {
+		return None;
+	}
+	Some((title, artists.join("\u{c}")))
+}
+
+fn fingerprints_match(a: &[u32], b: &[u32]) -> bool {
+	let length = a.len().min(b.len()).min(FINGERPRINT_COMPARISON_LENGTH);
+	if length == 0 {
+		return false;
+	}
+	let max_errors = (length as f64 * 32.0 * MAX_FINGERPRINT_BIT_ERROR_RATE) as u32;
+	let mut errors = 0;
+	for (x, y) in a.iter().zip(b).take(length) {
+		errors += (x ^ y).count_ones();
+		if errors > max_errors {
+			return false;
+		}
+	}
+	true
+}
+
+// Splits songs sorted by duration into runs of songs with similar durations
+fn group_by_duration(songs: Vec<index::Song>) -> Vec<Vec<index::Song>> {
+	let mut groups: Vec<Vec<index::Song>> = vec![];
+	for song in songs {
+		match groups.last_mut() {
+			Some(group)
+				if group.last().and_then(|s| s.duration).is_some_and(|d| {
+					song.duration
+						.is_some_and(|duration| duration - d <= MAX_DURATION_DIFFERENCE)
+				}) =>
+			{
+				group.push(song)
+			}
+			_ => groups.push(vec![song]),
+		}
+	}
+	groups
+}
+
+fn find_duplicates_by_tags(songs: &[index::Song]) -> Vec<Duplicates> {
+	let mut by_tags = HashMap::<(String, String), Vec<index::Song>>::new();
+	for song in songs.iter().filter(|s| s.duration.is_some()) {
+		if let Some(key) = tags_key(song) {
+			by_tags.entry(key).or_default().push(song.clone());
+		}
+	}
+
+	by_tags
+		.into_values()
+		.flat_map(|mut songs| {
+			songs.sort_by_key(|s| s.duration);
+			group_by_duration(songs)
+		})
+		.filter(|songs| songs.len() > 1)
+		.map(|songs| Duplicates {
+			reason: Reason::Tags,
+			songs,
+		})
+		.collect()
+}
+
+fn find_duplicates_by_fingerprint(
+	songs: &[index::Song],
+	fingerprints: &[acoustid::Fingerprint],
+) -> Vec<Duplicates> {
+	let songs = songs
+		.iter()
+		.map(|s| (&s.virtual_path, s))
+		.collect::<HashMap<_, _>>();
+	let mut candidates = fingerprints
+		.iter()
+		.filter(|f| f.duration.is_some() && !f.data.is_empty())
+		.filter_map(|f| songs.get(&f.virtual_path).map(|s| (f, *s)))
+		.collect::<Vec<_>>();
+	candidates.sort_by_key(|(f, _)| f.duration);
+
+	// Each song joins the group of the first similar song found before it
+	let mut group_of = (0..candidates.len()).collect::<Vec<_>>();
+	for (i, &(fingerprint, song)) in candidates.iter().enumerate() {
+		for (j, &(other_fingerprint, other_song)) in candidates[..i].iter().enumerate().rev() {
+			let duration_difference = fingerprint.duration.unwrap_or_default()
+				- other_fingerprint.duration.unwrap_or_default();
+			if duration_difference > MAX_DURATION_DIFFERENCE {
+				break;
+			}
+			// Songs split from the same file are not duplicates of each other
+			if song.real_path != other_song.real_path
+				&& fingerprints_match(&fingerprint.data, &other_fingerprint.data)
+			{
+				group_of[i] = group_of[j];
+				break;
+			}
+		}
+	}
+
+	let mut groups = HashMap::<usize, Vec<index::Song>>::new();
+	for (i, (_, song)) in candidates.into_iter().enumerate() {
+		groups.entry(group_of[i]).or_default().push(song.clone());
+	}
+
+	groups
+		.into_values()
+		.filter(|songs| songs.len() > 1)
+		.map(|songs| Duplicates {
+			reason: Reason::Fingerprint,
+			songs,
+		})
+		.collect()
+}
+
+fn find_duplicates(
+	songs: &[index::Song],
+	fingerprints: &[acoustid::Fingerprint],
+) -> Vec<Duplicates> {
+	let mut duplicates = find_duplicates_by_tags(songs);
+	duplicates.extend(find_duplicates_by_fingerprint(songs, fingerprints));
+	for group in &mut duplicates {
+		group
+			.songs
+			.sort_by(|a, b| a.virtual_path.cmp(&b.virtual_path));
+	}
+	duplicates.sort_by(|a, b| a.songs[0].virtual_path.cmp(&b.songs[0].virtual_path));
+	duplicates
+}
+
+impl Manager {
+	pub fn new(acoustid_manager: acoustid::Manager, index_manager: index::Manager) -> Self {
+		Self {
+			acoustid_manager,
+			index_manager,
+		}
+	}
+
+	pub async fn get_duplicates(&self) -> Result<Vec<Duplicates>, Error> {
+		let songs = self.index_manager.get_all_songs().await;
+		let fingerprints = self.acoustid_manager.get_fingerprints().await?;
+		Ok(tokio::task::spawn_blocking(move || find_duplicates(&songs, &fingerprints)).await?)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::path::PathBuf;
+
+	use super::*;
+
+	fn make_song(path: &str, title: &str, artist: &str, duration: i64) -> index::Song {
+		index::Song {
+			real_path: PathBuf::from(path),
+			virtual_path: PathBuf::from(path),
+			title: Some(title.to_owned()),
+			artists: vec![artist.to_owned()],
+			duration: Some(duration),
+			..Default::default()
+		}
+	}
+
+	fn make_fingerprint(path: &str, duration: i64, data: Vec<u32>) -> acoustid::Fingerprint {
+		acoustid::Fingerprint {
+			virtual_path: PathBuf::from(path),
+			duration: Some(duration),
+			data,
+		}
+	}
+
+	fn paths(duplicates: &Duplicates) -> Vec<&str> {
+		duplicates
+			.songs
+			.iter()
+			.map(|s| s.virtual_path.to_str().unwrap())
+			.collect()
+	}
+
+	#[test]
+	fn finds_duplicates_by_tags() {
+		let songs = [
+			make_song("flac/destiny.flac", "Destiny", "Stratovarius", 600),
+			make_song("mp3/destiny.mp3", "destiny", "STRATOVARIUS", 601),
+			make_song("live/destiny.mp3", "Destiny", "Stratovarius", 680),
+			make_song("mp3/sos.mp3", "S.O.S.", "Stratovarius", 300),
+		];
+
+		let duplicates = find_duplicates(&songs, &[]);
+		assert_eq!(duplicates.len(), 1);
+		assert_eq!(duplicates[0].reason, Reason::Tags);
+		assert_eq!(
+			paths(&duplicates[0]),
+			vec!["flac/destiny.flac", "mp3/destiny.mp3"]
+		);
+	}
+
+	#[test]
+	fn finds_duplicates_by_fingerprint() {
+		let songs = [
+			make_song("untagged/track01.mp3", "Track 01", "Unknown Artist", 240),
+			make_song("khemmis/candlelight.flac", "Candlelight", "Khemmis", 241),
+			make_song("khemmis/hunted.flac", "Hunted", "Khemmis", 240),
+		];
+		let fingerprints = [
+			make_fingerprint("untagged/track01.mp3", 240, vec![0xF0F0_F0F0; 50]),
+			make_fingerprint("khemmis/candlelight.flac", 241, vec![0xF0F0_F0F1; 50]),
+			make_fingerprint("khemmis/hunted.flac", 240, vec![0x0F0F_0F0F; 50]),
+		];
+
+		let duplicates = find_duplicates(&songs, &fingerprints);
+		assert_eq!(duplicates.len(), 1);
+		assert_eq!(duplicates[0].reason, Reason::Fingerprint);
+		assert_eq!(
+			paths(&duplicates[0]),
+			vec!["khemmis/candlelight.flac", "untagged/track01.mp3"]
+		);
+	}
+}
@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use tokio::sync::{broadcast, Mutex};
+use tokio::task::spawn_blocking;
+
+use crate::app::{config, lyrics, Error};
+
+const CHANNEL_CAPACITY: usize = 64;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+	LyricLine {
+		path: PathBuf,
+		line: lyrics::LyricLine,
+		/// Time at which the following line starts, in milliseconds
+		next_line_start: Option<i64>,
+	},
+}
+
+struct Playback {
+	virtual_path: PathBuf,
+	lyrics: Option<lyrics::SyncedLyrics>,
+	current_line: Option<usize>,
+}
+
+#[derive(Clone)]
+pub struct Manager {
+	config_manager: config::Manager,
+	channels: Arc<Mutex<HashMap<String, broadcast::Sender<Event>>>>,
+	playbacks: Arc<Mutex<HashMap<String, Playback>>>,
+}
+
+impl Manager {
+	pub fn new(config_manager: config::Manager) -> Self {
+		Self {
+			config_manager,
+			channels: Arc::default(),
+			playbacks: Arc::default(),
+		}
+	}
+
+	pub async fn subscribe(&self, username: &str) -> broadcast::Receiver<Event> {
+		let mut channels = self.channels.lock().await;
+		channels.retain(|_, c| c.receiver_count() > 0);
+		channels
+			.entry(username.to_owned())
+			.or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0)
+			.subscribe()
+	}
+
+	async fn send(&self, username: &str, event: Event) {
+		if let Some(channel) = self.channels.lock().await.get(username) {
+			channel.send(event).ok();
+		}
+	}
+
+	pub async fn report_playback(
+		&self,
+		username: &str,
+		virtual_path: &Path,
+		position: i64,
+		want_lyrics: bool,
+	) -> Result<(), Error> {
+		if !want_lyrics {
+			self.playbacks.lock().await.remove(username);
+			return Ok(());
+		}
+
+		let mut playbacks = self.playbacks.lock().await;
+
+		let is_new_song = playbacks
+			.get(username)
+			.is_none_or(|p| p.virtual_path != virtual_path);
+
+		if is_new_song {
+			let real_path = self
+				.config_manager
+				.resolve_virtual_path(virtual_path)
+				.await?;
+			let lyrics = spawn_blocking(move || lyrics::read_synced_lyrics(&real_path)).await??;
+			playbacks.insert(
+				username.to_owned(),
+				Playback {
+					virtual_path: virtual_path.to_owned(),
+					lyrics,
+					current_line: None,
+				},
+			);
+		}
+
+		let Some(playback) = playbacks.get_mut(username) else {
+			return Ok(());
+		};
+
+		let Some(lyrics) = &playback.lyrics else {
+			return Ok(());
+		};
+
+		let line_index = lyrics.line_at(position);
+		if line_index == playback.current_line {
+			return Ok(());
+		}
+		playback.current_line = line_index;
+
+		let Some(line_index) = line_index else {
+			return Ok(());
+		};
+
+		let event = Event::LyricLine {
+			path: playback.virtual_path.clone(),
+			line: lyrics.lines[line_index].clone(),
+			next_line_start: lyrics.lines.get(line_index + 1).map(|l| l.start),
+		};
+
+		drop(playbacks);
+		self.send(username, event).await;
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::path::PathBuf;
+
+	use super::*;
+	use crate::app::test;
+	use crate::test_name;
+
+	const TEST_USER: &str = "test_user";
+	const TEST_MOUNT_NAME: &str = "root";
+
+	#[tokio::test]
+	async fn pushes_lyric_lines() {
+		let builder = test::ContextBuilder::new(test_name!());
+		let test_directory = builder.test_directory.clone();
+		let ctx = builder.build().await;
+
+		let song_path = test_directory.join("song.mp3");
+		std::fs::copy(
+			PathBuf::from_iter(["test-data", "formats", "sample.mp3"]),
+			&song_path,
+		)
+		.unwrap();
+		std::fs::write(
+			test_directory.join("song.lrc"),
+			"[00:01.00]One\n[00:02.00]Two",
+		)
+		.unwrap();
+		ctx.config_manager
+			.set_mounts(vec![config::storage::MountDir {
+				source: test_directory.clone(),
+				name: TEST_MOUNT_NAME.to_owned(),
+				exclude_patterns: vec![],
+				symlinks: None,
+				split_artists: None,
+				remote: None,
+			}])
+			.await
+			.unwrap();
+
+		let virtual_path = PathBuf::from_iter([TEST_MOUNT_NAME, "song.mp3"]);
+		let mut receiver = ctx.events_manager.subscribe(TEST_USER).await;
+
+		for position in [0, 1200, 1500, 2500] {
+			ctx.events_manager
+				.report_playback(TEST_USER, &virtual_path, position, true)
+				.await
+				.unwrap();
+		}
+
+		let Ok(Event::LyricLine {
+			line,
+			next_line_start,
+			..
+		}) = receiver.try_recv()
+		else {
+			panic!();
+		};
+		assert_eq!(line.text, "One");
+		assert_eq!(next_line_start, Some(2000));
+
+		let Ok(Event::LyricLine { line, .. }) = receiver.try_recv() else {
+			panic!();
+		};
+		assert_eq!(line.text, "Two");
+
+		assert!(receiver.try_recv().is_err());
+	}
+}
@@ -0,0 +1,207 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use native_db::*;
+use native_model::{native_model, Model};
+use serde::{Deserialize, Serialize};
+use tokio::task::spawn_blocking;
+
+use crate::app::{auth, ndb, Error};
+
+// Songs, albums and artists starred by each user
+#[derive(Clone)]
+pub struct Manager {
+	db: ndb::Manager,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Item {
+	Song(PathBuf),
+	Album { name: String, artists: Vec<String> },
+	Artist(String),
+}
+
+impl Item {
+	// Identifies the item among the stars of a user
+	fn key(&self) -> String {
+		match self {
+			Item::Song(path) => format!("song:{}", path.to_string_lossy()),
+			Item::Album { name, artists } => format!("album:{}\u{c}{name}", artists.join("\u{c}")),
+			Item::Artist(name) => format!("artist:{name}"),
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Star {
+	pub item: Item,
+	pub starred_at: i64,
+}
+
+pub type StarModel = v1::StarModel;
+type StarModelKey = v1::StarModelKey;
+
+pub mod v1 {
+
+	use super::*;
+
+	#[derive(Clone, Debug, Serialize, Deserialize)]
+	#[native_model(id = 10, version = 1)]
+	#[native_db(primary_key(custom_id -> (&str, &str)))]
+	pub struct StarModel {
+		#[secondary_key]
+		pub username: String,
+		pub key: String,
+		pub item: Item,
+		pub starred_at: i64,
+	}
+
+	impl StarModel {
+		fn custom_id(&self) -> (&str, &str) {
+			(&self.username, &self.key)
+		}
+	}
+}
+
+impl From<StarModel> for Star {
+	fn from(s: StarModel) -> Self {
+		Self {
+			item: s.item,
+			starred_at: s.starred_at,
+		}
+	}
+}
+
+impl Manager {
+	pub fn new(db: ndb::Manager) -> Self {
+		Self { db }
+	}
+
+	// Starring an item again keeps the time at which it was first starred
+	pub async fn star(&self, username: &str, item: Item) -> Result<(), Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			let username = username.to_owned();
+			move || {
+				let key = item.key();
+				let transaction = manager.db.rw_transaction()?;
+				let existing = transaction
+					.get()
+					.primary::<StarModel>((username.as_str(), key.as_str()))?;
+				if existing.is_none() {
+					transaction.insert::<StarModel>(StarModel {
+						username,
+						key,
+						item,
+						starred_at: auth::now() as i64,
+					})?;
+				}
+				transaction.commit()?;
+				Ok(())
+			}
+		})
+		.await?
+	}
+
+	// Unstarring an item which is not starred does nothing
+	pub async fn unstar(&self, username: &str, item: Item) -> Result<(), Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			let username = username.to_owned();
+			move || {
+				let key = item.key();
+				let transaction = manager.db.rw_transaction()?;
+				if let Some(star) = transaction
+					.get()
+					.primary::<StarModel>((username.as_str(), key.as_str()))?
+				{
+					transaction.remove::<StarModel>(star)?;
+				}
+				transaction.commit()?;
+				Ok(())
+			}
+		})
+		.await?
+	}
+
+	// Most recently starred first
+	pub async fn list_stars(&self, username: &str) -> Result<Vec<Star>, Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			let username = username.to_owned();
+			move || {
+				let transaction = manager.db.r_transaction()?;
+				let mut stars = transaction
+					.scan()
+					.secondary::<StarModel>(StarModelKey::username)?
+					.range(username.as_str()..=username.as_str())?
+					.filter_map(|s| s.ok())
+					.map(Star::from)
+					.collect::<Vec<_>>();
+				stars.sort_by_key(|s| std::cmp::Reverse(s.starred_at));
+				Ok(stars)
+			}
+		})
+		.await?
+	}
+
+	pub async fn get_starred_songs(&self, username: &str) -> Result<HashSet<PathBuf>, Error> {
+		let songs = self
+			.list_stars(username)
+			.await?
+			.into_iter()
+			.filter_map(|s| match s.item {
+				Item::Song(path) => Some(path),
+				_ => None,
+			})
+			.collect();
+		Ok(songs)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::app::test;
+	use crate::test_name;
+
+	const TEST_USER: &str = "test_user";
+
+	#[tokio::test]
+	async fn can_star_and_unstar() {
+		let ctx = test::ContextBuilder::new(test_name!()).build().await;
+		let favorites = ctx.favorites_manager;
+
+		let song = Item::Song(PathBuf::from("my_music/destiny.mp3"));
+		let album = Item::Album {
+			name: "Destiny".to_owned(),
+			artists: vec!["Stratovarius".to_owned()],
+		};
+		let artist = Item::Artist("Stratovarius".to_owned());
+
+		favorites.star(TEST_USER, song.clone()).await.unwrap();
+		favorites.star(TEST_USER, song.clone()).await.unwrap();
+		favorites.star(TEST_USER, album.clone()).await.unwrap();
+		favorites.star(TEST_USER, artist.clone()).await.unwrap();
+		favorites.star("other_user", song.clone()).await.unwrap();
+
+		let stars = favorites.list_stars(TEST_USER).await.unwrap();
+		assert_eq!(stars.len(), 3);
+		assert_eq!(
+			favorites.get_starred_songs(TEST_USER).await.unwrap(),
+			HashSet::from([PathBuf::from("my_music/destiny.mp3")])
+		);
+
+		favorites.unstar(TEST_USER, song.clone()).await.unwrap();
+		favorites.unstar(TEST_USER, song).await.unwrap();
+		let items = favorites
+			.list_stars(TEST_USER)
+			.await
+			.unwrap()
+			.into_iter()
+			.map(|s| s.item)
+			.collect::<HashSet<_>>();
+		assert_eq!(items, HashSet::from([album, artist]));
+		assert_eq!(favorites.list_stars("other_user").await.unwrap().len(), 1);
+	}
+}
@@ -1,17 +1,27 @@
 use id3::TagLike;
 use lewton::inside_ogg::OggStreamReader;
-use log::error;
 use std::fs;
-use std::io::{Seek, SeekFrom};
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
 use std::path::Path;
 
-use crate::app::Error;
+use crate::app::{lyrics, Error};
 use crate::utils;
 use crate::utils::AudioFormat;
 
+mod cue;
+mod filename;
+mod headers;
+mod replay_gain;
+
+pub use cue::*;
+pub use filename::*;
+pub use replay_gain::*;
+
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct SongMetadata {
 	pub disc_number: Option<u32>,
+	// Title of the disc within a multi-disc release
+	pub disc_subtitle: Option<String>,
 	pub track_number: Option<u32>,
 	pub title: Option<String>,
 	pub duration: Option<u32>,
@@ -20,35 +30,64 @@ pub struct SongMetadata {
 	pub album: Option<String>,
 	pub year: Option<i32>,
 	pub has_artwork: bool,
+	pub has_lyrics: bool,
 	pub lyricists: Vec<String>,
 	pub composers: Vec<String>,
 	pub genres: Vec<String>,
 	pub labels: Vec<String>,
+	pub compilation: bool,
+	pub conductors: Vec<String>,
+	pub performers: Vec<String>,
+	// Classical work this song is part of, like `Symphony No. 9 in D minor, Op. 125`
+	pub work: Option<String>,
+	pub movement: Option<String>,
+	pub bpm: Option<u32>,
+	// Musical key, in whichever notation the tagging software uses (eg. `Am`, `8A` or `1m`)
+	pub initial_key: Option<String>,
+	pub replay_gain: ReplayGain,
+	pub musicbrainz_recording_id: Option<String>,
+	pub musicbrainz_release_id: Option<String>,
+	pub musicbrainz_artist_ids: Vec<String>,
+}
+
+// Multiple MusicBrainz identifiers are sometimes stored in a single tag value
+fn read_musicbrainz_ids(value: &str) -> Vec<String> {
+	value
+		.split(['\0', '/', ';'])
+		.map(str::trim)
+		.filter(|id| !id.is_empty())
+		.map(str::to_owned)
+		.collect()
+}
+
+// Tempos are sometimes written with decimals (eg. `127.50`)
+fn parse_bpm(value: &str) -> Option<u32> {
+	let bpm = value.trim().replace(',', ".").parse::<f64>().ok()?;
+	(bpm >= 1.0 && bpm.is_finite()).then(|| bpm.round() as u32)
+}
+
+fn parse_initial_key(value: &str) -> Option<String> {
+	Some(value.trim().to_owned()).filter(|k| !k.is_empty())
 }
 
-pub fn read_metadata<P: AsRef<Path>>(path: P) -> Option<SongMetadata> {
+// Returns `None` for files which are not in a supported audio format
+pub fn read_metadata<P: AsRef<Path>>(path: P) -> Result<Option<SongMetadata>, Error> {
 	let data = match utils::get_audio_format(&path) {
-		Some(AudioFormat::AIFF) => read_id3(&path),
+		Some(AudioFormat::AIFF) => read_aiff(&path),
+		Some(AudioFormat::DFF) => read_dsd(&path, headers::read_dff_info),
+		Some(AudioFormat::DSF) => read_dsd(&path, headers::read_dsf_info),
 		Some(AudioFormat::FLAC) => read_flac(&path),
 		Some(AudioFormat::MP3) => read_mp3(&path),
 		Some(AudioFormat::OGG) => read_vorbis(&path),
 		Some(AudioFormat::OPUS) => read_opus(&path),
 		Some(AudioFormat::WAVE) => read_id3(&path),
-		Some(AudioFormat::APE) | Some(AudioFormat::MPC) => read_ape(&path),
+		Some(AudioFormat::APE) => read_ape(&path),
+		Some(AudioFormat::MPC) => read_ape_with_duration(&path, headers::read_musepack_duration),
+		Some(AudioFormat::WAVPACK) => read_ape_with_duration(&path, headers::read_wavpack_duration),
 		Some(AudioFormat::MP4) | Some(AudioFormat::M4B) => read_mp4(&path),
-		None => return None,
+		None => return Ok(None),
 	};
-	match data {
-		Ok(d) => Some(d),
-		Err(e) => {
-			error!(
-				"Error while reading file metadata for '{:?}': {}",
-				path.as_ref(),
-				e
-			);
-			None
-		}
-	}
+	data.map(Some)
 }
 
 trait ID3Ext {
@@ -66,14 +105,19 @@ impl ID3Ext for id3::Tag {
 
 fn read_id3<P: AsRef<Path>>(path: P) -> Result<SongMetadata, Error> {
 	let file = fs::File::open(path.as_ref()).map_err(|e| Error::Io(path.as_ref().to_owned(), e))?;
-	read_id3_from_file(&file, path)
+	read_id3_from(&file, path)
 }
 
-fn read_id3_from_file<P: AsRef<Path>>(file: &fs::File, path: P) -> Result<SongMetadata, Error> {
-	let tag = id3::Tag::read_from2(file)
+fn read_id3_from<R: Read + Seek, P: AsRef<Path>>(
+	reader: R,
+	path: P,
+) -> Result<SongMetadata, Error> {
+	let tag = id3::Tag::read_from2(reader)
 		.or_else(|error| {
 			if let Some(tag) = error.partial_tag {
 				Ok(tag)
+			} else if let id3::ErrorKind::NoTag = error.kind {
+				Ok(id3::Tag::new())
 			} else {
 				Err(error)
 			}
@@ -86,6 +130,7 @@ fn read_id3_from_file<P: AsRef<Path>>(file: &fs::File, path: P) -> Result<SongMe
 	let title = tag.title().map(|s| s.to_string());
 	let duration = tag.duration();
 	let disc_number = tag.disc();
+	let disc_subtitle = tag.get_text_values("TSST").into_iter().next();
 	let track_number = tag.track();
 	let year = tag
 		.year()
@@ -93,13 +138,53 @@ fn read_id3_from_file<P: AsRef<Path>>(file: &fs::File, path: P) -> Result<SongMe
 		.or_else(|| tag.original_date_released().map(|d| d.year))
 		.or_else(|| tag.date_recorded().map(|d| d.year));
 	let has_artwork = tag.pictures().count() > 0;
+	let has_lyrics = tag.lyrics().next().is_some() || tag.synchronised_lyrics().next().is_some();
 	let lyricists = tag.get_text_values("TEXT");
 	let composers = tag.get_text_values("TCOM");
 	let genres = tag.get_text_values("TCON");
 	let labels = tag.get_text_values("TPUB");
+	let compilation = tag.get_text_values("TCMP").iter().any(|v| v == "1");
+	let conductors = tag.get_text_values("TPE3");
+	// Musician credits are listed as instrument and performer pairs
+	let performers = tag
+		.get("TMCL")
+		.and_then(|f| f.content().involved_people_list())
+		.map(|l| l.items.iter().map(|i| i.involvee.clone()).collect())
+		.unwrap_or_default();
+	let work = tag.get_text_values("TIT1").into_iter().next();
+	let movement = tag.get_text_values("MVNM").into_iter().next();
+	let bpm = tag
+		.get_text_values("TBPM")
+		.first()
+		.and_then(|v| parse_bpm(v));
+	let initial_key = tag
+		.get_text_values("TKEY")
+		.first()
+		.and_then(|v| parse_initial_key(v));
+	let mut replay_gain = ReplayGain::default();
+	let mut musicbrainz_release_id = None;
+	let mut musicbrainz_artist_ids = vec![];
+	for text in tag.extended_texts() {
+		let description = &text.description;
+		utils::match_ignore_case! {
+			match description {
+				"MusicBrainz Album Id" => musicbrainz_release_id = Some(text.value.clone()),
+				"MusicBrainz Artist Id" => musicbrainz_artist_ids = read_musicbrainz_ids(&text.value),
+				_ => replay_gain.read_tag(description, &text.value),
+			}
+		}
+	}
+	// Recording identifiers are stored in a unique file identifier frame owned by MusicBrainz
+	let musicbrainz_recording_id = tag.frames().find_map(|f| match f.content() {
+		id3::Content::UniqueFileIdentifier(u) if u.owner_identifier == "http://musicbrainz.org" => {
+			String::from_utf8(u.identifier.clone()).ok()
+		}
+		_ => None,
+	});
 
 	Ok(SongMetadata {
 		disc_number,
+		disc_subtitle,
 		track_number,
 		title,
 		duration,
@@ -108,16 +193,28 @@ fn read_id3_from_file<P: AsRef<Path>>(file: &fs::File, path: P) -> Result<SongMe
 		album,
 		year,
 		has_artwork,
+		has_lyrics,
 		lyricists,
 		composers,
 		genres,
 		labels,
+		compilation,
+		conductors,
+		performers,
+		work,
+		movement,
+		bpm,
+		initial_key,
+		replay_gain,
+		musicbrainz_recording_id,
+		musicbrainz_release_id,
+		musicbrainz_artist_ids,
 	})
 }
 
 fn read_mp3<P: AsRef<Path>>(path: P) -> Result<SongMetadata, Error> {
 	let mut file = fs::File::open(&path).unwrap();
-	let mut metadata = read_id3_from_file(&file, &path)?;
+	let mut metadata = read_id3_from(&file, &path)?;
 	metadata.duration = metadata.duration.or_else(|| {
 		file.seek(SeekFrom::Start(0)).unwrap();
 		mp3_duration::from_file(&file)
@@ -127,6 +224,45 @@ fn read_mp3<P: AsRef<Path>>(path: P) -> Result<SongMetadata, Error> {
 	Ok(metadata)
 }
 
+fn read_aiff<P: AsRef<Path>>(path: P) -> Result<SongMetadata, Error> {
+	let file = fs::File::open(path.as_ref()).map_err(|e| Error::Io(path.as_ref().to_owned(), e))?;
+	let mut metadata = read_id3_from(&file, &path)?;
+	metadata.duration = metadata.duration.or_else(|| {
+		let mut reader = BufReader::new(&file);
+		reader.seek(SeekFrom::Start(0)).ok()?;
+		headers::read_aiff_duration(&mut reader)
+	});
+	Ok(metadata)
+}
+
+// DSD files carry an ID3v2 tag at an offset given by their header
+fn read_dsd<P, F>(path: P, read_info: F) -> Result<SongMetadata, Error>
+where
+	P: AsRef<Path>,
+	F: FnOnce(&mut BufReader<fs::File>) -> Option<headers::DsdInfo>,
+{
+	let file = fs::File::open(path.as_ref()).map_err(|e| Error::Io(path.as_ref().to_owned(), e))?;
+	let mut reader = BufReader::new(file);
+	let info = read_info(&mut reader).unwrap_or(headers::DsdInfo {
+		duration: None,
+		id3_offset: None,
+	});
+
+	let mut metadata = match info.id3_offset {
+		Some(offset) => {
+			let mut tag = Vec::new();
+			reader
+				.seek(SeekFrom::Start(offset))
+				.and_then(|_| reader.read_to_end(&mut tag))
+				.map_err(|e| Error::Io(path.as_ref().to_owned(), e))?;
+			read_id3_from(Cursor::new(tag), &path)?
+		}
+		None => SongMetadata::default(),
+	};
+	metadata.duration = metadata.duration.or(info.duration);
+	Ok(metadata)
+}
+
 mod ape_ext {
 	use regex::Regex;
 	use std::sync::LazyLock;
@@ -170,11 +306,45 @@ fn read_ape<P: AsRef<Path>>(path: P) -> Result<SongMetadata, Error> {
 	let title = tag.item("Title").and_then(ape_ext::read_string);
 	let year = tag.item("Year").and_then(ape_ext::read_i32);
 	let disc_number = tag.item("Disc").and_then(ape_ext::read_x_of_y);
+	let disc_subtitle = tag.item("DiscSubtitle").and_then(ape_ext::read_string);
 	let track_number = tag.item("Track").and_then(ape_ext::read_x_of_y);
 	let lyricists = ape_ext::read_strings(tag.item("LYRICIST"));
 	let composers = ape_ext::read_strings(tag.item("COMPOSER"));
 	let genres = ape_ext::read_strings(tag.item("GENRE"));
 	let labels = ape_ext::read_strings(tag.item("PUBLISHER"));
+	let compilation = tag
+		.item("Compilation")
+		.and_then(ape_ext::read_string)
+		.is_some_and(|c| c == "1");
+	let conductors = ape_ext::read_strings(tag.item("CONDUCTOR"));
+	let performers = ape_ext::read_strings(tag.item("PERFORMER"));
+	let work = tag.item("WORK").and_then(ape_ext::read_string);
+	let movement = tag.item("MOVEMENTNAME").and_then(ape_ext::read_string);
+	let bpm = tag
+		.item("BPM")
+		.and_then(ape_ext::read_string)
+		.and_then(|v| parse_bpm(&v));
+	let initial_key = tag
+		.item("INITIALKEY")
+		.or_else(|| tag.item("KEY"))
+		.and_then(ape_ext::read_string)
+		.and_then(|v| parse_initial_key(&v));
+	let musicbrainz_recording_id = tag
+		.item("MUSICBRAINZ_TRACKID")
+		.and_then(ape_ext::read_string);
+	let musicbrainz_release_id = tag
+		.item("MUSICBRAINZ_ALBUMID")
+		.and_then(ape_ext::read_string);
+	let musicbrainz_artist_ids = ape_ext::read_strings(tag.item("MUSICBRAINZ_ARTISTID"))
+		.iter()
+		.flat_map(|v| read_musicbrainz_ids(v))
+		.collect();
+	let mut replay_gain = ReplayGain::default();
+	for key in ReplayGain::KEYS {
+		if let Some(value) = tag.item(key).and_then(ape_ext::read_string) {
+			replay_gain.read_tag(key, &value);
+		}
+	}
 	Ok(SongMetadata {
 		artists,
 		album_artists,
@@ -182,13 +352,45 @@ fn read_ape<P: AsRef<Path>>(path: P) -> Result<SongMetadata, Error> {
 		title,
 		duration: None,
 		disc_number,
+		disc_subtitle,
 		track_number,
 		year,
-		has_artwork: false,
+		has_artwork: tag.item("Cover Art (Front)").is_some(),
+		has_lyrics: tag.item("Lyrics").is_some(),
 		lyricists,
 		composers,
 		genres,
 		labels,
+		compilation,
+		conductors,
+		performers,
+		work,
+		movement,
+		bpm,
+		initial_key,
+		replay_gain,
+		musicbrainz_recording_id,
+		musicbrainz_release_id,
+		musicbrainz_artist_ids,
+	})
+}
+
+// Formats using APEv2 tags, whose duration comes from their own stream header
+fn read_ape_with_duration<P, F>(path: P, read_duration: F) -> Result<SongMetadata, Error>
+where
+	P: AsRef<Path>,
+	F: FnOnce(&mut BufReader<fs::File>) -> Option<u32>,
+{
+	let file = fs::File::open(path.as_ref()).map_err(|e| Error::Io(path.as_ref().to_owned(), e))?;
+	let duration = read_duration(&mut BufReader::new(file));
+	let metadata = match read_ape(&path) {
+		Ok(metadata) => metadata,
+		Err(Error::Ape(ape::Error::TagNotFound)) => SongMetadata::default(),
+		Err(e) => return Err(e),
+	};
+	Ok(SongMetadata {
+		duration,
+		..metadata
 	})
 }
 
@@ -206,12 +408,27 @@ fn read_vorbis<P: AsRef<Path>>(path: P) -> Result<SongMetadata, Error> {
 				"ALBUMARTIST" => metadata.album_artists.push(value),
 				"TRACKNUMBER" => metadata.track_number = value.parse::<u32>().ok(),
 				"DISCNUMBER" => metadata.disc_number = value.parse::<u32>().ok(),
+				"DISCSUBTITLE" => metadata.disc_subtitle = Some(value),
 				"DATE" => metadata.year = value.parse::<i32>().ok(),
 				"LYRICIST" => metadata.lyricists.push(value),
 				"COMPOSER" => metadata.composers.push(value),
 				"GENRE" => metadata.genres.push(value),
 				"PUBLISHER" => metadata.labels.push(value),
-				_ => (),
+				"COMPILATION" => metadata.compilation = value == "1",
+				"CONDUCTOR" => metadata.conductors.push(value),
+				"PERFORMER" => metadata.performers.push(value),
+				"WORK" => metadata.work = Some(value),
+				"MOVEMENTNAME" => metadata.movement = Some(value),
+				"BPM" => metadata.bpm = parse_bpm(&value),
+				"INITIALKEY" => metadata.initial_key = parse_initial_key(&value),
+				"KEY" => metadata.initial_key = parse_initial_key(&value),
+				"LYRICS" => metadata.has_lyrics = true,
+				"UNSYNCEDLYRICS" => metadata.has_lyrics = true,
+				"METADATA_BLOCK_PICTURE" => metadata.has_artwork = true,
+				"MUSICBRAINZ_TRACKID" => metadata.musicbrainz_recording_id = Some(value),
+				"MUSICBRAINZ_ALBUMID" => metadata.musicbrainz_release_id = Some(value),
+				"MUSICBRAINZ_ARTISTID" => metadata.musicbrainz_artist_ids.extend(read_musicbrainz_ids(&value)),
+				_ => metadata.replay_gain.read_tag(&key, &value),
 			}
 		}
 	}
@@ -232,12 +449,27 @@ fn read_opus<P: AsRef<Path>>(path: P) -> Result<SongMetadata, Error> {
 				"ALBUMARTIST" => metadata.album_artists.push(value),
 				"TRACKNUMBER" => metadata.track_number = value.parse::<u32>().ok(),
 				"DISCNUMBER" => metadata.disc_number = value.parse::<u32>().ok(),
+				"DISCSUBTITLE" => metadata.disc_subtitle = Some(value),
 				"DATE" => metadata.year = value.parse::<i32>().ok(),
 				"LYRICIST" => metadata.lyricists.push(value),
 				"COMPOSER" => metadata.composers.push(value),
 				"GENRE" => metadata.genres.push(value),
 				"PUBLISHER" => metadata.labels.push(value),
-				_ => (),
+				"COMPILATION" => metadata.compilation = value == "1",
+				"CONDUCTOR" => metadata.conductors.push(value),
+				"PERFORMER" => metadata.performers.push(value),
+				"WORK" => metadata.work = Some(value),
+				"MOVEMENTNAME" => metadata.movement = Some(value),
+				"BPM" => metadata.bpm = parse_bpm(&value),
+				"INITIALKEY" => metadata.initial_key = parse_initial_key(&value),
+				"KEY" => metadata.initial_key = parse_initial_key(&value),
+				"LYRICS" => metadata.has_lyrics = true,
+				"UNSYNCEDLYRICS" => metadata.has_lyrics = true,
+				"METADATA_BLOCK_PICTURE" => metadata.has_artwork = true,
+				"MUSICBRAINZ_TRACKID" => metadata.musicbrainz_recording_id = Some(value),
+				"MUSICBRAINZ_ALBUMID" => metadata.musicbrainz_release_id = Some(value),
+				"MUSICBRAINZ_ARTISTID" => metadata.musicbrainz_artist_ids.extend(read_musicbrainz_ids(&value)),
+				_ => metadata.replay_gain.read_tag(&key, &value),
 			}
 		}
 	}
@@ -264,6 +496,13 @@ fn read_flac<P: AsRef<Path>>(path: P) -> Result<SongMetadata, Error> {
 
 	let multivalue = |o: Option<&Vec<String>>| o.cloned().unwrap_or_default();
 
+	let mut replay_gain = ReplayGain::default();
+	for (key, values) in &vorbis.comments {
+		for value in values {
+			replay_gain.read_tag(key, value);
+		}
+	}
+
 	Ok(SongMetadata {
 		artists: multivalue(vorbis.artist()),
 		album_artists: multivalue(vorbis.album_artist()),
@@ -271,13 +510,34 @@ fn read_flac<P: AsRef<Path>>(path: P) -> Result<SongMetadata, Error> {
 		title: vorbis.title().map(|v| v[0].clone()),
 		duration,
 		disc_number,
+		disc_subtitle: vorbis.get("DISCSUBTITLE").map(|v| v[0].clone()),
 		track_number: vorbis.track(),
 		year,
 		has_artwork,
+		has_lyrics: lyrics::LYRICS_COMMENTS
+			.iter()
+			.any(|key| vorbis.get(key).is_some()),
 		lyricists: multivalue(vorbis.get("LYRICIST")),
 		composers: multivalue(vorbis.get("COMPOSER")),
 		genres: multivalue(vorbis.get("GENRE")),
 		labels: multivalue(vorbis.get("PUBLISHER")),
+		compilation: multivalue(vorbis.get("COMPILATION")).contains(&"1".to_owned()),
+		conductors: multivalue(vorbis.get("CONDUCTOR")),
+		performers: multivalue(vorbis.get("PERFORMER")),
+		work: vorbis.get("WORK").map(|v| v[0].clone()),
+		movement: vorbis.get("MOVEMENTNAME").map(|v| v[0].clone()),
+		bpm: vorbis.get("BPM").and_then(|v| parse_bpm(&v[0])),
+		initial_key: vorbis
+			.get("INITIALKEY")
+			.or_else(|| vorbis.get("KEY"))
+			.and_then(|v| parse_initial_key(&v[0])),
+		replay_gain,
+		musicbrainz_recording_id: vorbis.get("MUSICBRAINZ_TRACKID").map(|v| v[0].clone()),
+		musicbrainz_release_id: vorbis.get("MUSICBRAINZ_ALBUMID").map(|v| v[0].clone()),
+		musicbrainz_artist_ids: multivalue(vorbis.get("MUSICBRAINZ_ARTISTID"))
+			.iter()
+			.flat_map(|v| read_musicbrainz_ids(v))
+			.collect(),
 	})
 }
 
@@ -290,6 +550,31 @@ fn read_mp4<P: AsRef<Path>>(path: P) -> Result<SongMetadata, Error> {
 	let mut tag = mp4ameta::Tag::read_with_path(&path, &cfg)
 		.map_err(|e| Error::Mp4aMeta(path.as_ref().to_owned(), e))?;
 	let label_ident = mp4ameta::FreeformIdent::new_static("com.apple.iTunes", "LABEL");
+	let conductor_ident = mp4ameta::FreeformIdent::new_static("com.apple.iTunes", "CONDUCTOR");
+	let performer_ident = mp4ameta::FreeformIdent::new_static("com.apple.iTunes", "PERFORMER");
+	let disc_subtitle_ident =
+		mp4ameta::FreeformIdent::new_static("com.apple.iTunes", "DISCSUBTITLE");
+	let recording_id_ident =
+		mp4ameta::FreeformIdent::new_static("com.apple.iTunes", "MusicBrainz Track Id");
+	let release_id_ident =
+		mp4ameta::FreeformIdent::new_static("com.apple.iTunes", "MusicBrainz Album Id");
+	let artist_id_ident =
+		mp4ameta::FreeformIdent::new_static("com.apple.iTunes", "MusicBrainz Artist Id");
+	let initial_key_ident = mp4ameta::FreeformIdent::new_static("com.apple.iTunes", "initialkey");
+	let mut replay_gain = ReplayGain::default();
+	for key in ReplayGain::KEYS {
+		// Freeform names are case sensitive, and usually written in lowercase
+		let lowercase_key = key.to_lowercase();
+		let idents = [
+			mp4ameta::FreeformIdent::new_static("com.apple.iTunes", key),
+			mp4ameta::FreeformIdent::new("com.apple.iTunes", &lowercase_key),
+		];
+		for ident in idents {
+			if let Some(value) = tag.strings_of(&ident).next() {
+				replay_gain.read_tag(key, value);
+			}
+		}
+	}
 
 	Ok(SongMetadata {
 		artists: tag.take_artists().collect(),
@@ -298,13 +583,32 @@ fn read_mp4<P: AsRef<Path>>(path: P) -> Result<SongMetadata, Error> {
 		title: tag.take_title(),
 		duration: Some(tag.duration().as_secs() as u32),
 		disc_number: tag.disc_number().map(|d| d as u32),
+		disc_subtitle: tag.take_strings_of(&disc_subtitle_ident).next(),
 		track_number: tag.track_number().map(|d| d as u32),
 		year: tag.year().and_then(|v| v.parse::<i32>().ok()),
 		has_artwork: tag.artwork().is_some(),
+		has_lyrics: tag.lyrics().is_some(),
 		lyricists: tag.take_lyricists().collect(),
 		composers: tag.take_composers().collect(),
 		genres: tag.take_genres().collect(),
 		labels: tag.take_strings_of(&label_ident).collect(),
+		compilation: tag.compilation(),
+		conductors: tag.take_strings_of(&conductor_ident).collect(),
+		performers: tag.take_strings_of(&performer_ident).collect(),
+		work: tag.take_work(),
+		movement: tag.take_movement(),
+		bpm: tag.bpm().map(u32::from).filter(|b| *b > 0),
+		initial_key: tag
+			.take_strings_of(&initial_key_ident)
+			.next()
+			.and_then(|v| parse_initial_key(&v)),
+		replay_gain,
+		musicbrainz_recording_id: tag.take_strings_of(&recording_id_ident).next(),
+		musicbrainz_release_id: tag.take_strings_of(&release_id_ident).next(),
+		musicbrainz_artist_ids: tag
+			.take_strings_of(&artist_id_ident)
+			.flat_map(|v| read_musicbrainz_ids(&v))
+			.collect(),
 	})
 }
 
@@ -312,6 +616,7 @@ fn read_mp4<P: AsRef<Path>>(path: P) -> Result<SongMetadata, Error> {
 fn reads_file_metadata() {
 	let expected_without_duration = SongMetadata {
 		disc_number: Some(3),
+		disc_subtitle: None,
 		track_number: Some(1),
 		title: Some("TEST TITLE".into()),
 		artists: vec!["TEST ARTIST".into()],
@@ -320,73 +625,131 @@ fn reads_file_metadata() {
 		duration: None,
 		year: Some(2016),
 		has_artwork: false,
+		has_lyrics: false,
 		lyricists: vec!["TEST LYRICIST".into()],
 		composers: vec!["TEST COMPOSER".into()],
 		genres: vec!["TEST GENRE".into()],
 		labels: vec!["TEST LABEL".into()],
+		compilation: false,
+		conductors: vec![],
+		performers: vec![],
+		work: None,
+		movement: None,
+		bpm: None,
+		initial_key: None,
+		replay_gain: Default::default(),
+		musicbrainz_recording_id: None,
+		musicbrainz_release_id: None,
+		musicbrainz_artist_ids: vec![],
 	};
 	let expected_with_duration = SongMetadata {
 		duration: Some(0),
 		..expected_without_duration.clone()
 	};
 	assert_eq!(
-		read_metadata(Path::new("test-data/formats/sample.aif")).unwrap(),
-		expected_without_duration
+		read_metadata(Path::new("test-data/formats/sample.aif"))
+			.unwrap()
+			.unwrap(),
+		SongMetadata {
+			duration: Some(2),
+			..expected_without_duration.clone()
+		}
 	);
 	assert_eq!(
-		read_metadata(Path::new("test-data/formats/sample.mp3")).unwrap(),
+		read_metadata(Path::new("test-data/formats/sample.mp3"))
+			.unwrap()
+			.unwrap(),
 		expected_with_duration
 	);
 	assert_eq!(
-		read_metadata(Path::new("test-data/formats/sample.ogg")).unwrap(),
+		read_metadata(Path::new("test-data/formats/sample.ogg"))
+			.unwrap()
+			.unwrap(),
 		expected_without_duration
 	);
 	assert_eq!(
-		read_metadata(Path::new("test-data/formats/sample.flac")).unwrap(),
+		read_metadata(Path::new("test-data/formats/sample.flac"))
+			.unwrap()
+			.unwrap(),
 		expected_with_duration
 	);
 	assert_eq!(
-		read_metadata(Path::new("test-data/formats/sample.m4a")).unwrap(),
+		read_metadata(Path::new("test-data/formats/sample.m4a"))
+			.unwrap()
+			.unwrap(),
 		expected_with_duration
 	);
 	assert_eq!(
-		read_metadata(Path::new("test-data/formats/sample.opus")).unwrap(),
+		read_metadata(Path::new("test-data/formats/sample.opus"))
+			.unwrap()
+			.unwrap(),
 		expected_without_duration
 	);
 	assert_eq!(
-		read_metadata(Path::new("test-data/formats/sample.ape")).unwrap(),
+		read_metadata(Path::new("test-data/formats/sample.ape"))
+			.unwrap()
+			.unwrap(),
 		expected_without_duration
 	);
 	assert_eq!(
-		read_metadata(Path::new("test-data/formats/sample.wav")).unwrap(),
+		read_metadata(Path::new("test-data/formats/sample.wav"))
+			.unwrap()
+			.unwrap(),
 		expected_without_duration
 	);
 }
 
+#[test]
+fn reads_multiple_musicbrainz_ids_from_one_value() {
+	assert_eq!(
+		read_musicbrainz_ids(
+			"a74b1b7f-71a5-4011-9441-d0b5e4122711/ 8bfac288-ccc5-448d-9573-c33ea2aa5c30"
+		),
+		vec![
+			"a74b1b7f-71a5-4011-9441-d0b5e4122711".to_owned(),
+			"8bfac288-ccc5-448d-9573-c33ea2aa5c30".to_owned(),
+		]
+	);
+}
+
+#[test]
+fn reads_bpm_values() {
+	assert_eq!(parse_bpm("128"), Some(128));
+	assert_eq!(parse_bpm(" 127.50 "), Some(128));
+	assert_eq!(parse_bpm("93,4"), Some(93));
+	assert_eq!(parse_bpm("0"), None);
+	assert_eq!(parse_bpm("fast"), None);
+}
+
 #[test]
 fn reads_embedded_artwork() {
 	assert!(
 		read_metadata(Path::new("test-data/artwork/sample.aif"))
+			.unwrap()
 			.unwrap()
 			.has_artwork
 	);
 	assert!(
 		read_metadata(Path::new("test-data/artwork/sample.mp3"))
+			.unwrap()
 			.unwrap()
 			.has_artwork
 	);
 	assert!(
 		read_metadata(Path::new("test-data/artwork/sample.flac"))
+			.unwrap()
 			.unwrap()
 			.has_artwork
 	);
 	assert!(
 		read_metadata(Path::new("test-data/artwork/sample.m4a"))
+			.unwrap()
 			.unwrap()
 			.has_artwork
 	);
 	assert!(
 		read_metadata(Path::new("test-data/artwork/sample.wav"))
+			.unwrap()
 			.unwrap()
 			.has_artwork
 	);
@@ -396,6 +759,7 @@ fn reads_embedded_artwork() {
 fn reads_multivalue_fields() {
 	let expected_without_duration = SongMetadata {
 		disc_number: Some(3),
+		disc_subtitle: None,
 		track_number: Some(1),
 		title: Some("TEST TITLE".into()),
 		artists: vec!["TEST ARTIST".into(), "OTHER ARTIST".into()],
@@ -404,42 +768,71 @@ fn reads_multivalue_fields() {
 		duration: None,
 		year: Some(2016),
 		has_artwork: false,
+		has_lyrics: false,
 		lyricists: vec!["TEST LYRICIST".into(), "OTHER LYRICIST".into()],
 		composers: vec!["TEST COMPOSER".into(), "OTHER COMPOSER".into()],
 		genres: vec!["TEST GENRE".into(), "OTHER GENRE".into()],
 		labels: vec!["TEST LABEL".into(), "OTHER LABEL".into()],
+		compilation: false,
+		conductors: vec![],
+		performers: vec![],
+		work: None,
+		movement: None,
+		bpm: None,
+		initial_key: None,
+		replay_gain: Default::default(),
+		musicbrainz_recording_id: None,
+		musicbrainz_release_id: None,
+		musicbrainz_artist_ids: vec![],
 	};
 	let expected_with_duration = SongMetadata {
 		duration: Some(0),
 		..expected_without_duration.clone()
 	};
 	assert_eq!(
-		read_metadata(Path::new("test-data/multivalue/multivalue.aif")).unwrap(),
-		expected_without_duration
+		read_metadata(Path::new("test-data/multivalue/multivalue.aif"))
+			.unwrap()
+			.unwrap(),
+		SongMetadata {
+			duration: Some(2),
+			..expected_without_duration.clone()
+		}
 	);
 	assert_eq!(
-		read_metadata(Path::new("test-data/multivalue/multivalue.mp3")).unwrap(),
+		read_metadata(Path::new("test-data/multivalue/multivalue.mp3"))
+			.unwrap()
+			.unwrap(),
 		expected_with_duration
 	);
 	assert_eq!(
-		read_metadata(Path::new("test-data/multivalue/multivalue.ogg")).unwrap(),
+		read_metadata(Path::new("test-data/multivalue/multivalue.ogg"))
+			.unwrap()
+			.unwrap(),
 		expected_without_duration
 	);
 	assert_eq!(
-		read_metadata(Path::new("test-data/multivalue/multivalue.flac")).unwrap(),
+		read_metadata(Path::new("test-data/multivalue/multivalue.flac"))
+			.unwrap()
+			.unwrap(),
 		expected_with_duration
 	);
 	// TODO Test m4a support (likely working). Pending https://tickets.metabrainz.org/browse/PICARD-3029
 	assert_eq!(
-		read_metadata(Path::new("test-data/multivalue/multivalue.opus")).unwrap(),
+		read_metadata(Path::new("test-data/multivalue/multivalue.opus"))
+			.unwrap()
+			.unwrap(),
 		expected_without_duration
 	);
 	assert_eq!(
-		read_metadata(Path::new("test-data/multivalue/multivalue.ape")).unwrap(),
+		read_metadata(Path::new("test-data/multivalue/multivalue.ape"))
+			.unwrap()
+			.unwrap(),
 		expected_without_duration
 	);
 	assert_eq!(
-		read_metadata(Path::new("test-data/multivalue/multivalue.wav")).unwrap(),
+		read_metadata(Path::new("test-data/multivalue/multivalue.wav"))
+			.unwrap()
+			.unwrap(),
 		expected_without_duration
 	);
 }
@@ -0,0 +1,230 @@
+use std::path::Path;
+
+use crate::app::Error;
+
+// CUE sheet positions are expressed in frames, of which there are 75 per second
+const FRAMES_PER_SECOND: i64 = 75;
+
+// Album described by a `.cue` file, usually accompanying a single-file rip
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CueSheet {
+	pub title: Option<String>,
+	pub performer: Option<String>,
+	pub songwriter: Option<String>,
+	pub genre: Option<String>,
+	pub year: Option<i64>,
+	pub tracks: Vec<CueTrack>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CueTrack {
+	// Audio file containing this track, relative to the CUE sheet
+	pub file: String,
+	pub number: u32,
+	pub title: Option<String>,
+	pub performer: Option<String>,
+	pub songwriter: Option<String>,
+	// Offset of the track within its file, in milliseconds
+	pub start: i64,
+}
+
+// Part of an audio file holding a single song, in milliseconds
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Segment {
+	pub start: i64,
+	// Songs without an end play until the end of the file
+	pub end: Option<i64>,
+}
+
+impl CueSheet {
+	pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+		let bytes =
+			std::fs::read(path.as_ref()).map_err(|e| Error::Io(path.as_ref().to_owned(), e))?;
+		Self::parse(&String::from_utf8_lossy(&bytes))
+			.ok_or_else(|| Error::CueSheetInvalid(path.as_ref().to_owned()))
+	}
+
+	fn parse(content: &str) -> Option<Self> {
+		let mut sheet = CueSheet::default();
+		let mut file = None;
+		let mut track: Option<CueTrack> = None;
+
+		for line in content.trim_start_matches('\u{feff}').lines() {
+			let mut arguments = tokenize(line).into_iter();
+			let Some(command) = arguments.next() else {
+				continue;
+			};
+			let command = command.to_uppercase();
+			let argument = arguments.next();
+			match command.as_str() {
+				"FILE" => {
+					sheet.tracks.extend(track.take());
+					file = Some(argument?);
+					continue;
+				}
+				"TRACK" => {
+					sheet.tracks.extend(track.take());
+					track = Some(CueTrack {
+						file: file.clone()?,
+						number: argument?.parse().ok()?,
+						start: -1,
+						..Default::default()
+					});
+					continue;
+				}
+				_ => (),
+			}
+			match (command.as_str(), &mut track) {
+				("INDEX", Some(track)) => {
+					let index = argument?.parse::<u32>().ok()?;
+					let position = parse_position(&arguments.next()?)?;
+					// Index 0 marks the pregap, which belongs to the previous track
+					if index == 1 || (index > 1 && track.start < 0) {
+						track.start = position;
+					}
+				}
+				("TITLE", Some(track)) => track.title = argument,
+				("TITLE", None) => sheet.title = argument,
+				("PERFORMER", Some(track)) => track.performer = argument,
+				("PERFORMER", None) => sheet.performer = argument,
+				("SONGWRITER", Some(track)) => track.songwriter = argument,
+				("SONGWRITER", None) => sheet.songwriter = argument,
+				("REM", None) => {
+					let value = arguments.next();
+					match argument.map(|a| a.to_uppercase()).as_deref() {
+						Some("GENRE") => sheet.genre = value,
+						Some("DATE") => sheet.year = value.and_then(|v| v.get(..4)?.parse().ok()),
+						_ => (),
+					}
+				}
+				_ => (),
+			}
+		}
+		sheet.tracks.extend(track.take());
+
+		if sheet.tracks.is_empty() || sheet.tracks.iter().any(|t| t.start < 0) {
+			return None;
+		}
+		Some(sheet)
+	}
+
+	// Portion of its file occupied by each track
+	pub fn segments(&self) -> Vec<Segment> {
+		self.tracks
+			.iter()
+			.enumerate()
+			.map(|(i, track)| Segment {
+				start: track.start,
+				end: self
+					.tracks
+					.get(i + 1)
+					.filter(|next| next.file == track.file)
+					.map(|next| next.start),
+			})
+			.collect()
+	}
+}
+
+// Splits a line into words, keeping quoted strings together
+fn tokenize(line: &str) -> Vec<String> {
+	let mut tokens = Vec::new();
+	let mut chars = line.trim().chars().peekable();
+	while let Some(c) = chars.next() {
+		match c {
+			c if c.is_whitespace() => continue,
+			'"' => tokens.push(chars.by_ref().take_while(|c| *c != '"').collect()),
+			c => {
+				let mut token = String::from(c);
+				while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+					token.push(c);
+				}
+				tokens.push(token);
+			}
+		}
+	}
+	tokens
+}
+
+// Converts a `mm:ss:ff` position into milliseconds
+fn parse_position(position: &str) -> Option<i64> {
+	let mut parts = position.split(':').map(|p| p.parse::<i64>().ok());
+	let (minutes, seconds, frames) = (parts.next()??, parts.next()??, parts.next()??);
+	if parts.next().is_some() || seconds >= 60 || frames >= FRAMES_PER_SECOND {
+		return None;
+	}
+	Some((minutes * 60 + seconds) * 1000 + frames * 1000 / FRAMES_PER_SECOND)
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	const SHEET: &str = r#"REM GENRE "Progressive Rock"
+REM DATE 1994
+PERFORMER "Stratovarius"
+TITLE "Dreamspace"
+FILE "Dreamspace.flac" WAVE
+  TRACK 01 AUDIO
+    TITLE "Chasing Shadows"
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE "4th Reich"
+    PERFORMER "Timo Tolkki"
+    INDEX 00 04:10:20
+    INDEX 01 04:12:37
+"#;
+
+	#[test]
+	fn parses_cue_sheet() {
+		let sheet = CueSheet::parse(SHEET).unwrap();
+		assert_eq!(sheet.title.as_deref(), Some("Dreamspace"));
+		assert_eq!(sheet.performer.as_deref(), Some("Stratovarius"));
+		assert_eq!(sheet.genre.as_deref(), Some("Progressive Rock"));
+		assert_eq!(sheet.year, Some(1994));
+		assert_eq!(
+			sheet.tracks,
+			vec![
+				CueTrack {
+					file: "Dreamspace.flac".to_owned(),
+					number: 1,
+					title: Some("Chasing Shadows".to_owned()),
+					start: 0,
+					..Default::default()
+				},
+				CueTrack {
+					file: "Dreamspace.flac".to_owned(),
+					number: 2,
+					title: Some("4th Reich".to_owned()),
+					performer: Some("Timo Tolkki".to_owned()),
+					start: 252_493,
+					..Default::default()
+				},
+			]
+		);
+		assert_eq!(
+			sheet.segments(),
+			vec![
+				Segment {
+					start: 0,
+					end: Some(252_493)
+				},
+				Segment {
+					start: 252_493,
+					end: None
+				},
+			]
+		);
+	}
+
+	#[test]
+	fn rejects_invalid_cue_sheets() {
+		assert!(CueSheet::parse("").is_none());
+		assert!(CueSheet::parse("TRACK 01 AUDIO\nINDEX 01 00:00:00").is_none());
+		assert!(
+			CueSheet::parse("FILE \"a.flac\" WAVE\nTRACK 01 AUDIO\nINDEX 01 00:61:00").is_none()
+		);
+		assert!(
+			CueSheet::parse("FILE \"a.flac\" WAVE\nTRACK 01 AUDIO\nTITLE \"No Index\"").is_none()
+		);
+	}
+}
@@ -0,0 +1,150 @@
+use std::path::Path;
+
+use regex::Regex;
+
+use crate::app::formats::SongMetadata;
+use crate::app::Error;
+
+const TEXT_FIELDS: [&str; 4] = ["artist", "albumartist", "album", "title"];
+const NUMBER_FIELDS: [&str; 3] = ["track", "disc", "year"];
+
+// Extracts metadata from file paths, using patterns like `%artist%/%album%/%track% - %title%`.
+// Patterns are matched against the end of the path, with the file extension removed.
+#[derive(Clone, Debug)]
+pub struct FilenamePattern {
+	pattern: String,
+	regex: Regex,
+}
+
+impl PartialEq for FilenamePattern {
+	fn eq(&self, other: &Self) -> bool {
+		self.pattern == other.pattern
+	}
+}
+
+impl Eq for FilenamePattern {}
+
+impl FilenamePattern {
+	pub fn new(pattern: &str) -> Result<Self, Error> {
+		let pattern = pattern.trim().trim_matches('/');
+		// An odd number of `%` leaves a dangling field name
+		if pattern.is_empty() || !pattern.matches('%').count().is_multiple_of(2) {
+			return Err(Error::FilenamePatternInvalid);
+		}
+
+		let mut regex = String::from("(?:^|/)");
+		let mut parts = pattern.split('%');
+		regex.push_str(&regex::escape(parts.next().unwrap_or_default()));
+		while let (Some(field), Some(literal)) = (parts.next(), parts.next()) {
+			let field = field.to_lowercase();
+			if TEXT_FIELDS.contains(&field.as_str()) {
+				regex.push_str(&format!("(?P<{field}>[^/]+?)"));
+			} else if NUMBER_FIELDS.contains(&field.as_str()) {
+				regex.push_str(&format!("(?P<{field}>\\d+)"));
+			} else {
+				return Err(Error::FilenamePatternInvalid);
+			}
+			regex.push_str(&regex::escape(literal));
+		}
+		regex.push('$');
+
+		// Fails on fields appearing more than once
+		let regex = Regex::new(&regex).map_err(|_| Error::FilenamePatternInvalid)?;
+
+		Ok(Self {
+			pattern: pattern.to_owned(),
+			regex,
+		})
+	}
+
+	pub fn as_str(&self) -> &str {
+		&self.pattern
+	}
+
+	// Fills in fields which are missing from the song tags
+	pub fn apply(&self, path: &Path, metadata: &mut SongMetadata) {
+		let mut components = path
+			.components()
+			.map(|c| c.as_os_str().to_string_lossy().to_string())
+			.collect::<Vec<_>>();
+		if let Some(file_stem) = path.file_stem() {
+			components.pop();
+			components.push(file_stem.to_string_lossy().to_string());
+		}
+		let path = components.join("/");
+
+		let Some(captures) = self.regex.captures(&path) else {
+			return;
+		};
+
+		let text = |name: &str| {
+			captures
+				.name(name)
+				.map(|m| m.as_str().trim().to_owned())
+				.filter(|s| !s.is_empty())
+		};
+		let number = |name: &str| captures.name(name).and_then(|m| m.as_str().parse().ok());
+
+		if metadata.artists.is_empty() {
+			metadata.artists.extend(text("artist"));
+		}
+		if metadata.album_artists.is_empty() {
+			metadata.album_artists.extend(text("albumartist"));
+		}
+		metadata.album = metadata.album.take().or_else(|| text("album"));
+		metadata.title = metadata.title.take().or_else(|| text("title"));
+		metadata.track_number = metadata.track_number.or_else(|| number("track"));
+		metadata.disc_number = metadata.disc_number.or_else(|| number("disc"));
+		metadata.year = metadata.year.or_else(|| number("year").map(|y| y as i32));
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::path::PathBuf;
+
+	use super::*;
+
+	#[test]
+	fn can_parse_path() {
+		let pattern = FilenamePattern::new("%artist%/%album% (%year%)/%track% - %title%").unwrap();
+		let path = PathBuf::from_iter(["root", "Khemmis", "Hunted (2016)", "02 - Candlelight.mp3"]);
+		let mut metadata = SongMetadata::default();
+		pattern.apply(&path, &mut metadata);
+		assert_eq!(metadata.artists, vec!["Khemmis".to_owned()]);
+		assert_eq!(metadata.album, Some("Hunted".to_owned()));
+		assert_eq!(metadata.year, Some(2016));
+		assert_eq!(metadata.track_number, Some(2));
+		assert_eq!(metadata.title, Some("Candlelight".to_owned()));
+	}
+
+	#[test]
+	fn tags_take_precedence() {
+		let pattern = FilenamePattern::new("%artist%/%album%/%track% - %title%").unwrap();
+		let path = PathBuf::from_iter(["root", "Khemmis", "Hunted", "02 - Candlelight.mp3"]);
+		let mut metadata = SongMetadata {
+			title: Some("Three Gates".to_owned()),
+			..Default::default()
+		};
+		pattern.apply(&path, &mut metadata);
+		assert_eq!(metadata.title, Some("Three Gates".to_owned()));
+		assert_eq!(metadata.album, Some("Hunted".to_owned()));
+	}
+
+	#[test]
+	fn mismatched_path_is_ignored() {
+		let pattern = FilenamePattern::new("%artist%/%album%/%track% - %title%").unwrap();
+		let path = PathBuf::from_iter(["root", "Candlelight.mp3"]);
+		let mut metadata = SongMetadata::default();
+		pattern.apply(&path, &mut metadata);
+		assert_eq!(metadata, SongMetadata::default());
+	}
+
+	#[test]
+	fn rejects_invalid_patterns() {
+		assert!(FilenamePattern::new("").is_err());
+		assert!(FilenamePattern::new("%artist%/%oink%").is_err());
+		assert!(FilenamePattern::new("%artist%/%title").is_err());
+		assert!(FilenamePattern::new("%title%/%title%").is_err());
+	}
+}
@@ -0,0 +1,262 @@
+// Durations and tag locations read from the headers of formats which tag libraries do not cover
+
+use std::io::{Read, Seek, SeekFrom};
+
+fn read_array<R: Read, const N: usize>(reader: &mut R) -> Option<[u8; N]> {
+	let mut buffer = [0; N];
+	reader.read_exact(&mut buffer).ok()?;
+	Some(buffer)
+}
+
+fn duration(num_samples: u64, sample_rate: u64) -> Option<u32> {
+	(sample_rate > 0).then(|| (num_samples / sample_rate) as u32)
+}
+
+// Converts an 80-bit IEEE 754 extended precision number, as used by AIFF sample rates
+fn read_extended(bytes: [u8; 10]) -> f64 {
+	let exponent = u16::from_be_bytes([bytes[0], bytes[1]]) & 0x7FFF;
+	let mantissa = u64::from_be_bytes(bytes[2..].try_into().unwrap());
+	mantissa as f64 * 2f64.powi(exponent as i32 - 16383 - 63)
+}
+
+pub fn read_aiff_duration<R: Read + Seek>(reader: &mut R) -> Option<u32> {
+	let header = read_array::<_, 12>(reader)?;
+	if header[..4] != *b"FORM" {
+		return None;
+	}
+	loop {
+		let header = read_array::<_, 8>(reader)?;
+		let size = u32::from_be_bytes(header[4..].try_into().unwrap()) as i64;
+		if header[..4] == *b"COMM" {
+			let comm = read_array::<_, 18>(reader)?;
+			let num_frames = u32::from_be_bytes(comm[2..6].try_into().unwrap());
+			let sample_rate = read_extended(comm[8..].try_into().unwrap());
+			return (sample_rate > 0.0).then(|| (num_frames as f64 / sample_rate) as u32);
+		}
+		// Chunks are padded to an even size
+		reader.seek(SeekFrom::Current(size + (size & 1))).ok()?;
+	}
+}
+
+pub fn read_wavpack_duration<R: Read>(reader: &mut R) -> Option<u32> {
+	const SAMPLE_RATES: [u64; 15] = [
+		6000, 8000, 9600, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000, 64000, 88200,
+		96000, 192000,
+	];
+	let header = read_array::<_, 32>(reader)?;
+	if header[..4] != *b"wvpk" {
+		return None;
+	}
+	let num_samples = u32::from_le_bytes(header[12..16].try_into().unwrap());
+	let flags = u32::from_le_bytes(header[24..28].try_into().unwrap());
+	// Files with a custom sample rate, or written without knowing their length, are not supported
+	let sample_rate = SAMPLE_RATES.get(((flags >> 23) & 0xF) as usize)?;
+	if num_samples == u32::MAX {
+		return None;
+	}
+	duration(num_samples as u64, *sample_rate)
+}
+
+pub fn read_musepack_duration<R: Read>(reader: &mut R) -> Option<u32> {
+	const SAMPLE_RATES: [u64; 4] = [44100, 48000, 37800, 32000];
+	const SAMPLES_PER_FRAME: u64 = 1152;
+
+	let magic = read_array::<_, 4>(reader)?;
+	match magic {
+		// Stream version 7
+		[b'M', b'P', b'+', version] if version & 0x0F == 7 => {
+			let header = read_array::<_, 8>(reader)?;
+			let num_frames = u32::from_le_bytes(header[..4].try_into().unwrap());
+			let sample_rate = SAMPLE_RATES[(header[6] & 0x03) as usize];
+			duration(num_frames as u64 * SAMPLES_PER_FRAME, sample_rate)
+		}
+		// Stream version 8, made of packets starting with a two letter key
+		[b'M', b'P', b'C', b'K'] => loop {
+			let key = read_array::<_, 2>(reader)?;
+			let (size, size_length) = read_varint(reader)?;
+			let payload_size = size.checked_sub(2 + size_length)?;
+			if key == *b"SH" {
+				let mut payload = reader.by_ref().take(payload_size);
+				// CRC and stream version
+				read_array::<_, 5>(&mut payload)?;
+				let (num_samples, _) = read_varint(&mut payload)?;
+				let (beginning_silence, _) = read_varint(&mut payload)?;
+				let [rate_index, _] = read_array::<_, 2>(&mut payload)?;
+				let sample_rate = SAMPLE_RATES.get((rate_index >> 5) as usize)?;
+				return duration(num_samples.saturating_sub(beginning_silence), *sample_rate);
+			}
+			std::io::copy(
+				&mut reader.by_ref().take(payload_size),
+				&mut std::io::sink(),
+			)
+			.ok()?;
+		},
+		_ => None,
+	}
+}
+
+// Musepack SV8 sizes hold 7 bits per byte, the last byte having its top bit cleared
+fn read_varint<R: Read>(reader: &mut R) -> Option<(u64, u64)> {
+	let mut value = 0u64;
+	for length in 1..=9 {
+		let [byte] = read_array::<_, 1>(reader)?;
+		value = (value << 7) | (byte & 0x7F) as u64;
+		if byte & 0x80 == 0 {
+			return Some((value, length));
+		}
+	}
+	None
+}
+
+pub struct DsdInfo {
+	pub duration: Option<u32>,
+	// Position of the ID3v2 tag within the file
+	pub id3_offset: Option<u64>,
+}
+
+pub fn read_dsf_info<R: Read>(reader: &mut R) -> Option<DsdInfo> {
+	let header = read_array::<_, 80>(reader)?;
+	if header[..4] != *b"DSD " || header[28..32] != *b"fmt " {
+		return None;
+	}
+	let id3_offset = u64::from_le_bytes(header[20..28].try_into().unwrap());
+	let sample_rate = u32::from_le_bytes(header[56..60].try_into().unwrap());
+	let num_samples = u64::from_le_bytes(header[64..72].try_into().unwrap());
+	Some(DsdInfo {
+		duration: duration(num_samples, sample_rate as u64),
+		id3_offset: (id3_offset > 0).then_some(id3_offset),
+	})
+}
+
+pub fn read_dff_info<R: Read + Seek>(reader: &mut R) -> Option<DsdInfo> {
+	let header = read_array::<_, 16>(reader)?;
+	if header[..4] != *b"FRM8" || header[12..] != *b"DSD " {
+		return None;
+	}
+
+	let mut info = DsdInfo {
+		duration: None,
+		id3_offset: None,
+	};
+	let mut sample_rate = None;
+	let mut num_channels = None;
+	let mut data_size = None;
+	while let Some(chunk) = read_array::<_, 12>(reader) {
+		let size = u64::from_be_bytes(chunk[4..].try_into().unwrap());
+		let start = reader.stream_position().ok()?;
+		match &chunk[..4] {
+			// Sound properties, made of sub-chunks following the `SND ` marker
+			b"PROP" => {
+				read_array::<_, 4>(reader)?;
+				while reader.stream_position().ok()? < start + size {
+					let property = read_array::<_, 12>(reader)?;
+					let property_size = u64::from_be_bytes(property[4..].try_into().unwrap());
+					let property_start = reader.stream_position().ok()?;
+					match &property[..4] {
+						b"FS  " => {
+							sample_rate = Some(u32::from_be_bytes(read_array(reader)?));
+						}
+						b"CHNL" => {
+							num_channels = Some(u16::from_be_bytes(read_array(reader)?));
+						}
+						_ => (),
+					}
+					let next = property_start + property_size + (property_size & 1);
+					reader.seek(SeekFrom::Start(next)).ok()?;
+				}
+			}
+			b"DSD " => data_size = Some(size),
+			b"ID3 " => info.id3_offset = Some(start),
+			_ => (),
+		}
+		reader
+			.seek(SeekFrom::Start(start + size + (size & 1)))
+			.ok()?;
+	}
+
+	// DSD audio holds one bit per sample and channel
+	if let (Some(sample_rate), Some(num_channels), Some(data_size)) =
+		(sample_rate, num_channels, data_size)
+	{
+		if num_channels > 0 {
+			info.duration = duration(data_size * 8 / num_channels as u64, sample_rate as u64);
+		}
+	}
+	Some(info)
+}
+
+#[cfg(test)]
+mod test {
+	use std::io::Cursor;
+
+	use super::*;
+
+	#[test]
+	fn reads_aiff_duration() {
+		let file = std::fs::read("test-data/formats/sample.aif").unwrap();
+		assert_eq!(read_aiff_duration(&mut Cursor::new(file)), Some(2));
+	}
+
+	#[test]
+	fn reads_wavpack_duration() {
+		let mut header = vec![0; 32];
+		header[..4].copy_from_slice(b"wvpk");
+		header[12..16].copy_from_slice(&(44100u32 * 185).to_le_bytes());
+		header[24..28].copy_from_slice(&(9u32 << 23).to_le_bytes());
+		assert_eq!(read_wavpack_duration(&mut Cursor::new(header)), Some(185));
+	}
+
+	#[test]
+	fn reads_musepack_duration() {
+		let mut sv7 = b"MP+\x17".to_vec();
+		sv7.extend_from_slice(&((48000u32 * 60) / 1152).to_le_bytes());
+		sv7.extend_from_slice(&[0, 0, 0x01, 0]);
+		assert_eq!(read_musepack_duration(&mut Cursor::new(sv7)), Some(60));
+
+		let mut sv8 = b"MPCK".to_vec();
+		// Encoder info packet, skipped
+		sv8.extend_from_slice(&[b'E', b'I', 6, 0, 0, 0]);
+		// Stream header: CRC, version, 441000 samples, no silence, 44.1kHz
+		sv8.extend_from_slice(&[b'S', b'H', 14, 0, 0, 0, 0, 8, 0x9A, 0xF5, 0x28, 0, 0, 0]);
+		assert_eq!(read_musepack_duration(&mut Cursor::new(sv8)), Some(10));
+	}
+
+	#[test]
+	fn reads_dsf_info() {
+		let mut header = vec![0; 80];
+		header[..4].copy_from_slice(b"DSD ");
+		header[20..28].copy_from_slice(&1000u64.to_le_bytes());
+		header[28..32].copy_from_slice(b"fmt ");
+		header[56..60].copy_from_slice(&2822400u32.to_le_bytes());
+		header[64..72].copy_from_slice(&(2822400u64 * 240).to_le_bytes());
+		let info = read_dsf_info(&mut Cursor::new(header)).unwrap();
+		assert_eq!(info.duration, Some(240));
+		assert_eq!(info.id3_offset, Some(1000));
+	}
+
+	#[test]
+	fn reads_dff_info() {
+		let mut file = b"FRM8".to_vec();
+		file.extend_from_slice(&0u64.to_be_bytes());
+		file.extend_from_slice(b"DSD ");
+		file.extend_from_slice(b"PROP");
+		file.extend_from_slice(&34u64.to_be_bytes());
+		file.extend_from_slice(b"SND ");
+		file.extend_from_slice(b"FS  ");
+		file.extend_from_slice(&4u64.to_be_bytes());
+		file.extend_from_slice(&8000u32.to_be_bytes());
+		file.extend_from_slice(b"CHNL");
+		file.extend_from_slice(&2u64.to_be_bytes());
+		file.extend_from_slice(&2u16.to_be_bytes());
+		file.extend_from_slice(b"DSD ");
+		file.extend_from_slice(&(8000u64 * 2 * 3 / 8).to_be_bytes());
+		file.extend(std::iter::repeat(0).take(8000 * 2 * 3 / 8));
+		file.extend_from_slice(b"ID3 ");
+		file.extend_from_slice(&10u64.to_be_bytes());
+		let id3_offset = file.len() as u64;
+		file.extend_from_slice(&[0; 10]);
+		let info = read_dff_info(&mut Cursor::new(file)).unwrap();
+		assert_eq!(info.duration, Some(3));
+		assert_eq!(info.id3_offset, Some(id3_offset));
+	}
+}
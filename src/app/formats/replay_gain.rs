@@ -0,0 +1,105 @@
+use crate::utils;
+
+// R128 gains are relative to -23 LUFS, while ReplayGain targets -18 LUFS
+const R128_OFFSET: i32 = 500;
+
+// Volume normalization data, from ReplayGain tags or converted from R128 tags.
+// Gains are in hundredths of a decibel and peaks in millionths of full scale.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReplayGain {
+	pub track_gain: Option<i32>,
+	pub track_peak: Option<u32>,
+	pub album_gain: Option<i32>,
+	pub album_peak: Option<u32>,
+}
+
+impl ReplayGain {
+	// Tag names to look up in formats which cannot list all their tags
+	pub const KEYS: [&'static str; 6] = [
+		"REPLAYGAIN_TRACK_GAIN",
+		"REPLAYGAIN_TRACK_PEAK",
+		"REPLAYGAIN_ALBUM_GAIN",
+		"REPLAYGAIN_ALBUM_PEAK",
+		"R128_TRACK_GAIN",
+		"R128_ALBUM_GAIN",
+	];
+
+	// ReplayGain tags take precedence over R128 tags, regardless of the order they are read in
+	pub fn read_tag(&mut self, key: &str, value: &str) {
+		utils::match_ignore_case! {
+			match key {
+				"REPLAYGAIN_TRACK_GAIN" => self.track_gain = parse_gain(value).or(self.track_gain),
+				"REPLAYGAIN_TRACK_PEAK" => self.track_peak = parse_peak(value),
+				"REPLAYGAIN_ALBUM_GAIN" => self.album_gain = parse_gain(value).or(self.album_gain),
+				"REPLAYGAIN_ALBUM_PEAK" => self.album_peak = parse_peak(value),
+				"R128_TRACK_GAIN" => self.track_gain = self.track_gain.or(parse_r128(value)),
+				"R128_ALBUM_GAIN" => self.album_gain = self.album_gain.or(parse_r128(value)),
+				_ => (),
+			}
+		}
+	}
+}
+
+// Parses values like `-6.52 dB`
+fn parse_gain(value: &str) -> Option<i32> {
+	let value = value
+		.trim()
+		.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+	let gain = value.trim().parse::<f64>().ok().filter(|g| g.is_finite())?;
+	Some((gain * 100.0).round() as i32)
+}
+
+fn parse_peak(value: &str) -> Option<u32> {
+	let peak = value.trim().parse::<f64>().ok();
+	let peak = peak.filter(|p| p.is_finite() && *p >= 0.0)?;
+	Some((peak * 1_000_000.0).round() as u32)
+}
+
+// R128 gains are stored as Q7.8 fixed point numbers
+fn parse_r128(value: &str) -> Option<i32> {
+	let gain = value.trim().parse::<i16>().ok()? as i32;
+	Some((gain * 100 + gain.signum() * 128) / 256 + R128_OFFSET)
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn reads_replay_gain_tags() {
+		let mut replay_gain = ReplayGain::default();
+		replay_gain.read_tag("REPLAYGAIN_TRACK_GAIN", "-6.52 dB");
+		replay_gain.read_tag("replaygain_track_peak", "0.988525");
+		replay_gain.read_tag("REPLAYGAIN_ALBUM_GAIN", "+1.30 dB");
+		replay_gain.read_tag("REPLAYGAIN_ALBUM_PEAK", "not a number");
+		assert_eq!(
+			replay_gain,
+			ReplayGain {
+				track_gain: Some(-652),
+				track_peak: Some(988_525),
+				album_gain: Some(130),
+				album_peak: None,
+			}
+		);
+	}
+
+	#[test]
+	fn converts_r128_tags() {
+		let mut replay_gain = ReplayGain::default();
+		replay_gain.read_tag("R128_TRACK_GAIN", "-2816");
+		replay_gain.read_tag("R128_ALBUM_GAIN", "384");
+		assert_eq!(replay_gain.track_gain, Some(-600));
+		assert_eq!(replay_gain.album_gain, Some(650));
+	}
+
+	#[test]
+	fn prefers_replay_gain_over_r128() {
+		let mut replay_gain = ReplayGain::default();
+		replay_gain.read_tag("R128_TRACK_GAIN", "-2816");
+		replay_gain.read_tag("REPLAYGAIN_TRACK_GAIN", "-7.00 dB");
+		replay_gain.read_tag("REPLAYGAIN_ALBUM_GAIN", "-3.00 dB");
+		replay_gain.read_tag("R128_ALBUM_GAIN", "384");
+		assert_eq!(replay_gain.track_gain, Some(-700));
+		assert_eq!(replay_gain.album_gain, Some(-300));
+	}
+}
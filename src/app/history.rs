@@ -0,0 +1,245 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use native_db::*;
+use native_model::{native_model, Model};
+use serde::{Deserialize, Serialize};
+use tokio::task::spawn_blocking;
+
+use crate::app::{auth, ndb, Error};
+
+// Songs played to completion by each user
+#[derive(Clone)]
+pub struct Manager {
+	db: ndb::Manager,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Play {
+	pub id: u64,
+	pub path: PathBuf,
+	pub played_at: i64,
+	pub client: Option<String>,
+}
+
+pub type PlayModel = v1::PlayModel;
+type PlayModelKey = v1::PlayModelKey;
+
+pub mod v1 {
+
+	use super::*;
+
+	#[derive(Clone, Debug, Serialize, Deserialize)]
+	#[native_model(id = 12, version = 1)]
+	#[native_db]
+	pub struct PlayModel {
+		// Increases with each play, so that plays are stored in chronological order
+		#[primary_key]
+		pub id: u64,
+		#[secondary_key]
+		pub username: String,
+		pub path: PathBuf,
+		pub played_at: i64,
+		pub client: Option<String>,
+	}
+}
+
+impl From<PlayModel> for Play {
+	fn from(p: PlayModel) -> Self {
+		Self {
+			id: p.id,
+			path: p.path,
+			played_at: p.played_at,
+			client: p.client,
+		}
+	}
+}
+
+impl Manager {
+	pub fn new(db: ndb::Manager) -> Self {
+		Self { db }
+	}
+
+	pub async fn record_play(
+		&self,
+		username: &str,
+		path: PathBuf,
+		client: Option<String>,
+	) -> Result<Play, Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			let username = username.to_owned();
+			move || {
+				let transaction = manager.db.rw_transaction()?;
+				let last_id = transaction
+					.scan()
+					.primary::<PlayModel>()?
+					.all()?
+					.next_back()
+					.transpose()?
+					.map(|p| p.id);
+				let play = PlayModel {
+					id: last_id.map_or(0, |id| id + 1),
+					username,
+					path,
+					played_at: auth::now() as i64,
+					client,
+				};
+				transaction.insert::<PlayModel>(play.clone())?;
+				transaction.commit()?;
+				Ok(play.into())
+			}
+		})
+		.await?
+	}
+
+	// Most recent first
+	pub async fn get_history(&self, username: &str) -> Result<Vec<Play>, Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			let username = username.to_owned();
+			move || {
+				let transaction = manager.db.r_transaction()?;
+				let mut plays = transaction
+					.scan()
+					.secondary::<PlayModel>(PlayModelKey::username)?
+					.range(username.as_str()..=username.as_str())?
+					.filter_map(|p| p.ok())
+					.map(Play::from)
+					.collect::<Vec<_>>();
+				plays.sort_by_key(|p| std::cmp::Reverse(p.id));
+				Ok(plays)
+			}
+		})
+		.await?
+	}
+
+	// Songs in the history without repeats, most recently played first
+	pub async fn get_recently_played(&self, username: &str) -> Result<Vec<PathBuf>, Error> {
+		let mut seen = HashSet::new();
+		let songs = self
+			.get_history(username)
+			.await?
+			.into_iter()
+			.filter_map(|p| seen.insert(p.path.clone()).then_some(p.path))
+			.collect();
+		Ok(songs)
+	}
+
+	pub async fn get_play_counts(&self, username: &str) -> Result<HashMap<PathBuf, u32>, Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			let username = username.to_owned();
+			move || {
+				let transaction = manager.db.r_transaction()?;
+				let mut play_counts = HashMap::new();
+				for play in transaction
+					.scan()
+					.secondary::<PlayModel>(PlayModelKey::username)?
+					.range(username.as_str()..=username.as_str())?
+					.filter_map(|p| p.ok())
+				{
+					*play_counts.entry(play.path).or_default() += 1;
+				}
+				Ok(play_counts)
+			}
+		})
+		.await?
+	}
+
+	// Time of the most recent play of each song
+	pub async fn get_last_played(&self, username: &str) -> Result<HashMap<PathBuf, i64>, Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			let username = username.to_owned();
+			move || {
+				let transaction = manager.db.r_transaction()?;
+				let mut last_played = HashMap::<PathBuf, i64>::new();
+				for play in transaction
+					.scan()
+					.secondary::<PlayModel>(PlayModelKey::username)?
+					.range(username.as_str()..=username.as_str())?
+					.filter_map(|p| p.ok())
+				{
+					let played_at = last_played.entry(play.path).or_insert(play.played_at);
+					*played_at = (*played_at).max(play.played_at);
+				}
+				Ok(last_played)
+			}
+		})
+		.await?
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::app::test;
+	use crate::test_name;
+
+	const TEST_USER: &str = "test_user";
+
+	#[tokio::test]
+	async fn can_record_plays() {
+		let ctx = test::ContextBuilder::new(test_name!()).build().await;
+		let history = ctx.history_manager;
+
+		let destiny = PathBuf::from("my_music/destiny.mp3");
+		let sos = PathBuf::from("my_music/sos.mp3");
+
+		history
+			.record_play(TEST_USER, destiny.clone(), Some("Phone".to_owned()))
+			.await
+			.unwrap();
+		history
+			.record_play("other_user", destiny.clone(), None)
+			.await
+			.unwrap();
+		history
+			.record_play(TEST_USER, sos.clone(), None)
+			.await
+			.unwrap();
+		history
+			.record_play(TEST_USER, destiny.clone(), None)
+			.await
+			.unwrap();
+
+		let plays = history.get_history(TEST_USER).await.unwrap();
+		assert_eq!(
+			plays.iter().map(|p| p.path.clone()).collect::<Vec<_>>(),
+			vec![destiny.clone(), sos.clone(), destiny.clone()]
+		);
+		assert_eq!(plays[2].client.as_deref(), Some("Phone"));
+
+		assert_eq!(
+			history.get_play_counts(TEST_USER).await.unwrap(),
+			HashMap::from([(destiny.clone(), 2), (sos.clone(), 1)])
+		);
+		assert_eq!(history.get_history("other_user").await.unwrap().len(), 1);
+
+		let last_played = history.get_last_played(TEST_USER).await.unwrap();
+		assert_eq!(last_played.len(), 2);
+		assert_eq!(last_played[&destiny], plays[0].played_at);
+		assert_eq!(last_played[&sos], plays[1].played_at);
+	}
+
+	#[tokio::test]
+	async fn recently_played_skips_repeats() {
+		let ctx = test::ContextBuilder::new(test_name!()).build().await;
+		let history = ctx.history_manager;
+
+		let destiny = PathBuf::from("my_music/destiny.mp3");
+		let sos = PathBuf::from("my_music/sos.mp3");
+		for path in [&destiny, &sos, &destiny, &destiny] {
+			history
+				.record_play(TEST_USER, path.clone(), None)
+				.await
+				.unwrap();
+		}
+
+		assert_eq!(
+			history.get_recently_played(TEST_USER).await.unwrap(),
+			vec![destiny, sos]
+		);
+	}
+}
@@ -0,0 +1,326 @@
+use std::io::Write;
+use std::net::IpAddr;
+use std::process::{Command, Stdio};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log::{error, info};
+use serde::Serialize;
+use tokio::task::spawn_blocking;
+
+use crate::app::config;
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Details about a completed scan, sent to post-scan hooks as JSON
+#[derive(Clone, Debug, Serialize)]
+pub struct ScanSummary {
+	pub start_time: u64,
+	pub end_time: u64,
+	pub duration_ms: u64,
+	pub num_songs: usize,
+}
+
+impl ScanSummary {
+	pub fn new(start_time: SystemTime, end_time: SystemTime, num_songs: usize) -> Self {
+		let seconds = |t: SystemTime| {
+			t.duration_since(UNIX_EPOCH)
+				.map(|d| d.as_secs())
+				.unwrap_or_default()
+		};
+		Self {
+			start_time: seconds(start_time),
+			end_time: seconds(end_time),
+			duration_ms: end_time
+				.duration_since(start_time)
+				.map(|d| d.as_millis() as u64)
+				.unwrap_or_default(),
+			num_songs,
+		}
+	}
+}
+
+// Server events delivered to webhooks
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+	ScanCompleted {
+		duration_ms: u64,
+		num_songs: usize,
+	},
+	AlbumAdded {
+		name: String,
+		artists: Vec<String>,
+	},
+	UserCreated {
+		name: String,
+		admin: bool,
+	},
+	LoginFailed {
+		username: String,
+		address: Option<IpAddr>,
+	},
+}
+
+impl Event {
+	pub fn kind(&self) -> config::WebhookEvent {
+		match self {
+			Event::ScanCompleted { .. } => config::WebhookEvent::ScanCompleted,
+			Event::AlbumAdded { .. } => config::WebhookEvent::AlbumAdded,
+			Event::UserCreated { .. } => config::WebhookEvent::UserCreated,
+			Event::LoginFailed { .. } => config::WebhookEvent::LoginFailed,
+		}
+	}
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+	#[serde(flatten)]
+	event: &'a Event,
+	timestamp: u64,
+}
+
+#[derive(Clone)]
+pub struct Manager {
+	config_manager: config::Manager,
+}
+
+impl Manager {
+	pub fn new(config_manager: config::Manager) -> Self {
+		Self { config_manager }
+	}
+
+	// Lets callers skip work to produce events which no webhook wants
+	pub async fn is_subscribed(&self, kind: config::WebhookEvent) -> bool {
+		self.config_manager
+			.get_webhooks()
+			.await
+			.iter()
+			.any(|w| w.accepts(kind))
+	}
+
+	// Sends an event to interested webhooks in the background. Failures are logged.
+	pub async fn notify(&self, event: Event) {
+		let webhooks = self.config_manager.get_webhooks().await;
+		let urls = webhooks
+			.into_iter()
+			.filter(|w| w.accepts(event.kind()))
+			.map(|w| w.url.to_string())
+			.collect::<Vec<_>>();
+		if urls.is_empty() {
+			return;
+		}
+
+		let timestamp = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map(|d| d.as_secs())
+			.unwrap_or_default();
+		let Ok(payload) = serde_json::to_string(&Payload {
+			event: &event,
+			timestamp,
+		}) else {
+			return;
+		};
+
+		for url in urls {
+			let payload = payload.clone();
+			tokio::task::spawn_blocking(move || {
+				let response = ureq::post(&url)
+					.timeout(WEBHOOK_TIMEOUT)
+					.set("Content-Type", "application/json")
+					.send_string(&payload);
+				if let Err(e) = response {
+					error!("Webhook delivery to `{url}` failed: {e}");
+				}
+			});
+		}
+	}
+}
+
+// Runs the configured command and/or webhook. Failures are logged but do not affect the scan.
+pub async fn run_post_scan(hook: config::PostScanHook, summary: ScanSummary) {
+	let Ok(payload) = serde_json::to_string(&summary) else {
+		return;
+	};
+
+	if let Some(command) = hook.command {
+		let payload = payload.clone();
+		match spawn_blocking(move || run_command(&command, &payload)).await {
+			Ok(Ok(())) => info!("Post-scan command completed"),
+			Ok(Err(e)) => error!("Post-scan command failed: {e}"),
+			Err(e) => error!("Post-scan command failed: {e}"),
+		}
+	}
+
+	if let Some(url) = hook.webhook_url {
+		let url = url.to_string();
+		let response = spawn_blocking(move || {
+			ureq::post(&url)
+				.set("Content-Type", "application/json")
+				.send_string(&payload)
+		})
+		.await;
+		match response {
+			Ok(Ok(_)) => info!("Post-scan webhook completed"),
+			Ok(Err(e)) => error!("Post-scan webhook failed: {e}"),
+			Err(e) => error!("Post-scan webhook failed: {e}"),
+		}
+	}
+}
+
+// Runs a command through the system shell, with the scan summary on its standard input
+fn run_command(command: &str, payload: &str) -> std::io::Result<()> {
+	let (shell, flag) = if cfg!(windows) {
+		("cmd", "/C")
+	} else {
+		("sh", "-c")
+	};
+
+	let mut child = Command::new(shell)
+		.args([flag, command])
+		.stdin(Stdio::piped())
+		.stdout(Stdio::null())
+		.spawn()?;
+
+	if let Some(mut stdin) = child.stdin.take() {
+		// Commands are free to ignore their input
+		stdin.write_all(payload.as_bytes()).ok();
+	}
+
+	let status = child.wait()?;
+	if !status.success() {
+		return Err(std::io::Error::other(format!("exited with {status}")));
+	}
+	Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod test {
+	use std::time::Duration;
+
+	use tokio::io::{AsyncReadExt, AsyncWriteExt};
+	use tokio::net::TcpListener;
+
+	use super::*;
+	use crate::app::config::storage;
+	use crate::app::test;
+	use crate::test::prepare_test_directory;
+	use crate::test_name;
+
+	// Accepts a single HTTP request and returns its body
+	async fn receive_request(listener: TcpListener) -> String {
+		let (mut stream, _) = listener.accept().await.unwrap();
+		let mut request = Vec::new();
+		let mut buffer = [0; 1024];
+		loop {
+			let len = stream.read(&mut buffer).await.unwrap();
+			request.extend_from_slice(&buffer[..len]);
+			let text = String::from_utf8_lossy(&request);
+			if let Some((headers, body)) = text.split_once("\r\n\r\n") {
+				let content_length = headers
+					.lines()
+					.find_map(|l| {
+						l.to_lowercase()
+							.strip_prefix("content-length:")
+							.map(str::to_owned)
+					})
+					.and_then(|l| l.trim().parse::<usize>().ok())
+					.unwrap_or_default();
+				if body.len() >= content_length {
+					stream
+						.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+						.await
+						.unwrap();
+					return body.to_owned();
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn payload_names_event() {
+		let event = Event::UserCreated {
+			name: "Walter".to_owned(),
+			admin: false,
+		};
+		let payload = serde_json::to_value(Payload {
+			event: &event,
+			timestamp: 1000,
+		})
+		.unwrap();
+		assert_eq!(
+			payload,
+			serde_json::json!({
+				"event": "user_created",
+				"name": "Walter",
+				"admin": false,
+				"timestamp": 1000,
+			})
+		);
+	}
+
+	#[tokio::test]
+	async fn webhook_receives_subscribed_events() {
+		let ctx = test::ContextBuilder::new(test_name!()).build().await;
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let url = format!("http://{}/hook", listener.local_addr().unwrap());
+		ctx.config_manager
+			.apply_config(storage::Config {
+				webhooks: vec![storage::Webhook {
+					url,
+					events: vec![config::WebhookEvent::LoginFailed],
+				}],
+				..Default::default()
+			})
+			.await
+			.unwrap();
+
+		assert!(
+			!ctx.hooks_manager
+				.is_subscribed(config::WebhookEvent::UserCreated)
+				.await
+		);
+
+		let received = tokio::spawn(receive_request(listener));
+		ctx.hooks_manager
+			.notify(Event::UserCreated {
+				name: "Walter".to_owned(),
+				admin: false,
+			})
+			.await;
+		ctx.hooks_manager
+			.notify(Event::LoginFailed {
+				username: "Walter".to_owned(),
+				address: None,
+			})
+			.await;
+
+		let body = tokio::time::timeout(Duration::from_secs(10), received)
+			.await
+			.unwrap()
+			.unwrap();
+		let payload: serde_json::Value = serde_json::from_str(&body).unwrap();
+		assert_eq!(payload["event"], "login_failed");
+		assert_eq!(payload["username"], "Walter");
+	}
+
+	#[tokio::test]
+	async fn command_receives_summary() {
+		let output_dir = prepare_test_directory(test_name!());
+		let output_file = output_dir.join("summary.json");
+
+		let start_time = UNIX_EPOCH + Duration::from_secs(1000);
+		let end_time = start_time + Duration::from_millis(2500);
+		let hook = config::PostScanHook {
+			command: Some(format!("cat > '{}'", output_file.display())),
+			webhook_url: None,
+		};
+		run_post_scan(hook, ScanSummary::new(start_time, end_time, 12)).await;
+
+		let summary: serde_json::Value =
+			serde_json::from_slice(&std::fs::read(&output_file).unwrap()).unwrap();
+		assert_eq!(summary["start_time"], 1000);
+		assert_eq!(summary["end_time"], 1002);
+		assert_eq!(summary["duration_ms"], 2500);
+		assert_eq!(summary["num_songs"], 12);
+	}
+}
@@ -1,15 +1,18 @@
 use std::{
+	collections::HashMap,
 	path::{Path, PathBuf},
 	sync::{Arc, RwLock},
+	time::{SystemTime, UNIX_EPOCH},
 };
 
 use log::{error, info};
 use serde::{Deserialize, Serialize};
 use tokio::task::spawn_blocking;
 
-use crate::app::{scanner, Error};
+use crate::app::{scanner, silence, Error};
 
 mod browser;
+mod changes;
 mod collection;
 mod dictionary;
 mod query;
@@ -17,13 +20,27 @@ mod search;
 mod storage;
 
 pub use browser::File;
-pub use collection::{Album, AlbumHeader, Artist, ArtistHeader, Genre, GenreHeader, Song};
+use changes::Changelog;
+pub use changes::{AlbumId, Changes};
+pub use collection::{
+	sort_listing, Album, AlbumHeader, Artist, ArtistHeader, Composer, Disc, Genre, GenreHeader,
+	ListingKey, ListingOrder, Song, Work,
+};
 use storage::{store_song, AlbumKey, ArtistKey, GenreKey, InternPath, SongKey};
 
+// Prepended to the serialized index so that snapshots written by an incompatible version of
+// Polaris are discarded instead of misread. Bump the version whenever the layout of `Index`
+// (or of anything it contains) changes.
+const INDEX_FILE_MAGIC: &[u8; 8] = b"POLARIDX";
+const INDEX_FORMAT_VERSION: u32 = 1;
+
 #[derive(Clone)]
 pub struct Manager {
 	index_file_path: PathBuf,
+	search_directory_path: PathBuf,
+	changelog_file_path: PathBuf,
 	index: Arc<RwLock<Index>>, // Not a tokio RwLock as we want to do CPU-bound work with Index and lock this inside spawn_blocking()
+	changelog: Arc<RwLock<Changelog>>,
 }
 
 impl Manager {
@@ -34,7 +51,10 @@ impl Manager {
 
 		let index_manager = Self {
 			index_file_path: directory.join("collection.index"),
+			search_directory_path: directory.join("search"),
+			changelog_file_path: directory.join("collection.changes"),
 			index: Arc::default(),
+			changelog: Arc::new(RwLock::new(Changelog::new(&[]))),
 		};
 
 		match index_manager.try_restore_index().await {
@@ -43,6 +63,17 @@ impl Manager {
 			Err(e) => error!("Failed to restore collection index: {}", e),
 		};
 
+		match index_manager.try_restore_changelog().await {
+			Ok(true) => info!("Restored collection changelog from disk"),
+			result => {
+				if let Err(e) = result {
+					error!("Failed to restore collection changelog: {}", e);
+				}
+				let songs = index_manager.get_all_songs().await;
+				*index_manager.changelog.write().unwrap() = Changelog::new(&songs);
+			}
+		};
+
 		Ok(index_manager)
 	}
 
@@ -70,17 +101,182 @@ impl Manager {
 		.unwrap()
 	}
 
-	pub async fn persist_index(&self, index: &Index) -> Result<(), Error> {
+	pub async fn persist_index(&self, index: &mut Index) -> Result<(), Error> {
+		// Search index files are written first, so that the collection index never refers to a
+		// search index which is not on disk
+		index.search = spawn_blocking({
+			let mut search = index.search.clone();
+			let search_directory_path = self.search_directory_path.clone();
+			move || {
+				search.persist(&search_directory_path)?;
+				Ok::<_, Error>(search)
+			}
+		})
+		.await
+		.unwrap()?;
+
 		let serialized = match bitcode::serialize(index) {
 			Ok(s) => s,
 			Err(_) => return Err(Error::IndexSerializationError),
 		};
-		tokio::fs::write(&self.index_file_path, &serialized[..])
+
+		let mut content = Vec::with_capacity(INDEX_FILE_MAGIC.len() + 4 + serialized.len());
+		content.extend_from_slice(INDEX_FILE_MAGIC);
+		content.extend_from_slice(&INDEX_FORMAT_VERSION.to_le_bytes());
+		content.extend_from_slice(&serialized);
+
+		// Written to a temporary file first so that a crash mid-write never leaves a truncated
+		// index behind
+		let temporary_path = self.index_file_path.with_extension("index.tmp");
+		tokio::fs::write(&temporary_path, &content[..])
+			.await
+			.map_err(|e| Error::Io(temporary_path.clone(), e))?;
+		tokio::fs::rename(&temporary_path, &self.index_file_path)
 			.await
 			.map_err(|e| Error::Io(self.index_file_path.clone(), e))?;
+
+		index.search.remove_stale_files(&self.search_directory_path);
+
 		Ok(())
 	}
 
+	// Replaces the content of a directory and its descendants, leaving the rest of the index untouched
+	// Replaces the content of several directories of the index in one go
+	pub async fn replace_directories(
+		&self,
+		virtual_paths: Vec<PathBuf>,
+		directories: Vec<scanner::Directory>,
+		songs: Vec<scanner::Song>,
+	) -> Result<(), Error> {
+		let mut new_index = spawn_blocking({
+			let index_manager = self.clone();
+			move || {
+				let index = index_manager.index.read().unwrap();
+				index.replace_directories(&virtual_paths, directories, songs)
+			}
+		})
+		.await
+		.unwrap();
+
+		self.persist_index(&mut new_index).await?;
+		self.replace_index(new_index).await;
+		self.record_changes().await
+	}
+
+	// Compares the current index with the previous generation, so clients can later ask
+	// what changed since they last synced
+	pub async fn record_changes(&self) -> Result<(), Error> {
+		let serialized = spawn_blocking({
+			let index_manager = self.clone();
+			move || {
+				let songs = {
+					let index = index_manager.index.read().unwrap();
+					index.collection.get_all_songs(&index.dictionary)
+				};
+				let mut changelog = index_manager.changelog.write().unwrap();
+				match changelog.update(&songs) {
+					true => Some(bitcode::serialize(&*changelog)),
+					false => None,
+				}
+			}
+		})
+		.await
+		.unwrap();
+
+		let Some(serialized) = serialized else {
+			return Ok(());
+		};
+		let serialized = serialized.map_err(|_| Error::IndexSerializationError)?;
+		tokio::fs::write(&self.changelog_file_path, &serialized[..])
+			.await
+			.map_err(|e| Error::Io(self.changelog_file_path.clone(), e))?;
+		Ok(())
+	}
+
+	async fn try_restore_changelog(&self) -> Result<bool, Error> {
+		match tokio::fs::try_exists(&self.changelog_file_path).await {
+			Ok(true) => (),
+			Ok(false) => return Ok(false),
+			Err(e) => return Err(Error::Io(self.changelog_file_path.clone(), e)),
+		};
+
+		let serialized = tokio::fs::read(&self.changelog_file_path)
+			.await
+			.map_err(|e| Error::Io(self.changelog_file_path.clone(), e))?;
+
+		let changelog = match bitcode::deserialize(&serialized[..]) {
+			Ok(c) => c,
+			Err(_) => return Err(Error::IndexDeserializationError),
+		};
+
+		*self.changelog.write().unwrap() = changelog;
+
+		Ok(true)
+	}
+
+	// Without a token, or with a token the changelog cannot answer for, clients are told
+	// to download the whole collection again
+	pub async fn get_changes(&self, since: Option<String>) -> Changes {
+		spawn_blocking({
+			let index_manager = self.clone();
+			move || {
+				let index = index_manager.index.read().unwrap();
+				let changelog = index_manager.changelog.read().unwrap();
+				let token = changelog.token();
+				let Some((songs, albums)) = since.and_then(|t| changelog.changes_since(&t)) else {
+					return Changes {
+						token,
+						full_sync_required: true,
+						..Default::default()
+					};
+				};
+
+				let get_songs = |paths: Vec<PathBuf>| -> Vec<Song> {
+					paths
+						.into_iter()
+						.filter_map(|p| {
+							let virtual_path = p.get(&index.dictionary)?;
+							let key = SongKey { virtual_path };
+							index.collection.get_song(&index.dictionary, key)
+						})
+						.collect()
+				};
+
+				let get_albums = |ids: Vec<AlbumId>| -> Vec<AlbumHeader> {
+					ids.into_iter()
+						.filter_map(|id| {
+							let album_key = AlbumKey {
+								artists: id
+									.artists
+									.iter()
+									.map(|a| index.dictionary.get(a).map(ArtistKey))
+									.collect::<Option<_>>()?,
+								name: index.dictionary.get(&id.name)?,
+							};
+							index
+								.collection
+								.get_album(&index.dictionary, album_key)
+								.map(|a| a.header)
+						})
+						.collect()
+				};
+
+				Changes {
+					token,
+					full_sync_required: false,
+					added_songs: get_songs(songs.added),
+					modified_songs: get_songs(songs.modified),
+					removed_songs: songs.removed,
+					added_albums: get_albums(albums.added),
+					modified_albums: get_albums(albums.modified),
+					removed_albums: albums.removed,
+				}
+			}
+		})
+		.await
+		.unwrap()
+	}
+
 	async fn try_restore_index(&self) -> Result<bool, Error> {
 		match tokio::fs::try_exists(&self.index_file_path).await {
 			Ok(true) => (),
@@ -88,15 +284,30 @@ impl Manager {
 			Err(e) => return Err(Error::Io(self.index_file_path.clone(), e)),
 		};
 
-		let serialized = tokio::fs::read(&self.index_file_path)
+		let content = tokio::fs::read(&self.index_file_path)
 			.await
 			.map_err(|e| Error::Io(self.index_file_path.clone(), e))?;
 
-		let index = match bitcode::deserialize(&serialized[..]) {
+		let Some(serialized) = strip_index_header(&content) else {
+			info!("Collection index on disk was written in a different format and will be rebuilt");
+			return Ok(false);
+		};
+
+		let mut index: Index = match bitcode::deserialize(serialized) {
 			Ok(i) => i,
 			Err(_) => return Err(Error::IndexDeserializationError),
 		};
 
+		let index = spawn_blocking({
+			let search_directory_path = self.search_directory_path.clone();
+			move || {
+				index.search.restore(&search_directory_path)?;
+				Ok::<_, Error>(index)
+			}
+		})
+		.await
+		.unwrap()?;
+
 		self.replace_index(index).await;
 
 		Ok(true)
@@ -126,6 +337,29 @@ impl Manager {
 		.unwrap()
 	}
 
+	// Directories are ordered according to the songs they contain, and remain listed before songs
+	pub async fn sort_files(&self, files: Vec<File>, order: ListingOrder) -> Vec<File> {
+		if order == ListingOrder::Name {
+			return files;
+		}
+		spawn_blocking({
+			let index_manager = self.clone();
+			move || {
+				let index = index_manager.index.read().unwrap();
+				let (mut directories, mut songs): (Vec<_>, Vec<_>) = files
+					.into_iter()
+					.partition(|f| matches!(f, File::Directory(_)));
+				let key = |f: &File| index.listing_key(f);
+				sort_listing(&mut directories, order, key);
+				sort_listing(&mut songs, order, key);
+				directories.extend(songs);
+				directories
+			}
+		})
+		.await
+		.unwrap()
+	}
+
 	pub async fn get_genres(&self) -> Vec<GenreHeader> {
 		spawn_blocking({
 			let index_manager = self.clone();
@@ -202,6 +436,38 @@ impl Manager {
 		.unwrap()
 	}
 
+	pub async fn get_composers(&self) -> Vec<ArtistHeader> {
+		spawn_blocking({
+			let index_manager = self.clone();
+			move || {
+				let index = index_manager.index.read().unwrap();
+				index.collection.get_composers(&index.dictionary)
+			}
+		})
+		.await
+		.unwrap()
+	}
+
+	pub async fn get_composer(&self, name: String) -> Result<Composer, Error> {
+		spawn_blocking({
+			let index_manager = self.clone();
+			move || {
+				let index = index_manager.index.read().unwrap();
+				let name = index
+					.dictionary
+					.get(name)
+					.ok_or_else(|| Error::ArtistNotFound)?;
+				let artist_key = ArtistKey(name);
+				index
+					.collection
+					.get_composer(&index.dictionary, artist_key)
+					.ok_or_else(|| Error::ArtistNotFound)
+			}
+		})
+		.await
+		.unwrap()
+	}
+
 	pub async fn get_album(&self, artists: Vec<String>, name: String) -> Result<Album, Error> {
 		spawn_blocking({
 			let index_manager = self.clone();
@@ -229,6 +495,63 @@ impl Manager {
 		.unwrap()
 	}
 
+	pub async fn get_artist_by_mbid(&self, mbid: String) -> Result<Artist, Error> {
+		spawn_blocking({
+			let index_manager = self.clone();
+			move || {
+				let index = index_manager.index.read().unwrap();
+				let mbid = index
+					.dictionary
+					.get(mbid.trim().to_ascii_lowercase())
+					.ok_or_else(|| Error::ArtistNotFound)?;
+				index
+					.collection
+					.get_artist_by_mbid(&index.dictionary, mbid)
+					.ok_or_else(|| Error::ArtistNotFound)
+			}
+		})
+		.await
+		.unwrap()
+	}
+
+	pub async fn get_album_by_mbid(&self, mbid: String) -> Result<Album, Error> {
+		spawn_blocking({
+			let index_manager = self.clone();
+			move || {
+				let index = index_manager.index.read().unwrap();
+				let mbid = index
+					.dictionary
+					.get(mbid.trim().to_ascii_lowercase())
+					.ok_or_else(|| Error::AlbumNotFound)?;
+				index
+					.collection
+					.get_album_by_mbid(&index.dictionary, mbid)
+					.ok_or_else(|| Error::AlbumNotFound)
+			}
+		})
+		.await
+		.unwrap()
+	}
+
+	pub async fn get_song_by_mbid(&self, mbid: String) -> Result<Song, Error> {
+		spawn_blocking({
+			let index_manager = self.clone();
+			move || {
+				let index = index_manager.index.read().unwrap();
+				let mbid = index
+					.dictionary
+					.get(mbid.trim().to_ascii_lowercase())
+					.ok_or_else(|| Error::SongNotFound)?;
+				index
+					.collection
+					.get_song_by_mbid(&index.dictionary, mbid)
+					.ok_or_else(|| Error::SongNotFound)
+			}
+		})
+		.await
+		.unwrap()
+	}
+
 	pub async fn get_random_albums(
 		&self,
 		seed: Option<u64>,
@@ -266,6 +589,73 @@ impl Manager {
 		.unwrap()
 	}
 
+	pub async fn get_mix(
+		&self,
+		genre: Option<String>,
+		decade: Option<i64>,
+		seed: Option<u64>,
+		play_counts: HashMap<PathBuf, u32>,
+		count: usize,
+	) -> Result<Vec<PathBuf>, Error> {
+		spawn_blocking({
+			let index_manager = self.clone();
+			move || {
+				let index = index_manager.index.read().unwrap();
+				let play_counts = play_counts
+					.into_iter()
+					.filter_map(|(path, count)| {
+						let virtual_path = path.get(&index.dictionary)?;
+						Some((SongKey { virtual_path }, count))
+					})
+					.collect();
+				let genre_key = match genre {
+					Some(name) => Some(GenreKey(
+						index
+							.dictionary
+							.get(&name)
+							.ok_or_else(|| Error::GenreNotFound)?,
+					)),
+					None => None,
+				};
+				let songs = index
+					.collection
+					.get_mix(genre_key, decade, seed, &play_counts, count)
+					.ok_or_else(|| Error::GenreNotFound)?;
+				Ok(songs
+					.into_iter()
+					.map(|k| PathBuf::from(index.dictionary.resolve(&k.virtual_path.0)))
+					.collect())
+			}
+		})
+		.await
+		.unwrap()
+	}
+
+	pub async fn get_all_songs(&self) -> Vec<Song> {
+		spawn_blocking({
+			let index_manager = self.clone();
+			move || {
+				let index = index_manager.index.read().unwrap();
+				index.collection.get_all_songs(&index.dictionary)
+			}
+		})
+		.await
+		.unwrap()
+	}
+
+	// Most recently added first
+	pub async fn get_recent_songs(&self) -> Vec<PathBuf> {
+		spawn_blocking({
+			let index_manager = self.clone();
+			move || {
+				let index = index_manager.index.read().unwrap();
+				index.collection.get_recent_songs(&index.dictionary)
+			}
+		})
+		.await
+		.unwrap()
+	}
+
 	pub async fn get_songs(&self, virtual_paths: Vec<PathBuf>) -> Vec<Result<Song, Error>> {
 		spawn_blocking({
 			let index_manager = self.clone();
@@ -311,6 +701,89 @@ pub struct Index {
 	pub search: search::Search,
 }
 
+impl Index {
+	fn replace_directories(
+		&self,
+		virtual_paths: &[PathBuf],
+		directories: Vec<scanner::Directory>,
+		songs: Vec<scanner::Song>,
+	) -> Index {
+		let mut builder = Builder::new();
+		let is_replaced = |path: &Path| virtual_paths.iter().any(|p| path.starts_with(p));
+
+		for path in self.browser.directories(&self.dictionary) {
+			if !is_replaced(&path) {
+				builder.add_directory(scanner::Directory { virtual_path: path });
+			}
+		}
+
+		for song in self.collection.get_all_songs(&self.dictionary) {
+			if !is_replaced(&song.virtual_path) {
+				builder.add_song(song.into());
+			}
+		}
+
+		for directory in directories {
+			builder.add_directory(directory);
+		}
+
+		// Songs which were already indexed keep the time they were first added
+		let now = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map(|d| d.as_secs() as i64)
+			.unwrap_or_default();
+		for mut song in songs {
+			let previous_song = song
+				.virtual_path
+				.get(&self.dictionary)
+				.and_then(|virtual_path| {
+					let key = SongKey { virtual_path };
+					self.collection.get_song(&self.dictionary, key)
+				});
+			song.date_added = previous_song.map_or(now, |s| s.date_added);
+			builder.add_song(song);
+		}
+
+		builder.build()
+	}
+
+	// Directories use the earliest year, latest date added and first album artist of their songs
+	fn listing_key(&self, file: &File) -> ListingKey {
+		let paths = match file {
+			File::Song(path) => vec![path.clone()],
+			File::Directory(path) => self
+				.browser
+				.flatten(&self.dictionary, path)
+				.unwrap_or_default(),
+		};
+
+		let songs = paths
+			.iter()
+			.filter_map(|p| p.get(&self.dictionary))
+			.filter_map(|virtual_path| {
+				let key = SongKey { virtual_path };
+				self.collection.get_song(&self.dictionary, key)
+			});
+
+		let mut key = ListingKey::default();
+		for song in songs {
+			key.year = match (key.year, song.year) {
+				(Some(a), Some(b)) => Some(a.min(b)),
+				(a, b) => a.or(b),
+			};
+			key.date_added = key.date_added.max(song.date_added);
+			if key.album_artist.is_none() {
+				let artists = match song.album_artists.is_empty() {
+					true => &song.artists,
+					false => &song.album_artists,
+				};
+				key.album_artist = (!artists.is_empty()).then(|| artists.join(", "));
+			}
+		}
+		key
+	}
+}
+
 impl Default for Index {
 	fn default() -> Self {
 		Self {
@@ -322,6 +795,57 @@ impl Default for Index {
 	}
 }
 
+impl From<Song> for scanner::Song {
+	fn from(s: Song) -> Self {
+		let audible_range = match (s.audible_start, s.audible_end) {
+			(Some(start), Some(end)) => Some(silence::AudibleRange { start, end }),
+			_ => None,
+		};
+		let segment = s.segment();
+		let replay_gain = s.replay_gain();
+		Self {
+			real_path: s.real_path,
+			virtual_path: s.virtual_path,
+			track_number: s.track_number,
+			disc_number: s.disc_number,
+			disc_subtitle: s.disc_subtitle,
+			title: s.title,
+			artists: s.artists,
+			album_artists: s.album_artists,
+			year: s.year,
+			album: s.album,
+			artwork: s.artwork,
+			lyrics: s.lyrics,
+			duration: s.duration,
+			lyricists: s.lyricists,
+			composers: s.composers,
+			genres: s.genres,
+			labels: s.labels,
+			compilation: s.compilation,
+			conductors: s.conductors,
+			performers: s.performers,
+			work: s.work,
+			movement: s.movement,
+			bpm: s.bpm,
+			initial_key: s.initial_key,
+			replay_gain,
+			musicbrainz_recording_id: s.musicbrainz_recording_id,
+			musicbrainz_release_id: s.musicbrainz_release_id,
+			musicbrainz_artist_ids: s.musicbrainz_artist_ids,
+			date_added: s.date_added,
+			audible_range,
+			fingerprint: s.fingerprint,
+			segment,
+		}
+	}
+}
+
+fn strip_index_header(content: &[u8]) -> Option<&[u8]> {
+	let content = content.strip_prefix(INDEX_FILE_MAGIC)?;
+	let (version, serialized) = content.split_first_chunk::<4>()?;
+	(u32::from_le_bytes(*version) == INDEX_FORMAT_VERSION).then_some(serialized)
+}
+
 #[derive(Clone)]
 pub struct Builder {
 	dictionary_builder: dictionary::Builder,
@@ -381,8 +905,52 @@ mod test {
 	async fn can_persist_index() {
 		let ctx = test::ContextBuilder::new(test_name!()).build().await;
 		assert_eq!(ctx.index_manager.try_restore_index().await.unwrap(), false);
-		let index = index::Builder::new().build();
-		ctx.index_manager.persist_index(&index).await.unwrap();
+		let mut index = index::Builder::new().build();
+		ctx.index_manager.persist_index(&mut index).await.unwrap();
 		assert_eq!(ctx.index_manager.try_restore_index().await.unwrap(), true);
 	}
+
+	#[tokio::test]
+	async fn ignores_index_from_other_format_version() {
+		let ctx = test::ContextBuilder::new(test_name!()).build().await;
+		let mut index = index::Builder::new().build();
+		ctx.index_manager.persist_index(&mut index).await.unwrap();
+
+		let index_file_path = &ctx.index_manager.index_file_path;
+		let mut content = std::fs::read(index_file_path).unwrap();
+		let version_offset = index::INDEX_FILE_MAGIC.len();
+		content[version_offset..version_offset + 4]
+			.copy_from_slice(&(index::INDEX_FORMAT_VERSION + 1).to_le_bytes());
+		std::fs::write(index_file_path, &content).unwrap();
+		assert_eq!(ctx.index_manager.try_restore_index().await.unwrap(), false);
+
+		std::fs::write(index_file_path, b"not an index").unwrap();
+		assert_eq!(ctx.index_manager.try_restore_index().await.unwrap(), false);
+	}
+
+	#[tokio::test]
+	async fn can_list_changes_across_restarts() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.mount("root", "test-data/small-collection")
+			.build()
+			.await;
+
+		let initial = ctx.index_manager.get_changes(None).await;
+		assert!(initial.full_sync_required);
+
+		ctx.scanner.run_scan().await.unwrap();
+		let changes = ctx
+			.index_manager
+			.get_changes(Some(initial.token.clone()))
+			.await;
+		assert!(!changes.full_sync_required);
+		assert_eq!(changes.added_songs.len(), 13);
+		assert_eq!(changes.added_albums.len(), 3);
+		assert!(changes.removed_songs.is_empty());
+
+		let index_directory = ctx.index_manager.index_file_path.parent().unwrap();
+		let index_manager = index::Manager::new(index_directory).await.unwrap();
+		let restored = index_manager.get_changes(Some(initial.token)).await;
+		assert_eq!(restored, changes);
+	}
 }
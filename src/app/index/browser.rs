@@ -92,6 +92,13 @@ impl Browser {
 		Ok(files)
 	}
 
+	pub fn directories(&self, dictionary: &Dictionary) -> Vec<PathBuf> {
+		self.directories
+			.keys()
+			.map(|k| PathBuf::from(dictionary.resolve(&k.0)))
+			.collect()
+	}
+
 	pub fn flatten<P: AsRef<Path>>(
 		&self,
 		dictionary: &Dictionary,
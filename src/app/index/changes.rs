@@ -0,0 +1,310 @@
+use std::{
+	collections::{hash_map::Entry, HashMap, VecDeque},
+	hash::{DefaultHasher, Hash, Hasher},
+	path::PathBuf,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::index::{AlbumHeader, Song};
+
+// Number of index generations whose changes are remembered. Clients which fell further
+// behind than this need to download the whole collection again.
+const MAX_GENERATIONS: usize = 100;
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct AlbumId {
+	pub name: String,
+	pub artists: Vec<String>,
+}
+
+impl AlbumId {
+	pub fn from_song(song: &Song) -> Option<Self> {
+		let artists = match song.album_artists.is_empty() {
+			true => &song.artists,
+			false => &song.album_artists,
+		};
+		if artists.is_empty() {
+			return None;
+		}
+		song.album.as_ref().map(|name| Self {
+			name: name.clone(),
+			artists: artists.clone(),
+		})
+	}
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Delta<K> {
+	pub added: Vec<K>,
+	pub modified: Vec<K>,
+	pub removed: Vec<K>,
+}
+
+impl<K> Default for Delta<K> {
+	fn default() -> Self {
+		Self {
+			added: Vec::new(),
+			modified: Vec::new(),
+			removed: Vec::new(),
+		}
+	}
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Changes {
+	pub token: String,
+	pub full_sync_required: bool,
+	pub added_songs: Vec<Song>,
+	pub modified_songs: Vec<Song>,
+	pub removed_songs: Vec<PathBuf>,
+	pub added_albums: Vec<AlbumHeader>,
+	pub modified_albums: Vec<AlbumHeader>,
+	pub removed_albums: Vec<AlbumId>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+enum Change {
+	Added,
+	Modified,
+	Removed,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Generation {
+	number: u64,
+	songs: Vec<(PathBuf, Change)>,
+	albums: Vec<(AlbumId, Change)>,
+}
+
+// Keeps track of what changed in the collection between successive index generations.
+// Songs and albums are compared using a hash of their metadata, so the previous index
+// does not need to be kept around.
+#[derive(Serialize, Deserialize)]
+pub struct Changelog {
+	epoch: u64,
+	generation: u64,
+	songs: HashMap<PathBuf, u64>,
+	albums: HashMap<AlbumId, u64>,
+	history: VecDeque<Generation>,
+}
+
+impl Changelog {
+	pub fn new(songs: &[Song]) -> Self {
+		let (songs, albums) = snapshot(songs);
+		Self {
+			// Tokens issued before the changelog was lost or reset are never accepted again
+			epoch: rand::random(),
+			generation: 0,
+			songs,
+			albums,
+			history: VecDeque::new(),
+		}
+	}
+
+	pub fn token(&self) -> String {
+		format!("{:x}-{}", self.epoch, self.generation)
+	}
+
+	// Returns whether the collection changed since the last update
+	pub fn update(&mut self, songs: &[Song]) -> bool {
+		let (songs, albums) = snapshot(songs);
+		let song_changes = diff(&self.songs, &songs);
+		let album_changes = diff(&self.albums, &albums);
+		if song_changes.is_empty() && album_changes.is_empty() {
+			return false;
+		}
+
+		self.generation += 1;
+		self.songs = songs;
+		self.albums = albums;
+		self.history.push_back(Generation {
+			number: self.generation,
+			songs: song_changes,
+			albums: album_changes,
+		});
+		while self.history.len() > MAX_GENERATIONS {
+			self.history.pop_front();
+		}
+
+		true
+	}
+
+	// Lists changes since the generation a token was issued for. Returns `None` for tokens
+	// which are malformed, were issued by a different changelog, or are too old.
+	pub fn changes_since(&self, token: &str) -> Option<(Delta<PathBuf>, Delta<AlbumId>)> {
+		let (epoch, generation) = token.split_once('-')?;
+		let epoch = u64::from_str_radix(epoch, 16).ok()?;
+		let generation = generation.parse::<u64>().ok()?;
+		if epoch != self.epoch || generation > self.generation {
+			return None;
+		}
+
+		let history = self
+			.history
+			.iter()
+			.filter(|g| g.number > generation)
+			.collect::<Vec<_>>();
+		if history.first().map(|g| g.number) != Some(generation + 1)
+			&& generation != self.generation
+		{
+			return None;
+		}
+
+		let songs = summarize(history.iter().map(|g| &g.songs), &self.songs);
+		let albums = summarize(history.iter().map(|g| &g.albums), &self.albums);
+		Some((songs, albums))
+	}
+}
+
+fn snapshot(songs: &[Song]) -> (HashMap<PathBuf, u64>, HashMap<AlbumId, u64>) {
+	let mut song_hashes = HashMap::with_capacity(songs.len());
+	let mut album_hashes = HashMap::<AlbumId, u64>::new();
+	for song in songs {
+		let mut hasher = DefaultHasher::new();
+		song.hash(&mut hasher);
+		let hash = hasher.finish();
+		song_hashes.insert(song.virtual_path.clone(), hash);
+		if let Some(album_id) = AlbumId::from_song(song) {
+			// Order-independent combination of the album's songs
+			let album_hash = album_hashes.entry(album_id).or_default();
+			*album_hash = album_hash.wrapping_add(hash);
+		}
+	}
+	(song_hashes, album_hashes)
+}
+
+fn diff<K: Clone + Eq + Hash>(old: &HashMap<K, u64>, new: &HashMap<K, u64>) -> Vec<(K, Change)> {
+	let mut changes = Vec::new();
+	for (key, hash) in new {
+		match old.get(key) {
+			None => changes.push((key.clone(), Change::Added)),
+			Some(old_hash) if old_hash != hash => changes.push((key.clone(), Change::Modified)),
+			Some(_) => (),
+		}
+	}
+	for key in old.keys() {
+		if !new.contains_key(key) {
+			changes.push((key.clone(), Change::Removed));
+		}
+	}
+	changes
+}
+
+// Collapses successive changes to the same item into a single one, based on the first
+// change the client missed and whether the item still exists.
+fn summarize<'a, K: 'a + Clone + Eq + Hash + Ord>(
+	history: impl Iterator<Item = &'a Vec<(K, Change)>>,
+	current: &HashMap<K, u64>,
+) -> Delta<K> {
+	let mut first_changes = HashMap::new();
+	for changes in history {
+		for (key, change) in changes {
+			if let Entry::Vacant(e) = first_changes.entry(key) {
+				e.insert(*change);
+			}
+		}
+	}
+
+	let mut delta = Delta::default();
+	for (key, change) in first_changes {
+		match (current.contains_key(key), change) {
+			(true, Change::Added) => delta.added.push(key.clone()),
+			(true, _) => delta.modified.push(key.clone()),
+			(false, Change::Added) => (),
+			(false, _) => delta.removed.push(key.clone()),
+		}
+	}
+	delta.added.sort();
+	delta.modified.sort();
+	delta.removed.sort();
+	delta
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn make_song(path: &str, album: &str, title: &str) -> Song {
+		Song {
+			virtual_path: PathBuf::from(path),
+			album: Some(album.to_owned()),
+			artists: vec!["Stratovarius".to_owned()],
+			title: Some(title.to_owned()),
+			..Default::default()
+		}
+	}
+
+	fn album(name: &str) -> AlbumId {
+		AlbumId {
+			name: name.to_owned(),
+			artists: vec!["Stratovarius".to_owned()],
+		}
+	}
+
+	#[test]
+	fn lists_changes_since_token() {
+		let a = make_song("a.mp3", "Elements", "Eagleheart");
+		let b = make_song("b.mp3", "Elements", "Soul of a Vagabond");
+		let c = make_song("c.mp3", "Visions", "Black Diamond");
+
+		let mut changelog = Changelog::new(&[a.clone(), b.clone()]);
+		let initial_token = changelog.token();
+		assert!(!changelog.update(&[a.clone(), b.clone()]));
+		assert_eq!(changelog.token(), initial_token);
+
+		let b_retitled = make_song("b.mp3", "Elements", "Fantasia");
+		assert!(changelog.update(&[a.clone(), b_retitled.clone(), c.clone()]));
+		let middle_token = changelog.token();
+		assert!(changelog.update(&[b_retitled.clone(), c.clone()]));
+
+		let (songs, albums) = changelog.changes_since(&initial_token).unwrap();
+		assert_eq!(songs.added, vec![PathBuf::from("c.mp3")]);
+		assert_eq!(songs.modified, vec![PathBuf::from("b.mp3")]);
+		assert_eq!(songs.removed, vec![PathBuf::from("a.mp3")]);
+		assert_eq!(albums.added, vec![album("Visions")]);
+		assert_eq!(albums.modified, vec![album("Elements")]);
+		assert!(albums.removed.is_empty());
+
+		let (songs, albums) = changelog.changes_since(&middle_token).unwrap();
+		assert!(songs.added.is_empty());
+		assert!(songs.modified.is_empty());
+		assert_eq!(songs.removed, vec![PathBuf::from("a.mp3")]);
+		assert_eq!(albums.modified, vec![album("Elements")]);
+
+		let (songs, albums) = changelog.changes_since(&changelog.token()).unwrap();
+		assert_eq!(songs, Delta::default());
+		assert_eq!(albums, Delta::default());
+	}
+
+	#[test]
+	fn ignores_short_lived_songs() {
+		let a = make_song("a.mp3", "Elements", "Eagleheart");
+		let b = make_song("b.mp3", "Elements", "Fantasia");
+		let mut changelog = Changelog::new(std::slice::from_ref(&a));
+		let token = changelog.token();
+		changelog.update(&[a.clone(), b]);
+		changelog.update(&[a]);
+
+		let (songs, _) = changelog.changes_since(&token).unwrap();
+		assert_eq!(songs, Delta::default());
+	}
+
+	#[test]
+	fn rejects_unknown_tokens() {
+		let a = make_song("a.mp3", "Elements", "Eagleheart");
+		let mut changelog = Changelog::new(&[]);
+		let first_token = changelog.token();
+		for i in 0..=MAX_GENERATIONS {
+			let song = make_song("b.mp3", "Elements", &i.to_string());
+			changelog.update(&[a.clone(), song]);
+		}
+
+		assert!(changelog.changes_since(&first_token).is_none());
+		assert!(changelog.changes_since("garbage").is_none());
+		assert!(changelog
+			.changes_since(&Changelog::new(&[]).token())
+			.is_none());
+		assert!(changelog.changes_since(&changelog.token()).is_some());
+	}
+}
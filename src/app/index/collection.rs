@@ -5,12 +5,14 @@ use std::{
 	path::PathBuf,
 };
 
-use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use lasso2::Spur;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
 use rayon::slice::ParallelSliceMut;
 use serde::{Deserialize, Serialize};
 use tinyvec::TinyVec;
 use unicase::UniCase;
 
+use crate::app::formats;
 use crate::app::index::dictionary::Dictionary;
 use crate::app::index::storage::{self, AlbumKey, ArtistKey, GenreKey, SongKey};
 
@@ -19,6 +21,9 @@ use super::{dictionary, storage::fetch_song};
 #[derive(Debug, Default, PartialEq, Eq)]
 pub struct GenreHeader {
 	pub name: String,
+	pub num_albums: u32,
+	pub num_artists: u32,
+	pub num_songs: u32,
 }
 
 #[derive(Debug, Default, PartialEq, Eq)]
@@ -39,6 +44,7 @@ pub struct ArtistHeader {
 	pub num_albums_as_lyricist: u32,
 	pub num_songs_by_genre: HashMap<String, u32>,
 	pub num_songs: u32,
+	pub musicbrainz_id: Option<String>,
 }
 
 #[derive(Debug, Default, PartialEq, Eq)]
@@ -47,6 +53,20 @@ pub struct Artist {
 	pub albums: Vec<Album>,
 }
 
+// Songs written by a composer, grouped by the work they belong to
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Composer {
+	pub header: ArtistHeader,
+	pub works: Vec<Work>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Work {
+	// Songs which are not part of a work are listed under `None`
+	pub name: Option<String>,
+	pub songs: Vec<Song>,
+}
+
 #[derive(Debug, Default, PartialEq, Eq)]
 pub struct AlbumHeader {
 	pub name: String,
@@ -54,32 +74,85 @@ pub struct AlbumHeader {
 	pub artists: Vec<String>,
 	pub year: Option<i64>,
 	pub date_added: i64,
+	pub compilation: bool,
+	pub musicbrainz_release_id: Option<String>,
 }
 
 #[derive(Debug, Default, PartialEq, Eq)]
 pub struct Album {
 	pub header: AlbumHeader,
+	pub discs: Vec<Disc>,
 	pub songs: Vec<Song>,
 }
 
-#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+// Section of an album's song list sharing the same disc number
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Disc {
+	pub number: Option<i64>,
+	pub subtitle: Option<String>,
+	pub num_songs: u32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Song {
 	pub real_path: PathBuf,
 	pub virtual_path: PathBuf,
 	pub track_number: Option<i64>,
 	pub disc_number: Option<i64>,
+	pub disc_subtitle: Option<String>,
 	pub title: Option<String>,
 	pub artists: Vec<String>,
 	pub album_artists: Vec<String>,
 	pub year: Option<i64>,
 	pub album: Option<String>,
 	pub artwork: Option<PathBuf>,
+	pub lyrics: Option<PathBuf>,
 	pub duration: Option<i64>,
 	pub lyricists: Vec<String>,
 	pub composers: Vec<String>,
 	pub genres: Vec<String>,
 	pub labels: Vec<String>,
+	pub compilation: bool,
+	pub conductors: Vec<String>,
+	pub performers: Vec<String>,
+	pub work: Option<String>,
+	pub movement: Option<String>,
+	pub bpm: Option<i64>,
+	pub initial_key: Option<String>,
+	// Gains in hundredths of a decibel, peaks in millionths of full scale
+	pub track_gain: Option<i32>,
+	pub track_peak: Option<u32>,
+	pub album_gain: Option<i32>,
+	pub album_peak: Option<u32>,
+	pub musicbrainz_recording_id: Option<String>,
+	pub musicbrainz_release_id: Option<String>,
+	pub musicbrainz_artist_ids: Vec<String>,
 	pub date_added: i64,
+	pub audible_start: Option<i64>,
+	pub audible_end: Option<i64>,
+	pub fingerprint: Option<u64>,
+	// Offsets in milliseconds of the part of the file holding this song, for songs split from a
+	// CUE sheet
+	pub segment_start: Option<i64>,
+	pub segment_end: Option<i64>,
+}
+
+impl Song {
+	pub fn segment(&self) -> Option<formats::Segment> {
+		self.segment_start.map(|start| formats::Segment {
+			start,
+			end: self.segment_end,
+		})
+	}
+
+	pub fn replay_gain(&self) -> formats::ReplayGain {
+		formats::ReplayGain {
+			track_gain: self.track_gain,
+			track_peak: self.track_peak,
+			album_gain: self.album_gain,
+			album_peak: self.album_peak,
+		}
+	}
 }
 
 #[derive(Default, Serialize, Deserialize)]
@@ -89,6 +162,9 @@ pub struct Collection {
 	genres: HashMap<GenreKey, storage::Genre>,
 	songs: HashMap<SongKey, storage::Song>,
 	recent_albums: Vec<AlbumKey>,
+	artists_by_mbid: HashMap<Spur, ArtistKey>,
+	albums_by_mbid: HashMap<Spur, AlbumKey>,
+	songs_by_mbid: HashMap<Spur, SongKey>,
 }
 
 impl Collection {
@@ -136,6 +212,61 @@ impl Collection {
 		})
 	}
 
+	pub fn get_composers(&self, dictionary: &Dictionary) -> Vec<ArtistHeader> {
+		let mut composers = self
+			.artists
+			.values()
+			.filter(|a| !a.albums_as_composer.is_empty())
+			.map(|a| make_artist_header(a, dictionary))
+			.collect::<Vec<_>>();
+		let collator = dictionary::make_collator();
+		composers.sort_by(|a, b| collator.compare(&a.name, &b.name));
+		composers
+	}
+
+	pub fn get_composer(&self, dictionary: &Dictionary, artist_key: ArtistKey) -> Option<Composer> {
+		let artist = self.artists.get(&artist_key)?;
+
+		let mut song_keys = artist
+			.albums_as_composer
+			.iter()
+			.filter_map(|album_key| self.albums.get(album_key))
+			.flat_map(|album| album.songs.iter().copied())
+			.filter(|song_key| {
+				self.songs
+					.get(song_key)
+					.is_some_and(|s| s.composers.contains(&artist_key))
+			})
+			.collect::<Vec<_>>();
+		self.sort_songs(&mut song_keys, dictionary);
+
+		let mut works: Vec<Work> = vec![];
+		for song in song_keys
+			.into_iter()
+			.filter_map(|k| self.get_song(dictionary, k))
+		{
+			match works.iter_mut().find(|w| w.name == song.work) {
+				Some(work) => work.songs.push(song),
+				None => works.push(Work {
+					name: song.work.clone(),
+					songs: vec![song],
+				}),
+			}
+		}
+
+		let collator = dictionary::make_collator();
+		works.sort_by(|a, b| match (&a.name, &b.name) {
+			(Some(a), Some(b)) => collator.compare(a, b),
+			// Songs outside of a work come last
+			(a, b) => a.is_none().cmp(&b.is_none()),
+		});
+
+		Some(Composer {
+			header: make_artist_header(artist, dictionary),
+			works,
+		})
+	}
+
 	pub fn get_album(&self, dictionary: &Dictionary, album_key: AlbumKey) -> Option<Album> {
 		self.albums.get(&album_key).map(|a| {
 			let mut songs = a
@@ -153,8 +284,24 @@ impl Collection {
 
 			songs.sort_by_key(|s| (s.disc_number.unwrap_or(-1), s.track_number.unwrap_or(-1)));
 
+			let mut discs: Vec<Disc> = vec![];
+			for song in &songs {
+				match discs.last_mut() {
+					Some(disc) if disc.number == song.disc_number => {
+						disc.subtitle = disc.subtitle.take().or(song.disc_subtitle.clone());
+						disc.num_songs += 1;
+					}
+					_ => discs.push(Disc {
+						number: song.disc_number,
+						subtitle: song.disc_subtitle.clone(),
+						num_songs: 1,
+					}),
+				}
+			}
+
 			Album {
 				header: make_album_header(a, dictionary),
+				discs,
 				songs,
 			}
 		})
@@ -199,6 +346,54 @@ impl Collection {
 			.collect()
 	}
 
+	// Songs are drawn at random, with frequently played songs less likely to come up
+	pub fn get_mix(
+		&self,
+		genre: Option<GenreKey>,
+		decade: Option<i64>,
+		seed: Option<u64>,
+		play_counts: &HashMap<SongKey, u32>,
+		count: usize,
+	) -> Option<Vec<SongKey>> {
+		let candidates = match genre {
+			Some(genre_key) => self.genres.get(&genre_key)?.songs.clone(),
+			None => self.songs.keys().copied().collect(),
+		};
+
+		let mut songs = candidates
+			.into_iter()
+			.filter(|k| match decade {
+				Some(d) => self
+					.songs
+					.get(k)
+					.and_then(|s| s.year)
+					.is_some_and(|y| y.div_euclid(10) * 10 == d),
+				None => true,
+			})
+			.collect::<Vec<_>>();
+
+		let mut rng = match seed {
+			Some(seed) => StdRng::seed_from_u64(seed),
+			None => StdRng::from_entropy(),
+		};
+		songs.sort_unstable_by_key(|k| k.virtual_path);
+
+		// Weighted random order where a song's weight is 1 / (1 + play count),
+		// by sorting on u^(1 / weight) for a uniform u (Efraimidis-Spirakis)
+		let mut songs = songs
+			.into_iter()
+			.map(|k| {
+				let play_count = play_counts.get(&k).copied().unwrap_or_default();
+				let sort_key = rng.gen::<f64>().powf(1.0 + play_count as f64);
+				(sort_key, k)
+			})
+			.collect::<Vec<_>>();
+		songs.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+		songs.truncate(count);
+
+		Some(songs.into_iter().map(|(_, k)| k).collect())
+	}
+
 	pub fn get_genres(&self, dictionary: &Dictionary) -> Vec<GenreHeader> {
 		let mut genres = self
 			.genres
@@ -262,6 +457,21 @@ impl Collection {
 		})
 	}
 
+	pub fn get_artist_by_mbid(&self, dictionary: &Dictionary, mbid: Spur) -> Option<Artist> {
+		let artist_key = self.artists_by_mbid.get(&mbid)?;
+		self.get_artist(dictionary, *artist_key)
+	}
+
+	pub fn get_album_by_mbid(&self, dictionary: &Dictionary, mbid: Spur) -> Option<Album> {
+		let album_key = self.albums_by_mbid.get(&mbid)?;
+		self.get_album(dictionary, album_key.clone())
+	}
+
+	pub fn get_song_by_mbid(&self, dictionary: &Dictionary, mbid: Spur) -> Option<Song> {
+		let song_key = self.songs_by_mbid.get(&mbid)?;
+		self.get_song(dictionary, *song_key)
+	}
+
 	pub fn num_songs(&self) -> usize {
 		self.songs.len()
 	}
@@ -270,6 +480,24 @@ impl Collection {
 		self.songs.get(&song_key).map(|s| fetch_song(dictionary, s))
 	}
 
+	pub fn get_all_songs(&self, dictionary: &Dictionary) -> Vec<Song> {
+		self.songs
+			.values()
+			.map(|s| fetch_song(dictionary, s))
+			.collect()
+	}
+
+	// Songs added at the same time are listed in collection order
+	pub fn get_recent_songs(&self, dictionary: &Dictionary) -> Vec<PathBuf> {
+		let mut songs = self.songs.keys().copied().collect::<Vec<_>>();
+		self.sort_songs(&mut songs, dictionary);
+		songs.sort_by_key(|k| self.songs.get(k).map(|s| -s.date_added).unwrap_or_default());
+		songs
+			.into_iter()
+			.map(|k| PathBuf::from(dictionary.resolve(&k.virtual_path.0)))
+			.collect()
+	}
+
 	pub fn sort_songs(&self, songs: &mut [SongKey], dictionary: &Dictionary) {
 		songs.par_sort_unstable_by(|a, b| self.compare_songs(*a, *b, dictionary));
 	}
@@ -326,6 +554,70 @@ impl Collection {
 	}
 }
 
+// Orders for album and file listings, which are otherwise listed alphabetically
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ListingOrder {
+	#[default]
+	Name,
+	Year,
+	DateAdded,
+	AlbumArtist,
+	Random(u64),
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ListingKey {
+	pub year: Option<i64>,
+	pub date_added: i64,
+	pub album_artist: Option<String>,
+}
+
+impl AlbumHeader {
+	pub fn listing_key(&self) -> ListingKey {
+		ListingKey {
+			year: self.year,
+			date_added: self.date_added,
+			album_artist: (!self.artists.is_empty()).then(|| self.artists.join(", ")),
+		}
+	}
+}
+
+// Items are expected in alphabetical order, which is preserved between items with equal keys
+pub fn sort_listing<T, F>(items: &mut Vec<T>, order: ListingOrder, key: F)
+where
+	F: Fn(&T) -> ListingKey,
+{
+	let collator = match order {
+		ListingOrder::Name => return,
+		ListingOrder::Random(seed) => {
+			items.shuffle(&mut StdRng::seed_from_u64(seed));
+			return;
+		}
+		ListingOrder::Year | ListingOrder::DateAdded | ListingOrder::AlbumArtist => {
+			dictionary::make_collator()
+		}
+	};
+
+	let mut keyed = std::mem::take(items)
+		.into_iter()
+		.map(|i| (key(&i), i))
+		.collect::<Vec<_>>();
+
+	keyed.sort_by(|(a, _), (b, _)| match order {
+		ListingOrder::Year => (a.year.is_none(), a.year).cmp(&(b.year.is_none(), b.year)),
+		ListingOrder::DateAdded => b.date_added.cmp(&a.date_added),
+		ListingOrder::AlbumArtist => match (&a.album_artist, &b.album_artist) {
+			(Some(a), Some(b)) => collator.compare(a, b),
+			(Some(_), None) => Ordering::Less,
+			(None, Some(_)) => Ordering::Greater,
+			(None, None) => Ordering::Equal,
+		},
+		ListingOrder::Name | ListingOrder::Random(_) => Ordering::Equal,
+	});
+
+	*items = keyed.into_iter().map(|(_, i)| i).collect();
+}
+
 fn make_album_header(album: &storage::Album, dictionary: &Dictionary) -> AlbumHeader {
 	AlbumHeader {
 		name: dictionary.resolve(&album.name).to_string(),
@@ -341,6 +633,10 @@ fn make_album_header(album: &storage::Album, dictionary: &Dictionary) -> AlbumHe
 			.collect(),
 		year: album.year,
 		date_added: album.date_added,
+		compilation: album.compilation,
+		musicbrainz_release_id: album
+			.musicbrainz_release_id
+			.map(|id| dictionary.resolve(&id).to_string()),
 	}
 }
 
@@ -357,12 +653,18 @@ fn make_artist_header(artist: &storage::Artist, dictionary: &Dictionary) -> Arti
 			.map(|(genre, num)| (dictionary.resolve(genre).to_string(), *num))
 			.collect(),
 		num_songs: artist.num_songs,
+		musicbrainz_id: artist
+			.musicbrainz_id
+			.map(|id| dictionary.resolve(&id).to_string()),
 	}
 }
 
 fn make_genre_header(genre: &storage::Genre, dictionary: &Dictionary) -> GenreHeader {
 	GenreHeader {
 		name: dictionary.resolve(&genre.name).to_string(),
+		num_albums: genre.albums.len() as u32,
+		num_artists: genre.artists.len() as u32,
+		num_songs: genre.songs.len() as u32,
 	}
 }
 
@@ -372,6 +674,9 @@ pub struct Builder {
 	albums: HashMap<AlbumKey, storage::Album>,
 	genres: HashMap<GenreKey, storage::Genre>,
 	songs: HashMap<SongKey, storage::Song>,
+	artists_by_mbid: HashMap<Spur, ArtistKey>,
+	albums_by_mbid: HashMap<Spur, AlbumKey>,
+	songs_by_mbid: HashMap<Spur, SongKey>,
 }
 
 impl Builder {
@@ -380,12 +685,13 @@ impl Builder {
 		self.add_song_to_artists(song);
 		self.add_song_to_genres(song);
 
-		self.songs.insert(
-			SongKey {
-				virtual_path: song.virtual_path,
-			},
-			song.clone(),
-		);
+		let song_key = SongKey {
+			virtual_path: song.virtual_path,
+		};
+		if let Some(mbid) = song.musicbrainz_recording_id {
+			self.songs_by_mbid.entry(mbid).or_insert(song_key);
+		}
+		self.songs.insert(song_key, song.clone());
 	}
 
 	pub fn build(self) -> Collection {
@@ -403,6 +709,9 @@ impl Builder {
 			genres: self.genres,
 			songs: self.songs,
 			recent_albums,
+			artists_by_mbid: self.artists_by_mbid,
+			albums_by_mbid: self.albums_by_mbid,
+			songs_by_mbid: self.songs_by_mbid,
 		}
 	}
 
@@ -449,6 +758,15 @@ impl Builder {
 			}
 		}
 
+		// Artist identifiers can only be attributed when there is one per artist
+		if song.artists.len() == song.musicbrainz_artist_ids.len() {
+			for (artist_key, mbid) in song.artists.iter().zip(&song.musicbrainz_artist_ids) {
+				self.artists_by_mbid.entry(*mbid).or_insert(*artist_key);
+				let artist = self.get_or_create_artist(*artist_key);
+				artist.musicbrainz_id = artist.musicbrainz_id.or(Some(*mbid));
+			}
+		}
+
 		for artist_key in all_artists {
 			let artist = self.get_or_create_artist(artist_key);
 			artist.num_songs += 1;
@@ -477,6 +795,7 @@ impl Builder {
 				albums_as_lyricist: HashSet::new(),
 				num_songs_by_genre: HashMap::new(),
 				num_songs: 0,
+				musicbrainz_id: None,
 			})
 			.borrow_mut()
 	}
@@ -487,7 +806,11 @@ impl Builder {
 		};
 
 		let name = album_key.name;
-		let album = self.albums.entry(album_key).or_default().borrow_mut();
+		let album = self
+			.albums
+			.entry(album_key.clone())
+			.or_default()
+			.borrow_mut();
 		album.name = name;
 
 		if album.artwork.is_none() {
@@ -499,6 +822,12 @@ impl Builder {
 		}
 
 		album.date_added = album.date_added.max(song.date_added);
+		album.compilation |= song.compilation;
+
+		if let Some(mbid) = song.musicbrainz_release_id {
+			album.musicbrainz_release_id = album.musicbrainz_release_id.or(Some(mbid));
+			self.albums_by_mbid.entry(mbid).or_insert(album_key);
+		}
 
 		if !song.album_artists.is_empty() {
 			album.artists = song.album_artists.clone();
@@ -748,6 +1077,46 @@ mod test {
 		);
 	}
 
+	#[test]
+	fn albums_are_flagged_as_compilations() {
+		let (collection, strings) = setup_test(Vec::from([
+			scanner::Song {
+				virtual_path: PathBuf::from("Rain of Fury.mp3"),
+				album: Some("Metal Hammer".to_owned()),
+				artists: vec!["Rhapsody Of Fire".to_owned()],
+				album_artists: vec!["Various Artists".to_owned()],
+				compilation: true,
+				..Default::default()
+			},
+			scanner::Song {
+				virtual_path: PathBuf::from("Paradise.mp3"),
+				album: Some("Metal Hammer".to_owned()),
+				artists: vec!["Stratovarius".to_owned()],
+				album_artists: vec!["Various Artists".to_owned()],
+				compilation: true,
+				..Default::default()
+			},
+			scanner::Song {
+				virtual_path: PathBuf::from("Elysium.mp3"),
+				album: Some("Elysium".to_owned()),
+				artists: vec!["Stratovarius".to_owned()],
+				..Default::default()
+			},
+		]));
+
+		let albums = collection.get_albums(&strings);
+		assert_eq!(
+			albums
+				.into_iter()
+				.map(|a| (a.name, a.compilation))
+				.collect::<Vec<_>>(),
+			vec![
+				("Elysium".to_owned(), false),
+				("Metal Hammer".to_owned(), true),
+			]
+		);
+	}
+
 	#[test]
 	fn can_get_random_albums() {
 		let (collection, strings) = setup_test(Vec::from([
@@ -775,6 +1144,88 @@ mod test {
 		);
 	}
 
+	#[test]
+	fn can_get_mix() {
+		let (collection, strings) = setup_test(Vec::from([
+			scanner::Song {
+				virtual_path: PathBuf::from("Kai.mp3"),
+				genres: vec!["Ambient".to_owned()],
+				year: Some(1994),
+				..Default::default()
+			},
+			scanner::Song {
+				virtual_path: PathBuf::from("Papua New Guinea.mp3"),
+				genres: vec!["Ambient".to_owned()],
+				year: Some(1991),
+				..Default::default()
+			},
+			scanner::Song {
+				virtual_path: PathBuf::from("Smokebelch.mp3"),
+				genres: vec!["Ambient".to_owned()],
+				year: Some(2004),
+				..Default::default()
+			},
+			scanner::Song {
+				virtual_path: PathBuf::from("Windowlicker.mp3"),
+				genres: vec!["IDM".to_owned()],
+				year: Some(1999),
+				..Default::default()
+			},
+		]));
+
+		let ambient = GenreKey(strings.get("Ambient").unwrap());
+		let mix = collection
+			.get_mix(Some(ambient), Some(1990), None, &HashMap::new(), 10)
+			.unwrap()
+			.into_iter()
+			.map(|k| PathBuf::from(strings.resolve(&k.virtual_path.0)))
+			.collect::<HashSet<_>>();
+		assert_eq!(
+			mix,
+			HashSet::from_iter([
+				PathBuf::from("Kai.mp3"),
+				PathBuf::from("Papua New Guinea.mp3")
+			])
+		);
+
+		let no_plays = HashMap::new();
+		assert_eq!(
+			collection
+				.get_mix(None, None, None, &no_plays, 3)
+				.unwrap()
+				.len(),
+			3
+		);
+		assert_eq!(
+			collection.get_mix(None, None, Some(7), &no_plays, 4),
+			collection.get_mix(None, None, Some(7), &no_plays, 4)
+		);
+	}
+
+	#[test]
+	fn mix_favors_unplayed_songs() {
+		let (collection, strings) = setup_test(Vec::from([
+			scanner::Song {
+				virtual_path: PathBuf::from("Kai.mp3"),
+				..Default::default()
+			},
+			scanner::Song {
+				virtual_path: PathBuf::from("Windowlicker.mp3"),
+				..Default::default()
+			},
+		]));
+
+		let played = SongKey {
+			virtual_path: PathBuf::from("Kai.mp3").get(&strings).unwrap(),
+		};
+		let play_counts = HashMap::from([(played, 50)]);
+		let first_picks = (0..100)
+			.filter_map(|seed| collection.get_mix(None, None, Some(seed), &play_counts, 1))
+			.filter(|mix| mix == &vec![played])
+			.count();
+		assert!(first_picks < 20);
+	}
+
 	#[test]
 	fn can_get_recent_albums() {
 		let (collection, strings) = setup_test(Vec::from([
@@ -804,6 +1255,38 @@ mod test {
 		);
 	}
 
+	#[test]
+	fn can_get_recent_songs() {
+		let (collection, strings) = setup_test(Vec::from([
+			scanner::Song {
+				virtual_path: PathBuf::from("Kai.mp3"),
+				date_added: 400,
+				..Default::default()
+			},
+			scanner::Song {
+				virtual_path: PathBuf::from("Papua New Guinea.mp3"),
+				track_number: Some(2),
+				date_added: 2000,
+				..Default::default()
+			},
+			scanner::Song {
+				virtual_path: PathBuf::from("Cascade.mp3"),
+				track_number: Some(1),
+				date_added: 2000,
+				..Default::default()
+			},
+		]));
+
+		assert_eq!(
+			collection.get_recent_songs(&strings),
+			vec![
+				PathBuf::from("Cascade.mp3"),
+				PathBuf::from("Papua New Guinea.mp3"),
+				PathBuf::from("Kai.mp3"),
+			]
+		);
+	}
+
 	#[test]
 	fn albums_are_associated_with_artists() {
 		let artist_name = "Bestest Artist";
@@ -892,6 +1375,143 @@ mod test {
 		}
 	}
 
+	#[test]
+	fn can_get_by_musicbrainz_id() {
+		let (collection, strings) = setup_test(Vec::from([
+			scanner::Song {
+				virtual_path: PathBuf::from("Kai.mp3"),
+				title: Some("Kai".to_owned()),
+				artists: vec!["FSOL".to_owned()],
+				album: Some("Lifeforms".to_owned()),
+				musicbrainz_recording_id: Some("4A0D2C9E-5F6B-4C3A-9E1D-7B8A6F5E4D3C".to_owned()),
+				musicbrainz_release_id: Some("9b3f1e2d-6c5a-4d8e-b7f0-1a2c3d4e5f60".to_owned()),
+				musicbrainz_artist_ids: vec!["e1f2a3b4-c5d6-4e7f-8a9b-0c1d2e3f4a5b".to_owned()],
+				..Default::default()
+			},
+			scanner::Song {
+				virtual_path: PathBuf::from("Flak.mp3"),
+				title: Some("Flak".to_owned()),
+				artists: vec!["FSOL".to_owned()],
+				album: Some("Lifeforms".to_owned()),
+				..Default::default()
+			},
+		]));
+
+		let song = collection
+			.get_song_by_mbid(
+				&strings,
+				strings.get("4a0d2c9e-5f6b-4c3a-9e1d-7b8a6f5e4d3c").unwrap(),
+			)
+			.unwrap();
+		assert_eq!(song.title, Some("Kai".to_owned()));
+
+		let album = collection
+			.get_album_by_mbid(
+				&strings,
+				strings.get("9b3f1e2d-6c5a-4d8e-b7f0-1a2c3d4e5f60").unwrap(),
+			)
+			.unwrap();
+		assert_eq!(album.header.name, "Lifeforms");
+		assert_eq!(
+			album.header.musicbrainz_release_id,
+			Some("9b3f1e2d-6c5a-4d8e-b7f0-1a2c3d4e5f60".to_owned())
+		);
+		assert_eq!(album.songs.len(), 2);
+
+		let artist = collection
+			.get_artist_by_mbid(
+				&strings,
+				strings.get("e1f2a3b4-c5d6-4e7f-8a9b-0c1d2e3f4a5b").unwrap(),
+			)
+			.unwrap();
+		assert_eq!(artist.header.name, UniCase::new("FSOL".to_owned()));
+	}
+
+	#[test]
+	fn can_get_composer_works() {
+		let (collection, strings) = setup_test(Vec::from([
+			scanner::Song {
+				virtual_path: PathBuf::from("Ode.mp3"),
+				title: Some("Ode to Joy".to_owned()),
+				track_number: Some(4),
+				artists: vec!["Wiener Philharmoniker".to_owned()],
+				album: Some("Masterworks".to_owned()),
+				composers: vec!["Beethoven".to_owned()],
+				work: Some("Symphony No. 9".to_owned()),
+				..Default::default()
+			},
+			scanner::Song {
+				virtual_path: PathBuf::from("Elise.mp3"),
+				title: Some("Für Elise".to_owned()),
+				artists: vec!["Wiener Philharmoniker".to_owned()],
+				album: Some("Masterworks".to_owned()),
+				composers: vec!["Beethoven".to_owned()],
+				..Default::default()
+			},
+			scanner::Song {
+				virtual_path: PathBuf::from("Allegro.mp3"),
+				title: Some("Allegro ma non troppo".to_owned()),
+				track_number: Some(1),
+				artists: vec!["Wiener Philharmoniker".to_owned()],
+				album: Some("Masterworks".to_owned()),
+				composers: vec!["Beethoven".to_owned()],
+				work: Some("Symphony No. 9".to_owned()),
+				..Default::default()
+			},
+			scanner::Song {
+				virtual_path: PathBuf::from("Moonlight.mp3"),
+				title: Some("Adagio sostenuto".to_owned()),
+				artists: vec!["Wiener Philharmoniker".to_owned()],
+				album: Some("Masterworks".to_owned()),
+				composers: vec!["Beethoven".to_owned()],
+				work: Some("Piano Sonata No. 14".to_owned()),
+				..Default::default()
+			},
+			scanner::Song {
+				virtual_path: PathBuf::from("Spring.mp3"),
+				title: Some("Spring".to_owned()),
+				artists: vec!["Wiener Philharmoniker".to_owned()],
+				album: Some("Masterworks".to_owned()),
+				composers: vec!["Vivaldi".to_owned()],
+				work: Some("The Four Seasons".to_owned()),
+				..Default::default()
+			},
+		]));
+
+		let composers = collection.get_composers(&strings);
+		assert_eq!(
+			composers.iter().map(|c| c.name.clone()).collect::<Vec<_>>(),
+			vec![UniCase::new("Beethoven"), UniCase::new("Vivaldi")]
+		);
+
+		let composer = collection
+			.get_composer(&strings, ArtistKey(strings.get("Beethoven").unwrap()))
+			.unwrap();
+
+		let works = composer
+			.works
+			.iter()
+			.map(|w| {
+				let titles = w.songs.iter().map(|s| s.title.clone().unwrap());
+				(w.name.clone(), titles.collect::<Vec<_>>())
+			})
+			.collect::<Vec<_>>();
+		assert_eq!(
+			works,
+			vec![
+				(
+					Some("Piano Sonata No. 14".to_owned()),
+					vec!["Adagio sostenuto".to_owned()]
+				),
+				(
+					Some("Symphony No. 9".to_owned()),
+					vec!["Allegro ma non troppo".to_owned(), "Ode to Joy".to_owned()]
+				),
+				(None, vec!["Für Elise".to_owned()]),
+			]
+		);
+	}
+
 	#[test]
 	fn albums_are_sorted_by_year() {
 		let (collection, strings) = setup_test(Vec::from([
@@ -969,6 +1589,7 @@ mod test {
 				artists: vec!["FSOL".to_owned()],
 				album: Some("Lifeforms".to_owned()),
 				disc_number: Some(2),
+				disc_subtitle: Some("Part Two".to_owned()),
 				track_number: Some(1),
 				..Default::default()
 			},
@@ -992,8 +1613,8 @@ mod test {
 			},
 		);
 
+		let album = album.unwrap();
 		let titles = album
-			.unwrap()
 			.songs
 			.into_iter()
 			.map(|s| s.title.unwrap())
@@ -1008,6 +1629,21 @@ mod test {
 				"Interstat".to_owned(),
 			]
 		);
+		assert_eq!(
+			album.discs,
+			vec![
+				Disc {
+					number: Some(1),
+					subtitle: None,
+					num_songs: 2,
+				},
+				Disc {
+					number: Some(2),
+					subtitle: Some("Part Two".to_owned()),
+					num_songs: 2,
+				},
+			]
+		);
 	}
 
 	#[test]
@@ -1068,6 +1704,37 @@ mod test {
 		assert_eq!(genres, vec!["Ambient".to_owned(), "Metal".to_owned()]);
 	}
 
+	#[test]
+	fn genre_spellings_are_counted_once() {
+		let (collection, strings) = setup_test(Vec::from([
+			scanner::Song {
+				virtual_path: PathBuf::from("Shook Ones.mp3"),
+				album: Some("The Infamous".to_owned()),
+				artists: vec!["Mobb Deep".to_owned()],
+				genres: vec!["Hip-Hop".to_owned(), "hip hop".to_owned()],
+				..Default::default()
+			},
+			scanner::Song {
+				virtual_path: PathBuf::from("C.R.E.A.M.mp3"),
+				album: Some("Enter the Wu-Tang".to_owned()),
+				artists: vec!["Wu-Tang Clan".to_owned()],
+				genres: vec!["HipHop".to_owned()],
+				..Default::default()
+			},
+		]));
+
+		let genres = collection.get_genres(&strings);
+		assert_eq!(genres.len(), 1);
+		assert_eq!(genres[0].num_albums, 2);
+		assert_eq!(genres[0].num_artists, 2);
+		assert_eq!(genres[0].num_songs, 2);
+
+		let artists = collection.get_artists(&strings);
+		assert!(artists
+			.iter()
+			.all(|a| a.num_songs_by_genre.values().all(|n| *n == 1)));
+	}
+
 	#[test]
 	fn can_get_genre() {
 		let (collection, strings) = setup_test(Vec::from([
@@ -1113,4 +1780,49 @@ mod test {
 			HashMap::from_iter([("Power Metal".to_owned(), 1)])
 		);
 	}
+
+	#[test]
+	fn can_sort_listings() {
+		let header = |name: &str, year, date_added, artist: &str| AlbumHeader {
+			name: name.to_owned(),
+			year,
+			date_added,
+			artists: vec![artist.to_owned()],
+			..Default::default()
+		};
+		let albums = vec![
+			header("Hunted", Some(2016), 100, "Khemmis"),
+			header("Picnic", None, 300, "Tobokegao"),
+			header("Seventh Son", Some(1988), 200, "Iron Maiden"),
+		];
+		let sorted = |order| {
+			let mut albums = albums
+				.iter()
+				.map(|a| header(&a.name, a.year, a.date_added, &a.artists[0]))
+				.collect::<Vec<_>>();
+			sort_listing(&mut albums, order, AlbumHeader::listing_key);
+			albums.into_iter().map(|a| a.name).collect::<Vec<_>>()
+		};
+
+		assert_eq!(
+			sorted(ListingOrder::Name),
+			vec!["Hunted", "Picnic", "Seventh Son"]
+		);
+		assert_eq!(
+			sorted(ListingOrder::Year),
+			vec!["Seventh Son", "Hunted", "Picnic"]
+		);
+		assert_eq!(
+			sorted(ListingOrder::DateAdded),
+			vec!["Picnic", "Seventh Son", "Hunted"]
+		);
+		assert_eq!(
+			sorted(ListingOrder::AlbumArtist),
+			vec!["Seventh Son", "Hunted", "Picnic"]
+		);
+		assert_eq!(
+			sorted(ListingOrder::Random(42)),
+			sorted(ListingOrder::Random(42))
+		);
+	}
 }
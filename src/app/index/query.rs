@@ -16,6 +16,7 @@ pub enum TextField {
 	Artist,
 	Composer,
 	Genre,
+	Key,
 	Label,
 	Lyricist,
 	Path,
@@ -30,6 +31,7 @@ pub enum TextOp {
 
 #[derive(Clone, Copy, Debug, Deserialize, Enum, Eq, Hash, PartialEq, Serialize)]
 pub enum NumberField {
+	Bpm,
 	DiscNumber,
 	TrackNumber,
 	Year,
@@ -88,6 +90,7 @@ pub fn make_parser() -> impl Parser<char, Expr, Error = Simple<char>> {
 			keyword("artist").to(TextField::Artist),
 			keyword("composer").to(TextField::Composer),
 			keyword("genre").to(TextField::Genre),
+			keyword("key").to(TextField::Key),
 			keyword("label").to(TextField::Label),
 			keyword("lyricist").to(TextField::Lyricist),
 			keyword("path").to(TextField::Path),
@@ -95,7 +98,13 @@ pub fn make_parser() -> impl Parser<char, Expr, Error = Simple<char>> {
 		))
 		.padded();
 
-		let text_op = choice((just("=").to(TextOp::Eq), just("%").to(TextOp::Like))).padded();
+		let text_op = choice((
+			just(":=").to(TextOp::Eq),
+			just(":").to(TextOp::Like),
+			just("=").to(TextOp::Eq),
+			just("%").to(TextOp::Like),
+		))
+		.padded();
 
 		let text_cmp = text_field
 			.then(text_op)
@@ -103,6 +112,7 @@ pub fn make_parser() -> impl Parser<char, Expr, Error = Simple<char>> {
 			.map(|((a, b), c)| Expr::TextCmp(a, b, c));
 
 		let number_field = choice((
+			keyword("bpm").to(NumberField::Bpm),
 			keyword("discnumber").to(NumberField::DiscNumber),
 			keyword("tracknumber").to(NumberField::TrackNumber),
 			keyword("year").to(NumberField::Year),
@@ -115,6 +125,13 @@ pub fn make_parser() -> impl Parser<char, Expr, Error = Simple<char>> {
 			just(">").to(NumberOp::Greater),
 			just("<=").to(NumberOp::LessOrEq),
 			just("<").to(NumberOp::Less),
+		));
+
+		// Field-scoped syntax, eg. `year:>2000` or `year:2000`
+		let number_op = choice((
+			just(':').ignore_then(number_op.clone()),
+			just(':').to(NumberOp::Eq),
+			number_op,
 		))
 		.padded();
 
@@ -217,6 +234,10 @@ fn can_parse_text_fields() {
 		parser.parse(r#"genre = "jazz""#).unwrap(),
 		Expr::TextCmp(TextField::Genre, TextOp::Eq, "jazz".to_owned()),
 	);
+	assert_eq!(
+		parser.parse(r#"key = "Am""#).unwrap(),
+		Expr::TextCmp(TextField::Key, TextOp::Eq, "Am".to_owned()),
+	);
 	assert_eq!(
 		parser.parse(r#"label = "diverse system""#).unwrap(),
 		Expr::TextCmp(TextField::Label, TextOp::Eq, "diverse system".to_owned()),
@@ -255,6 +276,10 @@ fn can_parse_text_operators() {
 #[test]
 fn can_parse_number_fields() {
 	let parser = make_parser();
+	assert_eq!(
+		parser.parse(r#"bpm = 128"#).unwrap(),
+		Expr::NumberCmp(NumberField::Bpm, NumberOp::Eq, 128),
+	);
 	assert_eq!(
 		parser.parse(r#"discnumber = 6"#).unwrap(),
 		Expr::NumberCmp(NumberField::DiscNumber, NumberOp::Eq, 6),
@@ -294,6 +319,66 @@ fn can_parse_number_operators() {
 	);
 }
 
+#[test]
+fn can_parse_field_scoped_syntax() {
+	let parser = make_parser();
+	assert_eq!(
+		parser.parse(r#"artist:boards"#).unwrap(),
+		Expr::TextCmp(TextField::Artist, TextOp::Like, "boards".to_owned()),
+	);
+	assert_eq!(
+		parser.parse(r#"album:"music has the right""#).unwrap(),
+		Expr::TextCmp(
+			TextField::Album,
+			TextOp::Like,
+			"music has the right".to_owned()
+		),
+	);
+	assert_eq!(
+		parser.parse(r#"genre:=ambient"#).unwrap(),
+		Expr::TextCmp(TextField::Genre, TextOp::Eq, "ambient".to_owned()),
+	);
+	assert_eq!(
+		parser.parse(r#"year:1998"#).unwrap(),
+		Expr::NumberCmp(NumberField::Year, NumberOp::Eq, 1998),
+	);
+	assert_eq!(
+		parser.parse(r#"year:>2000"#).unwrap(),
+		Expr::NumberCmp(NumberField::Year, NumberOp::Greater, 2000),
+	);
+	assert_eq!(
+		parser.parse(r#"bpm:<=90"#).unwrap(),
+		Expr::NumberCmp(NumberField::Bpm, NumberOp::LessOrEq, 90),
+	);
+}
+
+#[test]
+fn can_combine_field_scoped_filters() {
+	let parser = make_parser();
+	assert_eq!(
+		parser
+			.parse(r#"artist:boards album:"music has the right" year:>2000"#)
+			.unwrap(),
+		Expr::Combined(
+			Box::new(Expr::Combined(
+				Box::new(Expr::TextCmp(
+					TextField::Artist,
+					TextOp::Like,
+					"boards".to_owned()
+				)),
+				BoolOp::And,
+				Box::new(Expr::TextCmp(
+					TextField::Album,
+					TextOp::Like,
+					"music has the right".to_owned()
+				)),
+			)),
+			BoolOp::And,
+			Box::new(Expr::NumberCmp(NumberField::Year, NumberOp::Greater, 2000)),
+		),
+	);
+}
+
 #[test]
 fn can_use_and_operator() {
 	let parser = make_parser();
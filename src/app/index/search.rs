@@ -1,38 +1,246 @@
+use std::collections::HashMap;
+use std::ops::Bound;
+use std::path::Path;
+
 use chumsky::Parser;
 use enum_map::EnumMap;
-use lasso2::Spur;
-use nohash_hasher::IntSet;
+use lasso2::{Key, Spur};
+use log::error;
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, HashMap};
-use tinyvec::TinyVec;
+use tantivy::{
+	collector::TopDocs,
+	directory::{MmapDirectory, RamDirectory},
+	query::{
+		BooleanQuery, EmptyQuery, FuzzyTermQuery, Occur, PhrasePrefixQuery, Query, RangeQuery,
+		TermQuery,
+	},
+	schema::{Field, IndexRecordOption, Schema, TextFieldIndexing, TextOptions, FAST, INDEXED},
+	tokenizer::{
+		AsciiFoldingFilter, LowerCaser, RawTokenizer, RemoveLongFilter, SimpleTokenizer,
+		TextAnalyzer, TokenStream,
+	},
+	IndexReader, IndexSettings, ReloadPolicy, Score, TantivyDocument, Term,
+};
 
 use crate::app::{
 	index::{
 		dictionary::Dictionary,
 		query::{BoolOp, Expr, Literal, NumberField, NumberOp, TextField, TextOp},
-		storage::SongKey,
+		storage::{PathKey, SongKey},
 	},
 	scanner, Error,
 };
 
 use super::{collection, dictionary::sanitize, query::make_parser, storage};
 
-#[derive(Serialize, Deserialize)]
+// Bumped whenever the schema or tokenization changes, so outdated index files are never opened
+const SCHEMA_VERSION: u32 = 2;
+const TEXT_TOKENIZER: &str = "folded";
+const EXACT_TOKENIZER: &str = "folded_exact";
+const WRITER_MEMORY_BUDGET: usize = 50_000_000;
+
+fn text_field_name(field: TextField) -> &'static str {
+	match field {
+		TextField::Album => "album",
+		TextField::AlbumArtist => "album_artist",
+		TextField::Artist => "artist",
+		TextField::Composer => "composer",
+		TextField::Genre => "genre",
+		TextField::Key => "key",
+		TextField::Label => "label",
+		TextField::Lyricist => "lyricist",
+		TextField::Path => "path",
+		TextField::Title => "title",
+	}
+}
+
+fn number_field_name(field: NumberField) -> &'static str {
+	match field {
+		NumberField::Bpm => "bpm",
+		NumberField::DiscNumber => "disc_number",
+		NumberField::TrackNumber => "track_number",
+		NumberField::Year => "year",
+	}
+}
+
+#[derive(Clone)]
+struct Fields {
+	song: Field,
+	// Tokenized values, for full-text queries
+	text: EnumMap<TextField, Field>,
+	// Whole values, for exact comparisons
+	exact: EnumMap<TextField, Field>,
+	numbers: EnumMap<NumberField, Field>,
+}
+
+fn make_schema() -> (Schema, Fields) {
+	let text_options = TextOptions::default().set_indexing_options(
+		TextFieldIndexing::default()
+			.set_tokenizer(TEXT_TOKENIZER)
+			.set_index_option(IndexRecordOption::WithFreqsAndPositions),
+	);
+	let exact_options = TextOptions::default().set_indexing_options(
+		TextFieldIndexing::default()
+			.set_tokenizer(EXACT_TOKENIZER)
+			.set_index_option(IndexRecordOption::Basic),
+	);
+
+	let mut builder = Schema::builder();
+	let song = builder.add_u64_field("song", FAST);
+	let text =
+		EnumMap::from_fn(|f| builder.add_text_field(text_field_name(f), text_options.clone()));
+	let exact = EnumMap::from_fn(|f| {
+		let name = format!("exact_{}", text_field_name(f));
+		builder.add_text_field(&name, exact_options.clone())
+	});
+	let numbers = EnumMap::from_fn(|f| builder.add_i64_field(number_field_name(f), INDEXED));
+	let fields = Fields {
+		song,
+		text,
+		exact,
+		numbers,
+	};
+	(builder.build(), fields)
+}
+
+// Case and diacritics are folded, so that "Sigur Ros" matches "Sigur Rós"
+fn register_tokenizers(index: &tantivy::Index) {
+	let text = TextAnalyzer::builder(SimpleTokenizer::default())
+		.filter(RemoveLongFilter::limit(40))
+		.filter(LowerCaser)
+		.filter(AsciiFoldingFilter)
+		.build();
+	let exact = TextAnalyzer::builder(RawTokenizer::default())
+		.filter(LowerCaser)
+		.filter(AsciiFoldingFilter)
+		.build();
+	index.tokenizers().register(TEXT_TOKENIZER, text);
+	index.tokenizers().register(EXACT_TOKENIZER, exact);
+}
+
+// Maps full-width forms of ASCII characters (eg. `ｓｉｇｕｒ`) to their regular counterparts
+fn normalize_width(s: &str) -> String {
+	s.chars()
+		.map(|c| match c {
+			'\u{3000}' => ' ',
+			'\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+			_ => c,
+		})
+		.collect()
+}
+
+// Full-text index of the collection, backed by tantivy. Its files are stored next to the
+// collection index, in a directory named after the generation of the index.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Search {
-	text_fields: EnumMap<TextField, TextFieldIndex>,
-	number_fields: EnumMap<NumberField, NumberFieldIndex>,
+	generation: u64,
+	// Missing when the index could not be built or restored, in which case searches find nothing
+	#[serde(skip)]
+	engine: Option<Engine>,
+}
+
+#[derive(Clone)]
+struct Engine {
+	index: tantivy::Index,
+	reader: IndexReader,
+	fields: Fields,
+	// Indexes which have not been written to disk yet
+	memory: Option<RamDirectory>,
+}
+
+impl Engine {
+	fn new(documents: Vec<TantivyDocument>) -> Result<Self, Error> {
+		let (schema, fields) = make_schema();
+		let memory = RamDirectory::create();
+		let index = tantivy::Index::create(memory.clone(), schema, IndexSettings::default())?;
+		register_tokenizers(&index);
+		let mut writer = index.writer_with_num_threads(1, WRITER_MEMORY_BUDGET)?;
+		for document in documents {
+			writer.add_document(document)?;
+		}
+		writer.commit()?;
+		writer.wait_merging_threads()?;
+		Ok(Self {
+			reader: make_reader(&index)?,
+			index,
+			fields,
+			memory: Some(memory),
+		})
+	}
+
+	fn open(directory: &Path) -> Result<Self, Error> {
+		let directory = MmapDirectory::open(directory).map_err(|e| Error::SearchIndex(e.into()))?;
+		let index = tantivy::Index::open(directory)?;
+		register_tokenizers(&index);
+		let (schema, fields) = make_schema();
+		if index.schema() != schema {
+			return Err(Error::IndexDeserializationError);
+		}
+		Ok(Self {
+			reader: make_reader(&index)?,
+			index,
+			fields,
+			memory: None,
+		})
+	}
+}
+
+fn make_reader(index: &tantivy::Index) -> Result<IndexReader, Error> {
+	Ok(index
+		.reader_builder()
+		.reload_policy(ReloadPolicy::Manual)
+		.try_into()?)
 }
 
 impl Default for Search {
 	fn default() -> Self {
 		Self {
-			text_fields: Default::default(),
-			number_fields: Default::default(),
+			generation: rand::random(),
+			engine: None,
 		}
 	}
 }
 
 impl Search {
+	fn directory_name(&self) -> String {
+		format!("v{SCHEMA_VERSION}-{:016x}", self.generation)
+	}
+
+	// Once written to disk, the index is read from there instead of being kept in memory
+	pub fn persist(&mut self, directory: &Path) -> Result<(), Error> {
+		let Some(memory) = self.engine.as_ref().and_then(|e| e.memory.as_ref()) else {
+			return Ok(());
+		};
+		let path = directory.join(self.directory_name());
+		if !path.exists() {
+			std::fs::create_dir_all(&path).map_err(|e| Error::Io(path.clone(), e))?;
+			let destination =
+				MmapDirectory::open(&path).map_err(|e| Error::SearchIndex(e.into()))?;
+			memory.persist(&destination)?;
+		}
+		self.engine = Some(Engine::open(&path)?);
+		Ok(())
+	}
+
+	pub fn restore(&mut self, directory: &Path) -> Result<(), Error> {
+		self.engine = Some(Engine::open(&directory.join(self.directory_name()))?);
+		Ok(())
+	}
+
+	// Files of other generations may still be in use until the index they belong to is dropped,
+	// failures are retried on the next call
+	pub fn remove_stale_files(&self, directory: &Path) {
+		let Ok(entries) = std::fs::read_dir(directory) else {
+			return;
+		};
+		let current = self.directory_name();
+		for entry in entries.flatten() {
+			if entry.file_name().to_string_lossy() != current {
+				std::fs::remove_dir_all(entry.path()).ok();
+			}
+		}
+	}
+
 	pub fn find_songs(
 		&self,
 		collection: &collection::Collection,
@@ -44,11 +252,15 @@ impl Search {
 			.parse(query)
 			.map_err(|_| Error::SearchQueryParseError)?;
 
-		let mut songs = self
-			.eval(dictionary, &parsed_query)
-			.into_iter()
-			.collect::<Vec<_>>();
+		let Some(engine) = &self.engine else {
+			return Ok(vec![]);
+		};
+		let scores = engine.eval(&*engine.make_query(&parsed_query))?;
+
+		// Songs are listed by relevance, equally relevant songs in collection order
+		let mut songs = scores.keys().copied().collect::<Vec<_>>();
 		collection.sort_songs(&mut songs, dictionary);
+		songs.sort_by(|a, b| scores[b].total_cmp(&scores[a]));
 		let songs = songs
 			.into_iter()
 			.filter_map(|song_key| collection.get_song(dictionary, song_key))
@@ -56,282 +268,265 @@ impl Search {
 
 		Ok(songs)
 	}
+}
+
+impl Engine {
+	fn eval(&self, query: &dyn Query) -> Result<HashMap<SongKey, Score>, Error> {
+		let searcher = self.reader.searcher();
+		let num_docs = searcher.num_docs() as usize;
+		if num_docs == 0 {
+			return Ok(HashMap::new());
+		}
+
+		let mut scores = HashMap::new();
+		for (score, address) in searcher.search(query, &TopDocs::with_limit(num_docs))? {
+			let songs = searcher
+				.segment_reader(address.segment_ord)
+				.fast_fields()
+				.u64("song")?;
+			let spur = songs
+				.first(address.doc_id)
+				.and_then(|s| Spur::try_from_usize(s as usize));
+			if let Some(spur) = spur {
+				let song_key = SongKey {
+					virtual_path: PathKey(spur),
+				};
+				scores.insert(song_key, score);
+			}
+		}
+		Ok(scores)
+	}
 
-	fn eval(&self, dictionary: &Dictionary, expr: &Expr) -> IntSet<SongKey> {
+	fn make_query(&self, expr: &Expr) -> Box<dyn Query> {
 		match expr {
-			Expr::Fuzzy(s) => self.eval_fuzzy(dictionary, s),
-			Expr::TextCmp(field, op, s) => self.eval_text_operator(dictionary, *field, *op, s),
-			Expr::NumberCmp(field, op, n) => self.eval_number_operator(*field, *op, *n),
-			Expr::Combined(e, op, f) => self.combine(dictionary, e, *op, f),
+			Expr::Fuzzy(s) => self.make_fuzzy_query(s),
+			Expr::TextCmp(field, op, s) => self.make_text_query(*field, *op, s),
+			Expr::NumberCmp(field, op, n) => self.make_number_query(*field, *op, *n),
+			Expr::Combined(e, op, f) => self.combine(e, *op, f),
 		}
 	}
 
-	fn combine(
-		&self,
-		dictionary: &Dictionary,
-		e: &Expr,
-		op: BoolOp,
-		f: &Expr,
-	) -> IntSet<SongKey> {
+	fn combine(&self, e: &Expr, op: BoolOp, f: &Expr) -> Box<dyn Query> {
 		let is_operable = |expr: &Expr| match expr {
-			Expr::Fuzzy(Literal::Text(s)) if s.chars().count() < BIGRAM_SIZE => false,
+			Expr::Fuzzy(Literal::Text(s)) if s.chars().count() < 2 => false,
 			Expr::Fuzzy(Literal::Number(n)) if *n < 10 => false,
-			Expr::TextCmp(_, _, s) if s.chars().count() < BIGRAM_SIZE => false,
+			Expr::TextCmp(_, _, s) if s.chars().count() < 2 => false,
 			_ => true,
 		};
 
-		let left = is_operable(e).then(|| self.eval(dictionary, e));
-		let right = is_operable(f).then(|| self.eval(dictionary, f));
+		let left = is_operable(e).then(|| self.make_query(e));
+		let right = is_operable(f).then(|| self.make_query(f));
 
 		match (left, op, right) {
-			(Some(l), BoolOp::And, Some(r)) => l.intersection(&r).cloned().collect(),
-			(Some(l), BoolOp::Or, Some(r)) => l.union(&r).cloned().collect(),
-			(Some(l), BoolOp::Not, Some(r)) => l.difference(&r).cloned().collect(),
-			(None, BoolOp::Not, _) => IntSet::default(),
+			(Some(l), BoolOp::And, Some(r)) => {
+				Box::new(BooleanQuery::new(vec![(Occur::Must, l), (Occur::Must, r)]))
+			}
+			(Some(l), BoolOp::Or, Some(r)) => Box::new(BooleanQuery::new(vec![
+				(Occur::Should, l),
+				(Occur::Should, r),
+			])),
+			(Some(l), BoolOp::Not, Some(r)) => Box::new(BooleanQuery::new(vec![
+				(Occur::Must, l),
+				(Occur::MustNot, r),
+			])),
+			(None, BoolOp::Not, _) => Box::new(EmptyQuery),
 			(Some(l), _, None) => l,
 			(None, _, Some(r)) => r,
-			(None, _, None) => IntSet::default(),
+			(None, _, None) => Box::new(EmptyQuery),
 		}
 	}
 
-	fn eval_fuzzy(&self, dictionary: &Dictionary, value: &Literal) -> IntSet<SongKey> {
+	fn make_fuzzy_query(&self, value: &Literal) -> Box<dyn Query> {
 		match value {
 			Literal::Text(s) => {
-				let mut songs = IntSet::default();
-				for field in self.text_fields.values() {
-					songs.extend(field.find_like(dictionary, s));
-				}
-				songs
+				let tokens = self.tokenize(TEXT_TOKENIZER, s);
+				let clauses = self
+					.fields
+					.text
+					.values()
+					.map(|field| (Occur::Should, self.match_tokens(*field, &tokens)))
+					.collect();
+				Box::new(BooleanQuery::new(clauses))
 			}
 			Literal::Number(n) => {
-				let mut songs = IntSet::default();
-				for field in self.number_fields.values() {
-					songs.extend(field.find(*n as i64, NumberOp::Eq));
-				}
-				songs
-					.union(&self.eval_fuzzy(dictionary, &Literal::Text(n.to_string())))
-					.copied()
-					.collect()
+				let mut clauses = self
+					.fields
+					.numbers
+					.values()
+					.map(|field| {
+						let term = Term::from_field_i64(*field, *n as i64);
+						let query: Box<dyn Query> =
+							Box::new(TermQuery::new(term, IndexRecordOption::Basic));
+						(Occur::Should, query)
+					})
+					.collect::<Vec<_>>();
+				clauses.push((
+					Occur::Should,
+					self.make_fuzzy_query(&Literal::Text(n.to_string())),
+				));
+				Box::new(BooleanQuery::new(clauses))
 			}
 		}
 	}
 
-	fn eval_text_operator(
-		&self,
-		dictionary: &Dictionary,
-		field: TextField,
-		operator: TextOp,
-		value: &str,
-	) -> IntSet<SongKey> {
+	fn make_text_query(&self, field: TextField, operator: TextOp, value: &str) -> Box<dyn Query> {
 		match operator {
-			TextOp::Eq => self.text_fields[field].find_exact(dictionary, value),
-			TextOp::Like => self.text_fields[field].find_like(dictionary, value),
+			TextOp::Eq => {
+				let value = sanitize(&normalize_width(value));
+				let Some(token) = self.tokenize(EXACT_TOKENIZER, &value).pop() else {
+					return Box::new(EmptyQuery);
+				};
+				let term = Term::from_field_text(self.fields.exact[field], &token);
+				Box::new(TermQuery::new(term, IndexRecordOption::Basic))
+			}
+			TextOp::Like => {
+				let tokens = self.tokenize(TEXT_TOKENIZER, value);
+				self.match_tokens(self.fields.text[field], &tokens)
+			}
 		}
 	}
 
-	fn eval_number_operator(
+	fn make_number_query(
 		&self,
 		field: NumberField,
 		operator: NumberOp,
 		value: i32,
-	) -> IntSet<SongKey> {
-		self.number_fields[field].find(value as i64, operator)
-	}
-}
-
-const BIGRAM_SIZE: usize = 2;
-const ASCII_RANGE: usize = u8::MAX as usize;
-
-#[derive(Clone, Deserialize, Serialize)]
-struct TextFieldIndex {
-	exact: HashMap<Spur, IntSet<SongKey>>,
-	ascii_bigrams: Vec<Vec<(SongKey, Spur)>>,
-	other_bigrams: HashMap<[char; BIGRAM_SIZE], Vec<(SongKey, Spur)>>,
-}
-
-impl Default for TextFieldIndex {
-	fn default() -> Self {
-		Self {
-			exact: Default::default(),
-			ascii_bigrams: vec![Default::default(); ASCII_RANGE * ASCII_RANGE],
-			other_bigrams: Default::default(),
-		}
+	) -> Box<dyn Query> {
+		let value = value as i64;
+		let (lower, upper) = match operator {
+			NumberOp::Eq => (Bound::Included(value), Bound::Included(value)),
+			NumberOp::Greater => (Bound::Excluded(value), Bound::Unbounded),
+			NumberOp::GreaterOrEq => (Bound::Included(value), Bound::Unbounded),
+			NumberOp::Less => (Bound::Unbounded, Bound::Excluded(value)),
+			NumberOp::LessOrEq => (Bound::Unbounded, Bound::Included(value)),
+		};
+		Box::new(RangeQuery::new_i64_bounds(
+			number_field_name(field).to_owned(),
+			lower,
+			upper,
+		))
 	}
-}
 
-impl TextFieldIndex {
-	fn ascii_bigram_to_index(a: char, b: char) -> usize {
-		assert!(a.is_ascii());
-		assert!(b.is_ascii());
-		(a as usize) * ASCII_RANGE + (b as usize)
-	}
-
-	pub fn insert(&mut self, raw_value: &str, value: Spur, song: SongKey) {
-		let characters = sanitize(raw_value).chars().collect::<TinyVec<[char; 32]>>();
-		for substring in characters[..].windows(BIGRAM_SIZE) {
-			if substring.iter().all(|c| c.is_ascii()) {
-				let index = Self::ascii_bigram_to_index(substring[0], substring[1]);
-				self.ascii_bigrams[index].push((song, value));
-			} else {
-				self.other_bigrams
-					.entry(substring.try_into().unwrap())
-					.or_default()
-					.push((song, value));
+	// Matches consecutive tokens, the last of which may be incomplete (eg. while a user is still
+	// typing). Songs where the last token matches in full rank higher.
+	fn match_tokens(&self, field: Field, tokens: &[String]) -> Box<dyn Query> {
+		let mut terms = tokens
+			.iter()
+			.map(|t| Term::from_field_text(field, t))
+			.collect::<Vec<_>>();
+		match terms.len() {
+			0 => Box::new(EmptyQuery),
+			1 => {
+				let term = terms.remove(0);
+				let exact: Box<dyn Query> =
+					Box::new(TermQuery::new(term.clone(), IndexRecordOption::WithFreqs));
+				let prefix: Box<dyn Query> = Box::new(FuzzyTermQuery::new_prefix(term, 0, false));
+				Box::new(BooleanQuery::new(vec![
+					(Occur::Should, exact),
+					(Occur::Should, prefix),
+				]))
 			}
+			_ => Box::new(PhrasePrefixQuery::new(terms)),
 		}
-
-		self.exact.entry(value).or_default().insert(song);
-	}
-
-	pub fn find_like(&self, dictionary: &Dictionary, value: &str) -> IntSet<SongKey> {
-		let sanitized = sanitize(value);
-		let characters = sanitized.chars().collect::<Vec<_>>();
-		let empty = Vec::new();
-
-		let candidates_by_bigram = characters[..]
-			.windows(BIGRAM_SIZE)
-			.map(|s| {
-				if s.iter().all(|c| c.is_ascii()) {
-					let index = Self::ascii_bigram_to_index(s[0], s[1]);
-					&self.ascii_bigrams[index]
-				} else {
-					self.other_bigrams
-						.get::<[char; BIGRAM_SIZE]>(s.try_into().unwrap())
-						.unwrap_or(&empty)
-				}
-			})
-			.collect::<Vec<_>>();
-
-		candidates_by_bigram
-			.into_iter()
-			.min_by_key(|h| h.len()) // Only check songs that contain the least common bigram from the search term
-			.unwrap_or(&empty)
-			.iter()
-			.filter(|(_song_key, indexed_value)| {
-				// Only keep songs that actually contain the search term in full
-				let resolved = dictionary.resolve(indexed_value);
-				sanitize(resolved).contains(&sanitized)
-			})
-			.map(|(k, _v)| k)
-			.copied()
-			.collect()
-	}
-
-	pub fn find_exact(&self, dictionary: &Dictionary, value: &str) -> IntSet<SongKey> {
-		dictionary
-			.get_canon(value)
-			.and_then(|s| self.exact.get(&s))
-			.cloned()
-			.unwrap_or_default()
 	}
-}
-
-#[derive(Clone, Default, Deserialize, Serialize)]
-struct NumberFieldIndex {
-	values: BTreeMap<i64, IntSet<SongKey>>,
-}
 
-impl NumberFieldIndex {
-	pub fn insert(&mut self, value: i64, key: SongKey) {
-		self.values.entry(value).or_default().insert(key);
-	}
-
-	pub fn find(&self, value: i64, operator: NumberOp) -> IntSet<SongKey> {
-		let range = match operator {
-			NumberOp::Eq => self.values.range(value..=value),
-			NumberOp::Greater => self.values.range((value + 1)..),
-			NumberOp::GreaterOrEq => self.values.range(value..),
-			NumberOp::Less => self.values.range(..value),
-			NumberOp::LessOrEq => self.values.range(..=value),
+	fn tokenize(&self, tokenizer: &str, text: &str) -> Vec<String> {
+		let Some(mut analyzer) = self.index.tokenizers().get(tokenizer) else {
+			return vec![];
 		};
-		let candidates = range.map(|(_n, songs)| songs).collect::<Vec<_>>();
-		let mut results = Vec::with_capacity(candidates.iter().map(|c| c.len()).sum());
-		candidates
-			.into_iter()
-			.for_each(|songs| results.extend(songs.iter()));
-		IntSet::from_iter(results)
+		let mut tokens = vec![];
+		analyzer
+			.token_stream(&normalize_width(text))
+			.process(&mut |token| tokens.push(token.text.clone()));
+		tokens
 	}
 }
 
-#[derive(Clone, Default)]
+#[derive(Clone)]
 pub struct Builder {
-	text_fields: EnumMap<TextField, TextFieldIndex>,
-	number_fields: EnumMap<NumberField, NumberFieldIndex>,
+	fields: Fields,
+	documents: Vec<TantivyDocument>,
+}
+
+impl Default for Builder {
+	fn default() -> Self {
+		Self {
+			fields: make_schema().1,
+			documents: vec![],
+		}
+	}
 }
 
 impl Builder {
 	pub fn add_song(&mut self, scanner_song: &scanner::Song, storage_song: &storage::Song) {
-		let song_key = SongKey {
-			virtual_path: storage_song.virtual_path,
+		let fields = &self.fields;
+		let mut document = TantivyDocument::default();
+		document.add_u64(fields.song, storage_song.virtual_path.0.into_usize() as u64);
+
+		let mut add_text = |field: TextField, value: &str| {
+			let value = normalize_width(value);
+			document.add_text(fields.exact[field], sanitize(&value));
+			document.add_text(fields.text[field], value);
 		};
 
-		if let (Some(str), Some(spur)) = (&scanner_song.album, storage_song.album) {
-			self.text_fields[TextField::Album].insert(str, spur, song_key);
+		if let Some(album) = &scanner_song.album {
+			add_text(TextField::Album, album);
 		}
-
-		for (str, artist_key) in scanner_song
-			.album_artists
-			.iter()
-			.zip(storage_song.album_artists.iter())
-		{
-			self.text_fields[TextField::AlbumArtist].insert(str, artist_key.0, song_key);
+		for artist in &scanner_song.album_artists {
+			add_text(TextField::AlbumArtist, artist);
 		}
-
-		for (str, artist_key) in scanner_song.artists.iter().zip(storage_song.artists.iter()) {
-			self.text_fields[TextField::Artist].insert(str, artist_key.0, song_key);
+		for artist in &scanner_song.artists {
+			add_text(TextField::Artist, artist);
 		}
-
-		for (str, artist_key) in scanner_song
-			.composers
-			.iter()
-			.zip(storage_song.composers.iter())
-		{
-			self.text_fields[TextField::Composer].insert(str, artist_key.0, song_key);
+		for composer in &scanner_song.composers {
+			add_text(TextField::Composer, composer);
 		}
-
-		if let Some(disc_number) = &scanner_song.disc_number {
-			self.number_fields[NumberField::DiscNumber].insert(*disc_number, song_key);
+		for genre in &scanner_song.genres {
+			add_text(TextField::Genre, genre);
 		}
-
-		for (str, spur) in scanner_song.genres.iter().zip(storage_song.genres.iter()) {
-			self.text_fields[TextField::Genre].insert(str, *spur, song_key);
+		if let Some(key) = &scanner_song.initial_key {
+			add_text(TextField::Key, key);
 		}
-
-		for (str, spur) in scanner_song.labels.iter().zip(storage_song.labels.iter()) {
-			self.text_fields[TextField::Label].insert(str, *spur, song_key);
+		for label in &scanner_song.labels {
+			add_text(TextField::Label, label);
 		}
-
-		for (str, artist_key) in scanner_song
-			.lyricists
-			.iter()
-			.zip(storage_song.lyricists.iter())
-		{
-			self.text_fields[TextField::Lyricist].insert(str, artist_key.0, song_key);
+		for lyricist in &scanner_song.lyricists {
+			add_text(TextField::Lyricist, lyricist);
 		}
-
-		self.text_fields[TextField::Path].insert(
+		add_text(
+			TextField::Path,
 			scanner_song.virtual_path.to_string_lossy().as_ref(),
-			storage_song.virtual_path.0,
-			song_key,
 		);
-
-		if let (Some(str), Some(spur)) = (&scanner_song.title, storage_song.title) {
-			self.text_fields[TextField::Title].insert(str, spur, song_key);
+		if let Some(title) = &scanner_song.title {
+			add_text(TextField::Title, title);
 		}
 
-		if let Some(track_number) = &scanner_song.track_number {
-			self.number_fields[NumberField::TrackNumber].insert(*track_number, song_key);
+		let numbers = [
+			(NumberField::Bpm, scanner_song.bpm),
+			(NumberField::DiscNumber, scanner_song.disc_number),
+			(NumberField::TrackNumber, scanner_song.track_number),
+			(NumberField::Year, scanner_song.year),
+		];
+		for (field, value) in numbers {
+			if let Some(value) = value {
+				document.add_i64(fields.numbers[field], value);
+			}
 		}
 
-		if let Some(year) = &scanner_song.year {
-			self.number_fields[NumberField::Year].insert(*year, song_key);
-		}
+		self.documents.push(document);
 	}
 
 	pub fn build(self) -> Search {
+		let engine = match Engine::new(self.documents) {
+			Ok(engine) => Some(engine),
+			Err(e) => {
+				error!("Could not build search index: {e}");
+				None
+			}
+		};
 		Search {
-			text_fields: self.text_fields,
-			number_fields: self.number_fields,
+			generation: rand::random(),
+			engine,
 		}
 	}
 }
@@ -342,6 +537,8 @@ mod test {
 
 	use super::*;
 	use crate::app::index::dictionary;
+	use crate::test::prepare_test_directory;
+	use crate::test_name;
 	use collection::Collection;
 	use storage::store_song;
 
@@ -402,12 +599,26 @@ mod test {
 			},
 		]);
 
-		let songs = ctx.search("agon");
+		let songs = ctx.search("dragon");
 		assert_eq!(songs.len(), 2);
 		assert!(songs.contains(&PathBuf::from("seasons.mp3")));
 		assert!(songs.contains(&PathBuf::from("potd.mp3")));
 	}
 
+	#[test]
+	fn can_search_after_persisting() {
+		let mut ctx = setup_test(vec![scanner::Song {
+			virtual_path: PathBuf::from("seasons.mp3"),
+			title: Some("Seasons".to_owned()),
+			..Default::default()
+		}]);
+
+		let directory = prepare_test_directory(test_name!());
+		ctx.search.persist(&directory).unwrap();
+		assert!(ctx.search.engine.as_ref().unwrap().memory.is_none());
+		assert_eq!(ctx.search("seasons"), vec![PathBuf::from("seasons.mp3")]);
+	}
+
 	#[test]
 	fn can_find_field_like() {
 		let ctx = setup_test(vec![
@@ -425,7 +636,7 @@ mod test {
 			},
 		]);
 
-		let songs = ctx.search("artist % agon");
+		let songs = ctx.search("artist % dragon");
 		assert_eq!(songs.len(), 1);
 		assert!(songs.contains(&PathBuf::from("seasons.mp3")));
 	}
@@ -515,6 +726,60 @@ mod test {
 		assert!(songs.contains(&PathBuf::from("2000.mp3")));
 	}
 
+	#[test]
+	fn can_query_bpm_and_key() {
+		let ctx = setup_test(vec![
+			scanner::Song {
+				virtual_path: PathBuf::from("warmup.mp3"),
+				bpm: Some(100),
+				initial_key: Some("Am".to_owned()),
+				..Default::default()
+			},
+			scanner::Song {
+				virtual_path: PathBuf::from("sprint.mp3"),
+				bpm: Some(174),
+				initial_key: Some("F#m".to_owned()),
+				..Default::default()
+			},
+			scanner::Song {
+				virtual_path: PathBuf::from("untagged.mp3"),
+				..Default::default()
+			},
+		]);
+
+		let songs = ctx.search("bpm>=120");
+		assert_eq!(songs, vec![PathBuf::from("sprint.mp3")]);
+
+		let songs = ctx.search("key=am");
+		assert_eq!(songs, vec![PathBuf::from("warmup.mp3")]);
+	}
+
+	#[test]
+	fn can_query_field_scoped_syntax() {
+		let ctx = setup_test(vec![
+			scanner::Song {
+				virtual_path: PathBuf::from("roygbiv.mp3"),
+				artists: vec!["Boards of Canada".to_owned()],
+				album: Some("Music Has the Right to Children".to_owned()),
+				year: Some(1998),
+				..Default::default()
+			},
+			scanner::Song {
+				virtual_path: PathBuf::from("reach for the dead.mp3"),
+				artists: vec!["Boards of Canada".to_owned()],
+				album: Some("Tomorrow's Harvest".to_owned()),
+				year: Some(2013),
+				..Default::default()
+			},
+		]);
+
+		let songs = ctx.search(r#"artist:boards album:"music has the right""#);
+		assert_eq!(songs, vec![PathBuf::from("roygbiv.mp3")]);
+
+		let songs = ctx.search("artist:boards year:>2000");
+		assert_eq!(songs, vec![PathBuf::from("reach for the dead.mp3")]);
+	}
+
 	#[test]
 	fn fuzzy_numbers_query_all_fields() {
 		let ctx = setup_test(vec![
@@ -675,6 +940,74 @@ mod test {
 		);
 	}
 
+	#[test]
+	fn results_are_ranked_by_relevance() {
+		let ctx = setup_test(vec![
+			scanner::Song {
+				virtual_path: PathBuf::from("1.mp3"),
+				title: Some("Seasonstorm".to_owned()),
+				artists: vec!["Aaa".to_owned()],
+				..Default::default()
+			},
+			scanner::Song {
+				virtual_path: PathBuf::from("2.mp3"),
+				title: Some("Seasons".to_owned()),
+				artists: vec!["Zzz".to_owned()],
+				..Default::default()
+			},
+		]);
+
+		let songs = ctx.search("seasons");
+		assert_eq!(songs, vec![PathBuf::from("2.mp3"), PathBuf::from("1.mp3")]);
+	}
+
+	#[test]
+	fn can_find_phrase() {
+		let ctx = setup_test(vec![
+			scanner::Song {
+				virtual_path: PathBuf::from("one.mp3"),
+				title: Some("The Power of One".to_owned()),
+				..Default::default()
+			},
+			scanner::Song {
+				virtual_path: PathBuf::from("potd.mp3"),
+				title: Some("Power of the Dragonflame".to_owned()),
+				..Default::default()
+			},
+		]);
+
+		let songs = ctx.search(r#""the power""#);
+		assert_eq!(songs, vec![PathBuf::from("one.mp3")]);
+	}
+
+	#[test]
+	fn text_is_folded() {
+		let ctx = setup_test(vec![
+			scanner::Song {
+				virtual_path: PathBuf::from("hoppipolla.mp3"),
+				artists: vec!["Sigur Rós".to_owned()],
+				..Default::default()
+			},
+			scanner::Song {
+				virtual_path: PathBuf::from("fullwidth.mp3"),
+				title: Some("Ｔｏｋｙｏ　Ｎｉｇｈｔ".to_owned()),
+				..Default::default()
+			},
+		]);
+
+		let songs = ctx.search("sigur ros");
+		assert_eq!(songs, vec![PathBuf::from("hoppipolla.mp3")]);
+
+		let songs = ctx.search("artist = \"SIGUR ROS\"");
+		assert_eq!(songs, vec![PathBuf::from("hoppipolla.mp3")]);
+
+		let songs = ctx.search("tokyo night");
+		assert_eq!(songs, vec![PathBuf::from("fullwidth.mp3")]);
+
+		let songs = ctx.search("ｓｉｇｕｒ");
+		assert_eq!(songs, vec![PathBuf::from("hoppipolla.mp3")]);
+	}
+
 	#[test]
 	fn avoids_bigram_false_positives() {
 		let ctx = setup_test(vec![scanner::Song {
@@ -37,6 +37,7 @@ pub struct Artist {
 	pub albums_as_lyricist: HashSet<AlbumKey>,
 	pub num_songs_by_genre: HashMap<Spur, u32>,
 	pub num_songs: u32,
+	pub musicbrainz_id: Option<Spur>,
 }
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
@@ -46,6 +47,8 @@ pub struct Album {
 	pub artists: TinyVec<[ArtistKey; 1]>,
 	pub year: Option<i64>,
 	pub date_added: i64,
+	pub compilation: bool,
+	pub musicbrainz_release_id: Option<Spur>,
 	pub songs: HashSet<SongKey>,
 }
 
@@ -55,18 +58,39 @@ pub struct Song {
 	pub virtual_path: PathKey,
 	pub track_number: Option<i64>,
 	pub disc_number: Option<i64>,
+	pub disc_subtitle: Option<Spur>,
 	pub title: Option<Spur>,
 	pub artists: TinyVec<[ArtistKey; 1]>,
 	pub album_artists: TinyVec<[ArtistKey; 1]>,
 	pub year: Option<i64>,
 	pub album: Option<Spur>,
 	pub artwork: Option<PathKey>,
+	pub lyrics: Option<PathKey>,
 	pub duration: Option<i64>,
 	pub lyricists: TinyVec<[ArtistKey; 0]>,
 	pub composers: TinyVec<[ArtistKey; 0]>,
 	pub genres: TinyVec<[Spur; 1]>,
 	pub labels: TinyVec<[Spur; 0]>,
+	pub compilation: bool,
+	pub conductors: TinyVec<[ArtistKey; 0]>,
+	pub performers: TinyVec<[ArtistKey; 0]>,
+	pub work: Option<Spur>,
+	pub movement: Option<Spur>,
+	pub bpm: Option<i64>,
+	pub initial_key: Option<Spur>,
+	pub track_gain: Option<i32>,
+	pub track_peak: Option<u32>,
+	pub album_gain: Option<i32>,
+	pub album_peak: Option<u32>,
+	pub musicbrainz_recording_id: Option<Spur>,
+	pub musicbrainz_release_id: Option<Spur>,
+	pub musicbrainz_artist_ids: TinyVec<[Spur; 0]>,
 	pub date_added: i64,
+	pub audible_start: Option<i64>,
+	pub audible_end: Option<i64>,
+	pub fingerprint: Option<u64>,
+	pub segment_start: Option<i64>,
+	pub segment_end: Option<i64>,
 }
 
 #[derive(
@@ -125,13 +149,41 @@ pub fn store_song(
 		None => None,
 	};
 
+	let lyrics = match &song.lyrics {
+		Some(l) => Some(l.get_or_intern(dictionary_builder)?),
+		None => None,
+	};
+
+	let musicbrainz_recording_id = song
+		.musicbrainz_recording_id
+		.as_ref()
+		.map(|id| intern_mbid(dictionary_builder, id));
+	let musicbrainz_release_id = song
+		.musicbrainz_release_id
+		.as_ref()
+		.map(|id| intern_mbid(dictionary_builder, id));
+	let musicbrainz_artist_ids = song
+		.musicbrainz_artist_ids
+		.iter()
+		.map(|id| intern_mbid(dictionary_builder, id))
+		.collect();
+
 	let mut canonicalize = |s: &String| dictionary_builder.get_or_intern_canon(s);
 
+	// Spellings of the same genre would otherwise count the song several times
+	let mut genres = TinyVec::<[Spur; 1]>::new();
+	for genre in song.genres.iter().filter_map(&mut canonicalize) {
+		if !genres.contains(&genre) {
+			genres.push(genre);
+		}
+	}
+
 	Some(Song {
 		real_path,
 		virtual_path,
 		track_number: song.track_number,
 		disc_number: song.disc_number,
+		disc_subtitle: song.disc_subtitle.as_ref().and_then(&mut canonicalize),
 		title: song.title.as_ref().and_then(&mut canonicalize),
 		artists: song
 			.artists
@@ -148,6 +200,7 @@ pub fn store_song(
 		year: song.year,
 		album: song.album.as_ref().and_then(&mut canonicalize),
 		artwork: artwork,
+		lyrics,
 		duration: song.duration,
 		lyricists: song
 			.lyricists
@@ -161,18 +214,55 @@ pub fn store_song(
 			.filter_map(&mut canonicalize)
 			.map(ArtistKey)
 			.collect(),
-		genres: song.genres.iter().filter_map(&mut canonicalize).collect(),
+		genres,
 		labels: song.labels.iter().filter_map(&mut canonicalize).collect(),
+		compilation: song.compilation,
+		conductors: song
+			.conductors
+			.iter()
+			.filter_map(&mut canonicalize)
+			.map(ArtistKey)
+			.collect(),
+		performers: song
+			.performers
+			.iter()
+			.filter_map(&mut canonicalize)
+			.map(ArtistKey)
+			.collect(),
+		work: song.work.as_ref().and_then(&mut canonicalize),
+		movement: song.movement.as_ref().and_then(&mut canonicalize),
+		bpm: song.bpm,
+		initial_key: song.initial_key.as_ref().and_then(&mut canonicalize),
 		date_added: song.date_added,
+		track_gain: song.replay_gain.track_gain,
+		track_peak: song.replay_gain.track_peak,
+		album_gain: song.replay_gain.album_gain,
+		album_peak: song.replay_gain.album_peak,
+		musicbrainz_recording_id,
+		musicbrainz_release_id,
+		musicbrainz_artist_ids,
+		audible_start: song.audible_range.map(|r| r.start),
+		audible_end: song.audible_range.map(|r| r.end),
+		fingerprint: song.fingerprint,
+		segment_start: song.segment.map(|s| s.start),
+		segment_end: song.segment.and_then(|s| s.end),
 	})
 }
 
+// MusicBrainz identifiers are looked up verbatim rather than through canonical spellings
+pub fn intern_mbid(dictionary_builder: &mut dictionary::Builder, mbid: &str) -> Spur {
+	dictionary_builder.get_or_intern(mbid.trim().to_ascii_lowercase())
+}
+
 pub fn fetch_song(dictionary: &Dictionary, song: &Song) -> super::Song {
 	super::Song {
 		real_path: PathBuf::from(dictionary.resolve(&song.real_path.0)),
 		virtual_path: PathBuf::from(dictionary.resolve(&song.virtual_path.0)),
 		track_number: song.track_number,
 		disc_number: song.disc_number,
+		disc_subtitle: song
+			.disc_subtitle
+			.map(|s| dictionary.resolve(&s).to_string()),
 		title: song.title.map(|s| dictionary.resolve(&s).to_string()),
 		artists: song
 			.artists
@@ -189,6 +279,7 @@ pub fn fetch_song(dictionary: &Dictionary, song: &Song) -> super::Song {
 		artwork: song
 			.artwork
 			.map(|a| PathBuf::from(dictionary.resolve(&a.0))),
+		lyrics: song.lyrics.map(|l| PathBuf::from(dictionary.resolve(&l.0))),
 		duration: song.duration,
 		lyricists: song
 			.lyricists
@@ -210,7 +301,42 @@ pub fn fetch_song(dictionary: &Dictionary, song: &Song) -> super::Song {
 			.iter()
 			.map(|s| dictionary.resolve(s).to_string())
 			.collect(),
+		compilation: song.compilation,
+		conductors: song
+			.conductors
+			.iter()
+			.map(|k| dictionary.resolve(&k.0).to_string())
+			.collect(),
+		performers: song
+			.performers
+			.iter()
+			.map(|k| dictionary.resolve(&k.0).to_string())
+			.collect(),
+		work: song.work.map(|s| dictionary.resolve(&s).to_string()),
+		movement: song.movement.map(|s| dictionary.resolve(&s).to_string()),
+		bpm: song.bpm,
+		initial_key: song.initial_key.map(|s| dictionary.resolve(&s).to_string()),
 		date_added: song.date_added,
+		track_gain: song.track_gain,
+		track_peak: song.track_peak,
+		album_gain: song.album_gain,
+		album_peak: song.album_peak,
+		musicbrainz_recording_id: song
+			.musicbrainz_recording_id
+			.map(|s| dictionary.resolve(&s).to_string()),
+		musicbrainz_release_id: song
+			.musicbrainz_release_id
+			.map(|s| dictionary.resolve(&s).to_string()),
+		musicbrainz_artist_ids: song
+			.musicbrainz_artist_ids
+			.iter()
+			.map(|s| dictionary.resolve(s).to_string())
+			.collect(),
+		audible_start: song.audible_start,
+		audible_end: song.audible_end,
+		fingerprint: song.fingerprint,
+		segment_start: song.segment_start,
+		segment_end: song.segment_end,
 	}
 }
 
@@ -0,0 +1,342 @@
+use std::net::IpAddr;
+
+use native_db::*;
+use native_model::{native_model, Model};
+use rand::distributions::{Alphanumeric, DistString};
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use tokio::task::spawn_blocking;
+
+use crate::app::{auth, config, ndb, throttle, Error};
+
+pub const DEFAULT_VALIDITY_DAYS: u32 = 7;
+
+// Single-use tokens which let people create their own (non-admin) account
+#[derive(Clone)]
+pub struct Manager {
+	db: ndb::Manager,
+	config_manager: config::Manager,
+	throttle: throttle::Throttle,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Invite {
+	pub id: String,
+	pub created_by: String,
+	pub created_at: i64,
+	pub expires_at: i64,
+}
+
+pub type InviteModel = v1::InviteModel;
+
+pub mod v1 {
+
+	use super::*;
+
+	#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+	#[native_model(id = 7, version = 1)]
+	#[native_db]
+	pub struct InviteModel {
+		#[primary_key]
+		pub id: String,
+		pub created_by: String,
+		// Tokens are only shown when created, the database keeps a hash of their secret
+		pub secret_hash: Vec<u8>,
+		pub created_at: i64,
+		pub expires_at: i64,
+	}
+}
+
+impl From<InviteModel> for Invite {
+	fn from(i: InviteModel) -> Self {
+		Self {
+			id: i.id,
+			created_by: i.created_by,
+			created_at: i.created_at,
+			expires_at: i.expires_at,
+		}
+	}
+}
+
+// Deletes invites which can no longer be used, and returns the others
+fn remove_expired_invites(
+	transaction: &transaction::RwTransaction,
+	now: i64,
+) -> Result<Vec<InviteModel>, Error> {
+	let (invites, expired): (Vec<_>, Vec<_>) = transaction
+		.scan()
+		.primary::<InviteModel>()?
+		.all()?
+		.filter_map(|i| i.ok())
+		.partition(|i: &InviteModel| i.expires_at > now);
+	for invite in expired {
+		transaction.remove::<InviteModel>(invite)?;
+	}
+	Ok(invites)
+}
+
+impl Manager {
+	pub fn new(db: ndb::Manager, config_manager: config::Manager) -> Self {
+		Self {
+			db,
+			config_manager,
+			throttle: throttle::Throttle::default(),
+		}
+	}
+
+	// Returns the new invite, along with the token to hand out to the person being invited
+	pub async fn create_invite(
+		&self,
+		created_by: &str,
+		validity_days: u32,
+	) -> Result<(Invite, String), Error> {
+		if validity_days == 0 {
+			return Err(Error::InviteValidityInvalid);
+		}
+
+		let id = Alphanumeric.sample_string(&mut OsRng, 16);
+		let secret = Alphanumeric.sample_string(&mut OsRng, 32);
+		let created_at = auth::now() as i64;
+		let model = InviteModel {
+			id: id.clone(),
+			created_by: created_by.to_owned(),
+			secret_hash: auth::hash_secret(&secret),
+			created_at,
+			expires_at: created_at + i64::from(validity_days) * 24 * 60 * 60,
+		};
+
+		spawn_blocking({
+			let manager = self.clone();
+			let model = model.clone();
+			move || -> Result<(), Error> {
+				let transaction = manager.db.rw_transaction()?;
+				remove_expired_invites(&transaction, model.created_at)?;
+				transaction.insert::<InviteModel>(model)?;
+				transaction.commit()?;
+				Ok(())
+			}
+		})
+		.await??;
+
+		Ok((model.into(), format!("{id}_{secret}")))
+	}
+
+	// Expired invites are deleted rather than listed
+	pub async fn list_invites(&self) -> Result<Vec<Invite>, Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			move || {
+				let transaction = manager.db.rw_transaction()?;
+				let invites = remove_expired_invites(&transaction, auth::now() as i64)?;
+				transaction.commit()?;
+				let mut invites = invites.into_iter().map(Invite::from).collect::<Vec<_>>();
+				invites.sort_by_key(|i| i.created_at);
+				Ok(invites)
+			}
+		})
+		.await?
+	}
+
+	pub async fn revoke_invite(&self, id: &str) -> Result<(), Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			let id = id.to_owned();
+			move || {
+				let transaction = manager.db.rw_transaction()?;
+				let invite = transaction
+					.get()
+					.primary::<InviteModel>(id)?
+					.ok_or(Error::InviteNotFound)?;
+				transaction.remove::<InviteModel>(invite)?;
+				transaction.commit()?;
+				Ok(())
+			}
+		})
+		.await?
+	}
+
+	// Creates a non-admin account, using up the invite
+	pub async fn register(
+		&self,
+		token: &str,
+		username: &str,
+		password: &str,
+		address: Option<IpAddr>,
+	) -> Result<(), Error> {
+		self.throttle.check(address, username)?;
+		let invite = match self.take_invite(token).await {
+			Ok(invite) => invite,
+			Err(e) => {
+				if matches!(e, Error::InviteInvalid) {
+					self.throttle.record_failure(address, username);
+				}
+				return Err(e);
+			}
+		};
+		self.throttle.record_success(address, username);
+
+		let result = self.config_manager.register_user(username, password).await;
+
+		// Invites remain usable when registration fails, eg. for a taken username
+		if result.is_err() {
+			spawn_blocking({
+				let manager = self.clone();
+				move || -> Result<(), Error> {
+					let transaction = manager.db.rw_transaction()?;
+					transaction.insert::<InviteModel>(invite)?;
+					transaction.commit()?;
+					Ok(())
+				}
+			})
+			.await??;
+		}
+
+		result
+	}
+
+	async fn take_invite(&self, token: &str) -> Result<InviteModel, Error> {
+		let (id, secret) = token.split_once('_').ok_or(Error::InviteInvalid)?;
+		spawn_blocking({
+			let manager = self.clone();
+			let id = id.to_owned();
+			let secret_hash = auth::hash_secret(secret);
+			move || {
+				let transaction = manager.db.rw_transaction()?;
+				let Some(invite) = transaction
+					.get()
+					.primary::<InviteModel>(id)?
+					.filter(|i| i.secret_hash == secret_hash)
+				else {
+					return Err(Error::InviteInvalid);
+				};
+				transaction.remove::<InviteModel>(invite.clone())?;
+				transaction.commit()?;
+				if invite.expires_at <= auth::now() as i64 {
+					return Err(Error::InviteInvalid);
+				}
+				Ok(invite)
+			}
+		})
+		.await?
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::app::test;
+	use crate::test_name;
+
+	const TEST_ADMIN: &str = "test_admin";
+	const TEST_PASSWORD: &str = "password";
+
+	#[tokio::test]
+	async fn invites_are_single_use() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_ADMIN, TEST_PASSWORD, true)
+			.build()
+			.await;
+
+		let (invite, token) = ctx
+			.invite_manager
+			.create_invite(TEST_ADMIN, DEFAULT_VALIDITY_DAYS)
+			.await
+			.unwrap();
+		assert_eq!(invite.created_by, TEST_ADMIN);
+		assert_eq!(
+			ctx.invite_manager.list_invites().await.unwrap(),
+			vec![invite]
+		);
+
+		// Failed registrations do not use up the invite
+		assert!(matches!(
+			ctx.invite_manager
+				.register(&token, TEST_ADMIN, "hunter2", None)
+				.await,
+			Err(Error::DuplicateUsername)
+		));
+
+		ctx.invite_manager
+			.register(&token, "new_user", "hunter2", None)
+			.await
+			.unwrap();
+		let user = ctx.config_manager.get_user("new_user").await.unwrap();
+		assert!(!user.is_admin());
+		assert!(ctx
+			.config_manager
+			.login("new_user", "hunter2", None)
+			.await
+			.is_ok());
+
+		assert!(matches!(
+			ctx.invite_manager
+				.register(&token, "other_user", "hunter2", None)
+				.await,
+			Err(Error::InviteInvalid)
+		));
+		assert!(ctx.invite_manager.list_invites().await.unwrap().is_empty());
+	}
+
+	#[tokio::test]
+	async fn rejects_forged_and_revoked_invites() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_ADMIN, TEST_PASSWORD, true)
+			.build()
+			.await;
+
+		let (invite, token) = ctx
+			.invite_manager
+			.create_invite(TEST_ADMIN, 1)
+			.await
+			.unwrap();
+
+		let forged = format!("{}_garbage", invite.id);
+		assert!(matches!(
+			ctx.invite_manager
+				.register(&forged, "new_user", "hunter2", None)
+				.await,
+			Err(Error::InviteInvalid)
+		));
+
+		ctx.invite_manager.revoke_invite(&invite.id).await.unwrap();
+		assert!(matches!(
+			ctx.invite_manager
+				.register(&token, "new_user", "hunter2", None)
+				.await,
+			Err(Error::InviteInvalid)
+		));
+		assert!(matches!(
+			ctx.invite_manager.revoke_invite(&invite.id).await,
+			Err(Error::InviteNotFound)
+		));
+		assert!(matches!(
+			ctx.invite_manager.create_invite(TEST_ADMIN, 0).await,
+			Err(Error::InviteValidityInvalid)
+		));
+	}
+
+	#[tokio::test]
+	async fn expired_invites_are_deleted() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_ADMIN, TEST_PASSWORD, true)
+			.build()
+			.await;
+
+		let expired = InviteModel {
+			id: "expired".to_owned(),
+			created_by: TEST_ADMIN.to_owned(),
+			secret_hash: auth::hash_secret("secret"),
+			created_at: 0,
+			expires_at: 1,
+		};
+		let transaction = ctx.invite_manager.db.rw_transaction().unwrap();
+		transaction.insert::<InviteModel>(expired).unwrap();
+		transaction.commit().unwrap();
+
+		assert!(ctx.invite_manager.list_invites().await.unwrap().is_empty());
+		assert!(matches!(
+			ctx.invite_manager.revoke_invite("expired").await,
+			Err(Error::InviteNotFound)
+		));
+	}
+}
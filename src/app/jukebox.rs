@@ -0,0 +1,371 @@
+use std::{
+	path::PathBuf,
+	sync::{Arc, OnceLock},
+	time::Duration,
+};
+
+use log::{error, info};
+use tokio::sync::{mpsc, Mutex};
+
+use crate::app::{config, index, Error};
+
+mod output;
+
+use output::{Command, Event, Output};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Playback {
+	#[default]
+	Stopped,
+	Playing,
+	Paused,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Status {
+	pub playback: Playback,
+	pub queue: Vec<PathBuf>,
+	pub current: Option<usize>,
+	pub position: Duration,
+	pub volume: f32,
+}
+
+#[derive(Debug)]
+struct State {
+	playback: Playback,
+	queue: Vec<PathBuf>,
+	current: Option<usize>,
+	volume: f32,
+}
+
+impl Default for State {
+	fn default() -> Self {
+		Self {
+			playback: Playback::default(),
+			queue: vec![],
+			current: None,
+			volume: 1.0,
+		}
+	}
+}
+
+// Plays songs through the audio device of the machine running Polaris, from a queue controlled
+// over the API.
+#[derive(Clone)]
+pub struct Manager {
+	config_manager: config::Manager,
+	index_manager: index::Manager,
+	output: Arc<OnceLock<Output>>,
+	state: Arc<Mutex<State>>,
+}
+
+impl Manager {
+	pub fn new(config_manager: config::Manager, index_manager: index::Manager) -> Self {
+		Self {
+			config_manager,
+			index_manager,
+			output: Arc::default(),
+			state: Arc::default(),
+		}
+	}
+
+	pub async fn begin_playback(&self) {
+		let Some(jukebox) = self.config_manager.get_jukebox().await else {
+			return;
+		};
+
+		let (events_sender, mut events) = mpsc::unbounded_channel();
+		let device = jukebox.device.clone();
+		let output =
+			match tokio::task::spawn_blocking(move || output::open(device, events_sender)).await {
+				Ok(Ok(o)) => o,
+				Ok(Err(e)) => {
+					error!("Could not start jukebox: {e}");
+					return;
+				}
+				Err(e) => {
+					error!("Could not start jukebox: {e}");
+					return;
+				}
+			};
+
+		if self.output.set(output).is_err() {
+			return;
+		}
+
+		info!(
+			"Jukebox is playing through {}",
+			jukebox
+				.device
+				.as_deref()
+				.unwrap_or("the default audio device")
+		);
+
+		tokio::spawn({
+			let manager = self.clone();
+			async move {
+				while let Some(Event::Finished) = events.recv().await {
+					if let Err(e) = manager.on_finished().await {
+						error!("Jukebox could not play next song: {e}");
+					}
+				}
+			}
+		});
+	}
+
+	fn output(&self) -> Result<&Output, Error> {
+		self.output.get().ok_or(Error::JukeboxUnavailable)
+	}
+
+	pub async fn get_status(&self) -> Result<Status, Error> {
+		let output = self.output()?;
+		let state = self.state.lock().await;
+		Ok(Status {
+			playback: state.playback,
+			queue: state.queue.clone(),
+			current: state.current,
+			position: match state.playback {
+				Playback::Stopped => Duration::ZERO,
+				_ => output.position(),
+			},
+			volume: state.volume,
+		})
+	}
+
+	async fn find_songs(&self, virtual_paths: Vec<PathBuf>) -> Vec<PathBuf> {
+		self.index_manager
+			.get_songs(virtual_paths)
+			.await
+			.into_iter()
+			.filter_map(|s| s.ok())
+			.map(|s| s.virtual_path)
+			.collect()
+	}
+
+	pub async fn set_queue(&self, virtual_paths: Vec<PathBuf>) -> Result<(), Error> {
+		let output = self.output()?;
+		let songs = self.find_songs(virtual_paths).await;
+		let mut state = self.state.lock().await;
+		output.send(Command::Stop)?;
+		state.playback = Playback::Stopped;
+		state.current = None;
+		state.queue = songs;
+		Ok(())
+	}
+
+	pub async fn add_to_queue(&self, virtual_paths: Vec<PathBuf>) -> Result<(), Error> {
+		self.output()?;
+		let songs = self.find_songs(virtual_paths).await;
+		self.state.lock().await.queue.extend(songs);
+		Ok(())
+	}
+
+	// Starts playing the song at the given queue position, or resumes playback if there is none
+	pub async fn play(&self, position: Option<usize>) -> Result<(), Error> {
+		let output = self.output()?;
+		let mut state = self.state.lock().await;
+		match position {
+			Some(p) if p >= state.queue.len() => Err(Error::JukeboxPositionInvalid),
+			Some(p) => self.start(&mut state, output, p).await,
+			None => match state.playback {
+				Playback::Playing => Ok(()),
+				Playback::Paused => {
+					output.send(Command::Resume)?;
+					state.playback = Playback::Playing;
+					Ok(())
+				}
+				Playback::Stopped => match state.current.unwrap_or_default() {
+					p if p < state.queue.len() => self.start(&mut state, output, p).await,
+					_ => Ok(()),
+				},
+			},
+		}
+	}
+
+	pub async fn pause(&self) -> Result<(), Error> {
+		let output = self.output()?;
+		let mut state = self.state.lock().await;
+		if state.playback == Playback::Playing {
+			output.send(Command::Pause)?;
+			state.playback = Playback::Paused;
+		}
+		Ok(())
+	}
+
+	pub async fn stop(&self) -> Result<(), Error> {
+		let output = self.output()?;
+		let mut state = self.state.lock().await;
+		output.send(Command::Stop)?;
+		state.playback = Playback::Stopped;
+		Ok(())
+	}
+
+	pub async fn next(&self) -> Result<(), Error> {
+		let output = self.output()?;
+		let mut state = self.state.lock().await;
+		let next = state.current.map_or(0, |c| c + 1);
+		if next < state.queue.len() {
+			self.skip_to(&mut state, output, next).await
+		} else {
+			output.send(Command::Stop)?;
+			state.playback = Playback::Stopped;
+			state.current = None;
+			Ok(())
+		}
+	}
+
+	pub async fn previous(&self) -> Result<(), Error> {
+		let output = self.output()?;
+		let mut state = self.state.lock().await;
+		match state.current {
+			Some(c) => self.skip_to(&mut state, output, c.saturating_sub(1)).await,
+			None => Ok(()),
+		}
+	}
+
+	pub async fn seek(&self, position: Duration) -> Result<(), Error> {
+		let output = self.output()?;
+		let state = self.state.lock().await;
+		if state.playback != Playback::Stopped {
+			output.send(Command::Seek(position))?;
+		}
+		Ok(())
+	}
+
+	pub async fn set_volume(&self, volume: f32) -> Result<(), Error> {
+		let output = self.output()?;
+		let mut state = self.state.lock().await;
+		state.volume = volume.clamp(0.0, 1.0);
+		output.send(Command::Volume(state.volume))
+	}
+
+	async fn on_finished(&self) -> Result<(), Error> {
+		if self.state.lock().await.playback != Playback::Playing {
+			return Ok(());
+		}
+		self.next().await
+	}
+
+	// Moves to another song, only starting playback if the jukebox was not stopped
+	async fn skip_to(
+		&self,
+		state: &mut State,
+		output: &Output,
+		position: usize,
+	) -> Result<(), Error> {
+		match state.playback {
+			Playback::Stopped => {
+				state.current = Some(position);
+				Ok(())
+			}
+			_ => self.start(state, output, position).await,
+		}
+	}
+
+	async fn start(
+		&self,
+		state: &mut State,
+		output: &Output,
+		position: usize,
+	) -> Result<(), Error> {
+		let real_path = self
+			.config_manager
+			.resolve_virtual_path(&state.queue[position])
+			.await?;
+		output.send(Command::Play(real_path))?;
+		state.current = Some(position);
+		state.playback = Playback::Playing;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::sync::mpsc::Receiver;
+
+	use super::*;
+	use crate::app::test;
+	use crate::test_name;
+
+	async fn make_jukebox(test_name: String) -> (Manager, Receiver<Command>, Vec<PathBuf>) {
+		let ctx = test::ContextBuilder::new(test_name)
+			.mount("root", "test-data/small-collection")
+			.build()
+			.await;
+		ctx.scanner.run_scan().await.unwrap();
+
+		let songs = ctx
+			.index_manager
+			.flatten(PathBuf::from("root/Khemmis"))
+			.await
+			.unwrap();
+
+		let manager = Manager::new(ctx.config_manager, ctx.index_manager);
+		let (output, commands) = Output::detached();
+		manager.output.set(output).ok();
+		(manager, commands, songs)
+	}
+
+	#[tokio::test]
+	async fn queue_plays_in_order() {
+		let (jukebox, commands, songs) = make_jukebox(test_name!()).await;
+		assert!(songs.len() > 1);
+		jukebox.set_queue(songs.clone()).await.unwrap();
+		assert_eq!(commands.try_recv(), Ok(Command::Stop));
+
+		jukebox.play(None).await.unwrap();
+		assert!(
+			matches!(commands.try_recv(), Ok(Command::Play(p)) if p.ends_with(songs[0].file_name().unwrap()))
+		);
+
+		jukebox.on_finished().await.unwrap();
+		assert!(
+			matches!(commands.try_recv(), Ok(Command::Play(p)) if p.ends_with(songs[1].file_name().unwrap()))
+		);
+
+		jukebox.play(Some(songs.len() - 1)).await.unwrap();
+		commands.try_recv().unwrap();
+		jukebox.next().await.unwrap();
+		assert_eq!(commands.try_recv(), Ok(Command::Stop));
+
+		let status = jukebox.get_status().await.unwrap();
+		assert_eq!(status.playback, Playback::Stopped);
+		assert_eq!(status.current, None);
+		assert_eq!(status.queue, songs);
+	}
+
+	#[tokio::test]
+	async fn pause_and_resume() {
+		let (jukebox, commands, songs) = make_jukebox(test_name!()).await;
+		jukebox.add_to_queue(songs).await.unwrap();
+		jukebox.play(Some(0)).await.unwrap();
+		commands.try_recv().unwrap();
+
+		jukebox.pause().await.unwrap();
+		assert_eq!(commands.try_recv(), Ok(Command::Pause));
+		assert_eq!(
+			jukebox.get_status().await.unwrap().playback,
+			Playback::Paused
+		);
+
+		jukebox.on_finished().await.unwrap();
+		assert!(commands.try_recv().is_err());
+
+		jukebox.play(None).await.unwrap();
+		assert_eq!(commands.try_recv(), Ok(Command::Resume));
+	}
+
+	#[tokio::test]
+	async fn rejects_invalid_position() {
+		let (jukebox, _commands, songs) = make_jukebox(test_name!()).await;
+		jukebox
+			.add_to_queue(vec![songs[0].clone(), PathBuf::from("not/a/song.mp3")])
+			.await
+			.unwrap();
+		assert_eq!(jukebox.get_status().await.unwrap().queue.len(), 1);
+		assert!(matches!(
+			jukebox.play(Some(1)).await,
+			Err(Error::JukeboxPositionInvalid)
+		));
+	}
+}
@@ -0,0 +1,187 @@
+use std::{
+	path::PathBuf,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		mpsc, Arc,
+	},
+	time::Duration,
+};
+
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::app::Error;
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+	Play(PathBuf),
+	Pause,
+	Resume,
+	Stop,
+	Seek(Duration),
+	Volume(f32),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(not(feature = "jukebox"), allow(dead_code))]
+pub enum Event {
+	Finished,
+}
+
+// Handle to the thread which owns the audio device. Audio streams cannot be moved across threads,
+// so all playback happens on that thread and is driven by commands sent over a channel.
+pub struct Output {
+	commands: mpsc::Sender<Command>,
+	position_ms: Arc<AtomicU64>,
+}
+
+impl Output {
+	pub fn send(&self, command: Command) -> Result<(), Error> {
+		self.commands
+			.send(command)
+			.map_err(|_| Error::JukeboxUnavailable)
+	}
+
+	// Playback position within the current song
+	pub fn position(&self) -> Duration {
+		Duration::from_millis(self.position_ms.load(Ordering::Relaxed))
+	}
+
+	#[cfg(test)]
+	pub fn detached() -> (Self, mpsc::Receiver<Command>) {
+		let (commands, receiver) = mpsc::channel();
+		let output = Self {
+			commands,
+			position_ms: Arc::default(),
+		};
+		(output, receiver)
+	}
+}
+
+#[cfg(not(feature = "jukebox"))]
+pub fn open(_device: Option<String>, _events: UnboundedSender<Event>) -> Result<Output, Error> {
+	Err(Error::AudioOutput(
+		"Polaris was built without the `jukebox` feature".to_owned(),
+	))
+}
+
+#[cfg(feature = "jukebox")]
+pub fn open(device: Option<String>, events: UnboundedSender<Event>) -> Result<Output, Error> {
+	let (commands, receiver) = mpsc::channel();
+	let (ready_sender, ready_receiver) = mpsc::channel();
+	let position_ms = Arc::<AtomicU64>::default();
+	std::thread::Builder::new()
+		.name("jukebox".to_owned())
+		.spawn({
+			let position_ms = position_ms.clone();
+			move || playback::run(device, receiver, events, position_ms, ready_sender)
+		})
+		.map_err(|e| Error::AudioOutput(e.to_string()))?;
+	match ready_receiver.recv() {
+		Ok(Ok(())) => Ok(Output {
+			commands,
+			position_ms,
+		}),
+		Ok(Err(e)) => Err(Error::AudioOutput(e)),
+		Err(_) => Err(Error::AudioOutput("Playback thread exited".to_owned())),
+	}
+}
+
+#[cfg(feature = "jukebox")]
+mod playback {
+	use std::{
+		fs::File,
+		io::BufReader,
+		sync::{
+			atomic::{AtomicU64, Ordering},
+			mpsc, Arc,
+		},
+		time::Duration,
+	};
+
+	use log::error;
+	use rodio::{
+		cpal::traits::HostTrait, Decoder, DeviceTrait, OutputStream, OutputStreamHandle, Sink,
+	};
+	use tokio::sync::mpsc::UnboundedSender;
+
+	use super::{Command, Event};
+
+	fn open_stream(device: Option<&str>) -> Result<(OutputStream, OutputStreamHandle), String> {
+		let Some(name) = device else {
+			return OutputStream::try_default().map_err(|e| e.to_string());
+		};
+		let device = rodio::cpal::default_host()
+			.output_devices()
+			.map_err(|e| e.to_string())?
+			.find(|d| d.name().is_ok_and(|n| n == name))
+			.ok_or_else(|| format!("No audio output device named `{name}`"))?;
+		OutputStream::try_from_device(&device).map_err(|e| e.to_string())
+	}
+
+	pub fn run(
+		device: Option<String>,
+		commands: mpsc::Receiver<Command>,
+		events: UnboundedSender<Event>,
+		position_ms: Arc<AtomicU64>,
+		ready: mpsc::Sender<Result<(), String>>,
+	) {
+		let (_stream, sink) = match open_stream(device.as_deref()).and_then(|(stream, handle)| {
+			Ok((stream, Sink::try_new(&handle).map_err(|e| e.to_string())?))
+		}) {
+			Ok(s) => s,
+			Err(e) => {
+				ready.send(Err(e)).ok();
+				return;
+			}
+		};
+		ready.send(Ok(())).ok();
+
+		let mut playing = false;
+		loop {
+			match commands.recv_timeout(Duration::from_millis(200)) {
+				Ok(Command::Play(path)) => {
+					sink.clear();
+					let source = File::open(&path)
+						.map_err(|e| e.to_string())
+						.and_then(|f| Decoder::new(BufReader::new(f)).map_err(|e| e.to_string()));
+					match source {
+						Ok(source) => {
+							sink.append(source);
+							sink.play();
+						}
+						Err(e) => error!("Could not play `{}`: {e}", path.display()),
+					}
+					playing = true;
+				}
+				Ok(Command::Pause) => {
+					sink.pause();
+					playing = false;
+				}
+				Ok(Command::Resume) => {
+					sink.play();
+					playing = true;
+				}
+				Ok(Command::Stop) => {
+					sink.clear();
+					playing = false;
+				}
+				Ok(Command::Seek(position)) => {
+					if let Err(e) = sink.try_seek(position) {
+						error!("Could not seek: {e}");
+					}
+				}
+				Ok(Command::Volume(volume)) => sink.set_volume(volume),
+				Err(mpsc::RecvTimeoutError::Timeout) => (),
+				Err(mpsc::RecvTimeoutError::Disconnected) => return,
+			}
+
+			position_ms.store(sink.get_pos().as_millis() as u64, Ordering::Relaxed);
+			if playing && sink.empty() {
+				playing = false;
+				if events.send(Event::Finished).is_err() {
+					return;
+				}
+			}
+		}
+	}
+}
@@ -35,8 +35,33 @@ pub fn read_legacy_config(
 
 	Ok(Some(config::storage::Config {
 		album_art_pattern: Some(album_art_pattern),
+		base_path: None,
 		mount_dirs,
 		ddns_update_url: None,
+		detect_silence: None,
+		acoustid_fingerprints: None,
+		musicbrainz_enrichment: None,
+		change_detection: None,
+		scan_threads: None,
+		scan_schedule: None,
+		filename_pattern: None,
+		artist_separators: None,
+		artist_aliases: vec![],
+		album_aliases: vec![],
+		genre_aliases: vec![],
+		acme: None,
+		post_scan_hook: None,
+		dlna: None,
+		mpd: None,
+		jukebox: None,
+		artist_images: None,
+		limits: None,
+		auth: None,
+		password_policy: None,
+		oidc: None,
+		passkeys: None,
+		webhooks: vec![],
+		trusted_proxies: vec![],
 		users: users.into_values().collect(),
 	}))
 }
@@ -52,6 +77,10 @@ fn read_mount_dirs(db_file_path: &PathBuf) -> Result<Vec<config::storage::MountD
 		Ok(config::storage::MountDir {
 			source,
 			name: row.get::<_, String>(1)?,
+			exclude_patterns: vec![],
+			symlinks: None,
+			split_artists: None,
+			remote: None,
 		})
 	})?;
 
@@ -75,6 +104,14 @@ fn read_users(db_file_path: &PathBuf) -> Result<HashMap<u32, config::storage::Us
 				admin: row.get(3)?,
 				initial_password: None,
 				hashed_password: row.get(2)?,
+				password_changed_at: None,
+				token_generation: None,
+				passkeys: vec![],
+				stream_quality: None,
+				transfer_quota: None,
+				guest: None,
+				permissions: None,
+				oidc_subject: None,
 			},
 		))
 	})?;
@@ -209,8 +246,33 @@ mod test {
 
 		let expected = config::storage::Config {
 			album_art_pattern: Some("Folder.(jpeg|jpg|png)".to_owned()),
+			base_path: None,
 			mount_dirs: vec![],
 			ddns_update_url: None,
+			detect_silence: None,
+			acoustid_fingerprints: None,
+			musicbrainz_enrichment: None,
+			change_detection: None,
+			scan_threads: None,
+			scan_schedule: None,
+			filename_pattern: None,
+			artist_separators: None,
+			artist_aliases: vec![],
+			album_aliases: vec![],
+			genre_aliases: vec![],
+			acme: None,
+			post_scan_hook: None,
+			dlna: None,
+			mpd: None,
+			jukebox: None,
+			artist_images: None,
+			limits: None,
+			auth: None,
+			password_policy: None,
+			oidc: None,
+			passkeys: None,
+			webhooks: vec![],
+			trusted_proxies: vec![],
 			users: vec![],
 		};
 
@@ -228,16 +290,53 @@ mod test {
 
 		let expected = config::storage::Config {
 			album_art_pattern: Some("Folder.(jpeg|jpg|png)".to_owned()),
+			base_path: None,
 			mount_dirs: vec![config::storage::MountDir {
 				source: PathBuf::from_iter(["test-data", "small-collection"]),
 				name: "root".to_owned(),
+				exclude_patterns: vec![],
+				symlinks: None,
+				split_artists: None,
+				remote: None,
 			}],
 			ddns_update_url: None,
+			detect_silence: None,
+			acoustid_fingerprints: None,
+			musicbrainz_enrichment: None,
+			change_detection: None,
+			scan_threads: None,
+			scan_schedule: None,
+			filename_pattern: None,
+			artist_separators: None,
+			artist_aliases: vec![],
+			album_aliases: vec![],
+			genre_aliases: vec![],
+			acme: None,
+			post_scan_hook: None,
+			dlna: None,
+			mpd: None,
+			jukebox: None,
+			artist_images: None,
+			limits: None,
+			auth: None,
+			password_policy: None,
+			oidc: None,
+			passkeys: None,
+			webhooks: vec![],
+			trusted_proxies: vec![],
 			users: vec![config::storage::User {
 				name: "example_user".to_owned(),
 				admin: Some(true),
 				initial_password: None,
 				hashed_password: Some("$pbkdf2-sha256$i=10000,l=32$ADvDnwBv3kLUtjTJEwGcFA$oK43ICpNt2rbH21diMo6cSXL62qqLWOM7qs8f0s/9Oo".to_owned()),
+				password_changed_at: None,
+				token_generation: None,
+				passkeys: vec![],
+				stream_quality: None,
+				transfer_quota: None,
+				guest: None,
+				permissions: None,
+				oidc_subject: None,
 			}],
 		};
 
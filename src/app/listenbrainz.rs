@@ -0,0 +1,625 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{error, warn};
+use native_db::*;
+use native_model::{native_model, Model};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tokio::task::spawn_blocking;
+
+use crate::app::{auth, index, ndb, Error};
+
+const API_URL: &str = "https://api.listenbrainz.org/1";
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+// Delay before retrying a listen, doubled after each failed attempt
+const RETRY_DELAY: i64 = 60;
+const MAX_RETRY_DELAY: i64 = 6 * 60 * 60;
+
+// Submits the songs users listen to to their ListenBrainz account
+#[derive(Clone)]
+pub struct Manager {
+	db: ndb::Manager,
+	index_manager: index::Manager,
+	// Song last reported as playing by each user, to only submit it once
+	now_playing: Arc<Mutex<HashMap<String, PathBuf>>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Account {
+	pub user_name: String,
+	// Listens waiting to be submitted again after ListenBrainz could not be reached
+	pub pending_listens: usize,
+}
+
+pub type ListenBrainzAccountModel = v1::ListenBrainzAccountModel;
+pub type PendingListenModel = v1::PendingListenModel;
+type PendingListenModelKey = v1::PendingListenModelKey;
+
+pub mod v1 {
+
+	use super::*;
+
+	#[derive(Clone, Debug, Serialize, Deserialize)]
+	#[native_model(id = 13, version = 1)]
+	#[native_db]
+	pub struct ListenBrainzAccountModel {
+		#[primary_key]
+		pub username: String,
+		pub token: String,
+		pub user_name: String,
+	}
+
+	#[derive(Clone, Debug, Serialize, Deserialize)]
+	#[native_model(id = 14, version = 1)]
+	#[native_db]
+	pub struct PendingListenModel {
+		#[primary_key]
+		pub id: u64,
+		#[secondary_key]
+		pub username: String,
+		// Body of the submission, as sent to ListenBrainz
+		pub payload: String,
+		pub attempts: u32,
+		pub retry_at: i64,
+	}
+}
+
+fn retry_delay(attempts: u32) -> i64 {
+	RETRY_DELAY
+		.saturating_mul(1 << attempts.min(16))
+		.min(MAX_RETRY_DELAY)
+}
+
+// Submissions rejected for reasons other than rate limiting or server errors would fail again
+fn is_retryable(error: &ureq::Error) -> bool {
+	match error {
+		ureq::Error::Status(code, _) => *code == 429 || *code >= 500,
+		ureq::Error::Transport(_) => true,
+	}
+}
+
+fn post_submission(token: &str, payload: &str) -> Result<(), ureq::Error> {
+	ureq::post(&format!("{API_URL}/submit-listens"))
+		.timeout(HTTP_TIMEOUT)
+		.set("Authorization", &format!("Token {token}"))
+		.set("Content-Type", "application/json")
+		.send_string(payload)?;
+	Ok(())
+}
+
+#[derive(Deserialize)]
+struct TokenValidation {
+	valid: bool,
+	user_name: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct Submission<'a> {
+	listen_type: &'static str,
+	payload: Vec<Listen<'a>>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct Listen<'a> {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	listened_at: Option<i64>,
+	track_metadata: TrackMetadata<'a>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct TrackMetadata<'a> {
+	artist_name: String,
+	track_name: &'a str,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	release_name: Option<&'a str>,
+	additional_info: AdditionalInfo,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct AdditionalInfo {
+	submission_client: &'static str,
+	submission_client_version: &'static str,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	duration_ms: Option<i64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	tracknumber: Option<i64>,
+}
+
+// Songs without a title or artist cannot be submitted
+fn make_submission(song: &index::Song, listened_at: Option<i64>) -> Option<Submission<'_>> {
+	let track_name = song.title.as_deref()?;
+	if song.artists.is_empty() {
+		return None;
+	}
+	Some(Submission {
+		listen_type: match listened_at {
+			Some(_) => "single",
+			None => "playing_now",
+		},
+		payload: vec![Listen {
+			listened_at,
+			track_metadata: TrackMetadata {
+				artist_name: song.artists.join(", "),
+				track_name,
+				release_name: song.album.as_deref(),
+				additional_info: AdditionalInfo {
+					submission_client: "Polaris",
+					submission_client_version: env!("CARGO_PKG_VERSION"),
+					duration_ms: song.duration.map(|d| d * 1000),
+					tracknumber: song.track_number,
+				},
+			},
+		}],
+	})
+}
+
+impl Manager {
+	pub fn new(db: ndb::Manager, index_manager: index::Manager) -> Self {
+		Self {
+			db,
+			index_manager,
+			now_playing: Arc::default(),
+		}
+	}
+
+	pub async fn get_account(&self, username: &str) -> Result<Account, Error> {
+		let model = self
+			.read_account(username)
+			.await?
+			.ok_or(Error::ListenBrainzNotLinked)?;
+		let pending_listens = self.get_pending_listens(username).await?.len();
+		Ok(Account {
+			user_name: model.user_name,
+			pending_listens,
+		})
+	}
+
+	// Checks the token with ListenBrainz before saving it
+	pub async fn link(&self, username: &str, token: &str) -> Result<Account, Error> {
+		let validation: TokenValidation = spawn_blocking({
+			let token = token.to_owned();
+			move || {
+				ureq::get(&format!("{API_URL}/validate-token"))
+					.timeout(HTTP_TIMEOUT)
+					.set("Authorization", &format!("Token {token}"))
+					.call()
+					.map_err(|e| Error::ListenBrainzFailed(e.to_string()))
+					.and_then(|r| {
+						serde_json::from_reader(r.into_reader())
+							.map_err(|e| Error::ListenBrainzFailed(e.to_string()))
+					})
+			}
+		})
+		.await??;
+
+		let user_name = match validation {
+			TokenValidation {
+				valid: true,
+				user_name: Some(user_name),
+			} => user_name,
+			_ => return Err(Error::ListenBrainzTokenInvalid),
+		};
+
+		self.save_account(ListenBrainzAccountModel {
+			username: username.to_owned(),
+			token: token.to_owned(),
+			user_name: user_name.clone(),
+		})
+		.await?;
+
+		self.get_account(username).await
+	}
+
+	pub async fn unlink(&self, username: &str) -> Result<(), Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			let username = username.to_owned();
+			move || {
+				let transaction = manager.db.rw_transaction()?;
+				let account = transaction
+					.get()
+					.primary::<ListenBrainzAccountModel>(username.as_str())?
+					.ok_or(Error::ListenBrainzNotLinked)?;
+				transaction.remove::<ListenBrainzAccountModel>(account)?;
+				let pending_listens = transaction
+					.scan()
+					.secondary::<PendingListenModel>(PendingListenModelKey::username)?
+					.range(username.as_str()..=username.as_str())?
+					.collect::<Result<Vec<_>, _>>()?;
+				for listen in pending_listens {
+					transaction.remove::<PendingListenModel>(listen)?;
+				}
+				transaction.commit()?;
+				Ok(())
+			}
+		})
+		.await?
+	}
+
+	// Submits a `playing now` notification the first time a song is reported as playing
+	pub async fn report_playback(&self, username: &str, virtual_path: &Path) {
+		{
+			let mut now_playing = self.now_playing.lock().await;
+			if now_playing.get(username).is_some_and(|p| p == virtual_path) {
+				return;
+			}
+			now_playing.insert(username.to_owned(), virtual_path.to_owned());
+		}
+
+		let Some(Ok(song)) = self
+			.index_manager
+			.get_songs(vec![virtual_path.to_owned()])
+			.await
+			.into_iter()
+			.next()
+		else {
+			return;
+		};
+		self.submit(username, &song, None).await;
+	}
+
+	pub async fn submit_listen(&self, username: &str, song: &index::Song, listened_at: i64) {
+		self.submit(username, song, Some(listened_at)).await;
+	}
+
+	// Failures are logged, so that they do not affect playback. Listens which could not be
+	// delivered are queued and retried later, while `playing now` notifications are dropped.
+	async fn submit(&self, username: &str, song: &index::Song, listened_at: Option<i64>) {
+		let account = match self.read_account(username).await {
+			Ok(Some(a)) => a,
+			Ok(None) => return,
+			Err(e) => {
+				error!("Could not read ListenBrainz account: {e}");
+				return;
+			}
+		};
+		let Some(submission) = make_submission(song, listened_at) else {
+			return;
+		};
+		let Ok(payload) = serde_json::to_string(&submission) else {
+			return;
+		};
+
+		tokio::spawn({
+			let manager = self.clone();
+			async move {
+				let result = spawn_blocking({
+					let token = account.token.clone();
+					let payload = payload.clone();
+					move || post_submission(&token, &payload)
+				})
+				.await;
+				match result {
+					Ok(Ok(())) => (),
+					Ok(Err(e)) if listened_at.is_some() && is_retryable(&e) => {
+						warn!("ListenBrainz submission failed, queuing it for later: {e}");
+						if let Err(e) = manager.queue_listen(&account.username, payload).await {
+							error!("Could not queue ListenBrainz listen: {e}");
+						}
+					}
+					Ok(Err(e)) => error!("ListenBrainz submission failed: {e}"),
+					Err(e) => error!("ListenBrainz submission failed: {e}"),
+				}
+			}
+		});
+	}
+
+	pub fn begin_periodic_retries(&self) {
+		tokio::spawn({
+			let manager = self.clone();
+			async move {
+				loop {
+					if let Err(e) = manager.retry_pending_listens().await {
+						error!("ListenBrainz retry error: {e}");
+					}
+					tokio::time::sleep(RETRY_INTERVAL).await;
+				}
+			}
+		});
+	}
+
+	async fn retry_pending_listens(&self) -> Result<(), Error> {
+		for listen in self.get_due_listens(auth::now() as i64).await? {
+			let Some(account) = self.read_account(&listen.username).await? else {
+				self.remove_pending_listen(listen).await?;
+				continue;
+			};
+			let result = spawn_blocking({
+				let payload = listen.payload.clone();
+				move || post_submission(&account.token, &payload)
+			})
+			.await?;
+			match result {
+				Ok(()) => self.remove_pending_listen(listen).await?,
+				Err(e) if is_retryable(&e) => {
+					self.reschedule_pending_listen(listen, auth::now() as i64)
+						.await?;
+					// ListenBrainz is still unavailable, the other listens wait for the next round
+					break;
+				}
+				Err(e) => {
+					error!("ListenBrainz rejected a queued listen: {e}");
+					self.remove_pending_listen(listen).await?;
+				}
+			}
+		}
+		Ok(())
+	}
+
+	async fn queue_listen(&self, username: &str, payload: String) -> Result<(), Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			let username = username.to_owned();
+			move || {
+				let transaction = manager.db.rw_transaction()?;
+				let last_id = transaction
+					.scan()
+					.primary::<PendingListenModel>()?
+					.all()?
+					.next_back()
+					.transpose()?
+					.map(|l| l.id);
+				transaction.insert::<PendingListenModel>(PendingListenModel {
+					id: last_id.map_or(0, |id| id + 1),
+					username,
+					payload,
+					attempts: 1,
+					retry_at: auth::now() as i64 + retry_delay(0),
+				})?;
+				transaction.commit()?;
+				Ok(())
+			}
+		})
+		.await?
+	}
+
+	async fn get_pending_listens(&self, username: &str) -> Result<Vec<PendingListenModel>, Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			let username = username.to_owned();
+			move || {
+				let transaction = manager.db.r_transaction()?;
+				let listens = transaction
+					.scan()
+					.secondary::<PendingListenModel>(PendingListenModelKey::username)?
+					.range(username.as_str()..=username.as_str())?
+					.collect::<Result<Vec<_>, _>>()?;
+				Ok(listens)
+			}
+		})
+		.await?
+	}
+
+	// Oldest first
+	async fn get_due_listens(&self, time: i64) -> Result<Vec<PendingListenModel>, Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			move || {
+				let transaction = manager.db.r_transaction()?;
+				let listens = transaction
+					.scan()
+					.primary::<PendingListenModel>()?
+					.all()?
+					.filter_map(|l| l.ok())
+					.filter(|l| l.retry_at <= time)
+					.collect();
+				Ok(listens)
+			}
+		})
+		.await?
+	}
+
+	async fn reschedule_pending_listen(
+		&self,
+		listen: PendingListenModel,
+		time: i64,
+	) -> Result<(), Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			move || {
+				let transaction = manager.db.rw_transaction()?;
+				let updated = PendingListenModel {
+					attempts: listen.attempts + 1,
+					retry_at: time + retry_delay(listen.attempts),
+					..listen.clone()
+				};
+				transaction.update::<PendingListenModel>(listen, updated)?;
+				transaction.commit()?;
+				Ok(())
+			}
+		})
+		.await?
+	}
+
+	async fn remove_pending_listen(&self, listen: PendingListenModel) -> Result<(), Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			move || {
+				let transaction = manager.db.rw_transaction()?;
+				transaction.remove::<PendingListenModel>(listen)?;
+				transaction.commit()?;
+				Ok(())
+			}
+		})
+		.await?
+	}
+
+	async fn read_account(
+		&self,
+		username: &str,
+	) -> Result<Option<ListenBrainzAccountModel>, Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			let username = username.to_owned();
+			move || {
+				let transaction = manager.db.r_transaction()?;
+				Ok(transaction
+					.get()
+					.primary::<ListenBrainzAccountModel>(username)?)
+			}
+		})
+		.await?
+	}
+
+	async fn save_account(&self, account: ListenBrainzAccountModel) -> Result<(), Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			move || {
+				let transaction = manager.db.rw_transaction()?;
+				transaction.upsert::<ListenBrainzAccountModel>(account)?;
+				transaction.commit()?;
+				Ok(())
+			}
+		})
+		.await?
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::app::test;
+	use crate::test_name;
+
+	const TEST_USER: &str = "test_user";
+
+	#[test]
+	fn builds_submissions() {
+		let song = index::Song {
+			title: Some("Destiny".to_owned()),
+			artists: vec!["Stratovarius".to_owned()],
+			album: Some("Destiny".to_owned()),
+			duration: Some(600),
+			..Default::default()
+		};
+
+		let submission = make_submission(&song, Some(1718000000)).unwrap();
+		assert_eq!(submission.listen_type, "single");
+		let metadata = &submission.payload[0].track_metadata;
+		assert_eq!(metadata.artist_name, "Stratovarius");
+		assert_eq!(metadata.track_name, "Destiny");
+		assert_eq!(metadata.additional_info.duration_ms, Some(600_000));
+
+		let submission = make_submission(&song, None).unwrap();
+		assert_eq!(submission.listen_type, "playing_now");
+		assert!(serde_json::to_string(&submission)
+			.unwrap()
+			.find("listened_at")
+			.is_none());
+
+		let untitled = index::Song {
+			title: None,
+			..song
+		};
+		assert!(make_submission(&untitled, None).is_none());
+	}
+
+	#[tokio::test]
+	async fn can_unlink_account() {
+		let ctx = test::ContextBuilder::new(test_name!()).build().await;
+		let manager = ctx.listenbrainz_manager;
+
+		assert!(matches!(
+			manager.get_account(TEST_USER).await,
+			Err(Error::ListenBrainzNotLinked)
+		));
+
+		manager
+			.save_account(ListenBrainzAccountModel {
+				username: TEST_USER.to_owned(),
+				token: "token".to_owned(),
+				user_name: "lb_user".to_owned(),
+			})
+			.await
+			.unwrap();
+		assert_eq!(
+			manager.get_account(TEST_USER).await.unwrap(),
+			Account {
+				user_name: "lb_user".to_owned(),
+				pending_listens: 0,
+			}
+		);
+
+		manager.unlink(TEST_USER).await.unwrap();
+		assert!(matches!(
+			manager.unlink(TEST_USER).await,
+			Err(Error::ListenBrainzNotLinked)
+		));
+	}
+
+	#[test]
+	fn backs_off_retries() {
+		assert_eq!(retry_delay(0), RETRY_DELAY);
+		assert_eq!(retry_delay(1), 2 * RETRY_DELAY);
+		assert_eq!(retry_delay(3), 8 * RETRY_DELAY);
+		assert_eq!(retry_delay(40), MAX_RETRY_DELAY);
+	}
+
+	#[tokio::test]
+	async fn can_queue_listens() {
+		let ctx = test::ContextBuilder::new(test_name!()).build().await;
+		let manager = ctx.listenbrainz_manager;
+		manager
+			.save_account(ListenBrainzAccountModel {
+				username: TEST_USER.to_owned(),
+				token: "token".to_owned(),
+				user_name: "lb_user".to_owned(),
+			})
+			.await
+			.unwrap();
+
+		manager
+			.queue_listen(TEST_USER, "first".to_owned())
+			.await
+			.unwrap();
+		manager
+			.queue_listen(TEST_USER, "second".to_owned())
+			.await
+			.unwrap();
+		assert_eq!(
+			manager
+				.get_account(TEST_USER)
+				.await
+				.unwrap()
+				.pending_listens,
+			2
+		);
+		assert!(manager
+			.get_due_listens(auth::now() as i64)
+			.await
+			.unwrap()
+			.is_empty());
+
+		let later = auth::now() as i64 + MAX_RETRY_DELAY;
+		let due = manager.get_due_listens(later).await.unwrap();
+		assert_eq!(
+			due.iter().map(|l| l.payload.as_str()).collect::<Vec<_>>(),
+			vec!["first", "second"]
+		);
+
+		let mut due = due.into_iter();
+		let first = due.next().unwrap();
+		manager
+			.reschedule_pending_listen(first, later)
+			.await
+			.unwrap();
+		let second = due.next().unwrap();
+		manager.remove_pending_listen(second).await.unwrap();
+
+		assert!(manager.get_due_listens(later).await.unwrap().is_empty());
+		let pending = manager.get_pending_listens(TEST_USER).await.unwrap();
+		assert_eq!(pending[0].attempts, 2);
+		assert_eq!(pending[0].retry_at, later + retry_delay(1));
+
+		manager.unlink(TEST_USER).await.unwrap();
+		assert!(manager
+			.get_pending_listens(TEST_USER)
+			.await
+			.unwrap()
+			.is_empty());
+	}
+}
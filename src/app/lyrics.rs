@@ -0,0 +1,313 @@
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use regex::Regex;
+use tokio::task::spawn_blocking;
+
+use crate::app::Error;
+use crate::utils::{self, AudioFormat};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LyricLine {
+	/// Time at which this line starts, in milliseconds
+	pub start: i64,
+	pub text: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SyncedLyrics {
+	pub lines: Vec<LyricLine>,
+}
+
+impl SyncedLyrics {
+	fn new(mut lines: Vec<LyricLine>) -> Option<Self> {
+		if lines.is_empty() {
+			return None;
+		}
+		lines.sort_by_key(|l| l.start);
+		Some(Self { lines })
+	}
+
+	/// Index of the line being sung at the given playback position
+	pub fn line_at(&self, position: i64) -> Option<usize> {
+		match self.lines.partition_point(|l| l.start <= position) {
+			0 => None,
+			n => Some(n - 1),
+		}
+	}
+}
+
+static LRC_TIMESTAMP: LazyLock<Regex> =
+	LazyLock::new(|| Regex::new(r"\[(\d+):(\d{1,2})(?:[.:](\d{1,3}))?\]").unwrap());
+
+static LRC_OFFSET: LazyLock<Regex> =
+	LazyLock::new(|| Regex::new(r"(?m)^\s*\[offset:\s*([+-]?\d+)\s*\]").unwrap());
+
+pub fn parse_lrc(content: &str) -> Option<SyncedLyrics> {
+	// Positive offsets make lyrics appear sooner
+	let offset = LRC_OFFSET
+		.captures(content)
+		.and_then(|c| c[1].parse::<i64>().ok())
+		.unwrap_or_default();
+
+	let mut lines = vec![];
+	for line in content.lines() {
+		let mut text_start = 0;
+		let mut timestamps = vec![];
+		for captures in LRC_TIMESTAMP.captures_iter(line) {
+			let whole = captures.get(0).unwrap();
+			if whole.start() != text_start {
+				break;
+			}
+			text_start = whole.end();
+			let minutes = captures[1].parse::<i64>().unwrap_or_default();
+			let seconds = captures[2].parse::<i64>().unwrap_or_default();
+			let fraction = captures.get(3).map_or(0, |f| {
+				let digits = f.as_str();
+				let value = digits.parse::<i64>().unwrap_or_default();
+				match digits.len() {
+					1 => value * 100,
+					2 => value * 10,
+					_ => value,
+				}
+			});
+			timestamps.push(minutes * 60_000 + seconds * 1000 + fraction - offset);
+		}
+		let text = line[text_start..].trim();
+		for start in timestamps {
+			lines.push(LyricLine {
+				start: start.max(0),
+				text: text.to_owned(),
+			});
+		}
+	}
+
+	SyncedLyrics::new(lines)
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Lyrics {
+	/// Lyrics without timing information, derived from synced lyrics when no other text is available
+	pub plain: Option<String>,
+	pub synced: Option<SyncedLyrics>,
+}
+
+impl Lyrics {
+	/// Sorts lyrics found in a song into plain and synced lyrics
+	fn new(texts: Vec<String>, synced: Option<SyncedLyrics>) -> Option<Self> {
+		let synced = synced.or_else(|| texts.iter().find_map(|t| parse_lrc(t)));
+		let plain = texts
+			.into_iter()
+			.map(|t| t.trim().to_owned())
+			.find(|t| !t.is_empty() && parse_lrc(t).is_none())
+			.or_else(|| {
+				let lines = synced.as_ref()?.lines.iter().map(|l| l.text.as_str());
+				Some(lines.collect::<Vec<_>>().join("\n"))
+			});
+		match (&plain, &synced) {
+			(None, None) => None,
+			_ => Some(Self { plain, synced }),
+		}
+	}
+}
+
+/// Location of the `.lrc` file holding lyrics for a song, which may not exist
+pub fn sidecar_path(path: &Path) -> PathBuf {
+	path.with_extension("lrc")
+}
+
+/// Reads time-synced lyrics from a `.lrc` file next to the song, or from the song's metadata.
+pub fn read_synced_lyrics(path: &Path) -> Result<Option<SyncedLyrics>, Error> {
+	Ok(read_lyrics(path)?.and_then(|l| l.synced))
+}
+
+pub async fn get_lyrics(path: PathBuf) -> Result<Lyrics, Error> {
+	spawn_blocking(move || read_lyrics(&path)?.ok_or(Error::LyricsNotFound(path))).await?
+}
+
+/// Reads lyrics from a `.lrc` file next to the song, or from the song's metadata.
+pub fn read_lyrics(path: &Path) -> Result<Option<Lyrics>, Error> {
+	let sidecar_path = sidecar_path(path);
+	match std::fs::read_to_string(&sidecar_path) {
+		Ok(content) => return Ok(Lyrics::new(vec![content], None)),
+		Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+		Err(e) => return Err(Error::Io(sidecar_path, e)),
+	}
+
+	match utils::get_audio_format(path) {
+		Some(AudioFormat::AIFF) | Some(AudioFormat::MP3) | Some(AudioFormat::WAVE) => {
+			read_id3(path)
+		}
+		Some(AudioFormat::APE) | Some(AudioFormat::MPC) | Some(AudioFormat::WAVPACK) => {
+			read_ape(path)
+		}
+		Some(AudioFormat::FLAC) => read_flac(path),
+		Some(AudioFormat::MP4) | Some(AudioFormat::M4B) => read_mp4(path),
+		Some(AudioFormat::OGG) => read_vorbis(path),
+		Some(AudioFormat::OPUS) => read_opus(path),
+		_ => Ok(None),
+	}
+}
+
+fn read_id3(path: &Path) -> Result<Option<Lyrics>, Error> {
+	let tag = match id3::Tag::read_from_path(path) {
+		Ok(tag) => tag,
+		Err(id3::Error {
+			kind: id3::ErrorKind::NoTag,
+			..
+		}) => return Ok(None),
+		Err(e) => return Err(Error::Id3(path.to_owned(), e)),
+	};
+
+	let synced = tag
+		.synchronised_lyrics()
+		.find(|l| l.timestamp_format == id3::frame::TimestampFormat::Ms)
+		.and_then(|l| {
+			SyncedLyrics::new(
+				l.content
+					.iter()
+					.map(|(start, text)| LyricLine {
+						start: *start as i64,
+						text: text.trim().to_owned(),
+					})
+					.collect(),
+			)
+		});
+
+	// Some taggers store LRC content as regular (unsynchronised) lyrics
+	let texts = tag.lyrics().map(|l| l.text.clone()).collect();
+	Ok(Lyrics::new(texts, synced))
+}
+
+fn read_ape(path: &Path) -> Result<Option<Lyrics>, Error> {
+	let tag = match ape::read_from_path(path) {
+		Ok(tag) => tag,
+		Err(ape::Error::TagNotFound) => return Ok(None),
+		Err(e) => return Err(e.into()),
+	};
+	let texts = tag
+		.item("Lyrics")
+		.and_then(|i| <&str>::try_from(i).ok())
+		.map(str::to_owned);
+	Ok(Lyrics::new(texts.into_iter().collect(), None))
+}
+
+fn read_flac(path: &Path) -> Result<Option<Lyrics>, Error> {
+	let tag =
+		metaflac::Tag::read_from_path(path).map_err(|e| Error::Metaflac(path.to_owned(), e))?;
+	let Some(vorbis) = tag.vorbis_comments() else {
+		return Ok(None);
+	};
+	let texts = LYRICS_COMMENTS
+		.iter()
+		.filter_map(|key| vorbis.get(key))
+		.flatten()
+		.cloned()
+		.collect();
+	Ok(Lyrics::new(texts, None))
+}
+
+fn read_mp4(path: &Path) -> Result<Option<Lyrics>, Error> {
+	let cfg = mp4ameta::ReadConfig {
+		read_meta_items: true,
+		..mp4ameta::ReadConfig::NONE
+	};
+	let mut tag = mp4ameta::Tag::read_with_path(path, &cfg)
+		.map_err(|e| Error::Mp4aMeta(path.to_owned(), e))?;
+	Ok(Lyrics::new(tag.take_lyrics().into_iter().collect(), None))
+}
+
+fn read_vorbis(path: &Path) -> Result<Option<Lyrics>, Error> {
+	let file = std::fs::File::open(path).map_err(|e| Error::Io(path.to_owned(), e))?;
+	let source = lewton::inside_ogg::OggStreamReader::new(file)?;
+	Ok(Lyrics::new(
+		filter_lyrics_comments(source.comment_hdr.comment_list),
+		None,
+	))
+}
+
+fn read_opus(path: &Path) -> Result<Option<Lyrics>, Error> {
+	let headers = opus_headers::parse_from_path(path)?;
+	Ok(Lyrics::new(
+		filter_lyrics_comments(headers.comments.user_comments),
+		None,
+	))
+}
+
+/// Vorbis comments which may hold lyrics
+pub const LYRICS_COMMENTS: [&str; 2] = ["LYRICS", "UNSYNCEDLYRICS"];
+
+fn filter_lyrics_comments<I: IntoIterator<Item = (String, String)>>(comments: I) -> Vec<String> {
+	comments
+		.into_iter()
+		.filter(|(key, _)| LYRICS_COMMENTS.iter().any(|k| k.eq_ignore_ascii_case(key)))
+		.map(|(_, value)| value)
+		.collect()
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn can_parse_lrc() {
+		let lyrics = parse_lrc(
+			"[ar:Khemmis]\n[00:12.00]First line\n[00:17.5][01:02.250]Chorus\n\n[00:30.00]Second line",
+		)
+		.unwrap();
+		assert_eq!(
+			lyrics.lines,
+			vec![
+				LyricLine {
+					start: 12_000,
+					text: "First line".to_owned()
+				},
+				LyricLine {
+					start: 17_500,
+					text: "Chorus".to_owned()
+				},
+				LyricLine {
+					start: 30_000,
+					text: "Second line".to_owned()
+				},
+				LyricLine {
+					start: 62_250,
+					text: "Chorus".to_owned()
+				},
+			]
+		);
+	}
+
+	#[test]
+	fn lrc_offset_is_applied() {
+		let lyrics = parse_lrc("[offset:+500]\n[00:12.00]First line").unwrap();
+		assert_eq!(lyrics.lines[0].start, 11_500);
+	}
+
+	#[test]
+	fn plain_lyrics_are_derived_from_synced_lyrics() {
+		let lyrics = Lyrics::new(vec!["[00:01.00]One\n[00:02.00]Two".to_owned()], None).unwrap();
+		assert_eq!(lyrics.plain.as_deref(), Some("One\nTwo"));
+		assert_eq!(lyrics.synced.unwrap().lines.len(), 2);
+
+		let lyrics = Lyrics::new(vec!["One\nTwo".to_owned()], None).unwrap();
+		assert_eq!(lyrics.plain.as_deref(), Some("One\nTwo"));
+		assert_eq!(lyrics.synced, None);
+
+		assert_eq!(Lyrics::new(vec![" ".to_owned()], None), None);
+	}
+
+	#[test]
+	fn plain_text_is_not_synced() {
+		assert_eq!(parse_lrc("Just some words\nand more words"), None);
+	}
+
+	#[test]
+	fn can_find_line_at_position() {
+		let lyrics = parse_lrc("[00:01.00]One\n[00:02.00]Two").unwrap();
+		assert_eq!(lyrics.line_at(500), None);
+		assert_eq!(lyrics.line_at(1000), Some(0));
+		assert_eq!(lyrics.line_at(1999), Some(0));
+		assert_eq!(lyrics.line_at(60_000), Some(1));
+	}
+}
@@ -0,0 +1,1107 @@
+use std::{
+	collections::BTreeSet,
+	fmt::Write,
+	io,
+	net::{IpAddr, SocketAddr},
+	path::PathBuf,
+	sync::{Arc, Mutex},
+	time::{Duration, Instant},
+};
+
+use log::{error, info};
+use tokio::{
+	io::{
+		AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt,
+		BufReader,
+	},
+	net::TcpListener,
+	sync::broadcast::{self, error::TryRecvError},
+};
+
+use crate::app::{auth, config, index, scanner, session, Error};
+
+mod player;
+mod protocol;
+
+use player::{Player, Source};
+use protocol::{parse_bool, parse_command, parse_range, uri, write_song, Ack, AckCode, Command};
+
+// Longer requests end the connection, so clients cannot make the server buffer unlimited data
+const MAX_LINE_LENGTH: usize = 8 * 1024;
+const MAX_COMMAND_LIST_SIZE: usize = 2 * 1024 * 1024;
+
+// Commands which can be used before sending a password
+const PUBLIC_COMMANDS: [&str; 6] = [
+	"close",
+	"commands",
+	"notcommands",
+	"password",
+	"ping",
+	"tagtypes",
+];
+
+const COMMANDS: [&str; 45] = [
+	"add",
+	"addid",
+	"clear",
+	"close",
+	"command_list_begin",
+	"command_list_end",
+	"command_list_ok_begin",
+	"commands",
+	"consume",
+	"currentsong",
+	"decoders",
+	"delete",
+	"deleteid",
+	"find",
+	"findadd",
+	"idle",
+	"list",
+	"listall",
+	"listallinfo",
+	"listplaylists",
+	"lsinfo",
+	"move",
+	"next",
+	"noidle",
+	"notcommands",
+	"outputs",
+	"password",
+	"pause",
+	"ping",
+	"play",
+	"playid",
+	"playlistid",
+	"playlistinfo",
+	"plchanges",
+	"plchangesposid",
+	"previous",
+	"random",
+	"repeat",
+	"rescan",
+	"search",
+	"searchadd",
+	"seek",
+	"seekcur",
+	"seekid",
+	"single",
+];
+
+const TAG_TYPES: [&str; 9] = [
+	"Artist",
+	"AlbumArtist",
+	"Album",
+	"Title",
+	"Track",
+	"Disc",
+	"Date",
+	"Genre",
+	"Composer",
+];
+
+// Lets MPD clients browse and search the collection, and manage a play queue shared between them.
+#[derive(Clone)]
+pub struct Manager {
+	config_manager: config::Manager,
+	index_manager: index::Manager,
+	scanner: scanner::Scanner,
+	session_manager: session::Manager,
+	player: Arc<Mutex<Player>>,
+	changes: broadcast::Sender<&'static str>,
+	start_time: Instant,
+}
+
+struct Client {
+	address: Option<IpAddr>,
+	// Set once the client sent a valid password
+	username: Option<String>,
+	changes: broadcast::Receiver<&'static str>,
+}
+
+// Reads request lines, up to `MAX_LINE_LENGTH` bytes long
+struct Lines<R> {
+	reader: R,
+	buffer: Vec<u8>,
+}
+
+impl<R: AsyncBufRead + Unpin> Lines<R> {
+	fn new(reader: R) -> Self {
+		Self {
+			reader,
+			buffer: vec![],
+		}
+	}
+
+	// Partial lines are kept in `buffer`, so this can be cancelled without losing data
+	async fn next_line(&mut self) -> io::Result<Option<String>> {
+		let limit = (MAX_LINE_LENGTH + 1).saturating_sub(self.buffer.len());
+		let length = (&mut self.reader)
+			.take(limit as u64)
+			.read_until(b'\n', &mut self.buffer)
+			.await?;
+		if self.buffer.last() != Some(&b'\n') {
+			if self.buffer.len() > MAX_LINE_LENGTH {
+				return Err(io::Error::new(io::ErrorKind::InvalidData, "line too long"));
+			}
+			if length == 0 && self.buffer.is_empty() {
+				return Ok(None);
+			}
+		}
+		let line = String::from_utf8(std::mem::take(&mut self.buffer))
+			.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+		Ok(Some(line.trim_end_matches(['\r', '\n']).to_owned()))
+	}
+}
+
+impl Manager {
+	pub fn new(
+		config_manager: config::Manager,
+		index_manager: index::Manager,
+		scanner: scanner::Scanner,
+		session_manager: session::Manager,
+	) -> Self {
+		Self {
+			config_manager,
+			index_manager,
+			scanner,
+			session_manager,
+			player: Arc::default(),
+			changes: broadcast::channel(64).0,
+			start_time: Instant::now(),
+		}
+	}
+
+	// Listens on the same addresses as the web server, with the MPD port
+	pub async fn begin_listening(&self, bind_addresses: &[SocketAddr]) {
+		let Some(mpd) = self.config_manager.get_mpd().await else {
+			return;
+		};
+
+		let mut addresses = bind_addresses
+			.iter()
+			.map(|a| SocketAddr::new(a.ip(), mpd.port))
+			.collect::<Vec<_>>();
+		addresses.dedup();
+
+		for address in addresses {
+			let listener = match TcpListener::bind(address).await {
+				Ok(l) => l,
+				Err(e) => {
+					error!("Could not listen for MPD clients on {address}: {e}");
+					continue;
+				}
+			};
+
+			info!("Listening for MPD clients on {address}");
+
+			tokio::spawn({
+				let manager = self.clone();
+				async move {
+					loop {
+						match listener.accept().await {
+							Ok((stream, address)) => {
+								let manager = manager.clone();
+								let address = Some(address.ip());
+								tokio::spawn(async move { manager.serve(stream, address).await });
+							}
+							Err(e) => error!("Could not accept MPD client connection: {e}"),
+						}
+					}
+				}
+			});
+		}
+
+		// Lets idling clients know when a song finished playing
+		tokio::spawn({
+			let manager = self.clone();
+			async move {
+				let mut interval = tokio::time::interval(Duration::from_secs(1));
+				loop {
+					interval.tick().await;
+					let changed = manager.player.lock().unwrap().update();
+					if changed {
+						manager.notify(&["player", "playlist"]);
+					}
+				}
+			}
+		});
+	}
+
+	pub async fn serve<S: AsyncRead + AsyncWrite>(&self, stream: S, address: Option<IpAddr>) {
+		let (reader, mut writer) = tokio::io::split(stream);
+		let mut lines = Lines::new(BufReader::new(reader));
+		let mut client = Client {
+			address,
+			username: None,
+			changes: self.changes.subscribe(),
+		};
+
+		if writer
+			.write_all(protocol::GREETING.as_bytes())
+			.await
+			.is_err()
+		{
+			return;
+		}
+
+		while let Ok(Some(line)) = lines.next_line().await {
+			let response = match parse_command(&line) {
+				Err(ack) => ack.format(0, ""),
+				Ok(command) => match command.name.as_str() {
+					"close" => break,
+					"idle" if client.username.is_some() => {
+						match self.idle(&mut client, &command, &mut lines).await {
+							Some(response) => response,
+							None => break,
+						}
+					}
+					"command_list_begin" | "command_list_ok_begin" => {
+						let ok_mode = command.name == "command_list_ok_begin";
+						match self.command_list(&mut client, ok_mode, &mut lines).await {
+							Some(response) => response,
+							None => break,
+						}
+					}
+					_ => self.respond(&mut client, &command, 0).await,
+				},
+			};
+			if writer.write_all(response.as_bytes()).await.is_err() {
+				break;
+			}
+		}
+	}
+
+	async fn respond(&self, client: &mut Client, command: &Command, position: usize) -> String {
+		match self.execute(client, command).await {
+			Ok(mut response) => {
+				response.push_str("OK\n");
+				response
+			}
+			Err(ack) => ack.format(position, &command.name),
+		}
+	}
+
+	async fn command_list<R: AsyncBufRead + Unpin>(
+		&self,
+		client: &mut Client,
+		ok_mode: bool,
+		lines: &mut Lines<R>,
+	) -> Option<String> {
+		let mut commands = vec![];
+		let mut size = 0;
+		loop {
+			let line = lines.next_line().await.ok()??;
+			if line.trim() == "command_list_end" {
+				break;
+			}
+			size += line.len();
+			if size > MAX_COMMAND_LIST_SIZE {
+				return None;
+			}
+			commands.push(line);
+		}
+
+		let mut response = String::new();
+		for (position, line) in commands.iter().enumerate() {
+			let command = match parse_command(line) {
+				Ok(c) => c,
+				Err(ack) => return Some(response + &ack.format(position, "")),
+			};
+			match self.execute(client, &command).await {
+				Ok(r) => response.push_str(&r),
+				Err(ack) => return Some(response + &ack.format(position, &command.name)),
+			}
+			if ok_mode {
+				response.push_str("list_OK\n");
+			}
+		}
+		response.push_str("OK\n");
+		Some(response)
+	}
+
+	// Waits until a subsystem the client is interested in changes, or until the client sends `noidle`
+	async fn idle<R: AsyncBufRead + Unpin>(
+		&self,
+		client: &mut Client,
+		command: &Command,
+		lines: &mut Lines<R>,
+	) -> Option<String> {
+		let subsystems = command
+			.arguments
+			.iter()
+			.map(|s| s.to_lowercase())
+			.collect::<Vec<_>>();
+		let is_relevant = |s: &str| subsystems.is_empty() || subsystems.iter().any(|x| x == s);
+
+		let mut changed = BTreeSet::new();
+		loop {
+			match client.changes.try_recv() {
+				Ok(s) if is_relevant(s) => {
+					changed.insert(s);
+				}
+				Ok(_) => (),
+				Err(TryRecvError::Lagged(_)) => {
+					changed.extend(["database", "player", "playlist", "options"]);
+				}
+				Err(_) => break,
+			}
+		}
+
+		while changed.is_empty() {
+			tokio::select! {
+				change = client.changes.recv() => match change {
+					Ok(s) if is_relevant(s) => {
+						changed.insert(s);
+					}
+					Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => (),
+					Err(broadcast::error::RecvError::Closed) => return None,
+				},
+				line = lines.next_line() => match line.ok()?? {
+					l if l.trim() == "noidle" => break,
+					_ => return None,
+				},
+			}
+		}
+
+		let mut response = String::new();
+		for subsystem in changed {
+			writeln!(response, "changed: {subsystem}").ok();
+		}
+		response.push_str("OK\n");
+		Some(response)
+	}
+
+	fn notify(&self, subsystems: &[&'static str]) {
+		for subsystem in subsystems {
+			self.changes.send(subsystem).ok();
+		}
+	}
+
+	async fn execute(&self, client: &mut Client, command: &Command) -> Result<String, Ack> {
+		let name = command.name.as_str();
+		if client.username.is_none() && !PUBLIC_COMMANDS.contains(&name) {
+			return Err(Ack::new(
+				AckCode::Permission,
+				format!("you don't have permission for \"{name}\""),
+			));
+		}
+
+		let response = match name {
+			"ping" | "noidle" => String::new(),
+			"password" => {
+				self.login(client, command.argument(0)?).await?;
+				String::new()
+			}
+			"commands" => COMMANDS.iter().map(|c| format!("command: {c}\n")).collect(),
+			"notcommands" => String::new(),
+			"tagtypes" => match command.optional_argument(0) {
+				None => TAG_TYPES
+					.iter()
+					.map(|t| format!("tagtype: {t}\n"))
+					.collect(),
+				Some(_) => String::new(),
+			},
+			"outputs" | "decoders" | "listplaylists" => String::new(),
+			"stats" => self.stats().await,
+			"update" | "rescan" => {
+				self.check_permission(client, auth::Permission::TriggerScan)
+					.await?;
+				self.scanner.try_trigger_scan();
+				self.notify(&["update"]);
+				"updating_db: 1\n".to_owned()
+			}
+			"lsinfo" => {
+				self.lsinfo(command.optional_argument(0).unwrap_or_default())
+					.await?
+			}
+			"listall" => {
+				let paths = self
+					.flatten(command.optional_argument(0).unwrap_or_default())
+					.await?;
+				paths
+					.iter()
+					.map(|p| format!("file: {}\n", uri(p)))
+					.collect()
+			}
+			"listallinfo" => {
+				let paths = self
+					.flatten(command.optional_argument(0).unwrap_or_default())
+					.await?;
+				let mut response = String::new();
+				for song in self
+					.index_manager
+					.get_songs(paths)
+					.await
+					.into_iter()
+					.flatten()
+				{
+					write_song(&mut response, &song);
+				}
+				response
+			}
+			"find" | "search" => {
+				let mut response = String::new();
+				for song in self.search(command).await? {
+					write_song(&mut response, &song);
+				}
+				response
+			}
+			"findadd" | "searchadd" => {
+				let songs = self.search(command).await?;
+				let mut player = self.player.lock().unwrap();
+				for song in songs {
+					player.add(Source::Song(Box::new(song)), None)?;
+				}
+				String::new()
+			}
+			"list" => self.list(command).await?,
+			"add" => {
+				let sources = self.resolve(command.argument(0)?).await?;
+				let mut player = self.player.lock().unwrap();
+				for source in sources {
+					player.add(source, None)?;
+				}
+				String::new()
+			}
+			"addid" => {
+				command.expect_arguments(2)?;
+				let mut sources = self.resolve(command.argument(0)?).await?;
+				if sources.len() != 1 {
+					return Err(Ack::no_exist("No such song"));
+				}
+				let position = command
+					.optional_argument(1)
+					.map(|p| p.parse().map_err(|_| Ack::argument("Bad song index")))
+					.transpose()?;
+				let id = self
+					.player
+					.lock()
+					.unwrap()
+					.add(sources.remove(0), position)?;
+				format!("Id: {id}\n")
+			}
+			_ => self.execute_player_command(command)?,
+		};
+
+		self.notify(changed_subsystems(name));
+		Ok(response)
+	}
+
+	// Commands which only affect the play queue and playback state
+	fn execute_player_command(&self, command: &Command) -> Result<String, Ack> {
+		let mut player = self.player.lock().unwrap();
+		player.update();
+
+		let mut response = String::new();
+		match command.name.as_str() {
+			"clear" => player.clear(),
+			"delete" => {
+				let (start, end) = parse_range(command.argument(0)?, player.queue().len())?;
+				player.delete(start, end);
+			}
+			"deleteid" => {
+				let position = player.position_of(parse_id(command.argument(0)?)?)?;
+				player.delete(position, position + 1);
+			}
+			"move" => {
+				let (from, _) = parse_range(command.argument(0)?, player.queue().len())?;
+				let to = command
+					.argument(1)?
+					.parse()
+					.map_err(|_| Ack::argument("Bad song index"))?;
+				player.move_entry(from, to)?;
+			}
+			"playlistinfo" => {
+				let length = player.queue().len();
+				let (start, end) = match command.optional_argument(0) {
+					Some(range) => parse_range(range, length)?,
+					None => (0, length),
+				};
+				for position in start..end {
+					write_entry(&mut response, &player, position);
+				}
+			}
+			"playlistid" => {
+				let positions = match command.optional_argument(0) {
+					Some(id) => vec![player.position_of(parse_id(id)?)?],
+					None => (0..player.queue().len()).collect(),
+				};
+				for position in positions {
+					write_entry(&mut response, &player, position);
+				}
+			}
+			// Clients use these to fetch queue changes since a given version. Replying with the
+			// whole queue whenever the version differs is always correct, if not minimal.
+			"plchanges" | "plchangesposid" => {
+				let version = parse_id(command.argument(0)?)?;
+				if version != player.version() {
+					for (position, entry) in player.queue().iter().enumerate() {
+						match command.name.as_str() {
+							"plchanges" => write_entry(&mut response, &player, position),
+							_ => {
+								writeln!(response, "cpos: {position}\nId: {}", entry.id).ok();
+							}
+						}
+					}
+				}
+			}
+			"currentsong" => {
+				if let Some(current) = player.current() {
+					write_entry(&mut response, &player, current);
+				}
+			}
+			"status" => write_status(&mut response, &player),
+			"play" => {
+				let position = command
+					.optional_argument(0)
+					.map(|p| p.parse().map_err(|_| Ack::argument("Bad song index")))
+					.transpose()?;
+				player.play(position)?;
+			}
+			"playid" => {
+				let position = command
+					.optional_argument(0)
+					.map(|id| parse_id(id).and_then(|id| player.position_of(id)))
+					.transpose()?;
+				player.play(position)?;
+			}
+			"pause" => {
+				let pause = command.optional_argument(0).map(parse_bool).transpose()?;
+				player.pause(pause);
+			}
+			"stop" => player.stop(),
+			"next" => player.next(),
+			"previous" => player.previous(),
+			"seek" => {
+				let (position, _) = parse_range(command.argument(0)?, player.queue().len())?;
+				player.seek(position, parse_time(command.argument(1)?)?)?;
+			}
+			"seekid" => {
+				let position = player.position_of(parse_id(command.argument(0)?)?)?;
+				player.seek(position, parse_time(command.argument(1)?)?)?;
+			}
+			"seekcur" => {
+				let Some(current) = player.current() else {
+					return Err(Ack::new(AckCode::Unknown, "Not playing"));
+				};
+				let argument = command.argument(0)?;
+				let time = parse_time(argument.trim_start_matches(['+', '-']))?;
+				let time = match argument.chars().next() {
+					Some('+') => player.elapsed() + time,
+					Some('-') => player.elapsed().saturating_sub(time),
+					_ => time,
+				};
+				player.seek(current, time)?;
+			}
+			"random" => player.random = parse_bool(command.argument(0)?)?,
+			"repeat" => player.repeat = parse_bool(command.argument(0)?)?,
+			"single" => player.single = parse_bool(command.argument(0)?)?,
+			"consume" => player.consume = parse_bool(command.argument(0)?)?,
+			"setvol" | "volume" => return Err(Ack::new(AckCode::System, "No mixer")),
+			name => {
+				return Err(Ack::new(
+					AckCode::Unknown,
+					format!("unknown command \"{name}\""),
+				))
+			}
+		}
+		Ok(response)
+	}
+
+	// Clients only send a password, which holds a Polaris username and password (`username:password`)
+	async fn login(&self, client: &mut Client, password: &str) -> Result<(), Ack> {
+		let incorrect = || Ack::new(AckCode::Password, "incorrect password");
+		let (username, password) = password.split_once(':').ok_or_else(incorrect)?;
+		self.session_manager
+			.check_password(username, password, client.address)
+			.await
+			.map_err(|e| match e {
+				Error::LoginThrottled(_) => Ack::new(AckCode::Password, e.to_string()),
+				_ => incorrect(),
+			})?;
+		client.username = Some(username.to_owned());
+		Ok(())
+	}
+
+	// Guests cannot use any permission, like in the Polaris API
+	async fn check_permission(
+		&self,
+		client: &Client,
+		permission: auth::Permission,
+	) -> Result<(), Ack> {
+		let user = match &client.username {
+			Some(username) => self.config_manager.get_user(username).await.ok(),
+			None => None,
+		};
+		match user {
+			Some(u) if !u.is_guest() && u.has_permission(permission) => Ok(()),
+			_ => Err(Ack::new(
+				AckCode::Permission,
+				"you don't have permission for this command",
+			)),
+		}
+	}
+
+	async fn stats(&self) -> String {
+		let artists = self.index_manager.get_artists().await.len();
+		let albums = self.index_manager.get_albums().await.len();
+		let songs = self.index_manager.get_all_songs().await;
+		let playtime = songs
+			.iter()
+			.filter_map(|s| s.duration)
+			.map(|d| d.max(0))
+			.sum::<i64>();
+		format!(
+			"artists: {artists}\nalbums: {albums}\nsongs: {}\nuptime: {}\nplaytime: 0\ndb_playtime: {playtime}\n",
+			songs.len(),
+			self.start_time.elapsed().as_secs(),
+		)
+	}
+
+	async fn lsinfo(&self, uri: &str) -> Result<String, Ack> {
+		let path = virtual_path(uri);
+		let mut response = String::new();
+		match self.index_manager.browse(path.clone()).await {
+			Ok(files) => {
+				let mut songs = vec![];
+				for file in files {
+					match file {
+						index::File::Directory(p) => {
+							writeln!(response, "directory: {}", protocol::uri(&p)).ok();
+						}
+						index::File::Song(p) => songs.push(p),
+					}
+				}
+				for song in self
+					.index_manager
+					.get_songs(songs)
+					.await
+					.into_iter()
+					.flatten()
+				{
+					write_song(&mut response, &song);
+				}
+			}
+			Err(_) => match self.index_manager.get_songs(vec![path]).await.pop() {
+				Some(Ok(song)) => write_song(&mut response, &song),
+				_ => return Err(Ack::no_exist("No such directory")),
+			},
+		}
+		Ok(response)
+	}
+
+	async fn flatten(&self, uri: &str) -> Result<Vec<PathBuf>, Ack> {
+		self.index_manager
+			.flatten(virtual_path(uri))
+			.await
+			.map_err(|_| Ack::no_exist("No such directory"))
+	}
+
+	// Queue entries for a song, directory or URL
+	async fn resolve(&self, uri: &str) -> Result<Vec<Source>, Ack> {
+		if uri.starts_with("http://") || uri.starts_with("https://") {
+			return Ok(vec![Source::Url(uri.to_owned())]);
+		}
+		let paths = self.flatten(uri).await?;
+		let songs = self.index_manager.get_songs(paths).await;
+		let sources = songs
+			.into_iter()
+			.flatten()
+			.map(|s| Source::Song(Box::new(s)))
+			.collect::<Vec<_>>();
+		match sources.is_empty() {
+			true => Err(Ack::no_exist("No such song")),
+			false => Ok(sources),
+		}
+	}
+
+	async fn search(&self, command: &Command) -> Result<Vec<index::Song>, Ack> {
+		let exact = matches!(command.name.as_str(), "find" | "findadd" | "list");
+		let (query, window) = make_query(&command.arguments, exact)?;
+		let songs = self
+			.index_manager
+			.search(query)
+			.await
+			.map_err(|e| Ack::argument(e.to_string()))?;
+		Ok(match window {
+			Some(window) => {
+				let (start, end) = parse_range(window, usize::MAX)?;
+				songs.into_iter().skip(start).take(end - start).collect()
+			}
+			None => songs,
+		})
+	}
+
+	async fn list(&self, command: &Command) -> Result<String, Ack> {
+		let tag = command.argument(0)?.to_lowercase();
+		let key = match tag.as_str() {
+			"artist" => "Artist",
+			"albumartist" => "AlbumArtist",
+			"album" => "Album",
+			"title" => "Title",
+			"date" => "Date",
+			"genre" => "Genre",
+			"composer" => "Composer",
+			"file" => "file",
+			_ => return Err(Ack::argument(format!("Unknown tag type: {tag}"))),
+		};
+
+		// Grouping is not supported, filters are whatever precedes it
+		let mut filters = command.arguments[1..]
+			.iter()
+			.take_while(|a| !a.eq_ignore_ascii_case("group"))
+			.cloned()
+			.collect::<Vec<_>>();
+		// Older clients send `list album ARTIST`
+		if tag == "album" && filters.len() == 1 {
+			filters.insert(0, "artist".to_owned());
+		}
+
+		let songs = match filters.is_empty() {
+			true => self.index_manager.get_all_songs().await,
+			false => {
+				let (query, _) = make_query(&filters, true)?;
+				self.index_manager
+					.search(query)
+					.await
+					.map_err(|e| Ack::argument(e.to_string()))?
+			}
+		};
+
+		let values = songs
+			.iter()
+			.flat_map(|song| match key {
+				"Artist" => song.artists.clone(),
+				"AlbumArtist" => song.album_artists.clone(),
+				"Album" => song.album.iter().cloned().collect(),
+				"Title" => song.title.iter().cloned().collect(),
+				"Date" => song.year.iter().map(|y| y.to_string()).collect(),
+				"Genre" => song.genres.clone(),
+				"Composer" => song.composers.clone(),
+				_ => vec![uri(&song.virtual_path)],
+			})
+			.collect::<BTreeSet<_>>();
+
+		Ok(values
+			.into_iter()
+			.map(|v| format!("{key}: {v}\n"))
+			.collect())
+	}
+}
+
+fn changed_subsystems(command: &str) -> &'static [&'static str] {
+	match command {
+		"add" | "addid" | "clear" | "delete" | "deleteid" | "findadd" | "move" | "searchadd" => {
+			&["playlist", "player"]
+		}
+		"play" | "playid" | "pause" | "stop" | "next" | "previous" | "seek" | "seekid"
+		| "seekcur" => &["player"],
+		"random" | "repeat" | "single" | "consume" => &["options"],
+		_ => &[],
+	}
+}
+
+fn virtual_path(uri: &str) -> PathBuf {
+	uri.split('/').filter(|s| !s.is_empty()).collect()
+}
+
+fn parse_id(argument: &str) -> Result<u32, Ack> {
+	argument
+		.parse()
+		.map_err(|_| Ack::argument(format!("Integer expected: {argument}")))
+}
+
+fn parse_time(argument: &str) -> Result<Duration, Ack> {
+	argument
+		.parse::<f64>()
+		.ok()
+		.filter(|t| t.is_finite() && *t >= 0.0)
+		.map(Duration::from_secs_f64)
+		.ok_or_else(|| Ack::argument(format!("Number expected: {argument}")))
+}
+
+// Translates `TAG VALUE` pairs into a Polaris search query. Also returns the `window` argument, if any.
+fn make_query(arguments: &[String], exact: bool) -> Result<(String, Option<&str>), Ack> {
+	if arguments.first().is_some_and(|a| a.starts_with('(')) {
+		return Err(Ack::argument("Filter expressions are not supported"));
+	}
+	if !arguments.len().is_multiple_of(2) {
+		return Err(Ack::argument("Incorrect number of filter arguments"));
+	}
+
+	let operator = if exact { "=" } else { "%" };
+	let mut filters = vec![];
+	let mut window = None;
+	for pair in arguments.chunks(2) {
+		let value = pair[1].replace('"', "");
+		let filter = match pair[0].to_lowercase().as_str() {
+			"window" => {
+				window = Some(pair[1].as_str());
+				continue;
+			}
+			"sort" => continue,
+			"any" => format!("\"{value}\""),
+			"file" | "base" => format!("path % \"{value}\""),
+			field @ ("artist" | "albumartist" | "album" | "title" | "genre" | "composer") => {
+				format!("{field} {operator} \"{value}\"")
+			}
+			tag => return Err(Ack::argument(format!("Unknown filter type: {tag}"))),
+		};
+		filters.push(filter);
+	}
+
+	match filters.is_empty() {
+		true => Err(Ack::argument("No filter given")),
+		false => Ok((filters.join(" && "), window)),
+	}
+}
+
+fn write_entry(response: &mut String, player: &Player, position: usize) {
+	let entry = &player.queue()[position];
+	match &entry.source {
+		Source::Song(song) => write_song(response, song),
+		Source::Url(url) => {
+			writeln!(response, "file: {url}").ok();
+		}
+	}
+	writeln!(response, "Pos: {position}\nId: {}", entry.id).ok();
+}
+
+fn write_status(response: &mut String, player: &Player) {
+	writeln!(response, "volume: -1").ok();
+	writeln!(response, "repeat: {}", player.repeat as u8).ok();
+	writeln!(response, "random: {}", player.random as u8).ok();
+	writeln!(response, "single: {}", player.single as u8).ok();
+	writeln!(response, "consume: {}", player.consume as u8).ok();
+	writeln!(response, "playlist: {}", player.version()).ok();
+	writeln!(response, "playlistlength: {}", player.queue().len()).ok();
+	writeln!(response, "state: {}", player.state().as_str()).ok();
+
+	let Some(current) = player.current() else {
+		return;
+	};
+	let entry = &player.queue()[current];
+	writeln!(response, "song: {current}\nsongid: {}", entry.id).ok();
+	if player.state() != player::State::Stop {
+		let elapsed = player.elapsed();
+		writeln!(response, "elapsed: {:.3}", elapsed.as_secs_f64()).ok();
+		let duration = entry.duration().unwrap_or_default();
+		writeln!(
+			response,
+			"time: {}:{}",
+			elapsed.as_secs(),
+			duration.as_secs()
+		)
+		.ok();
+		if !duration.is_zero() {
+			writeln!(response, "duration: {:.3}", duration.as_secs_f64()).ok();
+		}
+	}
+	if !player.random {
+		if let Some(next) = player.next_position() {
+			let id = player.queue()[next].id;
+			writeln!(response, "nextsong: {next}\nnextsongid: {id}").ok();
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::path::PathBuf;
+
+	use tokio::io::{AsyncReadExt, DuplexStream};
+
+	use super::*;
+	use crate::app::test;
+	use crate::test_name;
+
+	const TEST_USERNAME: &str = "alice";
+	const TEST_PASSWORD: &str = "secret";
+
+	async fn connect(test_name: String) -> DuplexStream {
+		let ctx = test::ContextBuilder::new(test_name)
+			.user(TEST_USERNAME, TEST_PASSWORD, false)
+			.mount("root", "test-data/small-collection")
+			.build()
+			.await;
+		ctx.scanner.run_scan().await.unwrap();
+		serve(ctx).await
+	}
+
+	async fn serve(ctx: test::Context) -> DuplexStream {
+		let manager = Manager::new(
+			ctx.config_manager,
+			ctx.index_manager,
+			ctx.scanner,
+			ctx.session_manager,
+		);
+		let (client, server) = tokio::io::duplex(64 * 1024);
+		tokio::spawn(async move { manager.serve(server, None).await });
+
+		let mut client = client;
+		let mut greeting = vec![0; protocol::GREETING.len()];
+		client.read_exact(&mut greeting).await.unwrap();
+		assert_eq!(greeting, protocol::GREETING.as_bytes());
+		client
+	}
+
+	async fn read_response(stream: &mut DuplexStream) -> String {
+		let mut response = String::new();
+		let mut buffer = [0; 4096];
+		while !(response.ends_with("OK\n") || response.starts_with("ACK")) {
+			let length = stream.read(&mut buffer).await.unwrap();
+			assert!(length > 0);
+			response.push_str(&String::from_utf8_lossy(&buffer[..length]));
+		}
+		response
+	}
+
+	async fn send(stream: &mut DuplexStream, request: &str) -> String {
+		stream.write_all(request.as_bytes()).await.unwrap();
+		stream.write_all(b"\n").await.unwrap();
+		read_response(stream).await
+	}
+
+	async fn login(stream: &mut DuplexStream) {
+		let password = format!("password \"{TEST_USERNAME}:{TEST_PASSWORD}\"");
+		assert_eq!(send(stream, &password).await, "OK\n");
+	}
+
+	#[tokio::test]
+	async fn requires_password() {
+		let mut stream = connect(test_name!()).await;
+		assert_eq!(send(&mut stream, "ping").await, "OK\n");
+		assert!(send(&mut stream, "status")
+			.await
+			.starts_with("ACK [4@0] {status}"));
+		assert!(send(&mut stream, "password \"alice:wrong\"")
+			.await
+			.starts_with("ACK [3@0] {password}"));
+		login(&mut stream).await;
+		assert!(send(&mut stream, "status").await.ends_with("OK\n"));
+	}
+
+	#[tokio::test]
+	async fn password_respects_lockout() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USERNAME, TEST_PASSWORD, false)
+			.auth(config::storage::Auth {
+				lockout_threshold: Some(1),
+				..Default::default()
+			})
+			.build()
+			.await;
+		let mut stream = serve(ctx).await;
+
+		assert!(send(&mut stream, "password \"alice:wrong\"")
+			.await
+			.starts_with("ACK [3@0] {password}"));
+		let password = format!("password \"{TEST_USERNAME}:{TEST_PASSWORD}\"");
+		assert!(send(&mut stream, &password)
+			.await
+			.starts_with("ACK [3@0] {password}"));
+		assert!(send(&mut stream, "status")
+			.await
+			.starts_with("ACK [4@0] {status}"));
+	}
+
+	#[tokio::test]
+	async fn update_requires_permission() {
+		let mut stream = connect(test_name!()).await;
+		login(&mut stream).await;
+		assert!(send(&mut stream, "update")
+			.await
+			.starts_with("ACK [4@0] {update}"));
+
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USERNAME, TEST_PASSWORD, true)
+			.build()
+			.await;
+		let mut stream = serve(ctx).await;
+		login(&mut stream).await;
+		assert_eq!(send(&mut stream, "update").await, "updating_db: 1\nOK\n");
+	}
+
+	#[tokio::test]
+	async fn long_lines_close_connection() {
+		let mut stream = connect(test_name!()).await;
+		let line = "a".repeat(MAX_LINE_LENGTH + 1);
+		stream.write_all(line.as_bytes()).await.unwrap();
+		let mut buffer = [0; 64];
+		assert_eq!(stream.read(&mut buffer).await.unwrap(), 0);
+	}
+
+	#[tokio::test]
+	async fn can_browse() {
+		let mut stream = connect(test_name!()).await;
+		login(&mut stream).await;
+
+		let response = send(&mut stream, "lsinfo").await;
+		assert!(response.contains("directory: root/Khemmis\n"));
+
+		let response = send(&mut stream, "lsinfo \"root/Khemmis/Hunted\"").await;
+		assert!(response.contains("file: root/Khemmis/Hunted/02 - Candlelight.mp3\n"));
+		assert!(response.contains("Title: Candlelight\n"));
+
+		let response = send(&mut stream, "lsinfo \"root/Not a directory\"").await;
+		assert!(response.starts_with("ACK [50@0] {lsinfo}"));
+	}
+
+	#[tokio::test]
+	async fn can_search() {
+		let mut stream = connect(test_name!()).await;
+		login(&mut stream).await;
+
+		let response = send(&mut stream, "find album \"Hunted\" title \"Candlelight\"").await;
+		assert_eq!(response.matches("file: ").count(), 1);
+
+		let response = send(&mut stream, "list album artist \"Khemmis\"").await;
+		assert_eq!(response, "Album: Hunted\nOK\n");
+	}
+
+	#[tokio::test]
+	async fn can_manage_queue() {
+		let mut stream = connect(test_name!()).await;
+		login(&mut stream).await;
+
+		let response = send(&mut stream, "add \"root/Khemmis/Hunted\"").await;
+		assert_eq!(response, "OK\n");
+		let response = send(&mut stream, "addid \"https://example.com/stream.mp3\" 0").await;
+		assert!(response.starts_with("Id: "));
+
+		let response = send(&mut stream, "playlistinfo").await;
+		assert_eq!(response.matches("file: ").count(), 6);
+		assert!(response.starts_with("file: https://example.com/stream.mp3\nPos: 0\n"));
+
+		let response = send(
+			&mut stream,
+			"command_list_ok_begin\ndelete 0\nplay 1\ncommand_list_end",
+		)
+		.await;
+		assert_eq!(response, "list_OK\nlist_OK\nOK\n");
+
+		let response = send(&mut stream, "status").await;
+		assert!(response.contains("state: play\n"));
+		assert!(response.contains("song: 1\n"));
+		assert!(response.contains("playlistlength: 5\n"));
+
+		let response = send(&mut stream, "currentsong").await;
+		let path = PathBuf::from_iter(["root", "Khemmis", "Hunted", "02 - Candlelight.mp3"]);
+		assert!(response.starts_with(&format!("file: {}\n", uri(&path))));
+	}
+
+	#[tokio::test]
+	async fn idle_reports_changes() {
+		let mut stream = connect(test_name!()).await;
+		login(&mut stream).await;
+
+		stream.write_all(b"idle playlist\n").await.unwrap();
+		let response = send(&mut stream, "noidle").await;
+		assert_eq!(response, "OK\n");
+
+		send(&mut stream, "add \"root/Khemmis/Hunted\"").await;
+		let response = send(&mut stream, "idle playlist").await;
+		assert_eq!(response, "changed: playlist\nOK\n");
+	}
+}
@@ -0,0 +1,344 @@
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+
+use crate::app::index;
+
+use super::protocol::Ack;
+
+pub enum Source {
+	Song(Box<index::Song>),
+	Url(String),
+}
+
+pub struct Entry {
+	pub id: u32,
+	pub source: Source,
+}
+
+impl Entry {
+	pub fn duration(&self) -> Option<Duration> {
+		match &self.source {
+			Source::Song(song) => song.duration.map(|d| Duration::from_secs(d.max(0) as u64)),
+			Source::Url(_) => None,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum State {
+	#[default]
+	Stop,
+	Play,
+	Pause,
+}
+
+impl State {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			State::Stop => "stop",
+			State::Play => "play",
+			State::Pause => "pause",
+		}
+	}
+}
+
+// Play queue shared by all MPD clients. Polaris has no audio output of its own, so playback only
+// tracks which song is current and how far into it playback is.
+#[derive(Default)]
+pub struct Player {
+	queue: Vec<Entry>,
+	next_id: u32,
+	version: u32,
+	state: State,
+	current: Option<usize>,
+	elapsed: Duration,
+	resumed_at: Option<Instant>,
+	pub random: bool,
+	pub repeat: bool,
+	pub single: bool,
+	pub consume: bool,
+}
+
+impl Player {
+	pub fn queue(&self) -> &[Entry] {
+		&self.queue
+	}
+
+	pub fn version(&self) -> u32 {
+		self.version
+	}
+
+	pub fn state(&self) -> State {
+		self.state
+	}
+
+	pub fn current(&self) -> Option<usize> {
+		self.current
+	}
+
+	pub fn position_of(&self, id: u32) -> Result<usize, Ack> {
+		self.queue
+			.iter()
+			.position(|e| e.id == id)
+			.ok_or_else(|| Ack::no_exist("No such song"))
+	}
+
+	pub fn elapsed(&self) -> Duration {
+		match self.resumed_at {
+			Some(resumed_at) => self.elapsed + resumed_at.elapsed(),
+			None => self.elapsed,
+		}
+	}
+
+	pub fn add(&mut self, source: Source, position: Option<usize>) -> Result<u32, Ack> {
+		let position = position.unwrap_or(self.queue.len());
+		if position > self.queue.len() {
+			return Err(Ack::argument("Bad song index"));
+		}
+		self.next_id += 1;
+		let id = self.next_id;
+		self.queue.insert(position, Entry { id, source });
+		if let Some(current) = self.current.filter(|c| *c >= position) {
+			self.current = Some(current + 1);
+		}
+		self.version += 1;
+		Ok(id)
+	}
+
+	pub fn clear(&mut self) {
+		self.stop();
+		self.queue.clear();
+		self.current = None;
+		self.version += 1;
+	}
+
+	pub fn delete(&mut self, start: usize, end: usize) {
+		self.queue.drain(start..end);
+		self.current = match self.current {
+			Some(c) if c >= end => Some(c - (end - start)),
+			Some(c) if c >= start => {
+				self.stop();
+				None
+			}
+			current => current,
+		};
+		self.version += 1;
+	}
+
+	pub fn move_entry(&mut self, from: usize, to: usize) -> Result<(), Ack> {
+		if from >= self.queue.len() || to >= self.queue.len() {
+			return Err(Ack::argument("Bad song index"));
+		}
+		let entry = self.queue.remove(from);
+		self.queue.insert(to, entry);
+		self.current = self.current.map(|c| match c {
+			c if c == from => to,
+			c if from < c && c <= to => c - 1,
+			c if to <= c && c < from => c + 1,
+			c => c,
+		});
+		self.version += 1;
+		Ok(())
+	}
+
+	pub fn play(&mut self, position: Option<usize>) -> Result<(), Ack> {
+		match position {
+			Some(p) if p >= self.queue.len() => return Err(Ack::argument("Bad song index")),
+			Some(p) => self.start(p),
+			None if self.state == State::Pause => self.resume(),
+			None => {
+				if let Some(p) = self.current.or((!self.queue.is_empty()).then_some(0)) {
+					self.start(p);
+				}
+			}
+		}
+		Ok(())
+	}
+
+	pub fn pause(&mut self, pause: Option<bool>) {
+		let pause = pause.unwrap_or(self.state == State::Play);
+		match (self.state, pause) {
+			(State::Play, true) => {
+				self.elapsed = self.elapsed();
+				self.resumed_at = None;
+				self.state = State::Pause;
+			}
+			(State::Pause, false) => self.resume(),
+			_ => (),
+		}
+	}
+
+	pub fn stop(&mut self) {
+		self.state = State::Stop;
+		self.elapsed = Duration::ZERO;
+		self.resumed_at = None;
+	}
+
+	pub fn seek(&mut self, position: usize, time: Duration) -> Result<(), Ack> {
+		if position >= self.queue.len() {
+			return Err(Ack::argument("Bad song index"));
+		}
+		if self.current != Some(position) || self.state == State::Stop {
+			self.start(position);
+		}
+		self.elapsed = time;
+		if self.resumed_at.is_some() {
+			self.resumed_at = Some(Instant::now());
+		}
+		Ok(())
+	}
+
+	pub fn next(&mut self) {
+		if self.state == State::Stop {
+			return;
+		}
+		match self.next_position() {
+			Some(p) => self.start(p),
+			None => {
+				self.stop();
+				self.current = None;
+			}
+		}
+	}
+
+	pub fn previous(&mut self) {
+		if self.state == State::Stop {
+			return;
+		}
+		match self.current {
+			Some(c) if c > 0 => self.start(c - 1),
+			Some(_) if self.repeat && !self.queue.is_empty() => self.start(self.queue.len() - 1),
+			Some(c) => self.start(c),
+			None => (),
+		}
+	}
+
+	pub fn next_position(&self) -> Option<usize> {
+		let current = self.current?;
+		let length = self.queue.len();
+		if self.single {
+			return self.repeat.then_some(current);
+		}
+		if self.random && length > 1 {
+			let offset = rand::thread_rng().gen_range(1..length);
+			return Some((current + offset) % length);
+		}
+		match current + 1 {
+			n if n < length => Some(n),
+			_ if self.repeat && length > 0 => Some(0),
+			_ => None,
+		}
+	}
+
+	// Moves on to the following songs once the current one has played in full. Returns whether
+	// the current song changed.
+	pub fn update(&mut self) -> bool {
+		let mut changed = false;
+		while self.state == State::Play {
+			let Some(duration) = self.current.and_then(|c| self.queue[c].duration()) else {
+				break;
+			};
+			let elapsed = self.elapsed();
+			if elapsed < duration || duration.is_zero() {
+				break;
+			}
+			let finished = self.current;
+			self.next();
+			if self.state == State::Play {
+				self.elapsed = elapsed - duration;
+			}
+			if let (true, Some(finished)) = (self.consume, finished) {
+				self.delete(finished, finished + 1);
+			}
+			changed = true;
+		}
+		changed
+	}
+
+	fn start(&mut self, position: usize) {
+		self.current = Some(position);
+		self.state = State::Play;
+		self.elapsed = Duration::ZERO;
+		self.resumed_at = Some(Instant::now());
+	}
+
+	fn resume(&mut self) {
+		self.state = State::Play;
+		self.resumed_at = Some(Instant::now());
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::path::PathBuf;
+
+	use super::*;
+
+	fn song(name: &str) -> Source {
+		Source::Song(Box::new(index::Song {
+			virtual_path: PathBuf::from(name),
+			duration: Some(180),
+			..Default::default()
+		}))
+	}
+
+	#[test]
+	fn playback_follows_queue() {
+		let mut player = Player::default();
+		let first = player.add(song("a.mp3"), None).unwrap();
+		player.add(song("b.mp3"), None).unwrap();
+		assert_eq!(player.version(), 2);
+
+		player.play(None).unwrap();
+		assert_eq!(player.state(), State::Play);
+		assert_eq!(player.current(), Some(0));
+
+		player.next();
+		assert_eq!(player.current(), Some(1));
+		player.next();
+		assert_eq!(player.state(), State::Stop);
+		assert_eq!(player.current(), None);
+
+		player.repeat = true;
+		player.play(Some(1)).unwrap();
+		player.next();
+		assert_eq!(player.current(), Some(0));
+		assert_eq!(player.position_of(first), Ok(0));
+	}
+
+	#[test]
+	fn editing_queue_keeps_current_song() {
+		let mut player = Player::default();
+		for name in ["a.mp3", "b.mp3", "c.mp3"] {
+			player.add(song(name), None).unwrap();
+		}
+		player.play(Some(1)).unwrap();
+
+		player.add(song("d.mp3"), Some(0)).unwrap();
+		assert_eq!(player.current(), Some(2));
+
+		player.delete(0, 1);
+		assert_eq!(player.current(), Some(1));
+
+		player.move_entry(1, 2).unwrap();
+		assert_eq!(player.current(), Some(2));
+
+		player.delete(2, 3);
+		assert_eq!(player.current(), None);
+		assert_eq!(player.state(), State::Stop);
+	}
+
+	#[test]
+	fn pause_keeps_position() {
+		let mut player = Player::default();
+		player.add(song("a.mp3"), None).unwrap();
+		player.seek(0, Duration::from_secs(42)).unwrap();
+		player.pause(Some(true));
+		assert_eq!(player.state(), State::Pause);
+		assert_eq!(player.elapsed().as_secs(), 42);
+		player.play(None).unwrap();
+		assert_eq!(player.state(), State::Play);
+		assert!(player.elapsed().as_secs() >= 42);
+	}
+}
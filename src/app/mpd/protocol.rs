@@ -0,0 +1,230 @@
+use std::{fmt::Write, path::Path};
+
+use crate::app::index;
+
+pub const GREETING: &str = "OK MPD 0.23.5\n";
+
+// Error codes from the MPD protocol (`ACK [code@position] {command} message`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AckCode {
+	Argument = 2,
+	Password = 3,
+	Permission = 4,
+	Unknown = 5,
+	NoExist = 50,
+	System = 52,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Ack {
+	pub code: AckCode,
+	pub message: String,
+}
+
+impl Ack {
+	pub fn new(code: AckCode, message: impl Into<String>) -> Self {
+		Self {
+			code,
+			message: message.into(),
+		}
+	}
+
+	pub fn argument(message: impl Into<String>) -> Self {
+		Self::new(AckCode::Argument, message)
+	}
+
+	pub fn no_exist(message: impl Into<String>) -> Self {
+		Self::new(AckCode::NoExist, message)
+	}
+
+	pub fn format(&self, position: usize, command: &str) -> String {
+		format!(
+			"ACK [{}@{position}] {{{command}}} {}\n",
+			self.code as u8, self.message
+		)
+	}
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Command {
+	pub name: String,
+	pub arguments: Vec<String>,
+}
+
+impl Command {
+	pub fn argument(&self, index: usize) -> Result<&str, Ack> {
+		self.arguments
+			.get(index)
+			.map(|a| a.as_str())
+			.ok_or_else(|| Ack::argument("missing argument"))
+	}
+
+	pub fn optional_argument(&self, index: usize) -> Option<&str> {
+		self.arguments.get(index).map(|a| a.as_str())
+	}
+
+	pub fn expect_arguments(&self, max: usize) -> Result<(), Ack> {
+		match self.arguments.len() > max {
+			true => Err(Ack::argument("too many arguments")),
+			false => Ok(()),
+		}
+	}
+}
+
+// Splits a request line into a command name and its arguments. Arguments may be wrapped in double
+// quotes, within which `\"` and `\\` are escaped characters.
+pub fn parse_command(line: &str) -> Result<Command, Ack> {
+	let mut tokens = vec![];
+	let mut chars = line.trim().chars().peekable();
+	while let Some(c) = chars.peek().copied() {
+		if c.is_whitespace() {
+			chars.next();
+			continue;
+		}
+		let mut token = String::new();
+		if c == '"' {
+			chars.next();
+			loop {
+				match chars.next() {
+					Some('"') => break,
+					Some('\\') => match chars.next() {
+						Some(escaped) => token.push(escaped),
+						None => return Err(Ack::argument("unterminated string")),
+					},
+					Some(c) => token.push(c),
+					None => return Err(Ack::argument("unterminated string")),
+				}
+			}
+		} else {
+			while let Some(c) = chars.peek().copied() {
+				if c.is_whitespace() {
+					break;
+				}
+				token.push(c);
+				chars.next();
+			}
+		}
+		tokens.push(token);
+	}
+
+	let mut tokens = tokens.into_iter();
+	let Some(name) = tokens.next() else {
+		return Err(Ack::new(AckCode::Unknown, "No command given"));
+	};
+	Ok(Command {
+		name: name.to_lowercase(),
+		arguments: tokens.collect(),
+	})
+}
+
+// Position arguments are either a single index (`3`) or a half-open range (`3:7`, `3:`)
+pub fn parse_range(argument: &str, length: usize) -> Result<(usize, usize), Ack> {
+	let invalid = || Ack::argument(format!("Bad song index: {argument}"));
+	let (start, end) = match argument.split_once(':') {
+		Some((start, "")) => (start.parse().map_err(|_| invalid())?, length),
+		Some((start, end)) => (
+			start.parse().map_err(|_| invalid())?,
+			end.parse().map_err(|_| invalid())?,
+		),
+		None => {
+			let position: usize = argument.parse().map_err(|_| invalid())?;
+			(position, position + 1)
+		}
+	};
+	if start > end || end > length {
+		return Err(Ack::argument("Bad song index"));
+	}
+	Ok((start, end))
+}
+
+pub fn parse_bool(argument: &str) -> Result<bool, Ack> {
+	match argument {
+		"0" => Ok(false),
+		"1" => Ok(true),
+		_ => Err(Ack::argument(format!("Boolean (0/1) expected: {argument}"))),
+	}
+}
+
+// MPD URIs always use forward slashes
+pub fn uri(virtual_path: &Path) -> String {
+	virtual_path
+		.iter()
+		.map(|s| s.to_string_lossy())
+		.collect::<Vec<_>>()
+		.join("/")
+}
+
+pub fn write_song(response: &mut String, song: &index::Song) {
+	writeln!(response, "file: {}", uri(&song.virtual_path)).ok();
+	if let Some(duration) = song.duration {
+		writeln!(response, "Time: {duration}").ok();
+		writeln!(response, "duration: {duration}").ok();
+	}
+	for artist in &song.artists {
+		writeln!(response, "Artist: {artist}").ok();
+	}
+	for album_artist in &song.album_artists {
+		writeln!(response, "AlbumArtist: {album_artist}").ok();
+	}
+	if let Some(title) = &song.title {
+		writeln!(response, "Title: {title}").ok();
+	}
+	if let Some(album) = &song.album {
+		writeln!(response, "Album: {album}").ok();
+	}
+	if let Some(track_number) = song.track_number {
+		writeln!(response, "Track: {track_number}").ok();
+	}
+	if let Some(disc_number) = song.disc_number {
+		writeln!(response, "Disc: {disc_number}").ok();
+	}
+	if let Some(year) = song.year {
+		writeln!(response, "Date: {year}").ok();
+	}
+	for genre in &song.genres {
+		writeln!(response, "Genre: {genre}").ok();
+	}
+	for composer in &song.composers {
+		writeln!(response, "Composer: {composer}").ok();
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn can_parse_commands() {
+		assert_eq!(
+			parse_command(r#"find Artist "Tom \"The Bomb\" Jones" album Hunted"#),
+			Ok(Command {
+				name: "find".to_owned(),
+				arguments: vec![
+					"Artist".to_owned(),
+					r#"Tom "The Bomb" Jones"#.to_owned(),
+					"album".to_owned(),
+					"Hunted".to_owned()
+				],
+			})
+		);
+		assert_eq!(
+			parse_command("STATUS"),
+			Ok(Command {
+				name: "status".to_owned(),
+				arguments: vec![],
+			})
+		);
+		assert!(parse_command(r#"add "unterminated"#).is_err());
+		assert!(parse_command("   ").is_err());
+	}
+
+	#[test]
+	fn can_parse_ranges() {
+		assert_eq!(parse_range("2", 5), Ok((2, 3)));
+		assert_eq!(parse_range("1:3", 5), Ok((1, 3)));
+		assert_eq!(parse_range("1:", 5), Ok((1, 5)));
+		assert!(parse_range("5", 5).is_err());
+		assert!(parse_range("3:1", 5).is_err());
+		assert!(parse_range("a", 5).is_err());
+	}
+}
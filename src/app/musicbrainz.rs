@@ -0,0 +1,354 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use log::{error, info};
+use native_db::*;
+use native_model::{native_model, Model};
+use serde::{Deserialize, Serialize};
+use tokio::task::spawn_blocking;
+
+use crate::app::{auth, index, ndb, scanner, Error};
+
+const API_URL: &str = "https://musicbrainz.org/ws/2";
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+// MusicBrainz allows one request per second from each client
+const REQUEST_INTERVAL: Duration = Duration::from_secs(1);
+
+// Release details looked up on MusicBrainz for albums tagged with a MusicBrainz release
+// identifier. They are cached in the database, and layered over file tags during collection scans
+// without modifying the files.
+#[derive(Clone)]
+pub struct Manager {
+	db: ndb::Manager,
+	index_manager: index::Manager,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Release {
+	pub year: Option<i64>,
+	pub genres: Vec<String>,
+	// Canonical names of the credited artists
+	pub artists: Vec<String>,
+}
+
+pub type MusicBrainzReleaseModel = v1::MusicBrainzReleaseModel;
+
+pub mod v1 {
+
+	use super::*;
+
+	#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+	#[native_model(id = 17, version = 1)]
+	#[native_db]
+	pub struct MusicBrainzReleaseModel {
+		#[primary_key]
+		pub mbid: String,
+		// Releases unknown to MusicBrainz are also cached, without any details
+		pub found: bool,
+		pub year: Option<i64>,
+		pub genres: Vec<String>,
+		pub artists: Vec<String>,
+		pub fetched_at: i64,
+	}
+}
+
+impl From<MusicBrainzReleaseModel> for Release {
+	fn from(m: MusicBrainzReleaseModel) -> Self {
+		Self {
+			year: m.year,
+			genres: m.genres,
+			artists: m.artists,
+		}
+	}
+}
+
+#[derive(Deserialize)]
+struct ReleaseResponse {
+	date: Option<String>,
+	#[serde(rename = "artist-credit", default)]
+	artist_credit: Vec<ArtistCredit>,
+	#[serde(default)]
+	genres: Vec<Genre>,
+	#[serde(rename = "release-group")]
+	release_group: Option<ReleaseGroup>,
+}
+
+#[derive(Deserialize)]
+struct ArtistCredit {
+	artist: Artist,
+}
+
+#[derive(Deserialize)]
+struct Artist {
+	name: String,
+}
+
+#[derive(Deserialize)]
+struct Genre {
+	name: String,
+}
+
+#[derive(Deserialize)]
+struct ReleaseGroup {
+	#[serde(rename = "first-release-date")]
+	first_release_date: Option<String>,
+	#[serde(default)]
+	genres: Vec<Genre>,
+}
+
+fn is_valid_mbid(mbid: &str) -> bool {
+	mbid.len() == 36 && mbid.chars().all(|c| c.is_ascii_hexdigit() || c == '-')
+}
+
+// Dates are formatted as YYYY, YYYY-MM or YYYY-MM-DD
+fn parse_year(date: Option<&str>) -> Option<i64> {
+	date?.get(..4)?.parse().ok()
+}
+
+fn parse_release(response: ReleaseResponse) -> Release {
+	let release_group = response.release_group;
+	let year = parse_year(response.date.as_deref()).or_else(|| {
+		parse_year(
+			release_group
+				.as_ref()
+				.and_then(|g| g.first_release_date.as_deref()),
+		)
+	});
+
+	let mut genres = Vec::<String>::new();
+	let group_genres = release_group.map(|g| g.genres).unwrap_or_default();
+	for genre in response.genres.into_iter().chain(group_genres) {
+		if !genres.contains(&genre.name) {
+			genres.push(genre.name);
+		}
+	}
+
+	Release {
+		year,
+		genres,
+		artists: response
+			.artist_credit
+			.into_iter()
+			.map(|c| c.artist.name)
+			.collect(),
+	}
+}
+
+// Returns `None` for releases which do not exist on MusicBrainz
+fn fetch_release(mbid: &str) -> Result<Option<Release>, String> {
+	let user_agent = format!(
+		"Polaris/{} ( https://github.com/agersant/polaris )",
+		env!("CARGO_PKG_VERSION")
+	);
+	let response = ureq::get(&format!("{API_URL}/release/{mbid}"))
+		.timeout(HTTP_TIMEOUT)
+		.set("User-Agent", &user_agent)
+		.set("Accept", "application/json")
+		.query("inc", "artist-credits+genres+release-groups")
+		.call();
+	match response {
+		Ok(r) => serde_json::from_reader::<_, ReleaseResponse>(r.into_reader())
+			.map(|r| Some(parse_release(r)))
+			.map_err(|e| e.to_string()),
+		Err(ureq::Error::Status(404, _)) => Ok(None),
+		Err(e) => Err(e.to_string()),
+	}
+}
+
+// Details from MusicBrainz take precedence over file tags when they are available
+pub fn apply(releases: &HashMap<String, Release>, song: &mut scanner::Song) {
+	let Some(release) = song
+		.musicbrainz_release_id
+		.as_deref()
+		.and_then(|mbid| releases.get(&mbid.trim().to_lowercase()))
+	else {
+		return;
+	};
+	song.year = release.year.or(song.year);
+	if !release.genres.is_empty() {
+		song.genres = release.genres.clone();
+	}
+	if !release.artists.is_empty() {
+		song.album_artists = release.artists.clone();
+	}
+}
+
+impl Manager {
+	pub fn new(db: ndb::Manager, index_manager: index::Manager) -> Self {
+		Self { db, index_manager }
+	}
+
+	pub async fn get_releases(&self) -> Result<HashMap<String, Release>, Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			move || {
+				let transaction = manager.db.r_transaction()?;
+				let releases = transaction
+					.scan()
+					.primary::<MusicBrainzReleaseModel>()?
+					.all()?
+					.filter_map(|m| m.ok())
+					.filter(|m| m.found)
+					.map(|m| (m.mbid.clone(), Release::from(m)))
+					.collect();
+				Ok(releases)
+			}
+		})
+		.await?
+	}
+
+	// Looks up releases of indexed songs which have not been looked up before. Returns how many
+	// releases were found on MusicBrainz.
+	pub async fn update_releases(&self) -> Result<usize, Error> {
+		let mbids = self
+			.index_manager
+			.get_all_songs()
+			.await
+			.into_iter()
+			.filter_map(|s| s.musicbrainz_release_id)
+			.filter(|mbid| is_valid_mbid(mbid))
+			.collect::<HashSet<_>>();
+
+		let known = spawn_blocking({
+			let manager = self.clone();
+			move || -> Result<HashSet<String>, Error> {
+				let transaction = manager.db.r_transaction()?;
+				let known = transaction
+					.scan()
+					.primary::<MusicBrainzReleaseModel>()?
+					.all()?
+					.filter_map(|m| m.ok())
+					.map(|m| m.mbid)
+					.collect();
+				Ok(known)
+			}
+		})
+		.await??;
+
+		let mut num_found = 0;
+		for mbid in mbids.difference(&known) {
+			let result = spawn_blocking({
+				let mbid = mbid.clone();
+				move || fetch_release(&mbid)
+			})
+			.await?;
+			tokio::time::sleep(REQUEST_INTERVAL).await;
+
+			let release = match result {
+				Ok(release) => release,
+				Err(e) => {
+					// Releases which could not be looked up are tried again after the next scan
+					error!("Could not look up MusicBrainz release `{mbid}`: {e}");
+					continue;
+				}
+			};
+
+			let found = release.is_some();
+			num_found += found as usize;
+			let release = release.unwrap_or_default();
+			self.save_release(MusicBrainzReleaseModel {
+				mbid: mbid.clone(),
+				found,
+				year: release.year,
+				genres: release.genres,
+				artists: release.artists,
+				fetched_at: auth::now() as i64,
+			})
+			.await?;
+		}
+
+		if num_found > 0 {
+			info!("Looked up {num_found} releases on MusicBrainz");
+		}
+
+		Ok(num_found)
+	}
+
+	async fn save_release(&self, model: MusicBrainzReleaseModel) -> Result<(), Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			move || {
+				let transaction = manager.db.rw_transaction()?;
+				transaction.upsert::<MusicBrainzReleaseModel>(model)?;
+				transaction.commit()?;
+				Ok(())
+			}
+		})
+		.await?
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	const RELEASE_RESPONSE: &str = r#"{
+		"id": "1f9b2b52-7b4e-4a3f-9a8c-9d3f2a1b6c7d",
+		"title": "Destiny",
+		"date": "1998-10-07",
+		"artist-credit": [
+			{ "name": "Strato", "joinphrase": "", "artist": { "id": "a", "name": "Stratovarius" } }
+		],
+		"genres": [{ "name": "power metal", "count": 4 }],
+		"release-group": {
+			"first-release-date": "1998-09-30",
+			"genres": [
+				{ "name": "power metal", "count": 6 },
+				{ "name": "symphonic metal", "count": 2 }
+			]
+		}
+	}"#;
+
+	#[test]
+	fn parses_release_details() {
+		let response = serde_json::from_str::<ReleaseResponse>(RELEASE_RESPONSE).unwrap();
+		assert_eq!(
+			parse_release(response),
+			Release {
+				year: Some(1998),
+				genres: vec!["power metal".to_owned(), "symphonic metal".to_owned()],
+				artists: vec!["Stratovarius".to_owned()],
+			}
+		);
+	}
+
+	#[test]
+	fn falls_back_to_release_group_date() {
+		let response = serde_json::from_str::<ReleaseResponse>(
+			r#"{ "release-group": { "first-release-date": "2016" } }"#,
+		)
+		.unwrap();
+		assert_eq!(parse_release(response).year, Some(2016));
+	}
+
+	#[test]
+	fn release_details_override_tags() {
+		let releases = HashMap::from([(
+			"1f9b2b52-7b4e-4a3f-9a8c-9d3f2a1b6c7d".to_owned(),
+			Release {
+				year: Some(1998),
+				genres: vec![],
+				artists: vec!["Stratovarius".to_owned()],
+			},
+		)]);
+
+		let mut song = scanner::Song {
+			year: Some(2003),
+			genres: vec!["Metal".to_owned()],
+			album_artists: vec!["Strato".to_owned()],
+			musicbrainz_release_id: Some("1F9B2B52-7B4E-4A3F-9A8C-9D3F2A1B6C7D".to_owned()),
+			..Default::default()
+		};
+		apply(&releases, &mut song);
+		assert_eq!(song.year, Some(1998));
+		assert_eq!(song.genres, vec!["Metal".to_owned()]);
+		assert_eq!(song.album_artists, vec!["Stratovarius".to_owned()]);
+
+		let mut untagged = scanner::Song {
+			year: Some(2003),
+			..Default::default()
+		};
+		apply(&releases, &mut untagged);
+		assert_eq!(untagged.year, Some(2003));
+	}
+}
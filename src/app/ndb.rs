@@ -6,11 +6,47 @@ use std::{
 
 use native_db::{Database, Models};
 
-use crate::app::{playlist, Error};
+use crate::app::{
+	acoustid, api_key, artist_image, artwork, audit, favorites, history, invite, listenbrainz,
+	musicbrainz, playlist, preferences, queue, radio, ratings, session, transfers, Error,
+};
 
 static MODELS: LazyLock<Models> = LazyLock::new(|| {
 	let mut models = Models::new();
 	models.define::<playlist::v1::PlaylistModel>().unwrap();
+	models.define::<playlist::v2::PlaylistModel>().unwrap();
+	models.define::<session::v1::SessionModel>().unwrap();
+	models.define::<session::v2::SessionModel>().unwrap();
+	models.define::<radio::v1::RadioStationModel>().unwrap();
+	models
+		.define::<artwork::v1::ArtworkOverrideModel>()
+		.unwrap();
+	models.define::<queue::v1::PlayQueueModel>().unwrap();
+	models.define::<api_key::v1::ApiKeyModel>().unwrap();
+	models.define::<invite::v1::InviteModel>().unwrap();
+	models.define::<audit::v1::AuditEntryModel>().unwrap();
+	models
+		.define::<preferences::v1::PreferencesModel>()
+		.unwrap();
+	models.define::<favorites::v1::StarModel>().unwrap();
+	models.define::<ratings::v1::RatingModel>().unwrap();
+	models.define::<history::v1::PlayModel>().unwrap();
+	models
+		.define::<listenbrainz::v1::ListenBrainzAccountModel>()
+		.unwrap();
+	models
+		.define::<listenbrainz::v1::PendingListenModel>()
+		.unwrap();
+	models.define::<transfers::v1::TransferModel>().unwrap();
+	models
+		.define::<acoustid::v1::AcoustIdFingerprintModel>()
+		.unwrap();
+	models
+		.define::<musicbrainz::v1::MusicBrainzReleaseModel>()
+		.unwrap();
+	models
+		.define::<artist_image::v1::ArtistImageModel>()
+		.unwrap();
 	models
 });
 
@@ -26,6 +62,12 @@ impl Manager {
 		let database = native_db::Builder::new()
 			.create(&MODELS, path)
 			.map_err(Error::NativeDatabaseCreationError)?;
+
+		let transaction = database.rw_transaction()?;
+		transaction.migrate::<playlist::PlaylistModel>()?;
+		transaction.migrate::<session::SessionModel>()?;
+		transaction.commit()?;
+
 		let database = Arc::new(database);
 		Ok(Self { database })
 	}
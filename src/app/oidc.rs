@@ -0,0 +1,721 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use rand::distributions::{Alphanumeric, DistString};
+use rand::rngs::OsRng;
+use ring::signature::{self, RsaPublicKeyComponents, UnparsedPublicKey};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::task::spawn_blocking;
+
+use crate::app::{config, Error};
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Time users have to sign in with the identity provider
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(600);
+
+// Logins which can be pending at the same time, so unauthenticated clients cannot exhaust memory
+const MAX_PENDING_LOGINS: usize = 1000;
+
+// Time provider metadata is reused for, so starting logins does not query the provider every time
+const METADATA_LIFETIME: Duration = Duration::from_secs(3600);
+
+// Sign in through an OpenID Connect identity provider, using the authorization code flow
+#[derive(Clone)]
+pub struct Manager {
+	config_manager: config::Manager,
+	pending_logins: Arc<Mutex<HashMap<String, PendingLogin>>>,
+	metadata: Arc<Mutex<Option<CachedMetadata>>>,
+}
+
+struct CachedMetadata {
+	issuer_url: String,
+	metadata: ProviderMetadata,
+	fetched: Instant,
+}
+
+struct PendingLogin {
+	nonce: String,
+	code_verifier: String,
+	redirect_uri: String,
+	return_to: Option<String>,
+	started: Instant,
+}
+
+// User vouched for by the identity provider
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Identity {
+	// Stable identifier of the account on the identity provider
+	pub subject: String,
+	pub username: String,
+	// Only known when an admin group is configured
+	pub admin: Option<bool>,
+	pub create_user: bool,
+	pub return_to: Option<String>,
+}
+
+#[derive(Clone, Deserialize)]
+struct ProviderMetadata {
+	issuer: String,
+	authorization_endpoint: String,
+	token_endpoint: String,
+	jwks_uri: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+	id_token: String,
+}
+
+#[derive(Deserialize)]
+struct JwkSet {
+	keys: Vec<Jwk>,
+}
+
+#[derive(Default, Deserialize)]
+struct Jwk {
+	kty: String,
+	kid: Option<String>,
+	n: Option<String>,
+	e: Option<String>,
+	crv: Option<String>,
+	x: Option<String>,
+	y: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct JwtHeader {
+	alg: String,
+	kid: Option<String>,
+}
+
+impl Manager {
+	pub fn new(config_manager: config::Manager) -> Self {
+		Self {
+			config_manager,
+			pending_logins: Arc::default(),
+			metadata: Arc::default(),
+		}
+	}
+
+	async fn get_metadata(&self, issuer_url: &str) -> Result<ProviderMetadata, Error> {
+		if let Some(cached) = self.metadata.lock().unwrap().as_ref() {
+			if cached.issuer_url == issuer_url && cached.fetched.elapsed() < METADATA_LIFETIME {
+				return Ok(cached.metadata.clone());
+			}
+		}
+		let metadata = fetch_metadata(issuer_url).await?;
+		*self.metadata.lock().unwrap() = Some(CachedMetadata {
+			issuer_url: issuer_url.to_owned(),
+			metadata: metadata.clone(),
+			fetched: Instant::now(),
+		});
+		Ok(metadata)
+	}
+
+	fn has_room_for_login(&self) -> bool {
+		let mut pending_logins = self.pending_logins.lock().unwrap();
+		pending_logins.retain(|_, l| l.started.elapsed() < LOGIN_TIMEOUT);
+		pending_logins.len() < MAX_PENDING_LOGINS
+	}
+
+	// Returns the identity provider URL users should be sent to. After signing in, they are
+	// redirected to `redirect_uri` which must complete the login.
+	pub async fn begin_login(
+		&self,
+		redirect_uri: String,
+		return_to: Option<String>,
+	) -> Result<String, Error> {
+		if return_to.as_deref().is_some_and(|p| !is_local_path(p)) {
+			return Err(Error::OidcReturnPathInvalid);
+		}
+
+		let oidc = self
+			.config_manager
+			.get_oidc()
+			.await
+			.ok_or(Error::OidcNotConfigured)?;
+		if !self.has_room_for_login() {
+			return Err(Error::TooManyPendingLogins);
+		}
+		let metadata = self.get_metadata(&oidc.issuer_url).await?;
+
+		let state = Alphanumeric.sample_string(&mut OsRng, 32);
+		let nonce = Alphanumeric.sample_string(&mut OsRng, 32);
+		let code_verifier = Alphanumeric.sample_string(&mut OsRng, 64);
+		let code_challenge = URL_SAFE_NO_PAD.encode(ring::digest::digest(
+			&ring::digest::SHA256,
+			code_verifier.as_bytes(),
+		));
+
+		let scope = match oidc.admin_group {
+			Some(_) => "openid profile groups",
+			None => "openid profile",
+		};
+		let parameters = [
+			("response_type", "code"),
+			("client_id", &oidc.client_id),
+			("redirect_uri", &redirect_uri),
+			("scope", scope),
+			("state", &state),
+			("nonce", &nonce),
+			("code_challenge", &code_challenge),
+			("code_challenge_method", "S256"),
+		];
+		let query = parameters
+			.iter()
+			.map(|(k, v)| format!("{k}={}", utf8_percent_encode(v, NON_ALPHANUMERIC)))
+			.collect::<Vec<_>>()
+			.join("&");
+		let separator = match metadata.authorization_endpoint.contains('?') {
+			true => '&',
+			false => '?',
+		};
+		let url = format!("{}{separator}{query}", metadata.authorization_endpoint);
+
+		let mut pending_logins = self.pending_logins.lock().unwrap();
+		if pending_logins.len() >= MAX_PENDING_LOGINS {
+			return Err(Error::TooManyPendingLogins);
+		}
+		pending_logins.insert(
+			state,
+			PendingLogin {
+				nonce,
+				code_verifier,
+				redirect_uri,
+				return_to,
+				started: Instant::now(),
+			},
+		);
+
+		Ok(url)
+	}
+
+	// Redeems the authorization code sent back by the identity provider
+	pub async fn complete_login(&self, state: &str, code: &str) -> Result<Identity, Error> {
+		let pending = self
+			.pending_logins
+			.lock()
+			.unwrap()
+			.remove(state)
+			.filter(|l| l.started.elapsed() < LOGIN_TIMEOUT)
+			.ok_or(Error::OidcLoginExpired)?;
+
+		let oidc = self
+			.config_manager
+			.get_oidc()
+			.await
+			.ok_or(Error::OidcNotConfigured)?;
+		let metadata = self.get_metadata(&oidc.issuer_url).await?;
+
+		let credentials = format!(
+			"{}:{}",
+			utf8_percent_encode(&oidc.client_id, NON_ALPHANUMERIC),
+			utf8_percent_encode(&oidc.client_secret, NON_ALPHANUMERIC)
+		);
+		let token_response: TokenResponse = spawn_blocking({
+			let token_endpoint = metadata.token_endpoint.clone();
+			let code = code.to_owned();
+			move || {
+				ureq::post(&token_endpoint)
+					.timeout(HTTP_TIMEOUT)
+					.set(
+						"Authorization",
+						&format!("Basic {}", STANDARD.encode(credentials)),
+					)
+					.send_form(&[
+						("grant_type", "authorization_code"),
+						("code", &code),
+						("redirect_uri", &pending.redirect_uri),
+						("code_verifier", &pending.code_verifier),
+					])
+					.map_err(|e| Error::OidcProviderFailed(e.to_string()))
+					.and_then(parse_response)
+			}
+		})
+		.await??;
+
+		let jwks: JwkSet = fetch_json(metadata.jwks_uri).await?;
+		let claims = verify_id_token(
+			&token_response.id_token,
+			&jwks.keys,
+			&metadata.issuer,
+			&oidc.client_id,
+			&pending.nonce,
+		)?;
+
+		let subject = claims
+			.get("sub")
+			.and_then(Value::as_str)
+			.filter(|s| !s.is_empty())
+			.ok_or_else(|| Error::OidcTokenInvalid("missing `sub` claim".to_owned()))?;
+
+		let username = claims
+			.get(&oidc.username_claim)
+			.and_then(Value::as_str)
+			.filter(|u| !u.is_empty())
+			.ok_or_else(|| {
+				Error::OidcTokenInvalid(format!("missing `{}` claim", oidc.username_claim))
+			})?;
+
+		let admin = oidc.admin_group.map(|group| {
+			claims
+				.get("groups")
+				.and_then(Value::as_array)
+				.is_some_and(|groups| groups.iter().any(|g| g.as_str() == Some(&group)))
+		});
+
+		Ok(Identity {
+			subject: subject.to_owned(),
+			username: username.to_owned(),
+			admin,
+			create_user: oidc.create_users,
+			return_to: pending.return_to,
+		})
+	}
+}
+
+async fn fetch_metadata(issuer_url: &str) -> Result<ProviderMetadata, Error> {
+	let metadata: ProviderMetadata =
+		fetch_json(format!("{issuer_url}/.well-known/openid-configuration")).await?;
+	if metadata.issuer.trim_end_matches('/') != issuer_url {
+		return Err(Error::OidcProviderFailed(format!(
+			"provider reports issuer `{}`",
+			metadata.issuer
+		)));
+	}
+	Ok(metadata)
+}
+
+async fn fetch_json<T: DeserializeOwned + Send + 'static>(url: String) -> Result<T, Error> {
+	spawn_blocking(move || {
+		ureq::get(&url)
+			.timeout(HTTP_TIMEOUT)
+			.call()
+			.map_err(|e| Error::OidcProviderFailed(e.to_string()))
+			.and_then(parse_response)
+	})
+	.await?
+}
+
+// Paths users may be sent back to after signing in. Browsers ignore some characters in URLs, so
+// `/\t/evil.example.com` would lead to another host: paths containing such characters are
+// refused, including percent-encoded control characters and slashes.
+fn is_local_path(path: &str) -> bool {
+	let is_relative = |p: &str| p.starts_with('/') && !p.starts_with("//");
+	let decoded = percent_decode_str(path).decode_utf8_lossy();
+	is_relative(path)
+		&& is_relative(&decoded)
+		&& !path
+			.chars()
+			.any(|c| c.is_control() || c.is_whitespace() || c == '\\')
+		&& !decoded.chars().any(|c| c.is_control() || c == '\\')
+}
+
+fn parse_response<T: DeserializeOwned>(response: ureq::Response) -> Result<T, Error> {
+	serde_json::from_reader(response.into_reader())
+		.map_err(|e| Error::OidcProviderFailed(e.to_string()))
+}
+
+fn decode_base64(input: &str) -> Result<Vec<u8>, Error> {
+	URL_SAFE_NO_PAD
+		.decode(input.trim_end_matches('='))
+		.map_err(|_| Error::OidcTokenInvalid("malformed base64".to_owned()))
+}
+
+// Checks the signature and claims of an ID token, and returns its claims
+fn verify_id_token(
+	token: &str,
+	keys: &[Jwk],
+	issuer: &str,
+	client_id: &str,
+	nonce: &str,
+) -> Result<serde_json::Map<String, Value>, Error> {
+	let invalid = |reason: &str| Error::OidcTokenInvalid(reason.to_owned());
+
+	let mut parts = token.split('.');
+	let (Some(header), Some(payload), Some(signature), None) =
+		(parts.next(), parts.next(), parts.next(), parts.next())
+	else {
+		return Err(invalid("malformed token"));
+	};
+
+	let header: JwtHeader =
+		serde_json::from_slice(&decode_base64(header)?).map_err(|_| invalid("malformed header"))?;
+	let message = &token[..token.len() - signature.len() - 1];
+	let signature = decode_base64(signature)?;
+
+	let verified = keys
+		.iter()
+		.filter(|k| header.kid.is_none() || k.kid == header.kid)
+		.any(|k| verify_signature(&header.alg, k, message.as_bytes(), &signature));
+	if !verified {
+		return Err(invalid("signature does not match any provider key"));
+	}
+
+	let claims: serde_json::Map<String, Value> = serde_json::from_slice(&decode_base64(payload)?)
+		.map_err(|_| invalid("malformed claims"))?;
+
+	if claims.get("iss").and_then(Value::as_str) != Some(issuer) {
+		return Err(invalid("unexpected issuer"));
+	}
+
+	let audience_matches = match claims.get("aud") {
+		Some(Value::String(a)) => a == client_id,
+		Some(Value::Array(a)) => a.iter().any(|a| a.as_str() == Some(client_id)),
+		_ => false,
+	};
+	if !audience_matches {
+		return Err(invalid("unexpected audience"));
+	}
+
+	let now = SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map(|d| d.as_secs())
+		.unwrap_or_default();
+	if !claims
+		.get("exp")
+		.and_then(Value::as_u64)
+		.is_some_and(|exp| exp > now)
+	{
+		return Err(invalid("token expired"));
+	}
+
+	if claims.get("nonce").and_then(Value::as_str) != Some(nonce) {
+		return Err(invalid("unexpected nonce"));
+	}
+
+	Ok(claims)
+}
+
+fn verify_signature(alg: &str, key: &Jwk, message: &[u8], signature: &[u8]) -> bool {
+	let decode = |value: &Option<String>| value.as_deref().and_then(|v| decode_base64(v).ok());
+
+	let rsa_parameters = match alg {
+		"RS256" => Some(&signature::RSA_PKCS1_2048_8192_SHA256),
+		"RS384" => Some(&signature::RSA_PKCS1_2048_8192_SHA384),
+		"RS512" => Some(&signature::RSA_PKCS1_2048_8192_SHA512),
+		"PS256" => Some(&signature::RSA_PSS_2048_8192_SHA256),
+		"PS384" => Some(&signature::RSA_PSS_2048_8192_SHA384),
+		"PS512" => Some(&signature::RSA_PSS_2048_8192_SHA512),
+		_ => None,
+	};
+	if let Some(parameters) = rsa_parameters {
+		let (Some(n), Some(e)) = (decode(&key.n), decode(&key.e)) else {
+			return false;
+		};
+		return key.kty == "RSA"
+			&& RsaPublicKeyComponents { n, e }
+				.verify(parameters, message, signature)
+				.is_ok();
+	}
+
+	match (alg, key.kty.as_str(), key.crv.as_deref()) {
+		("ES256", "EC", Some("P-256")) | ("ES384", "EC", Some("P-384")) => {
+			let (Some(x), Some(y)) = (decode(&key.x), decode(&key.y)) else {
+				return false;
+			};
+			let algorithm = match alg {
+				"ES256" => &signature::ECDSA_P256_SHA256_FIXED,
+				_ => &signature::ECDSA_P384_SHA384_FIXED,
+			};
+			let point = [&[0x04], x.as_slice(), y.as_slice()].concat();
+			UnparsedPublicKey::new(algorithm, point)
+				.verify(message, signature)
+				.is_ok()
+		}
+		("EdDSA", "OKP", Some("Ed25519")) => {
+			let Some(x) = decode(&key.x) else {
+				return false;
+			};
+			UnparsedPublicKey::new(&signature::ED25519, x)
+				.verify(message, signature)
+				.is_ok()
+		}
+		_ => false,
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::io::{BufRead, BufReader, Read, Write};
+	use std::net::TcpListener;
+
+	use ring::rand::SystemRandom;
+	use ring::signature::{Ed25519KeyPair, KeyPair};
+	use serde_json::json;
+
+	use super::*;
+	use crate::app::config::storage;
+	use crate::app::test;
+	use crate::test_name;
+
+	const CLIENT_ID: &str = "polaris";
+
+	fn make_key_pair() -> Ed25519KeyPair {
+		let document = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+		Ed25519KeyPair::from_pkcs8(document.as_ref()).unwrap()
+	}
+
+	fn make_jwk(key_pair: &Ed25519KeyPair) -> Value {
+		json!({
+			"kty": "OKP",
+			"crv": "Ed25519",
+			"kid": "test-key",
+			"x": URL_SAFE_NO_PAD.encode(key_pair.public_key().as_ref()),
+		})
+	}
+
+	fn make_id_token(key_pair: &Ed25519KeyPair, claims: Value) -> String {
+		let header = json!({"alg": "EdDSA", "kid": "test-key"});
+		let message = format!(
+			"{}.{}",
+			URL_SAFE_NO_PAD.encode(header.to_string()),
+			URL_SAFE_NO_PAD.encode(claims.to_string())
+		);
+		let signature = URL_SAFE_NO_PAD.encode(key_pair.sign(message.as_bytes()).as_ref());
+		format!("{message}.{signature}")
+	}
+
+	fn make_claims(issuer: &str, nonce: &str) -> Value {
+		let exp = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.unwrap()
+			.as_secs()
+			+ 300;
+		json!({
+			"iss": issuer,
+			"aud": CLIENT_ID,
+			"exp": exp,
+			"nonce": nonce,
+			"sub": "248289761001",
+			"preferred_username": "walter",
+			"groups": ["admins", "users"],
+		})
+	}
+
+	#[test]
+	fn verifies_id_tokens() {
+		let key_pair = make_key_pair();
+		let keys = vec![serde_json::from_value::<Jwk>(make_jwk(&key_pair)).unwrap()];
+		let issuer = "https://auth.example.com";
+		let verify = |token: &str| verify_id_token(token, &keys, issuer, CLIENT_ID, "nonce");
+
+		let token = make_id_token(&key_pair, make_claims(issuer, "nonce"));
+		let claims = verify(&token).unwrap();
+		assert_eq!(claims["preferred_username"], "walter");
+
+		let token = make_id_token(&key_pair, make_claims(issuer, "other nonce"));
+		assert!(verify(&token).is_err());
+
+		let token = make_id_token(&key_pair, make_claims("https://evil.example.com", "nonce"));
+		assert!(verify(&token).is_err());
+
+		let mut claims = make_claims(issuer, "nonce");
+		claims["aud"] = json!(["someone-else"]);
+		assert!(verify(&make_id_token(&key_pair, claims)).is_err());
+
+		let mut claims = make_claims(issuer, "nonce");
+		claims["exp"] = json!(1000);
+		assert!(verify(&make_id_token(&key_pair, claims)).is_err());
+
+		let other_key_pair = make_key_pair();
+		let token = make_id_token(&other_key_pair, make_claims(issuer, "nonce"));
+		assert!(verify(&token).is_err());
+
+		let (message, _) = token.rsplit_once('.').unwrap();
+		assert!(verify(&format!("{message}.")).is_err());
+	}
+
+	// Serves discovery, token and key set endpoints of a fake identity provider
+	fn serve_provider(listener: TcpListener, id_token: Arc<Mutex<String>>, jwks: Value) {
+		let issuer = format!("http://{}", listener.local_addr().unwrap());
+		std::thread::spawn(move || {
+			for stream in listener.incoming() {
+				let Ok(mut stream) = stream else {
+					continue;
+				};
+				let mut reader = BufReader::new(stream.try_clone().unwrap());
+				let mut request_line = String::new();
+				reader.read_line(&mut request_line).unwrap();
+				let mut content_length = 0;
+				loop {
+					let mut line = String::new();
+					reader.read_line(&mut line).unwrap();
+					if line.trim().is_empty() {
+						break;
+					}
+					if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+						content_length = value.trim().parse().unwrap();
+					}
+				}
+				let mut body = vec![0; content_length];
+				reader.read_exact(&mut body).unwrap();
+
+				let path = request_line.split(' ').nth(1).unwrap_or_default();
+				let response = match path {
+					"/.well-known/openid-configuration" => json!({
+						"issuer": issuer,
+						"authorization_endpoint": format!("{issuer}/authorize"),
+						"token_endpoint": format!("{issuer}/token"),
+						"jwks_uri": format!("{issuer}/jwks"),
+					}),
+					"/token" => json!({"id_token": *id_token.lock().unwrap()}),
+					"/jwks" => jwks.clone(),
+					_ => json!({}),
+				}
+				.to_string();
+				write!(
+					stream,
+					"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{response}",
+					response.len()
+				)
+				.unwrap();
+			}
+		});
+	}
+
+	#[tokio::test]
+	async fn can_login_through_provider() {
+		let ctx = test::ContextBuilder::new(test_name!()).build().await;
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let issuer = format!("http://{}", listener.local_addr().unwrap());
+		let key_pair = make_key_pair();
+		let id_token = Arc::new(Mutex::new(String::new()));
+		serve_provider(
+			listener,
+			id_token.clone(),
+			json!({"keys": [make_jwk(&key_pair)]}),
+		);
+
+		ctx.config_manager
+			.apply_config(storage::Config {
+				oidc: Some(storage::Oidc {
+					issuer_url: format!("{issuer}/"),
+					client_id: CLIENT_ID.to_owned(),
+					client_secret: "secret".to_owned(),
+					admin_group: Some("admins".to_owned()),
+					create_users: Some(true),
+					..Default::default()
+				}),
+				..Default::default()
+			})
+			.await
+			.unwrap();
+
+		let oidc_manager = Manager::new(ctx.config_manager.clone());
+		let url = oidc_manager
+			.begin_login(
+				"http://polaris/api/oidc/callback".to_owned(),
+				Some("/library".to_owned()),
+			)
+			.await
+			.unwrap();
+		assert!(url.starts_with(&format!("{issuer}/authorize?")));
+
+		let parameter = |name: &str| {
+			url.split(['?', '&'])
+				.find_map(|p| p.strip_prefix(&format!("{name}=")))
+				.unwrap()
+				.to_owned()
+		};
+		*id_token.lock().unwrap() =
+			make_id_token(&key_pair, make_claims(&issuer, &parameter("nonce")));
+
+		let identity = oidc_manager
+			.complete_login(&parameter("state"), "code")
+			.await
+			.unwrap();
+		assert_eq!(
+			identity,
+			Identity {
+				subject: "248289761001".to_owned(),
+				username: "walter".to_owned(),
+				admin: Some(true),
+				create_user: true,
+				return_to: Some("/library".to_owned()),
+			}
+		);
+
+		assert!(matches!(
+			oidc_manager
+				.complete_login(&parameter("state"), "code")
+				.await,
+			Err(Error::OidcLoginExpired)
+		));
+	}
+
+	#[tokio::test]
+	async fn pending_logins_are_capped() {
+		let ctx = test::ContextBuilder::new(test_name!()).build().await;
+		ctx.config_manager
+			.apply_config(storage::Config {
+				oidc: Some(storage::Oidc {
+					issuer_url: "http://127.0.0.1:1".to_owned(),
+					client_id: CLIENT_ID.to_owned(),
+					client_secret: "secret".to_owned(),
+					..Default::default()
+				}),
+				..Default::default()
+			})
+			.await
+			.unwrap();
+
+		let oidc_manager = Manager::new(ctx.config_manager.clone());
+		for i in 0..MAX_PENDING_LOGINS {
+			oidc_manager.pending_logins.lock().unwrap().insert(
+				i.to_string(),
+				PendingLogin {
+					nonce: String::new(),
+					code_verifier: String::new(),
+					redirect_uri: String::new(),
+					return_to: None,
+					started: Instant::now(),
+				},
+			);
+		}
+		assert!(matches!(
+			oidc_manager.begin_login(String::new(), None).await,
+			Err(Error::TooManyPendingLogins)
+		));
+	}
+
+	#[tokio::test]
+	async fn rejects_foreign_return_paths() {
+		let ctx = test::ContextBuilder::new(test_name!()).build().await;
+		let oidc_manager = Manager::new(ctx.config_manager.clone());
+		for return_to in [
+			"https://evil.example.com",
+			"//evil.example.com",
+			"/\\evil",
+			"/\t/evil.example.com",
+			"/%09/evil.example.com",
+			"/\n/evil.example.com",
+			"/ /evil.example.com",
+		] {
+			assert!(matches!(
+				oidc_manager
+					.begin_login(String::new(), Some(return_to.to_owned()))
+					.await,
+				Err(Error::OidcReturnPathInvalid)
+			));
+		}
+		assert!(matches!(
+			oidc_manager.begin_login(String::new(), None).await,
+			Err(Error::OidcNotConfigured)
+		));
+	}
+
+	#[test]
+	fn accepts_local_paths() {
+		assert!(is_local_path("/"));
+		assert!(is_local_path("/library/Khemmis%20-%20Hunted?view=list"));
+		assert!(!is_local_path("/%2F/evil.example.com"));
+	}
+}
@@ -1,11 +1,14 @@
 use core::clone::Clone;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::path::PathBuf;
 use std::time::Duration;
 
 use icu_collator::{Collator, CollatorOptions, Strength};
 use native_db::*;
 use native_model::{native_model, Model};
+use rand::distributions::{Alphanumeric, DistString};
+use rand::rngs::OsRng;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
 use tokio::task::spawn_blocking;
 
@@ -27,10 +30,27 @@ pub struct PlaylistHeader {
 pub struct Playlist {
 	pub header: PlaylistHeader,
 	pub songs: Vec<PathBuf>,
+	/// Identifies the podcast feed of this playlist, if one was created
+	pub feed_id: Option<String>,
 }
 
-pub type PlaylistModel = v1::PlaylistModel;
-type PlaylistModelKey = v1::PlaylistModelKey;
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlendMode {
+	/// Only songs found in the playlists of every listener
+	#[default]
+	Intersection,
+	/// Songs found in the playlists of any listener, favoring songs shared by many (or heavily weighted) listeners
+	WeightedUnion,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Listener {
+	pub username: String,
+	pub weight: f64,
+}
+
+pub type PlaylistModel = v2::PlaylistModel;
+type PlaylistModelKey = v2::PlaylistModelKey;
 
 pub mod v1 {
 
@@ -53,6 +73,55 @@ pub mod v1 {
 			(&self.owner, &self.name)
 		}
 	}
+
+	impl From<v2::PlaylistModel> for PlaylistModel {
+		fn from(p: v2::PlaylistModel) -> Self {
+			Self {
+				owner: p.owner,
+				name: p.name,
+				duration: p.duration,
+				num_songs_by_genre: p.num_songs_by_genre,
+				virtual_paths: p.virtual_paths,
+			}
+		}
+	}
+}
+
+pub mod v2 {
+
+	use super::*;
+
+	#[derive(Debug, Default, Serialize, Deserialize)]
+	#[native_model(id = 1, version = 2, from = v1::PlaylistModel)]
+	#[native_db(primary_key(custom_id -> (&str, &str)))]
+	pub struct PlaylistModel {
+		#[secondary_key]
+		pub owner: String,
+		pub name: String,
+		pub duration: Duration,
+		pub num_songs_by_genre: BTreeMap<String, u32>,
+		pub virtual_paths: Vec<PathBuf>,
+		pub feed_id: Option<String>,
+	}
+
+	impl PlaylistModel {
+		fn custom_id(&self) -> (&str, &str) {
+			(&self.owner, &self.name)
+		}
+	}
+
+	impl From<v1::PlaylistModel> for PlaylistModel {
+		fn from(p: v1::PlaylistModel) -> Self {
+			Self {
+				owner: p.owner,
+				name: p.name,
+				duration: p.duration,
+				num_songs_by_genre: p.num_songs_by_genre,
+				virtual_paths: p.virtual_paths,
+				feed_id: None,
+			}
+		}
+	}
 }
 
 impl From<PlaylistModel> for PlaylistHeader {
@@ -68,8 +137,10 @@ impl From<PlaylistModel> for PlaylistHeader {
 impl From<PlaylistModel> for Playlist {
 	fn from(mut p: PlaylistModel) -> Self {
 		let songs = p.virtual_paths.drain(0..).collect();
+		let feed_id = p.feed_id.take();
 		Self {
 			songs,
+			feed_id,
 			header: p.into(),
 		}
 	}
@@ -135,12 +206,19 @@ impl Manager {
 
 				let virtual_paths = songs.into_iter().map(|s| s.virtual_path).collect();
 
+				// Saving over an existing playlist keeps its feed working
+				let feed_id = transaction
+					.get()
+					.primary::<PlaylistModel>((owner.as_str(), name.as_str()))?
+					.and_then(|p| p.feed_id);
+
 				transaction.upsert::<PlaylistModel>(PlaylistModel {
 					owner: owner.to_owned(),
 					name: name.to_owned(),
 					duration: Duration::from_secs(duration),
 					num_songs_by_genre,
 					virtual_paths,
+					feed_id,
 				})?;
 
 				transaction.commit()?;
@@ -168,6 +246,121 @@ impl Manager {
 		.await?
 	}
 
+	// Picks songs from the playlists of several users
+	pub async fn blend_playlists(
+		&self,
+		listeners: Vec<Listener>,
+		mode: BlendMode,
+		seed: Option<u64>,
+		count: usize,
+	) -> Result<Vec<PathBuf>, Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			move || {
+				let transaction = manager.db.r_transaction()?;
+
+				let mut scores = BTreeMap::<PathBuf, f64>::new();
+				let mut num_listeners = BTreeMap::<PathBuf, usize>::new();
+				for listener in &listeners {
+					let mut songs = BTreeSet::new();
+					for playlist in transaction
+						.scan()
+						.secondary::<PlaylistModel>(PlaylistModelKey::owner)?
+						.range(listener.username.as_str()..=listener.username.as_str())?
+						.filter_map(|p| p.ok())
+					{
+						songs.extend(playlist.virtual_paths);
+					}
+					for song in songs {
+						*scores.entry(song.clone()).or_default() += listener.weight.max(0.0);
+						*num_listeners.entry(song).or_default() += 1;
+					}
+				}
+
+				let mut rng = match seed {
+					Some(seed) => StdRng::seed_from_u64(seed),
+					None => StdRng::from_entropy(),
+				};
+
+				let songs = match mode {
+					BlendMode::Intersection => {
+						let mut songs = num_listeners
+							.into_iter()
+							.filter(|(_, n)| *n == listeners.len())
+							.map(|(path, _)| path)
+							.collect::<Vec<_>>();
+						songs.shuffle(&mut rng);
+						songs.truncate(count);
+						songs
+					}
+					BlendMode::WeightedUnion => {
+						// Weighted sampling without replacement (Efraimidis & Spirakis)
+						let mut keyed_songs = scores
+							.into_iter()
+							.filter(|(_, score)| *score > 0.0)
+							.map(|(path, score)| (rng.gen::<f64>().powf(1.0 / score), path))
+							.collect::<Vec<_>>();
+						keyed_songs.sort_by(|a, b| b.0.total_cmp(&a.0));
+						keyed_songs
+							.into_iter()
+							.take(count)
+							.map(|(_, path)| path)
+							.collect()
+					}
+				};
+
+				Ok(songs)
+			}
+		})
+		.await?
+	}
+
+	// Returns the identifier of the playlist's podcast feed, creating one if needed
+	pub async fn create_feed(&self, name: &str, owner: &str) -> Result<String, Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			let owner = owner.to_owned();
+			let name = name.to_owned();
+			move || {
+				let transaction = manager.db.rw_transaction()?;
+				let mut playlist = transaction
+					.get()
+					.primary::<PlaylistModel>((owner.as_str(), name.as_str()))?
+					.ok_or(Error::PlaylistNotFound)?;
+				if let Some(feed_id) = &playlist.feed_id {
+					return Ok(feed_id.clone());
+				}
+				let feed_id = Alphanumeric.sample_string(&mut OsRng, 32);
+				playlist.feed_id = Some(feed_id.clone());
+				transaction.upsert::<PlaylistModel>(playlist)?;
+				transaction.commit()?;
+				Ok(feed_id)
+			}
+		})
+		.await?
+	}
+
+	// Stops the playlist's podcast feed from working, until a new one is created
+	pub async fn revoke_feed(&self, name: &str, owner: &str) -> Result<(), Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			let owner = owner.to_owned();
+			let name = name.to_owned();
+			move || {
+				let transaction = manager.db.rw_transaction()?;
+				let mut playlist = transaction
+					.get()
+					.primary::<PlaylistModel>((owner.as_str(), name.as_str()))?
+					.ok_or(Error::PlaylistNotFound)?;
+				playlist.feed_id = None;
+				transaction.upsert::<PlaylistModel>(playlist)?;
+				transaction.commit()?;
+				Ok(())
+			}
+		})
+		.await?
+	}
+
 	pub async fn delete_playlist(&self, name: &str, owner: &str) -> Result<(), Error> {
 		spawn_blocking({
 			let manager = self.clone();
@@ -196,11 +389,13 @@ impl Manager {
 mod test {
 	use std::path::PathBuf;
 
+	use super::*;
 	use crate::app::index;
 	use crate::app::test::{self, Context};
 	use crate::test_name;
 
 	const TEST_USER: &str = "test_user";
+	const OTHER_USER: &str = "other_user";
 	const TEST_PASSWORD: &str = "password";
 	const TEST_PLAYLIST_NAME: &str = "Chill & Grill";
 	const TEST_MOUNT_NAME: &str = "root";
@@ -371,4 +566,175 @@ mod test {
 
 		assert_eq!(names, vec!["ax", "Ay", "àz", "B", "b"]);
 	}
+
+	#[tokio::test]
+	async fn can_blend_playlists() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.user(OTHER_USER, TEST_PASSWORD, false)
+			.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+			.build()
+			.await;
+
+		ctx.scanner.run_scan().await.unwrap();
+		let songs = list_all_songs(&ctx).await;
+
+		ctx.playlist_manager
+			.save_playlist(TEST_PLAYLIST_NAME, TEST_USER, songs[0..5].to_vec())
+			.await
+			.unwrap();
+		ctx.playlist_manager
+			.save_playlist(TEST_PLAYLIST_NAME, OTHER_USER, songs[3..8].to_vec())
+			.await
+			.unwrap();
+
+		let listeners = vec![
+			Listener {
+				username: TEST_USER.to_owned(),
+				weight: 1.0,
+			},
+			Listener {
+				username: OTHER_USER.to_owned(),
+				weight: 1.0,
+			},
+		];
+
+		let intersection = ctx
+			.playlist_manager
+			.blend_playlists(listeners.clone(), BlendMode::Intersection, None, 100)
+			.await
+			.unwrap();
+		let intersection = BTreeSet::from_iter(intersection);
+		let expected = songs[3..5].iter().map(|s| s.virtual_path.clone());
+		assert_eq!(intersection, BTreeSet::from_iter(expected));
+
+		let union = ctx
+			.playlist_manager
+			.blend_playlists(listeners.clone(), BlendMode::WeightedUnion, None, 100)
+			.await
+			.unwrap();
+		assert_eq!(union.len(), 8);
+
+		let limited = ctx
+			.playlist_manager
+			.blend_playlists(listeners, BlendMode::WeightedUnion, Some(0), 3)
+			.await
+			.unwrap();
+		assert_eq!(limited.len(), 3);
+	}
+
+	#[tokio::test]
+	async fn blend_ignores_unweighted_listeners() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.user(OTHER_USER, TEST_PASSWORD, false)
+			.mount(TEST_MOUNT_NAME, "test-data/small-collection")
+			.build()
+			.await;
+
+		ctx.scanner.run_scan().await.unwrap();
+		let songs = list_all_songs(&ctx).await;
+
+		ctx.playlist_manager
+			.save_playlist(TEST_PLAYLIST_NAME, TEST_USER, songs[0..5].to_vec())
+			.await
+			.unwrap();
+		ctx.playlist_manager
+			.save_playlist(TEST_PLAYLIST_NAME, OTHER_USER, songs[5..8].to_vec())
+			.await
+			.unwrap();
+
+		let listeners = vec![
+			Listener {
+				username: TEST_USER.to_owned(),
+				weight: 1.0,
+			},
+			Listener {
+				username: OTHER_USER.to_owned(),
+				weight: 0.0,
+			},
+		];
+
+		let blend = ctx
+			.playlist_manager
+			.blend_playlists(listeners, BlendMode::WeightedUnion, None, 100)
+			.await
+			.unwrap();
+		let expected = songs[0..5].iter().map(|s| s.virtual_path.clone());
+		assert_eq!(BTreeSet::from_iter(blend), BTreeSet::from_iter(expected));
+	}
+
+	#[tokio::test]
+	async fn feed_is_kept_when_saving_and_lost_when_deleting() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.build()
+			.await;
+
+		let save = || {
+			ctx.playlist_manager
+				.save_playlist(TEST_PLAYLIST_NAME, TEST_USER, Vec::new())
+		};
+		let read_feed_id = || async {
+			ctx.playlist_manager
+				.read_playlist(TEST_PLAYLIST_NAME, TEST_USER)
+				.await
+				.unwrap()
+				.feed_id
+		};
+
+		save().await.unwrap();
+		let feed_id = ctx
+			.playlist_manager
+			.create_feed(TEST_PLAYLIST_NAME, TEST_USER)
+			.await
+			.unwrap();
+		assert_eq!(
+			ctx.playlist_manager
+				.create_feed(TEST_PLAYLIST_NAME, TEST_USER)
+				.await
+				.unwrap(),
+			feed_id
+		);
+
+		save().await.unwrap();
+		assert_eq!(read_feed_id().await, Some(feed_id));
+
+		ctx.playlist_manager
+			.delete_playlist(TEST_PLAYLIST_NAME, TEST_USER)
+			.await
+			.unwrap();
+		save().await.unwrap();
+		assert_eq!(read_feed_id().await, None);
+	}
+
+	#[tokio::test]
+	async fn feed_can_be_revoked() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.build()
+			.await;
+
+		ctx.playlist_manager
+			.save_playlist(TEST_PLAYLIST_NAME, TEST_USER, Vec::new())
+			.await
+			.unwrap();
+		let feed_id = ctx
+			.playlist_manager
+			.create_feed(TEST_PLAYLIST_NAME, TEST_USER)
+			.await
+			.unwrap();
+
+		ctx.playlist_manager
+			.revoke_feed(TEST_PLAYLIST_NAME, TEST_USER)
+			.await
+			.unwrap();
+
+		let new_feed_id = ctx
+			.playlist_manager
+			.create_feed(TEST_PLAYLIST_NAME, TEST_USER)
+			.await
+			.unwrap();
+		assert_ne!(new_feed_id, feed_id);
+	}
 }
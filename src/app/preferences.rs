@@ -0,0 +1,207 @@
+use std::collections::BTreeMap;
+
+use native_db::*;
+use native_model::{native_model, Model};
+use serde::{Deserialize, Serialize};
+use tokio::task::spawn_blocking;
+
+use crate::app::{ndb, Error};
+
+pub const MAX_KEY_LENGTH: usize = 64;
+pub const MAX_NUM_KEYS: usize = 100;
+pub const MAX_VALUE_LENGTH: usize = 16 * 1024;
+
+pub type Preferences = BTreeMap<String, serde_json::Value>;
+
+// Default sort orders for file and album listings
+pub const BROWSE_SORT_KEY: &str = "browse_sort";
+pub const ALBUM_SORT_KEY: &str = "album_sort";
+
+// Whether other users can blend this user's playlists into their family mixes
+pub const FAMILY_MIX_KEY: &str = "family_mix";
+
+// Settings chosen by users in their clients (eg. theme or language), stored server-side so they
+// follow users between browsers and devices. Polaris does not interpret them, except for the
+// keys above.
+#[derive(Clone)]
+pub struct Manager {
+	db: ndb::Manager,
+}
+
+pub type PreferencesModel = v1::PreferencesModel;
+
+pub mod v1 {
+
+	use super::*;
+
+	#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+	#[native_model(id = 9, version = 1)]
+	#[native_db]
+	pub struct PreferencesModel {
+		#[primary_key]
+		pub username: String,
+		// Values are stored as JSON text, since the database encoding cannot describe arbitrary
+		// JSON values
+		pub values: BTreeMap<String, String>,
+	}
+}
+
+fn is_valid_key(key: &str) -> bool {
+	!key.is_empty()
+		&& key.len() <= MAX_KEY_LENGTH
+		&& key
+			.chars()
+			.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+impl Manager {
+	pub fn new(db: ndb::Manager) -> Self {
+		Self { db }
+	}
+
+	pub async fn get_preferences(&self, username: &str) -> Result<Preferences, Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			let username = username.to_owned();
+			move || {
+				let transaction = manager.db.r_transaction()?;
+				let model = transaction
+					.get()
+					.primary::<PreferencesModel>(username)?
+					.unwrap_or_default();
+				let preferences = model
+					.values
+					.into_iter()
+					.filter_map(|(k, v)| Some((k, serde_json::from_str(&v).ok()?)))
+					.collect();
+				Ok(preferences)
+			}
+		})
+		.await?
+	}
+
+	// Merges new values into existing preferences. Keys set to `null` are removed.
+	pub async fn update_preferences(
+		&self,
+		username: &str,
+		update: Preferences,
+	) -> Result<Preferences, Error> {
+		let mut changes = Vec::new();
+		for (key, value) in update {
+			if !is_valid_key(&key) {
+				return Err(Error::PreferenceKeyInvalid(key));
+			}
+			let value = match value {
+				serde_json::Value::Null => None,
+				v => Some(v.to_string()),
+			};
+			if value.as_ref().is_some_and(|v| v.len() > MAX_VALUE_LENGTH) {
+				return Err(Error::PreferencesTooLarge);
+			}
+			changes.push((key, value));
+		}
+
+		spawn_blocking({
+			let manager = self.clone();
+			let username = username.to_owned();
+			move || -> Result<(), Error> {
+				let transaction = manager.db.rw_transaction()?;
+				let existing = transaction
+					.get()
+					.primary::<PreferencesModel>(username.as_str())?;
+				let mut model = existing.clone().unwrap_or_else(|| PreferencesModel {
+					username,
+					values: BTreeMap::new(),
+				});
+				for (key, value) in changes {
+					match value {
+						Some(v) => model.values.insert(key, v),
+						None => model.values.remove(&key),
+					};
+				}
+				if model.values.len() > MAX_NUM_KEYS {
+					return Err(Error::PreferencesTooLarge);
+				}
+				match existing {
+					Some(existing) => transaction.update::<PreferencesModel>(existing, model)?,
+					None => transaction.insert::<PreferencesModel>(model)?,
+				}
+				transaction.commit()?;
+				Ok(())
+			}
+		})
+		.await??;
+
+		self.get_preferences(username).await
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use serde_json::json;
+
+	use super::*;
+	use crate::app::test;
+	use crate::test_name;
+
+	const TEST_USER: &str = "test_user";
+
+	#[tokio::test]
+	async fn can_update_preferences() {
+		let ctx = test::ContextBuilder::new(test_name!()).build().await;
+		let manager = ctx.preferences_manager;
+
+		assert!(manager.get_preferences(TEST_USER).await.unwrap().is_empty());
+
+		let update = Preferences::from([
+			("theme".to_owned(), json!("dark")),
+			("web.columns".to_owned(), json!(["title", "artist"])),
+		]);
+		manager.update_preferences(TEST_USER, update).await.unwrap();
+
+		let update = Preferences::from([
+			("theme".to_owned(), json!(null)),
+			("language".to_owned(), json!("fr")),
+		]);
+		let preferences = manager.update_preferences(TEST_USER, update).await.unwrap();
+		assert_eq!(
+			preferences,
+			Preferences::from([
+				("language".to_owned(), json!("fr")),
+				("web.columns".to_owned(), json!(["title", "artist"])),
+			])
+		);
+		assert!(manager
+			.get_preferences("other_user")
+			.await
+			.unwrap()
+			.is_empty());
+	}
+
+	#[tokio::test]
+	async fn rejects_invalid_preferences() {
+		let ctx = test::ContextBuilder::new(test_name!()).build().await;
+		let manager = ctx.preferences_manager;
+
+		let update = Preferences::from([("not a key!".to_owned(), json!(1))]);
+		assert!(matches!(
+			manager.update_preferences(TEST_USER, update).await,
+			Err(Error::PreferenceKeyInvalid(_))
+		));
+
+		let update = Preferences::from([("notes".to_owned(), json!("a".repeat(MAX_VALUE_LENGTH)))]);
+		assert!(matches!(
+			manager.update_preferences(TEST_USER, update).await,
+			Err(Error::PreferencesTooLarge)
+		));
+
+		let update = (0..=MAX_NUM_KEYS)
+			.map(|i| (format!("key{i}"), json!(i)))
+			.collect();
+		assert!(matches!(
+			manager.update_preferences(TEST_USER, update).await,
+			Err(Error::PreferencesTooLarge)
+		));
+		assert!(manager.get_preferences(TEST_USER).await.unwrap().is_empty());
+	}
+}
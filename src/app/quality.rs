@@ -0,0 +1,181 @@
+use time::OffsetDateTime;
+
+use crate::app::{index, Error};
+
+// Sound recording did not exist before this year
+const MIN_PLAUSIBLE_YEAR: i64 = 1877;
+
+// Songs with incomplete or unlikely tags, to help prioritize tagging work
+#[derive(Clone)]
+pub struct Manager {
+	index_manager: index::Manager,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Issue {
+	MissingTitle,
+	MissingArtwork,
+	MissingTrackNumber,
+	// Year before the invention of sound recording, or in the future
+	SuspiciousYear,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SongIssues {
+	pub song: index::Song,
+	pub issues: Vec<Issue>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Report {
+	pub missing_title_count: u32,
+	pub missing_artwork_count: u32,
+	pub missing_track_number_count: u32,
+	pub suspicious_year_count: u32,
+	// Songs with the most issues come first
+	pub songs: Vec<SongIssues>,
+}
+
+fn find_issues(song: &index::Song, current_year: i64) -> Vec<Issue> {
+	let mut issues = vec![];
+	let has_title = song.title.as_deref().is_some_and(|t| !t.trim().is_empty());
+	if !has_title {
+		issues.push(Issue::MissingTitle);
+	}
+	if song.artwork.is_none() {
+		issues.push(Issue::MissingArtwork);
+	}
+	if song.track_number.is_none() {
+		issues.push(Issue::MissingTrackNumber);
+	}
+	if song
+		.year
+		.is_some_and(|y| !(MIN_PLAUSIBLE_YEAR..=current_year).contains(&y))
+	{
+		issues.push(Issue::SuspiciousYear);
+	}
+	issues
+}
+
+fn compile(songs: Vec<index::Song>, current_year: i64) -> Report {
+	let mut report = Report::default();
+
+	for song in songs {
+		let issues = find_issues(&song, current_year);
+		if issues.is_empty() {
+			continue;
+		}
+		for issue in &issues {
+			match issue {
+				Issue::MissingTitle => report.missing_title_count += 1,
+				Issue::MissingArtwork => report.missing_artwork_count += 1,
+				Issue::MissingTrackNumber => report.missing_track_number_count += 1,
+				Issue::SuspiciousYear => report.suspicious_year_count += 1,
+			}
+		}
+		report.songs.push(SongIssues { song, issues });
+	}
+
+	report.songs.sort_by(|a, b| {
+		b.issues
+			.len()
+			.cmp(&a.issues.len())
+			.then_with(|| a.song.virtual_path.cmp(&b.song.virtual_path))
+	});
+
+	report
+}
+
+impl Manager {
+	pub fn new(index_manager: index::Manager) -> Self {
+		Self { index_manager }
+	}
+
+	pub async fn get_report(&self) -> Result<Report, Error> {
+		let songs = self.index_manager.get_all_songs().await;
+		let current_year = OffsetDateTime::now_utc().year() as i64;
+		Ok(tokio::task::spawn_blocking(move || compile(songs, current_year)).await?)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::path::PathBuf;
+
+	use super::*;
+
+	fn make_song(path: &str) -> index::Song {
+		index::Song {
+			virtual_path: PathBuf::from(path),
+			title: Some("Destiny".to_owned()),
+			artwork: Some(PathBuf::from("Folder.jpg")),
+			track_number: Some(1),
+			year: Some(1998),
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn well_tagged_songs_have_no_issues() {
+		let report = compile(vec![make_song("destiny.mp3")], 2024);
+		assert_eq!(report, Report::default());
+	}
+
+	#[test]
+	fn finds_songs_with_issues() {
+		let untitled = index::Song {
+			title: None,
+			track_number: None,
+			..make_song("untitled.mp3")
+		};
+		let no_artwork = index::Song {
+			artwork: None,
+			..make_song("no_artwork.mp3")
+		};
+		let ancient = index::Song {
+			year: Some(98),
+			..make_song("ancient.mp3")
+		};
+		let future = index::Song {
+			year: Some(2998),
+			..make_song("future.mp3")
+		};
+
+		let report = compile(
+			vec![
+				make_song("destiny.mp3"),
+				future.clone(),
+				no_artwork.clone(),
+				untitled.clone(),
+				ancient.clone(),
+			],
+			2024,
+		);
+
+		assert_eq!(report.missing_title_count, 1);
+		assert_eq!(report.missing_artwork_count, 1);
+		assert_eq!(report.missing_track_number_count, 1);
+		assert_eq!(report.suspicious_year_count, 2);
+		assert_eq!(
+			report.songs,
+			vec![
+				SongIssues {
+					song: untitled,
+					issues: vec![Issue::MissingTitle, Issue::MissingTrackNumber],
+				},
+				SongIssues {
+					song: ancient,
+					issues: vec![Issue::SuspiciousYear],
+				},
+				SongIssues {
+					song: future,
+					issues: vec![Issue::SuspiciousYear],
+				},
+				SongIssues {
+					song: no_artwork,
+					issues: vec![Issue::MissingArtwork],
+				},
+			]
+		);
+	}
+}
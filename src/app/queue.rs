@@ -0,0 +1,163 @@
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use native_db::*;
+use native_model::{native_model, Model};
+use serde::{Deserialize, Serialize};
+use tokio::task::spawn_blocking;
+
+use crate::app::{ndb, Error};
+
+// Play queues saved by clients, so users can resume listening on another device
+#[derive(Clone)]
+pub struct Manager {
+	db: ndb::Manager,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PlayQueue {
+	pub songs: Vec<PathBuf>,
+	/// Index of the current song within `songs`
+	pub position: usize,
+	/// Playback position within the current song, in milliseconds
+	pub offset: u64,
+	/// Unix timestamp of the last save, zero for queues that were never saved
+	pub updated_at: i64,
+}
+
+pub type PlayQueueModel = v1::PlayQueueModel;
+
+pub mod v1 {
+
+	use super::*;
+
+	#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+	#[native_model(id = 5, version = 1)]
+	#[native_db]
+	pub struct PlayQueueModel {
+		#[primary_key]
+		pub username: String,
+		pub virtual_paths: Vec<PathBuf>,
+		pub position: usize,
+		pub offset: u64,
+		pub updated_at: i64,
+	}
+}
+
+impl From<PlayQueueModel> for PlayQueue {
+	fn from(q: PlayQueueModel) -> Self {
+		Self {
+			songs: q.virtual_paths,
+			position: q.position,
+			offset: q.offset,
+			updated_at: q.updated_at,
+		}
+	}
+}
+
+impl Manager {
+	pub fn new(db: ndb::Manager) -> Self {
+		Self { db }
+	}
+
+	pub async fn get_play_queue(&self, username: &str) -> Result<PlayQueue, Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			let username = username.to_owned();
+			move || {
+				let transaction = manager.db.r_transaction()?;
+				let model = transaction.get().primary::<PlayQueueModel>(username)?;
+				Ok(model.map(PlayQueue::from).unwrap_or_default())
+			}
+		})
+		.await?
+	}
+
+	pub async fn save_play_queue(
+		&self,
+		username: &str,
+		songs: Vec<PathBuf>,
+		position: usize,
+		offset: u64,
+	) -> Result<PlayQueue, Error> {
+		if position >= songs.len().max(1) {
+			return Err(Error::PlayQueuePositionInvalid);
+		}
+
+		let updated_at = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map(|d| d.as_secs() as i64)
+			.unwrap_or_default();
+
+		let model = PlayQueueModel {
+			username: username.to_owned(),
+			virtual_paths: songs,
+			position,
+			offset,
+			updated_at,
+		};
+
+		spawn_blocking({
+			let manager = self.clone();
+			let model = model.clone();
+			move || -> Result<(), Error> {
+				let transaction = manager.db.rw_transaction()?;
+				transaction.upsert::<PlayQueueModel>(model)?;
+				transaction.commit()?;
+				Ok(())
+			}
+		})
+		.await??;
+
+		Ok(model.into())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::app::test;
+	use crate::test_name;
+
+	#[tokio::test]
+	async fn can_save_and_restore_play_queue() {
+		let ctx = test::ContextBuilder::new(test_name!()).build().await;
+		let queue_manager = ctx.queue_manager;
+
+		let empty = queue_manager.get_play_queue("walter").await.unwrap();
+		assert_eq!(empty, PlayQueue::default());
+
+		let songs = vec![PathBuf::from("root/a.mp3"), PathBuf::from("root/b.mp3")];
+		let saved = queue_manager
+			.save_play_queue("walter", songs.clone(), 1, 42_000)
+			.await
+			.unwrap();
+		assert!(saved.updated_at > 0);
+
+		let restored = queue_manager.get_play_queue("walter").await.unwrap();
+		assert_eq!(restored, saved);
+		assert_eq!(restored.songs, songs);
+		assert_eq!(restored.position, 1);
+		assert_eq!(restored.offset, 42_000);
+
+		let other_user = queue_manager.get_play_queue("jesse").await.unwrap();
+		assert!(other_user.songs.is_empty());
+	}
+
+	#[tokio::test]
+	async fn rejects_out_of_range_position() {
+		let ctx = test::ContextBuilder::new(test_name!()).build().await;
+		let songs = vec![PathBuf::from("root/a.mp3")];
+		assert!(matches!(
+			ctx.queue_manager
+				.save_play_queue("walter", songs, 1, 0)
+				.await,
+			Err(Error::PlayQueuePositionInvalid)
+		));
+		assert!(ctx
+			.queue_manager
+			.save_play_queue("walter", vec![], 0, 0)
+			.await
+			.is_ok());
+	}
+}
@@ -0,0 +1,332 @@
+use std::io::Read;
+use std::time::Duration;
+
+use icu_collator::{Collator, CollatorOptions, Strength};
+use log::error;
+use native_db::*;
+use native_model::{native_model, Model};
+use rand::distributions::{Alphanumeric, DistString};
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::task::spawn_blocking;
+
+use crate::app::{ndb, Error};
+
+const STREAM_CHUNK_SIZE: usize = 16 * 1024;
+
+#[derive(Clone)]
+pub struct Manager {
+	db: ndb::Manager,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Station {
+	pub id: String,
+	pub name: String,
+	pub stream_url: String,
+	pub homepage_url: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NewStation {
+	pub name: String,
+	pub stream_url: String,
+	pub homepage_url: Option<String>,
+}
+
+// Audio received from a radio station, relayed as it arrives
+pub struct Stream {
+	pub content_type: Option<String>,
+	pub chunks: mpsc::Receiver<std::io::Result<Vec<u8>>>,
+}
+
+pub type RadioStationModel = v1::RadioStationModel;
+
+pub mod v1 {
+
+	use super::*;
+
+	#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+	#[native_model(id = 3, version = 1)]
+	#[native_db]
+	pub struct RadioStationModel {
+		#[primary_key]
+		pub id: String,
+		pub name: String,
+		pub stream_url: String,
+		pub homepage_url: Option<String>,
+	}
+}
+
+impl From<RadioStationModel> for Station {
+	fn from(s: RadioStationModel) -> Self {
+		Self {
+			id: s.id,
+			name: s.name,
+			stream_url: s.stream_url,
+			homepage_url: s.homepage_url,
+		}
+	}
+}
+
+fn validate_url(url: &str) -> Result<String, Error> {
+	let url = url.trim();
+	match url.parse::<http::Uri>() {
+		Ok(uri) if matches!(uri.scheme_str(), Some("http" | "https")) && uri.host().is_some() => {
+			Ok(url.to_owned())
+		}
+		_ => Err(Error::RadioStationUrlInvalid),
+	}
+}
+
+impl NewStation {
+	fn validate(self) -> Result<Self, Error> {
+		let name = self.name.trim().to_owned();
+		if name.is_empty() {
+			return Err(Error::EmptyRadioStationName);
+		}
+		Ok(Self {
+			name,
+			stream_url: validate_url(&self.stream_url)?,
+			homepage_url: self
+				.homepage_url
+				.filter(|u| !u.trim().is_empty())
+				.map(|u| validate_url(&u))
+				.transpose()?,
+		})
+	}
+}
+
+impl Manager {
+	pub fn new(db: ndb::Manager) -> Self {
+		Self { db }
+	}
+
+	pub async fn list_stations(&self) -> Result<Vec<Station>, Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			move || {
+				let transaction = manager.db.r_transaction()?;
+				let mut stations = transaction
+					.scan()
+					.primary::<RadioStationModel>()?
+					.all()?
+					.filter_map(|s| s.ok())
+					.map(Station::from)
+					.collect::<Vec<_>>();
+
+				let collator_options = {
+					let mut o = CollatorOptions::new();
+					o.strength = Some(Strength::Secondary);
+					o
+				};
+				let collator = Collator::try_new(&Default::default(), collator_options).unwrap();
+
+				stations.sort_by(|a, b| collator.compare(&a.name, &b.name));
+				Ok(stations)
+			}
+		})
+		.await?
+	}
+
+	pub async fn get_station(&self, id: &str) -> Result<Station, Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			let id = id.to_owned();
+			move || {
+				let transaction = manager.db.r_transaction()?;
+				transaction
+					.get()
+					.primary::<RadioStationModel>(id)?
+					.map(Station::from)
+					.ok_or(Error::RadioStationNotFound)
+			}
+		})
+		.await?
+	}
+
+	pub async fn create_station(&self, station: NewStation) -> Result<Station, Error> {
+		let station = station.validate()?;
+		spawn_blocking({
+			let manager = self.clone();
+			move || {
+				let model = RadioStationModel {
+					id: Alphanumeric.sample_string(&mut OsRng, 16),
+					name: station.name,
+					stream_url: station.stream_url,
+					homepage_url: station.homepage_url,
+				};
+				let transaction = manager.db.rw_transaction()?;
+				transaction.insert::<RadioStationModel>(model.clone())?;
+				transaction.commit()?;
+				Ok(model.into())
+			}
+		})
+		.await?
+	}
+
+	pub async fn update_station(&self, id: &str, station: NewStation) -> Result<(), Error> {
+		let station = station.validate()?;
+		spawn_blocking({
+			let manager = self.clone();
+			let id = id.to_owned();
+			move || {
+				let transaction = manager.db.rw_transaction()?;
+				let old = transaction
+					.get()
+					.primary::<RadioStationModel>(id.as_str())?
+					.ok_or(Error::RadioStationNotFound)?;
+				transaction.update::<RadioStationModel>(
+					old,
+					RadioStationModel {
+						id,
+						name: station.name,
+						stream_url: station.stream_url,
+						homepage_url: station.homepage_url,
+					},
+				)?;
+				transaction.commit()?;
+				Ok(())
+			}
+		})
+		.await?
+	}
+
+	pub async fn delete_station(&self, id: &str) -> Result<(), Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			let id = id.to_owned();
+			move || {
+				let transaction = manager.db.rw_transaction()?;
+				let station = transaction
+					.get()
+					.primary::<RadioStationModel>(id)?
+					.ok_or(Error::RadioStationNotFound)?;
+				transaction.remove::<RadioStationModel>(station)?;
+				transaction.commit()?;
+				Ok(())
+			}
+		})
+		.await?
+	}
+
+	// Connects to a radio station and relays its audio until the returned stream is dropped.
+	// This lets clients play stations served over plain HTTP, or without CORS headers.
+	pub async fn open_stream(&self, id: &str) -> Result<Stream, Error> {
+		let station = self.get_station(id).await?;
+
+		let response = spawn_blocking(move || {
+			ureq::AgentBuilder::new()
+				.timeout_connect(Duration::from_secs(10))
+				.build()
+				.get(&station.stream_url)
+				.call()
+		})
+		.await?
+		.map_err(|e| {
+			error!("Could not connect to radio station: {e}");
+			Error::RadioStreamUnavailable
+		})?;
+
+		let content_type = response.header("Content-Type").map(str::to_owned);
+		let mut reader = response.into_reader();
+		let (sender, chunks) = mpsc::channel(16);
+		spawn_blocking(move || {
+			let mut buffer = vec![0; STREAM_CHUNK_SIZE];
+			loop {
+				let chunk = match reader.read(&mut buffer) {
+					Ok(0) => break,
+					Ok(n) => Ok(buffer[..n].to_vec()),
+					Err(e) => Err(e),
+				};
+				let failed = chunk.is_err();
+				if sender.blocking_send(chunk).is_err() || failed {
+					break;
+				}
+			}
+		});
+
+		Ok(Stream {
+			content_type,
+			chunks,
+		})
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::app::test;
+	use crate::test_name;
+
+	fn station(name: &str) -> NewStation {
+		NewStation {
+			name: name.to_owned(),
+			stream_url: "https://radio.example.com/stream.mp3".to_owned(),
+			homepage_url: Some("https://radio.example.com".to_owned()),
+		}
+	}
+
+	#[tokio::test]
+	async fn can_manage_stations() {
+		let ctx = test::ContextBuilder::new(test_name!()).build().await;
+		let radio = ctx.radio_manager;
+
+		let jazz = radio.create_station(station("jazz FM")).await.unwrap();
+		radio.create_station(station("Ambient")).await.unwrap();
+
+		let names =
+			|stations: Vec<Station>| stations.into_iter().map(|s| s.name).collect::<Vec<_>>();
+		assert_eq!(
+			names(radio.list_stations().await.unwrap()),
+			vec!["Ambient", "jazz FM"]
+		);
+
+		radio
+			.update_station(&jazz.id, station("Jazz FM"))
+			.await
+			.unwrap();
+		assert_eq!(radio.get_station(&jazz.id).await.unwrap().name, "Jazz FM");
+
+		radio.delete_station(&jazz.id).await.unwrap();
+		assert_eq!(names(radio.list_stations().await.unwrap()), vec!["Ambient"]);
+		assert!(matches!(
+			radio.get_station(&jazz.id).await,
+			Err(Error::RadioStationNotFound)
+		));
+	}
+
+	#[tokio::test]
+	async fn rejects_invalid_stations() {
+		let ctx = test::ContextBuilder::new(test_name!()).build().await;
+		let radio = ctx.radio_manager;
+
+		assert!(matches!(
+			radio.create_station(station("  ")).await,
+			Err(Error::EmptyRadioStationName)
+		));
+
+		for url in [
+			"",
+			"radio.example.com/stream",
+			"ftp://radio.example.com/stream",
+		] {
+			let new_station = NewStation {
+				stream_url: url.to_owned(),
+				..station("Jazz FM")
+			};
+			assert!(matches!(
+				radio.create_station(new_station).await,
+				Err(Error::RadioStationUrlInvalid)
+			));
+		}
+
+		let new_station = NewStation {
+			homepage_url: Some(String::new()),
+			..station("Jazz FM")
+		};
+		let created = radio.create_station(new_station).await.unwrap();
+		assert_eq!(created.homepage_url, None);
+	}
+}
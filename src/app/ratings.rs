@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use native_db::*;
+use native_model::{native_model, Model};
+use serde::{Deserialize, Serialize};
+use tokio::task::spawn_blocking;
+
+use crate::app::{ndb, Error};
+
+pub const MAX_RATING: u8 = 5;
+
+// Star ratings given by each user to songs and albums
+#[derive(Clone)]
+pub struct Manager {
+	db: ndb::Manager,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Item {
+	Song(PathBuf),
+	Album { name: String, artists: Vec<String> },
+}
+
+impl Item {
+	// Identifies the item among the ratings of all users
+	fn key(&self) -> String {
+		match self {
+			Item::Song(path) => format!("song:{}", path.to_string_lossy()),
+			Item::Album { name, artists } => format!("album:{}\u{c}{name}", artists.join("\u{c}")),
+		}
+	}
+}
+
+pub type RatingModel = v1::RatingModel;
+type RatingModelKey = v1::RatingModelKey;
+
+pub mod v1 {
+
+	use super::*;
+
+	#[derive(Clone, Debug, Serialize, Deserialize)]
+	#[native_model(id = 11, version = 1)]
+	#[native_db(primary_key(custom_id -> (&str, &str)))]
+	pub struct RatingModel {
+		#[secondary_key]
+		pub username: String,
+		#[secondary_key]
+		pub key: String,
+		pub item: Item,
+		pub rating: u8,
+	}
+
+	impl RatingModel {
+		fn custom_id(&self) -> (&str, &str) {
+			(&self.username, &self.key)
+		}
+	}
+}
+
+impl Manager {
+	pub fn new(db: ndb::Manager) -> Self {
+		Self { db }
+	}
+
+	pub async fn set_rating(&self, username: &str, item: Item, rating: u8) -> Result<(), Error> {
+		if rating > MAX_RATING {
+			return Err(Error::RatingInvalid);
+		}
+		spawn_blocking({
+			let manager = self.clone();
+			let username = username.to_owned();
+			move || {
+				let key = item.key();
+				let transaction = manager.db.rw_transaction()?;
+				let model = RatingModel {
+					username,
+					key,
+					item,
+					rating,
+				};
+				let existing = transaction
+					.get()
+					.primary::<RatingModel>((model.username.as_str(), model.key.as_str()))?;
+				match existing {
+					Some(existing) => transaction.update::<RatingModel>(existing, model)?,
+					None => transaction.insert::<RatingModel>(model)?,
+				}
+				transaction.commit()?;
+				Ok(())
+			}
+		})
+		.await?
+	}
+
+	// Clearing an item which is not rated does nothing
+	pub async fn clear_rating(&self, username: &str, item: Item) -> Result<(), Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			let username = username.to_owned();
+			move || {
+				let key = item.key();
+				let transaction = manager.db.rw_transaction()?;
+				if let Some(rating) = transaction
+					.get()
+					.primary::<RatingModel>((username.as_str(), key.as_str()))?
+				{
+					transaction.remove::<RatingModel>(rating)?;
+				}
+				transaction.commit()?;
+				Ok(())
+			}
+		})
+		.await?
+	}
+
+	pub async fn get_rating(&self, username: &str, item: Item) -> Result<Option<u8>, Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			let username = username.to_owned();
+			move || {
+				let key = item.key();
+				let transaction = manager.db.r_transaction()?;
+				let rating = transaction
+					.get()
+					.primary::<RatingModel>((username.as_str(), key.as_str()))?
+					.map(|r| r.rating);
+				Ok(rating)
+			}
+		})
+		.await?
+	}
+
+	// Average of the ratings given by all users
+	pub async fn get_average_rating(&self, item: Item) -> Result<Option<f64>, Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			move || {
+				let key = item.key();
+				let transaction = manager.db.r_transaction()?;
+				let ratings = transaction
+					.scan()
+					.secondary::<RatingModel>(RatingModelKey::key)?
+					.range(key.as_str()..=key.as_str())?
+					.filter_map(|r| r.ok())
+					.map(|r| f64::from(r.rating))
+					.collect::<Vec<_>>();
+				if ratings.is_empty() {
+					return Ok(None);
+				}
+				Ok(Some(ratings.iter().sum::<f64>() / ratings.len() as f64))
+			}
+		})
+		.await?
+	}
+
+	pub async fn get_song_ratings(&self, username: &str) -> Result<HashMap<PathBuf, u8>, Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			let username = username.to_owned();
+			move || {
+				let transaction = manager.db.r_transaction()?;
+				let ratings = transaction
+					.scan()
+					.secondary::<RatingModel>(RatingModelKey::username)?
+					.range(username.as_str()..=username.as_str())?
+					.filter_map(|r| r.ok())
+					.filter_map(|r| match r.item {
+						Item::Song(path) => Some((path, r.rating)),
+						Item::Album { .. } => None,
+					})
+					.collect();
+				Ok(ratings)
+			}
+		})
+		.await?
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::app::test;
+	use crate::test_name;
+
+	const TEST_USER: &str = "test_user";
+
+	#[tokio::test]
+	async fn can_rate_songs_and_albums() {
+		let ctx = test::ContextBuilder::new(test_name!()).build().await;
+		let ratings = ctx.ratings_manager;
+
+		let song = Item::Song(PathBuf::from("my_music/destiny.mp3"));
+		let album = Item::Album {
+			name: "Destiny".to_owned(),
+			artists: vec!["Stratovarius".to_owned()],
+		};
+
+		ratings
+			.set_rating(TEST_USER, song.clone(), 2)
+			.await
+			.unwrap();
+		ratings
+			.set_rating(TEST_USER, song.clone(), 4)
+			.await
+			.unwrap();
+		ratings
+			.set_rating(TEST_USER, album.clone(), 5)
+			.await
+			.unwrap();
+		ratings
+			.set_rating("other_user", album.clone(), 2)
+			.await
+			.unwrap();
+
+		assert_eq!(
+			ratings.get_song_ratings(TEST_USER).await.unwrap(),
+			HashMap::from([(PathBuf::from("my_music/destiny.mp3"), 4)])
+		);
+		assert_eq!(
+			ratings.get_rating(TEST_USER, album.clone()).await.unwrap(),
+			Some(5)
+		);
+		assert_eq!(
+			ratings.get_average_rating(album.clone()).await.unwrap(),
+			Some(3.5)
+		);
+
+		ratings
+			.clear_rating(TEST_USER, album.clone())
+			.await
+			.unwrap();
+		ratings
+			.clear_rating(TEST_USER, album.clone())
+			.await
+			.unwrap();
+		assert_eq!(
+			ratings.get_rating(TEST_USER, album.clone()).await.unwrap(),
+			None
+		);
+		assert_eq!(ratings.get_average_rating(album).await.unwrap(), Some(2.0));
+		assert_eq!(ratings.get_average_rating(song).await.unwrap(), Some(4.0));
+	}
+
+	#[tokio::test]
+	async fn rejects_out_of_range_ratings() {
+		let ctx = test::ContextBuilder::new(test_name!()).build().await;
+		let item = Item::Song(PathBuf::from("my_music/destiny.mp3"));
+		assert!(matches!(
+			ctx.ratings_manager
+				.set_rating(TEST_USER, item, MAX_RATING + 1)
+				.await,
+			Err(Error::RatingInvalid)
+		));
+	}
+}
@@ -0,0 +1,202 @@
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+use tokio::task::spawn_blocking;
+
+use crate::app::{config, Error};
+
+mod s3;
+mod smb;
+mod webdav;
+mod xml;
+
+const STREAM_CHUNK_SIZE: usize = 16 * 1024;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Request headers relayed to remote servers when streaming files, so they can serve partial and
+// conditional requests themselves
+pub const RELAYED_REQUEST_HEADERS: [&str; 4] =
+	["range", "if-range", "if-none-match", "if-modified-since"];
+
+// Response headers relayed to clients when streaming files from HTTP servers
+const RELAYED_RESPONSE_HEADERS: [&str; 6] = [
+	"accept-ranges",
+	"content-length",
+	"content-range",
+	"content-type",
+	"etag",
+	"last-modified",
+];
+
+static AGENT: LazyLock<ureq::Agent> = LazyLock::new(|| {
+	ureq::AgentBuilder::new()
+		.timeout_connect(CONNECT_TIMEOUT)
+		.timeout_read(READ_TIMEOUT)
+		.build()
+});
+
+// File stored on the server of a remote mount directory
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct File {
+	// Location relative to the mount source, with `/` separators
+	pub path: String,
+	pub size: u64,
+	// Seconds since the Unix epoch
+	pub modified: Option<i64>,
+	// Changes whenever the content of the file changes (eg. the ETag of an S3 object)
+	pub version: String,
+}
+
+// Reply of a remote server to a request for (part of) a file
+pub struct Response {
+	pub status: u16,
+	pub headers: Vec<(&'static str, String)>,
+	pub body: Box<dyn Read + Send>,
+}
+
+impl Response {
+	// Relays the body as it is received
+	pub fn into_chunks(self) -> mpsc::Receiver<std::io::Result<Vec<u8>>> {
+		let (sender, chunks) = mpsc::channel(16);
+		let mut body = self.body;
+		spawn_blocking(move || {
+			let mut buffer = vec![0; STREAM_CHUNK_SIZE];
+			loop {
+				let chunk = match body.read(&mut buffer) {
+					Ok(0) => break,
+					Ok(n) => Ok(buffer[..n].to_vec()),
+					Err(e) => Err(e),
+				};
+				let failed = chunk.is_err();
+				if sender.blocking_send(chunk).is_err() || failed {
+					break;
+				}
+			}
+		});
+		chunks
+	}
+}
+
+// Lists all files within a directory of a remote server, including those in its subdirectories
+pub fn list_files(remote: &config::Remote, source: &Path) -> Result<Vec<File>, Error> {
+	let prefix = match get_key(source) {
+		k if k.is_empty() => k,
+		k => k + "/",
+	};
+	match remote {
+		config::Remote::S3(bucket) => s3::list_files(bucket, &prefix),
+		config::Remote::Webdav(share) => webdav::list_files(share, &prefix),
+		config::Remote::Smb(share) => smb::list_files(share, &prefix),
+	}
+	.map_err(|e| Error::RemoteStorage(source.to_owned(), e))
+}
+
+// Copy of a remote file in the temporary directory, for code which can only read local files.
+// The copy is deleted when dropped.
+pub struct LocalCopy {
+	path: PathBuf,
+}
+
+impl LocalCopy {
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+}
+
+impl Drop for LocalCopy {
+	fn drop(&mut self) {
+		std::fs::remove_file(&self.path).ok();
+	}
+}
+
+pub fn download(remote: &config::Remote, real_path: &Path) -> Result<LocalCopy, Error> {
+	let mut body = read(remote, real_path)?;
+
+	// The extension is kept, as file formats are recognized by it
+	let mut name = format!("polaris-{:016x}", rand::random::<u64>());
+	if let Some(extension) = real_path.extension() {
+		name.push('.');
+		name.push_str(&extension.to_string_lossy());
+	}
+	let local_copy = LocalCopy {
+		path: std::env::temp_dir().join(name),
+	};
+
+	let mut file = std::fs::File::create(local_copy.path())
+		.map_err(|e| Error::Io(local_copy.path().to_owned(), e))?;
+	std::io::copy(&mut body, &mut file).map_err(|e| Error::Io(real_path.to_owned(), e))?;
+	Ok(local_copy)
+}
+
+// Requests a file, with headers from `RELAYED_REQUEST_HEADERS`
+pub fn open(
+	remote: &config::Remote,
+	real_path: &Path,
+	headers: &[(&str, &str)],
+) -> Result<Response, Error> {
+	let key = get_key(real_path);
+	match remote {
+		config::Remote::S3(bucket) => s3::get_object(bucket, &key, headers),
+		config::Remote::Webdav(share) => webdav::get_file(share, &key, headers),
+		config::Remote::Smb(share) => smb::get_file(share, &key, headers),
+	}
+	.map_err(|e| Error::RemoteStorage(real_path.to_owned(), e))
+}
+
+// Reads a whole file as it is received, eg. to feed it to ffmpeg without handing it credentials
+pub fn read(remote: &config::Remote, real_path: &Path) -> Result<Box<dyn Read + Send>, Error> {
+	Ok(open(remote, real_path, &[])?.body)
+}
+
+// Relays the response of an HTTP server to a file request
+fn relay(result: Result<ureq::Response, ureq::Error>) -> Result<Response, String> {
+	let response = match result {
+		Ok(r) => r,
+		// Unsatisfiable ranges and failed preconditions are for clients to handle
+		Err(ureq::Error::Status(412 | 416, r)) => r,
+		Err(e) => return Err(e.to_string()),
+	};
+	Ok(Response {
+		status: response.status(),
+		headers: RELAYED_RESPONSE_HEADERS
+			.iter()
+			.filter_map(|&name| Some((name, response.header(name)?.to_owned())))
+			.collect(),
+		body: Box::new(response.into_reader()),
+	})
+}
+
+fn get_key(real_path: &Path) -> String {
+	real_path
+		.components()
+		.filter_map(|c| match c {
+			Component::Normal(name) => Some(name.to_string_lossy()),
+			_ => None,
+		})
+		.collect::<Vec<_>>()
+		.join("/")
+}
+
+#[cfg(test)]
+mod test {
+	use std::path::PathBuf;
+
+	use super::*;
+
+	#[test]
+	fn keys_use_forward_slashes() {
+		let real_path: PathBuf = ["music", "Khemmis", "Hunted", "01 - Above The Water.mp3"]
+			.iter()
+			.collect();
+		assert_eq!(
+			get_key(&real_path),
+			"music/Khemmis/Hunted/01 - Above The Water.mp3"
+		);
+		assert_eq!(get_key(Path::new("/music/")), "music");
+		assert_eq!(get_key(Path::new("")), "");
+	}
+}
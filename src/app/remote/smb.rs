@@ -0,0 +1,300 @@
+#[cfg(not(feature = "smb"))]
+const UNSUPPORTED: &str = "Polaris was built without the `smb` feature";
+
+#[cfg(not(feature = "smb"))]
+pub fn list_files(
+	_share: &crate::app::config::SmbShare,
+	_prefix: &str,
+) -> Result<Vec<super::File>, String> {
+	Err(UNSUPPORTED.to_owned())
+}
+
+#[cfg(not(feature = "smb"))]
+pub fn get_file(
+	_share: &crate::app::config::SmbShare,
+	_key: &str,
+	_headers: &[(&str, &str)],
+) -> Result<super::Response, String> {
+	Err(UNSUPPORTED.to_owned())
+}
+
+#[cfg(feature = "smb")]
+pub use client::{get_file, list_files};
+
+#[cfg(feature = "smb")]
+mod client {
+	use std::io::{self, Read, Seek, SeekFrom};
+	use std::sync::mpsc;
+	use std::time::UNIX_EPOCH;
+
+	use pavao::{SmbClient, SmbCredentials, SmbDirentType, SmbOpenOptions, SmbOptions};
+
+	use crate::app::config::SmbShare;
+	use crate::app::remote::{File, Response};
+
+	const CHUNK_SIZE: usize = 64 * 1024;
+
+	// Status and headers of a response
+	type Head = (u16, Vec<(&'static str, String)>);
+
+	pub fn list_files(share: &SmbShare, prefix: &str) -> Result<Vec<File>, String> {
+		let client = connect(share)?;
+		let mut files = vec![];
+		let mut directories = vec![String::new()];
+		while let Some(directory) = directories.pop() {
+			let entries = client
+				.list_dir(format!("/{prefix}{directory}"))
+				.map_err(|e| e.to_string())?;
+			for entry in entries {
+				let name = entry.name();
+				if name == "." || name == ".." {
+					continue;
+				}
+				let path = format!("{directory}{name}");
+				match entry.get_type() {
+					SmbDirentType::Dir => directories.push(path + "/"),
+					SmbDirentType::File => {
+						let stat = client
+							.stat(format!("/{prefix}{path}"))
+							.map_err(|e| e.to_string())?;
+						let modified = stat
+							.modified
+							.duration_since(UNIX_EPOCH)
+							.ok()
+							.map(|d| d.as_secs() as i64);
+						files.push(File {
+							path,
+							size: stat.size,
+							modified,
+							version: format!("{}-{}", stat.size, modified.unwrap_or_default()),
+						});
+					}
+					_ => (),
+				}
+			}
+		}
+		Ok(files)
+	}
+
+	pub fn get_file(
+		share: &SmbShare,
+		key: &str,
+		headers: &[(&str, &str)],
+	) -> Result<Response, String> {
+		let get_header = |name: &str| {
+			headers
+				.iter()
+				.find(|(n, _)| n.eq_ignore_ascii_case(name))
+				.map(|(_, v)| v.to_string())
+		};
+		// Partial requests conditioned by `If-Range` get the whole file, as SMB servers have
+		// nothing to compare it to
+		let range = get_header("range").filter(|_| get_header("if-range").is_none());
+
+		// Open files borrow their client, so both live on a thread of their own
+		let (head_sender, head_receiver) = mpsc::channel();
+		let (chunk_sender, chunk_receiver) = mpsc::sync_channel(16);
+		let share = share.clone();
+		let path = format!("/{key}");
+		std::thread::Builder::new()
+			.name("smb".to_owned())
+			.spawn(move || read_file(&share, &path, range.as_deref(), head_sender, chunk_sender))
+			.map_err(|e| e.to_string())?;
+
+		let (status, headers) = head_receiver
+			.recv()
+			.map_err(|_| "SMB thread exited".to_owned())??;
+		Ok(Response {
+			status,
+			headers,
+			body: Box::new(ChunkReader {
+				chunks: chunk_receiver,
+				chunk: vec![],
+				position: 0,
+			}),
+		})
+	}
+
+	fn connect(share: &SmbShare) -> Result<SmbClient, String> {
+		let mut credentials = SmbCredentials::default()
+			.server(&share.server)
+			.share(&share.share);
+		if let Some(workgroup) = &share.workgroup {
+			credentials = credentials.workgroup(workgroup);
+		}
+		if let Some(username) = &share.username {
+			credentials = credentials.username(username);
+		}
+		if let Some(password) = &share.password {
+			credentials = credentials.password(password.read().map_err(|e| e.to_string())?);
+		}
+		SmbClient::new(credentials, SmbOptions::default()).map_err(|e| e.to_string())
+	}
+
+	fn read_file(
+		share: &SmbShare,
+		path: &str,
+		range: Option<&str>,
+		head: mpsc::Sender<Result<Head, String>>,
+		chunks: mpsc::SyncSender<io::Result<Vec<u8>>>,
+	) {
+		let client = match connect(share) {
+			Ok(c) => c,
+			Err(e) => {
+				head.send(Err(e)).ok();
+				return;
+			}
+		};
+		let opened = client.stat(path).and_then(|stat| {
+			let file = client.open_with(path, SmbOpenOptions::default().read(true))?;
+			Ok((stat.size, file))
+		});
+		let (size, mut file) = match opened {
+			Ok(o) => o,
+			Err(e) => {
+				head.send(Err(e.to_string())).ok();
+				return;
+			}
+		};
+
+		let (response_head, start, length) = get_head(range, size);
+		if let Err(e) = file.seek(SeekFrom::Start(start)) {
+			head.send(Err(e.to_string())).ok();
+			return;
+		}
+		if head.send(Ok(response_head)).is_err() {
+			return;
+		}
+
+		let mut body = file.take(length);
+		let mut buffer = vec![0; CHUNK_SIZE];
+		loop {
+			let chunk = match body.read(&mut buffer) {
+				Ok(0) => break,
+				Ok(n) => Ok(buffer[..n].to_vec()),
+				Err(e) => Err(e),
+			};
+			let failed = chunk.is_err();
+			if chunks.send(chunk).is_err() || failed {
+				break;
+			}
+		}
+	}
+
+	// Response to a request for a file of `size` bytes, and the span of the file it contains
+	fn get_head(range: Option<&str>, size: u64) -> (Head, u64, u64) {
+		let accept_ranges = ("accept-ranges", "bytes".to_owned());
+		match range.and_then(|r| parse_range(r, size)) {
+			None => (
+				(
+					200,
+					vec![accept_ranges, ("content-length", size.to_string())],
+				),
+				0,
+				size,
+			),
+			Some(None) => (
+				(416, vec![("content-range", format!("bytes */{size}"))]),
+				0,
+				0,
+			),
+			Some(Some((first, last))) => {
+				let length = last - first + 1;
+				let headers = vec![
+					accept_ranges,
+					("content-length", length.to_string()),
+					("content-range", format!("bytes {first}-{last}/{size}")),
+				];
+				((206, headers), first, length)
+			}
+		}
+	}
+
+	// First and last byte of a single byte range, or `Some(None)` when it cannot be satisfied.
+	// Malformed and multiple ranges are ignored.
+	fn parse_range(range: &str, size: u64) -> Option<Option<(u64, u64)>> {
+		let range = range.trim().strip_prefix("bytes=")?;
+		if range.contains(',') {
+			return None;
+		}
+		let (first, last) = range.split_once('-')?;
+		let bounds = match (first.trim(), last.trim()) {
+			("", suffix) => {
+				let suffix: u64 = suffix.parse().ok()?;
+				(suffix > 0 && size > 0).then(|| (size.saturating_sub(suffix), size - 1))
+			}
+			(first, "") => {
+				let first: u64 = first.parse().ok()?;
+				(first < size).then(|| (first, size - 1))
+			}
+			(first, last) => {
+				let first: u64 = first.parse().ok()?;
+				let last: u64 = last.parse().ok()?;
+				if last < first {
+					return None;
+				}
+				(first < size).then(|| (first, last.min(size - 1)))
+			}
+		};
+		Some(bounds)
+	}
+
+	// Reads the chunks sent by the thread holding a file
+	struct ChunkReader {
+		chunks: mpsc::Receiver<io::Result<Vec<u8>>>,
+		chunk: Vec<u8>,
+		position: usize,
+	}
+
+	impl Read for ChunkReader {
+		fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+			while self.position == self.chunk.len() {
+				match self.chunks.recv() {
+					Ok(chunk) => {
+						self.chunk = chunk?;
+						self.position = 0;
+					}
+					Err(_) => return Ok(0),
+				}
+			}
+			let length = buffer.len().min(self.chunk.len() - self.position);
+			buffer[..length].copy_from_slice(&self.chunk[self.position..self.position + length]);
+			self.position += length;
+			Ok(length)
+		}
+	}
+
+	#[cfg(test)]
+	mod test {
+		use super::*;
+
+		#[test]
+		fn can_parse_ranges() {
+			assert_eq!(parse_range("bytes=0-99", 1000), Some(Some((0, 99))));
+			assert_eq!(parse_range("bytes=900-", 1000), Some(Some((900, 999))));
+			assert_eq!(parse_range("bytes=-100", 1000), Some(Some((900, 999))));
+			assert_eq!(parse_range("bytes=500-5000", 1000), Some(Some((500, 999))));
+			assert_eq!(parse_range("bytes=1000-", 1000), Some(None));
+			assert_eq!(parse_range("bytes=-0", 1000), Some(None));
+			assert_eq!(parse_range("bytes=0-1,5-6", 1000), None);
+			assert_eq!(parse_range("bytes=9-1", 1000), None);
+			assert_eq!(parse_range("items=0-1", 1000), None);
+		}
+
+		#[test]
+		fn can_read_chunks() {
+			let (sender, chunks) = mpsc::sync_channel(4);
+			sender.send(Ok(b"Above ".to_vec())).unwrap();
+			sender.send(Ok(b"The Water".to_vec())).unwrap();
+			drop(sender);
+			let mut reader = ChunkReader {
+				chunks,
+				chunk: vec![],
+				position: 0,
+			};
+			let mut content = String::new();
+			reader.read_to_string(&mut content).unwrap();
+			assert_eq!(content, "Above The Water");
+		}
+	}
+}
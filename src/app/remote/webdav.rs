@@ -0,0 +1,197 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use headers::Header;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+use crate::app::config::WebdavShare;
+
+use super::xml::{get_element, get_elements, unescape};
+use super::{File, Response, AGENT};
+
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?><propfind xmlns="DAV:"><prop><resourcetype/><getcontentlength/><getlastmodified/><getetag/></prop></propfind>"#;
+
+// Everything but the unreserved characters of RFC 3986 is percent-encoded in URLs
+const URL_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+	.remove(b'-')
+	.remove(b'_')
+	.remove(b'.')
+	.remove(b'~');
+
+pub fn list_files(share: &WebdavShare, prefix: &str) -> Result<Vec<File>, String> {
+	let base_path = get_base_path(share);
+	let mut files = vec![];
+	let mut directories = vec![prefix.to_owned()];
+
+	// Directories are listed one level at a time, as many servers refuse `Depth: infinity`
+	while let Some(directory) = directories.pop() {
+		let body = AGENT
+			.request("PROPFIND", &get_file_url(share, &directory))
+			.set("depth", "1")
+			.set("content-type", "application/xml; charset=utf-8")
+			.authorize(share)?
+			.send_string(PROPFIND_BODY)
+			.map_err(|e| e.to_string())?
+			.into_string()
+			.map_err(|e| e.to_string())?;
+
+		for response in get_elements(&body, "response") {
+			let Some(href) = get_element(response, "href") else {
+				continue;
+			};
+			let href_path = get_href_path(href);
+			let Some(key) = href_path
+				.strip_prefix(&base_path)
+				.map(|k| k.trim_start_matches('/'))
+			else {
+				continue;
+			};
+
+			if get_element(response, "collection").is_some() {
+				// Listings include the listed directory itself
+				let key = key.trim_end_matches('/');
+				if key.starts_with(prefix) && key.len() > directory.trim_end_matches('/').len() {
+					directories.push(format!("{key}/"));
+				}
+				continue;
+			}
+
+			let Some(path) = key.strip_prefix(prefix) else {
+				continue;
+			};
+			let last_modified = get_element(response, "getlastmodified");
+			files.push(File {
+				path: path.to_owned(),
+				size: get_element(response, "getcontentlength")
+					.and_then(|s| s.trim().parse().ok())
+					.unwrap_or_default(),
+				modified: last_modified.and_then(parse_http_date),
+				version: get_element(response, "getetag")
+					.or(last_modified)
+					.map(unescape)
+					.unwrap_or_default(),
+			});
+		}
+	}
+
+	Ok(files)
+}
+
+pub fn get_file(
+	share: &WebdavShare,
+	key: &str,
+	headers: &[(&str, &str)],
+) -> Result<Response, String> {
+	let mut request = AGENT.get(&get_file_url(share, key)).authorize(share)?;
+	for (name, value) in headers {
+		request = request.set(name, value);
+	}
+	super::relay(request.call())
+}
+
+trait Authorize: Sized {
+	fn authorize(self, share: &WebdavShare) -> Result<Self, String>;
+}
+
+impl Authorize for ureq::Request {
+	fn authorize(self, share: &WebdavShare) -> Result<Self, String> {
+		let Some(username) = &share.username else {
+			return Ok(self);
+		};
+		let password = match &share.password {
+			Some(p) => p.read().map_err(|e| e.to_string())?,
+			None => String::new(),
+		};
+		let credentials = STANDARD.encode(format!("{username}:{password}"));
+		Ok(self.set("authorization", &format!("Basic {credentials}")))
+	}
+}
+
+fn get_file_url(share: &WebdavShare, key: &str) -> String {
+	let scheme = share.url.scheme_str().unwrap_or("https");
+	let authority = share
+		.url
+		.authority()
+		.map(|a| a.as_str())
+		.unwrap_or_default();
+	let path = share.url.path().trim_end_matches('/');
+	let key = key
+		.split('/')
+		.map(|s| utf8_percent_encode(s, URL_ENCODE_SET).to_string())
+		.collect::<Vec<_>>()
+		.join("/");
+	format!("{scheme}://{authority}{path}/{key}")
+}
+
+fn get_base_path(share: &WebdavShare) -> String {
+	let path = percent_decode_str(share.url.path()).decode_utf8_lossy();
+	path.trim_end_matches('/').to_owned()
+}
+
+// Decoded path of the `href` of a listed file, which servers may send as a full URL
+fn get_href_path(href: &str) -> String {
+	let href = unescape(href.trim());
+	let path = match href.split_once("://") {
+		Some((_, rest)) => rest.find('/').map(|i| &rest[i..]).unwrap_or("/"),
+		None => href.as_str(),
+	};
+	percent_decode_str(path).decode_utf8_lossy().into_owned()
+}
+
+fn parse_http_date(text: &str) -> Option<i64> {
+	let value = http::HeaderValue::from_str(text.trim()).ok()?;
+	let last_modified = headers::LastModified::decode(&mut std::iter::once(&value)).ok()?;
+	let seconds = SystemTime::from(last_modified)
+		.duration_since(UNIX_EPOCH)
+		.ok()?
+		.as_secs();
+	Some(seconds as i64)
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn example_share() -> WebdavShare {
+		WebdavShare {
+			url: http::Uri::from_static("https://cloud.example.com/remote.php/dav/files/alice/"),
+			username: Some("alice".to_owned()),
+			password: Some(crate::app::config::Secret::Value("p@ss word".to_owned())),
+		}
+	}
+
+	#[test]
+	fn can_build_file_urls() {
+		let share = example_share();
+		assert_eq!(
+			get_file_url(&share, "Khemmis/Hunted/01 - Above The Water.mp3"),
+			"https://cloud.example.com/remote.php/dav/files/alice/Khemmis/Hunted/01%20-%20Above%20The%20Water.mp3"
+		);
+		assert_eq!(
+			get_file_url(&share, "AC&DC/"),
+			"https://cloud.example.com/remote.php/dav/files/alice/AC%26DC/"
+		);
+	}
+
+	#[test]
+	fn can_read_href_paths() {
+		assert_eq!(
+			get_href_path("/remote.php/dav/files/alice/AC%26DC/Back%20in%20Black.mp3"),
+			"/remote.php/dav/files/alice/AC&DC/Back in Black.mp3"
+		);
+		assert_eq!(
+			get_href_path("https://cloud.example.com/dav/a&amp;b/"),
+			"/dav/a&b/"
+		);
+	}
+
+	#[test]
+	fn can_read_last_modified_dates() {
+		assert_eq!(
+			parse_http_date("Fri, 24 May 2013 00:00:00 GMT"),
+			Some(1369353600)
+		);
+		assert_eq!(parse_http_date("yesterday"), None);
+	}
+}
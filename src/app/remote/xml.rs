@@ -0,0 +1,66 @@
+// Minimal reading of the XML documents returned by remote servers. Namespace prefixes are ignored,
+// so `<D:href>` and `<href>` are both found as `href`. Elements must not contain other elements
+// with the same name.
+
+// Text content of the first `<name>` element
+pub fn get_element<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+	get_elements(xml, name).next()
+}
+
+// Contents of the `<name>` elements, in document order. Empty elements like `<name/>` have no
+// content.
+pub fn get_elements<'a>(xml: &'a str, name: &str) -> impl Iterator<Item = &'a str> {
+	let name = name.to_owned();
+	let mut rest = xml;
+	std::iter::from_fn(move || loop {
+		let start = rest.find('<')?;
+		let end = start + rest[start..].find('>')?;
+		let tag = &rest[start + 1..end];
+		rest = &rest[end + 1..];
+
+		if tag.starts_with(['/', '?', '!']) {
+			continue;
+		}
+		let qualified_name = tag
+			.split(|c: char| c.is_whitespace() || c == '/')
+			.next()
+			.unwrap_or_default();
+		if qualified_name.rsplit(':').next() != Some(name.as_str()) {
+			continue;
+		}
+		if tag.ends_with('/') {
+			return Some("");
+		}
+
+		let close = format!("</{qualified_name}>");
+		let length = rest.find(&close)?;
+		let content = &rest[..length];
+		rest = &rest[length + close.len()..];
+		return Some(content);
+	})
+}
+
+pub fn unescape(text: &str) -> String {
+	text.replace("&quot;", "\"")
+		.replace("&apos;", "'")
+		.replace("&lt;", "<")
+		.replace("&gt;", ">")
+		.replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn ignores_namespace_prefixes() {
+		let xml = r#"<?xml version="1.0"?><d:multistatus xmlns:d="DAV:"><d:response><d:href>/a</d:href><d:resourcetype><d:collection/></d:resourcetype></d:response><response><href>/b</href><resourcetype /></response></d:multistatus>"#;
+		let responses = get_elements(xml, "response").collect::<Vec<_>>();
+		assert_eq!(responses.len(), 2);
+		assert_eq!(get_element(responses[0], "href"), Some("/a"));
+		assert_eq!(get_element(responses[0], "collection"), Some(""));
+		assert_eq!(get_element(responses[1], "href"), Some("/b"));
+		assert_eq!(get_element(responses[1], "collection"), None);
+		assert_eq!(get_element(xml, "resource"), None);
+	}
+}
@@ -1,45 +1,74 @@
-use log::{error, info};
+use log::{error, info, warn};
 use notify::{RecommendedWatcher, Watcher};
 use notify_debouncer_full::{Debouncer, FileIdMap};
 use rayon::{Scope, ThreadPoolBuilder};
 use regex::Regex;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
 use std::fs;
+use std::hash::Hasher;
+use std::io::{Read, Seek, SeekFrom};
+use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::mpsc::{channel, Sender, TryRecvError};
-use std::sync::Arc;
-use std::time::SystemTime;
+use std::sync::{Arc, LazyLock};
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::{cmp::min, time::Duration};
+use time::OffsetDateTime;
 use tokio::sync::mpsc::unbounded_channel;
 use tokio::sync::{Notify, RwLock};
-use tokio::task::JoinSet;
+use tokio::task::{spawn_blocking, JoinSet};
 use tokio::time::Instant;
 
-use crate::app::{config, formats, index, Error};
+use crate::app::{config, formats, hooks, index, lyrics, musicbrainz, remote, silence, Error};
+use crate::utils;
+
+mod schedule;
+
+pub use schedule::Schedule;
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct Directory {
 	pub virtual_path: PathBuf,
 }
 
-#[derive(Debug, Default, PartialEq, Eq)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct Song {
 	pub real_path: PathBuf,
 	pub virtual_path: PathBuf,
 	pub track_number: Option<i64>,
 	pub disc_number: Option<i64>,
+	pub disc_subtitle: Option<String>,
 	pub title: Option<String>,
 	pub artists: Vec<String>,
 	pub album_artists: Vec<String>,
 	pub year: Option<i64>,
 	pub album: Option<String>,
 	pub artwork: Option<PathBuf>,
+	// Song itself for embedded lyrics, or its `.lrc` file
+	pub lyrics: Option<PathBuf>,
 	pub duration: Option<i64>,
 	pub lyricists: Vec<String>,
 	pub composers: Vec<String>,
 	pub genres: Vec<String>,
 	pub labels: Vec<String>,
+	pub compilation: bool,
+	pub conductors: Vec<String>,
+	pub performers: Vec<String>,
+	pub work: Option<String>,
+	pub movement: Option<String>,
+	pub bpm: Option<i64>,
+	pub initial_key: Option<String>,
+	pub replay_gain: formats::ReplayGain,
+	pub musicbrainz_recording_id: Option<String>,
+	pub musicbrainz_release_id: Option<String>,
+	pub musicbrainz_artist_ids: Vec<String>,
 	pub date_added: i64,
+	pub audible_range: Option<silence::AudibleRange>,
+	pub fingerprint: Option<u64>,
+	// Part of the file holding this song, for songs split from a CUE sheet
+	pub segment: Option<formats::Segment>,
 }
 
 #[derive(Clone, Default)]
@@ -48,13 +77,70 @@ pub enum State {
 	Initial,
 	Pending,
 	InProgress,
+	Paused,
+	Cancelled,
 	UpToDate,
 }
 
+// Steps of a scan in progress
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+	// Reading the content of mount directories
+	Scanning,
+	// Writing the new index to disk
+	Saving,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum ControlState {
+	#[default]
+	Running,
+	Paused,
+	Cancelled,
+}
+
+// Lets a scan in progress be paused, resumed or cancelled from another thread.
+// Paused scans keep their position in the collection, and pick up from there when resumed.
+#[derive(Default)]
+struct ScanControl {
+	state: std::sync::Mutex<ControlState>,
+	on_change: std::sync::Condvar,
+}
+
+impl ScanControl {
+	fn set(&self, state: ControlState) {
+		*self.state.lock().unwrap() = state;
+		self.on_change.notify_all();
+	}
+
+	fn is_cancelled(&self) -> bool {
+		*self.state.lock().unwrap() == ControlState::Cancelled
+	}
+
+	// Blocks while the scan is paused. Returns false if the scan should stop.
+	fn proceed(&self) -> bool {
+		let state = self.state.lock().unwrap();
+		let state = self
+			.on_change
+			.wait_while(state, |s| *s == ControlState::Paused)
+			.unwrap();
+		*state == ControlState::Running
+	}
+}
+
 #[derive(Clone)]
 struct Parameters {
 	artwork_regex: Option<Regex>,
 	mount_dirs: Vec<config::MountDir>,
+	detect_silence: bool,
+	num_threads: Option<NonZeroUsize>,
+	change_detection: config::ChangeDetection,
+	filename_pattern: Option<formats::FilenamePattern>,
+	artist_aliases: config::AliasTable,
+	album_aliases: config::AliasTable,
+	genre_aliases: config::AliasTable,
+	artist_separators: Option<Regex>,
+	musicbrainz_enrichment: bool,
 }
 
 impl PartialEq for Parameters {
@@ -62,41 +148,175 @@ impl PartialEq for Parameters {
 		self.artwork_regex.as_ref().map(|r| r.as_str())
 			== other.artwork_regex.as_ref().map(|r| r.as_str())
 			&& self.mount_dirs == other.mount_dirs
+			&& self.detect_silence == other.detect_silence
+			// Scanning with more or fewer threads yields the same index
+			&& self.change_detection == other.change_detection
+			&& self.filename_pattern == other.filename_pattern
+			&& self.artist_aliases == other.artist_aliases
+			&& self.album_aliases == other.album_aliases
+			&& self.genre_aliases == other.genre_aliases
+			&& self.artist_separators.as_ref().map(|r| r.as_str())
+				== other.artist_separators.as_ref().map(|r| r.as_str())
+			// Songs enriched with MusicBrainz details must be read again when enrichment is disabled
+			&& self.musicbrainz_enrichment == other.musicbrainz_enrichment
+	}
+}
+
+// What woke up the scanner
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Change {
+	Config,
+	Files,
+}
+
+// Maximum number of errors kept in the scan status. Further errors are only counted.
+const MAX_REPORTED_ERRORS: usize = 1000;
+
+// Album artist given to compilations whose songs do not have one
+const VARIOUS_ARTISTS: &str = "Various Artists";
+
+// A file or directory which could not be read during a scan
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScanError {
+	pub virtual_path: PathBuf,
+	pub message: String,
+}
+
+#[derive(Clone, Debug, Default)]
+struct ErrorLog {
+	num_errors: u32,
+	errors: Vec<ScanError>,
+}
+
+impl ErrorLog {
+	fn push<P: AsRef<Path>>(&mut self, virtual_path: P, message: String) {
+		self.num_errors += 1;
+		if self.errors.len() < MAX_REPORTED_ERRORS {
+			self.errors.push(ScanError {
+				virtual_path: virtual_path.as_ref().to_owned(),
+				message,
+			});
+		}
+	}
+}
+
+// Counts reported by the index task while a scan is in progress
+#[derive(Clone, Copy, Debug, Default)]
+struct Progress {
+	num_songs_indexed: u32,
+	num_directories_scanned: u32,
+	num_songs_added: u32,
+	num_songs_updated: u32,
+	num_songs_removed: u32,
+}
+
+// Modification time and content of a directory, as of the last scan which read it
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct DirectorySnapshot {
+	modified: SystemTime,
+	subdirectories: Vec<OsString>,
+	songs: Vec<PathBuf>,
+}
+
+// What traversal threads know about earlier scans, and what they learn during the current one
+#[derive(Default)]
+struct ScanHistory {
+	previous_songs: HashMap<PathBuf, Song>,
+	previous_directories: HashMap<PathBuf, DirectorySnapshot>,
+	directories: std::sync::Mutex<HashMap<PathBuf, DirectorySnapshot>>,
+	error_log: Arc<std::sync::Mutex<ErrorLog>>,
+}
+
+impl ScanHistory {
+	fn report_error<P: AsRef<Path>>(&self, virtual_path: P, message: String) {
+		self.error_log.lock().unwrap().push(virtual_path, message);
 	}
 }
 
+// Everything traversal threads share while reading a collection
+#[derive(Clone)]
+struct Traversal {
+	directories_output: Sender<Directory>,
+	songs_output: Sender<Song>,
+	parameters: Arc<Parameters>,
+	control: Arc<ScanControl>,
+	history: Arc<ScanHistory>,
+}
+
 #[derive(Clone, Default)]
 pub struct Status {
 	pub state: State,
+	pub phase: Option<Phase>,
 	pub last_start_time: Option<SystemTime>,
 	pub last_end_time: Option<SystemTime>,
 	pub num_songs_indexed: u32,
+	pub num_directories_scanned: u32,
+	// Compared to the index before the scan
+	pub num_songs_added: u32,
+	pub num_songs_updated: u32,
+	pub num_songs_removed: u32,
+	// Including errors beyond the ones listed in `errors`
+	pub num_errors: u32,
+	pub errors: Vec<ScanError>,
+}
+
+impl Status {
+	// Time spent on the scan in progress, or on the last completed scan
+	pub fn elapsed(&self) -> Option<Duration> {
+		let start = self.last_start_time?;
+		match (&self.state, self.last_end_time) {
+			(State::InProgress | State::Paused, _) => start.elapsed().ok(),
+			(_, Some(end)) => end.duration_since(start).ok(),
+			_ => None,
+		}
+	}
 }
 
 #[derive(Clone)]
 pub struct Scanner {
 	index_manager: index::Manager,
 	config_manager: config::Manager,
+	hooks_manager: hooks::Manager,
+	musicbrainz_manager: musicbrainz::Manager,
 	file_watcher: Arc<RwLock<Option<Debouncer<RecommendedWatcher, FileIdMap>>>>,
 	on_file_change: Arc<Notify>,
+	changed_paths: Arc<std::sync::Mutex<HashSet<PathBuf>>>,
 	pending_scan: Arc<Notify>,
 	status: Arc<RwLock<Status>>,
 	parameters: Arc<RwLock<Option<Parameters>>>,
+	indexed_parameters: Arc<RwLock<Option<Parameters>>>,
+	indexed_directories: Arc<RwLock<HashMap<PathBuf, DirectorySnapshot>>>,
+	error_log: Arc<std::sync::Mutex<ErrorLog>>,
+	control: Arc<std::sync::Mutex<Arc<ScanControl>>>,
+	// Held by full scans and partial updates, so they never replace each other's index
+	update_lock: Arc<tokio::sync::Mutex<()>>,
+	on_pause: Arc<Notify>,
 }
 
 impl Scanner {
 	pub async fn new(
 		index_manager: index::Manager,
 		config_manager: config::Manager,
+		hooks_manager: hooks::Manager,
+		musicbrainz_manager: musicbrainz::Manager,
 	) -> Result<Self, Error> {
 		let scanner = Self {
 			index_manager,
 			config_manager: config_manager.clone(),
+			hooks_manager,
+			musicbrainz_manager,
 			file_watcher: Arc::default(),
 			on_file_change: Arc::default(),
+			changed_paths: Arc::default(),
 			pending_scan: Arc::new(Notify::new()),
 			status: Arc::new(RwLock::new(Status::default())),
 			parameters: Arc::default(),
+			indexed_parameters: Arc::default(),
+			indexed_directories: Arc::default(),
+			error_log: Arc::default(),
+			control: Arc::default(),
+			update_lock: Arc::default(),
+			on_pause: Arc::default(),
 		};
 
 		let abort_scan = Arc::new(Notify::new());
@@ -106,14 +326,64 @@ impl Scanner {
 			let abort_scan = abort_scan.clone();
 			async move {
 				loop {
-					scanner.wait_for_change().await;
-					abort_scan.notify_waiters();
-					scanner.status.write().await.state = State::Pending;
-					while tokio::time::timeout(Duration::from_secs(2), scanner.wait_for_change())
-						.await
-						.is_ok()
-					{}
-					scanner.pending_scan.notify_waiters();
+					let change = scanner.wait_for_change().await;
+
+					// Changes to files are applied directly to an up-to-date index
+					let mut full_scan = change == Change::Config
+						|| !matches!(scanner.status.read().await.state, State::UpToDate);
+					if full_scan {
+						abort_scan.notify_waiters();
+						scanner.status.write().await.state = State::Pending;
+					}
+
+					while let Ok(change) =
+						tokio::time::timeout(Duration::from_secs(2), scanner.wait_for_change())
+							.await
+					{
+						if change == Change::Config && !full_scan {
+							full_scan = true;
+							abort_scan.notify_waiters();
+							scanner.status.write().await.state = State::Pending;
+						}
+					}
+
+					if !full_scan {
+						if let Err(e) = scanner.apply_file_changes().await {
+							error!("Error while applying file changes, rescanning collection: {e}");
+							// Scans paused in the meantime would otherwise miss these changes
+							abort_scan.notify_waiters();
+							scanner.status.write().await.state = State::Pending;
+							full_scan = true;
+						}
+					}
+
+					if full_scan {
+						scanner.pending_scan.notify_waiters();
+					}
+				}
+			}
+		});
+
+		tokio::spawn({
+			let scanner = scanner.clone();
+			async move {
+				loop {
+					let schedule = scanner.config_manager.get_scan_schedule().await;
+					let next_scan = schedule.and_then(|s| s.next_after(OffsetDateTime::now_utc()));
+					let Some(next_scan) = next_scan else {
+						scanner.config_manager.on_config_change().await;
+						continue;
+					};
+					let delay: Duration = (next_scan - OffsetDateTime::now_utc())
+						.try_into()
+						.unwrap_or_default();
+					tokio::select! {
+						_ = tokio::time::sleep(delay) => {
+							info!("Beginning scheduled collection scan");
+							scanner.queue_scan();
+						}
+						_ = scanner.config_manager.on_config_change() => {}
+					}
 				}
 			}
 		});
@@ -143,14 +413,25 @@ impl Scanner {
 	async fn setup_file_watcher(
 		config_manager: &config::Manager,
 		on_file_changed: Arc<Notify>,
+		changed_paths: Arc<std::sync::Mutex<HashSet<PathBuf>>>,
 	) -> Result<Debouncer<RecommendedWatcher, FileIdMap>, Error> {
-		let mut debouncer =
-			notify_debouncer_full::new_debouncer(Duration::from_millis(100), None, move |_| {
+		let mut debouncer = notify_debouncer_full::new_debouncer(
+			Duration::from_millis(100),
+			None,
+			move |result: notify_debouncer_full::DebounceEventResult| {
+				if let Ok(events) = result {
+					let mut changed_paths = changed_paths.lock().unwrap();
+					for event in events {
+						changed_paths.extend(event.event.paths);
+					}
+				}
 				on_file_changed.notify_waiters();
-			})?;
+			},
+		)?;
 
 		let mount_dirs = config_manager.get_mounts().await;
-		for mount_dir in &mount_dirs {
+		// Changes on remote servers are only picked up by scans
+		for mount_dir in mount_dirs.iter().filter(|m| m.remote.is_none()) {
 			if let Err(e) = debouncer
 				.watcher()
 				.watch(&mount_dir.source, notify::RecursiveMode::Recursive)
@@ -162,7 +443,7 @@ impl Scanner {
 		Ok(debouncer)
 	}
 
-	async fn wait_for_change(&self) {
+	async fn wait_for_change(&self) -> Change {
 		tokio::select! {
 			_ = async {
 				loop {
@@ -172,8 +453,21 @@ impl Scanner {
 					}
 					break;
 				}
-			} => {},
-			_ = self.on_file_change.notified() => {},
+			} => Change::Config,
+			_ = self.on_file_change.notified() => Change::Files,
+		}
+	}
+
+	// Re-scans the directories containing files reported by the file watcher
+	async fn apply_file_changes(&self) -> Result<(), Error> {
+		loop {
+			let changed_paths = std::mem::take(&mut *self.changed_paths.lock().unwrap());
+			if changed_paths.is_empty() {
+				return Ok(());
+			}
+			let mount_dirs = self.config_manager.get_mounts().await;
+			let virtual_paths = get_directories_to_refresh(&mount_dirs, changed_paths);
+			self.update_directories(virtual_paths).await?;
 		}
 	}
 
@@ -183,11 +477,28 @@ impl Scanner {
 		Parameters {
 			artwork_regex,
 			mount_dirs: self.config_manager.get_mounts().await,
+			detect_silence: self.config_manager.get_detect_silence().await,
+			num_threads: self.config_manager.get_scan_threads().await,
+			change_detection: self.config_manager.get_change_detection().await,
+			filename_pattern: self.config_manager.get_filename_pattern().await,
+			artist_aliases: config::AliasTable::new(
+				&self.config_manager.get_artist_aliases().await,
+			),
+			album_aliases: config::AliasTable::new(&self.config_manager.get_album_aliases().await),
+			genre_aliases: config::AliasTable::new(&self.config_manager.get_genre_aliases().await),
+			artist_separators: build_artist_separators(
+				&self.config_manager.get_artist_separators().await,
+			),
+			musicbrainz_enrichment: self.config_manager.get_musicbrainz_enrichment().await,
 		}
 	}
 
 	pub async fn get_status(&self) -> Status {
-		self.status.read().await.clone()
+		let mut status = self.status.read().await.clone();
+		let error_log = self.error_log.lock().unwrap().clone();
+		status.num_errors = error_log.num_errors;
+		status.errors = error_log.errors;
+		status
 	}
 
 	pub fn queue_scan(&self) {
@@ -198,16 +509,68 @@ impl Scanner {
 		self.pending_scan.notify_waiters();
 	}
 
+	pub async fn pause_scan(&self) -> Result<(), Error> {
+		let mut status = self.status.write().await;
+		if !matches!(status.state, State::InProgress) {
+			return Err(Error::NoScanInProgress);
+		}
+		self.control.lock().unwrap().set(ControlState::Paused);
+		status.state = State::Paused;
+		self.on_pause.notify_waiters();
+		info!("Paused collection scan");
+		Ok(())
+	}
+
+	pub async fn resume_scan(&self) -> Result<(), Error> {
+		let mut status = self.status.write().await;
+		if !matches!(status.state, State::Paused) {
+			return Err(Error::NoScanInProgress);
+		}
+		self.control.lock().unwrap().set(ControlState::Running);
+		status.state = State::InProgress;
+		info!("Resumed collection scan");
+		Ok(())
+	}
+
+	// The index from the previous scan remains in use after cancelling a scan
+	pub async fn cancel_scan(&self) -> Result<(), Error> {
+		let mut status = self.status.write().await;
+		if !matches!(status.state, State::InProgress | State::Paused) {
+			return Err(Error::NoScanInProgress);
+		}
+		self.control.lock().unwrap().set(ControlState::Cancelled);
+		status.state = State::Cancelled;
+		status.phase = None;
+		info!("Cancelled collection scan");
+		Ok(())
+	}
+
 	pub async fn run_scan(&self) -> Result<(), Error> {
+		let _update = self.update_lock.lock().await;
 		info!("Beginning collection scan");
 
 		let start = Instant::now();
+		let control = {
+			// A full scan picks up these changes anyway
+			self.changed_paths.lock().unwrap().clear();
+			let mut control = self.control.lock().unwrap();
+			// Stops traversal threads left behind by an interrupted scan
+			control.set(ControlState::Cancelled);
+			*control = Arc::default();
+			control.clone()
+		};
 		{
 			let mut status = self.status.write().await;
 			status.last_start_time = Some(SystemTime::now());
 			status.state = State::InProgress;
+			status.phase = Some(Phase::Scanning);
 			status.num_songs_indexed = 0;
+			status.num_directories_scanned = 0;
+			status.num_songs_added = 0;
+			status.num_songs_updated = 0;
+			status.num_songs_removed = 0;
 		}
+		*self.error_log.lock().unwrap() = ErrorLog::default();
 
 		let was_empty = self.index_manager.is_index_empty().await;
 		let mut partial_update_time = Instant::now();
@@ -215,9 +578,45 @@ impl Scanner {
 		let new_parameters = self.read_parameters().await;
 		*self.parameters.write().await = Some(new_parameters.clone());
 
+		let indexed_songs = self.index_manager.get_all_songs().await;
+		let previous_fingerprints = indexed_songs
+			.iter()
+			.map(|s| (s.virtual_path.clone(), s.fingerprint))
+			.collect::<HashMap<_, _>>();
+		let previous_dates_added = indexed_songs
+			.iter()
+			.map(|s| (s.virtual_path.clone(), s.date_added))
+			.collect::<HashMap<_, _>>();
+		let scan_time = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map(|d| d.as_secs() as i64)
+			.unwrap_or_default();
+
+		// Songs in the current index can be reused if they were read with the same settings
+		let (previous_songs, previous_directories) =
+			if self.indexed_parameters.read().await.as_ref() == Some(&new_parameters) {
+				let songs = indexed_songs
+					.into_iter()
+					.map(|s| (s.real_path.clone(), s.into()))
+					.collect();
+				(songs, self.indexed_directories.read().await.clone())
+			} else {
+				(HashMap::new(), HashMap::new())
+			};
+
+		let releases = self.get_musicbrainz_releases(&new_parameters).await?;
+
 		let (scan_directories_output, collection_directories_input) = channel();
 		let (scan_songs_output, collection_songs_input) = channel();
-		let scan = Scan::new(scan_directories_output, scan_songs_output, new_parameters);
+		let scan = Scan::new(
+			scan_directories_output,
+			scan_songs_output,
+			new_parameters.clone(),
+		)
+		.with_control(control.clone())
+		.with_previous_songs(previous_songs)
+		.with_previous_directories(previous_directories)
+		.with_error_log(self.error_log.clone());
 
 		let mut scan_task_set = JoinSet::new();
 		let mut index_task_set = JoinSet::new();
@@ -233,8 +632,12 @@ impl Scanner {
 				let mut watcher = scanner.file_watcher.write().await;
 				*watcher = None; // Drops previous watcher
 				*watcher = Some(
-					Self::setup_file_watcher(&config_manager, scanner.on_file_change.clone())
-						.await?,
+					Self::setup_file_watcher(
+						&config_manager,
+						scanner.on_file_change.clone(),
+						scanner.changed_paths.clone(),
+					)
+					.await?,
 				);
 				Ok(())
 			}
@@ -261,25 +664,49 @@ impl Scanner {
 		});
 
 		let (status_sender, mut status_receiver) = unbounded_channel();
-		secondary_task_set.spawn({
+		let status_task = tokio::spawn({
 			let manager = self.clone();
 			async move {
-				while let Some(n) = status_receiver.recv().await {
-					manager.status.write().await.num_songs_indexed = n;
+				while let Some(progress) = status_receiver.recv().await {
+					let progress: Progress = progress;
+					let mut status = manager.status.write().await;
+					status.num_songs_indexed = progress.num_songs_indexed;
+					status.num_directories_scanned = progress.num_directories_scanned;
+					status.num_songs_added = progress.num_songs_added;
+					status.num_songs_updated = progress.num_songs_updated;
+					status.num_songs_removed = progress.num_songs_removed;
 				}
 			}
 		});
 
 		index_task_set.spawn_blocking(move || {
 			let mut index_builder = index::Builder::default();
-			let mut num_songs_scanned = 0;
+			let mut progress = Progress::default();
+			let mut num_previous_songs_found = 0;
 
 			loop {
 				let exhausted_songs = match collection_songs_input.try_recv() {
-					Ok(song) => {
+					Ok(mut song) => {
+						musicbrainz::apply(&releases, &mut song);
+						// Songs keep the time they first entered the index. During the first
+						// scan, file creation times are the best approximation available.
+						song.date_added = match previous_dates_added.get(&song.virtual_path) {
+							Some(date_added) => *date_added,
+							None if was_empty => song.date_added,
+							None => scan_time,
+						};
+						match previous_fingerprints.get(&song.virtual_path) {
+							None => progress.num_songs_added += 1,
+							Some(fingerprint) => {
+								num_previous_songs_found += 1;
+								if *fingerprint != song.fingerprint {
+									progress.num_songs_updated += 1;
+								}
+							}
+						}
 						index_builder.add_song(song);
-						num_songs_scanned += 1;
-						status_sender.send(num_songs_scanned).ok();
+						progress.num_songs_indexed += 1;
+						status_sender.send(progress).ok();
 						false
 					}
 					Err(TryRecvError::Empty) => {
@@ -292,6 +719,8 @@ impl Scanner {
 				let exhausted_directories = match collection_directories_input.try_recv() {
 					Ok(directory) => {
 						index_builder.add_directory(directory);
+						progress.num_directories_scanned += 1;
+						status_sender.send(progress).ok();
 						false
 					}
 					Err(TryRecvError::Empty) => false,
@@ -311,21 +740,96 @@ impl Scanner {
 				}
 			}
 
+			progress.num_songs_removed =
+				(previous_fingerprints.len() - num_previous_songs_found) as u32;
+			status_sender.send(progress).ok();
+
 			index_builder.build()
 		});
 
-		scan_task_set.join_next().await.unwrap()??;
+		let directories = scan_task_set.join_next().await.unwrap()??;
 		watch_task_set.join_next().await.unwrap()??;
-		let index = index_task_set.join_next().await.unwrap()?;
+		let mut index = index_task_set.join_next().await.unwrap()?;
 		secondary_task_set.abort_all();
+		status_task.await?;
+
+		if control.is_cancelled() {
+			return Ok(());
+		}
+
+		let num_songs = index.collection.num_songs();
+		self.status.write().await.phase = Some(Phase::Saving);
+		self.index_manager.persist_index(&mut index).await?;
+
+		let report_new_albums = !was_empty
+			&& self
+				.hooks_manager
+				.is_subscribed(config::WebhookEvent::AlbumAdded)
+				.await;
+		let previous_albums = if report_new_albums {
+			self.index_manager.get_albums().await
+		} else {
+			Vec::new()
+		};
 
-		self.index_manager.persist_index(&index).await?;
 		self.index_manager.replace_index(index).await;
+		self.index_manager.record_changes().await?;
+		*self.indexed_parameters.write().await = Some(new_parameters);
+		*self.indexed_directories.write().await = directories;
 
-		{
+		let summary = {
 			let mut status = self.status.write().await;
+			let end_time = SystemTime::now();
 			status.state = State::UpToDate;
-			status.last_end_time = Some(SystemTime::now());
+			status.phase = None;
+			status.last_end_time = Some(end_time);
+			hooks::ScanSummary::new(
+				status.last_start_time.unwrap_or(end_time),
+				end_time,
+				num_songs,
+			)
+		};
+
+		if report_new_albums {
+			let previous_albums = previous_albums
+				.into_iter()
+				.map(|a| (a.name, a.artists))
+				.collect::<HashSet<_>>();
+			for album in self.index_manager.get_albums().await {
+				if !previous_albums.contains(&(album.name.clone(), album.artists.clone())) {
+					self.hooks_manager
+						.notify(hooks::Event::AlbumAdded {
+							name: album.name,
+							artists: album.artists,
+						})
+						.await;
+				}
+			}
+		}
+
+		self.hooks_manager
+			.notify(hooks::Event::ScanCompleted {
+				duration_ms: summary.duration_ms,
+				num_songs: summary.num_songs,
+			})
+			.await;
+
+		if let Some(hook) = self.config_manager.get_post_scan_hook().await {
+			tokio::spawn(hooks::run_post_scan(hook, summary));
+		}
+
+		// Releases found on MusicBrainz are applied to the collection by another scan
+		if self.config_manager.get_musicbrainz_enrichment().await {
+			tokio::spawn({
+				let scanner = self.clone();
+				async move {
+					match scanner.musicbrainz_manager.update_releases().await {
+						Ok(0) => (),
+						Ok(_) => scanner.queue_scan(),
+						Err(e) => error!("MusicBrainz enrichment error: {e}"),
+					}
+				}
+			});
 		}
 
 		info!(
@@ -335,12 +839,184 @@ impl Scanner {
 
 		Ok(())
 	}
+
+	// Re-scans a single directory and its descendants, without waiting for the next full scan.
+	// Scans in progress complete first, while paused scans fail the refresh.
+	pub async fn refresh_directory(&self, virtual_path: PathBuf) -> Result<Vec<PathBuf>, Error> {
+		let with_songs = self.update_directories(vec![virtual_path.clone()]).await?;
+		// Directories without any songs cannot be flattened
+		if !with_songs.contains(&virtual_path) {
+			return Ok(vec![]);
+		}
+		self.index_manager.flatten(virtual_path).await
+	}
+
+	async fn get_musicbrainz_releases(
+		&self,
+		parameters: &Parameters,
+	) -> Result<HashMap<String, musicbrainz::Release>, Error> {
+		match parameters.musicbrainz_enrichment {
+			true => self.musicbrainz_manager.get_releases().await,
+			false => Ok(HashMap::new()),
+		}
+	}
+
+	// Paused scans hold the update lock until resumed, so partial updates fail instead of waiting
+	// for them.
+	async fn lock_update(&self) -> Result<tokio::sync::MutexGuard<'_, ()>, Error> {
+		loop {
+			let paused = self.on_pause.notified();
+			tokio::pin!(paused);
+			paused.as_mut().enable();
+			if matches!(self.status.read().await.state, State::Paused) {
+				return Err(Error::ScanPaused);
+			}
+			tokio::select! {
+				update = self.update_lock.lock() => return Ok(update),
+				_ = &mut paused => (),
+			}
+		}
+	}
+
+	// Re-scans several directories and applies them to the index as a single update.
+	// Returns the directories which contain any songs.
+	async fn update_directories(
+		&self,
+		virtual_paths: Vec<PathBuf>,
+	) -> Result<HashSet<PathBuf>, Error> {
+		if virtual_paths.is_empty() {
+			return Ok(HashSet::new());
+		}
+		let _update = self.lock_update().await?;
+
+		let mut targets = vec![];
+		for virtual_path in &virtual_paths {
+			let real_path = self
+				.config_manager
+				.resolve_virtual_path(virtual_path)
+				.await?;
+			let remote = self.config_manager.get_remote(virtual_path).await;
+			if remote.is_none() && !real_path.is_dir() {
+				return Err(Error::DirectoryNotFound(virtual_path.clone()));
+			}
+			info!("Refreshing `{}`", virtual_path.display());
+			targets.push((virtual_path.clone(), real_path, remote));
+		}
+
+		let parameters = Arc::new(self.read_parameters().await);
+		let releases = self.get_musicbrainz_releases(&parameters).await?;
+		let (directories_output, directories_input) = channel();
+		let (songs_output, songs_input) = channel();
+
+		spawn_blocking(move || {
+			let thread_pool = ThreadPoolBuilder::new()
+				.num_threads(get_num_traverser_threads(parameters.num_threads))
+				.build()?;
+			let traversal = Traversal {
+				directories_output,
+				songs_output,
+				parameters,
+				control: Arc::default(),
+				history: Arc::default(),
+			};
+			thread_pool.scope(|scope| {
+				for (virtual_path, real_path, remote) in targets {
+					match remote {
+						Some(remote) => process_remote_directory(
+							scope,
+							remote,
+							real_path,
+							virtual_path,
+							traversal.clone(),
+						),
+						None => {
+							process_directory(scope, real_path, virtual_path, traversal.clone())
+						}
+					}
+				}
+			});
+			Ok::<(), Error>(())
+		})
+		.await??;
+
+		let directories = directories_input.into_iter().collect();
+		let songs = songs_input
+			.into_iter()
+			.map(|mut song| {
+				musicbrainz::apply(&releases, &mut song);
+				song
+			})
+			.collect::<Vec<_>>();
+		let with_songs = virtual_paths
+			.iter()
+			.filter(|p| songs.iter().any(|s| s.virtual_path.starts_with(p)))
+			.cloned()
+			.collect();
+		self.index_manager
+			.replace_directories(virtual_paths, directories, songs)
+			.await?;
+		Ok(with_songs)
+	}
+}
+
+// Virtual paths of the directories affected by changes to the given files or directories.
+// Directories nested inside other affected directories are omitted, as are changes to excluded
+// files and directories.
+fn get_directories_to_refresh(
+	mount_dirs: &[config::MountDir],
+	changed_paths: HashSet<PathBuf>,
+) -> Vec<PathBuf> {
+	// The file watcher reports absolute paths
+	let mount_dirs = mount_dirs
+		.iter()
+		.map(|m| {
+			let source = std::path::absolute(&m.source).unwrap_or_else(|_| m.source.clone());
+			(source, m)
+		})
+		.collect::<Vec<_>>();
+
+	let mut directories = changed_paths
+		.into_iter()
+		.filter_map(|path| {
+			let (source, mount_dir) = mount_dirs.iter().find(|(s, _)| path.starts_with(s))?;
+			let name = &mount_dir.name;
+			let changed_path = path.strip_prefix(source).ok()?;
+			if changed_path.ancestors().any(|p| mount_dir.excludes(p)) {
+				return None;
+			}
+			// Removed files and directories are refreshed from their closest remaining parent
+			let mut directory = path.as_path();
+			while directory != source && !directory.is_dir() {
+				directory = directory.parent()?;
+			}
+			let relative_path = directory.strip_prefix(source).ok()?;
+			Some(
+				Path::new(name)
+					.components()
+					.chain(relative_path.components())
+					.collect::<PathBuf>(),
+			)
+		})
+		.collect::<Vec<_>>();
+
+	directories.sort();
+	let mut deduplicated: Vec<PathBuf> = Vec::new();
+	for directory in directories {
+		if !deduplicated.iter().any(|d| directory.starts_with(d)) {
+			deduplicated.push(directory);
+		}
+	}
+	deduplicated
 }
 
 struct Scan {
 	directories_output: Sender<Directory>,
 	songs_output: Sender<Song>,
 	parameters: Parameters,
+	control: Arc<ScanControl>,
+	previous_songs: HashMap<PathBuf, Song>,
+	previous_directories: HashMap<PathBuf, DirectorySnapshot>,
+	error_log: Arc<std::sync::Mutex<ErrorLog>>,
 }
 
 impl Scan {
@@ -353,51 +1029,177 @@ impl Scan {
 			directories_output,
 			songs_output,
 			parameters,
+			control: Arc::default(),
+			previous_songs: HashMap::new(),
+			previous_directories: HashMap::new(),
+			error_log: Arc::default(),
+		}
+	}
+
+	fn with_control(self, control: Arc<ScanControl>) -> Self {
+		Self { control, ..self }
+	}
+
+	// Songs from an earlier scan, which are reused as-is when their files have not changed
+	fn with_previous_songs(self, previous_songs: HashMap<PathBuf, Song>) -> Self {
+		Self {
+			previous_songs,
+			..self
+		}
+	}
+
+	// Directories from an earlier scan, which are not read again when their modification time has
+	// not changed (if change detection is based on directory modification times)
+	fn with_previous_directories(
+		self,
+		previous_directories: HashMap<PathBuf, DirectorySnapshot>,
+	) -> Self {
+		Self {
+			previous_directories,
+			..self
 		}
 	}
 
-	pub fn run(self) -> Result<(), Error> {
-		let key = "POLARIS_NUM_TRAVERSER_THREADS";
-		let num_threads = std::env::var_os(key)
-			.map(|v| v.to_string_lossy().to_string())
-			.and_then(|v| usize::from_str(&v).ok())
-			.unwrap_or_else(|| min(num_cpus::get(), 8));
+	// Where to report files which could not be read
+	fn with_error_log(self, error_log: Arc<std::sync::Mutex<ErrorLog>>) -> Self {
+		Self { error_log, ..self }
+	}
+
+	pub fn run(self) -> Result<HashMap<PathBuf, DirectorySnapshot>, Error> {
+		let num_threads = get_num_traverser_threads(self.parameters.num_threads);
 		info!("Browsing collection using {} threads", num_threads);
 
-		let directories_output = self.directories_output.clone();
-		let songs_output = self.songs_output.clone();
-		let artwork_regex = self.parameters.artwork_regex.clone();
+		let traversal = Traversal {
+			directories_output: self.directories_output.clone(),
+			songs_output: self.songs_output.clone(),
+			parameters: Arc::new(self.parameters),
+			control: self.control,
+			history: Arc::new(ScanHistory {
+				previous_songs: self.previous_songs,
+				previous_directories: self.previous_directories,
+				directories: Default::default(),
+				error_log: self.error_log,
+			}),
+		};
 
 		let thread_pool = ThreadPoolBuilder::new().num_threads(num_threads).build()?;
 		thread_pool.scope({
 			|scope| {
-				for mount in self.parameters.mount_dirs {
-					scope.spawn(|scope| {
-						process_directory(
+				for mount in traversal.parameters.mount_dirs.clone() {
+					scope.spawn(|scope| match mount.remote {
+						Some(remote) => process_remote_directory(
 							scope,
+							remote,
 							mount.source,
-							mount.name,
-							directories_output.clone(),
-							songs_output.clone(),
-							artwork_regex.clone(),
-						);
+							PathBuf::from(mount.name),
+							traversal.clone(),
+						),
+						None => {
+							process_directory(scope, mount.source, mount.name, traversal.clone())
+						}
 					});
 				}
 			}
 		});
 
-		Ok(())
+		let directories = std::mem::take(&mut *traversal.history.directories.lock().unwrap());
+		Ok(directories)
 	}
 }
 
+// Thread count from the configuration file takes precedence over the environment variable
+fn get_num_traverser_threads(configured: Option<NonZeroUsize>) -> usize {
+	let key = "POLARIS_NUM_TRAVERSER_THREADS";
+	configured
+		.map(NonZeroUsize::get)
+		.or_else(|| {
+			std::env::var_os(key)
+				.map(|v| v.to_string_lossy().to_string())
+				.and_then(|v| usize::from_str(&v).ok())
+		})
+		.unwrap_or_else(|| min(num_cpus::get(), 8))
+}
+
+// Mount containing a virtual path, along with the path relative to the mount source
+fn find_mount<'a, 'b>(
+	mount_dirs: &'a [config::MountDir],
+	virtual_path: &'b Path,
+) -> Option<(&'a config::MountDir, &'b Path)> {
+	mount_dirs
+		.iter()
+		.find_map(|m| Some((m, virtual_path.strip_prefix(&m.name).ok()?)))
+}
+
+// Whether a symlink points to a directory containing it, which would be traversed endlessly
+fn is_symlink_loop<P: AsRef<Path>>(symlink_path: P) -> bool {
+	let Ok(target) = fs::canonicalize(&symlink_path) else {
+		return true;
+	};
+	symlink_path
+		.as_ref()
+		.ancestors()
+		.skip(1)
+		.any(|a| fs::canonicalize(a).is_ok_and(|a| a == target))
+}
+
 fn process_directory<P: AsRef<Path>, Q: AsRef<Path>>(
 	scope: &Scope,
 	real_path: P,
 	virtual_path: Q,
-	directories_output: Sender<Directory>,
-	songs_output: Sender<Song>,
-	artwork_regex: Option<Regex>,
+	traversal: Traversal,
 ) {
+	let Traversal {
+		directories_output,
+		songs_output,
+		parameters,
+		control,
+		history,
+	} = &traversal;
+
+	if !control.proceed() {
+		return;
+	}
+
+	let modified = fs::metadata(&real_path).and_then(|m| m.modified()).ok();
+	if parameters.change_detection == config::ChangeDetection::DirectoryModificationTime {
+		let unchanged = history
+			.previous_directories
+			.get(real_path.as_ref())
+			.filter(|d| Some(d.modified) == modified);
+		let songs = unchanged.and_then(|d| {
+			d.songs
+				.iter()
+				.map(|p| history.previous_songs.get(p).cloned())
+				.collect::<Option<Vec<_>>>()
+		});
+		if let (Some(snapshot), Some(songs)) = (unchanged, songs) {
+			for subdirectory in &snapshot.subdirectories {
+				let entry_real_path = real_path.as_ref().join(subdirectory);
+				let entry_virtual_path = virtual_path.as_ref().join(subdirectory);
+				scope.spawn({
+					let traversal = traversal.clone();
+					move |scope| {
+						process_directory(scope, entry_real_path, entry_virtual_path, traversal);
+					}
+				});
+			}
+			for song in songs {
+				songs_output.send(song).ok();
+			}
+			directories_output
+				.send(Directory {
+					virtual_path: virtual_path.as_ref().to_owned(),
+				})
+				.ok();
+			history
+				.directories
+				.lock()
+				.unwrap()
+				.insert(real_path.as_ref().to_owned(), snapshot.clone());
+			return;
+		}
+	}
+
 	let read_dir = match fs::read_dir(&real_path) {
 		Ok(read_dir) => read_dir,
 		Err(e) => {
@@ -406,12 +1208,23 @@ fn process_directory<P: AsRef<Path>, Q: AsRef<Path>>(
 				real_path.as_ref().display(),
 				e
 			);
+			history.report_error(&virtual_path, format!("Could not read directory: {e}"));
 			return;
 		}
 	};
 
+	let mount = find_mount(&parameters.mount_dirs, virtual_path.as_ref());
+	let symlinks = mount.map(|(m, _)| m.symlinks).unwrap_or_default();
+	let artist_separators = parameters
+		.artist_separators
+		.as_ref()
+		.filter(|_| mount.is_some_and(|(m, _)| m.split_artists));
+
 	let mut songs = vec![];
+	let mut subdirectories = vec![];
 	let mut artwork_file = None;
+	let mut lyrics_files = HashSet::new();
+	let mut cue_sheets = vec![];
 
 	for entry in read_dir {
 		let entry = match entry {
@@ -422,62 +1235,106 @@ fn process_directory<P: AsRef<Path>, Q: AsRef<Path>>(
 					real_path.as_ref().display(),
 					e
 				);
+				history.report_error(&virtual_path, format!("Could not read file: {e}"));
 				continue;
 			}
 		};
 
-		let is_dir = match entry.file_type().map(|f| f.is_dir()) {
-			Ok(d) => d,
+		let name = entry.file_name();
+		let entry_real_path = real_path.as_ref().join(&name);
+		let entry_virtual_path = virtual_path.as_ref().join(&name);
+		if mount.is_some_and(|(m, p)| m.excludes(p.join(&name))) {
+			continue;
+		}
+
+		let file_type = match entry.file_type() {
+			Ok(t) => t,
 			Err(e) => {
 				error!(
 					"Could not determine file type for `{}`: {}",
 					entry.path().to_string_lossy(),
 					e
 				);
+				history.report_error(
+					&entry_virtual_path,
+					format!("Could not determine file type: {e}"),
+				);
 				continue;
 			}
 		};
-		let name = entry.file_name();
-		let entry_real_path = real_path.as_ref().join(&name);
-		let entry_virtual_path = virtual_path.as_ref().join(&name);
+
+		let is_dir = if file_type.is_symlink() {
+			match symlinks {
+				config::SymlinkPolicy::Ignore => continue,
+				config::SymlinkPolicy::Files if entry_real_path.is_dir() => continue,
+				config::SymlinkPolicy::Files => false,
+				config::SymlinkPolicy::Follow if entry_real_path.is_dir() => {
+					if is_symlink_loop(&entry_real_path) {
+						warn!(
+							"Not following symlink `{}`, which points to one of its parent directories",
+							entry_real_path.display()
+						);
+						continue;
+					}
+					true
+				}
+				config::SymlinkPolicy::Follow => false,
+			}
+		} else {
+			file_type.is_dir()
+		};
 
 		if is_dir {
+			subdirectories.push(name.clone());
 			scope.spawn({
-				let directories_output = directories_output.clone();
-				let songs_output = songs_output.clone();
-				let artwork_regex = artwork_regex.clone();
-				|scope| {
-					process_directory(
-						scope,
-						entry_real_path,
-						entry_virtual_path,
-						directories_output,
-						songs_output,
-						artwork_regex,
-					);
+				let traversal = traversal.clone();
+				move |scope| {
+					process_directory(scope, entry_real_path, entry_virtual_path, traversal);
 				}
 			});
-		} else if let Some(metadata) = formats::read_metadata(&entry_real_path) {
+		} else if !control.proceed() {
+			return;
+		} else if is_lyrics_file(&name) {
+			lyrics_files.insert(entry_virtual_path);
+		} else if is_cue_sheet(&name) {
+			cue_sheets.push((entry_real_path, entry_virtual_path));
+		} else if let Some(song) = history
+			.previous_songs
+			.get(&entry_real_path)
+			.filter(|s| s.fingerprint.is_some())
+			.filter(|s| {
+				s.fingerprint == get_fingerprint(&entry_real_path, parameters.change_detection)
+			}) {
+			let mut song = song.clone();
+			// Artwork and lyrics from adjacent files are looked up again below
+			song.artwork = song.artwork.filter(|a| *a == song.virtual_path);
+			song.lyrics = song.lyrics.filter(|l| *l == song.virtual_path);
+			songs.push(song);
+		} else if let Some(mut metadata) =
+			read_metadata(&entry_real_path, &entry_virtual_path, history)
+		{
+			prepare_metadata(
+				&mut metadata,
+				&entry_virtual_path,
+				parameters,
+				artist_separators,
+			);
 			songs.push(Song {
-				real_path: entry_real_path.clone(),
-				virtual_path: entry_virtual_path.clone(),
-				track_number: metadata.track_number.map(|n| n as i64),
-				disc_number: metadata.disc_number.map(|n| n as i64),
-				title: metadata.title,
-				artists: metadata.artists,
-				album_artists: metadata.album_artists,
-				year: metadata.year.map(|n| n as i64),
-				album: metadata.album,
-				artwork: metadata.has_artwork.then(|| entry_virtual_path.clone()),
-				duration: metadata.duration.map(|n| n as i64),
-				lyricists: metadata.lyricists,
-				composers: metadata.composers,
-				genres: metadata.genres,
-				labels: metadata.labels,
 				date_added: get_date_created(&entry_real_path).unwrap_or_default(),
+				audible_range: parameters
+					.detect_silence
+					.then(|| get_audible_range(&entry_real_path))
+					.flatten(),
+				fingerprint: get_fingerprint(&entry_real_path, parameters.change_detection),
+				..make_song(
+					entry_real_path.clone(),
+					entry_virtual_path.clone(),
+					metadata,
+				)
 			});
 		} else if artwork_file.is_none()
-			&& artwork_regex
+			&& parameters
+				.artwork_regex
 				.as_ref()
 				.is_some_and(|r| r.is_match(name.to_str().unwrap_or_default()))
 		{
@@ -485,8 +1342,39 @@ fn process_directory<P: AsRef<Path>, Q: AsRef<Path>>(
 		}
 	}
 
+	for (cue_real_path, cue_virtual_path) in &cue_sheets {
+		let directories = split_cue_sheet(
+			cue_real_path,
+			cue_virtual_path,
+			&mut songs,
+			parameters,
+			history,
+		);
+		for directory in directories {
+			directories_output.send(directory).ok();
+		}
+	}
+
+	// Songs split from CUE sheets share the same file, so directories containing them are always
+	// read again
+	if let Some(modified) = modified.filter(|_| cue_sheets.is_empty()) {
+		history.directories.lock().unwrap().insert(
+			real_path.as_ref().to_owned(),
+			DirectorySnapshot {
+				modified,
+				subdirectories,
+				songs: songs.iter().map(|s| s.real_path.clone()).collect(),
+			},
+		);
+	}
+
 	for mut song in songs {
-		song.artwork = song.artwork.or_else(|| artwork_file.clone());
+		// Embedded artwork is only used when no file in the directory matches the album art pattern
+		song.artwork = artwork_file.clone().or(song.artwork);
+		song.lyrics = song.lyrics.or_else(|| {
+			let sidecar_path = lyrics::sidecar_path(&song.virtual_path);
+			lyrics_files.contains(&sidecar_path).then_some(sidecar_path)
+		});
 		songs_output.send(song).ok();
 	}
 
@@ -497,35 +1385,593 @@ fn process_directory<P: AsRef<Path>, Q: AsRef<Path>>(
 		.ok();
 }
 
-fn get_date_created<P: AsRef<Path>>(path: P) -> Option<i64> {
-	if let Ok(t) = fs::metadata(path).and_then(|m| m.created().or_else(|_| m.modified())) {
-		t.duration_since(std::time::UNIX_EPOCH)
-			.map(|d| d.as_secs() as i64)
-			.ok()
-	} else {
-		None
+// Counterpart of `process_directory` for mounts whose files are on a remote server. The whole
+// directory tree is listed at once, after which directories are processed in parallel.
+fn process_remote_directory(
+	scope: &Scope,
+	remote: config::Remote,
+	real_path: PathBuf,
+	virtual_path: PathBuf,
+	traversal: Traversal,
+) {
+	let Traversal {
+		parameters,
+		control,
+		history,
+		..
+	} = &traversal;
+
+	if !control.proceed() {
+		return;
 	}
-}
 
-#[cfg(test)]
-mod test {
-	use std::path::PathBuf;
+	let files = match remote::list_files(&remote, &real_path) {
+		Ok(files) => files,
+		Err(e) => {
+			error!(
+				"Directory listing error for `{}`: {}",
+				real_path.display(),
+				e
+			);
+			history.report_error(&virtual_path, format!("Could not list files: {e}"));
+			return;
+		}
+	};
 
-	use crate::app::test::{self};
-	use crate::test_name;
+	let mount = find_mount(&parameters.mount_dirs, &virtual_path);
+	let mut directories = BTreeMap::<PathBuf, Vec<(OsString, remote::File)>>::new();
+	directories.insert(PathBuf::new(), vec![]);
+	for file in files {
+		let relative_path = file.path.split('/').collect::<PathBuf>();
+		let excluded = relative_path
+			.ancestors()
+			.filter(|a| !a.as_os_str().is_empty())
+			.any(|a| mount.is_some_and(|(m, p)| m.excludes(p.join(a))));
+		let (Some(parent), Some(name)) = (relative_path.parent(), relative_path.file_name()) else {
+			continue;
+		};
+		if excluded {
+			continue;
+		}
+		for directory in parent.ancestors() {
+			directories.entry(directory.to_owned()).or_default();
+		}
+		if let Some(files) = directories.get_mut(parent) {
+			files.push((name.to_owned(), file));
+		}
+	}
 
-	use super::*;
+	let remote = Arc::new(remote);
+	for (relative_path, files) in directories {
+		let (real_path, virtual_path) = match relative_path.as_os_str().is_empty() {
+			true => (real_path.clone(), virtual_path.clone()),
+			false => (
+				real_path.join(&relative_path),
+				virtual_path.join(&relative_path),
+			),
+		};
+		scope.spawn({
+			let remote = remote.clone();
+			let traversal = traversal.clone();
+			move |_| {
+				process_remote_files(&remote, &real_path, &virtual_path, files, &traversal);
+			}
+		});
+	}
+}
 
-	#[tokio::test]
-	async fn scan_finds_songs_and_directories() {
-		let (directories_sender, directories_receiver) = channel();
-		let (songs_sender, songs_receiver) = channel();
+// Reads the songs of a single directory on a remote server. Audio files are downloaded to read
+// their tags, unless they have not changed since the previous scan.
+fn process_remote_files(
+	remote: &config::Remote,
+	real_path: &Path,
+	virtual_path: &Path,
+	files: Vec<(OsString, remote::File)>,
+	traversal: &Traversal,
+) {
+	let Traversal {
+		directories_output,
+		songs_output,
+		parameters,
+		control,
+		history,
+	} = traversal;
+
+	let mount = find_mount(&parameters.mount_dirs, virtual_path);
+	let artist_separators = parameters
+		.artist_separators
+		.as_ref()
+		.filter(|_| mount.is_some_and(|(m, _)| m.split_artists));
+
+	let mut songs = vec![];
+	let mut artwork_file = None;
+
+	for (name, file) in files {
+		if !control.proceed() {
+			return;
+		}
+
+		let entry_real_path = real_path.join(&name);
+		let entry_virtual_path = virtual_path.join(&name);
+		let fingerprint = get_remote_fingerprint(&file);
+
+		if utils::get_audio_format(&name).is_none() {
+			if artwork_file.is_none()
+				&& parameters
+					.artwork_regex
+					.as_ref()
+					.is_some_and(|r| r.is_match(name.to_str().unwrap_or_default()))
+			{
+				artwork_file = Some(entry_virtual_path);
+			}
+		} else if let Some(song) = history
+			.previous_songs
+			.get(&entry_real_path)
+			.filter(|s| s.fingerprint == Some(fingerprint))
+		{
+			let mut song = song.clone();
+			// Artwork from adjacent files is looked up again below
+			song.artwork = song.artwork.filter(|a| *a == song.virtual_path);
+			songs.push(song);
+		} else {
+			let local_copy = match remote::download(remote, &entry_real_path) {
+				Ok(c) => c,
+				Err(e) => {
+					error!("Download error for `{}`: {}", entry_real_path.display(), e);
+					history
+						.report_error(&entry_virtual_path, format!("Could not download file: {e}"));
+					continue;
+				}
+			};
+			let Some(mut metadata) = read_metadata(local_copy.path(), &entry_virtual_path, history)
+			else {
+				continue;
+			};
+			prepare_metadata(
+				&mut metadata,
+				&entry_virtual_path,
+				parameters,
+				artist_separators,
+			);
+			songs.push(Song {
+				// Lyrics are not read from remote servers
+				lyrics: None,
+				date_added: file.modified.unwrap_or_default(),
+				audible_range: parameters
+					.detect_silence
+					.then(|| get_audible_range(local_copy.path()))
+					.flatten(),
+				fingerprint: Some(fingerprint),
+				..make_song(entry_real_path, entry_virtual_path, metadata)
+			});
+		}
+	}
+
+	for mut song in songs {
+		// Embedded artwork is only used when no file in the directory matches the album art pattern
+		song.artwork = artwork_file.clone().or(song.artwork);
+		songs_output.send(song).ok();
+	}
+
+	directories_output
+		.send(Directory {
+			virtual_path: virtual_path.to_owned(),
+		})
+		.ok();
+}
+
+fn is_lyrics_file(name: &OsStr) -> bool {
+	Path::new(name).extension().is_some_and(|e| e == "lrc")
+}
+
+fn is_cue_sheet(name: &OsStr) -> bool {
+	Path::new(name)
+		.extension()
+		.is_some_and(|e| e.eq_ignore_ascii_case("cue"))
+}
+
+// Replaces songs described by a CUE sheet with one song per track. Tracks are listed under a
+// virtual directory named after the file containing them.
+fn split_cue_sheet(
+	real_path: &Path,
+	virtual_path: &Path,
+	songs: &mut Vec<Song>,
+	parameters: &Parameters,
+	history: &ScanHistory,
+) -> Vec<Directory> {
+	let Some(directory) = real_path.parent() else {
+		return vec![];
+	};
+	let sheet = match formats::CueSheet::read(real_path) {
+		Ok(sheet) => sheet,
+		Err(e) => {
+			error!("Could not read CUE sheet `{}`: {}", real_path.display(), e);
+			history.report_error(virtual_path, format!("Could not read CUE sheet: {e}"));
+			return vec![];
+		}
+	};
+
+	let artists = &parameters.artist_aliases;
+	let segments = sheet.segments();
+	let mut files = sheet.tracks.iter().map(|t| &t.file).collect::<Vec<_>>();
+	files.dedup();
+
+	let mut directories = vec![];
+	for file in files {
+		// CUE sheets often name the file they were ripped to, like `album.wav` for `album.flac`
+		let file_path = directory.join(file);
+		let Some(index) = songs
+			.iter()
+			.position(|s| s.segment.is_none() && s.real_path == file_path)
+			.or_else(|| {
+				songs.iter().position(|s| {
+					s.segment.is_none() && s.real_path.file_stem() == file_path.file_stem()
+				})
+			})
+		else {
+			continue;
+		};
+		let file_song = songs.swap_remove(index);
+		let file_duration = file_song.duration.map(|d| d * 1000);
+
+		for (track, segment) in sheet.tracks.iter().zip(&segments) {
+			if &track.file != file {
+				continue;
+			}
+			let name = match &track.title {
+				Some(title) => format!("{:02} - {}", track.number, title.replace(['/', '\\'], "-")),
+				None => format!("{:02}", track.number),
+			};
+			let performer = track.performer.as_ref().or(sheet.performer.as_ref());
+			let songwriter = track.songwriter.as_ref().or(sheet.songwriter.as_ref());
+			songs.push(Song {
+				real_path: file_song.real_path.clone(),
+				virtual_path: file_song.virtual_path.join(name),
+				track_number: Some(track.number as i64),
+				disc_number: file_song.disc_number,
+				disc_subtitle: file_song.disc_subtitle.clone(),
+				title: track.title.clone(),
+				artists: match performer {
+					Some(p) => vec![artists.resolve(p.clone())],
+					None => file_song.artists.clone(),
+				},
+				album_artists: match (&sheet.performer, file_song.album_artists.is_empty()) {
+					(Some(p), true) => vec![artists.resolve(p.clone())],
+					_ => file_song.album_artists.clone(),
+				},
+				year: file_song.year.or(sheet.year),
+				album: match &sheet.title {
+					Some(t) => Some(parameters.album_aliases.resolve(t.clone())),
+					None => file_song.album.clone(),
+				},
+				artwork: file_song.artwork.clone(),
+				lyrics: None,
+				duration: segment
+					.end
+					.or(file_duration)
+					.filter(|end| *end > segment.start)
+					.map(|end| (end - segment.start) / 1000),
+				lyricists: file_song.lyricists.clone(),
+				composers: match songwriter {
+					Some(s) => vec![artists.resolve(s.clone())],
+					None => file_song.composers.clone(),
+				},
+				genres: match (&sheet.genre, file_song.genres.is_empty()) {
+					(Some(g), true) => vec![parameters.genre_aliases.resolve(g.clone())],
+					_ => file_song.genres.clone(),
+				},
+				labels: file_song.labels.clone(),
+				compilation: file_song.compilation,
+				conductors: file_song.conductors.clone(),
+				performers: file_song.performers.clone(),
+				work: file_song.work.clone(),
+				movement: file_song.movement.clone(),
+				bpm: file_song.bpm,
+				initial_key: file_song.initial_key.clone(),
+				replay_gain: file_song.replay_gain,
+				// Identifiers of the file describe the whole release rather than this track
+				musicbrainz_recording_id: None,
+				musicbrainz_release_id: file_song.musicbrainz_release_id.clone(),
+				musicbrainz_artist_ids: vec![],
+				date_added: file_song.date_added,
+				audible_range: None,
+				fingerprint: None,
+				segment: Some(*segment),
+			});
+		}
+
+		directories.push(Directory {
+			virtual_path: file_song.virtual_path,
+		});
+	}
+	directories
+}
+
+// Applies the tag adjustments configured for the collection
+fn prepare_metadata(
+	metadata: &mut formats::SongMetadata,
+	virtual_path: &Path,
+	parameters: &Parameters,
+	artist_separators: Option<&Regex>,
+) {
+	if let Some(pattern) = &parameters.filename_pattern {
+		pattern.apply(virtual_path, metadata);
+	}
+	if let Some(separators) = artist_separators {
+		split_artists(metadata, separators);
+	}
+	apply_disc_number(metadata, virtual_path);
+	apply_aliases(metadata, parameters);
+	apply_compilation(metadata);
+}
+
+// Song described by the tags of its file. Details which do not come from tags are left unset.
+fn make_song(real_path: PathBuf, virtual_path: PathBuf, metadata: formats::SongMetadata) -> Song {
+	Song {
+		track_number: metadata.track_number.map(|n| n as i64),
+		disc_number: metadata.disc_number.map(|n| n as i64),
+		disc_subtitle: metadata.disc_subtitle,
+		title: metadata.title,
+		artists: metadata.artists,
+		album_artists: metadata.album_artists,
+		year: metadata.year.map(|n| n as i64),
+		album: metadata.album,
+		artwork: metadata.has_artwork.then(|| virtual_path.clone()),
+		lyrics: metadata.has_lyrics.then(|| virtual_path.clone()),
+		duration: metadata.duration.map(|n| n as i64),
+		lyricists: metadata.lyricists,
+		composers: metadata.composers,
+		genres: metadata.genres,
+		labels: metadata.labels,
+		compilation: metadata.compilation,
+		conductors: metadata.conductors,
+		performers: metadata.performers,
+		work: metadata.work,
+		movement: metadata.movement,
+		bpm: metadata.bpm.map(|n| n as i64),
+		initial_key: metadata.initial_key,
+		replay_gain: metadata.replay_gain,
+		musicbrainz_recording_id: metadata.musicbrainz_recording_id,
+		musicbrainz_release_id: metadata.musicbrainz_release_id,
+		musicbrainz_artist_ids: metadata.musicbrainz_artist_ids,
+		real_path,
+		virtual_path,
+		..Default::default()
+	}
+}
+
+fn read_metadata(
+	real_path: &Path,
+	virtual_path: &Path,
+	history: &ScanHistory,
+) -> Option<formats::SongMetadata> {
+	match formats::read_metadata(real_path) {
+		Ok(metadata) => metadata,
+		Err(e) => {
+			error!(
+				"Error while reading file metadata for `{}`: {}",
+				real_path.display(),
+				e
+			);
+			history.report_error(virtual_path, format!("Could not read metadata: {e}"));
+			None
+		}
+	}
+}
+
+// Matches any of the configured separators, ignoring case so `feat.` also matches `Feat.`
+fn build_artist_separators(separators: &[String]) -> Option<Regex> {
+	if separators.is_empty() {
+		return None;
+	}
+	let alternatives = separators
+		.iter()
+		.map(|s| regex::escape(s))
+		.collect::<Vec<_>>()
+		.join("|");
+	Regex::new(&format!("(?i){alternatives}")).ok()
+}
+
+fn split_artists(metadata: &mut formats::SongMetadata, separators: &Regex) {
+	for list in [&mut metadata.artists, &mut metadata.album_artists] {
+		*list = list
+			.iter()
+			.flat_map(|a| separators.split(a))
+			.map(str::trim)
+			.filter(|a| !a.is_empty())
+			.map(str::to_owned)
+			.collect();
+	}
+}
+
+fn apply_aliases(metadata: &mut formats::SongMetadata, parameters: &Parameters) {
+	let artists = &parameters.artist_aliases;
+	for list in [
+		&mut metadata.artists,
+		&mut metadata.album_artists,
+		&mut metadata.composers,
+		&mut metadata.lyricists,
+		&mut metadata.conductors,
+		&mut metadata.performers,
+	] {
+		*list = list.drain(..).map(|a| artists.resolve(a)).collect();
+	}
+	metadata.album = metadata
+		.album
+		.take()
+		.map(|a| parameters.album_aliases.resolve(a));
+	metadata.genres = metadata
+		.genres
+		.drain(..)
+		.map(|g| parameters.genre_aliases.resolve(g))
+		.collect();
+}
+
+// Album names ending like `(Disc 2)` or `CD2`
+static DISC_SUFFIX_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+	Regex::new(
+		r"(?i)^(.*?\S)(?:[\s\-_:,]+[(\[]?|[(\[])\s*(?:cd|dis[ck])\s*(\d+)(?:\s*of\s*\d+)?\s*[)\]]?$",
+	)
+	.unwrap()
+});
+
+// Directory names like `CD2` or `Disc 2`
+static DISC_DIRECTORY_REGEX: LazyLock<Regex> =
+	LazyLock::new(|| Regex::new(r"(?i)^(?:cd|dis[ck])\s*(\d+)$").unwrap());
+
+// Groups discs of a release into a single album, for songs whose album name mentions their disc
+// or which are stored in per-disc directories
+fn apply_disc_number(metadata: &mut formats::SongMetadata, virtual_path: &Path) {
+	let suffix = metadata
+		.album
+		.as_deref()
+		.and_then(|a| DISC_SUFFIX_REGEX.captures(a))
+		.map(|c| (c[1].to_owned(), c[2].parse::<u32>().ok()));
+	if let Some((album, disc_number)) = suffix {
+		metadata.album = Some(album);
+		metadata.disc_number = metadata.disc_number.or(disc_number);
+	}
+
+	if metadata.disc_number.is_none() {
+		metadata.disc_number = virtual_path
+			.parent()
+			.and_then(|p| p.file_name())
+			.and_then(|n| DISC_DIRECTORY_REGEX.captures(n.to_str()?))
+			.and_then(|c| c[1].parse().ok());
+	}
+}
+
+// Groups songs from a compilation into a single album, instead of one per contributing artist
+fn apply_compilation(metadata: &mut formats::SongMetadata) {
+	if metadata.compilation && metadata.album_artists.is_empty() {
+		metadata.album_artists.push(VARIOUS_ARTISTS.to_owned());
+	}
+}
+
+fn get_audible_range<P: AsRef<Path>>(path: P) -> Option<silence::AudibleRange> {
+	match silence::detect_audible_range(path.as_ref()) {
+		Ok(range) => range,
+		Err(e) => {
+			error!(
+				"Could not detect silence in `{}`: {}",
+				path.as_ref().display(),
+				e
+			);
+			None
+		}
+	}
+}
+
+// Summarizes either the size and modification time of a file, or its size and a sample of its content.
+// Songs whose fingerprint has not changed since the previous scan do not need to be read again.
+fn get_fingerprint<P: AsRef<Path>>(
+	path: P,
+	change_detection: config::ChangeDetection,
+) -> Option<u64> {
+	let mut hasher = Fnv1a::default();
+	match change_detection {
+		config::ChangeDetection::ModificationTime
+		| config::ChangeDetection::DirectoryModificationTime => {
+			let metadata = fs::metadata(path).ok()?;
+			let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+			hasher.write_u64(metadata.len());
+			hasher.write_u128(modified.as_nanos());
+		}
+		config::ChangeDetection::ContentHash => {
+			// Tags are usually stored at the beginning or end of audio files
+			const SAMPLE_SIZE: u64 = 64 * 1024;
+			let mut file = fs::File::open(path).ok()?;
+			let length = file.metadata().ok()?.len();
+			let mut sample = Vec::new();
+			(&mut file)
+				.take(SAMPLE_SIZE)
+				.read_to_end(&mut sample)
+				.ok()?;
+			if length > SAMPLE_SIZE {
+				let tail_start = length.saturating_sub(SAMPLE_SIZE).max(SAMPLE_SIZE);
+				file.seek(SeekFrom::Start(tail_start)).ok()?;
+				file.read_to_end(&mut sample).ok()?;
+			}
+			hasher.write_u64(length);
+			hasher.write(&sample);
+		}
+	}
+	Some(hasher.finish())
+}
+
+// Remote servers report a version of each file (eg. an ETag), which changes along with its content
+fn get_remote_fingerprint(file: &remote::File) -> u64 {
+	let mut hasher = Fnv1a::default();
+	hasher.write_u64(file.size);
+	hasher.write(file.version.as_bytes());
+	hasher.finish()
+}
+
+// 64-bit FNV-1a, whose output (unlike the standard library's hashers) is stable across releases
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+	fn default() -> Self {
+		Self(0xcbf2_9ce4_8422_2325)
+	}
+}
+
+impl Hasher for Fnv1a {
+	fn write(&mut self, bytes: &[u8]) {
+		for byte in bytes {
+			self.0 ^= *byte as u64;
+			self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+		}
+	}
+
+	fn finish(&self) -> u64 {
+		self.0
+	}
+}
+
+fn get_date_created<P: AsRef<Path>>(path: P) -> Option<i64> {
+	if let Ok(t) = fs::metadata(path).and_then(|m| m.created().or_else(|_| m.modified())) {
+		t.duration_since(std::time::UNIX_EPOCH)
+			.map(|d| d.as_secs() as i64)
+			.ok()
+	} else {
+		None
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::path::PathBuf;
+
+	use crate::app::test::{self};
+	use crate::test::prepare_test_directory;
+	use crate::test_name;
+
+	use super::*;
+
+	#[tokio::test]
+	async fn scan_finds_songs_and_directories() {
+		let (directories_sender, directories_receiver) = channel();
+		let (songs_sender, songs_receiver) = channel();
 		let parameters = Parameters {
 			artwork_regex: None,
 			mount_dirs: vec![config::MountDir {
 				source: ["test-data", "small-collection"].iter().collect(),
 				name: "root".to_owned(),
+				exclude_patterns: vec![],
+				symlinks: Default::default(),
+				split_artists: true,
+				remote: None,
 			}],
+			detect_silence: false,
+			num_threads: None,
+			change_detection: Default::default(),
+			filename_pattern: None,
+			artist_aliases: Default::default(),
+			album_aliases: Default::default(),
+			genre_aliases: Default::default(),
+			artist_separators: None,
+			musicbrainz_enrichment: false,
 		};
 
 		let scan = Scan::new(directories_sender, songs_sender, parameters);
@@ -538,6 +1984,229 @@ mod test {
 		assert_eq!(songs.len(), 13);
 	}
 
+	#[tokio::test]
+	async fn scan_skips_excluded_paths() {
+		let (directories_sender, directories_receiver) = channel();
+		let (songs_sender, songs_receiver) = channel();
+		let parameters = Parameters {
+			artwork_regex: None,
+			mount_dirs: vec![config::MountDir {
+				source: ["test-data", "small-collection"].iter().collect(),
+				name: "root".to_owned(),
+				exclude_patterns: vec![config::ExcludePattern::new("Picnic*").unwrap()],
+				symlinks: Default::default(),
+				split_artists: true,
+				remote: None,
+			}],
+			detect_silence: false,
+			num_threads: None,
+			change_detection: Default::default(),
+			filename_pattern: None,
+			artist_aliases: Default::default(),
+			album_aliases: Default::default(),
+			genre_aliases: Default::default(),
+			artist_separators: None,
+			musicbrainz_enrichment: false,
+		};
+
+		let scan = Scan::new(directories_sender, songs_sender, parameters);
+		scan.run().unwrap();
+
+		let directories = directories_receiver.iter().collect::<Vec<_>>();
+		assert_eq!(directories.len(), 4);
+
+		let songs = songs_receiver.iter().collect::<Vec<_>>();
+		let khemmis: PathBuf = ["root", "Khemmis"].iter().collect();
+		assert_eq!(songs.len(), 5);
+		assert!(songs.iter().all(|s| s.virtual_path.starts_with(&khemmis)));
+	}
+
+	#[tokio::test]
+	async fn paused_scan_can_resume() {
+		let (directories_sender, _) = channel();
+		let (songs_sender, songs_receiver) = channel();
+		let parameters = Parameters {
+			artwork_regex: None,
+			mount_dirs: vec![config::MountDir {
+				source: ["test-data", "small-collection"].iter().collect(),
+				name: "root".to_owned(),
+				exclude_patterns: vec![],
+				symlinks: Default::default(),
+				split_artists: true,
+				remote: None,
+			}],
+			detect_silence: false,
+			num_threads: None,
+			change_detection: Default::default(),
+			filename_pattern: None,
+			artist_aliases: Default::default(),
+			album_aliases: Default::default(),
+			genre_aliases: Default::default(),
+			artist_separators: None,
+			musicbrainz_enrichment: false,
+		};
+
+		let control = Arc::new(ScanControl::default());
+		control.set(ControlState::Paused);
+		let scan =
+			Scan::new(directories_sender, songs_sender, parameters).with_control(control.clone());
+		let scan_thread = std::thread::spawn(|| scan.run());
+
+		std::thread::sleep(Duration::from_millis(200));
+		assert!(songs_receiver.try_recv().is_err());
+
+		control.set(ControlState::Running);
+		scan_thread.join().unwrap().unwrap();
+		assert_eq!(songs_receiver.iter().count(), 13);
+	}
+
+	#[tokio::test]
+	async fn cancelled_scan_stops() {
+		let (directories_sender, directories_receiver) = channel();
+		let (songs_sender, songs_receiver) = channel();
+		let parameters = Parameters {
+			artwork_regex: None,
+			mount_dirs: vec![config::MountDir {
+				source: ["test-data", "small-collection"].iter().collect(),
+				name: "root".to_owned(),
+				exclude_patterns: vec![],
+				symlinks: Default::default(),
+				split_artists: true,
+				remote: None,
+			}],
+			detect_silence: false,
+			num_threads: None,
+			change_detection: Default::default(),
+			filename_pattern: None,
+			artist_aliases: Default::default(),
+			album_aliases: Default::default(),
+			genre_aliases: Default::default(),
+			artist_separators: None,
+			musicbrainz_enrichment: false,
+		};
+
+		let control = Arc::new(ScanControl::default());
+		control.set(ControlState::Cancelled);
+		let scan = Scan::new(directories_sender, songs_sender, parameters).with_control(control);
+		scan.run().unwrap();
+
+		assert_eq!(directories_receiver.iter().count(), 0);
+		assert_eq!(songs_receiver.iter().count(), 0);
+	}
+
+	#[tokio::test]
+	async fn scan_reuses_unchanged_songs() {
+		let song_path: PathBuf = ["test-data", "small-collection", "Khemmis", "Hunted"]
+			.iter()
+			.collect();
+		let unchanged_path = song_path.join("01 - Above The Water.mp3");
+		let changed_path = song_path.join("02 - Candlelight.mp3");
+		let previous_song = |real_path: &PathBuf, fingerprint| Song {
+			real_path: real_path.clone(),
+			virtual_path: PathBuf::from("root")
+				.join("Khemmis")
+				.join("Hunted")
+				.join(real_path.file_name().unwrap()),
+			title: Some("Previous Title".to_owned()),
+			fingerprint,
+			..Default::default()
+		};
+		let change_detection = config::ChangeDetection::ModificationTime;
+		let previous_songs = HashMap::from([
+			(
+				unchanged_path.clone(),
+				previous_song(
+					&unchanged_path,
+					get_fingerprint(&unchanged_path, change_detection),
+				),
+			),
+			(changed_path.clone(), previous_song(&changed_path, Some(0))),
+		]);
+
+		let (directories_sender, _) = channel();
+		let (songs_sender, songs_receiver) = channel();
+		let parameters = Parameters {
+			artwork_regex: None,
+			mount_dirs: vec![config::MountDir {
+				source: ["test-data", "small-collection"].iter().collect(),
+				name: "root".to_owned(),
+				exclude_patterns: vec![],
+				symlinks: Default::default(),
+				split_artists: true,
+				remote: None,
+			}],
+			detect_silence: false,
+			num_threads: None,
+			change_detection,
+			filename_pattern: None,
+			artist_aliases: Default::default(),
+			album_aliases: Default::default(),
+			genre_aliases: Default::default(),
+			artist_separators: None,
+			musicbrainz_enrichment: false,
+		};
+
+		let scan = Scan::new(directories_sender, songs_sender, parameters)
+			.with_previous_songs(previous_songs);
+		scan.run().unwrap();
+
+		let songs = songs_receiver.iter().collect::<Vec<_>>();
+		assert_eq!(songs.len(), 13);
+		let title = |path: &PathBuf| {
+			songs
+				.iter()
+				.find(|s| s.real_path == *path)
+				.and_then(|s| s.title.clone())
+		};
+		assert_eq!(title(&unchanged_path), Some("Previous Title".to_owned()));
+		assert_eq!(title(&changed_path), Some("Candlelight".to_owned()));
+		assert!(songs.iter().all(|s| s.fingerprint.is_some()));
+	}
+
+	#[test]
+	fn content_fingerprint_ignores_modification_time() {
+		let test_directory = prepare_test_directory(test_name!());
+		let song_path = test_directory.join("song.mp3");
+		fs::copy(
+			[
+				"test-data",
+				"small-collection",
+				"Khemmis",
+				"Hunted",
+				"02 - Candlelight.mp3",
+			]
+			.iter()
+			.collect::<PathBuf>(),
+			&song_path,
+		)
+		.unwrap();
+
+		let fingerprints = || {
+			(
+				get_fingerprint(&song_path, config::ChangeDetection::ModificationTime).unwrap(),
+				get_fingerprint(&song_path, config::ChangeDetection::ContentHash).unwrap(),
+			)
+		};
+
+		let (mtime_before, content_before) = fingerprints();
+		fs::File::options()
+			.write(true)
+			.open(&song_path)
+			.unwrap()
+			.set_modified(UNIX_EPOCH + Duration::from_secs(1_000_000))
+			.unwrap();
+		let (mtime_after, content_after) = fingerprints();
+		assert_ne!(mtime_before, mtime_after);
+		assert_eq!(content_before, content_after);
+
+		let mut content = fs::read(&song_path).unwrap();
+		let last = content.len() - 1;
+		content[last] ^= 0xff;
+		fs::write(&song_path, content).unwrap();
+		let (_, content_changed) = fingerprints();
+		assert_ne!(content_before, content_changed);
+	}
+
 	#[tokio::test]
 	async fn scan_finds_embedded_artwork() {
 		let (directories_sender, _) = channel();
@@ -547,7 +2216,20 @@ mod test {
 			mount_dirs: vec![config::MountDir {
 				source: ["test-data", "small-collection"].iter().collect(),
 				name: "root".to_owned(),
+				exclude_patterns: vec![],
+				symlinks: Default::default(),
+				split_artists: true,
+				remote: None,
 			}],
+			detect_silence: false,
+			num_threads: None,
+			change_detection: Default::default(),
+			filename_pattern: None,
+			artist_aliases: Default::default(),
+			album_aliases: Default::default(),
+			genre_aliases: Default::default(),
+			artist_separators: None,
+			musicbrainz_enrichment: false,
 		};
 
 		let scan = Scan::new(directories_sender, songs_sender, parameters);
@@ -560,6 +2242,512 @@ mod test {
 			.any(|s| s.artwork.as_ref() == Some(&s.virtual_path));
 	}
 
+	#[tokio::test]
+	async fn scan_detects_silence() {
+		let (directories_sender, _) = channel();
+		let (songs_sender, songs_receiver) = channel();
+		let parameters = Parameters {
+			artwork_regex: None,
+			mount_dirs: vec![config::MountDir {
+				source: ["test-data", "formats"].iter().collect(),
+				name: "root".to_owned(),
+				exclude_patterns: vec![],
+				symlinks: Default::default(),
+				split_artists: true,
+				remote: None,
+			}],
+			detect_silence: true,
+			num_threads: None,
+			change_detection: Default::default(),
+			filename_pattern: None,
+			artist_aliases: Default::default(),
+			album_aliases: Default::default(),
+			genre_aliases: Default::default(),
+			artist_separators: None,
+			musicbrainz_enrichment: false,
+		};
+
+		let scan = Scan::new(directories_sender, songs_sender, parameters);
+		scan.run().unwrap();
+
+		let songs = songs_receiver.iter().collect::<Vec<_>>();
+		assert!(songs
+			.iter()
+			.any(|s| s.audible_range.is_some_and(|r| r.start <= r.end)));
+	}
+
+	#[tokio::test]
+	async fn scan_reads_metadata_from_filename() {
+		let test_directory = prepare_test_directory(test_name!());
+		let album_directory = test_directory.join("Khemmis").join("Hunted");
+		fs::create_dir_all(&album_directory).unwrap();
+		let song_path = album_directory.join("02 - Candlelight.mp3");
+		fs::copy(
+			PathBuf::from_iter(["test-data", "formats", "sample.mp3"]),
+			&song_path,
+		)
+		.unwrap();
+		id3::Tag::remove_from_path(&song_path).unwrap();
+
+		let (directories_sender, _) = channel();
+		let (songs_sender, songs_receiver) = channel();
+		let parameters = Parameters {
+			artwork_regex: None,
+			mount_dirs: vec![config::MountDir {
+				source: test_directory,
+				name: "root".to_owned(),
+				exclude_patterns: vec![],
+				symlinks: Default::default(),
+				split_artists: true,
+				remote: None,
+			}],
+			detect_silence: false,
+			num_threads: None,
+			change_detection: Default::default(),
+			filename_pattern: Some(
+				formats::FilenamePattern::new("%artist%/%album%/%track% - %title%").unwrap(),
+			),
+			artist_aliases: Default::default(),
+			album_aliases: Default::default(),
+			genre_aliases: Default::default(),
+			artist_separators: None,
+			musicbrainz_enrichment: false,
+		};
+
+		let scan = Scan::new(directories_sender, songs_sender, parameters);
+		scan.run().unwrap();
+
+		let songs = songs_receiver.iter().collect::<Vec<_>>();
+		assert_eq!(songs.len(), 1);
+		assert_eq!(songs[0].artists, vec!["Khemmis".to_owned()]);
+		assert_eq!(songs[0].album, Some("Hunted".to_owned()));
+		assert_eq!(songs[0].track_number, Some(2));
+		assert_eq!(songs[0].title, Some("Candlelight".to_owned()));
+	}
+
+	#[cfg(unix)]
+	#[tokio::test]
+	async fn scan_applies_symlink_policy() {
+		use std::os::unix::fs::symlink;
+
+		let test_directory = std::path::absolute(prepare_test_directory(test_name!())).unwrap();
+		let music_directory = test_directory.join("music");
+		let album_directory = music_directory.join("Hunted");
+		let outside_directory = test_directory.join("elsewhere").join("Picnic");
+		fs::create_dir_all(&album_directory).unwrap();
+		fs::create_dir_all(&outside_directory).unwrap();
+		let sample = PathBuf::from_iter(["test-data", "formats", "sample.mp3"]);
+		fs::copy(&sample, album_directory.join("song.mp3")).unwrap();
+		fs::copy(&sample, outside_directory.join("song.mp3")).unwrap();
+		symlink(&music_directory, album_directory.join("loop")).unwrap();
+		symlink(&outside_directory, music_directory.join("Picnic")).unwrap();
+		symlink(
+			album_directory.join("song.mp3"),
+			music_directory.join("single.mp3"),
+		)
+		.unwrap();
+
+		for (symlinks, expected_songs) in [
+			(config::SymlinkPolicy::Follow, 3),
+			(config::SymlinkPolicy::Files, 2),
+			(config::SymlinkPolicy::Ignore, 1),
+		] {
+			let (directories_sender, _) = channel();
+			let (songs_sender, songs_receiver) = channel();
+			let parameters = Parameters {
+				artwork_regex: None,
+				mount_dirs: vec![config::MountDir {
+					source: music_directory.clone(),
+					name: "root".to_owned(),
+					exclude_patterns: vec![],
+					symlinks,
+					split_artists: true,
+					remote: None,
+				}],
+				detect_silence: false,
+				num_threads: None,
+				change_detection: Default::default(),
+				filename_pattern: None,
+				artist_aliases: Default::default(),
+				album_aliases: Default::default(),
+				genre_aliases: Default::default(),
+				artist_separators: None,
+				musicbrainz_enrichment: false,
+			};
+
+			let scan = Scan::new(directories_sender, songs_sender, parameters);
+			scan.run().unwrap();
+
+			let songs = songs_receiver.iter().collect::<Vec<_>>();
+			assert_eq!(songs.len(), expected_songs, "{symlinks:?}");
+		}
+	}
+
+	#[tokio::test]
+	async fn scan_splits_cue_sheets() {
+		let test_directory = prepare_test_directory(test_name!());
+		fs::copy(
+			PathBuf::from_iter(["test-data", "formats", "sample.flac"]),
+			test_directory.join("Dreamspace.flac"),
+		)
+		.unwrap();
+		fs::write(
+			test_directory.join("Dreamspace.cue"),
+			r#"PERFORMER "Stratovarius"
+TITLE "Dreamspace"
+FILE "Dreamspace.wav" WAVE
+  TRACK 01 AUDIO
+    TITLE "Chasing Shadows"
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE "4th Reich"
+    INDEX 01 01:00:00
+"#,
+		)
+		.unwrap();
+
+		let (directories_sender, directories_receiver) = channel();
+		let (songs_sender, songs_receiver) = channel();
+		let parameters = Parameters {
+			artwork_regex: None,
+			mount_dirs: vec![config::MountDir {
+				source: test_directory,
+				name: "root".to_owned(),
+				exclude_patterns: vec![],
+				symlinks: Default::default(),
+				split_artists: true,
+				remote: None,
+			}],
+			detect_silence: false,
+			num_threads: None,
+			change_detection: Default::default(),
+			filename_pattern: None,
+			artist_aliases: Default::default(),
+			album_aliases: Default::default(),
+			genre_aliases: Default::default(),
+			artist_separators: None,
+			musicbrainz_enrichment: false,
+		};
+
+		let scan = Scan::new(directories_sender, songs_sender, parameters);
+		scan.run().unwrap();
+
+		let directories = directories_receiver.iter().collect::<Vec<_>>();
+		assert!(directories.contains(&Directory {
+			virtual_path: PathBuf::from_iter(["root", "Dreamspace.flac"]),
+		}));
+
+		let mut songs = songs_receiver.iter().collect::<Vec<_>>();
+		songs.sort_by_key(|s| s.track_number);
+		assert_eq!(songs.len(), 2);
+		assert_eq!(
+			songs[0].virtual_path,
+			PathBuf::from_iter(["root", "Dreamspace.flac", "01 - Chasing Shadows"])
+		);
+		assert_eq!(songs[0].title, Some("Chasing Shadows".to_owned()));
+		assert_eq!(songs[0].artists, vec!["Stratovarius".to_owned()]);
+		assert_eq!(songs[0].album, Some("Dreamspace".to_owned()));
+		assert_eq!(songs[0].duration, Some(60));
+		assert_eq!(
+			songs[1].segment,
+			Some(formats::Segment {
+				start: 60_000,
+				end: None
+			})
+		);
+		assert!(songs.iter().all(|s| s.real_path == songs[0].real_path));
+	}
+
+	#[tokio::test]
+	async fn scan_applies_aliases() {
+		let (directories_sender, _) = channel();
+		let (songs_sender, songs_receiver) = channel();
+		let parameters = Parameters {
+			artwork_regex: None,
+			mount_dirs: vec![config::MountDir {
+				source: ["test-data", "small-collection", "Khemmis"]
+					.iter()
+					.collect(),
+				name: "root".to_owned(),
+				exclude_patterns: vec![],
+				symlinks: Default::default(),
+				split_artists: true,
+				remote: None,
+			}],
+			detect_silence: false,
+			num_threads: None,
+			change_detection: Default::default(),
+			filename_pattern: None,
+			artist_aliases: config::AliasTable::new(&[config::Alias {
+				name: "Khemmis (US)".to_owned(),
+				aliases: vec!["khemmis".to_owned()],
+			}]),
+			album_aliases: config::AliasTable::new(&[config::Alias {
+				name: "Hunted (Deluxe)".to_owned(),
+				aliases: vec!["Hunted".to_owned()],
+			}]),
+			genre_aliases: config::AliasTable::new(&[config::Alias {
+				name: "Doom".to_owned(),
+				aliases: vec!["doom metal".to_owned()],
+			}]),
+			artist_separators: None,
+			musicbrainz_enrichment: false,
+		};
+
+		let scan = Scan::new(directories_sender, songs_sender, parameters);
+		scan.run().unwrap();
+
+		let songs = songs_receiver.iter().collect::<Vec<_>>();
+		assert_eq!(songs.len(), 5);
+		for song in songs {
+			assert_eq!(song.artists, vec!["Khemmis (US)".to_owned()]);
+			assert_eq!(song.album, Some("Hunted (Deluxe)".to_owned()));
+			assert_eq!(song.genres, vec!["Metal".to_owned(), "Doom".to_owned()]);
+		}
+	}
+
+	#[tokio::test]
+	async fn scan_splits_artists() {
+		use id3::TagLike;
+
+		let test_directory = prepare_test_directory(test_name!());
+		for mount in ["split", "whole"] {
+			let song_path = test_directory.join(mount).join("sample.mp3");
+			fs::create_dir_all(song_path.parent().unwrap()).unwrap();
+			fs::copy(
+				PathBuf::from_iter(["test-data", "formats", "sample.mp3"]),
+				&song_path,
+			)
+			.unwrap();
+			let mut tag = id3::Tag::read_from_path(&song_path).unwrap();
+			tag.set_artist("Khemmis Feat. Ben Hutcherson; AC/DC");
+			tag.write_to_path(&song_path, id3::Version::Id3v24).unwrap();
+		}
+
+		let (directories_sender, _) = channel();
+		let (songs_sender, songs_receiver) = channel();
+		let parameters = Parameters {
+			artwork_regex: None,
+			mount_dirs: ["split", "whole"]
+				.into_iter()
+				.map(|mount| config::MountDir {
+					source: test_directory.join(mount),
+					name: mount.to_owned(),
+					exclude_patterns: vec![],
+					symlinks: Default::default(),
+					split_artists: mount == "split",
+					remote: None,
+				})
+				.collect(),
+			detect_silence: false,
+			num_threads: None,
+			change_detection: Default::default(),
+			filename_pattern: None,
+			artist_aliases: Default::default(),
+			album_aliases: Default::default(),
+			genre_aliases: Default::default(),
+			artist_separators: build_artist_separators(&[";".to_owned(), "feat.".to_owned()]),
+			musicbrainz_enrichment: false,
+		};
+
+		let scan = Scan::new(directories_sender, songs_sender, parameters);
+		scan.run().unwrap();
+
+		let songs = songs_receiver.iter().collect::<Vec<_>>();
+		let artists = |mount: &str| {
+			songs
+				.iter()
+				.find(|s| s.virtual_path.starts_with(mount))
+				.map(|s| s.artists.clone())
+				.unwrap()
+		};
+		assert_eq!(
+			artists("split"),
+			vec![
+				"Khemmis".to_owned(),
+				"Ben Hutcherson".to_owned(),
+				"AC/DC".to_owned()
+			]
+		);
+		assert_eq!(
+			artists("whole"),
+			vec!["Khemmis Feat. Ben Hutcherson; AC/DC".to_owned()]
+		);
+	}
+
+	#[tokio::test]
+	async fn scan_finds_lyrics_files() {
+		let test_directory = prepare_test_directory(test_name!());
+		for name in ["with_lyrics", "without_lyrics"] {
+			fs::copy(
+				PathBuf::from_iter(["test-data", "formats", "sample.mp3"]),
+				test_directory.join(format!("{name}.mp3")),
+			)
+			.unwrap();
+		}
+		fs::write(
+			test_directory.join("with_lyrics.lrc"),
+			"[00:01.00]Destiny, let me know my destiny",
+		)
+		.unwrap();
+
+		let (directories_sender, _) = channel();
+		let (songs_sender, songs_receiver) = channel();
+		let parameters = Parameters {
+			artwork_regex: None,
+			mount_dirs: vec![config::MountDir {
+				source: test_directory,
+				name: "root".to_owned(),
+				exclude_patterns: vec![],
+				symlinks: Default::default(),
+				split_artists: true,
+				remote: None,
+			}],
+			detect_silence: false,
+			num_threads: None,
+			change_detection: Default::default(),
+			filename_pattern: None,
+			artist_aliases: Default::default(),
+			album_aliases: Default::default(),
+			genre_aliases: Default::default(),
+			artist_separators: None,
+			musicbrainz_enrichment: false,
+		};
+
+		let scan = Scan::new(directories_sender, songs_sender, parameters);
+		scan.run().unwrap();
+
+		let songs = songs_receiver.iter().collect::<Vec<_>>();
+		assert_eq!(songs.len(), 2);
+		let lyrics = |name: &str| {
+			songs
+				.iter()
+				.find(|s| s.virtual_path == PathBuf::from_iter(["root", name]))
+				.map(|s| s.lyrics.clone())
+				.unwrap()
+		};
+		assert_eq!(
+			lyrics("with_lyrics.mp3"),
+			Some(PathBuf::from_iter(["root", "with_lyrics.lrc"]))
+		);
+		assert_eq!(lyrics("without_lyrics.mp3"), None);
+	}
+
+	#[tokio::test]
+	async fn folder_artwork_takes_precedence_over_embedded_artwork() {
+		let test_directory = prepare_test_directory(test_name!());
+		for name in ["with_folder", "without_folder"] {
+			fs::create_dir(test_directory.join(name)).unwrap();
+			fs::copy(
+				PathBuf::from_iter(["test-data", "artwork", "sample.mp3"]),
+				test_directory.join(name).join("sample.mp3"),
+			)
+			.unwrap();
+		}
+		fs::copy(
+			PathBuf::from_iter(["test-data", "artwork", "Folder.png"]),
+			test_directory.join("with_folder").join("Folder.png"),
+		)
+		.unwrap();
+
+		let (directories_sender, _) = channel();
+		let (songs_sender, songs_receiver) = channel();
+		let parameters = Parameters {
+			artwork_regex: Some(Regex::new("(?i)folder.(jpeg|jpg|png)").unwrap()),
+			mount_dirs: vec![config::MountDir {
+				source: test_directory,
+				name: "root".to_owned(),
+				exclude_patterns: vec![],
+				symlinks: Default::default(),
+				split_artists: true,
+				remote: None,
+			}],
+			detect_silence: false,
+			num_threads: None,
+			change_detection: Default::default(),
+			filename_pattern: None,
+			artist_aliases: Default::default(),
+			album_aliases: Default::default(),
+			genre_aliases: Default::default(),
+			artist_separators: None,
+			musicbrainz_enrichment: false,
+		};
+
+		let scan = Scan::new(directories_sender, songs_sender, parameters);
+		scan.run().unwrap();
+
+		let songs = songs_receiver.iter().collect::<Vec<_>>();
+		assert_eq!(songs.len(), 2);
+		let artwork = |directory: &str| {
+			songs
+				.iter()
+				.find(|s| s.virtual_path == PathBuf::from_iter(["root", directory, "sample.mp3"]))
+				.map(|s| s.artwork.clone())
+				.unwrap()
+		};
+		assert_eq!(
+			artwork("with_folder"),
+			Some(PathBuf::from_iter(["root", "with_folder", "Folder.png"]))
+		);
+		assert_eq!(
+			artwork("without_folder"),
+			Some(PathBuf::from_iter(["root", "without_folder", "sample.mp3"]))
+		);
+	}
+
+	#[test]
+	fn discs_are_grouped_into_albums() {
+		let path = PathBuf::from_iter(["root", "Mellon Collie", "01 - Mellon Collie.mp3"]);
+		for (album, expected_album, expected_disc) in [
+			("Mellon Collie (Disc 2)", "Mellon Collie", Some(2)),
+			("Mellon Collie [CD 1 of 2]", "Mellon Collie", Some(1)),
+			("Mellon Collie - disk2", "Mellon Collie", Some(2)),
+			("ABCD2", "ABCD2", None),
+			("Disc 2", "Disc 2", None),
+		] {
+			let mut metadata = formats::SongMetadata {
+				album: Some(album.to_owned()),
+				..Default::default()
+			};
+			apply_disc_number(&mut metadata, &path);
+			assert_eq!(metadata.album.as_deref(), Some(expected_album));
+			assert_eq!(metadata.disc_number, expected_disc);
+		}
+
+		let mut metadata = formats::SongMetadata {
+			album: Some("Mellon Collie (CD2)".to_owned()),
+			disc_number: Some(3),
+			..Default::default()
+		};
+		apply_disc_number(&mut metadata, &path);
+		assert_eq!(metadata.disc_number, Some(3));
+
+		let path = PathBuf::from_iter(["root", "Mellon Collie", "CD2", "01 - Tonight.mp3"]);
+		let mut metadata = formats::SongMetadata::default();
+		apply_disc_number(&mut metadata, &path);
+		assert_eq!(metadata.disc_number, Some(2));
+	}
+
+	#[test]
+	fn compilations_are_grouped_under_various_artists() {
+		let mut metadata = formats::SongMetadata {
+			artists: vec!["Stratovarius".to_owned()],
+			compilation: true,
+			..Default::default()
+		};
+		apply_compilation(&mut metadata);
+		assert_eq!(metadata.album_artists, vec!["Various Artists".to_owned()]);
+
+		let mut metadata = formats::SongMetadata {
+			artists: vec!["Stratovarius".to_owned()],
+			album_artists: vec!["Timo Tolkki".to_owned()],
+			compilation: true,
+			..Default::default()
+		};
+		apply_compilation(&mut metadata);
+		assert_eq!(metadata.album_artists, vec!["Timo Tolkki".to_owned()]);
+	}
+
 	#[tokio::test]
 	async fn album_art_pattern_is_case_insensitive() {
 		let artwork_path = PathBuf::from_iter(["root", "Khemmis", "Hunted", "Folder.jpg"]);
@@ -572,7 +2760,20 @@ mod test {
 				mount_dirs: vec![config::MountDir {
 					source: ["test-data", "small-collection"].iter().collect(),
 					name: "root".to_owned(),
+					exclude_patterns: vec![],
+					symlinks: Default::default(),
+					split_artists: true,
+					remote: None,
 				}],
+				detect_silence: false,
+				num_threads: None,
+				change_detection: Default::default(),
+				filename_pattern: None,
+				artist_aliases: Default::default(),
+				album_aliases: Default::default(),
+				genre_aliases: Default::default(),
+				artist_separators: None,
+				musicbrainz_enrichment: false,
 			};
 
 			let scan = Scan::new(directories_sender, songs_sender, parameters);
@@ -586,6 +2787,382 @@ mod test {
 		}
 	}
 
+	#[tokio::test]
+	async fn refresh_fails_while_scan_is_paused() {
+		let builder = test::ContextBuilder::new(test_name!());
+		let music_directory = builder.test_directory.join("music");
+		let ctx = builder
+			.mount("root", music_directory.to_str().unwrap())
+			.build()
+			.await;
+		fs::create_dir_all(music_directory.join("Hunted")).unwrap();
+		let hunted = PathBuf::from_iter(["root", "Hunted"]);
+
+		// Stands in for a scan in progress
+		let scan = ctx.scanner.update_lock.clone().lock_owned().await;
+		ctx.scanner.status.write().await.state = State::InProgress;
+
+		let refresh = tokio::spawn({
+			let scanner = ctx.scanner.clone();
+			let hunted = hunted.clone();
+			async move { scanner.refresh_directory(hunted).await }
+		});
+		tokio::time::sleep(Duration::from_millis(100)).await;
+		assert!(!refresh.is_finished());
+
+		ctx.scanner.pause_scan().await.unwrap();
+		let result = tokio::time::timeout(Duration::from_secs(5), refresh)
+			.await
+			.unwrap()
+			.unwrap();
+		assert!(matches!(result, Err(Error::ScanPaused)));
+
+		let result = tokio::time::timeout(
+			Duration::from_secs(5),
+			ctx.scanner.refresh_directory(hunted.clone()),
+		)
+		.await
+		.unwrap();
+		assert!(matches!(result, Err(Error::ScanPaused)));
+
+		ctx.scanner.resume_scan().await.unwrap();
+		drop(scan);
+		assert!(ctx.scanner.refresh_directory(hunted).await.is_ok());
+	}
+
+	#[tokio::test]
+	async fn can_refresh_single_directory() {
+		let builder = test::ContextBuilder::new(test_name!());
+		let music_directory = builder.test_directory.join("music");
+		let ctx = builder
+			.mount("root", music_directory.to_str().unwrap())
+			.build()
+			.await;
+
+		let sample = PathBuf::from_iter(["test-data", "formats", "sample.mp3"]);
+		for album in ["Hunted", "Desolation"] {
+			fs::create_dir_all(music_directory.join(album)).unwrap();
+		}
+		fs::copy(&sample, music_directory.join("Hunted").join("sample.mp3")).unwrap();
+		ctx.scanner.run_scan().await.unwrap();
+
+		fs::copy(
+			&sample,
+			music_directory.join("Desolation").join("sample.mp3"),
+		)
+		.unwrap();
+		let songs = ctx
+			.scanner
+			.refresh_directory(PathBuf::from_iter(["root", "Desolation"]))
+			.await
+			.unwrap();
+		assert_eq!(
+			songs,
+			vec![PathBuf::from_iter(["root", "Desolation", "sample.mp3"])]
+		);
+
+		let all_songs = ctx
+			.index_manager
+			.flatten(PathBuf::from("root"))
+			.await
+			.unwrap();
+		assert_eq!(all_songs.len(), 2);
+
+		fs::remove_file(music_directory.join("Hunted").join("sample.mp3")).unwrap();
+		let songs = ctx
+			.scanner
+			.refresh_directory(PathBuf::from_iter(["root", "Hunted"]))
+			.await
+			.unwrap();
+		assert!(songs.is_empty());
+
+		let all_songs = ctx
+			.index_manager
+			.flatten(PathBuf::from("root"))
+			.await
+			.unwrap();
+		assert_eq!(all_songs.len(), 1);
+	}
+
+	#[tokio::test]
+	async fn concurrent_refreshes_are_all_applied() {
+		let builder = test::ContextBuilder::new(test_name!());
+		let music_directory = builder.test_directory.join("music");
+		let ctx = builder
+			.mount("root", music_directory.to_str().unwrap())
+			.build()
+			.await;
+
+		let sample = PathBuf::from_iter(["test-data", "formats", "sample.mp3"]);
+		let albums = ["Hunted", "Desolation", "Deceiver", "Absolution"];
+		for album in albums {
+			fs::create_dir_all(music_directory.join(album)).unwrap();
+		}
+		ctx.scanner.run_scan().await.unwrap();
+
+		for album in albums {
+			fs::copy(&sample, music_directory.join(album).join("sample.mp3")).unwrap();
+		}
+		let refreshes = albums.map(|album| {
+			let scanner = ctx.scanner.clone();
+			tokio::spawn(async move {
+				scanner
+					.refresh_directory(PathBuf::from_iter(["root", album]))
+					.await
+			})
+		});
+		for refresh in refreshes {
+			refresh.await.unwrap().unwrap();
+		}
+
+		let all_songs = ctx
+			.index_manager
+			.flatten(PathBuf::from("root"))
+			.await
+			.unwrap();
+		assert_eq!(all_songs.len(), albums.len());
+	}
+
+	#[tokio::test]
+	async fn date_added_persists_across_scans() {
+		let builder = test::ContextBuilder::new(test_name!());
+		let music_directory = builder.test_directory.join("music");
+		let ctx = builder
+			.mount("root", music_directory.to_str().unwrap())
+			.build()
+			.await;
+
+		let sample = PathBuf::from_iter(["test-data", "formats", "sample.mp3"]);
+		let song_path = music_directory.join("Hunted").join("sample.mp3");
+		fs::create_dir_all(music_directory.join("Hunted")).unwrap();
+		fs::copy(&sample, &song_path).unwrap();
+		ctx.scanner.run_scan().await.unwrap();
+
+		async fn get_date_added(index_manager: &index::Manager) -> i64 {
+			let virtual_path = PathBuf::from_iter(["root", "Hunted", "sample.mp3"]);
+			let songs = index_manager.get_songs(vec![virtual_path]).await;
+			songs.into_iter().next().unwrap().unwrap().date_added
+		}
+		let date_added = get_date_added(&ctx.index_manager).await;
+
+		// Re-creating the file gives it a new creation time
+		std::thread::sleep(Duration::from_millis(1100));
+		fs::remove_file(&song_path).unwrap();
+		fs::copy(&sample, &song_path).unwrap();
+		ctx.scanner.run_scan().await.unwrap();
+		assert_eq!(get_date_added(&ctx.index_manager).await, date_added);
+
+		fs::remove_file(&song_path).unwrap();
+		fs::copy(&sample, &song_path).unwrap();
+		ctx.scanner
+			.refresh_directory(PathBuf::from_iter(["root", "Hunted"]))
+			.await
+			.unwrap();
+		assert_eq!(get_date_added(&ctx.index_manager).await, date_added);
+	}
+
+	#[test]
+	fn configured_thread_count_takes_precedence() {
+		assert_eq!(get_num_traverser_threads(NonZeroUsize::new(3)), 3);
+		assert!(get_num_traverser_threads(None) > 0);
+	}
+
+	#[test]
+	fn finds_directories_to_refresh() {
+		let test_directory = std::path::absolute(prepare_test_directory(test_name!())).unwrap();
+		let album_directory = test_directory.join("Khemmis").join("Hunted");
+		fs::create_dir_all(&album_directory).unwrap();
+		let mount_dirs = vec![config::MountDir {
+			source: test_directory.clone(),
+			name: "root".to_owned(),
+			exclude_patterns: vec![],
+			symlinks: Default::default(),
+			split_artists: true,
+			remote: None,
+		}];
+
+		let directories = get_directories_to_refresh(
+			&mount_dirs,
+			HashSet::from([
+				album_directory.join("01 - Above The Water.mp3"),
+				album_directory.join("Deleted").join("song.mp3"),
+				PathBuf::from_iter(["somewhere", "else.mp3"]),
+			]),
+		);
+		assert_eq!(
+			directories,
+			vec![PathBuf::from_iter(["root", "Khemmis", "Hunted"])]
+		);
+
+		let directories = get_directories_to_refresh(
+			&mount_dirs,
+			HashSet::from([
+				album_directory.clone(),
+				test_directory.join("Khemmis"),
+				test_directory.join("cover.jpg"),
+			]),
+		);
+		assert_eq!(directories, vec![PathBuf::from("root")]);
+	}
+
+	#[tokio::test]
+	async fn scanner_applies_file_changes() {
+		let builder = test::ContextBuilder::new(test_name!());
+		let music_directory = builder.test_directory.join("music");
+		fs::create_dir_all(music_directory.join("Hunted")).unwrap();
+		let ctx = builder
+			.mount("root", music_directory.to_str().unwrap())
+			.build()
+			.await;
+
+		let sample = PathBuf::from_iter(["test-data", "formats", "sample.mp3"]);
+		fs::copy(&sample, music_directory.join("Hunted").join("sample.mp3")).unwrap();
+		ctx.scanner.run_scan().await.unwrap();
+
+		fs::create_dir_all(music_directory.join("Desolation")).unwrap();
+		fs::copy(
+			&sample,
+			music_directory.join("Desolation").join("sample.mp3"),
+		)
+		.unwrap();
+
+		tokio::time::timeout(Duration::from_secs(10), async {
+			loop {
+				tokio::time::sleep(Duration::from_millis(100)).await;
+				let songs = ctx.index_manager.flatten(PathBuf::from("root")).await;
+				if songs.is_ok_and(|s| s.len() == 2) {
+					break;
+				}
+			}
+		})
+		.await
+		.expect("Index did not pick up new song");
+		assert!(matches!(
+			ctx.scanner.get_status().await.state,
+			State::UpToDate
+		));
+	}
+
+	#[tokio::test]
+	async fn scan_skips_unchanged_directories() {
+		let builder = test::ContextBuilder::new(test_name!());
+		let music_directory = builder.test_directory.join("music");
+		let album_directory = music_directory.join("Hunted");
+		fs::create_dir_all(&album_directory).unwrap();
+		let ctx = builder
+			.mount("root", music_directory.to_str().unwrap())
+			.change_detection(config::ChangeDetection::DirectoryModificationTime)
+			.build()
+			.await;
+
+		let source = PathBuf::from_iter(["test-data", "small-collection", "Khemmis", "Hunted"]);
+		let song_path = album_directory.join("song.mp3");
+		let get_titles = || async {
+			let mut titles = ctx
+				.index_manager
+				.get_all_songs()
+				.await
+				.into_iter()
+				.filter_map(|s| s.title)
+				.collect::<Vec<_>>();
+			titles.sort();
+			titles
+		};
+
+		fs::copy(source.join("02 - Candlelight.mp3"), &song_path).unwrap();
+		ctx.scanner.run_scan().await.unwrap();
+		assert_eq!(get_titles().await, vec!["Candlelight".to_owned()]);
+
+		// Overwriting a file does not change the modification time of its directory
+		fs::copy(source.join("03 - Three Gates.mp3"), &song_path).unwrap();
+		ctx.scanner.run_scan().await.unwrap();
+		assert_eq!(get_titles().await, vec!["Candlelight".to_owned()]);
+
+		fs::copy(
+			source.join("05 - Hunted.mp3"),
+			album_directory.join("other.mp3"),
+		)
+		.unwrap();
+		ctx.scanner.run_scan().await.unwrap();
+		assert_eq!(
+			get_titles().await,
+			vec!["Hunted".to_owned(), "Three Gates".to_owned()]
+		);
+	}
+
+	#[tokio::test]
+	async fn scan_reports_changes_and_errors() {
+		let builder = test::ContextBuilder::new(test_name!());
+		let music_directory = builder.test_directory.join("music");
+		fs::create_dir_all(&music_directory).unwrap();
+		let ctx = builder
+			.mount("root", music_directory.to_str().unwrap())
+			.build()
+			.await;
+
+		let source = PathBuf::from_iter(["test-data", "small-collection", "Khemmis", "Hunted"]);
+		fs::copy(
+			source.join("02 - Candlelight.mp3"),
+			music_directory.join("a.mp3"),
+		)
+		.unwrap();
+		fs::copy(
+			source.join("03 - Three Gates.mp3"),
+			music_directory.join("b.mp3"),
+		)
+		.unwrap();
+		fs::write(music_directory.join("broken.flac"), "not an mp3").unwrap();
+		ctx.scanner.run_scan().await.unwrap();
+
+		let status = ctx.scanner.get_status().await;
+		assert_eq!(status.num_songs_added, 2);
+		assert_eq!(status.num_songs_updated, 0);
+		assert_eq!(status.num_songs_removed, 0);
+		assert_eq!(status.num_errors, 1);
+		assert_eq!(
+			status.errors[0].virtual_path,
+			PathBuf::from_iter(["root", "broken.flac"])
+		);
+
+		fs::remove_file(music_directory.join("broken.flac")).unwrap();
+		fs::remove_file(music_directory.join("a.mp3")).unwrap();
+		fs::copy(
+			source.join("05 - Hunted.mp3"),
+			music_directory.join("b.mp3"),
+		)
+		.unwrap();
+		fs::copy(
+			source.join("02 - Candlelight.mp3"),
+			music_directory.join("c.mp3"),
+		)
+		.unwrap();
+		ctx.scanner.run_scan().await.unwrap();
+
+		let status = ctx.scanner.get_status().await;
+		assert_eq!(status.num_songs_added, 1);
+		assert_eq!(status.num_songs_updated, 1);
+		assert_eq!(status.num_songs_removed, 1);
+		assert_eq!(status.num_errors, 0);
+		assert!(status.errors.is_empty());
+	}
+
+	#[tokio::test]
+	async fn scan_reports_progress() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.mount("root", "test-data/small-collection")
+			.build()
+			.await;
+
+		ctx.scanner.run_scan().await.unwrap();
+
+		let status = ctx.scanner.get_status().await;
+		assert!(matches!(status.state, State::UpToDate));
+		assert_eq!(status.phase, None);
+		assert_eq!(status.num_songs_indexed, 13);
+		assert_eq!(status.num_directories_scanned, 6);
+		assert!(status.elapsed().is_some());
+	}
+
 	#[tokio::test]
 	async fn scanner_reacts_to_config_changes() {
 		let ctx = test::ContextBuilder::new(test_name!()).build().await;
@@ -596,6 +3173,10 @@ mod test {
 			.set_mounts(vec![config::storage::MountDir {
 				source: ["test-data", "small-collection"].iter().collect(),
 				name: "root".to_owned(),
+				exclude_patterns: vec![],
+				symlinks: None,
+				split_artists: None,
+				remote: None,
 			}])
 			.await
 			.unwrap();
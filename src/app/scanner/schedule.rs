@@ -0,0 +1,208 @@
+use time::{Date, OffsetDateTime, PrimitiveDateTime, Time};
+
+use crate::app::Error;
+
+// How far ahead to look for a matching day (covers schedules like `0 0 29 2 *`)
+const MAX_DAYS_AHEAD: usize = 366 * 8;
+
+// Times at which collection scans start, written as cron expressions like `0 4 * * *`.
+// Fields are minute, hour, day of month, month and day of week (0 or 7 being Sunday). Each
+// field accepts `*`, numbers, ranges (`1-5`), steps (`*/15`, `0-30/10`) and lists (`1,15`).
+// Shortcuts `@hourly`, `@daily`, `@weekly`, `@monthly` and `@yearly` are also supported.
+// Times are in UTC.
+#[derive(Clone, Debug)]
+pub struct Schedule {
+	expression: String,
+	minutes: u64,
+	hours: u64,
+	days_of_month: u64,
+	months: u64,
+	days_of_week: u64,
+	// Cron matches days satisfying either field when both are restricted
+	any_day_of_month: bool,
+	any_day_of_week: bool,
+}
+
+impl PartialEq for Schedule {
+	fn eq(&self, other: &Self) -> bool {
+		self.expression == other.expression
+	}
+}
+
+impl Eq for Schedule {}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, Error> {
+	let mut bits = 0;
+	for part in field.split(',') {
+		let (range, step) = match part.split_once('/') {
+			Some((range, step)) => {
+				let step = step
+					.parse::<u32>()
+					.ok()
+					.filter(|s| *s > 0)
+					.ok_or(Error::ScanScheduleInvalid)?;
+				(range, step)
+			}
+			None => (part, 1),
+		};
+		let parse = |n: &str| {
+			n.parse::<u32>()
+				.ok()
+				.filter(|n| (min..=max).contains(n))
+				.ok_or(Error::ScanScheduleInvalid)
+		};
+		let (start, end) = match range {
+			"*" => (min, max),
+			_ => match range.split_once('-') {
+				Some((start, end)) => (parse(start)?, parse(end)?),
+				// `5/10` means every 10 units starting at 5
+				None if step > 1 => (parse(range)?, max),
+				None => (parse(range)?, parse(range)?),
+			},
+		};
+		if start > end {
+			return Err(Error::ScanScheduleInvalid);
+		}
+		for n in (start..=end).step_by(step as usize) {
+			bits |= 1 << n;
+		}
+	}
+	Ok(bits)
+}
+
+impl Schedule {
+	pub fn new(expression: &str) -> Result<Self, Error> {
+		let expression = expression.trim();
+		let fields = match expression {
+			"@hourly" => "0 * * * *",
+			"@daily" | "@midnight" => "0 0 * * *",
+			"@weekly" => "0 0 * * 0",
+			"@monthly" => "0 0 1 * *",
+			"@yearly" | "@annually" => "0 0 1 1 *",
+			e => e,
+		};
+		let fields = fields.split_whitespace().collect::<Vec<_>>();
+		let [minutes, hours, days_of_month, months, days_of_week] = fields[..] else {
+			return Err(Error::ScanScheduleInvalid);
+		};
+
+		let mut days_of_week_bits = parse_field(days_of_week, 0, 7)?;
+		// Both 0 and 7 stand for Sunday
+		if days_of_week_bits & (1 << 7) != 0 {
+			days_of_week_bits |= 1;
+		}
+
+		Ok(Self {
+			expression: expression.to_owned(),
+			minutes: parse_field(minutes, 0, 59)?,
+			hours: parse_field(hours, 0, 23)?,
+			days_of_month: parse_field(days_of_month, 1, 31)?,
+			months: parse_field(months, 1, 12)?,
+			days_of_week: days_of_week_bits,
+			any_day_of_month: days_of_month == "*",
+			any_day_of_week: days_of_week == "*",
+		})
+	}
+
+	pub fn as_str(&self) -> &str {
+		&self.expression
+	}
+
+	fn matches_day(&self, date: Date) -> bool {
+		if self.months & (1 << u8::from(date.month())) == 0 {
+			return false;
+		}
+		let day_of_month = self.days_of_month & (1 << date.day()) != 0;
+		let day_of_week = self.days_of_week & (1 << date.weekday().number_days_from_sunday()) != 0;
+		match (self.any_day_of_month, self.any_day_of_week) {
+			(true, true) => true,
+			(true, false) => day_of_week,
+			(false, true) => day_of_month,
+			(false, false) => day_of_month || day_of_week,
+		}
+	}
+
+	// First time matching the schedule strictly after `time`, rounded down to the minute
+	pub fn next_after(&self, time: OffsetDateTime) -> Option<OffsetDateTime> {
+		let time = time.to_offset(time::UtcOffset::UTC);
+		let mut date = time.date();
+		for _ in 0..MAX_DAYS_AHEAD {
+			if self.matches_day(date) {
+				for hour in (0..24u8).filter(|h| self.hours & (1 << h) != 0) {
+					for minute in (0..60u8).filter(|m| self.minutes & (1 << m) != 0) {
+						let candidate =
+							PrimitiveDateTime::new(date, Time::from_hms(hour, minute, 0).ok()?)
+								.assume_utc();
+						if candidate > time {
+							return Some(candidate);
+						}
+					}
+				}
+			}
+			date = date.next_day()?;
+		}
+		None
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn at(timestamp: i64) -> OffsetDateTime {
+		OffsetDateTime::from_unix_timestamp(timestamp).unwrap()
+	}
+
+	#[test]
+	fn rejects_invalid_expressions() {
+		for expression in [
+			"",
+			"* * * *",
+			"60 * * * *",
+			"* 24 * * *",
+			"* * 0 * *",
+			"* * * 13 *",
+			"* * * * 8",
+			"*/0 * * * *",
+			"5-1 * * * *",
+			"@sometimes",
+		] {
+			assert!(Schedule::new(expression).is_err(), "{expression}");
+		}
+	}
+
+	#[test]
+	fn finds_next_occurrence() {
+		// Monday 2024-01-01 10:30:00 UTC
+		let monday = at(1704105000);
+
+		let nightly = Schedule::new("0 4 * * *").unwrap();
+		// Tuesday 2024-01-02 04:00:00 UTC
+		assert_eq!(nightly.next_after(monday), Some(at(1704168000)));
+
+		let quarter_hours = Schedule::new("*/15 * * * *").unwrap();
+		// Monday 2024-01-01 10:45:00 UTC
+		assert_eq!(quarter_hours.next_after(monday), Some(at(1704105900)));
+
+		let weekends = Schedule::new("0 3 * * 6,7").unwrap();
+		// Saturday 2024-01-06 03:00:00 UTC
+		assert_eq!(weekends.next_after(monday), Some(at(1704510000)));
+
+		let monthly = Schedule::new("@monthly").unwrap();
+		// Thursday 2024-02-01 00:00:00 UTC
+		assert_eq!(monthly.next_after(monday), Some(at(1706745600)));
+	}
+
+	#[test]
+	fn matches_either_day_field() {
+		// 13th of the month, or any Friday
+		let schedule = Schedule::new("0 0 13 * 5").unwrap();
+		// From Monday 2024-01-01 10:30:00 UTC to Friday 2024-01-05 00:00:00 UTC
+		assert_eq!(schedule.next_after(at(1704105000)), Some(at(1704412800)));
+		// Wednesday 2024-01-10 00:00:00 UTC
+		let next = schedule.next_after(at(1704844800)).unwrap();
+		// Friday 2024-01-12 00:00:00 UTC, then Saturday 2024-01-13
+		assert_eq!(next, at(1705017600));
+		assert_eq!(schedule.next_after(next), Some(at(1705104000)));
+	}
+}
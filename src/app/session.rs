@@ -0,0 +1,687 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use native_db::*;
+use native_model::{native_model, Model};
+use rand::distributions::{Alphanumeric, DistString};
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use tokio::task::spawn_blocking;
+
+use crate::app::{audit, auth, config, hooks, ndb, throttle, Error};
+
+// Avoids writing to the database on every request
+const LAST_SEEN_RESOLUTION_SECONDS: i64 = 60;
+
+// Clients like WebDAV file managers send credentials with every request. Their successful logins
+// are only audited once in this period.
+const REPEATED_LOGIN_AUDIT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Clone)]
+pub struct Manager {
+	db: ndb::Manager,
+	audit_manager: audit::Manager,
+	config_manager: config::Manager,
+	hooks_manager: hooks::Manager,
+	failed_logins: Arc<Mutex<HashMap<String, FailedLogins>>>,
+	audited_logins: Arc<Mutex<HashMap<(String, Option<IpAddr>), Instant>>>,
+	throttle: throttle::Throttle,
+}
+
+// Consecutive failed logins of a user, kept in memory so lockouts end when the server restarts
+struct FailedLogins {
+	count: u32,
+	last_failure: Instant,
+	locked_until: Option<Instant>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Session {
+	pub id: String,
+	pub username: String,
+	pub client_name: Option<String>,
+	pub user_agent: Option<String>,
+	pub ip_address: Option<String>,
+	pub created_at: i64,
+	pub last_seen_at: i64,
+}
+
+// Describes the device signing in
+#[derive(Clone, Debug, Default)]
+pub struct Client {
+	pub name: Option<String>,
+	pub user_agent: Option<String>,
+	pub ip_address: Option<IpAddr>,
+}
+
+pub type SessionModel = v2::SessionModel;
+type SessionModelKey = v2::SessionModelKey;
+
+pub mod v1 {
+
+	use super::*;
+
+	#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+	#[native_model(id = 2, version = 1)]
+	#[native_db]
+	pub struct SessionModel {
+		#[primary_key]
+		pub id: String,
+		#[secondary_key]
+		pub username: String,
+		pub user_agent: Option<String>,
+		pub created_at: i64,
+		pub last_seen_at: i64,
+	}
+
+	impl From<v2::SessionModel> for SessionModel {
+		fn from(s: v2::SessionModel) -> Self {
+			Self {
+				id: s.id,
+				username: s.username,
+				user_agent: s.user_agent,
+				created_at: s.created_at,
+				last_seen_at: s.last_seen_at,
+			}
+		}
+	}
+}
+
+pub mod v2 {
+
+	use super::*;
+
+	#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+	#[native_model(id = 2, version = 2, from = v1::SessionModel)]
+	#[native_db]
+	pub struct SessionModel {
+		#[primary_key]
+		pub id: String,
+		#[secondary_key]
+		pub username: String,
+		pub client_name: Option<String>,
+		pub user_agent: Option<String>,
+		pub ip_address: Option<String>,
+		pub created_at: i64,
+		pub last_seen_at: i64,
+	}
+
+	impl From<v1::SessionModel> for SessionModel {
+		fn from(s: v1::SessionModel) -> Self {
+			Self {
+				id: s.id,
+				username: s.username,
+				client_name: None,
+				user_agent: s.user_agent,
+				ip_address: None,
+				created_at: s.created_at,
+				last_seen_at: s.last_seen_at,
+			}
+		}
+	}
+}
+
+impl From<SessionModel> for Session {
+	fn from(s: SessionModel) -> Self {
+		Self {
+			id: s.id,
+			username: s.username,
+			client_name: s.client_name,
+			user_agent: s.user_agent,
+			ip_address: s.ip_address,
+			created_at: s.created_at,
+			last_seen_at: s.last_seen_at,
+		}
+	}
+}
+
+impl Manager {
+	pub fn new(
+		db: ndb::Manager,
+		audit_manager: audit::Manager,
+		config_manager: config::Manager,
+		hooks_manager: hooks::Manager,
+	) -> Self {
+		Self {
+			db,
+			audit_manager,
+			config_manager,
+			hooks_manager,
+			failed_logins: Arc::default(),
+			audited_logins: Arc::default(),
+			throttle: throttle::Throttle::default(),
+		}
+	}
+
+	// Signs in a user and records the new session
+	pub async fn login(
+		&self,
+		username: &str,
+		password: &str,
+		client: Client,
+	) -> Result<auth::Token, Error> {
+		let session_id = Alphanumeric.sample_string(&mut OsRng, 32);
+		let login = self
+			.config_manager
+			.login(username, password, Some(&session_id));
+		let token = self
+			.check_credentials(username, client.ip_address, login)
+			.await?;
+		self.audit_manager
+			.record(
+				Some(username),
+				client.ip_address,
+				audit::Event::LoginSucceeded,
+			)
+			.await;
+		self.record_session(session_id, username, client).await?;
+		Ok(token)
+	}
+
+	// Verifies the password of a user, for clients which send credentials instead of auth tokens
+	// (eg. WebDAV or MPD clients)
+	pub async fn check_password(
+		&self,
+		username: &str,
+		password: &str,
+		address: Option<IpAddr>,
+	) -> Result<(), Error> {
+		let login = self.config_manager.login(username, password, None);
+		self.check_credentials(username, address, login).await?;
+
+		let now = Instant::now();
+		let is_repeated = {
+			let mut audited_logins = self.audited_logins.lock().unwrap();
+			audited_logins.retain(|_, t| now.duration_since(*t) < REPEATED_LOGIN_AUDIT_INTERVAL);
+			let key = (username.to_owned(), address);
+			let is_repeated = audited_logins.contains_key(&key);
+			audited_logins.entry(key).or_insert(now);
+			is_repeated
+		};
+		if !is_repeated {
+			self.audit_manager
+				.record(Some(username), address, audit::Event::LoginSucceeded)
+				.await;
+		}
+		Ok(())
+	}
+
+	// Changes the password of a user, who must provide their current one
+	pub async fn change_password(
+		&self,
+		username: &str,
+		current_password: &str,
+		new_password: &str,
+		address: Option<IpAddr>,
+	) -> Result<(), Error> {
+		let change = self
+			.config_manager
+			.change_password(username, current_password, new_password);
+		self.check_credentials(username, address, change).await
+	}
+
+	// Every check of a password goes through here, so throttling and lockouts apply and failures
+	// are reported regardless of how credentials were submitted. `check` fails with
+	// `IncorrectUsername` or `IncorrectPassword` when the credentials are wrong.
+	async fn check_credentials<T>(
+		&self,
+		username: &str,
+		address: Option<IpAddr>,
+		check: impl Future<Output = Result<T, Error>>,
+	) -> Result<T, Error> {
+		self.throttle.check(address, username)?;
+
+		let lockout = self.config_manager.get_auth().await.lockout;
+		if lockout.is_some() && self.is_locked(username) {
+			return Err(Error::AccountLocked);
+		}
+
+		let result = check.await;
+		match &result {
+			Err(e @ (Error::IncorrectUsername | Error::IncorrectPassword)) => {
+				self.throttle.record_failure(address, username);
+				if let (Error::IncorrectPassword, Some(lockout)) = (e, &lockout) {
+					self.record_failed_login(username, lockout);
+				}
+				self.audit_manager
+					.record(Some(username), address, audit::Event::LoginFailed)
+					.await;
+				self.hooks_manager
+					.notify(hooks::Event::LoginFailed {
+						username: username.to_owned(),
+						address,
+					})
+					.await;
+			}
+			Ok(_) => {
+				self.throttle.record_success(address, username);
+				self.unlock(username);
+			}
+			Err(_) => (),
+		}
+		result
+	}
+
+	fn is_locked(&self, username: &str) -> bool {
+		self.failed_logins
+			.lock()
+			.unwrap()
+			.get(username)
+			.and_then(|f| f.locked_until)
+			.is_some_and(|t| t > Instant::now())
+	}
+
+	fn record_failed_login(&self, username: &str, lockout: &config::Lockout) {
+		let now = Instant::now();
+		let mut failed_logins = self.failed_logins.lock().unwrap();
+		failed_logins.retain(|_, f| now.duration_since(f.last_failure) < lockout.duration);
+		let entry = failed_logins
+			.entry(username.to_owned())
+			.or_insert(FailedLogins {
+				count: 0,
+				last_failure: now,
+				locked_until: None,
+			});
+		entry.count += 1;
+		entry.last_failure = now;
+		if entry.count >= lockout.threshold {
+			entry.count = 0;
+			entry.locked_until = Some(now + lockout.duration);
+		}
+	}
+
+	// Clears failed logins of a user, lifting their lockout if any
+	pub fn unlock(&self, username: &str) {
+		self.failed_logins.lock().unwrap().remove(username);
+	}
+
+	// Signs in a user vouched for by an external identity provider and records the new session
+	pub async fn login_external(
+		&self,
+		username: &str,
+		admin: Option<bool>,
+		create_user: bool,
+		client: Client,
+	) -> Result<auth::Token, Error> {
+		let session_id = Alphanumeric.sample_string(&mut OsRng, 32);
+		let token = self
+			.config_manager
+			.login_external(username, admin, create_user, Some(&session_id))
+			.await?;
+		self.record_session(session_id, username, client).await?;
+		Ok(token)
+	}
+
+	async fn record_session(
+		&self,
+		session_id: String,
+		username: &str,
+		client: Client,
+	) -> Result<(), Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			let username = username.to_owned();
+			move || {
+				let transaction = manager.db.rw_transaction()?;
+				let now = auth::now() as i64;
+				transaction.insert::<SessionModel>(SessionModel {
+					id: session_id,
+					username,
+					client_name: client.name,
+					user_agent: client.user_agent,
+					ip_address: client.ip_address.map(|ip| ip.to_string()),
+					created_at: now,
+					last_seen_at: now,
+				})?;
+				transaction.commit()?;
+				Ok(())
+			}
+		})
+		.await?
+	}
+
+	pub async fn authenticate(
+		&self,
+		auth_token: &auth::Token,
+		scope: auth::Scope,
+		ip_address: Option<IpAddr>,
+	) -> Result<auth::Authorization, Error> {
+		let authorization = self.config_manager.authenticate(auth_token, scope).await?;
+
+		let Some(session_id) = authorization.session_id.clone() else {
+			return Ok(authorization);
+		};
+
+		spawn_blocking({
+			let manager = self.clone();
+			let username = authorization.username.clone();
+			move || {
+				let session = {
+					let transaction = manager.db.r_transaction()?;
+					transaction.get().primary::<SessionModel>(session_id)?
+				};
+
+				let Some(session) = session.filter(|s| s.username == username) else {
+					return Err(Error::InvalidAuthToken);
+				};
+
+				let now = auth::now() as i64;
+				let ip_address = ip_address
+					.map(|ip| ip.to_string())
+					.or(session.ip_address.clone());
+				if now - session.last_seen_at >= LAST_SEEN_RESOLUTION_SECONDS
+					|| ip_address != session.ip_address
+				{
+					let transaction = manager.db.rw_transaction()?;
+					transaction.update::<SessionModel>(
+						session.clone(),
+						SessionModel {
+							ip_address,
+							last_seen_at: now,
+							..session
+						},
+					)?;
+					transaction.commit()?;
+				}
+
+				Ok(())
+			}
+		})
+		.await??;
+
+		Ok(authorization)
+	}
+
+	pub async fn list_sessions(&self, username: &str) -> Result<Vec<Session>, Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			let username = username.to_owned();
+			move || {
+				let transaction = manager.db.r_transaction()?;
+				let mut sessions = transaction
+					.scan()
+					.secondary::<SessionModel>(SessionModelKey::username)?
+					.range(username.as_str()..=username.as_str())?
+					.filter_map(|s| s.ok())
+					.map(Session::from)
+					.collect::<Vec<_>>();
+				sessions.sort_by_key(|s| std::cmp::Reverse(s.last_seen_at));
+				Ok(sessions)
+			}
+		})
+		.await?
+	}
+
+	// Revoked sessions can no longer be used to authenticate, even with tokens that did not expire
+	pub async fn revoke_session(&self, username: &str, session_id: &str) -> Result<(), Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			let username = username.to_owned();
+			let session_id = session_id.to_owned();
+			move || {
+				let transaction = manager.db.rw_transaction()?;
+				let session = transaction
+					.get()
+					.primary::<SessionModel>(session_id)?
+					.filter(|s| s.username == username)
+					.ok_or(Error::SessionNotFound)?;
+				transaction.remove::<SessionModel>(session)?;
+				transaction.commit()?;
+				Ok(())
+			}
+		})
+		.await?
+	}
+
+	// Revokes all sessions of a user, except the one making the request (if any)
+	pub async fn revoke_other_sessions(
+		&self,
+		username: &str,
+		current_session_id: Option<&str>,
+	) -> Result<usize, Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			let username = username.to_owned();
+			let current_session_id = current_session_id.map(str::to_owned);
+			move || {
+				let transaction = manager.db.rw_transaction()?;
+				let sessions = transaction
+					.scan()
+					.secondary::<SessionModel>(SessionModelKey::username)?
+					.range(username.as_str()..=username.as_str())?
+					.filter_map(|s| s.ok())
+					.filter(|s| Some(&s.id) != current_session_id.as_ref())
+					.collect::<Vec<_>>();
+				let num_revoked = sessions.len();
+				for session in sessions {
+					transaction.remove::<SessionModel>(session)?;
+				}
+				transaction.commit()?;
+				Ok(num_revoked)
+			}
+		})
+		.await?
+	}
+
+	// Signs a user out of all devices, including tokens issued without a session. API keys are
+	// managed separately (see `api_key::Manager::revoke_all_api_keys`)
+	pub async fn revoke_all_sessions(&self, username: &str) -> Result<(), Error> {
+		self.config_manager.revoke_tokens(username).await?;
+		self.revoke_other_sessions(username, None).await?;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::app::test;
+	use crate::test_name;
+
+	const TEST_USER: &str = "test_user";
+	const TEST_PASSWORD: &str = "password";
+
+	#[tokio::test]
+	async fn login_creates_session() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.build()
+			.await;
+
+		let token = ctx
+			.session_manager
+			.login(
+				TEST_USER,
+				TEST_PASSWORD,
+				Client {
+					name: Some("Living Room".to_owned()),
+					user_agent: Some("Firefox".to_owned()),
+					ip_address: Some(IpAddr::from([192, 168, 1, 20])),
+				},
+			)
+			.await
+			.unwrap();
+
+		let authorization = ctx
+			.session_manager
+			.authenticate(&token, auth::Scope::PolarisAuth, None)
+			.await
+			.unwrap();
+
+		let sessions = ctx.session_manager.list_sessions(TEST_USER).await.unwrap();
+		assert_eq!(sessions.len(), 1);
+		assert_eq!(Some(&sessions[0].id), authorization.session_id.as_ref());
+		assert_eq!(sessions[0].user_agent, Some("Firefox".to_owned()));
+		assert_eq!(sessions[0].client_name, Some("Living Room".to_owned()));
+		assert_eq!(sessions[0].ip_address, Some("192.168.1.20".to_owned()));
+	}
+
+	#[tokio::test]
+	async fn repeated_failures_lock_account() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.auth(config::storage::Auth {
+				lockout_threshold: Some(2),
+				..Default::default()
+			})
+			.build()
+			.await;
+
+		let login = |password: &'static str| {
+			ctx.session_manager
+				.login(TEST_USER, password, Client::default())
+		};
+
+		assert!(matches!(
+			login("garbage").await,
+			Err(Error::IncorrectPassword)
+		));
+		assert!(login(TEST_PASSWORD).await.is_ok());
+
+		assert!(matches!(
+			login("garbage").await,
+			Err(Error::IncorrectPassword)
+		));
+		assert!(matches!(
+			login("garbage").await,
+			Err(Error::IncorrectPassword)
+		));
+		assert!(matches!(
+			login(TEST_PASSWORD).await,
+			Err(Error::AccountLocked)
+		));
+
+		ctx.session_manager.unlock(TEST_USER);
+		assert!(login(TEST_PASSWORD).await.is_ok());
+	}
+
+	#[tokio::test]
+	async fn revoked_sessions_cannot_authenticate() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.build()
+			.await;
+
+		let mut tokens = Vec::new();
+		for _ in 0..3 {
+			let token = ctx
+				.session_manager
+				.login(TEST_USER, TEST_PASSWORD, Client::default())
+				.await
+				.unwrap();
+			tokens.push(token);
+		}
+
+		let mut session_ids = Vec::new();
+		for token in &tokens {
+			let authorization = ctx
+				.session_manager
+				.authenticate(token, auth::Scope::PolarisAuth, None)
+				.await
+				.unwrap();
+			session_ids.push(authorization.session_id.unwrap());
+		}
+
+		ctx.session_manager
+			.revoke_session(TEST_USER, &session_ids[0])
+			.await
+			.unwrap();
+		assert!(matches!(
+			ctx.session_manager
+				.authenticate(&tokens[0], auth::Scope::PolarisAuth, None)
+				.await,
+			Err(Error::InvalidAuthToken)
+		));
+		assert!(matches!(
+			ctx.session_manager
+				.revoke_session("someone_else", &session_ids[1])
+				.await,
+			Err(Error::SessionNotFound)
+		));
+
+		let num_revoked = ctx
+			.session_manager
+			.revoke_other_sessions(TEST_USER, Some(&session_ids[1]))
+			.await
+			.unwrap();
+		assert_eq!(num_revoked, 1);
+		let sessions = ctx.session_manager.list_sessions(TEST_USER).await.unwrap();
+		assert_eq!(sessions.len(), 1);
+		assert_eq!(sessions[0].id, session_ids[1]);
+	}
+
+	#[tokio::test]
+	async fn authenticate_rejects_unknown_session() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.build()
+			.await;
+
+		let token = ctx
+			.config_manager
+			.login(TEST_USER, TEST_PASSWORD, Some("oink"))
+			.await
+			.unwrap();
+
+		assert!(matches!(
+			ctx.session_manager
+				.authenticate(&token, auth::Scope::PolarisAuth, None)
+				.await,
+			Err(Error::InvalidAuthToken)
+		));
+	}
+
+	#[tokio::test]
+	async fn authenticate_accepts_tokens_without_session() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.build()
+			.await;
+
+		let token = ctx
+			.config_manager
+			.login(TEST_USER, TEST_PASSWORD, None)
+			.await
+			.unwrap();
+
+		assert!(ctx
+			.session_manager
+			.authenticate(&token, auth::Scope::PolarisAuth, None)
+			.await
+			.is_ok());
+	}
+
+	#[tokio::test]
+	async fn password_checks_are_throttled() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.build()
+			.await;
+
+		for _ in 0..4 {
+			assert!(matches!(
+				ctx.session_manager
+					.check_password(TEST_USER, "garbage", None)
+					.await,
+				Err(Error::IncorrectPassword)
+			));
+		}
+
+		// Credentials sent by WebDAV or MPD clients are throttled like logins
+		assert!(matches!(
+			ctx.session_manager
+				.check_password(TEST_USER, TEST_PASSWORD, None)
+				.await,
+			Err(Error::LoginThrottled(_))
+		));
+		assert!(matches!(
+			ctx.session_manager
+				.login(TEST_USER, TEST_PASSWORD, Client::default())
+				.await,
+			Err(Error::LoginThrottled(_))
+		));
+	}
+}
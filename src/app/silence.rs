@@ -0,0 +1,109 @@
+use std::path::Path;
+
+use symphonia::core::{
+	audio::SampleBuffer,
+	codecs::{DecoderOptions, CODEC_TYPE_NULL},
+	formats::FormatOptions,
+	io::{MediaSourceStream, MediaSourceStreamOptions},
+	meta::MetadataOptions,
+	probe::Hint,
+};
+
+use crate::app::Error;
+
+// Samples quieter than -60dBFS are considered silent
+const SILENCE_THRESHOLD: f32 = 0.001;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AudibleRange {
+	/// Offset of the first audible sample, in milliseconds
+	pub start: i64,
+	/// Offset of the last audible sample, in milliseconds
+	pub end: i64,
+}
+
+pub fn detect_audible_range(audio_path: &Path) -> Result<Option<AudibleRange>, Error> {
+	let file = std::fs::File::open(audio_path).map_err(|e| Error::Io(audio_path.to_owned(), e))?;
+	let media_source = MediaSourceStream::new(Box::new(file), MediaSourceStreamOptions::default());
+
+	let mut format = symphonia::default::get_probe()
+		.format(
+			&Hint::new(),
+			media_source,
+			&FormatOptions::default(),
+			&MetadataOptions::default(),
+		)
+		.map_err(Error::MediaProbeError)?
+		.format;
+
+	let track = format
+		.tracks()
+		.iter()
+		.find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+		.ok_or_else(|| Error::MediaEmpty(audio_path.to_owned()))?;
+
+	let track_id = track.id;
+
+	let mut decoder = symphonia::default::get_codecs()
+		.make(&track.codec_params, &DecoderOptions::default())
+		.map_err(Error::MediaDecoderError)?;
+
+	let mut first_audible: Option<f64> = None;
+	let mut last_audible: Option<f64> = None;
+	let mut elapsed_seconds = 0.0;
+
+	loop {
+		let packet = match format.next_packet() {
+			Ok(packet) => packet,
+			Err(symphonia::core::errors::Error::IoError(e))
+				if e.kind() == std::io::ErrorKind::UnexpectedEof =>
+			{
+				break;
+			}
+			Err(e) => return Err(Error::MediaPacketError(e)),
+		};
+
+		if packet.track_id() != track_id {
+			continue;
+		}
+
+		let decoded = match decoder.decode(&packet) {
+			Ok(d) => d,
+			Err(_) => continue,
+		};
+
+		let num_channels = decoded.spec().channels.count();
+		let sample_rate = decoded.spec().rate as f64;
+
+		let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, *decoded.spec());
+		buffer.copy_interleaved_ref(decoded);
+		for frame in buffer.samples().chunks_exact(num_channels) {
+			if frame.iter().any(|s| s.abs() > SILENCE_THRESHOLD) {
+				first_audible.get_or_insert(elapsed_seconds);
+				last_audible = Some(elapsed_seconds);
+			}
+			elapsed_seconds += 1.0 / sample_rate;
+		}
+	}
+
+	Ok(first_audible
+		.zip(last_audible)
+		.map(|(start, end)| AudibleRange {
+			start: (start * 1000.0).floor() as i64,
+			end: (end * 1000.0).ceil() as i64,
+		}))
+}
+
+#[cfg(test)]
+mod test {
+	use std::path::PathBuf;
+
+	use super::*;
+
+	#[test]
+	fn can_detect_audible_range() {
+		let path = PathBuf::from_iter(["test-data", "formats", "sample.flac"]);
+		let range = detect_audible_range(&path).unwrap().unwrap();
+		assert!(range.start <= range.end);
+	}
+}
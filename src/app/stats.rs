@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::app::{history, index, Error};
+
+pub const DEFAULT_TOP_COUNT: usize = 10;
+
+// Listening statistics, computed from the history of each user
+#[derive(Clone)]
+pub struct Manager {
+	history_manager: history::Manager,
+	index_manager: index::Manager,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Filter {
+	pub since: Option<i64>,
+	pub until: Option<i64>,
+	// Plays are bucketed by hour in the time zone of the user
+	pub utc_offset_minutes: i64,
+	pub top_count: usize,
+}
+
+impl Default for Filter {
+	fn default() -> Self {
+		Self {
+			since: None,
+			until: None,
+			utc_offset_minutes: 0,
+			top_count: DEFAULT_TOP_COUNT,
+		}
+	}
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Report {
+	pub play_count: u32,
+	// In seconds, based on the duration of the songs played
+	pub listening_time: i64,
+	pub top_songs: Vec<(PathBuf, u32)>,
+	pub top_albums: Vec<(index::AlbumId, u32)>,
+	pub top_artists: Vec<(String, u32)>,
+	// Number of plays for each hour of the day, starting at midnight
+	pub hourly_play_counts: [u32; 24],
+}
+
+fn top<K: Ord>(counts: HashMap<K, u32>, count: usize) -> Vec<(K, u32)> {
+	let mut counts = counts.into_iter().collect::<Vec<_>>();
+	counts.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b)));
+	counts.truncate(count);
+	counts
+}
+
+fn compile(
+	plays: &[history::Play],
+	songs: &HashMap<PathBuf, index::Song>,
+	filter: &Filter,
+) -> Report {
+	let mut report = Report::default();
+	let mut song_counts = HashMap::<PathBuf, u32>::new();
+	let mut album_counts = HashMap::<index::AlbumId, u32>::new();
+	let mut artist_counts = HashMap::<String, u32>::new();
+
+	for play in plays {
+		report.play_count += 1;
+
+		let local_time = play.played_at + filter.utc_offset_minutes * 60;
+		let hour = local_time.rem_euclid(24 * 60 * 60) / (60 * 60);
+		report.hourly_play_counts[hour as usize] += 1;
+
+		*song_counts.entry(play.path.clone()).or_default() += 1;
+
+		let Some(song) = songs.get(&play.path) else {
+			continue;
+		};
+		report.listening_time += song.duration.unwrap_or_default();
+		if let Some(album) = index::AlbumId::from_song(song) {
+			*album_counts.entry(album).or_default() += 1;
+		}
+		for artist in &song.artists {
+			*artist_counts.entry(artist.clone()).or_default() += 1;
+		}
+	}
+
+	report.top_songs = top(song_counts, filter.top_count);
+	report.top_albums = top(album_counts, filter.top_count);
+	report.top_artists = top(artist_counts, filter.top_count);
+	report
+}
+
+impl Manager {
+	pub fn new(history_manager: history::Manager, index_manager: index::Manager) -> Self {
+		Self {
+			history_manager,
+			index_manager,
+		}
+	}
+
+	pub async fn get_report(&self, username: &str, filter: Filter) -> Result<Report, Error> {
+		let plays = self
+			.history_manager
+			.get_history(username)
+			.await?
+			.into_iter()
+			.filter(|p| filter.since.is_none_or(|t| p.played_at >= t))
+			.filter(|p| filter.until.is_none_or(|t| p.played_at <= t))
+			.collect::<Vec<_>>();
+
+		let mut paths = plays.iter().map(|p| p.path.clone()).collect::<Vec<_>>();
+		paths.sort();
+		paths.dedup();
+		let songs = self
+			.index_manager
+			.get_songs(paths)
+			.await
+			.into_iter()
+			.filter_map(Result::ok)
+			.map(|s| (s.virtual_path.clone(), s))
+			.collect::<HashMap<_, _>>();
+
+		Ok(compile(&plays, &songs, &filter))
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn make_song(path: &str, album: &str, artist: &str, duration: i64) -> index::Song {
+		index::Song {
+			virtual_path: PathBuf::from(path),
+			album: Some(album.to_owned()),
+			artists: vec![artist.to_owned()],
+			duration: Some(duration),
+			..Default::default()
+		}
+	}
+
+	fn make_play(path: &str, played_at: i64) -> history::Play {
+		history::Play {
+			id: 0,
+			path: PathBuf::from(path),
+			played_at,
+			client: None,
+		}
+	}
+
+	#[test]
+	fn compiles_report() {
+		let songs = [
+			make_song("destiny.mp3", "Destiny", "Stratovarius", 600),
+			make_song("sos.mp3", "Destiny", "Stratovarius", 300),
+			make_song("hunted.mp3", "Hunted", "Khemmis", 200),
+		]
+		.into_iter()
+		.map(|s| (s.virtual_path.clone(), s))
+		.collect::<HashMap<_, _>>();
+
+		let plays = [
+			make_play("destiny.mp3", 3600),
+			make_play("sos.mp3", 3600 + 60),
+			make_play("hunted.mp3", 5 * 3600),
+			make_play("hunted.mp3", 5 * 3600 + 60),
+			make_play("hunted.mp3", 23 * 3600),
+			make_play("removed.mp3", 23 * 3600),
+		];
+
+		let filter = Filter {
+			utc_offset_minutes: 60,
+			top_count: 1,
+			..Default::default()
+		};
+		let report = compile(&plays, &songs, &filter);
+
+		assert_eq!(report.play_count, 6);
+		assert_eq!(report.listening_time, 600 + 300 + 3 * 200);
+		assert_eq!(report.top_songs, vec![(PathBuf::from("hunted.mp3"), 3)]);
+		assert_eq!(
+			report.top_albums,
+			vec![(
+				index::AlbumId {
+					name: "Hunted".to_owned(),
+					artists: vec!["Khemmis".to_owned()],
+				},
+				3
+			)]
+		);
+		assert_eq!(report.top_artists, vec![("Khemmis".to_owned(), 3)]);
+		assert_eq!(report.hourly_play_counts[0], 2);
+		assert_eq!(report.hourly_play_counts[2], 2);
+		assert_eq!(report.hourly_play_counts[6], 2);
+	}
+}
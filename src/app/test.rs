@@ -1,14 +1,36 @@
 use std::path::PathBuf;
 
 use crate::app::config::storage::*;
-use crate::app::{auth, config, index, ndb, playlist, scanner};
+use crate::app::{
+	acoustid, api_key, artist_image, artwork, audit, auth, config, events, favorites, history,
+	hooks, index, invite, listenbrainz, musicbrainz, ndb, playlist, preferences, queue, radio,
+	ratings, scanner, session, transfers, webauthn,
+};
 use crate::test::*;
 
 pub struct Context {
+	pub acoustid_manager: acoustid::Manager,
+	pub api_key_manager: api_key::Manager,
+	pub artist_image_manager: artist_image::Manager,
+	pub artwork_manager: artwork::Manager,
+	pub audit_manager: audit::Manager,
 	pub index_manager: index::Manager,
+	pub invite_manager: invite::Manager,
 	pub scanner: scanner::Scanner,
 	pub config_manager: config::Manager,
+	pub events_manager: events::Manager,
+	pub favorites_manager: favorites::Manager,
+	pub history_manager: history::Manager,
+	pub hooks_manager: hooks::Manager,
+	pub listenbrainz_manager: listenbrainz::Manager,
 	pub playlist_manager: playlist::Manager,
+	pub preferences_manager: preferences::Manager,
+	pub ratings_manager: ratings::Manager,
+	pub queue_manager: queue::Manager,
+	pub radio_manager: radio::Manager,
+	pub session_manager: session::Manager,
+	pub transfers_manager: transfers::Manager,
+	pub webauthn_manager: webauthn::Manager,
 }
 
 pub struct ContextBuilder {
@@ -34,10 +56,24 @@ impl ContextBuilder {
 		self
 	}
 
+	pub fn auth(mut self, auth: Auth) -> Self {
+		self.config.auth = Some(auth);
+		self
+	}
+
+	pub fn change_detection(mut self, change_detection: ChangeDetection) -> Self {
+		self.config.change_detection = Some(change_detection);
+		self
+	}
+
 	pub fn mount(mut self, name: &str, source: &str) -> Self {
 		self.config.mount_dirs.push(MountDir {
 			name: name.to_owned(),
 			source: PathBuf::from(source),
+			exclude_patterns: vec![],
+			symlinks: None,
+			split_artists: None,
+			remote: None,
 		});
 		self
 	}
@@ -46,23 +82,87 @@ impl ContextBuilder {
 		let config_path = self.test_directory.join("polaris.toml");
 
 		let auth_secret = auth::Secret::default();
-		let config_manager = config::Manager::new(&config_path, auth_secret)
+		let config_manager = config::Manager::new(&config_path, auth_secret.clone())
 			.await
 			.unwrap();
 		let ndb_manager = ndb::Manager::new(&self.test_directory).unwrap();
 		let index_manager = index::Manager::new(&self.test_directory).await.unwrap();
-		let scanner = scanner::Scanner::new(index_manager.clone(), config_manager.clone())
-			.await
-			.unwrap();
+		let artwork_manager = artwork::Manager::new(
+			self.test_directory.join("artwork"),
+			config_manager.clone(),
+			ndb_manager.clone(),
+		);
+		let artist_image_manager = artist_image::Manager::new(
+			self.test_directory.join("artist_images"),
+			config_manager.clone(),
+			index_manager.clone(),
+			ndb_manager.clone(),
+		);
+		let hooks_manager = hooks::Manager::new(config_manager.clone());
+		let musicbrainz_manager =
+			musicbrainz::Manager::new(ndb_manager.clone(), index_manager.clone());
+		let scanner = scanner::Scanner::new(
+			index_manager.clone(),
+			config_manager.clone(),
+			hooks_manager.clone(),
+			musicbrainz_manager,
+		)
+		.await
+		.unwrap();
+		let events_manager = events::Manager::new(config_manager.clone());
+		let acoustid_manager = acoustid::Manager::new(
+			config_manager.clone(),
+			index_manager.clone(),
+			ndb_manager.clone(),
+		);
 		let playlist_manager = playlist::Manager::new(ndb_manager.clone());
+		let queue_manager = queue::Manager::new(ndb_manager.clone());
+		let radio_manager = radio::Manager::new(ndb_manager.clone());
+		let api_key_manager = api_key::Manager::new(ndb_manager.clone(), config_manager.clone());
+		let invite_manager = invite::Manager::new(ndb_manager.clone(), config_manager.clone());
+		let audit_manager = audit::Manager::new(ndb_manager.clone());
+		let preferences_manager = preferences::Manager::new(ndb_manager.clone());
+		let favorites_manager = favorites::Manager::new(ndb_manager.clone());
+		let ratings_manager = ratings::Manager::new(ndb_manager.clone());
+		let history_manager = history::Manager::new(ndb_manager.clone());
+		let listenbrainz_manager =
+			listenbrainz::Manager::new(ndb_manager.clone(), index_manager.clone());
+		let session_manager = session::Manager::new(
+			ndb_manager.clone(),
+			audit_manager.clone(),
+			config_manager.clone(),
+			hooks_manager.clone(),
+		);
+
+		let transfers_manager =
+			transfers::Manager::new(ndb_manager.clone(), config_manager.clone());
+		let webauthn_manager = webauthn::Manager::new(config_manager.clone(), &auth_secret);
 
 		config_manager.apply_config(self.config).await.unwrap();
 
 		Context {
+			acoustid_manager,
+			api_key_manager,
+			artist_image_manager,
+			artwork_manager,
+			audit_manager,
 			index_manager,
+			invite_manager,
 			scanner,
 			config_manager,
+			events_manager,
+			favorites_manager,
+			history_manager,
+			hooks_manager,
+			listenbrainz_manager,
 			playlist_manager,
+			preferences_manager,
+			ratings_manager,
+			queue_manager,
+			radio_manager,
+			session_manager,
+			transfers_manager,
+			webauthn_manager,
 		}
 	}
 }
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use log::warn;
+
+use crate::app::Error;
+
+// Number of failed attempts allowed before throttling kicks in
+const FREE_ATTEMPTS: u32 = 3;
+const MAX_DELAY: Duration = Duration::from_secs(5 * 60);
+const FORGET_AFTER: Duration = Duration::from_secs(15 * 60);
+
+type Key = (Option<IpAddr>, String);
+
+struct Failures {
+	count: u32,
+	last_failure: Instant,
+	blocked_until: Instant,
+}
+
+// Throttles attempts after repeated failures from the same client for the same username.
+// The delay before the next attempt is allowed doubles with every failure.
+#[derive(Clone, Default)]
+pub struct Throttle {
+	failures: Arc<Mutex<HashMap<Key, Failures>>>,
+}
+
+impl Throttle {
+	// Fails with `LoginThrottled` while the client must wait before trying again
+	pub fn check(&self, address: Option<IpAddr>, username: &str) -> Result<(), Error> {
+		let key = (address, username.to_owned());
+		let retry_after = self
+			.failures
+			.lock()
+			.unwrap()
+			.get(&key)
+			.map(|f| f.blocked_until.saturating_duration_since(Instant::now()))
+			.filter(|d| !d.is_zero());
+		match retry_after {
+			Some(retry_after) => Err(Error::LoginThrottled(retry_after)),
+			None => Ok(()),
+		}
+	}
+
+	pub fn record_failure(&self, address: Option<IpAddr>, username: &str) {
+		let now = Instant::now();
+		let mut failures = self.failures.lock().unwrap();
+		failures.retain(|_, f| now.duration_since(f.last_failure) < FORGET_AFTER);
+		let entry = failures
+			.entry((address, username.to_owned()))
+			.or_insert(Failures {
+				count: 0,
+				last_failure: now,
+				blocked_until: now,
+			});
+		entry.count += 1;
+		entry.last_failure = now;
+		if entry.count > FREE_ATTEMPTS {
+			let exponent = (entry.count - FREE_ATTEMPTS).min(16);
+			let delay = Duration::from_secs(1 << exponent).min(MAX_DELAY);
+			entry.blocked_until = now + delay;
+			warn!(
+				"Throttling logins for `{}` from {:?} for {}s after {} failed attempts",
+				username,
+				address,
+				delay.as_secs(),
+				entry.count
+			);
+		}
+	}
+
+	pub fn record_success(&self, address: Option<IpAddr>, username: &str) {
+		self.failures
+			.lock()
+			.unwrap()
+			.remove(&(address, username.to_owned()));
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::net::Ipv4Addr;
+
+	use super::*;
+
+	const ADDRESS: Option<IpAddr> = Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 7)));
+
+	#[test]
+	fn throttles_after_repeated_failures() {
+		let throttle = Throttle::default();
+		for _ in 0..FREE_ATTEMPTS {
+			assert!(throttle.check(ADDRESS, "walter").is_ok());
+			throttle.record_failure(ADDRESS, "walter");
+		}
+		assert!(throttle.check(ADDRESS, "walter").is_ok());
+		throttle.record_failure(ADDRESS, "walter");
+
+		assert!(matches!(
+			throttle.check(ADDRESS, "walter"),
+			Err(Error::LoginThrottled(_))
+		));
+		assert!(throttle.check(None, "walter").is_ok());
+		assert!(throttle.check(ADDRESS, "jesse").is_ok());
+	}
+
+	#[test]
+	fn success_clears_failures() {
+		let throttle = Throttle::default();
+		for _ in 0..=FREE_ATTEMPTS {
+			throttle.record_failure(ADDRESS, "walter");
+		}
+		throttle.record_success(ADDRESS, "walter");
+		assert!(throttle.check(ADDRESS, "walter").is_ok());
+	}
+}
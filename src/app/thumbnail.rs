@@ -3,11 +3,13 @@ use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 use std::path::{Path, PathBuf};
 
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
 use image::codecs::jpeg::JpegEncoder;
 use image::{DynamicImage, GenericImage, GenericImageView, ImageBuffer};
 use tokio::task::spawn_blocking;
 
-use crate::app::Error;
+use crate::app::{config, remote, Error};
 use crate::utils::{get_audio_format, AudioFormat};
 
 #[derive(Clone, Debug, Hash)]
@@ -46,10 +48,30 @@ impl Manager {
 	) -> Result<PathBuf, Error> {
 		match self.read_from_cache(image_path, options).await {
 			Some(path) => Ok(path),
-			None => self.read_from_source(image_path, options).await,
+			None => self.read_from_source(image_path, image_path, options).await,
 		}
 	}
 
+	// Thumbnails of images on remote servers are generated from a temporary copy
+	pub async fn get_remote_thumbnail(
+		&self,
+		remote: &config::Remote,
+		image_path: &Path,
+		options: &Options,
+	) -> Result<PathBuf, Error> {
+		if let Some(path) = self.read_from_cache(image_path, options).await {
+			return Ok(path);
+		}
+		let local_copy = spawn_blocking({
+			let remote = remote.clone();
+			let image_path = image_path.to_owned();
+			move || remote::download(&remote, &image_path)
+		})
+		.await??;
+		self.read_from_source(local_copy.path(), image_path, options)
+			.await
+	}
+
 	fn get_thumbnail_path(&self, image_path: &Path, options: &Options) -> PathBuf {
 		let hash = Manager::hash(image_path, options);
 		let mut thumbnail_path = self.thumbnails_dir_path.clone();
@@ -67,13 +89,14 @@ impl Manager {
 
 	async fn read_from_source(
 		&self,
+		source_path: &Path,
 		image_path: &Path,
 		options: &Options,
 	) -> Result<PathBuf, Error> {
 		let thumbnail = spawn_blocking({
-			let image_path = image_path.to_owned();
+			let source_path = source_path.to_owned();
 			let options = options.clone();
-			move || generate_thumbnail(&image_path, &options)
+			move || generate_thumbnail(&source_path, &options)
 		})
 		.await??;
 
@@ -153,14 +176,31 @@ fn read(image_path: &Path) -> Result<DynamicImage, Error> {
 		Some(AudioFormat::OGG) => read_vorbis(image_path),
 		Some(AudioFormat::OPUS) => read_opus(image_path),
 		Some(AudioFormat::WAVE) => read_wave(image_path),
-		Some(AudioFormat::APE) | Some(AudioFormat::MPC) => read_ape(image_path),
+		Some(AudioFormat::APE) | Some(AudioFormat::MPC) | Some(AudioFormat::WAVPACK) => {
+			read_ape(image_path)
+		}
+		Some(AudioFormat::DFF) | Some(AudioFormat::DSF) => read_dsd(image_path),
 		Some(AudioFormat::MP4) | Some(AudioFormat::M4B) => read_mp4(image_path),
 		None => image::open(image_path).map_err(|e| Error::Image(image_path.to_owned(), e)),
 	}
 }
 
-fn read_ape(_: &Path) -> Result<DynamicImage, Error> {
-	Err(Error::UnsupportedFormat("ape"))
+// APE cover items hold a file name followed by a null byte, then the image data
+fn read_ape(path: &Path) -> Result<DynamicImage, Error> {
+	let tag = ape::read_from_path(path)?;
+	let data: &[u8] = tag
+		.item("Cover Art (Front)")
+		.and_then(|i| i.try_into().ok())
+		.ok_or_else(|| Error::EmbeddedArtworkNotFound(path.to_owned()))?;
+	let data = match data.iter().position(|b| *b == 0) {
+		Some(n) => &data[n + 1..],
+		None => data,
+	};
+	image::load_from_memory(data).map_err(|e| Error::Image(path.to_owned(), e))
+}
+
+fn read_dsd(_: &Path) -> Result<DynamicImage, Error> {
+	Err(Error::UnsupportedFormat("dsd"))
 }
 
 fn read_flac(path: &Path) -> Result<DynamicImage, Error> {
@@ -209,12 +249,28 @@ fn read_mp4(path: &Path) -> Result<DynamicImage, Error> {
 		.and_then(|d| image::load_from_memory(d.data).map_err(|e| Error::Image(path.to_owned(), e)))
 }
 
-fn read_vorbis(_: &Path) -> Result<DynamicImage, Error> {
-	Err(Error::UnsupportedFormat("vorbis"))
+fn read_vorbis(path: &Path) -> Result<DynamicImage, Error> {
+	let file = std::fs::File::open(path).map_err(|e| Error::Io(path.to_owned(), e))?;
+	let source = lewton::inside_ogg::OggStreamReader::new(file)?;
+	read_picture_comment(path, &source.comment_hdr.comment_list)
+}
+
+fn read_opus(path: &Path) -> Result<DynamicImage, Error> {
+	let headers = opus_headers::parse_from_path(path)?;
+	read_picture_comment(path, &headers.comments.user_comments)
 }
 
-fn read_opus(_: &Path) -> Result<DynamicImage, Error> {
-	Err(Error::UnsupportedFormat("opus"))
+// Vorbis comments embed pictures as base64 encoded FLAC picture blocks
+fn read_picture_comment(path: &Path, comments: &[(String, String)]) -> Result<DynamicImage, Error> {
+	comments
+		.iter()
+		.filter(|(key, _)| key.eq_ignore_ascii_case("METADATA_BLOCK_PICTURE"))
+		.filter_map(|(_, value)| STANDARD.decode(value.trim()).ok())
+		.find_map(|block| metaflac::block::Picture::from_bytes(&block).ok())
+		.ok_or_else(|| Error::EmbeddedArtworkNotFound(path.to_owned()))
+		.and_then(|p| {
+			image::load_from_memory(&p.data).map_err(|e| Error::Image(path.to_owned(), e))
+		})
 }
 
 #[cfg(test)]
@@ -0,0 +1,203 @@
+use std::ffi::OsString;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use log::error;
+use tokio::sync::mpsc;
+use tokio::task::spawn_blocking;
+
+use crate::app::config::storage::{StreamCodec, StreamQuality};
+use crate::app::{formats, Error};
+
+const STREAM_CHUNK_SIZE: usize = 16 * 1024;
+
+// Audio for ffmpeg to transcode
+pub enum Input {
+	File(PathBuf),
+	// Fed to ffmpeg through its standard input, eg. files of remote mount directories
+	Pipe(Box<dyn Read + Send>),
+}
+
+// Audio encoded by ffmpeg, relayed as it is produced
+pub struct Stream {
+	pub content_type: &'static str,
+	pub chunks: mpsc::Receiver<std::io::Result<Vec<u8>>>,
+}
+
+impl StreamCodec {
+	pub fn mime_type(&self) -> &'static str {
+		match self {
+			StreamCodec::Opus => "audio/ogg",
+			StreamCodec::Mp3 => "audio/mpeg",
+		}
+	}
+}
+
+fn ffmpeg_arguments(
+	path: &Path,
+	segment: Option<formats::Segment>,
+	quality: Option<&StreamQuality>,
+) -> Vec<OsString> {
+	let mut arguments: Vec<OsString> = ["-hide_banner", "-loglevel", "error", "-nostdin"]
+		.into_iter()
+		.map(OsString::from)
+		.collect();
+	if let Some(segment) = segment {
+		arguments.push("-ss".into());
+		arguments.push(format_seconds(segment.start).into());
+		if let Some(end) = segment.end {
+			arguments.push("-to".into());
+			arguments.push(format_seconds(end).into());
+		}
+	}
+	arguments.push("-i".into());
+	arguments.push(path.as_os_str().to_owned());
+	arguments.extend(["-map", "0:a:0", "-vn"].into_iter().map(OsString::from));
+	match quality {
+		Some(quality) => {
+			let (encoder, format) = match quality.codec.unwrap_or_default() {
+				StreamCodec::Opus => ("libopus", "ogg"),
+				StreamCodec::Mp3 => ("libmp3lame", "mp3"),
+			};
+			arguments.extend(
+				[
+					"-c:a".to_owned(),
+					encoder.to_owned(),
+					"-b:a".to_owned(),
+					format!("{}k", quality.max_bitrate_kbps),
+					"-f".to_owned(),
+					format.to_owned(),
+				]
+				.into_iter()
+				.map(OsString::from),
+			);
+		}
+		// Without a quality limit, segments are extracted losslessly
+		None => {
+			arguments.extend(
+				["-c:a", "flac", "-f", "flac"]
+					.into_iter()
+					.map(OsString::from),
+			);
+		}
+	}
+	arguments.push("pipe:1".into());
+	arguments
+}
+
+fn format_seconds(milliseconds: i64) -> String {
+	format!("{}.{:03}", milliseconds / 1000, milliseconds % 1000)
+}
+
+// Transcodes a song with the `ffmpeg` executable, until the returned stream is dropped. Songs split
+// from a CUE sheet are cut from their file, and encoded to FLAC when no quality is given.
+pub fn open_stream(
+	input: Input,
+	segment: Option<formats::Segment>,
+	quality: Option<&StreamQuality>,
+) -> Result<Stream, Error> {
+	let (path, stdin) = match &input {
+		Input::File(path) => (path.as_path(), Stdio::null()),
+		Input::Pipe(_) => (Path::new("pipe:0"), Stdio::piped()),
+	};
+	let mut child = Command::new("ffmpeg")
+		.args(ffmpeg_arguments(path, segment, quality))
+		.stdin(stdin)
+		.stdout(Stdio::piped())
+		.stderr(Stdio::null())
+		.spawn()
+		.map_err(|e| {
+			error!("Could not start ffmpeg: {e}");
+			Error::TranscodingFailed(e.to_string())
+		})?;
+
+	let Some(mut stdout) = child.stdout.take() else {
+		child.kill().ok();
+		return Err(Error::TranscodingFailed(
+			"ffmpeg output unavailable".to_owned(),
+		));
+	};
+
+	// Writing fails once ffmpeg exits, which ends the copy
+	if let (Input::Pipe(mut body), Some(mut stdin)) = (input, child.stdin.take()) {
+		spawn_blocking(move || std::io::copy(&mut body, &mut stdin).ok());
+	}
+
+	let (sender, chunks) = mpsc::channel(16);
+	spawn_blocking(move || {
+		let mut buffer = vec![0; STREAM_CHUNK_SIZE];
+		loop {
+			let chunk = match stdout.read(&mut buffer) {
+				Ok(0) => break,
+				Ok(n) => Ok(buffer[..n].to_vec()),
+				Err(e) => Err(e),
+			};
+			let failed = chunk.is_err();
+			if sender.blocking_send(chunk).is_err() || failed {
+				break;
+			}
+		}
+		// Clients disconnecting mid-song leave ffmpeg running otherwise
+		child.kill().ok();
+		child.wait().ok();
+	});
+
+	Ok(Stream {
+		content_type: match quality {
+			Some(quality) => quality.codec.unwrap_or_default().mime_type(),
+			None => "audio/flac",
+		},
+		chunks,
+	})
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn builds_ffmpeg_arguments() {
+		let quality = StreamQuality {
+			max_bitrate_kbps: 192,
+			codec: None,
+			remote_only: None,
+		};
+		let arguments = ffmpeg_arguments(Path::new("music/song.flac"), None, Some(&quality));
+		let arguments = arguments
+			.iter()
+			.map(|a| a.to_string_lossy())
+			.collect::<Vec<_>>()
+			.join(" ");
+		assert_eq!(
+			arguments,
+			"-hide_banner -loglevel error -nostdin -i music/song.flac -map 0:a:0 -vn -c:a libopus -b:a 192k -f ogg pipe:1"
+		);
+
+		let quality = StreamQuality {
+			codec: Some(StreamCodec::Mp3),
+			..quality
+		};
+		let arguments = ffmpeg_arguments(Path::new("song.flac"), None, Some(&quality));
+		assert!(arguments.contains(&OsString::from("libmp3lame")));
+		assert!(arguments.contains(&OsString::from("mp3")));
+	}
+
+	#[test]
+	fn builds_ffmpeg_arguments_for_segments() {
+		let segment = formats::Segment {
+			start: 252_493,
+			end: Some(500_040),
+		};
+		let arguments = ffmpeg_arguments(Path::new("album.flac"), Some(segment), None);
+		let arguments = arguments
+			.iter()
+			.map(|a| a.to_string_lossy())
+			.collect::<Vec<_>>()
+			.join(" ");
+		assert_eq!(
+			arguments,
+			"-hide_banner -loglevel error -nostdin -ss 252.493 -to 500.040 -i album.flac -map 0:a:0 -vn -c:a flac -f flac pipe:1"
+		);
+	}
+}
@@ -0,0 +1,235 @@
+use native_db::*;
+use native_model::{native_model, Model};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tokio::task::spawn_blocking;
+
+use crate::app::config::storage::StreamQuality;
+use crate::app::{config, ndb, Error};
+
+// Amount of audio and archives sent to each user, per calendar month (UTC)
+#[derive(Clone)]
+pub struct Manager {
+	db: ndb::Manager,
+	config_manager: config::Manager,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MonthlyTransfer {
+	// Formatted as `YYYY-MM`
+	pub month: String,
+	pub bytes: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Usage {
+	// Most recent first
+	pub months: Vec<MonthlyTransfer>,
+	pub monthly_quota_bytes: Option<u64>,
+}
+
+impl Usage {
+	pub fn current_month_bytes(&self) -> u64 {
+		self.months
+			.first()
+			.filter(|m| m.month == current_month())
+			.map_or(0, |m| m.bytes)
+	}
+
+	pub fn is_over_quota(&self) -> bool {
+		self.monthly_quota_bytes
+			.is_some_and(|q| self.current_month_bytes() >= q)
+	}
+}
+
+pub type TransferModel = v1::TransferModel;
+type TransferModelKey = v1::TransferModelKey;
+
+pub mod v1 {
+
+	use super::*;
+
+	#[derive(Clone, Debug, Serialize, Deserialize)]
+	#[native_model(id = 15, version = 1)]
+	#[native_db(primary_key(custom_id -> (&str, &str)))]
+	pub struct TransferModel {
+		#[secondary_key]
+		pub username: String,
+		pub month: String,
+		pub bytes: u64,
+	}
+
+	impl TransferModel {
+		fn custom_id(&self) -> (&str, &str) {
+			(&self.username, &self.month)
+		}
+	}
+}
+
+fn format_month(time: OffsetDateTime) -> String {
+	format!("{:04}-{:02}", time.year(), u8::from(time.month()))
+}
+
+fn current_month() -> String {
+	format_month(OffsetDateTime::now_utc())
+}
+
+impl Manager {
+	pub fn new(db: ndb::Manager, config_manager: config::Manager) -> Self {
+		Self { db, config_manager }
+	}
+
+	pub async fn record_transfer(&self, username: &str, bytes: u64) -> Result<(), Error> {
+		self.record_transfer_in(username, &current_month(), bytes)
+			.await
+	}
+
+	async fn record_transfer_in(
+		&self,
+		username: &str,
+		month: &str,
+		bytes: u64,
+	) -> Result<(), Error> {
+		spawn_blocking({
+			let manager = self.clone();
+			let username = username.to_owned();
+			let month = month.to_owned();
+			move || {
+				let transaction = manager.db.rw_transaction()?;
+				let existing = transaction
+					.get()
+					.primary::<TransferModel>((username.as_str(), month.as_str()))?;
+				match existing {
+					Some(existing) => {
+						let updated = TransferModel {
+							bytes: existing.bytes.saturating_add(bytes),
+							..existing.clone()
+						};
+						transaction.update::<TransferModel>(existing, updated)?;
+					}
+					None => transaction.insert::<TransferModel>(TransferModel {
+						username,
+						month,
+						bytes,
+					})?,
+				}
+				transaction.commit()?;
+				Ok(())
+			}
+		})
+		.await?
+	}
+
+	pub async fn get_usage(&self, username: &str) -> Result<Usage, Error> {
+		let user = self.config_manager.get_user(username).await?;
+		let mut months = spawn_blocking({
+			let manager = self.clone();
+			let username = username.to_owned();
+			move || -> Result<Vec<MonthlyTransfer>, Error> {
+				let transaction = manager.db.r_transaction()?;
+				let months = transaction
+					.scan()
+					.secondary::<TransferModel>(TransferModelKey::username)?
+					.range(username.as_str()..=username.as_str())?
+					.filter_map(|t| t.ok())
+					.map(|t| MonthlyTransfer {
+						month: t.month,
+						bytes: t.bytes,
+					})
+					.collect();
+				Ok(months)
+			}
+		})
+		.await??;
+		months.sort_by(|a, b| b.month.cmp(&a.month));
+
+		Ok(Usage {
+			months,
+			monthly_quota_bytes: user.transfer_quota.map(|q| q.monthly_mb * 1024 * 1024),
+		})
+	}
+
+	// Quality audio should be transcoded to because the user exceeded their monthly quota
+	pub async fn get_over_quota_stream_quality(
+		&self,
+		username: &str,
+	) -> Result<Option<StreamQuality>, Error> {
+		let user = self.config_manager.get_user(username).await?;
+		let Some(quality) = user.get_over_quota_stream_quality() else {
+			return Ok(None);
+		};
+		let usage = self.get_usage(username).await?;
+		Ok(usage.is_over_quota().then_some(quality))
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::app::config::storage;
+	use crate::app::test;
+	use crate::test_name;
+
+	const TEST_USER: &str = "test_user";
+	const TEST_PASSWORD: &str = "password";
+
+	#[test]
+	fn formats_months() {
+		let time = |t| OffsetDateTime::from_unix_timestamp(t).unwrap();
+		assert_eq!(format_month(time(1711929599)), "2024-03");
+		assert_eq!(format_month(time(1733011200)), "2024-12");
+	}
+
+	#[tokio::test]
+	async fn quota_applies_to_current_month() {
+		let quota = storage::TransferQuota {
+			monthly_mb: 1,
+			max_bitrate_kbps: 64,
+			codec: None,
+		};
+		let ctx = test::ContextBuilder::new(test_name!()).build().await;
+		ctx.config_manager
+			.apply_config(storage::Config {
+				users: vec![storage::User {
+					name: TEST_USER.to_owned(),
+					initial_password: Some(TEST_PASSWORD.to_owned()),
+					transfer_quota: Some(quota),
+					..Default::default()
+				}],
+				..Default::default()
+			})
+			.await
+			.unwrap();
+		let transfers = ctx.transfers_manager;
+
+		transfers
+			.record_transfer_in(TEST_USER, "2000-01", 5 * 1024 * 1024)
+			.await
+			.unwrap();
+		transfers
+			.record_transfer(TEST_USER, 1024 * 1024 - 1)
+			.await
+			.unwrap();
+		assert_eq!(
+			transfers
+				.get_over_quota_stream_quality(TEST_USER)
+				.await
+				.unwrap(),
+			None
+		);
+
+		transfers.record_transfer(TEST_USER, 1).await.unwrap();
+		let usage = transfers.get_usage(TEST_USER).await.unwrap();
+		assert_eq!(usage.months.len(), 2);
+		assert_eq!(usage.months[0].month, current_month());
+		assert_eq!(usage.current_month_bytes(), 1024 * 1024);
+		assert!(usage.is_over_quota());
+
+		let quality = transfers
+			.get_over_quota_stream_quality(TEST_USER)
+			.await
+			.unwrap()
+			.unwrap();
+		assert_eq!(quality.max_bitrate_kbps, 64);
+	}
+}
@@ -0,0 +1,702 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ciborium::Value;
+use rand::distributions::{Alphanumeric, DistString};
+use rand::rngs::OsRng;
+use ring::digest::{digest, SHA256};
+use ring::hmac;
+use ring::signature::{self, RsaPublicKeyComponents, UnparsedPublicKey};
+use serde::Deserialize;
+
+use crate::app::{auth, config, Error};
+
+// Time users have to interact with their authenticator
+pub const CHALLENGE_TIMEOUT: Duration = Duration::from_secs(300);
+
+// Challenges which can be pending at the same time, so unauthenticated clients cannot exhaust memory
+const MAX_PENDING_CHALLENGES: usize = 1000;
+
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_ATTESTED_CREDENTIAL_DATA: u8 = 0x40;
+
+// COSE algorithm identifiers
+pub const ALGORITHM_ES256: i64 = -7;
+pub const ALGORITHM_EDDSA: i64 = -8;
+pub const ALGORITHM_RS256: i64 = -257;
+
+// Registers and verifies passkeys (WebAuthn credentials), which let users sign in without a
+// password. Attestation statements are not verified, any authenticator is accepted.
+#[derive(Clone)]
+pub struct Manager {
+	config_manager: config::Manager,
+	challenges: Arc<Mutex<HashMap<String, PendingChallenge>>>,
+	// Derives stand-in passkeys for users who have none
+	decoy_key: hmac::Key,
+}
+
+// Website passkeys are scoped to, as seen by the browser
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RelyingParty {
+	pub id: String,
+	pub origin: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegistrationOptions {
+	pub challenge: String,
+	pub rp_id: String,
+	/// User handle stored by the authenticator
+	pub user_id: String,
+	pub username: String,
+	/// Passkeys the user already registered
+	pub exclude_credentials: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuthenticationOptions {
+	pub challenge: String,
+	pub rp_id: String,
+	/// Passkeys the user may sign in with, empty when the username is not known yet
+	pub allow_credentials: Vec<String>,
+}
+
+// Response of an authenticator to an authentication challenge
+#[derive(Clone, Debug, Default)]
+pub struct Assertion {
+	pub credential_id: String,
+	pub client_data_json: Vec<u8>,
+	pub authenticator_data: Vec<u8>,
+	pub signature: Vec<u8>,
+}
+
+enum Ceremony {
+	Registration { username: String },
+	Authentication { username: Option<String> },
+}
+
+struct PendingChallenge {
+	ceremony: Ceremony,
+	relying_party: RelyingParty,
+	started: Instant,
+}
+
+#[derive(Deserialize)]
+struct ClientData {
+	#[serde(rename = "type")]
+	ceremony: String,
+	challenge: String,
+	origin: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum PublicKey {
+	Es256 { point: Vec<u8> },
+	Ed25519 { x: Vec<u8> },
+	Rs256 { n: Vec<u8>, e: Vec<u8> },
+}
+
+impl Manager {
+	pub fn new(config_manager: config::Manager, auth_secret: &auth::Secret) -> Self {
+		Self {
+			config_manager,
+			challenges: Arc::default(),
+			decoy_key: hmac::Key::new(hmac::HMAC_SHA256, auth_secret.as_ref()),
+		}
+	}
+
+	fn issue_challenge(
+		&self,
+		ceremony: Ceremony,
+		relying_party: RelyingParty,
+	) -> Result<String, Error> {
+		let challenge = URL_SAFE_NO_PAD.encode(Alphanumeric.sample_string(&mut OsRng, 32));
+		let mut challenges = self.challenges.lock().unwrap();
+		challenges.retain(|_, c| c.started.elapsed() < CHALLENGE_TIMEOUT);
+		if challenges.len() >= MAX_PENDING_CHALLENGES {
+			return Err(Error::TooManyPendingLogins);
+		}
+		challenges.insert(
+			challenge.clone(),
+			PendingChallenge {
+				ceremony,
+				relying_party,
+				started: Instant::now(),
+			},
+		);
+		Ok(challenge)
+	}
+
+	// Stand-in passkey, which stays the same across requests for a given username
+	fn make_decoy_credential(&self, username: &str) -> String {
+		let tag = hmac::sign(
+			&self.decoy_key,
+			format!("passkey-decoy:{username}").as_bytes(),
+		);
+		URL_SAFE_NO_PAD.encode(tag.as_ref())
+	}
+
+	// Finds the pending challenge a client is responding to, and checks the client data
+	fn redeem_challenge(
+		&self,
+		client_data_json: &[u8],
+		expected_ceremony: &str,
+	) -> Result<PendingChallenge, Error> {
+		let client_data: ClientData =
+			serde_json::from_slice(client_data_json).map_err(|_| Error::PasskeyChallengeExpired)?;
+		let pending = self
+			.challenges
+			.lock()
+			.unwrap()
+			.remove(&client_data.challenge)
+			.filter(|c| c.started.elapsed() < CHALLENGE_TIMEOUT)
+			.ok_or(Error::PasskeyChallengeExpired)?;
+		if client_data.ceremony != expected_ceremony {
+			return Err(Error::PasskeyChallengeExpired);
+		}
+		if client_data.origin != pending.relying_party.origin {
+			return Err(Error::PasskeyChallengeExpired);
+		}
+		Ok(pending)
+	}
+
+	pub async fn begin_registration(
+		&self,
+		username: &str,
+		relying_party: RelyingParty,
+	) -> Result<RegistrationOptions, Error> {
+		let user = self.config_manager.get_user(username).await?;
+		let rp_id = relying_party.id.clone();
+		let challenge = self.issue_challenge(
+			Ceremony::Registration {
+				username: username.to_owned(),
+			},
+			relying_party,
+		)?;
+		Ok(RegistrationOptions {
+			challenge,
+			rp_id,
+			user_id: URL_SAFE_NO_PAD.encode(username),
+			username: username.to_owned(),
+			exclude_credentials: user.passkeys.into_iter().map(|p| p.id).collect(),
+		})
+	}
+
+	pub async fn finish_registration(
+		&self,
+		username: &str,
+		name: &str,
+		client_data_json: &[u8],
+		attestation_object: &[u8],
+	) -> Result<config::storage::Passkey, Error> {
+		let invalid = |reason: &str| Error::PasskeyRegistrationInvalid(reason.to_owned());
+
+		let pending = self.redeem_challenge(client_data_json, "webauthn.create")?;
+		match &pending.ceremony {
+			Ceremony::Registration { username: u } if u == username => (),
+			_ => return Err(Error::PasskeyChallengeExpired),
+		}
+
+		let attestation: Value = ciborium::from_reader(attestation_object)
+			.map_err(|_| invalid("malformed attestation object"))?;
+		let authenticator_data = map_get(&attestation, &Value::Text("authData".to_owned()))
+			.and_then(Value::as_bytes)
+			.ok_or_else(|| invalid("missing authenticator data"))?;
+
+		let flags = check_authenticator_data(authenticator_data, &pending.relying_party)
+			.map_err(|e| invalid(&e))?;
+		if flags & FLAG_ATTESTED_CREDENTIAL_DATA == 0 {
+			return Err(invalid("missing credential data"));
+		}
+
+		// Attested credential data: AAGUID (16 bytes), credential ID length (2 bytes),
+		// credential ID, then the credential public key.
+		let credential_data = &authenticator_data[37..];
+		if credential_data.len() < 18 {
+			return Err(invalid("truncated credential data"));
+		}
+		let id_length = u16::from_be_bytes([credential_data[16], credential_data[17]]) as usize;
+		let Some(credential_id) = credential_data.get(18..18 + id_length) else {
+			return Err(invalid("truncated credential ID"));
+		};
+		let mut cose_key = &credential_data[18 + id_length..];
+		let cose_key: Value =
+			ciborium::from_reader(&mut cose_key).map_err(|_| invalid("malformed public key"))?;
+		parse_public_key(&cose_key).map_err(|e| invalid(&e))?;
+
+		let mut public_key = Vec::new();
+		ciborium::into_writer(&cose_key, &mut public_key)
+			.map_err(|_| invalid("malformed public key"))?;
+
+		let name = name.trim();
+		let passkey = config::storage::Passkey {
+			id: URL_SAFE_NO_PAD.encode(credential_id),
+			name: match name.is_empty() {
+				true => "Passkey".to_owned(),
+				false => name.to_owned(),
+			},
+			public_key: URL_SAFE_NO_PAD.encode(public_key),
+			created_at: auth::now(),
+		};
+		self.config_manager
+			.add_passkey(username, passkey.clone())
+			.await?;
+		Ok(passkey)
+	}
+
+	// Usernames are optional, authenticators can offer discoverable passkeys instead
+	pub async fn begin_authentication(
+		&self,
+		username: Option<&str>,
+		relying_party: RelyingParty,
+	) -> Result<AuthenticationOptions, Error> {
+		// Users without passkeys, including unknown users, get a decoy passkey so responses do
+		// not reveal which accounts exist or have passkeys
+		let allow_credentials = match username {
+			Some(u) => match self.config_manager.get_user(u).await {
+				Ok(user) if !user.passkeys.is_empty() => {
+					user.passkeys.into_iter().map(|p| p.id).collect()
+				}
+				_ => vec![self.make_decoy_credential(u)],
+			},
+			None => Vec::new(),
+		};
+		let rp_id = relying_party.id.clone();
+		let challenge = self.issue_challenge(
+			Ceremony::Authentication {
+				username: username.map(str::to_owned),
+			},
+			relying_party,
+		)?;
+		Ok(AuthenticationOptions {
+			challenge,
+			rp_id,
+			allow_credentials,
+		})
+	}
+
+	// Returns the user who signed in
+	pub async fn finish_authentication(&self, assertion: &Assertion) -> Result<String, Error> {
+		let failed = |reason: &str| Error::PasskeyAuthenticationFailed(reason.to_owned());
+
+		let pending = self.redeem_challenge(&assertion.client_data_json, "webauthn.get")?;
+		let (username, passkey) = self
+			.config_manager
+			.find_passkey(&assertion.credential_id)
+			.await
+			.ok_or_else(|| failed("unknown passkey"))?;
+		match &pending.ceremony {
+			Ceremony::Authentication { username: None } => (),
+			Ceremony::Authentication { username: Some(u) } if *u == username => (),
+			_ => return Err(failed("passkey belongs to another user")),
+		}
+
+		check_authenticator_data(&assertion.authenticator_data, &pending.relying_party)
+			.map_err(|e| failed(&e))?;
+
+		let public_key = URL_SAFE_NO_PAD
+			.decode(&passkey.public_key)
+			.ok()
+			.and_then(|k| ciborium::from_reader::<Value, _>(k.as_slice()).ok())
+			.ok_or_else(|| failed("stored public key is malformed"))?;
+		let public_key = parse_public_key(&public_key).map_err(|e| failed(&e))?;
+
+		let message = [
+			assertion.authenticator_data.as_slice(),
+			digest(&SHA256, &assertion.client_data_json).as_ref(),
+		]
+		.concat();
+		if !verify_signature(&public_key, &message, &assertion.signature) {
+			return Err(failed("signature mismatch"));
+		}
+
+		Ok(username)
+	}
+
+	pub async fn list_passkeys(
+		&self,
+		username: &str,
+	) -> Result<Vec<config::storage::Passkey>, Error> {
+		Ok(self.config_manager.get_user(username).await?.passkeys)
+	}
+
+	pub async fn delete_passkey(&self, username: &str, id: &str) -> Result<(), Error> {
+		self.config_manager.delete_passkey(username, id).await
+	}
+
+	// Forgets passkey registrations in progress, eg. when the account of the user is deleted
+	pub fn cancel_registrations(&self, username: &str) {
+		self.challenges
+			.lock()
+			.unwrap()
+			.retain(|_, c| match &c.ceremony {
+				Ceremony::Registration { username: u } => u != username,
+				Ceremony::Authentication { .. } => true,
+			});
+	}
+}
+
+// Checks the relying party and user presence, and returns the authenticator data flags
+fn check_authenticator_data(data: &[u8], relying_party: &RelyingParty) -> Result<u8, String> {
+	if data.len() < 37 {
+		return Err("truncated authenticator data".to_owned());
+	}
+	if data[..32] != *digest(&SHA256, relying_party.id.as_bytes()).as_ref() {
+		return Err("passkey belongs to another website".to_owned());
+	}
+	let flags = data[32];
+	if flags & FLAG_USER_PRESENT == 0 {
+		return Err("user was not present".to_owned());
+	}
+	Ok(flags)
+}
+
+fn map_get<'a>(map: &'a Value, key: &Value) -> Option<&'a Value> {
+	map.as_map()?.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+}
+
+fn parse_public_key(cose_key: &Value) -> Result<PublicKey, String> {
+	let get = |label: i64| map_get(cose_key, &Value::Integer(label.into()));
+	let get_integer = |label: i64| {
+		get(label)
+			.and_then(Value::as_integer)
+			.and_then(|i| i64::try_from(i).ok())
+	};
+	let get_bytes = |label: i64| {
+		get(label)
+			.and_then(Value::as_bytes)
+			.cloned()
+			.ok_or_else(|| format!("missing public key parameter {label}"))
+	};
+
+	// Labels defined by RFC 9053: 1 is the key type, 3 the algorithm, and negative labels
+	// depend on the key type.
+	match (get_integer(1), get_integer(3), get_integer(-1)) {
+		(Some(2), Some(ALGORITHM_ES256), Some(1)) => {
+			let (x, y) = (get_bytes(-2)?, get_bytes(-3)?);
+			if x.len() != 32 || y.len() != 32 {
+				return Err("malformed P-256 public key".to_owned());
+			}
+			Ok(PublicKey::Es256 {
+				point: [&[0x04], x.as_slice(), y.as_slice()].concat(),
+			})
+		}
+		(Some(1), Some(ALGORITHM_EDDSA), Some(6)) => Ok(PublicKey::Ed25519 { x: get_bytes(-2)? }),
+		(Some(3), Some(ALGORITHM_RS256), _) => Ok(PublicKey::Rs256 {
+			n: get_bytes(-1)?,
+			e: get_bytes(-2)?,
+		}),
+		_ => Err("unsupported public key algorithm".to_owned()),
+	}
+}
+
+fn verify_signature(public_key: &PublicKey, message: &[u8], signature: &[u8]) -> bool {
+	match public_key {
+		PublicKey::Es256 { point } => {
+			UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_ASN1, point)
+				.verify(message, signature)
+				.is_ok()
+		}
+		PublicKey::Ed25519 { x } => UnparsedPublicKey::new(&signature::ED25519, x)
+			.verify(message, signature)
+			.is_ok(),
+		PublicKey::Rs256 { n, e } => RsaPublicKeyComponents { n, e }
+			.verify(&signature::RSA_PKCS1_2048_8192_SHA256, message, signature)
+			.is_ok(),
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use ring::rand::SystemRandom;
+	use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING};
+	use serde_json::json;
+
+	use super::*;
+	use crate::app::test;
+	use crate::test_name;
+
+	const TEST_USER: &str = "test_user";
+	const TEST_PASSWORD: &str = "password";
+
+	fn relying_party() -> RelyingParty {
+		RelyingParty {
+			id: "polaris.example.com".to_owned(),
+			origin: "https://polaris.example.com".to_owned(),
+		}
+	}
+
+	// Software authenticator holding a single P-256 passkey
+	struct Authenticator {
+		credential_id: Vec<u8>,
+		key_pair: EcdsaKeyPair,
+	}
+
+	impl Authenticator {
+		fn new() -> Self {
+			let rng = SystemRandom::new();
+			let pkcs8 =
+				EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng).unwrap();
+			let key_pair =
+				EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref(), &rng)
+					.unwrap();
+			Self {
+				credential_id: b"test-credential".to_vec(),
+				key_pair,
+			}
+		}
+
+		fn credential_id(&self) -> String {
+			URL_SAFE_NO_PAD.encode(&self.credential_id)
+		}
+
+		fn client_data(ceremony: &str, challenge: &str, origin: &str) -> Vec<u8> {
+			json!({"type": ceremony, "challenge": challenge, "origin": origin})
+				.to_string()
+				.into_bytes()
+		}
+
+		fn authenticator_data(rp_id: &str, flags: u8) -> Vec<u8> {
+			[
+				digest(&SHA256, rp_id.as_bytes()).as_ref(),
+				&[flags],
+				&[0, 0, 0, 1],
+			]
+			.concat()
+		}
+
+		fn register(&self, challenge: &str, rp: &RelyingParty) -> (Vec<u8>, Vec<u8>) {
+			let point = self.key_pair.public_key().as_ref();
+			let cose_key = Value::Map(vec![
+				(Value::Integer(1.into()), Value::Integer(2.into())),
+				(Value::Integer(3.into()), Value::Integer((-7).into())),
+				(Value::Integer((-1).into()), Value::Integer(1.into())),
+				(
+					Value::Integer((-2).into()),
+					Value::Bytes(point[1..33].to_vec()),
+				),
+				(
+					Value::Integer((-3).into()),
+					Value::Bytes(point[33..].to_vec()),
+				),
+			]);
+			let mut cose_key_bytes = Vec::new();
+			ciborium::into_writer(&cose_key, &mut cose_key_bytes).unwrap();
+
+			let authenticator_data = [
+				Self::authenticator_data(&rp.id, 0x45).as_slice(),
+				&[0; 16],
+				&(self.credential_id.len() as u16).to_be_bytes(),
+				&self.credential_id,
+				&cose_key_bytes,
+			]
+			.concat();
+			let attestation = Value::Map(vec![
+				(
+					Value::Text("fmt".to_owned()),
+					Value::Text("none".to_owned()),
+				),
+				(Value::Text("attStmt".to_owned()), Value::Map(vec![])),
+				(
+					Value::Text("authData".to_owned()),
+					Value::Bytes(authenticator_data),
+				),
+			]);
+			let mut attestation_object = Vec::new();
+			ciborium::into_writer(&attestation, &mut attestation_object).unwrap();
+
+			let client_data = Self::client_data("webauthn.create", challenge, &rp.origin);
+			(client_data, attestation_object)
+		}
+
+		fn sign_in(&self, challenge: &str, rp: &RelyingParty) -> Assertion {
+			let client_data_json = Self::client_data("webauthn.get", challenge, &rp.origin);
+			let authenticator_data = Self::authenticator_data(&rp.id, 0x05);
+			let message = [
+				authenticator_data.as_slice(),
+				digest(&SHA256, &client_data_json).as_ref(),
+			]
+			.concat();
+			let signature = self
+				.key_pair
+				.sign(&SystemRandom::new(), &message)
+				.unwrap()
+				.as_ref()
+				.to_vec();
+			Assertion {
+				credential_id: self.credential_id(),
+				client_data_json,
+				authenticator_data,
+				signature,
+			}
+		}
+	}
+
+	async fn register(ctx: &test::Context, authenticator: &Authenticator) {
+		let options = ctx
+			.webauthn_manager
+			.begin_registration(TEST_USER, relying_party())
+			.await
+			.unwrap();
+		let (client_data, attestation) =
+			authenticator.register(&options.challenge, &relying_party());
+		ctx.webauthn_manager
+			.finish_registration(TEST_USER, "Laptop", &client_data, &attestation)
+			.await
+			.unwrap();
+	}
+
+	#[tokio::test]
+	async fn can_register_and_sign_in() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.build()
+			.await;
+		let authenticator = Authenticator::new();
+		register(&ctx, &authenticator).await;
+
+		let passkeys = ctx.webauthn_manager.list_passkeys(TEST_USER).await.unwrap();
+		assert_eq!(passkeys.len(), 1);
+		assert_eq!(passkeys[0].id, authenticator.credential_id());
+		assert_eq!(passkeys[0].name, "Laptop");
+
+		let options = ctx
+			.webauthn_manager
+			.begin_authentication(Some(TEST_USER), relying_party())
+			.await
+			.unwrap();
+		assert_eq!(
+			options.allow_credentials,
+			vec![authenticator.credential_id()]
+		);
+
+		let assertion = authenticator.sign_in(&options.challenge, &relying_party());
+		let username = ctx
+			.webauthn_manager
+			.finish_authentication(&assertion)
+			.await
+			.unwrap();
+		assert_eq!(username, TEST_USER);
+
+		// Challenges can only be used once
+		assert!(matches!(
+			ctx.webauthn_manager.finish_authentication(&assertion).await,
+			Err(Error::PasskeyChallengeExpired)
+		));
+	}
+
+	#[tokio::test]
+	async fn rejects_forged_assertions() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.build()
+			.await;
+		let authenticator = Authenticator::new();
+		register(&ctx, &authenticator).await;
+
+		let begin = || async {
+			ctx.webauthn_manager
+				.begin_authentication(None, relying_party())
+				.await
+				.unwrap()
+				.challenge
+		};
+
+		let mut assertion = authenticator.sign_in(&begin().await, &relying_party());
+		assertion.signature[10] ^= 0xFF;
+		assert!(matches!(
+			ctx.webauthn_manager.finish_authentication(&assertion).await,
+			Err(Error::PasskeyAuthenticationFailed(_))
+		));
+
+		let other_site = RelyingParty {
+			id: "evil.example.com".to_owned(),
+			origin: "https://evil.example.com".to_owned(),
+		};
+		let assertion = authenticator.sign_in(&begin().await, &other_site);
+		assert!(matches!(
+			ctx.webauthn_manager.finish_authentication(&assertion).await,
+			Err(Error::PasskeyChallengeExpired)
+		));
+
+		let impostor = Authenticator::new();
+		let assertion = impostor.sign_in(&begin().await, &relying_party());
+		assert!(matches!(
+			ctx.webauthn_manager.finish_authentication(&assertion).await,
+			Err(Error::PasskeyAuthenticationFailed(_))
+		));
+	}
+
+	#[tokio::test]
+	async fn can_delete_passkey() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.build()
+			.await;
+		let authenticator = Authenticator::new();
+		register(&ctx, &authenticator).await;
+
+		ctx.webauthn_manager
+			.delete_passkey(TEST_USER, &authenticator.credential_id())
+			.await
+			.unwrap();
+		assert!(ctx
+			.webauthn_manager
+			.list_passkeys(TEST_USER)
+			.await
+			.unwrap()
+			.is_empty());
+		assert!(matches!(
+			ctx.webauthn_manager
+				.delete_passkey(TEST_USER, &authenticator.credential_id())
+				.await,
+			Err(Error::PasskeyNotFound)
+		));
+	}
+
+	#[tokio::test]
+	async fn authentication_options_do_not_reveal_users() {
+		let ctx = test::ContextBuilder::new(test_name!())
+			.user(TEST_USER, TEST_PASSWORD, false)
+			.build()
+			.await;
+
+		let get_credentials = |username: &'static str| {
+			let webauthn_manager = ctx.webauthn_manager.clone();
+			async move {
+				webauthn_manager
+					.begin_authentication(Some(username), relying_party())
+					.await
+					.unwrap()
+					.allow_credentials
+			}
+		};
+
+		let existing_user = get_credentials(TEST_USER).await;
+		let unknown_user = get_credentials("not_a_user").await;
+		assert_eq!(existing_user.len(), 1);
+		assert_eq!(unknown_user.len(), 1);
+		assert_ne!(existing_user, unknown_user);
+		assert_eq!(get_credentials("not_a_user").await, unknown_user);
+	}
+
+	#[tokio::test]
+	async fn pending_challenges_are_capped() {
+		let ctx = test::ContextBuilder::new(test_name!()).build().await;
+		for _ in 0..MAX_PENDING_CHALLENGES {
+			ctx.webauthn_manager
+				.begin_authentication(None, relying_party())
+				.await
+				.unwrap();
+		}
+		assert!(matches!(
+			ctx.webauthn_manager
+				.begin_authentication(None, relying_party())
+				.await,
+			Err(Error::TooManyPendingLogins)
+		));
+	}
+}
@@ -0,0 +1,161 @@
+use std::io::Write;
+use std::sync::Mutex;
+
+use log::kv::{self, VisitSource, VisitValue};
+use log::{LevelFilter, Log, Metadata, Record};
+use serde_json::{Map, Value};
+use simplelog::SharedLogger;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+// Writes each log record as a single line of JSON, for consumption by log aggregation tools.
+// Structured fields attached to log calls (eg. `info!(status = 200; "...")`) become JSON fields.
+pub struct JsonLogger<W: Write + Send + 'static> {
+	level: LevelFilter,
+	writable: Mutex<W>,
+}
+
+impl<W: Write + Send + 'static> JsonLogger<W> {
+	pub fn new(level: LevelFilter, writable: W) -> Box<Self> {
+		Box::new(Self {
+			level,
+			writable: Mutex::new(writable),
+		})
+	}
+}
+
+fn format_record(record: &Record) -> Value {
+	let timestamp = OffsetDateTime::now_utc()
+		.format(&Rfc3339)
+		.unwrap_or_default();
+
+	let mut fields = Map::new();
+	fields.insert("timestamp".to_owned(), timestamp.into());
+	fields.insert("level".to_owned(), record.level().as_str().into());
+	fields.insert("target".to_owned(), record.target().into());
+	fields.insert("message".to_owned(), record.args().to_string().into());
+	record
+		.key_values()
+		.visit(&mut FieldVisitor(&mut fields))
+		.ok();
+
+	Value::Object(fields)
+}
+
+impl<W: Write + Send + 'static> Log for JsonLogger<W> {
+	fn enabled(&self, metadata: &Metadata) -> bool {
+		metadata.level() <= self.level && !metadata.target().starts_with("symphonia")
+	}
+
+	fn log(&self, record: &Record) {
+		if !self.enabled(record.metadata()) {
+			return;
+		}
+		let line = format_record(record);
+		if let Ok(mut writable) = self.writable.lock() {
+			writeln!(writable, "{line}").ok();
+		}
+	}
+
+	fn flush(&self) {
+		if let Ok(mut writable) = self.writable.lock() {
+			writable.flush().ok();
+		}
+	}
+}
+
+impl<W: Write + Send + 'static> SharedLogger for JsonLogger<W> {
+	fn level(&self) -> LevelFilter {
+		self.level
+	}
+
+	fn config(&self) -> Option<&simplelog::Config> {
+		None
+	}
+
+	fn as_log(self: Box<Self>) -> Box<dyn Log> {
+		Box::new(*self)
+	}
+}
+
+struct FieldVisitor<'a>(&'a mut Map<String, Value>);
+
+impl<'kvs> VisitSource<'kvs> for FieldVisitor<'_> {
+	fn visit_pair(&mut self, key: kv::Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+		let mut visitor = ValueVisitor(Value::Null);
+		value.visit(&mut visitor)?;
+		self.0.insert(key.to_string(), visitor.0);
+		Ok(())
+	}
+}
+
+struct ValueVisitor(Value);
+
+impl<'v> VisitValue<'v> for ValueVisitor {
+	fn visit_any(&mut self, value: kv::Value) -> Result<(), kv::Error> {
+		self.0 = value.to_string().into();
+		Ok(())
+	}
+
+	fn visit_null(&mut self) -> Result<(), kv::Error> {
+		self.0 = Value::Null;
+		Ok(())
+	}
+
+	fn visit_u64(&mut self, value: u64) -> Result<(), kv::Error> {
+		self.0 = value.into();
+		Ok(())
+	}
+
+	fn visit_i64(&mut self, value: i64) -> Result<(), kv::Error> {
+		self.0 = value.into();
+		Ok(())
+	}
+
+	fn visit_f64(&mut self, value: f64) -> Result<(), kv::Error> {
+		self.0 = value.into();
+		Ok(())
+	}
+
+	fn visit_bool(&mut self, value: bool) -> Result<(), kv::Error> {
+		self.0 = value.into();
+		Ok(())
+	}
+
+	fn visit_str(&mut self, value: &str) -> Result<(), kv::Error> {
+		self.0 = value.into();
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use log::Level;
+
+	use super::*;
+
+	#[test]
+	fn can_format_record() {
+		let user: Option<&str> = None;
+		let fields: [(&str, kv::Value); 4] = [
+			("status", 404.into()),
+			("path", "/api/oink".into()),
+			("slow", false.into()),
+			("user", kv::ToValue::to_value(&user)),
+		];
+		let record = Record::builder()
+			.level(Level::Error)
+			.target("polaris")
+			.args(format_args!("Not found"))
+			.key_values(&fields)
+			.build();
+
+		let line = format_record(&record);
+		assert_eq!(line["level"], "ERROR");
+		assert_eq!(line["message"], "Not found");
+		assert_eq!(line["status"], 404);
+		assert_eq!(line["path"], "/api/oink");
+		assert_eq!(line["slow"], false);
+		assert_eq!(line["user"], Value::Null);
+		assert!(line["timestamp"].as_str().is_some_and(|t| !t.is_empty()));
+	}
+}
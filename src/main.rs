@@ -2,14 +2,16 @@
 #![recursion_limit = "256"]
 
 use log::{error, info};
-use options::CLIOptions;
+use options::{CLIOptions, LogFormat};
 use simplelog::{
 	ColorChoice, CombinedLogger, LevelFilter, SharedLogger, TermLogger, TerminalMode, WriteLogger,
 };
 use std::fs;
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 
 mod app;
+mod logging;
 mod options;
 mod paths;
 mod server;
@@ -26,6 +28,8 @@ pub enum Error {
 	ServiceStartup(std::io::Error),
 	#[error("Could not parse command line arguments:\n\n{0}")]
 	CliArgsParsing(getopts::Fail),
+	#[error("Invalid listen address `{0}`")]
+	InvalidBindAddress(String),
 	#[cfg(unix)]
 	#[error("Failed to turn polaris process into a daemon:\n\n{0}")]
 	Daemonize(daemonize::Error),
@@ -69,6 +73,7 @@ fn notify_ready() -> Result<(), Error> {
 
 fn init_logging<T: AsRef<Path>>(
 	log_level: LevelFilter,
+	log_format: LogFormat,
 	log_file_path: &Option<T>,
 ) -> Result<(), Error> {
 	let log_config = simplelog::ConfigBuilder::new()
@@ -76,24 +81,27 @@ fn init_logging<T: AsRef<Path>>(
 		.add_filter_ignore_str("symphonia")
 		.build();
 
-	let mut loggers: Vec<Box<dyn SharedLogger>> = vec![TermLogger::new(
-		log_level,
-		log_config.clone(),
-		TerminalMode::Mixed,
-		ColorChoice::Auto,
-	)];
+	let mut loggers: Vec<Box<dyn SharedLogger>> = match log_format {
+		LogFormat::Text => vec![TermLogger::new(
+			log_level,
+			log_config.clone(),
+			TerminalMode::Mixed,
+			ColorChoice::Auto,
+		)],
+		LogFormat::Json => vec![logging::JsonLogger::new(log_level, std::io::stdout())],
+	};
 
 	if let Some(path) = log_file_path {
 		if let Some(parent) = path.as_ref().parent() {
 			fs::create_dir_all(parent)
 				.map_err(|e| Error::LogDirectoryCreationError(parent.to_owned(), e))?;
 		}
-		loggers.push(WriteLogger::new(
-			log_level,
-			log_config,
-			fs::File::create(path)
-				.map_err(|e| Error::LogFileCreationError(path.as_ref().to_owned(), e))?,
-		));
+		let file = fs::File::create(path)
+			.map_err(|e| Error::LogFileCreationError(path.as_ref().to_owned(), e))?;
+		loggers.push(match log_format {
+			LogFormat::Text => WriteLogger::new(log_level, log_config, file),
+			LogFormat::Json => logging::JsonLogger::new(log_level, file),
+		});
 	}
 
 	CombinedLogger::init(loggers).map_err(Error::LogInitialization)?;
@@ -116,11 +124,16 @@ fn main() -> Result<(), Error> {
 		return Ok(());
 	}
 
+	let bind_addresses = cli_options
+		.get_bind_addresses()
+		.map_err(Error::InvalidBindAddress)?;
+
 	let paths = paths::Paths::new(&cli_options);
 
 	// Logging
 	let log_level = cli_options.log_level.unwrap_or(LevelFilter::Info);
-	init_logging(log_level, &paths.log_file_path)?;
+	let log_format = cli_options.log_format.unwrap_or_default();
+	init_logging(log_level, log_format, &paths.log_file_path)?;
 
 	// Fork
 	#[cfg(unix)]
@@ -137,19 +150,32 @@ fn main() -> Result<(), Error> {
 	}
 	info!("Web client files location is {:#?}", paths.web_dir_path);
 
-	async_main(cli_options, paths)
+	async_main(cli_options, paths, bind_addresses)
 }
 
 #[tokio::main]
-async fn async_main(cli_options: CLIOptions, paths: paths::Paths) -> Result<(), Error> {
+async fn async_main(
+	cli_options: CLIOptions,
+	paths: paths::Paths,
+	bind_addresses: Vec<SocketAddr>,
+) -> Result<(), Error> {
 	// Create and run app
-	let app = app::App::new(cli_options.port.unwrap_or(5050), paths).await?;
+	let port = bind_addresses
+		.first()
+		.map(|a| a.port())
+		.unwrap_or(cli_options.get_port());
+	let app = app::App::new(port, paths).await?;
 	app.scanner.queue_scan();
 	app.ddns_manager.begin_periodic_updates();
+	app.acoustid_manager.begin_periodic_fingerprinting();
+	app.listenbrainz_manager.begin_periodic_retries();
+	app.dlna_manager.begin_announcements().await;
+	app.mpd_manager.begin_listening(&bind_addresses).await;
+	app.jukebox_manager.begin_playback().await;
 
 	// Start server
 	info!("Starting up server");
-	if let Err(e) = server::launch(app).await {
+	if let Err(e) = server::launch(app, &bind_addresses).await {
 		return Err(Error::ServiceStartup(e));
 	}
 
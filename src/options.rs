@@ -1,5 +1,28 @@
 use simplelog::LevelFilter;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::path::PathBuf;
+use std::str::FromStr;
+
+const DEFAULT_PORT: u16 = 5050;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+	#[default]
+	Text,
+	Json,
+}
+
+impl FromStr for LogFormat {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.to_lowercase().as_str() {
+			"text" => Ok(Self::Text),
+			"json" => Ok(Self::Json),
+			_ => Err(()),
+		}
+	}
+}
 
 pub struct CLIOptions {
 	pub show_help: bool,
@@ -13,7 +36,39 @@ pub struct CLIOptions {
 	pub data_dir_path: Option<PathBuf>,
 	pub web_dir_path: Option<PathBuf>,
 	pub port: Option<u16>,
+	pub bind: Vec<String>,
 	pub log_level: Option<LevelFilter>,
+	pub log_format: Option<LogFormat>,
+}
+
+impl CLIOptions {
+	pub fn get_port(&self) -> u16 {
+		self.port.unwrap_or(DEFAULT_PORT)
+	}
+
+	// Addresses may omit their port, in which case the `--port` value is used.
+	// Without any `--bind` option, Polaris listens on all IPv4 interfaces.
+	pub fn get_bind_addresses(&self) -> Result<Vec<SocketAddr>, String> {
+		if self.bind.is_empty() {
+			let address = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+			return Ok(vec![SocketAddr::new(address, self.get_port())]);
+		}
+		self.bind
+			.iter()
+			.map(|b| parse_bind_address(b, self.get_port()))
+			.collect()
+	}
+}
+
+fn parse_bind_address(address: &str, default_port: u16) -> Result<SocketAddr, String> {
+	let address = address.trim();
+	if let Ok(a) = address.parse::<SocketAddr>() {
+		return Ok(a);
+	}
+	let ip = address.trim_start_matches('[').trim_end_matches(']');
+	ip.parse::<IpAddr>()
+		.map(|ip| SocketAddr::new(ip, default_port))
+		.map_err(|_| address.to_owned())
 }
 
 pub struct Manager {
@@ -45,7 +100,9 @@ impl Manager {
 			data_dir_path: matches.opt_str("data").map(PathBuf::from),
 			web_dir_path: matches.opt_str("w").map(PathBuf::from),
 			port: matches.opt_str("p").and_then(|p| p.parse().ok()),
+			bind: matches.opt_strs("bind"),
 			log_level: matches.opt_str("log-level").and_then(|l| l.parse().ok()),
+			log_format: matches.opt_str("log-format").and_then(|f| f.parse().ok()),
 		})
 	}
 
@@ -58,6 +115,12 @@ fn get_options() -> getopts::Options {
 	let mut options = getopts::Options::new();
 	options.optopt("c", "config", "set the configuration file", "FILE");
 	options.optopt("p", "port", "set polaris to run on a custom port", "PORT");
+	options.optmulti(
+		"",
+		"bind",
+		"listen on a specific address, eg. `[::]:5050` or `127.0.0.1` (can be repeated)",
+		"ADDRESS",
+	);
 	options.optopt("d", "database", "set the path to index database", "FILE");
 	options.optopt("w", "web", "set the path to web client files", "DIRECTORY");
 	options.optopt(
@@ -80,6 +143,12 @@ fn get_options() -> getopts::Options {
 		"set the log level to a value between 0 (off) and 3 (debug)",
 		"LEVEL",
 	);
+	options.optopt(
+		"",
+		"log-format",
+		"set the log output format, either `text` (default) or `json`",
+		"FORMAT",
+	);
 
 	#[cfg(unix)]
 	options.optflag(
@@ -91,3 +160,55 @@ fn get_options() -> getopts::Options {
 	options.optflag("h", "help", "print this help menu");
 	options
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn can_parse_bind_addresses() {
+		let address = |a: &str| a.parse::<SocketAddr>().unwrap();
+		assert_eq!(
+			parse_bind_address("0.0.0.0:5050", 80),
+			Ok(address("0.0.0.0:5050"))
+		);
+		assert_eq!(
+			parse_bind_address("[::]:8080", 80),
+			Ok(address("[::]:8080"))
+		);
+		assert_eq!(parse_bind_address("::1", 80), Ok(address("[::1]:80")));
+		assert_eq!(parse_bind_address("[::1]", 80), Ok(address("[::1]:80")));
+		assert_eq!(
+			parse_bind_address("192.168.1.10", 80),
+			Ok(address("192.168.1.10:80"))
+		);
+		assert!(parse_bind_address("localhost:5050", 80).is_err());
+	}
+
+	#[test]
+	fn binds_all_ipv4_interfaces_by_default() {
+		let options = Manager::new()
+			.parse(&["-p".to_owned(), "8000".to_owned()])
+			.unwrap();
+		assert_eq!(
+			options.get_bind_addresses(),
+			Ok(vec!["0.0.0.0:8000".parse().unwrap()])
+		);
+
+		let options = Manager::new()
+			.parse(&[
+				"--bind".to_owned(),
+				"[::]".to_owned(),
+				"--bind".to_owned(),
+				"0.0.0.0".to_owned(),
+			])
+			.unwrap();
+		assert_eq!(
+			options.get_bind_addresses(),
+			Ok(vec![
+				"[::]:5050".parse().unwrap(),
+				"0.0.0.0:5050".parse().unwrap()
+			])
+		);
+	}
+}
@@ -7,6 +7,7 @@ mod error;
 #[cfg(test)]
 mod test;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum APIMajorVersion {
 	V7,
 	V8,
@@ -24,6 +25,15 @@ impl TryFrom<i32> for APIMajorVersion {
 	}
 }
 
+impl From<APIMajorVersion> for i32 {
+	fn from(value: APIMajorVersion) -> Self {
+		match value {
+			APIMajorVersion::V7 => 7,
+			APIMajorVersion::V8 => 8,
+		}
+	}
+}
+
 pub const API_MAJOR_VERSION: i32 = 8;
 pub const API_MINOR_VERSION: i32 = 0;
 pub const API_ARRAY_SEPARATOR: &str = "\u{000C}";
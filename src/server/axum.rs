@@ -1,59 +1,244 @@
+use std::net::SocketAddr;
+
 use crate::app::{self, App};
-use crate::server::doc;
-use axum::{extract::FromRef, Router, ServiceExt};
+use crate::server::{doc, APIMajorVersion, API_MAJOR_VERSION};
+use axum::{extract::FromRef, Extension, Router, ServiceExt};
+use log::{error, info};
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::net::TcpListener;
 use tower::Layer;
 use tower_http::{
 	compression::CompressionLayer,
 	normalize_path::{NormalizePath, NormalizePathLayer},
 	services::ServeDir,
 };
+use utoipa::openapi::Server;
 use utoipa_axum::router::OpenApiRouter;
 use utoipa_scalar::{Scalar, Servable};
 
 mod api;
+mod archive;
 mod auth;
+mod compat;
+mod conditional;
+mod dlna;
 mod error;
+mod feed;
+mod forwarded;
+mod limits;
 mod logger;
+mod range;
+mod tls;
 mod version;
+mod webdav;
 
 #[cfg(test)]
 pub mod test;
 
-pub fn make_router(app: App) -> NormalizePath<Router> {
+pub async fn make_router(app: App) -> NormalizePath<Router> {
+	let base_path = app.config_manager.get_base_path().await;
+	let max_request_body_size =
+		app.config_manager.get_limits().await.max_request_body_mb * 1024 * 1024;
+
 	let static_files = Router::new()
 		.fallback_service(ServeDir::new(&app.web_dir_path))
 		.layer(CompressionLayer::new());
 
-	let (open_api_router, open_api) = OpenApiRouter::with_openapi(doc::open_api())
-		.nest("/api", api::router())
+	// Versioned endpoints have a stable response format and ignore the `Accept-Version` header.
+	// Only the current version is documented, older versions and unversioned endpoints are kept
+	// for compatibility with existing clients.
+	let stream_limiter =
+		limits::StreamLimiter::new(app.config_manager.clone(), app.transfers_manager.clone());
+	let api_router = || api::router(stream_limiter.clone(), max_request_body_size);
+	let (open_api_router, mut open_api) = OpenApiRouter::with_openapi(doc::open_api())
+		.nest(
+			&format!("/api/v{API_MAJOR_VERSION}"),
+			api_router().layer(Extension(version::PinnedVersion(APIMajorVersion::V8))),
+		)
 		.split_for_parts();
+	let open_api_router = open_api_router
+		.nest(
+			"/api/v7",
+			compat::pinned(api_router().into(), APIMajorVersion::V7, &base_path),
+		)
+		.nest("/api", compat::unversioned(api_router().into(), &base_path))
+		.nest("/dav", webdav::router(stream_limiter.clone()))
+		.nest("/dlna", dlna::router(stream_limiter.clone()));
+
+	if let Some(base_path) = &base_path {
+		open_api.servers = Some(vec![Server::new(base_path)]);
+	}
 
 	let router = open_api_router
 		.with_state(app.clone())
 		.merge(Scalar::with_url("/api-docs", open_api))
-		.fallback_service(static_files)
-		.layer(logger::LogLayer::new());
+		.fallback_service(static_files);
+
+	let router = match &base_path {
+		Some(base_path) => Router::new().nest(base_path, router),
+		None => router,
+	}
+	.layer(logger::LogLayer::new())
+	.layer(forwarded::ForwardedLayer::new(app.config_manager.clone()));
 
 	NormalizePathLayer::trim_trailing_slash().layer(router)
 }
 
-pub async fn launch(app: App) -> Result<(), std::io::Error> {
-	let port = app.port;
-	let router = make_router(app);
-	let make_service = ServiceExt::<axum::extract::Request>::into_make_service(router);
-	let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{port}")).await?;
-	tokio::spawn(async {
-		axum::serve(listener, make_service).await.unwrap();
-	});
+// IPv6 sockets only accept IPv6 connections, so that `[::]` and `0.0.0.0` can be bound together
+fn bind(address: SocketAddr) -> std::io::Result<TcpListener> {
+	let socket = Socket::new(
+		Domain::for_address(address),
+		Type::STREAM,
+		Some(Protocol::TCP),
+	)?;
+	if address.is_ipv6() {
+		socket.set_only_v6(true)?;
+	}
+	#[cfg(not(windows))]
+	socket.set_reuse_address(true)?;
+	socket.set_nonblocking(true)?;
+	socket.bind(&address.into())?;
+	socket.listen(1024)?;
+	TcpListener::from_std(socket.into())
+}
+
+pub async fn launch(app: App, addresses: &[SocketAddr]) -> Result<(), std::io::Error> {
+	let acme_manager = app.acme_manager.clone();
+	let limits = app.config_manager.get_limits().await;
+	let router = make_router(app).await;
+	let make_service = ServiceExt::<axum::extract::Request>::into_make_service_with_connect_info::<
+		forwarded::ClientAddress,
+	>(router);
+
+	let mut listeners = vec![];
+	for address in addresses {
+		let listener = bind(*address).map_err(|e| {
+			error!("Could not listen on {address}: {e}");
+			e
+		})?;
+		info!("Listening on {address}");
+		listeners.push(listener);
+	}
+
+	// Connection limits are shared by all listeners
+	let connection_limits =
+		limits::ConnectionLimits::new(limits.max_connections, limits.max_connections_per_ip);
+
+	match acme_manager.get_acme().await {
+		Some(acme) => {
+			let Some(local_addr) = listeners.first().map(|l| l.local_addr()).transpose()? else {
+				return Ok(());
+			};
+			let incoming = acme_manager.incoming(acme, listeners);
+			let listener = tls::AcmeListener::new(incoming, local_addr);
+			let listener = limits::LimitedListener::new(listener, connection_limits);
+			tokio::spawn(async {
+				axum::serve(listener, make_service).await.unwrap();
+			});
+		}
+		None => {
+			for listener in listeners {
+				let listener = limits::LimitedListener::new(listener, connection_limits.clone());
+				let make_service = make_service.clone();
+				tokio::spawn(async {
+					axum::serve(listener, make_service).await.unwrap();
+				});
+			}
+		}
+	}
 	Ok(())
 }
 
+impl FromRef<App> for app::events::Manager {
+	fn from_ref(app: &App) -> Self {
+		app.events_manager.clone()
+	}
+}
+
+impl FromRef<App> for app::favorites::Manager {
+	fn from_ref(app: &App) -> Self {
+		app.favorites_manager.clone()
+	}
+}
+
+impl FromRef<App> for app::history::Manager {
+	fn from_ref(app: &App) -> Self {
+		app.history_manager.clone()
+	}
+}
+
+impl FromRef<App> for app::device_login::Manager {
+	fn from_ref(app: &App) -> Self {
+		app.device_login_manager.clone()
+	}
+}
+
+impl FromRef<App> for app::listenbrainz::Manager {
+	fn from_ref(app: &App) -> Self {
+		app.listenbrainz_manager.clone()
+	}
+}
+
+impl FromRef<App> for app::transfers::Manager {
+	fn from_ref(app: &App) -> Self {
+		app.transfers_manager.clone()
+	}
+}
+
+impl FromRef<App> for app::stats::Manager {
+	fn from_ref(app: &App) -> Self {
+		app.stats_manager.clone()
+	}
+}
+
+impl FromRef<App> for app::ratings::Manager {
+	fn from_ref(app: &App) -> Self {
+		app.ratings_manager.clone()
+	}
+}
+
+impl FromRef<App> for app::artwork::Manager {
+	fn from_ref(app: &App) -> Self {
+		app.artwork_manager.clone()
+	}
+}
+
+impl FromRef<App> for app::audit::Manager {
+	fn from_ref(app: &App) -> Self {
+		app.audit_manager.clone()
+	}
+}
+
+impl FromRef<App> for app::hooks::Manager {
+	fn from_ref(app: &App) -> Self {
+		app.hooks_manager.clone()
+	}
+}
+
+impl FromRef<App> for app::api_key::Manager {
+	fn from_ref(app: &App) -> Self {
+		app.api_key_manager.clone()
+	}
+}
+
+impl FromRef<App> for app::session::Manager {
+	fn from_ref(app: &App) -> Self {
+		app.session_manager.clone()
+	}
+}
+
 impl FromRef<App> for app::index::Manager {
 	fn from_ref(app: &App) -> Self {
 		app.index_manager.clone()
 	}
 }
 
+impl FromRef<App> for app::invite::Manager {
+	fn from_ref(app: &App) -> Self {
+		app.invite_manager.clone()
+	}
+}
+
 impl FromRef<App> for app::scanner::Scanner {
 	fn from_ref(app: &App) -> Self {
 		app.scanner.clone()
@@ -66,12 +251,66 @@ impl FromRef<App> for app::config::Manager {
 	}
 }
 
+impl FromRef<App> for app::acme::Manager {
+	fn from_ref(app: &App) -> Self {
+		app.acme_manager.clone()
+	}
+}
+
 impl FromRef<App> for app::ddns::Manager {
 	fn from_ref(app: &App) -> Self {
 		app.ddns_manager.clone()
 	}
 }
 
+impl FromRef<App> for app::dlna::Manager {
+	fn from_ref(app: &App) -> Self {
+		app.dlna_manager.clone()
+	}
+}
+
+impl FromRef<App> for app::jukebox::Manager {
+	fn from_ref(app: &App) -> Self {
+		app.jukebox_manager.clone()
+	}
+}
+
+impl FromRef<App> for app::oidc::Manager {
+	fn from_ref(app: &App) -> Self {
+		app.oidc_manager.clone()
+	}
+}
+
+impl FromRef<App> for app::webauthn::Manager {
+	fn from_ref(app: &App) -> Self {
+		app.webauthn_manager.clone()
+	}
+}
+
+impl FromRef<App> for app::acoustid::Manager {
+	fn from_ref(app: &App) -> Self {
+		app.acoustid_manager.clone()
+	}
+}
+
+impl FromRef<App> for app::artist_image::Manager {
+	fn from_ref(app: &App) -> Self {
+		app.artist_image_manager.clone()
+	}
+}
+
+impl FromRef<App> for app::duplicates::Manager {
+	fn from_ref(app: &App) -> Self {
+		app.duplicates_manager.clone()
+	}
+}
+
+impl FromRef<App> for app::quality::Manager {
+	fn from_ref(app: &App) -> Self {
+		app.quality_manager.clone()
+	}
+}
+
 impl FromRef<App> for app::peaks::Manager {
 	fn from_ref(app: &App) -> Self {
 		app.peaks_manager.clone()
@@ -84,6 +323,24 @@ impl FromRef<App> for app::playlist::Manager {
 	}
 }
 
+impl FromRef<App> for app::preferences::Manager {
+	fn from_ref(app: &App) -> Self {
+		app.preferences_manager.clone()
+	}
+}
+
+impl FromRef<App> for app::queue::Manager {
+	fn from_ref(app: &App) -> Self {
+		app.queue_manager.clone()
+	}
+}
+
+impl FromRef<App> for app::radio::Manager {
+	fn from_ref(app: &App) -> Self {
+		app.radio_manager.clone()
+	}
+}
+
 impl FromRef<App> for app::thumbnail::Manager {
 	fn from_ref(app: &App) -> Self {
 		app.thumbnail_manager.clone()
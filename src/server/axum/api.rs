@@ -1,42 +1,104 @@
-use std::path::PathBuf;
+use std::{
+	collections::{HashMap, HashSet},
+	convert::Infallible,
+	path::PathBuf,
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use axum::{
+	body::{Body, Bytes},
 	extract::{DefaultBodyLimit, Path, Query, State},
-	response::{IntoResponse, Response},
+	response::{
+		sse::{self, KeepAlive, Sse},
+		IntoResponse, Response,
+	},
 	routing::get,
-	Json,
+	Extension, Json,
 };
-use axum_extra::headers::Range;
-use axum_extra::TypedHeader;
-use axum_range::{KnownSize, Ranged};
+use http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode, Uri};
 use regex::Regex;
+use tokio::task::spawn_blocking;
+use tokio_stream::{
+	wrappers::{BroadcastStream, ReceiverStream},
+	Stream, StreamExt,
+};
 use tower_http::{compression::CompressionLayer, CompressionLevel};
 use utoipa_axum::{router::OpenApiRouter, routes};
 
 use crate::{
-	app::{auth, config, ddns, index, peaks, playlist, scanner, thumbnail, App},
+	app::{
+		self, acoustid, api_key, artist_image, artwork, audit, auth, config, ddns, device_login,
+		duplicates, events, favorites, formats, history, hooks, index, invite, jukebox,
+		listenbrainz, lyrics, oidc, peaks, playlist, preferences, quality, queue, radio, ratings,
+		remote, scanner, session, stats, thumbnail, transcode, transfers, webauthn, App,
+	},
 	server::{
 		dto, error::APIError, APIMajorVersion, API_ARRAY_SEPARATOR, API_MAJOR_VERSION,
 		API_MINOR_VERSION,
 	},
 };
 
-use super::auth::{AdminRights, Auth};
+use crate::utils::get_audio_format;
 
-pub fn router() -> OpenApiRouter<App> {
+use super::auth::{permission, AdminRights, Auth, Permitted, SessionAuth};
+use super::forwarded::{get_base_url, ClientIp};
+use super::{archive, conditional, feed, limits};
+
+pub fn router(
+	stream_limiter: limits::StreamLimiter,
+	max_request_body_size: usize,
+) -> OpenApiRouter<App> {
 	OpenApiRouter::new()
+		// Authentication
+		.routes(routes!(post_auth))
+		.routes(routes!(post_auth_refresh))
+		.routes(routes!(get_oidc_login))
+		.routes(routes!(get_oidc_callback))
+		.routes(routes!(post_passkey_login_start))
+		.routes(routes!(post_passkey_login_finish))
+		.routes(routes!(post_register))
+		.routes(routes!(post_device_login))
+		.routes(routes!(post_device_login_approval))
+		.routes(routes!(post_device_login_token))
 		// Configuration
 		.routes(routes!(get_version))
+		.routes(routes!(get_health_live))
+		.routes(routes!(get_health_ready))
 		.routes(routes!(get_initial_setup))
 		.routes(routes!(get_settings, put_settings))
 		.routes(routes!(get_mount_dirs, put_mount_dirs))
+		.routes(routes!(get_aliases, put_aliases))
 		.routes(routes!(post_trigger_index))
+		.routes(routes!(post_index_refresh))
+		.routes(routes!(post_index_pause))
+		.routes(routes!(post_index_resume))
+		.routes(routes!(post_index_cancel))
 		.routes(routes!(get_index_status))
 		// User management
-		.routes(routes!(post_auth))
+		.routes(routes!(get_sessions, delete_sessions))
+		.routes(routes!(delete_session))
+		.routes(routes!(get_api_keys, post_api_key))
+		.routes(routes!(delete_api_key))
+		.routes(routes!(get_passkeys))
+		.routes(routes!(delete_passkey))
+		.routes(routes!(post_passkey_register_start))
+		.routes(routes!(post_passkey_register_finish))
 		.routes(routes!(post_user))
 		.routes(routes!(delete_user, put_user))
+		.routes(routes!(post_user_logout))
+		.routes(routes!(post_user_unlock))
+		.routes(routes!(get_user_transfers))
+		.routes(routes!(get_transfers))
 		.routes(routes!(get_users))
+		.routes(routes!(get_invites, post_invite))
+		.routes(routes!(delete_invite))
+		.routes(routes!(get_audit_log))
+		.routes(routes!(get_preferences, put_preferences))
+		.routes(routes!(
+			get_listenbrainz,
+			put_listenbrainz,
+			delete_listenbrainz
+		))
 		// File browser
 		.routes(routes!(get_browse_root))
 		.routes(routes!(get_browse))
@@ -45,15 +107,32 @@ pub fn router() -> OpenApiRouter<App> {
 		// Semantic
 		.routes(routes!(get_albums))
 		.routes(routes!(get_recent_albums))
+		.routes(routes!(get_recently_added_songs))
 		.routes(routes!(get_random_albums))
 		.routes(routes!(get_artists))
 		.routes(routes!(get_artist))
+		.routes(routes!(get_composers))
+		.routes(routes!(get_composer))
 		.routes(routes!(get_album))
+		.routes(routes!(get_musicbrainz_artist))
+		.routes(routes!(get_musicbrainz_release))
+		.routes(routes!(get_musicbrainz_recording))
 		.routes(routes!(get_genres))
 		.routes(routes!(get_genre))
 		.routes(routes!(get_genre_albums))
 		.routes(routes!(get_genre_artists))
 		.routes(routes!(get_genre_songs))
+		.routes(routes!(get_duplicates))
+		.routes(routes!(get_metadata_report))
+		.routes(routes!(get_mix))
+		// Favorites
+		.routes(routes!(get_starred))
+		.routes(routes!(put_star_song, delete_star_song))
+		.routes(routes!(put_star_album, delete_star_album))
+		.routes(routes!(put_star_artist, delete_star_artist))
+		// Ratings
+		.routes(routes!(put_song_rating, delete_song_rating))
+		.routes(routes!(put_album_rating, delete_album_rating))
 		.route("/random", get(get_random_albums)) // Deprecated
 		.route("/recent", get(get_recent_albums)) // Deprecated
 		// Search
@@ -61,15 +140,47 @@ pub fn router() -> OpenApiRouter<App> {
 		// Playlist management
 		.routes(routes!(get_playlists))
 		.routes(routes!(put_playlist, get_playlist, delete_playlist))
+		.routes(routes!(post_family_mix))
+		.routes(routes!(post_playlist_feed, delete_playlist_feed))
+		.routes(routes!(get_playlist_feed))
+		.routes(routes!(get_play_queue, put_play_queue))
+		// Internet radio
+		.routes(routes!(get_radio_stations, post_radio_station))
+		.routes(routes!(put_radio_station, delete_radio_station))
 		// Media
 		.routes(routes!(get_songs))
+		.routes(routes!(get_changes))
 		.routes(routes!(get_peaks))
+		.routes(routes!(get_lyrics))
+		.routes(routes!(get_fingerprint))
 		.routes(routes!(get_thumbnail))
+		.routes(routes!(get_artist_image))
+		.routes(routes!(put_artwork, delete_artwork))
+		.routes(routes!(put_playback))
+		.routes(routes!(get_history, post_history))
+		.routes(routes!(get_recently_played_songs))
+		.routes(routes!(get_recently_played_albums))
+		.routes(routes!(get_never_played_songs))
+		.routes(routes!(get_forgotten_albums))
+		.routes(routes!(get_stats))
+		.routes(routes!(get_streams))
+		// Jukebox
+		.routes(routes!(get_jukebox))
+		.routes(routes!(put_jukebox_queue, post_jukebox_queue))
+		.routes(routes!(put_jukebox_player))
+		.routes(routes!(post_jukebox_next))
+		.routes(routes!(post_jukebox_previous))
 		// Layers
 		.layer(CompressionLayer::new().quality(CompressionLevel::Fastest))
-		.layer(DefaultBodyLimit::max(10 * 1024 * 1024)) // 10MB
+		.layer(DefaultBodyLimit::max(max_request_body_size))
 		// Uncompressed
 		.routes(routes!(get_audio))
+		.routes(routes!(get_zip))
+		.routes(routes!(get_album_zip))
+		.routes(routes!(get_playlist_feed_audio))
+		.routes(routes!(get_radio_station_stream))
+		.routes(routes!(get_events))
+		.layer(Extension(stream_limiter))
 }
 
 #[utoipa::path(
@@ -89,6 +200,101 @@ async fn get_version() -> Json<dto::Version> {
 	Json(current_version)
 }
 
+#[utoipa::path(
+	get,
+	path = "/health/live",
+	tag = "Configuration",
+	description = "Reports whether the server is running and able to answer requests. Does not require authentication.",
+	responses(
+		(status = 200, body = dto::Health),
+	),
+)]
+async fn get_health_live() -> Json<dto::Health> {
+	Json(dto::Health {
+		healthy: true,
+		components: vec![],
+	})
+}
+
+// Remote mounts which take longer than this to list are reported as unreachable
+const REMOTE_HEALTH_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[utoipa::path(
+	get,
+	path = "/health/ready",
+	tag = "Configuration",
+	description = "Reports whether the server is ready to serve the music collection: the configuration file must be readable, all mount directories reachable (including remote storage), and the collection index populated. Does not require authentication.",
+	responses(
+		(status = 200, body = dto::Health),
+		(status = 503, body = dto::Health),
+	),
+)]
+async fn get_health_ready(
+	State(config_manager): State<config::Manager>,
+	State(index_manager): State<index::Manager>,
+	State(scanner): State<scanner::Scanner>,
+) -> (StatusCode, Json<dto::Health>) {
+	let component = |name: String, error: Option<&str>| dto::HealthComponent {
+		name,
+		healthy: error.is_none(),
+		error: error.map(|e| e.to_owned()),
+	};
+
+	let mut components = vec![component(
+		"config_file".to_owned(),
+		config_manager
+			.check_config_file()
+			.await
+			.err()
+			.map(|_| "Configuration file is not readable"),
+	)];
+
+	for mount_dir in config_manager.get_mounts().await {
+		let name = format!("mount_dir:{}", mount_dir.name);
+		let error = match mount_dir.remote {
+			Some(remote) => {
+				let listing = spawn_blocking({
+					let source = mount_dir.source.clone();
+					move || remote::list_files(&remote, &source)
+				});
+				let reachable = matches!(
+					tokio::time::timeout(REMOTE_HEALTH_TIMEOUT, listing).await,
+					Ok(Ok(Ok(_)))
+				);
+				(!reachable).then_some("Remote storage is not reachable")
+			}
+			None => {
+				let reachable = tokio::fs::metadata(&mount_dir.source)
+					.await
+					.is_ok_and(|m| m.is_dir());
+				(!reachable).then_some("Directory is not reachable")
+			}
+		};
+		components.push(component(name, error));
+	}
+
+	let index_ready =
+		scanner.get_status().await.last_end_time.is_some() || !index_manager.is_index_empty().await;
+	components.push(component(
+		"index".to_owned(),
+		(!index_ready).then_some("Collection has not been scanned yet"),
+	));
+
+	let healthy = components.iter().all(|c| c.healthy);
+	let status = if healthy {
+		StatusCode::OK
+	} else {
+		StatusCode::SERVICE_UNAVAILABLE
+	};
+	(
+		status,
+		Json(dto::Health {
+			healthy,
+			components,
+		}),
+	)
+}
+
 #[utoipa::path(
 	get,
 	path = "/initial_setup",
@@ -140,6 +346,13 @@ async fn get_settings(
 			.as_ref()
 			.map(http::Uri::to_string)
 			.unwrap_or_default(),
+		detect_silence: config_manager.get_detect_silence().await,
+		filename_pattern: config_manager
+			.get_filename_pattern()
+			.await
+			.map(|p| p.as_str().to_owned())
+			.unwrap_or_default(),
+		artist_separators: config_manager.get_artist_separators().await,
 	};
 	Ok(Json(settings))
 }
@@ -156,9 +369,11 @@ async fn get_settings(
 	request_body = dto::NewSettings,
 )]
 async fn put_settings(
-	_admin_rights: AdminRights,
+	admin_rights: AdminRights,
 	State(config_manager): State<config::Manager>,
 	State(ddns_manager): State<ddns::Manager>,
+	State(audit_manager): State<audit::Manager>,
+	client_ip: Option<Extension<ClientIp>>,
 	Json(new_settings): Json<dto::NewSettings>,
 ) -> Result<(), APIError> {
 	if let Some(pattern) = new_settings.album_art_pattern {
@@ -168,6 +383,22 @@ async fn put_settings(
 		config_manager.set_index_album_art_pattern(regex).await?;
 	}
 
+	if let Some(detect_silence) = new_settings.detect_silence {
+		config_manager.set_detect_silence(detect_silence).await?;
+	}
+
+	if let Some(pattern) = new_settings.filename_pattern {
+		let pattern = match pattern.trim() {
+			"" => None,
+			p => Some(formats::FilenamePattern::new(p)?),
+		};
+		config_manager.set_filename_pattern(pattern).await?;
+	}
+
+	if let Some(separators) = new_settings.artist_separators {
+		config_manager.set_artist_separators(separators).await?;
+	}
+
 	if let Some(url_string) = new_settings.ddns_update_url {
 		let uri = match url_string.trim() {
 			"" => None,
@@ -177,6 +408,13 @@ async fn put_settings(
 		ddns_manager.update_ddns().await?;
 	}
 
+	audit_manager
+		.record(
+			actor(admin_rights.get_auth()),
+			client_ip.map(|Extension(ClientIp(ip))| ip),
+			audit::Event::SettingsChanged,
+		)
+		.await;
 	Ok(())
 }
 
@@ -194,7 +432,7 @@ async fn put_settings(
 	),
 )]
 async fn get_mount_dirs(
-	_admin_rights: AdminRights,
+	_rights: Permitted<permission::ManageMounts>,
 	State(config_manager): State<config::Manager>,
 ) -> Result<Json<Vec<dto::MountDir>>, APIError> {
 	let mount_dirs = config_manager.get_mounts().await;
@@ -214,13 +452,100 @@ async fn get_mount_dirs(
 	request_body = Vec<dto::MountDir>,
 )]
 async fn put_mount_dirs(
-	_admin_rights: AdminRights,
+	rights: Permitted<permission::ManageMounts>,
 	State(config_manager): State<config::Manager>,
+	State(audit_manager): State<audit::Manager>,
+	client_ip: Option<Extension<ClientIp>>,
 	new_mount_dirs: Json<Vec<dto::MountDir>>,
 ) -> Result<(), APIError> {
 	let new_mount_dirs: Vec<config::storage::MountDir> =
 		new_mount_dirs.iter().cloned().map(|m| m.into()).collect();
 	config_manager.set_mounts(new_mount_dirs).await?;
+	audit_manager
+		.record(
+			actor(rights.get_auth()),
+			client_ip.map(|Extension(ClientIp(ip))| ip),
+			audit::Event::MountDirsChanged,
+		)
+		.await;
+	Ok(())
+}
+
+#[utoipa::path(
+	get,
+	path = "/aliases",
+	tag = "Configuration",
+	description = "Returns the alternative spellings of artist, album and genre names which are merged together when indexing the collection.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	responses(
+		(status = 200, body = dto::Aliases),
+	),
+)]
+async fn get_aliases(
+	_admin_rights: AdminRights,
+	State(config_manager): State<config::Manager>,
+) -> Result<Json<dto::Aliases>, APIError> {
+	Ok(Json(dto::Aliases {
+		artists: config_manager
+			.get_artist_aliases()
+			.await
+			.into_iter()
+			.map(|a| a.into())
+			.collect(),
+		albums: config_manager
+			.get_album_aliases()
+			.await
+			.into_iter()
+			.map(|a| a.into())
+			.collect(),
+		genres: config_manager
+			.get_genre_aliases()
+			.await
+			.into_iter()
+			.map(|a| a.into())
+			.collect(),
+	}))
+}
+
+#[utoipa::path(
+	put,
+	path = "/aliases",
+	tag = "Configuration",
+	description = "Replaces the alternative spellings of artist, album and genre names. The collection is re-indexed to apply the changes.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	request_body = dto::Aliases,
+	responses(
+		(status = 200),
+		(status = 400),
+	),
+)]
+async fn put_aliases(
+	admin_rights: AdminRights,
+	State(config_manager): State<config::Manager>,
+	State(audit_manager): State<audit::Manager>,
+	client_ip: Option<Extension<ClientIp>>,
+	Json(aliases): Json<dto::Aliases>,
+) -> Result<(), APIError> {
+	config_manager
+		.set_aliases(
+			aliases.artists.into_iter().map(|a| a.into()).collect(),
+			aliases.albums.into_iter().map(|a| a.into()).collect(),
+			aliases.genres.into_iter().map(|a| a.into()).collect(),
+		)
+		.await?;
+	audit_manager
+		.record(
+			actor(admin_rights.get_auth()),
+			client_ip.map(|Extension(ClientIp(ip))| ip),
+			audit::Event::AliasesChanged,
+		)
+		.await;
 	Ok(())
 }
 
@@ -228,20 +553,53 @@ async fn put_mount_dirs(
 	post,
 	path = "/auth",
 	tag = "User Management",
-	description = "Signs in a user. Tokens returned by this endpoint are required by most other endpoints.",
+	description = "Signs in a user. Tokens returned by this endpoint are required by most other endpoints.\n\nWhen the password policy requires users to change their initial password, signing in with it fails with a 403 status until a `new_password` is supplied.",
 	responses(
 		(status = 200, body = dto::Authorization),
+		(status = 400, description = "The new password does not meet the password policy"),
 		(status = 401),
+		(status = 403, description = "The password must be changed before signing in"),
+		(status = 423, description = "The account is temporarily locked after too many failed logins"),
+		(status = 429, description = "Too many failed login attempts. The `Retry-After` header indicates how many seconds to wait before trying again."),
 	),
 )]
 async fn post_auth(
 	State(config_manager): State<config::Manager>,
+	State(session_manager): State<session::Manager>,
+	client_ip: Option<Extension<ClientIp>>,
+	headers: HeaderMap,
 	credentials: Json<dto::Credentials>,
 ) -> Result<Json<dto::Authorization>, APIError> {
 	let username = credentials.username.clone();
+	let address = client_ip.map(|Extension(ClientIp(ip))| ip);
 
-	let auth::Token(token) = config_manager
-		.login(&credentials.username, &credentials.password)
+	let user_agent = headers
+		.get(http::header::USER_AGENT)
+		.and_then(|h| h.to_str().ok())
+		.map(str::to_owned);
+
+	let client = session::Client {
+		name: credentials.client_name.clone(),
+		user_agent,
+		ip_address: address,
+	};
+
+	if let Some(new_password) = &credentials.new_password {
+		session_manager
+			.change_password(
+				&credentials.username,
+				&credentials.password,
+				new_password,
+				address,
+			)
+			.await?;
+	}
+	let password = credentials
+		.new_password
+		.as_ref()
+		.unwrap_or(&credentials.password);
+	let auth::Token(token) = session_manager
+		.login(&credentials.username, password, client)
 		.await?;
 	let user = config_manager.get_user(&credentials.username).await?;
 	let is_admin = user.is_admin();
@@ -256,891 +614,4321 @@ async fn post_auth(
 }
 
 #[utoipa::path(
-	get,
-	path = "/users",
+	post,
+	path = "/auth/device",
 	tag = "User Management",
-	description = "Lists existing user accounts.",
-	security(
-		("auth_token" = []),
-		("auth_query_param" = []),
-	),
+	description = "Starts signing in a device without a convenient keyboard, like a TV or a command line tool.\n\nThe device displays the returned `user_code`, which users approve with `/auth/device/approve` from a client where they are signed in. Meanwhile, the device calls `/auth/device/token` every `interval` seconds until it receives a token.",
+	request_body = dto::DeviceLoginInput,
 	responses(
-		(status = 200, body = Vec<dto::User>),
+		(status = 200, body = dto::DeviceLogin),
+		(status = 503, description = "Too many device logins are pending"),
 	),
 )]
-async fn get_users(
-	_admin_rights: AdminRights,
-	State(config_manager): State<config::Manager>,
-) -> Result<Json<Vec<dto::User>>, APIError> {
-	let users = config_manager.get_users().await;
-	let users = users.into_iter().map(|u| u.into()).collect();
-	Ok(Json(users))
+async fn post_device_login(
+	State(device_login_manager): State<device_login::Manager>,
+	Json(input): Json<dto::DeviceLoginInput>,
+) -> Result<Json<dto::DeviceLogin>, APIError> {
+	let login = device_login_manager.begin_login(input.client_name)?;
+	Ok(Json(login.into()))
 }
 
 #[utoipa::path(
 	post,
-	path = "/user",
+	path = "/auth/device/approve",
 	tag = "User Management",
-	description = "Creates a new user account.",
+	description = "Signs in the device displaying the given code as the current user.",
 	security(
 		("auth_token" = []),
 		("auth_query_param" = []),
 	),
-	request_body = dto::NewUser,
+	request_body = dto::DeviceLoginApproval,
 	responses(
 		(status = 200),
-		(status = 400),
-		(status = 409)
-	)
+		(status = 404, description = "No device is displaying this code, or it expired"),
+	),
 )]
-async fn post_user(
-	_admin_rights: AdminRights,
-	State(config_manager): State<config::Manager>,
-	Json(new_user): Json<dto::NewUser>,
+async fn post_device_login_approval(
+	SessionAuth(auth): SessionAuth,
+	State(device_login_manager): State<device_login::Manager>,
+	Json(approval): Json<dto::DeviceLoginApproval>,
 ) -> Result<(), APIError> {
-	config_manager
-		.create_user(&new_user.name, &new_user.password, new_user.admin)
-		.await?;
+	device_login_manager.approve_login(&approval.user_code, auth.get_username())?;
 	Ok(())
 }
 
 #[utoipa::path(
-	put,
-	path = "/user/{name}",
+	post,
+	path = "/auth/device/token",
 	tag = "User Management",
-	description = "Amends an existing user account.\n\n`null` fields are left unchanged.",
+	description = "Returns a token for a device once users approved it. Tokens can only be retrieved once.",
+	request_body = dto::DeviceTokenInput,
+	responses(
+		(status = 200, body = dto::Authorization),
+		(status = 202, description = "The login was not approved yet"),
+		(status = 400, description = "The login expired"),
+		(status = 429, description = "The device polled sooner than `interval` seconds after its previous attempt"),
+	),
+)]
+async fn post_device_login_token(
+	State(config_manager): State<config::Manager>,
+	State(device_login_manager): State<device_login::Manager>,
+	State(session_manager): State<session::Manager>,
+	State(audit_manager): State<audit::Manager>,
+	client_ip: Option<Extension<ClientIp>>,
+	headers: HeaderMap,
+	Json(input): Json<dto::DeviceTokenInput>,
+) -> Result<Json<dto::Authorization>, APIError> {
+	let login = device_login_manager
+		.poll_login(&input.device_code)?
+		.ok_or(APIError::DeviceLoginPending)?;
+
+	let address = client_ip.map(|Extension(ClientIp(ip))| ip);
+	let user_agent = headers
+		.get(http::header::USER_AGENT)
+		.and_then(|h| h.to_str().ok())
+		.map(str::to_owned);
+	let client = session::Client {
+		name: login.client_name,
+		user_agent,
+		ip_address: address,
+	};
+
+	let auth::Token(token) = session_manager
+		.login_external(&login.username, None, false, client)
+		.await?;
+	audit_manager
+		.record(Some(&login.username), address, audit::Event::LoginSucceeded)
+		.await;
+	let is_admin = config_manager.get_user(&login.username).await?.is_admin();
+
+	Ok(Json(dto::Authorization {
+		username: login.username,
+		token,
+		is_admin,
+	}))
+}
+
+#[utoipa::path(
+	post,
+	path = "/auth/refresh",
+	tag = "User Management",
+	description = "Issues a new token for the session making the request. When tokens are configured to expire, clients should call this endpoint before their token expires to remain signed in.",
 	security(
 		("auth_token" = []),
 		("auth_query_param" = []),
 	),
-	request_body = dto::UserUpdate,
 	responses(
-		(status = 200),
-		(status = 404),
-		(status = 409)
-	)
+		(status = 200, body = dto::Authorization),
+		(status = 401),
+		(status = 403, description = "API keys cannot be refreshed"),
+	),
 )]
-async fn put_user(
-	admin_rights: AdminRights,
+async fn post_auth_refresh(
+	SessionAuth(auth): SessionAuth,
 	State(config_manager): State<config::Manager>,
-	Path(name): Path<String>,
-	user_update: Json<dto::UserUpdate>,
-) -> Result<(), APIError> {
-	if let Some(auth) = &admin_rights.get_auth() {
-		if auth.get_username() == name.as_str() && user_update.new_is_admin == Some(false) {
-			return Err(APIError::OwnAdminPrivilegeRemoval);
-		}
+) -> Result<Json<dto::Authorization>, APIError> {
+	if auth.is_api_key() {
+		return Err(APIError::ApiKeyScopeRequired);
 	}
+	let auth::Token(token) = config_manager
+		.refresh_auth_token(auth.get_username(), auth.get_session_id())
+		.await?;
+	let is_admin = config_manager
+		.get_user(auth.get_username())
+		.await?
+		.is_admin();
+	Ok(Json(dto::Authorization {
+		username: auth.get_username().clone(),
+		token,
+		is_admin,
+	}))
+}
 
-	if let Some(password) = &user_update.new_password {
-		config_manager.set_password(&name, password).await?;
+#[utoipa::path(
+	get,
+	path = "/oidc/login",
+	tag = "User Management",
+	description = "Starts signing in through the OpenID Connect identity provider configured on the server, by redirecting to it.",
+	params(dto::OidcLoginParameters),
+	responses(
+		(status = 303, description = "Redirects to the identity provider"),
+		(status = 404, description = "OpenID Connect is not configured"),
+		(status = 503, description = "Too many logins are pending"),
+	),
+)]
+async fn get_oidc_login(
+	State(config_manager): State<config::Manager>,
+	State(oidc_manager): State<oidc::Manager>,
+	Query(options): Query<dto::OidcLoginParameters>,
+	uri: Uri,
+	headers: HeaderMap,
+) -> Result<Response, APIError> {
+	let base_url = get_base_url(&config_manager, &uri, &headers).await;
+	let redirect_uri = format!("{base_url}/api/oidc/callback");
+	let url = oidc_manager
+		.begin_login(redirect_uri, options.return_to)
+		.await?;
+	Ok((StatusCode::SEE_OTHER, [(header::LOCATION, url)]).into_response())
+}
+
+#[utoipa::path(
+	get,
+	path = "/oidc/callback",
+	tag = "User Management",
+	description = "Completes signing in through OpenID Connect. Identity providers redirect users to this endpoint.\n\nWhen the login was started with a `return_to` path, users are redirected to it with the `username`, `token` and `is_admin` fields of the authorization in the URL fragment. Otherwise, the authorization is returned as JSON.",
+	params(dto::OidcCallbackParameters),
+	responses(
+		(status = 200, body = dto::Authorization),
+		(status = 303, description = "Redirects to the `return_to` path"),
+		(status = 400, description = "The login expired or was started on another server"),
+		(status = 401),
+		(status = 403, description = "A Polaris user with this name exists, but is not linked to this account of the identity provider"),
+		(status = 502, description = "The identity provider reported an error"),
+	),
+)]
+async fn get_oidc_callback(
+	State(config_manager): State<config::Manager>,
+	State(oidc_manager): State<oidc::Manager>,
+	State(session_manager): State<session::Manager>,
+	State(audit_manager): State<audit::Manager>,
+	client_ip: Option<Extension<ClientIp>>,
+	headers: HeaderMap,
+	Query(options): Query<dto::OidcCallbackParameters>,
+) -> Result<Response, APIError> {
+	if let Some(error) = options.error {
+		let description = options.error_description.unwrap_or_default();
+		return Err(APIError::OidcProviderError(
+			format!("{error} {description}").trim().to_owned(),
+		));
 	}
+	let (Some(state), Some(code)) = (options.state, options.code) else {
+		return Err(APIError::OidcLoginExpired);
+	};
 
-	if let Some(is_admin) = &user_update.new_is_admin {
-		config_manager.set_is_admin(&name, *is_admin).await?;
+	let identity = oidc_manager.complete_login(&state, &code).await?;
+	let address = client_ip.map(|Extension(ClientIp(ip))| ip);
+	let (username, created) = match config_manager
+		.resolve_oidc_user(&identity.subject, &identity.username, identity.create_user)
+		.await
+	{
+		Ok(resolved) => resolved,
+		Err(e) => {
+			audit_manager
+				.record(Some(&identity.username), address, audit::Event::LoginFailed)
+				.await;
+			return Err(e.into());
+		}
+	};
+	if created {
+		audit_manager
+			.record(
+				Some(&username),
+				address,
+				audit::Event::UserCreated {
+					name: username.clone(),
+					admin: false,
+				},
+			)
+			.await;
 	}
 
-	Ok(())
+	let user_agent = headers
+		.get(http::header::USER_AGENT)
+		.and_then(|h| h.to_str().ok())
+		.map(str::to_owned);
+	let client = session::Client {
+		name: None,
+		user_agent,
+		ip_address: address,
+	};
+
+	let auth::Token(token) = session_manager
+		.login_external(&username, identity.admin, false, client)
+		.await?;
+	audit_manager
+		.record(Some(&username), address, audit::Event::LoginSucceeded)
+		.await;
+	let is_admin = config_manager.get_user(&username).await?.is_admin();
+
+	let Some(return_to) = identity.return_to else {
+		return Ok(Json(dto::Authorization {
+			username,
+			token,
+			is_admin,
+		})
+		.into_response());
+	};
+
+	let encode = |s: &str| {
+		percent_encoding::utf8_percent_encode(s, percent_encoding::NON_ALPHANUMERIC).to_string()
+	};
+	let location = format!(
+		"{return_to}#username={}&token={}&is_admin={is_admin}",
+		encode(&username),
+		encode(&token),
+	);
+	Ok((StatusCode::SEE_OTHER, [(header::LOCATION, location)]).into_response())
 }
 
 #[utoipa::path(
-	delete,
-	path = "/user/{name}",
+	get,
+	path = "/sessions",
 	tag = "User Management",
-	description = "Deletes a user account.",
+	description = "Lists sessions of the current user, most recently active first.",
 	security(
 		("auth_token" = []),
 		("auth_query_param" = []),
 	),
 	responses(
-		(status = 200),
-		(status = 404),
-		(status = 409)
-	)
+		(status = 200, body = Vec<dto::Session>),
+	),
 )]
-async fn delete_user(
-	admin_rights: AdminRights,
-	State(config_manager): State<config::Manager>,
-	Path(name): Path<String>,
-) -> Result<(), APIError> {
-	if let Some(auth) = &admin_rights.get_auth() {
-		if auth.get_username() == name.as_str() {
-			return Err(APIError::DeletingOwnAccount);
-		}
-	}
-	config_manager.delete_user(&name).await?;
-	Ok(())
+async fn get_sessions(
+	auth: Auth,
+	State(session_manager): State<session::Manager>,
+) -> Result<Json<Vec<dto::Session>>, APIError> {
+	let sessions = session_manager
+		.list_sessions(auth.get_username())
+		.await?
+		.into_iter()
+		.map(|s| dto::Session {
+			current: auth.get_session_id() == Some(s.id.as_str()),
+			..s.into()
+		})
+		.collect();
+	Ok(Json(sessions))
 }
 
 #[utoipa::path(
-	post,
-	path = "/trigger_index",	
-	tag = "Configuration",
-	description = "Starts a scan of the mount directories that contain music files. If a scan is already in progress, it will be interrupted.\n\nThe music collection will update after the scan is fully completed.",
+	delete,
+	path = "/sessions",
+	tag = "User Management",
+	description = "Revokes all sessions of the current user, except the one making the request. Devices holding tokens for these sessions will have to sign in again.",
 	security(
 		("auth_token" = []),
 		("auth_query_param" = []),
 	),
 )]
-async fn post_trigger_index(
-	_admin_rights: AdminRights,
-	State(scanner): State<scanner::Scanner>,
+async fn delete_sessions(
+	SessionAuth(auth): SessionAuth,
+	State(session_manager): State<session::Manager>,
 ) -> Result<(), APIError> {
-	scanner.try_trigger_scan();
+	session_manager
+		.revoke_other_sessions(auth.get_username(), auth.get_session_id())
+		.await?;
 	Ok(())
 }
 
 #[utoipa::path(
-	get,
-	path = "/index_status",
-	tag = "Configuration",
-	description = "Returns the current state of the collection scanning process.",
+	delete,
+	path = "/session/{id}",
+	tag = "User Management",
+	description = "Revokes a session of the current user. The device holding a token for this session will have to sign in again.",
+	params(("id", example = "Qx8bz0a1Ee9tTKkUzu1qgPjRsFkXyV3m")),
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	responses(
+		(status = 200),
+		(status = 404),
+	),
+)]
+async fn delete_session(
+	SessionAuth(auth): SessionAuth,
+	State(session_manager): State<session::Manager>,
+	Path(id): Path<String>,
+) -> Result<(), APIError> {
+	session_manager
+		.revoke_session(auth.get_username(), &id)
+		.await?;
+	Ok(())
+}
+
+// Passkeys are bound to the configured relying party, or to the host name users see in their
+// browser when none is configured
+async fn relying_party(
+	config_manager: &config::Manager,
+	uri: &Uri,
+	headers: &HeaderMap,
+) -> webauthn::RelyingParty {
+	if let Some(passkeys) = config_manager.get_passkeys().await {
+		return webauthn::RelyingParty {
+			id: passkeys.rp_id,
+			origin: passkeys.origin,
+		};
+	}
+	let base_url = get_base_url(config_manager, uri, headers).await;
+	let base_url = Uri::try_from(base_url).unwrap_or_default();
+	let scheme = base_url.scheme_str().unwrap_or("http");
+	let authority = base_url
+		.authority()
+		.map(|a| a.as_str())
+		.unwrap_or("localhost");
+	webauthn::RelyingParty {
+		id: base_url.host().unwrap_or("localhost").to_owned(),
+		origin: format!("{scheme}://{authority}"),
+	}
+}
+
+fn decode_base64url(input: &str) -> Option<Vec<u8>> {
+	use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+	URL_SAFE_NO_PAD.decode(input.trim_end_matches('=')).ok()
+}
+
+#[utoipa::path(
+	get,
+	path = "/passkeys",
+	tag = "User Management",
+	description = "Lists passkeys of the current user.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	responses(
+		(status = 200, body = Vec<dto::Passkey>),
+	),
+)]
+async fn get_passkeys(
+	auth: Auth,
+	State(webauthn_manager): State<webauthn::Manager>,
+) -> Result<Json<Vec<dto::Passkey>>, APIError> {
+	let passkeys = webauthn_manager
+		.list_passkeys(auth.get_username())
+		.await?
+		.into_iter()
+		.map(|p| p.into())
+		.collect();
+	Ok(Json(passkeys))
+}
+
+#[utoipa::path(
+	delete,
+	path = "/passkey/{id}",
+	tag = "User Management",
+	description = "Removes a passkey of the current user.",
+	params(("id", example = "dGVzdC1jcmVkZW50aWFs")),
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	responses(
+		(status = 200),
+		(status = 404),
+	),
+)]
+async fn delete_passkey(
+	auth: Auth,
+	State(webauthn_manager): State<webauthn::Manager>,
+	Path(id): Path<String>,
+) -> Result<(), APIError> {
+	webauthn_manager
+		.delete_passkey(auth.get_username(), &id)
+		.await?;
+	Ok(())
+}
+
+#[utoipa::path(
+	post,
+	path = "/passkeys/register/start",
+	tag = "User Management",
+	description = "Starts registering a passkey for the current user. The returned options should be passed to `navigator.credentials.create()`, and its result to `/passkeys/register/finish`.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	responses(
+		(status = 200, body = dto::PasskeyCreationOptions),
+		(status = 403, description = "Passkeys cannot be registered with an API key"),
+		(status = 503, description = "Too many logins are pending"),
+	),
+)]
+async fn post_passkey_register_start(
+	auth: Auth,
+	State(config_manager): State<config::Manager>,
+	State(webauthn_manager): State<webauthn::Manager>,
+	uri: Uri,
+	headers: HeaderMap,
+) -> Result<Json<dto::PasskeyCreationOptions>, APIError> {
+	if auth.is_api_key() {
+		return Err(APIError::ApiKeyScopeRequired);
+	}
+	let relying_party = relying_party(&config_manager, &uri, &headers).await;
+	let options = webauthn_manager
+		.begin_registration(auth.get_username(), relying_party)
+		.await?;
+	Ok(Json(options.into()))
+}
+
+#[utoipa::path(
+	post,
+	path = "/passkeys/register/finish",
+	tag = "User Management",
+	description = "Completes registering a passkey for the current user.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	request_body = dto::NewPasskey,
+	responses(
+		(status = 200, body = dto::Passkey),
+		(status = 400),
+		(status = 403, description = "Passkeys cannot be registered with an API key"),
+		(status = 409, description = "The passkey is already registered"),
+	),
+)]
+async fn post_passkey_register_finish(
+	auth: Auth,
+	State(webauthn_manager): State<webauthn::Manager>,
+	Json(new_passkey): Json<dto::NewPasskey>,
+) -> Result<Json<dto::Passkey>, APIError> {
+	if auth.is_api_key() {
+		return Err(APIError::ApiKeyScopeRequired);
+	}
+	let invalid = || APIError::InvalidPasskeyRegistration("malformed base64url".to_owned());
+	let client_data_json = decode_base64url(&new_passkey.client_data_json).ok_or_else(invalid)?;
+	let attestation_object =
+		decode_base64url(&new_passkey.attestation_object).ok_or_else(invalid)?;
+	let passkey = webauthn_manager
+		.finish_registration(
+			auth.get_username(),
+			new_passkey.name.as_deref().unwrap_or_default(),
+			&client_data_json,
+			&attestation_object,
+		)
+		.await?;
+	Ok(Json(passkey.into()))
+}
+
+#[utoipa::path(
+	post,
+	path = "/passkeys/login/start",
+	tag = "User Management",
+	description = "Starts signing in with a passkey. The returned options should be passed to `navigator.credentials.get()`, and its result to `/passkeys/login/finish`. When a username is given, users without passkeys (including unknown users) receive a stand-in passkey so responses do not reveal which accounts exist.",
+	request_body = dto::PasskeyLoginOptions,
+	responses(
+		(status = 200, body = dto::PasskeyRequestOptions),
+		(status = 503, description = "Too many logins are pending"),
+	),
+)]
+async fn post_passkey_login_start(
+	State(config_manager): State<config::Manager>,
+	State(webauthn_manager): State<webauthn::Manager>,
+	uri: Uri,
+	headers: HeaderMap,
+	Json(options): Json<dto::PasskeyLoginOptions>,
+) -> Result<Json<dto::PasskeyRequestOptions>, APIError> {
+	let relying_party = relying_party(&config_manager, &uri, &headers).await;
+	let options = webauthn_manager
+		.begin_authentication(options.username.as_deref(), relying_party)
+		.await?;
+	Ok(Json(options.into()))
+}
+
+#[utoipa::path(
+	post,
+	path = "/passkeys/login/finish",
+	tag = "User Management",
+	description = "Completes signing in with a passkey. Tokens returned by this endpoint are equivalent to those returned by `/auth`.",
+	request_body = dto::PasskeyAssertion,
+	responses(
+		(status = 200, body = dto::Authorization),
+		(status = 400, description = "The challenge expired or was not issued by this server"),
+		(status = 401),
+	),
+)]
+async fn post_passkey_login_finish(
+	State(config_manager): State<config::Manager>,
+	State(session_manager): State<session::Manager>,
+	State(webauthn_manager): State<webauthn::Manager>,
+	State(audit_manager): State<audit::Manager>,
+	client_ip: Option<Extension<ClientIp>>,
+	headers: HeaderMap,
+	Json(assertion): Json<dto::PasskeyAssertion>,
+) -> Result<Json<dto::Authorization>, APIError> {
+	let decode = |input: &str| decode_base64url(input).ok_or(APIError::IncorrectCredentials);
+	let username = webauthn_manager
+		.finish_authentication(&webauthn::Assertion {
+			credential_id: assertion.credential_id.clone(),
+			client_data_json: decode(&assertion.client_data_json)?,
+			authenticator_data: decode(&assertion.authenticator_data)?,
+			signature: decode(&assertion.signature)?,
+		})
+		.await?;
+
+	let user_agent = headers
+		.get(http::header::USER_AGENT)
+		.and_then(|h| h.to_str().ok())
+		.map(str::to_owned);
+	let address = client_ip.map(|Extension(ClientIp(ip))| ip);
+	let client = session::Client {
+		name: assertion.client_name,
+		user_agent,
+		ip_address: address,
+	};
+
+	let auth::Token(token) = session_manager
+		.login_external(&username, None, false, client)
+		.await?;
+	audit_manager
+		.record(Some(&username), address, audit::Event::LoginSucceeded)
+		.await;
+	let is_admin = config_manager.get_user(&username).await?.is_admin();
+	Ok(Json(dto::Authorization {
+		username,
+		token,
+		is_admin,
+	}))
+}
+
+#[utoipa::path(
+	get,
+	path = "/api_keys",
+	tag = "User Management",
+	description = "Lists API keys of the current user.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	responses(
+		(status = 200, body = Vec<dto::ApiKey>),
+	),
+)]
+async fn get_api_keys(
+	auth: Auth,
+	State(api_key_manager): State<api_key::Manager>,
+) -> Result<Json<Vec<dto::ApiKey>>, APIError> {
+	let api_keys = api_key_manager
+		.list_api_keys(auth.get_username())
+		.await?
+		.into_iter()
+		.map(|k| k.into())
+		.collect();
+	Ok(Json(api_keys))
+}
+
+#[utoipa::path(
+	post,
+	path = "/api_keys",
+	tag = "User Management",
+	description = "Creates a long-lived API key for the current user, for scripts and headless clients. API keys are used like auth tokens, but can only make requests allowed by their scopes.\n\nRequests authenticated with an API key can only create keys with the same or fewer scopes.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	request_body = dto::NewApiKey,
+	responses(
+		(status = 200, body = dto::CreatedApiKey),
+		(status = 400),
+		(status = 403),
+	),
+)]
+async fn post_api_key(
+	auth: Auth,
+	State(api_key_manager): State<api_key::Manager>,
+	State(audit_manager): State<audit::Manager>,
+	client_ip: Option<Extension<ClientIp>>,
+	Json(new_api_key): Json<dto::NewApiKey>,
+) -> Result<Json<dto::CreatedApiKey>, APIError> {
+	let scopes = new_api_key
+		.scopes
+		.into_iter()
+		.map(api_key::Scope::from)
+		.collect::<Vec<_>>();
+	if !scopes.iter().all(|s| auth.allows(*s)) {
+		return Err(APIError::ApiKeyScopeRequired);
+	}
+	let (api_key, key) = api_key_manager
+		.create_api_key(auth.get_username(), &new_api_key.name, scopes)
+		.await?;
+	audit_manager
+		.record(
+			Some(auth.get_username()),
+			client_ip.map(|Extension(ClientIp(ip))| ip),
+			audit::Event::ApiKeyCreated {
+				id: api_key.id.clone(),
+				name: api_key.name.clone(),
+			},
+		)
+		.await;
+	Ok(Json(dto::CreatedApiKey {
+		api_key: api_key.into(),
+		key,
+	}))
+}
+
+#[utoipa::path(
+	delete,
+	path = "/api_key/{id}",
+	tag = "User Management",
+	description = "Revokes an API key of the current user.",
+	params(("id", example = "mV3kq8PzR1cXa9Lw")),
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	responses(
+		(status = 200),
+		(status = 404),
+	),
+)]
+async fn delete_api_key(
+	auth: Auth,
+	State(api_key_manager): State<api_key::Manager>,
+	State(audit_manager): State<audit::Manager>,
+	client_ip: Option<Extension<ClientIp>>,
+	Path(id): Path<String>,
+) -> Result<(), APIError> {
+	api_key_manager
+		.revoke_api_key(auth.get_username(), &id)
+		.await?;
+	audit_manager
+		.record(
+			Some(auth.get_username()),
+			client_ip.map(|Extension(ClientIp(ip))| ip),
+			audit::Event::ApiKeyRevoked { id },
+		)
+		.await;
+	Ok(())
+}
+
+#[utoipa::path(
+	get,
+	path = "/invites",
+	tag = "User Management",
+	description = "Lists invites which have not been used or expired yet.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	responses(
+		(status = 200, body = Vec<dto::Invite>),
+	),
+)]
+async fn get_invites(
+	_rights: Permitted<permission::ManageUsers>,
+	State(invite_manager): State<invite::Manager>,
+) -> Result<Json<Vec<dto::Invite>>, APIError> {
+	let invites = invite_manager
+		.list_invites()
+		.await?
+		.into_iter()
+		.map(|i| i.into())
+		.collect();
+	Ok(Json(invites))
+}
+
+#[utoipa::path(
+	post,
+	path = "/invites",
+	tag = "User Management",
+	description = "Creates an invite, whose token lets someone create their own account with the `/register` endpoint. Each invite can only be used once, and accounts created with it are never administrators.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	request_body = dto::NewInvite,
+	responses(
+		(status = 200, body = dto::CreatedInvite),
+		(status = 400),
+	),
+)]
+async fn post_invite(
+	rights: Permitted<permission::ManageUsers>,
+	State(invite_manager): State<invite::Manager>,
+	State(audit_manager): State<audit::Manager>,
+	client_ip: Option<Extension<ClientIp>>,
+	Json(new_invite): Json<dto::NewInvite>,
+) -> Result<Json<dto::CreatedInvite>, APIError> {
+	let created_by = rights
+		.get_auth()
+		.as_ref()
+		.map(|a| a.get_username().to_owned())
+		.unwrap_or_default();
+	let validity_days = new_invite
+		.valid_for_days
+		.unwrap_or(invite::DEFAULT_VALIDITY_DAYS);
+	let (invite, token) = invite_manager
+		.create_invite(&created_by, validity_days)
+		.await?;
+	audit_manager
+		.record(
+			actor(rights.get_auth()),
+			client_ip.map(|Extension(ClientIp(ip))| ip),
+			audit::Event::InviteCreated {
+				id: invite.id.clone(),
+			},
+		)
+		.await;
+	Ok(Json(dto::CreatedInvite {
+		invite: invite.into(),
+		token,
+	}))
+}
+
+#[utoipa::path(
+	delete,
+	path = "/invite/{id}",
+	tag = "User Management",
+	description = "Revokes an invite before it is used.",
+	params(("id", example = "Qw7rT2xLp0sVb4Nc")),
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	responses(
+		(status = 200),
+		(status = 404),
+	),
+)]
+async fn delete_invite(
+	rights: Permitted<permission::ManageUsers>,
+	State(invite_manager): State<invite::Manager>,
+	State(audit_manager): State<audit::Manager>,
+	client_ip: Option<Extension<ClientIp>>,
+	Path(id): Path<String>,
+) -> Result<(), APIError> {
+	invite_manager.revoke_invite(&id).await?;
+	audit_manager
+		.record(
+			actor(rights.get_auth()),
+			client_ip.map(|Extension(ClientIp(ip))| ip),
+			audit::Event::InviteRevoked { id },
+		)
+		.await;
+	Ok(())
+}
+
+#[utoipa::path(
+	post,
+	path = "/register",
+	tag = "User Management",
+	description = "Creates an account using an invite token. The new user can then sign in with the `/auth` endpoint.",
+	request_body = dto::Registration,
+	responses(
+		(status = 200),
+		(status = 400, description = "The username is empty or the password does not meet the password policy"),
+		(status = 401, description = "The invite is invalid, expired or was already used"),
+		(status = 409),
+		(status = 429, description = "Too many failed attempts. The `Retry-After` header indicates how many seconds to wait before trying again."),
+	),
+)]
+async fn post_register(
+	State(invite_manager): State<invite::Manager>,
+	State(hooks_manager): State<hooks::Manager>,
+	State(audit_manager): State<audit::Manager>,
+	client_ip: Option<Extension<ClientIp>>,
+	Json(registration): Json<dto::Registration>,
+) -> Result<(), APIError> {
+	let address = client_ip.map(|Extension(ClientIp(ip))| ip);
+	invite_manager
+		.register(
+			&registration.token,
+			&registration.username,
+			&registration.password,
+			address,
+		)
+		.await?;
+	audit_manager
+		.record(
+			Some(&registration.username),
+			address,
+			audit::Event::UserCreated {
+				name: registration.username.clone(),
+				admin: false,
+			},
+		)
+		.await;
+	hooks_manager
+		.notify(hooks::Event::UserCreated {
+			name: registration.username,
+			admin: false,
+		})
+		.await;
+	Ok(())
+}
+
+#[utoipa::path(
+	get,
+	path = "/audit_log",
+	tag = "User Management",
+	description = "Returns security-relevant events, such as logins and changes to users or settings, most recent first.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(dto::GetAuditLogParameters),
+	responses(
+		(status = 200, body = Vec<dto::AuditEntry>),
+	),
+)]
+async fn get_audit_log(
+	_admin_rights: AdminRights,
+	State(audit_manager): State<audit::Manager>,
+	Query(options): Query<dto::GetAuditLogParameters>,
+) -> Result<Json<Vec<dto::AuditEntry>>, APIError> {
+	let filter = audit::Filter {
+		actor: options.actor,
+		since: options.since,
+		until: options.until,
+		limit: options.count.unwrap_or(audit::DEFAULT_QUERY_LIMIT),
+	};
+	let entries = audit_manager
+		.query(filter)
+		.await?
+		.into_iter()
+		.map(|e| e.into())
+		.collect();
+	Ok(Json(entries))
+}
+
+#[utoipa::path(
+	get,
+	path = "/preferences",
+	tag = "User Management",
+	description = "Returns the preferences of the current user, such as their theme or language. Polaris stores these for clients without interpreting them, so that they follow users between devices.\n\nThe `browse_sort` and `album_sort` keys are an exception: they set the default order of file and album listings, using the values of their `sort` query parameter. Likewise, setting `family_mix` to `true` lets other users blend the playlists of the current user into their family mixes.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	responses(
+		(status = 200, body = dto::Preferences),
+	),
+)]
+async fn get_preferences(
+	auth: Auth,
+	State(preferences_manager): State<preferences::Manager>,
+) -> Result<Json<dto::Preferences>, APIError> {
+	let preferences = preferences_manager
+		.get_preferences(auth.get_username())
+		.await?;
+	Ok(Json(preferences.into()))
+}
+
+#[utoipa::path(
+	put,
+	path = "/preferences",
+	tag = "User Management",
+	description = "Amends the preferences of the current user and returns all of them.\n\nKeys which are omitted are left unchanged, keys set to `null` are removed. Keys can contain letters, digits, `_`, `-` and `.`, up to 64 characters.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	request_body = dto::Preferences,
+	responses(
+		(status = 200, body = dto::Preferences),
+		(status = 400),
+		(status = 413),
+	),
+)]
+async fn put_preferences(
+	auth: Auth,
+	State(preferences_manager): State<preferences::Manager>,
+	Json(update): Json<dto::Preferences>,
+) -> Result<Json<dto::Preferences>, APIError> {
+	let preferences = preferences_manager
+		.update_preferences(auth.get_username(), update.into())
+		.await?;
+	Ok(Json(preferences.into()))
+}
+
+#[utoipa::path(
+	get,
+	path = "/listenbrainz",
+	tag = "User Management",
+	description = "Returns the ListenBrainz account linked to the current user.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	responses(
+		(status = 200, body = dto::ListenBrainzAccount),
+		(status = 404),
+	),
+)]
+async fn get_listenbrainz(
+	auth: Auth,
+	State(listenbrainz_manager): State<listenbrainz::Manager>,
+) -> Result<Json<dto::ListenBrainzAccount>, APIError> {
+	let account = listenbrainz_manager
+		.get_account(auth.get_username())
+		.await?;
+	Ok(Json(account.into()))
+}
+
+#[utoipa::path(
+	put,
+	path = "/listenbrainz",
+	tag = "User Management",
+	description = "Links a ListenBrainz account to the current user. Once linked, songs reported as playing are submitted as `playing now`, and songs added to the listening history are submitted as listens. This works independently of other scrobbling services.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	request_body = dto::ListenBrainzLink,
+	responses(
+		(status = 200, body = dto::ListenBrainzAccount),
+		(status = 400),
+		(status = 502),
+	),
+)]
+async fn put_listenbrainz(
+	auth: Auth,
+	State(listenbrainz_manager): State<listenbrainz::Manager>,
+	Json(link): Json<dto::ListenBrainzLink>,
+) -> Result<Json<dto::ListenBrainzAccount>, APIError> {
+	let account = listenbrainz_manager
+		.link(auth.get_username(), &link.token)
+		.await?;
+	Ok(Json(account.into()))
+}
+
+#[utoipa::path(
+	delete,
+	path = "/listenbrainz",
+	tag = "User Management",
+	description = "Unlinks the ListenBrainz account of the current user.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	responses(
+		(status = 200),
+		(status = 404),
+	),
+)]
+async fn delete_listenbrainz(
+	auth: Auth,
+	State(listenbrainz_manager): State<listenbrainz::Manager>,
+) -> Result<(), APIError> {
+	listenbrainz_manager.unlink(auth.get_username()).await?;
+	Ok(())
+}
+
+// Username recorded in the audit log, which is unknown while setting up a server without users
+fn actor(auth: &Option<Auth>) -> Option<&str> {
+	auth.as_ref().map(|a| a.get_username().as_str())
+}
+
+// Users who were delegated user management cannot act on administrator accounts
+async fn check_user_delegation(
+	rights: &Permitted<permission::ManageUsers>,
+	config_manager: &config::Manager,
+	name: &str,
+) -> Result<(), APIError> {
+	if rights.is_admin() {
+		return Ok(());
+	}
+	match config_manager.get_user(name).await {
+		Ok(user) if user.is_admin() => Err(APIError::AdminPermissionRequired),
+		_ => Ok(()),
+	}
+}
+
+#[utoipa::path(
+	get,
+	path = "/users",
+	tag = "User Management",
+	description = "Lists existing user accounts.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	responses(
+		(status = 200, body = Vec<dto::User>),
+	),
+)]
+async fn get_users(
+	_rights: Permitted<permission::ManageUsers>,
+	State(config_manager): State<config::Manager>,
+) -> Result<Json<Vec<dto::User>>, APIError> {
+	let users = config_manager.get_users().await;
+	let users = users.into_iter().map(|u| u.into()).collect();
+	Ok(Json(users))
+}
+
+#[utoipa::path(
+	post,
+	path = "/user",
+	tag = "User Management",
+	description = "Creates a new user account.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	request_body = dto::NewUser,
+	responses(
+		(status = 200),
+		(status = 400),
+		(status = 409)
+	)
+)]
+async fn post_user(
+	rights: Permitted<permission::ManageUsers>,
+	State(config_manager): State<config::Manager>,
+	State(hooks_manager): State<hooks::Manager>,
+	State(audit_manager): State<audit::Manager>,
+	client_ip: Option<Extension<ClientIp>>,
+	Json(new_user): Json<dto::NewUser>,
+) -> Result<(), APIError> {
+	if !rights.is_admin() && (new_user.admin || new_user.permissions.is_some()) {
+		return Err(APIError::AdminPermissionRequired);
+	}
+	config_manager
+		.create_user(&new_user.name, &new_user.password, new_user.admin)
+		.await?;
+	if new_user.guest {
+		config_manager.set_is_guest(&new_user.name, true).await?;
+	}
+	if let Some(permissions) = new_user.permissions {
+		let permissions = permissions.into_iter().map(|p| p.into()).collect();
+		config_manager
+			.set_permissions(&new_user.name, Some(permissions))
+			.await?;
+	}
+	audit_manager
+		.record(
+			actor(rights.get_auth()),
+			client_ip.map(|Extension(ClientIp(ip))| ip),
+			audit::Event::UserCreated {
+				name: new_user.name.clone(),
+				admin: new_user.admin,
+			},
+		)
+		.await;
+	hooks_manager
+		.notify(hooks::Event::UserCreated {
+			name: new_user.name,
+			admin: new_user.admin,
+		})
+		.await;
+	Ok(())
+}
+
+#[utoipa::path(
+	put,
+	path = "/user/{name}",
+	tag = "User Management",
+	description = "Amends an existing user account.\n\n`null` fields are left unchanged.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	request_body = dto::UserUpdate,
+	responses(
+		(status = 200),
+		(status = 404),
+		(status = 409)
+	)
+)]
+async fn put_user(
+	rights: Permitted<permission::ManageUsers>,
+	State(config_manager): State<config::Manager>,
+	State(audit_manager): State<audit::Manager>,
+	client_ip: Option<Extension<ClientIp>>,
+	Path(name): Path<String>,
+	user_update: Json<dto::UserUpdate>,
+) -> Result<(), APIError> {
+	if let Some(auth) = &rights.get_auth() {
+		if auth.get_username() == name.as_str() && user_update.new_is_admin == Some(false) {
+			return Err(APIError::OwnAdminPrivilegeRemoval);
+		}
+	}
+	if (user_update.new_is_admin.is_some() || user_update.new_permissions.is_some())
+		&& !rights.is_admin()
+	{
+		return Err(APIError::AdminPermissionRequired);
+	}
+	check_user_delegation(&rights, &config_manager, &name).await?;
+
+	if let Some(password) = &user_update.new_password {
+		config_manager.set_password(&name, password).await?;
+	}
+
+	if let Some(is_admin) = &user_update.new_is_admin {
+		config_manager.set_is_admin(&name, *is_admin).await?;
+	}
+
+	if let Some(is_guest) = &user_update.new_is_guest {
+		config_manager.set_is_guest(&name, *is_guest).await?;
+	}
+
+	if let Some(permissions) = &user_update.new_permissions {
+		let permissions = permissions.iter().map(|p| (*p).into()).collect();
+		config_manager
+			.set_permissions(&name, Some(permissions))
+			.await?;
+	}
+
+	audit_manager
+		.record(
+			actor(rights.get_auth()),
+			client_ip.map(|Extension(ClientIp(ip))| ip),
+			audit::Event::UserUpdated { name },
+		)
+		.await;
+	Ok(())
+}
+
+#[utoipa::path(
+	delete,
+	path = "/user/{name}",
+	tag = "User Management",
+	description = "Deletes a user account. Sessions, API keys and passkey registrations in progress of the user are revoked, so that none of them work if an account with the same name is created later.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	responses(
+		(status = 200),
+		(status = 404),
+		(status = 409)
+	)
+)]
+async fn delete_user(
+	rights: Permitted<permission::ManageUsers>,
+	State(config_manager): State<config::Manager>,
+	State(session_manager): State<session::Manager>,
+	State(api_key_manager): State<api_key::Manager>,
+	State(webauthn_manager): State<webauthn::Manager>,
+	State(audit_manager): State<audit::Manager>,
+	client_ip: Option<Extension<ClientIp>>,
+	Path(name): Path<String>,
+) -> Result<(), APIError> {
+	if let Some(auth) = &rights.get_auth() {
+		if auth.get_username() == name.as_str() {
+			return Err(APIError::DeletingOwnAccount);
+		}
+	}
+	check_user_delegation(&rights, &config_manager, &name).await?;
+	config_manager.delete_user(&name).await?;
+	session_manager.revoke_other_sessions(&name, None).await?;
+	api_key_manager.revoke_all_api_keys(&name).await?;
+	webauthn_manager.cancel_registrations(&name);
+	audit_manager
+		.record(
+			actor(rights.get_auth()),
+			client_ip.map(|Extension(ClientIp(ip))| ip),
+			audit::Event::UserDeleted { name },
+		)
+		.await;
+	Ok(())
+}
+
+#[utoipa::path(
+	post,
+	path = "/user/{name}/logout",
+	tag = "User Management",
+	description = "Signs a user out of all their devices. All sessions of the user are revoked, and all tokens previously issued to them stop working, including playlist feed URLs. Their API keys are revoked too, unless `keep_api_keys` is `true`.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(dto::LogoutUserParameters),
+	responses(
+		(status = 200),
+		(status = 404),
+	)
+)]
+async fn post_user_logout(
+	rights: Permitted<permission::ManageUsers>,
+	State(config_manager): State<config::Manager>,
+	State(session_manager): State<session::Manager>,
+	State(api_key_manager): State<api_key::Manager>,
+	State(audit_manager): State<audit::Manager>,
+	client_ip: Option<Extension<ClientIp>>,
+	Path(name): Path<String>,
+	Query(options): Query<dto::LogoutUserParameters>,
+) -> Result<(), APIError> {
+	check_user_delegation(&rights, &config_manager, &name).await?;
+	session_manager.revoke_all_sessions(&name).await?;
+	if !options.keep_api_keys.unwrap_or_default() {
+		api_key_manager.revoke_all_api_keys(&name).await?;
+	}
+	audit_manager
+		.record(
+			actor(rights.get_auth()),
+			client_ip.map(|Extension(ClientIp(ip))| ip),
+			audit::Event::UserLoggedOut { name },
+		)
+		.await;
+	Ok(())
+}
+
+#[utoipa::path(
+	post,
+	path = "/user/{name}/unlock",
+	tag = "User Management",
+	description = "Lifts the lockout of a user whose account was locked after too many failed logins, and clears their failed login count.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	responses(
+		(status = 200),
+		(status = 404),
+	)
+)]
+async fn post_user_unlock(
+	rights: Permitted<permission::ManageUsers>,
+	State(config_manager): State<config::Manager>,
+	State(session_manager): State<session::Manager>,
+	State(audit_manager): State<audit::Manager>,
+	client_ip: Option<Extension<ClientIp>>,
+	Path(name): Path<String>,
+) -> Result<(), APIError> {
+	config_manager.get_user(&name).await?;
+	check_user_delegation(&rights, &config_manager, &name).await?;
+	session_manager.unlock(&name);
+	audit_manager
+		.record(
+			actor(rights.get_auth()),
+			client_ip.map(|Extension(ClientIp(ip))| ip),
+			audit::Event::UserUnlocked { name },
+		)
+		.await;
+	Ok(())
+}
+
+#[utoipa::path(
+	get,
+	path = "/user/{name}/transfers",
+	tag = "User Management",
+	description = "Returns how much audio and archive data was sent to a user each month, and their monthly quota.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	responses(
+		(status = 200, body = dto::Transfers),
+		(status = 404),
+	)
+)]
+async fn get_user_transfers(
+	rights: Permitted<permission::ManageUsers>,
+	State(config_manager): State<config::Manager>,
+	State(transfers_manager): State<transfers::Manager>,
+	Path(name): Path<String>,
+) -> Result<Json<dto::Transfers>, APIError> {
+	check_user_delegation(&rights, &config_manager, &name).await?;
+	let usage = transfers_manager.get_usage(&name).await?;
+	Ok(Json(usage.into()))
+}
+
+#[utoipa::path(
+	get,
+	path = "/transfers",
+	tag = "User Management",
+	description = "Returns how much audio and archive data was sent to the current user each month, and their monthly quota. Once the quota is used up, audio is transcoded to a lower bitrate until the end of the month.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	responses(
+		(status = 200, body = dto::Transfers),
+	)
+)]
+async fn get_transfers(
+	auth: Auth,
+	State(transfers_manager): State<transfers::Manager>,
+) -> Result<Json<dto::Transfers>, APIError> {
+	let usage = transfers_manager.get_usage(auth.get_username()).await?;
+	Ok(Json(usage.into()))
+}
+
+#[utoipa::path(
+	post,
+	path = "/trigger_index",	
+	tag = "Configuration",
+	description = "Starts a scan of the mount directories that contain music files. If a scan is already in progress, it will be interrupted.\n\nThe music collection will update after the scan is fully completed.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+)]
+async fn post_trigger_index(
+	_rights: Permitted<permission::TriggerScan>,
+	State(scanner): State<scanner::Scanner>,
+) -> Result<(), APIError> {
+	scanner.try_trigger_scan();
+	Ok(())
+}
+
+#[utoipa::path(
+	post,
+	path = "/index/refresh",
+	tag = "Configuration",
+	description = "Immediately scans a single directory of the music collection and updates the index with its content, without waiting for a full scan. This is useful after adding an album to the collection. If a collection scan is in progress, the directory is scanned once it completes. While a collection scan is paused, refreshing fails instead.\n\nThe response lists all songs found within the directory.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	request_body = dto::IndexRefreshInput,
+	responses(
+		(status = 200, body = dto::SongList),
+		(status = 404),
+		(status = 409),
+	)
+)]
+async fn post_index_refresh(
+	_rights: Permitted<permission::TriggerScan>,
+	State(scanner): State<scanner::Scanner>,
+	State(index_manager): State<index::Manager>,
+	Json(input): Json<dto::IndexRefreshInput>,
+) -> Result<Json<dto::SongList>, APIError> {
+	let paths = scanner.refresh_directory(input.path).await?;
+	Ok(Json(make_song_list(paths, &index_manager).await))
+}
+
+#[utoipa::path(
+	post,
+	path = "/index/pause",
+	tag = "Configuration",
+	description = "Pauses the collection scan in progress. The scan can later be resumed from where it stopped.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	responses(
+		(status = 200),
+		(status = 409),
+	)
+)]
+async fn post_index_pause(
+	_rights: Permitted<permission::TriggerScan>,
+	State(scanner): State<scanner::Scanner>,
+) -> Result<(), APIError> {
+	scanner.pause_scan().await?;
+	Ok(())
+}
+
+#[utoipa::path(
+	post,
+	path = "/index/resume",
+	tag = "Configuration",
+	description = "Resumes a paused collection scan.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	responses(
+		(status = 200),
+		(status = 409),
+	)
+)]
+async fn post_index_resume(
+	_rights: Permitted<permission::TriggerScan>,
+	State(scanner): State<scanner::Scanner>,
+) -> Result<(), APIError> {
+	scanner.resume_scan().await?;
+	Ok(())
+}
+
+#[utoipa::path(
+	post,
+	path = "/index/cancel",
+	tag = "Configuration",
+	description = "Stops the collection scan in progress or paused. The music collection keeps its content from the previous scan.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	responses(
+		(status = 200),
+		(status = 409),
+	)
+)]
+async fn post_index_cancel(
+	_rights: Permitted<permission::TriggerScan>,
+	State(scanner): State<scanner::Scanner>,
+) -> Result<(), APIError> {
+	scanner.cancel_scan().await?;
+	Ok(())
+}
+
+#[utoipa::path(
+	get,
+	path = "/index_status",
+	tag = "Configuration",
+	description = "Returns the current state of the collection scanning process, including its phase, progress counts, elapsed time, how many songs were added, updated or removed, and which files could not be read.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	responses(
+		(status = 200, body = dto::IndexStatus),
+	)
+)]
+async fn get_index_status(
+	_rights: Permitted<permission::TriggerScan>,
+	State(scanner): State<scanner::Scanner>,
+) -> Result<Json<dto::IndexStatus>, APIError> {
+	Ok(Json(scanner.get_status().await.into()))
+}
+
+fn index_files_to_response(files: Vec<index::File>, api_version: APIMajorVersion) -> Response {
+	match api_version {
+		APIMajorVersion::V7 => Json(
+			files
+				.into_iter()
+				.map(|f| f.into())
+				.collect::<Vec<dto::v7::CollectionFile>>(),
+		)
+		.into_response(),
+		APIMajorVersion::V8 => Json(
+			files
+				.into_iter()
+				.map(|f| f.into())
+				.collect::<Vec<dto::BrowserEntry>>(),
+		)
+		.into_response(),
+	}
+}
+
+const SONG_LIST_CAPACITY: usize = 200;
+const TOTAL_COUNT_HEADER: HeaderName = HeaderName::from_static("x-total-count");
+
+// Selects the requested page of results, and returns it alongside the total number of results
+fn paginate<T>(items: Vec<T>, pagination: &dto::PaginationParameters) -> (Vec<T>, usize) {
+	let total = items.len();
+	let items = items
+		.into_iter()
+		.skip(pagination.offset.unwrap_or(0))
+		.take(pagination.count.unwrap_or(usize::MAX))
+		.collect();
+	(items, total)
+}
+
+fn with_total_count(mut response: Response, total: usize) -> Response {
+	response
+		.headers_mut()
+		.insert(TOTAL_COUNT_HEADER, HeaderValue::from(total));
+	response
+}
+
+async fn make_song_list(paths: Vec<PathBuf>, index_manager: &index::Manager) -> dto::SongList {
+	let first_paths = paths.iter().take(SONG_LIST_CAPACITY).cloned().collect();
+	let first_songs = index_manager
+		.get_songs(first_paths)
+		.await
+		.into_iter()
+		.filter_map(Result::ok)
+		.map(dto::Song::from)
+		.collect();
+	dto::SongList { paths, first_songs }
+}
+
+// Per-user information about songs, which is not part of the index
+struct SongAnnotations {
+	starred: HashSet<PathBuf>,
+	ratings: HashMap<PathBuf, u8>,
+	play_counts: HashMap<PathBuf, u32>,
+}
+
+impl SongAnnotations {
+	async fn fetch(
+		username: &str,
+		favorites_manager: &favorites::Manager,
+		history_manager: &history::Manager,
+		ratings_manager: &ratings::Manager,
+	) -> Result<Self, APIError> {
+		Ok(Self {
+			starred: favorites_manager.get_starred_songs(username).await?,
+			ratings: ratings_manager.get_song_ratings(username).await?,
+			play_counts: history_manager.get_play_counts(username).await?,
+		})
+	}
+
+	fn apply(&self, songs: &mut [dto::Song]) {
+		for song in songs {
+			song.starred = self.starred.contains(&song.path);
+			song.rating = self.ratings.get(&song.path).copied();
+			song.play_count = self
+				.play_counts
+				.get(&song.path)
+				.copied()
+				.unwrap_or_default();
+		}
+	}
+
+	fn select<T>(
+		&self,
+		items: &mut Vec<T>,
+		path: impl Fn(&T) -> &std::path::Path,
+		filter: &dto::StarredParameters,
+		sort: &dto::SongSortParameters,
+	) {
+		if let Some(keep_starred) = filter.starred {
+			items.retain(|i| self.starred.contains(path(i)) == keep_starred);
+		}
+		match sort.sort {
+			Some(dto::SongSort::Rating) => {
+				items.sort_by_key(|i| std::cmp::Reverse(self.ratings.get(path(i)).copied()))
+			}
+			Some(dto::SongSort::Bpm | dto::SongSort::Key) | None => (),
+		}
+	}
+}
+
+// Sort orders based on song tags rather than on the current user's annotations
+fn sort_songs_by_tags(songs: &mut [index::Song], sort: &dto::SongSortParameters) {
+	match sort.sort {
+		Some(dto::SongSort::Bpm) => songs.sort_by_key(|s| (s.bpm.is_none(), s.bpm)),
+		Some(dto::SongSort::Key) => songs.sort_by_cached_key(|s| {
+			let key = s.initial_key.as_ref().map(|k| k.to_lowercase());
+			(key.is_none(), key)
+		}),
+		Some(dto::SongSort::Rating) | None => (),
+	}
+}
+
+async fn sort_paths_by_tags(
+	paths: &mut Vec<PathBuf>,
+	index_manager: &index::Manager,
+	sort: &dto::SongSortParameters,
+) {
+	if !matches!(sort.sort, Some(dto::SongSort::Bpm | dto::SongSort::Key)) {
+		return;
+	}
+	let mut songs = index_manager
+		.get_songs(std::mem::take(paths))
+		.await
+		.into_iter()
+		.filter_map(Result::ok)
+		.collect::<Vec<_>>();
+	sort_songs_by_tags(&mut songs, sort);
+	*paths = songs.into_iter().map(|s| s.virtual_path).collect();
+}
+
+// Falls back to the default order chosen by the current user when the request does not specify one
+async fn get_listing_order(
+	auth: &Auth,
+	preferences_manager: &preferences::Manager,
+	preference: &str,
+	parameters: &dto::ListingSortParameters,
+) -> index::ListingOrder {
+	let sort = match parameters.sort {
+		Some(sort) => Some(sort),
+		None => preferences_manager
+			.get_preferences(auth.get_username())
+			.await
+			.ok()
+			.and_then(|p| p.get(preference).cloned())
+			.and_then(|v| serde_json::from_value(v).ok()),
+	};
+	match sort {
+		None | Some(dto::ListingSort::Name) => index::ListingOrder::Name,
+		Some(dto::ListingSort::Year) => index::ListingOrder::Year,
+		Some(dto::ListingSort::DateAdded) => index::ListingOrder::DateAdded,
+		Some(dto::ListingSort::AlbumArtist) => index::ListingOrder::AlbumArtist,
+		Some(dto::ListingSort::Random) => {
+			index::ListingOrder::Random(parameters.seed.unwrap_or_else(rand::random))
+		}
+	}
+}
+
+fn song_list_to_response(song_list: dto::SongList, api_version: APIMajorVersion) -> Response {
+	match api_version {
+		APIMajorVersion::V7 => Json(
+			song_list
+				.paths
+				.into_iter()
+				.map(|p| (&p).into())
+				.collect::<Vec<dto::v7::Song>>(),
+		)
+		.into_response(),
+		APIMajorVersion::V8 => Json(song_list).into_response(),
+	}
+}
+
+fn albums_to_response(albums: Vec<index::Album>, api_version: APIMajorVersion) -> Response {
+	match api_version {
+		APIMajorVersion::V7 => Json(
+			albums
+				.into_iter()
+				.map(|f| f.into())
+				.collect::<Vec<dto::v7::Directory>>(),
+		)
+		.into_response(),
+		APIMajorVersion::V8 => Json(
+			albums
+				.into_iter()
+				.map(|f| f.header.into())
+				.collect::<Vec<dto::AlbumHeader>>(),
+		)
+		.into_response(),
+	}
+}
+
+#[utoipa::path(
+	get,
+	path = "/browse",
+	tag = "File Browser",
+	description = "Reads the content of the top-level directory in the music collection.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(
+		("Accept-Version" = Option<i32>, Header, minimum = 7, maximum = 8),
+		dto::PaginationParameters,
+		dto::ListingSortParameters,
+	),
+	responses(
+		(status = 200, body = Vec<dto::BrowserEntry>, headers(("X-Total-Count" = usize))),
+	)
+)]
+async fn get_browse_root(
+	auth: Auth,
+	api_version: APIMajorVersion,
+	State(index_manager): State<index::Manager>,
+	State(preferences_manager): State<preferences::Manager>,
+	Query(pagination): Query<dto::PaginationParameters>,
+	Query(sort): Query<dto::ListingSortParameters>,
+) -> Response {
+	let result = match index_manager.browse(PathBuf::new()).await {
+		Ok(r) => r,
+		Err(e) => return APIError::from(e).into_response(),
+	};
+	let order = get_listing_order(
+		&auth,
+		&preferences_manager,
+		preferences::BROWSE_SORT_KEY,
+		&sort,
+	)
+	.await;
+	let result = index_manager.sort_files(result, order).await;
+	let (result, total) = paginate(result, &pagination);
+	with_total_count(index_files_to_response(result, api_version), total)
+}
+
+#[utoipa::path(
+	get,
+	path = "/browse/{*path}",
+	tag = "File Browser",
+	description = "Reads the content of a directory in the music collection.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(
+		("Accept-Version" = Option<i32>, Header, minimum = 7, maximum = 8),
+		("path", allow_reserved, example = "my_music/classical/beethoven"),
+		dto::PaginationParameters,
+		dto::ListingSortParameters,
+	),
+	responses(
+		(status = 200, body = Vec<dto::BrowserEntry>, headers(("X-Total-Count" = usize))),
+	)
+)]
+async fn get_browse(
+	auth: Auth,
+	api_version: APIMajorVersion,
+	State(index_manager): State<index::Manager>,
+	State(preferences_manager): State<preferences::Manager>,
+	Path(path): Path<PathBuf>,
+	Query(pagination): Query<dto::PaginationParameters>,
+	Query(sort): Query<dto::ListingSortParameters>,
+) -> Response {
+	let result = match index_manager.browse(path).await {
+		Ok(r) => r,
+		Err(e) => return APIError::from(e).into_response(),
+	};
+	let order = get_listing_order(
+		&auth,
+		&preferences_manager,
+		preferences::BROWSE_SORT_KEY,
+		&sort,
+	)
+	.await;
+	let result = index_manager.sort_files(result, order).await;
+	let (result, total) = paginate(result, &pagination);
+	with_total_count(index_files_to_response(result, api_version), total)
+}
+
+#[utoipa::path(
+	get,
+	path = "/flatten",
+	tag = "File Browser",
+	description = "Recursively lists all the songs in the music collection.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(
+		("Accept-Version" = Option<i32>, Header, minimum = 7, maximum = 8),
+		dto::PaginationParameters,
+		dto::StarredParameters,
+		dto::SongSortParameters,
+	),
+	responses(
+		(status = 200, body = dto::SongList, headers(("X-Total-Count" = usize))),
+	)
+)]
+#[allow(clippy::too_many_arguments)]
+async fn get_flatten_root(
+	auth: Auth,
+	api_version: APIMajorVersion,
+	State(favorites_manager): State<favorites::Manager>,
+	State(history_manager): State<history::Manager>,
+	State(index_manager): State<index::Manager>,
+	State(ratings_manager): State<ratings::Manager>,
+	Query(pagination): Query<dto::PaginationParameters>,
+	Query(filter): Query<dto::StarredParameters>,
+	Query(sort): Query<dto::SongSortParameters>,
+) -> Response {
+	let mut paths = match index_manager.flatten(PathBuf::new()).await {
+		Ok(s) => s,
+		Err(e) => return APIError::from(e).into_response(),
+	};
+	let annotations = match SongAnnotations::fetch(
+		auth.get_username(),
+		&favorites_manager,
+		&history_manager,
+		&ratings_manager,
+	)
+	.await
+	{
+		Ok(a) => a,
+		Err(e) => return e.into_response(),
+	};
+	annotations.select(&mut paths, PathBuf::as_path, &filter, &sort);
+	sort_paths_by_tags(&mut paths, &index_manager, &sort).await;
+	let (paths, total) = paginate(paths, &pagination);
+	let mut song_list = make_song_list(paths, &index_manager).await;
+	annotations.apply(&mut song_list.first_songs);
+	with_total_count(song_list_to_response(song_list, api_version), total)
+}
+
+#[utoipa::path(
+	get,
+	path = "/flatten/{*path}",
+	tag = "File Browser",
+	description = "Recursively lists all the songs within a directory of the music collection.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(
+		("Accept-Version" = Option<i32>, Header, minimum = 7, maximum = 8),
+		("path", allow_reserved, example = "my_music/classical/beethoven"),
+		dto::PaginationParameters,
+		dto::StarredParameters,
+		dto::SongSortParameters,
+	),
+	responses(
+		(status = 200, body = dto::SongList, headers(("X-Total-Count" = usize))),
+	)
+)]
+#[allow(clippy::too_many_arguments)]
+async fn get_flatten(
+	auth: Auth,
+	api_version: APIMajorVersion,
+	State(favorites_manager): State<favorites::Manager>,
+	State(history_manager): State<history::Manager>,
+	State(index_manager): State<index::Manager>,
+	State(ratings_manager): State<ratings::Manager>,
+	Path(path): Path<PathBuf>,
+	Query(pagination): Query<dto::PaginationParameters>,
+	Query(filter): Query<dto::StarredParameters>,
+	Query(sort): Query<dto::SongSortParameters>,
+) -> Response {
+	let mut paths = match index_manager.flatten(path).await {
+		Ok(s) => s,
+		Err(e) => return APIError::from(e).into_response(),
+	};
+	let annotations = match SongAnnotations::fetch(
+		auth.get_username(),
+		&favorites_manager,
+		&history_manager,
+		&ratings_manager,
+	)
+	.await
+	{
+		Ok(a) => a,
+		Err(e) => return e.into_response(),
+	};
+	annotations.select(&mut paths, PathBuf::as_path, &filter, &sort);
+	sort_paths_by_tags(&mut paths, &index_manager, &sort).await;
+	let (paths, total) = paginate(paths, &pagination);
+	let mut song_list = make_song_list(paths, &index_manager).await;
+	annotations.apply(&mut song_list.first_songs);
+	with_total_count(song_list_to_response(song_list, api_version), total)
+}
+
+#[utoipa::path(
+	get,
+	path = "/zip/{*path}",
+	tag = "File Browser",
+	description = "Downloads all the songs within a directory of the music collection as a zip archive. The archive is streamed while it is being created. Archives of unchanged files are identical, so they carry an `ETag` and interrupted downloads can be resumed with range requests.\n\nArchives are not available to users with a `stream_quality` limit.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(("path", allow_reserved, example = "my_music/classical/beethoven")),
+	responses(
+		(status = 200, content_type = "application/zip"),
+		(status = 206, content_type = "application/zip"),
+		(status = 304),
+	)
+)]
+async fn get_zip(
+	rights: Permitted<permission::Download>,
+	Extension(stream_limiter): Extension<limits::StreamLimiter>,
+	State(config_manager): State<config::Manager>,
+	State(index_manager): State<index::Manager>,
+	Path(path): Path<PathBuf>,
+	client_ip: Option<Extension<ClientIp>>,
+	headers: HeaderMap,
+) -> Result<Response, APIError> {
+	if let Some(auth) = rights.get_auth() {
+		let client_ip = client_ip.map(|Extension(ClientIp(ip))| ip);
+		stream_limiter
+			.check_original_quality(auth.get_username(), client_ip)
+			.await?;
+	}
+	let paths = index_manager.flatten(path.clone()).await?;
+	let songs = index_manager
+		.get_songs(paths)
+		.await
+		.into_iter()
+		.filter_map(|s| s.ok())
+		.collect();
+	let entries = archive::list_entries(&config_manager, &path, songs).await;
+	let file_name = match path.file_name() {
+		Some(name) => format!("{}.zip", name.to_string_lossy()),
+		None => "polaris.zip".to_owned(),
+	};
+	let response = archive::serve_zip(entries, &file_name, &headers).await;
+	Ok(match rights.get_auth() {
+		Some(auth) => stream_limiter.record_transfer(auth.get_username(), response),
+		None => response,
+	})
+}
+
+#[utoipa::path(
+	get,
+	path = "/albums",
+	tag = "Collection",
+	description = "Lists all albums in the music collection.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(dto::GetAlbumsParameters, dto::ListingSortParameters),
+	responses(
+		(status = 200, body = Vec<dto::AlbumHeader>),
+	)
+)]
+async fn get_albums(
+	auth: Auth,
+	State(index_manager): State<index::Manager>,
+	State(preferences_manager): State<preferences::Manager>,
+	Query(options): Query<dto::GetAlbumsParameters>,
+	Query(sort): Query<dto::ListingSortParameters>,
+) -> Result<Json<Vec<dto::AlbumHeader>>, APIError> {
+	let mut albums = index_manager
+		.get_albums()
+		.await
+		.into_iter()
+		.filter(|a| options.compilation.is_none_or(|c| a.compilation == c))
+		.collect::<Vec<_>>();
+	let order = get_listing_order(
+		&auth,
+		&preferences_manager,
+		preferences::ALBUM_SORT_KEY,
+		&sort,
+	)
+	.await;
+	index::sort_listing(&mut albums, order, index::AlbumHeader::listing_key);
+	Ok(Json(albums.into_iter().map(|a| a.into()).collect()))
+}
+
+#[utoipa::path(
+	get,
+	path = "/artists",
+	tag = "Collection",
+	description = "Lists all artists in the music collection.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	responses(
+		(status = 200, body = Vec<dto::ArtistHeader>),
+	)
+)]
+async fn get_artists(
+	_auth: Auth,
+	State(index_manager): State<index::Manager>,
+) -> Result<Json<Vec<dto::ArtistHeader>>, APIError> {
+	Ok(Json(
+		index_manager
+			.get_artists()
+			.await
+			.into_iter()
+			.map(|a| a.into())
+			.collect::<Vec<_>>(),
+	))
+}
+
+#[utoipa::path(
+	get,
+	path = "/artist/{name}",
+	tag = "Collection",
+	description = "Returns detailed information about a single artist.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(("name", example = "Claude Frank")),
+	responses(
+		(status = 200, body = dto::Artist),
+	)
+)]
+async fn get_artist(
+	_auth: Auth,
+	State(index_manager): State<index::Manager>,
+	Path(name): Path<String>,
+) -> Result<Json<dto::Artist>, APIError> {
+	Ok(Json(index_manager.get_artist(name).await?.into()))
+}
+
+#[utoipa::path(
+	get,
+	path = "/composers",
+	tag = "Collection",
+	description = "Lists all artists credited as composers in the music collection.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	responses(
+		(status = 200, body = Vec<dto::ArtistHeader>),
+	)
+)]
+async fn get_composers(
+	_auth: Auth,
+	State(index_manager): State<index::Manager>,
+) -> Result<Json<Vec<dto::ArtistHeader>>, APIError> {
+	Ok(Json(
+		index_manager
+			.get_composers()
+			.await
+			.into_iter()
+			.map(|a| a.into())
+			.collect::<Vec<_>>(),
+	))
+}
+
+#[utoipa::path(
+	get,
+	path = "/composer/{name}",
+	tag = "Collection",
+	description = "Returns the songs written by a composer, grouped by work.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(("name", example = "Ludwig van Beethoven")),
+	responses(
+		(status = 200, body = dto::Composer),
+	)
+)]
+async fn get_composer(
+	_auth: Auth,
+	State(index_manager): State<index::Manager>,
+	Path(name): Path<String>,
+) -> Result<Json<dto::Composer>, APIError> {
+	Ok(Json(index_manager.get_composer(name).await?.into()))
+}
+
+#[utoipa::path(
+	get,
+	path = "/musicbrainz/artist/{mbid}",
+	tag = "Collection",
+	description = "Returns the artist tagged with a MusicBrainz artist identifier.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(("mbid", example = "c2a2d2a1-c2c1-4b73-9e6a-8b0a1d1e7d4f")),
+	responses(
+		(status = 200, body = dto::Artist),
+	)
+)]
+async fn get_musicbrainz_artist(
+	_auth: Auth,
+	State(index_manager): State<index::Manager>,
+	Path(mbid): Path<String>,
+) -> Result<Json<dto::Artist>, APIError> {
+	Ok(Json(index_manager.get_artist_by_mbid(mbid).await?.into()))
+}
+
+#[utoipa::path(
+	get,
+	path = "/musicbrainz/release/{mbid}",
+	tag = "Collection",
+	description = "Returns the album tagged with a MusicBrainz release identifier.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(("mbid", example = "f5a3b6d0-8e8a-4c4e-9d1c-2f6b1d0e7c3a")),
+	responses(
+		(status = 200, body = dto::Album),
+	)
+)]
+async fn get_musicbrainz_release(
+	_auth: Auth,
+	State(index_manager): State<index::Manager>,
+	Path(mbid): Path<String>,
+) -> Result<Json<dto::Album>, APIError> {
+	Ok(Json(index_manager.get_album_by_mbid(mbid).await?.into()))
+}
+
+#[utoipa::path(
+	get,
+	path = "/musicbrainz/recording/{mbid}",
+	tag = "Collection",
+	description = "Returns the song tagged with a MusicBrainz recording identifier.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(("mbid", example = "0b8f4c6e-3d2a-4e1b-a7c9-5f6d8e9a0b1c")),
+	responses(
+		(status = 200, body = dto::Song),
+	)
+)]
+async fn get_musicbrainz_recording(
+	_auth: Auth,
+	State(index_manager): State<index::Manager>,
+	Path(mbid): Path<String>,
+) -> Result<Json<dto::Song>, APIError> {
+	Ok(Json(index_manager.get_song_by_mbid(mbid).await?.into()))
+}
+
+#[utoipa::path(
+	get,
+	path = "/duplicates",
+	tag = "Collection",
+	description = "Lists groups of songs which are likely duplicates of each other. Songs are grouped when they have the same title and artists and a similar duration, or when their audio fingerprints match. Fingerprints are only compared when the `acoustid_fingerprints` configuration setting is enabled.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	responses(
+		(status = 200, body = Vec<dto::DuplicateGroup>),
+	)
+)]
+async fn get_duplicates(
+	_admin_rights: AdminRights,
+	State(duplicates_manager): State<duplicates::Manager>,
+) -> Result<Json<Vec<dto::DuplicateGroup>>, APIError> {
+	let duplicates = duplicates_manager.get_duplicates().await?;
+	Ok(Json(duplicates.into_iter().map(|d| d.into()).collect()))
+}
+
+#[utoipa::path(
+	get,
+	path = "/metadata_report",
+	tag = "Collection",
+	description = "Lists songs with missing titles, album art or track numbers, or with suspicious years, along with the number of songs affected by each issue. Songs with the most issues come first.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	responses(
+		(status = 200, body = dto::MetadataReport),
+	)
+)]
+async fn get_metadata_report(
+	_admin_rights: AdminRights,
+	State(quality_manager): State<quality::Manager>,
+) -> Result<Json<dto::MetadataReport>, APIError> {
+	Ok(Json(quality_manager.get_report().await?.into()))
+}
+
+#[utoipa::path(
+	get,
+	path = "/album/{name}/by/{artists}",
+	tag = "Collection",
+	description = "Returns detailed information about a single album.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(
+		("name", example = "The Piano Sonatas"),
+		("artists", example = "Claude Frank", description = "Artists the album is attributed to, separated by unicode \\u{000C} characters."),
+	),
+	responses(
+		(status = 200, body = dto::Album),
+	)
+)]
+async fn get_album(
+	auth: Auth,
+	State(favorites_manager): State<favorites::Manager>,
+	State(history_manager): State<history::Manager>,
+	State(index_manager): State<index::Manager>,
+	State(ratings_manager): State<ratings::Manager>,
+	Path((name, artists)): Path<(String, String)>,
+) -> Result<Json<dto::Album>, APIError> {
+	let artists = artists
+		.split(API_ARRAY_SEPARATOR)
+		.map(str::to_owned)
+		.collect::<Vec<_>>();
+	let album = index_manager.get_album(artists, name).await?;
+	let item = ratings::Item::Album {
+		name: album.header.name.clone(),
+		artists: album.header.artists.clone(),
+	};
+	let mut album: dto::Album = album.into();
+	let annotations = SongAnnotations::fetch(
+		auth.get_username(),
+		&favorites_manager,
+		&history_manager,
+		&ratings_manager,
+	)
+	.await?;
+	annotations.apply(&mut album.songs);
+	album.play_count = album.songs.iter().map(|s| s.play_count).sum();
+	album.rating = ratings_manager
+		.get_rating(auth.get_username(), item.clone())
+		.await?;
+	album.average_rating = ratings_manager.get_average_rating(item).await?;
+	Ok(Json(album))
+}
+
+#[utoipa::path(
+	get,
+	path = "/album/{name}/by/{artists}/zip",
+	tag = "Collection",
+	description = "Downloads all the songs of an album as a zip archive. The archive is streamed while it is being created. Archives of unchanged files are identical, so they carry an `ETag` and interrupted downloads can be resumed with range requests.\n\nArchives are not available to users with a `stream_quality` limit.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(
+		("name", example = "The Piano Sonatas"),
+		("artists", example = "Claude Frank", description = "Artists the album is attributed to, separated by unicode \\u{000C} characters."),
+	),
+	responses(
+		(status = 200, content_type = "application/zip"),
+		(status = 206, content_type = "application/zip"),
+		(status = 304),
+	)
+)]
+async fn get_album_zip(
+	rights: Permitted<permission::Download>,
+	Extension(stream_limiter): Extension<limits::StreamLimiter>,
+	State(config_manager): State<config::Manager>,
+	State(index_manager): State<index::Manager>,
+	Path((name, artists)): Path<(String, String)>,
+	client_ip: Option<Extension<ClientIp>>,
+	headers: HeaderMap,
+) -> Result<Response, APIError> {
+	if let Some(auth) = rights.get_auth() {
+		let client_ip = client_ip.map(|Extension(ClientIp(ip))| ip);
+		stream_limiter
+			.check_original_quality(auth.get_username(), client_ip)
+			.await?;
+	}
+	let artists = artists
+		.split(API_ARRAY_SEPARATOR)
+		.map(str::to_owned)
+		.collect::<Vec<_>>();
+	let credits = artists
+		.iter()
+		.filter(|a| !a.is_empty())
+		.map(String::as_str)
+		.collect::<Vec<_>>()
+		.join(", ");
+	let file_name = match credits.is_empty() {
+		true => format!("{name}.zip"),
+		false => format!("{credits} - {name}.zip"),
+	};
+	let album = index_manager.get_album(artists, name).await?;
+	let root = archive::common_ancestor(album.songs.iter().map(|s| s.virtual_path.as_path()));
+	let entries = archive::list_entries(&config_manager, &root, album.songs).await;
+	let response = archive::serve_zip(entries, &file_name, &headers).await;
+	Ok(match rights.get_auth() {
+		Some(auth) => stream_limiter.record_transfer(auth.get_username(), response),
+		None => response,
+	})
+}
+
+#[utoipa::path(
+	post, // post because of https://github.com/whatwg/fetch/issues/551
+	path = "/songs",
+	tag = "Collection",
+	description = "Returns detailed information about specific songs, in the order they were requested. This is intended for clients restoring large queues or playlists in a single request.\n\nEven though it is a read operation, this endpoint uses the `POST` method in order to facilitate usage of a request body (which is not standard for `GET` requests).",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	request_body = dto::GetSongsBulkInput,
+	responses(
+		(status = 200, body = dto::GetSongsBulkOutput),
+	)
+)]
+async fn get_songs(
+	auth: Auth,
+	State(favorites_manager): State<favorites::Manager>,
+	State(history_manager): State<history::Manager>,
+	State(index_manager): State<index::Manager>,
+	State(ratings_manager): State<ratings::Manager>,
+	Json(input): Json<dto::GetSongsBulkInput>,
+) -> Result<Json<dto::GetSongsBulkOutput>, APIError> {
+	let results = index_manager.get_songs(input.paths.clone()).await;
+
+	let mut output = dto::GetSongsBulkOutput::default();
+	for (path, result) in input.paths.into_iter().zip(results) {
+		match result {
+			Ok(s) => output.songs.push(s.into()),
+			Err(_) => output.not_found.push(path),
+		}
+	}
+
+	let annotations = SongAnnotations::fetch(
+		auth.get_username(),
+		&favorites_manager,
+		&history_manager,
+		&ratings_manager,
+	)
+	.await?;
+	annotations.apply(&mut output.songs);
+
+	Ok(Json(output))
+}
+
+#[utoipa::path(
+	get,
+	path = "/changes",
+	tag = "Collection",
+	description = "Lists songs and albums which were added, modified or removed since a previous call to this endpoint. This lets clients keeping an offline copy of the collection stay up to date without downloading it again.\n\nCalling this endpoint without a token, or with a token the server no longer recognizes, sets `full_sync_required`. Either way, the returned token should be used for the next call.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(dto::GetChangesParameters),
+	responses(
+		(status = 200, body = dto::Changes),
+	)
+)]
+async fn get_changes(
+	_auth: Auth,
+	State(index_manager): State<index::Manager>,
+	Query(options): Query<dto::GetChangesParameters>,
+) -> Json<dto::Changes> {
+	Json(index_manager.get_changes(options.since).await.into())
+}
+
+#[utoipa::path(
+	get,
+	path = "/albums/random",
+	tag = "Collection",
+	description = "Returns a random selection of albums from the collection.\n\nRe-using the same seed will return the same albums only as long as the collection does not change.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(
+		("Accept-Version" = Option<i32>, Header, minimum = 7, maximum = 8),
+		dto::GetRandomAlbumsParameters,
+	),
+	responses(
+		(status = 200, body = Vec<dto::AlbumHeader>),
+	)
+)]
+async fn get_random_albums(
+	_auth: Auth,
+	api_version: APIMajorVersion,
+	State(index_manager): State<index::Manager>,
+	Query(options): Query<dto::GetRandomAlbumsParameters>,
+) -> Response {
+	let offset = options.offset.unwrap_or(0);
+	let count = options.count.unwrap_or(20);
+	let albums = match index_manager
+		.get_random_albums(options.seed, offset, count)
+		.await
+	{
+		Ok(d) => d,
+		Err(e) => return APIError::from(e).into_response(),
+	};
+	albums_to_response(albums, api_version)
+}
+
+#[utoipa::path(
+	get,
+	path = "/albums/recent",
+	tag = "Collection",
+	description = "Returns the albums most recently added to the collection.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(
+		("Accept-Version" = Option<i32>, Header, minimum = 7, maximum = 8),
+		dto::GetRecentAlbumsParameters
+	),
+	responses(
+		(status = 200, body = Vec<dto::AlbumHeader>),
+	)
+)]
+async fn get_recent_albums(
+	_auth: Auth,
+	api_version: APIMajorVersion,
+	State(index_manager): State<index::Manager>,
+	Query(options): Query<dto::GetRecentAlbumsParameters>,
+) -> Response {
+	let offset = options.offset.unwrap_or(0);
+	let count = options.count.unwrap_or(20);
+	let albums = match index_manager.get_recent_albums(offset, count).await {
+		Ok(d) => d,
+		Err(e) => return APIError::from(e).into_response(),
+	};
+	albums_to_response(albums, api_version)
+}
+
+#[utoipa::path(
+	get,
+	path = "/recently_added/songs",
+	tag = "Collection",
+	description = "Lists songs in the collection, most recently added first. Songs keep the time they first appeared in the collection across scans, even when their files are modified.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(dto::PaginationParameters),
+	responses(
+		(status = 200, body = dto::SongList, headers(("X-Total-Count" = usize))),
+	)
+)]
+async fn get_recently_added_songs(
+	auth: Auth,
+	State(favorites_manager): State<favorites::Manager>,
+	State(history_manager): State<history::Manager>,
+	State(index_manager): State<index::Manager>,
+	State(ratings_manager): State<ratings::Manager>,
+	Query(pagination): Query<dto::PaginationParameters>,
+) -> Result<Response, APIError> {
+	let paths = index_manager.get_recent_songs().await;
+	let (paths, total) = paginate(paths, &pagination);
+	let mut song_list = make_song_list(paths, &index_manager).await;
+	SongAnnotations::fetch(
+		auth.get_username(),
+		&favorites_manager,
+		&history_manager,
+		&ratings_manager,
+	)
+	.await?
+	.apply(&mut song_list.first_songs);
+	Ok(with_total_count(Json(song_list).into_response(), total))
+}
+
+#[utoipa::path(
+	get,
+	path = "/genres",
+	tag = "Collection",
+	description = "Lists all music genres in the collection.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	responses(
+		(status = 200, body = Vec<dto::GenreHeader>),
+	)
+)]
+async fn get_genres(
+	_auth: Auth,
+	State(index_manager): State<index::Manager>,
+) -> Result<Json<Vec<dto::GenreHeader>>, APIError> {
+	Ok(Json(
+		index_manager
+			.get_genres()
+			.await
+			.into_iter()
+			.map(|g| g.into())
+			.collect(),
+	))
+}
+
+#[utoipa::path(
+	get,
+	path = "/genre/{name}",
+	tag = "Collection",
+	description = "Returns detailed information about a music genre.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(("name", example = "Classical")),
+	responses(
+		(status = 200, body = Vec<dto::Genre>),
+	)
+)]
+async fn get_genre(
+	_auth: Auth,
+	State(index_manager): State<index::Manager>,
+	Path(name): Path<String>,
+) -> Result<Json<dto::Genre>, APIError> {
+	Ok(Json(index_manager.get_genre(name).await?.into()))
+}
+
+#[utoipa::path(
+	get,
+	path = "/genre/{name}/albums",
+	tag = "Collection",
+	description = "Returns all albums associated with a music genre.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(("name", example = "Classical"), dto::ListingSortParameters),
+	responses(
+		(status = 200, body = Vec<dto::AlbumHeader>),
+	)
+)]
+async fn get_genre_albums(
+	auth: Auth,
+	State(index_manager): State<index::Manager>,
+	State(preferences_manager): State<preferences::Manager>,
+	Path(name): Path<String>,
+	Query(sort): Query<dto::ListingSortParameters>,
+) -> Result<Json<Vec<dto::AlbumHeader>>, APIError> {
+	let mut albums = index_manager.get_genre(name).await?.albums;
+	let order = get_listing_order(
+		&auth,
+		&preferences_manager,
+		preferences::ALBUM_SORT_KEY,
+		&sort,
+	)
+	.await;
+	index::sort_listing(&mut albums, order, index::AlbumHeader::listing_key);
+	Ok(Json(albums.into_iter().map(|a| a.into()).collect()))
+}
+
+#[utoipa::path(
+	get,
+	path = "/genre/{name}/artists",
+	tag = "Collection",
+	description = "Returns all artists associated with a music genre.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(("name", example = "Classical")),
+	responses(
+		(status = 200, body = Vec<dto::ArtistHeader>),
+	)
+)]
+async fn get_genre_artists(
+	_auth: Auth,
+	State(index_manager): State<index::Manager>,
+	Path(name): Path<String>,
+) -> Result<Json<Vec<dto::ArtistHeader>>, APIError> {
+	let artists = index_manager
+		.get_genre(name)
+		.await?
+		.artists
+		.into_iter()
+		.map(|a| a.into())
+		.collect();
+	Ok(Json(artists))
+}
+
+#[utoipa::path(
+	get,
+	path = "/genre/{name}/songs",
+	tag = "Collection",
+	description = "Returns all songs associated with a music genre.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(("name", example = "Classical")),
+	responses(
+		(status = 200, body = dto::SongList),
+	)
+)]
+async fn get_genre_songs(
+	_auth: Auth,
+	State(index_manager): State<index::Manager>,
+	Path(name): Path<String>,
+) -> Result<Json<dto::SongList>, APIError> {
+	let songs = index_manager.get_genre(name).await?.songs;
+	let song_list = dto::SongList {
+		paths: songs.iter().map(|s| s.virtual_path.clone()).collect(),
+		first_songs: songs
+			.into_iter()
+			.take(SONG_LIST_CAPACITY)
+			.map(|s| s.into())
+			.collect(),
+	};
+	Ok(Json(song_list))
+}
+
+#[utoipa::path(
+	get,
+	path = "/mix",
+	tag = "Collection",
+	description = "Generates a one-off mix of songs matching the requested filters, in playback order. Songs the current user has played often are less likely to be picked.\n\nRe-using the same seed will return the same songs only as long as the collection does not change.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(dto::GetMixParameters),
+	responses(
+		(status = 200, body = dto::SongList),
+	)
+)]
+async fn get_mix(
+	auth: Auth,
+	State(favorites_manager): State<favorites::Manager>,
+	State(history_manager): State<history::Manager>,
+	State(index_manager): State<index::Manager>,
+	State(ratings_manager): State<ratings::Manager>,
+	Query(options): Query<dto::GetMixParameters>,
+) -> Result<Json<dto::SongList>, APIError> {
+	let length = options.length.unwrap_or(50).min(1000);
+	let annotations = SongAnnotations::fetch(
+		auth.get_username(),
+		&favorites_manager,
+		&history_manager,
+		&ratings_manager,
+	)
+	.await?;
+	let paths = match options.min_rating {
+		None => {
+			index_manager
+				.get_mix(
+					options.genre,
+					options.decade,
+					options.seed,
+					annotations.play_counts.clone(),
+					length,
+				)
+				.await?
+		}
+		Some(min_rating) => {
+			let mut paths = index_manager
+				.get_mix(
+					options.genre,
+					options.decade,
+					options.seed,
+					annotations.play_counts.clone(),
+					usize::MAX,
+				)
+				.await?;
+			paths.retain(|p| annotations.ratings.get(p).is_some_and(|r| *r >= min_rating));
+			paths.truncate(length);
+			paths
+		}
+	};
+	let mut song_list = make_song_list(paths, &index_manager).await;
+	annotations.apply(&mut song_list.first_songs);
+	Ok(Json(song_list))
+}
+
+#[utoipa::path(
+	post,
+	path = "/family_mix",
+	tag = "Playlists",
+	description = "Generates a mix of songs from the playlists of several users, and saves it as a playlist of the current user.\n\nOther users must have set their `family_mix` preference to `true`. An existing playlist with the same name is replaced.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	request_body = dto::FamilyMixInput,
+	responses(
+		(status = 200, body = dto::SongList),
+	)
+)]
+async fn post_family_mix(
+	auth: Auth,
+	State(config_manager): State<config::Manager>,
+	State(index_manager): State<index::Manager>,
+	State(playlist_manager): State<playlist::Manager>,
+	State(preferences_manager): State<preferences::Manager>,
+	Json(input): Json<dto::FamilyMixInput>,
+) -> Result<Json<dto::SongList>, APIError> {
+	let mut listeners = vec![playlist::Listener {
+		username: auth.get_username().to_owned(),
+		weight: 1.0,
+	}];
+	for listener in input.listeners {
+		config_manager.get_user(&listener.username).await?;
+		if listener.username != auth.get_username() {
+			let preferences = preferences_manager
+				.get_preferences(&listener.username)
+				.await?;
+			let shared = preferences.get(preferences::FAMILY_MIX_KEY);
+			if shared != Some(&serde_json::Value::Bool(true)) {
+				return Err(APIError::FamilyMixNotAllowed(listener.username));
+			}
+		}
+		let weight = listener.weight.unwrap_or(1.0);
+		match listeners
+			.iter_mut()
+			.find(|l| l.username == listener.username)
+		{
+			Some(l) => l.weight = weight,
+			None => listeners.push(playlist::Listener {
+				username: listener.username,
+				weight,
+			}),
+		}
+	}
+
+	let length = input.length.unwrap_or(50).min(1000);
+	let paths = playlist_manager
+		.blend_playlists(listeners.clone(), input.mode.into(), input.seed, length)
+		.await?;
+
+	let songs: Vec<index::Song> = index_manager
+		.get_songs(paths.clone())
+		.await
+		.into_iter()
+		.filter_map(|s| s.ok())
+		.collect();
+
+	playlist_manager
+		.save_playlist(&input.name, auth.get_username(), songs)
+		.await?;
+
+	Ok(Json(make_song_list(paths, &index_manager).await))
+}
+
+#[utoipa::path(
+	get,
+	path = "/starred",
+	tag = "Collection",
+	description = "Lists the songs, albums and artists starred by the current user.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	responses(
+		(status = 200, body = dto::Starred),
+	)
+)]
+async fn get_starred(
+	auth: Auth,
+	State(favorites_manager): State<favorites::Manager>,
+	State(history_manager): State<history::Manager>,
+	State(index_manager): State<index::Manager>,
+	State(ratings_manager): State<ratings::Manager>,
+) -> Result<Json<dto::Starred>, APIError> {
+	let stars = favorites_manager.list_stars(auth.get_username()).await?;
+
+	let mut song_paths = vec![];
+	let mut albums = vec![];
+	let mut artists = vec![];
+	for star in stars {
+		match star.item {
+			favorites::Item::Song(path) => song_paths.push(path),
+			favorites::Item::Album { name, artists: a } => {
+				if let Ok(album) = index_manager.get_album(a, name).await {
+					albums.push(album.header.into());
+				}
+			}
+			favorites::Item::Artist(name) => {
+				if let Ok(artist) = index_manager.get_artist(name).await {
+					artists.push(artist.header.into());
+				}
+			}
+		}
+	}
+
+	let mut songs = make_song_list(song_paths, &index_manager).await;
+	SongAnnotations::fetch(
+		auth.get_username(),
+		&favorites_manager,
+		&history_manager,
+		&ratings_manager,
+	)
+	.await?
+	.apply(&mut songs.first_songs);
+
+	Ok(Json(dto::Starred {
+		songs,
+		albums,
+		artists,
+	}))
+}
+
+#[utoipa::path(
+	put,
+	path = "/star/song/{*path}",
+	tag = "Collection",
+	description = "Stars a song for the current user.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(("path", allow_reserved, example = "my_music/destiny.mp3")),
+	responses(
+		(status = 200),
+		(status = 404),
+	)
+)]
+async fn put_star_song(
+	auth: Auth,
+	State(favorites_manager): State<favorites::Manager>,
+	State(index_manager): State<index::Manager>,
+	Path(path): Path<PathBuf>,
+) -> Result<(), APIError> {
+	let song = index_manager
+		.get_songs(vec![path])
+		.await
+		.into_iter()
+		.next()
+		.ok_or(APIError::SongNotFound)??;
+	favorites_manager
+		.star(
+			auth.get_username(),
+			favorites::Item::Song(song.virtual_path),
+		)
+		.await?;
+	Ok(())
+}
+
+#[utoipa::path(
+	delete,
+	path = "/star/song/{*path}",
+	tag = "Collection",
+	description = "Unstars a song for the current user.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(("path", allow_reserved, example = "my_music/destiny.mp3")),
+	responses(
+		(status = 200),
+	)
+)]
+async fn delete_star_song(
+	auth: Auth,
+	State(favorites_manager): State<favorites::Manager>,
+	Path(path): Path<PathBuf>,
+) -> Result<(), APIError> {
+	favorites_manager
+		.unstar(auth.get_username(), favorites::Item::Song(path))
+		.await?;
+	Ok(())
+}
+
+#[utoipa::path(
+	put,
+	path = "/star/album/{name}/by/{artists}",
+	tag = "Collection",
+	description = "Stars an album for the current user.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(
+		("name", example = "The Piano Sonatas"),
+		("artists", example = "Claude Frank", description = "Artists the album is attributed to, separated by unicode \\u{000C} characters."),
+	),
+	responses(
+		(status = 200),
+		(status = 404),
+	)
+)]
+async fn put_star_album(
+	auth: Auth,
+	State(favorites_manager): State<favorites::Manager>,
+	State(index_manager): State<index::Manager>,
+	Path((name, artists)): Path<(String, String)>,
+) -> Result<(), APIError> {
+	let artists = artists
+		.split(API_ARRAY_SEPARATOR)
+		.map(str::to_owned)
+		.collect::<Vec<_>>();
+	let album = index_manager.get_album(artists, name).await?;
+	favorites_manager
+		.star(
+			auth.get_username(),
+			favorites::Item::Album {
+				name: album.header.name,
+				artists: album.header.artists,
+			},
+		)
+		.await?;
+	Ok(())
+}
+
+#[utoipa::path(
+	delete,
+	path = "/star/album/{name}/by/{artists}",
+	tag = "Collection",
+	description = "Unstars an album for the current user.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(
+		("name", example = "The Piano Sonatas"),
+		("artists", example = "Claude Frank", description = "Artists the album is attributed to, separated by unicode \\u{000C} characters."),
+	),
+	responses(
+		(status = 200),
+	)
+)]
+async fn delete_star_album(
+	auth: Auth,
+	State(favorites_manager): State<favorites::Manager>,
+	Path((name, artists)): Path<(String, String)>,
+) -> Result<(), APIError> {
+	let artists = artists
+		.split(API_ARRAY_SEPARATOR)
+		.map(str::to_owned)
+		.collect::<Vec<_>>();
+	favorites_manager
+		.unstar(
+			auth.get_username(),
+			favorites::Item::Album { name, artists },
+		)
+		.await?;
+	Ok(())
+}
+
+#[utoipa::path(
+	put,
+	path = "/star/artist/{name}",
+	tag = "Collection",
+	description = "Stars an artist for the current user.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(("name", example = "Stratovarius")),
+	responses(
+		(status = 200),
+		(status = 404),
+	)
+)]
+async fn put_star_artist(
+	auth: Auth,
+	State(favorites_manager): State<favorites::Manager>,
+	State(index_manager): State<index::Manager>,
+	Path(name): Path<String>,
+) -> Result<(), APIError> {
+	let artist = index_manager.get_artist(name).await?;
+	favorites_manager
+		.star(
+			auth.get_username(),
+			favorites::Item::Artist(artist.header.name.into_inner()),
+		)
+		.await?;
+	Ok(())
+}
+
+#[utoipa::path(
+	delete,
+	path = "/star/artist/{name}",
+	tag = "Collection",
+	description = "Unstars an artist for the current user.",
 	security(
 		("auth_token" = []),
 		("auth_query_param" = []),
 	),
+	params(("name", example = "Stratovarius")),
 	responses(
-		(status = 200, body = dto::IndexStatus),
+		(status = 200),
 	)
 )]
-async fn get_index_status(
-	_admin_rights: AdminRights,
-	State(scanner): State<scanner::Scanner>,
-) -> Result<Json<dto::IndexStatus>, APIError> {
-	Ok(Json(scanner.get_status().await.into()))
+async fn delete_star_artist(
+	auth: Auth,
+	State(favorites_manager): State<favorites::Manager>,
+	Path(name): Path<String>,
+) -> Result<(), APIError> {
+	favorites_manager
+		.unstar(auth.get_username(), favorites::Item::Artist(name))
+		.await?;
+	Ok(())
 }
 
-fn index_files_to_response(files: Vec<index::File>, api_version: APIMajorVersion) -> Response {
-	match api_version {
-		APIMajorVersion::V7 => Json(
-			files
-				.into_iter()
-				.map(|f| f.into())
-				.collect::<Vec<dto::v7::CollectionFile>>(),
-		)
-		.into_response(),
-		APIMajorVersion::V8 => Json(
-			files
-				.into_iter()
-				.map(|f| f.into())
-				.collect::<Vec<dto::BrowserEntry>>(),
+#[utoipa::path(
+	put,
+	path = "/rating/song/{*path}",
+	tag = "Collection",
+	description = "Rates a song for the current user, from 0 to 5 stars.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(("path", allow_reserved, example = "my_music/destiny.mp3")),
+	request_body = dto::Rating,
+	responses(
+		(status = 200),
+		(status = 400),
+		(status = 404),
+	)
+)]
+async fn put_song_rating(
+	auth: Auth,
+	State(index_manager): State<index::Manager>,
+	State(ratings_manager): State<ratings::Manager>,
+	Path(path): Path<PathBuf>,
+	Json(rating): Json<dto::Rating>,
+) -> Result<(), APIError> {
+	let song = index_manager
+		.get_songs(vec![path])
+		.await
+		.into_iter()
+		.next()
+		.ok_or(APIError::SongNotFound)??;
+	ratings_manager
+		.set_rating(
+			auth.get_username(),
+			ratings::Item::Song(song.virtual_path),
+			rating.rating,
 		)
-		.into_response(),
-	}
+		.await?;
+	Ok(())
 }
 
-const SONG_LIST_CAPACITY: usize = 200;
+#[utoipa::path(
+	delete,
+	path = "/rating/song/{*path}",
+	tag = "Collection",
+	description = "Clears the rating given to a song by the current user.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(("path", allow_reserved, example = "my_music/destiny.mp3")),
+	responses(
+		(status = 200),
+	)
+)]
+async fn delete_song_rating(
+	auth: Auth,
+	State(ratings_manager): State<ratings::Manager>,
+	Path(path): Path<PathBuf>,
+) -> Result<(), APIError> {
+	ratings_manager
+		.clear_rating(auth.get_username(), ratings::Item::Song(path))
+		.await?;
+	Ok(())
+}
 
-async fn make_song_list(paths: Vec<PathBuf>, index_manager: &index::Manager) -> dto::SongList {
-	let first_paths = paths.iter().take(SONG_LIST_CAPACITY).cloned().collect();
-	let first_songs = index_manager
-		.get_songs(first_paths)
-		.await
-		.into_iter()
-		.filter_map(Result::ok)
-		.map(dto::Song::from)
-		.collect();
-	dto::SongList { paths, first_songs }
+#[utoipa::path(
+	put,
+	path = "/rating/album/{name}/by/{artists}",
+	tag = "Collection",
+	description = "Rates an album for the current user, from 0 to 5 stars.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(
+		("name", example = "The Piano Sonatas"),
+		("artists", example = "Claude Frank", description = "Artists the album is attributed to, separated by unicode \\u{000C} characters."),
+	),
+	request_body = dto::Rating,
+	responses(
+		(status = 200),
+		(status = 400),
+		(status = 404),
+	)
+)]
+async fn put_album_rating(
+	auth: Auth,
+	State(index_manager): State<index::Manager>,
+	State(ratings_manager): State<ratings::Manager>,
+	Path((name, artists)): Path<(String, String)>,
+	Json(rating): Json<dto::Rating>,
+) -> Result<(), APIError> {
+	let artists = artists
+		.split(API_ARRAY_SEPARATOR)
+		.map(str::to_owned)
+		.collect::<Vec<_>>();
+	let album = index_manager.get_album(artists, name).await?;
+	ratings_manager
+		.set_rating(
+			auth.get_username(),
+			ratings::Item::Album {
+				name: album.header.name,
+				artists: album.header.artists,
+			},
+			rating.rating,
+		)
+		.await?;
+	Ok(())
 }
 
-fn song_list_to_response(song_list: dto::SongList, api_version: APIMajorVersion) -> Response {
-	match api_version {
+#[utoipa::path(
+	delete,
+	path = "/rating/album/{name}/by/{artists}",
+	tag = "Collection",
+	description = "Clears the rating given to an album by the current user.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(
+		("name", example = "The Piano Sonatas"),
+		("artists", example = "Claude Frank", description = "Artists the album is attributed to, separated by unicode \\u{000C} characters."),
+	),
+	responses(
+		(status = 200),
+	)
+)]
+async fn delete_album_rating(
+	auth: Auth,
+	State(ratings_manager): State<ratings::Manager>,
+	Path((name, artists)): Path<(String, String)>,
+) -> Result<(), APIError> {
+	let artists = artists
+		.split(API_ARRAY_SEPARATOR)
+		.map(str::to_owned)
+		.collect::<Vec<_>>();
+	ratings_manager
+		.clear_rating(auth.get_username(), ratings::Item::Album { name, artists })
+		.await?;
+	Ok(())
+}
+
+#[utoipa::path(
+	get,
+	path = "/search/{*query}",
+	tag = "Collection",
+	description = "Returns songs matching a search query. The query syntax is documented in the search section of the Polaris web UI. Tag fields can be matched individually using `field:value` filters (eg. `artist:boards album:\"music has the right\" year:>2000`).",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(
+		("Accept-Version" = Option<i32>, Header, minimum = 7, maximum = 8),
+		("query", allow_reserved, example = "sonata && moonlight"),
+		dto::PaginationParameters,
+		dto::StarredParameters,
+		dto::SongSortParameters,
+	),
+	responses(
+		(status = 200, body = dto::SongList, headers(("X-Total-Count" = usize))),
+	)
+)]
+#[allow(clippy::too_many_arguments)]
+async fn get_search(
+	auth: Auth,
+	api_version: APIMajorVersion,
+	State(favorites_manager): State<favorites::Manager>,
+	State(history_manager): State<history::Manager>,
+	State(index_manager): State<index::Manager>,
+	State(ratings_manager): State<ratings::Manager>,
+	Path(query): Path<String>,
+	Query(pagination): Query<dto::PaginationParameters>,
+	Query(filter): Query<dto::StarredParameters>,
+	Query(sort): Query<dto::SongSortParameters>,
+) -> Response {
+	let mut songs = match index_manager.search(query).await {
+		Ok(f) => f,
+		Err(e) => return APIError::from(e).into_response(),
+	};
+	let annotations = match SongAnnotations::fetch(
+		auth.get_username(),
+		&favorites_manager,
+		&history_manager,
+		&ratings_manager,
+	)
+	.await
+	{
+		Ok(a) => a,
+		Err(e) => return e.into_response(),
+	};
+	annotations.select(&mut songs, |s| s.virtual_path.as_path(), &filter, &sort);
+	sort_songs_by_tags(&mut songs, &sort);
+	let (songs, total) = paginate(songs, &pagination);
+
+	let mut song_list = dto::SongList {
+		paths: songs.iter().map(|s| s.virtual_path.clone()).collect(),
+		first_songs: songs
+			.into_iter()
+			.take(SONG_LIST_CAPACITY)
+			.map(|s| s.into())
+			.collect(),
+	};
+	annotations.apply(&mut song_list.first_songs);
+
+	let response = match api_version {
 		APIMajorVersion::V7 => Json(
 			song_list
 				.paths
-				.into_iter()
-				.map(|p| (&p).into())
-				.collect::<Vec<dto::v7::Song>>(),
+				.iter()
+				.map(|p| dto::v7::CollectionFile::Song(p.into()))
+				.collect::<Vec<_>>(),
 		)
 		.into_response(),
 		APIMajorVersion::V8 => Json(song_list).into_response(),
-	}
+	};
+	with_total_count(response, total)
 }
 
-fn albums_to_response(albums: Vec<index::Album>, api_version: APIMajorVersion) -> Response {
-	match api_version {
-		APIMajorVersion::V7 => Json(
-			albums
-				.into_iter()
-				.map(|f| f.into())
-				.collect::<Vec<dto::v7::Directory>>(),
-		)
-		.into_response(),
-		APIMajorVersion::V8 => Json(
-			albums
-				.into_iter()
-				.map(|f| f.header.into())
-				.collect::<Vec<dto::AlbumHeader>>(),
-		)
-		.into_response(),
-	}
+#[utoipa::path(
+	get,
+	path = "/playlists",
+	tag = "Playlists",
+	description = "Lists playlists owned by the current user.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	responses(
+		(status = 200, body = Vec<dto::PlaylistHeader>),
+	)
+)]
+async fn get_playlists(
+	auth: Auth,
+	State(playlist_manager): State<playlist::Manager>,
+) -> Result<Json<Vec<dto::PlaylistHeader>>, APIError> {
+	let playlists = playlist_manager.list_playlists(auth.get_username()).await?;
+	let playlists = playlists.into_iter().map(|p| p.into()).collect();
+
+	Ok(Json(playlists))
+}
+
+#[utoipa::path(
+	put,
+	path = "/playlist/{name}",
+	tag = "Playlists",
+	description = "Creates or updates a playlist for the current user.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(("name", example = "Chill Jazz")),
+	request_body = dto::SavePlaylistInput,
+)]
+async fn put_playlist(
+	auth: Auth,
+	State(playlist_manager): State<playlist::Manager>,
+	State(index_manager): State<index::Manager>,
+	Path(name): Path<String>,
+	playlist: Json<dto::SavePlaylistInput>,
+) -> Result<(), APIError> {
+	let songs = index_manager
+		.get_songs(playlist.tracks.clone())
+		.await
+		.into_iter()
+		.filter_map(|s| s.ok())
+		.collect();
+	playlist_manager
+		.save_playlist(&name, auth.get_username(), songs)
+		.await?;
+	Ok(())
 }
 
 #[utoipa::path(
 	get,
-	path = "/browse",
-	tag = "File Browser",
-	description = "Reads the content of the top-level directory in the music collection.",
+	path = "/playlist/{name}",
+	tag = "Playlists",
+	description = "Retrieves a playlist owned by the current user.",
 	security(
 		("auth_token" = []),
 		("auth_query_param" = []),
 	),
 	params(
-		("Accept-Version" = Option<i32>, Header, minimum = 7, maximum = 8)
+		("Accept-Version" = Option<i32>, Header, minimum = 7, maximum = 8),
+		("name", example = "Chill Jazz"),
+		dto::PaginationParameters,
 	),
 	responses(
-		(status = 200, body = Vec<dto::BrowserEntry>),
+		(status = 200, body = dto::Playlist, headers(("X-Total-Count" = usize))),
 	)
 )]
-async fn get_browse_root(
-	_auth: Auth,
+async fn get_playlist(
+	auth: Auth,
 	api_version: APIMajorVersion,
 	State(index_manager): State<index::Manager>,
+	State(playlist_manager): State<playlist::Manager>,
+	Path(name): Path<String>,
+	Query(pagination): Query<dto::PaginationParameters>,
 ) -> Response {
-	let result = match index_manager.browse(PathBuf::new()).await {
-		Ok(r) => r,
+	let playlist = match playlist_manager
+		.read_playlist(&name, auth.get_username())
+		.await
+	{
+		Ok(s) => s,
 		Err(e) => return APIError::from(e).into_response(),
 	};
-	index_files_to_response(result, api_version)
+
+	let (songs, total) = paginate(playlist.songs, &pagination);
+	let response = match api_version {
+		APIMajorVersion::V7 => Json(songs).into_response(),
+		APIMajorVersion::V8 => Json(dto::Playlist {
+			header: playlist.header.into(),
+			songs: make_song_list(songs, &index_manager).await,
+		})
+		.into_response(),
+	};
+	with_total_count(response, total)
 }
 
 #[utoipa::path(
-	get,
-	path = "/browse/{*path}",
-	tag = "File Browser",
-	description = "Reads the content of a directory in the music collection.",
+	delete,
+	path = "/playlist/{name}",
+	tag = "Playlists",
+	description = "Deletes a playlist owned by the current user.",
 	security(
 		("auth_token" = []),
 		("auth_query_param" = []),
 	),
-	params(
-		("Accept-Version" = Option<i32>, Header, minimum = 7, maximum = 8),
-		("path", allow_reserved, example = "my_music/classical/beethoven"),
+	params(("name", example = "Chill Jazz")),
+)]
+async fn delete_playlist(
+	auth: Auth,
+	State(playlist_manager): State<playlist::Manager>,
+	State(audit_manager): State<audit::Manager>,
+	client_ip: Option<Extension<ClientIp>>,
+	Path(name): Path<String>,
+) -> Result<(), APIError> {
+	playlist_manager
+		.delete_playlist(&name, auth.get_username())
+		.await?;
+	audit_manager
+		.record(
+			Some(auth.get_username()),
+			client_ip.map(|Extension(ClientIp(ip))| ip),
+			audit::Event::PlaylistDeleted { name },
+		)
+		.await;
+	Ok(())
+}
+
+#[utoipa::path(
+	post,
+	path = "/playlist/{name}/feed",
+	tag = "Playlists",
+	description = "Creates a podcast feed URL for a playlist owned by the current user.\n\nThe URL contains a token which only grants access to this playlist, for podcast apps which cannot sign in. Calling this endpoint again returns a URL for the same feed. Feeds stop working when they are revoked or when the playlist is deleted, even if a playlist with the same name is created later.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
 	),
+	params(("name", example = "Chill Jazz")),
 	responses(
-		(status = 200, body = Vec<dto::BrowserEntry>),
+		(status = 200, body = dto::PlaylistFeed),
+		(status = 404, description = "The playlist does not exist"),
 	)
 )]
-async fn get_browse(
-	_auth: Auth,
-	api_version: APIMajorVersion,
-	State(index_manager): State<index::Manager>,
-	Path(path): Path<PathBuf>,
-) -> Response {
-	let result = match index_manager.browse(path).await {
-		Ok(r) => r,
-		Err(e) => return APIError::from(e).into_response(),
+async fn post_playlist_feed(
+	rights: Permitted<permission::Share>,
+	State(config_manager): State<config::Manager>,
+	State(playlist_manager): State<playlist::Manager>,
+	Path(name): Path<String>,
+	uri: Uri,
+	headers: HeaderMap,
+) -> Result<Json<dto::PlaylistFeed>, APIError> {
+	let Some(auth) = rights.get_auth() else {
+		return Err(APIError::AuthenticationRequired);
 	};
-	index_files_to_response(result, api_version)
+	let feed_id = playlist_manager
+		.create_feed(&name, auth.get_username())
+		.await?;
+	let token = config_manager
+		.generate_feed_token(auth.get_username(), &name, &feed_id)
+		.await?;
+	let base_url = get_base_url(&config_manager, &uri, &headers).await;
+	Ok(Json(dto::PlaylistFeed {
+		url: format!("{base_url}/api/v{API_MAJOR_VERSION}/feed/{}", token.0),
+	}))
 }
 
 #[utoipa::path(
-	get,
-	path = "/flatten",
-	tag = "File Browser",
-	description = "Recursively lists all the songs in the music collection.",
+	delete,
+	path = "/playlist/{name}/feed",
+	tag = "Playlists",
+	description = "Revokes the podcast feed of a playlist owned by the current user. Existing feed URLs stop working, and creating a feed again issues a new URL.",
 	security(
 		("auth_token" = []),
 		("auth_query_param" = []),
 	),
+	params(("name", example = "Chill Jazz")),
+	responses(
+		(status = 200),
+		(status = 404, description = "The playlist does not exist"),
+	)
+)]
+async fn delete_playlist_feed(
+	auth: Auth,
+	State(playlist_manager): State<playlist::Manager>,
+	Path(name): Path<String>,
+) -> Result<(), APIError> {
+	playlist_manager
+		.revoke_feed(&name, auth.get_username())
+		.await?;
+	Ok(())
+}
+
+// Playlist readable with a feed token
+async fn authenticate_feed(
+	config_manager: &config::Manager,
+	playlist_manager: &playlist::Manager,
+	token: String,
+) -> Result<(playlist::Playlist, auth::Authorization), APIError> {
+	let authorization = config_manager
+		.authenticate(&auth::Token(token), auth::Scope::PlaylistFeed)
+		.await?;
+	let Some(name) = &authorization.playlist else {
+		return Err(APIError::IncorrectCredentials);
+	};
+	let playlist = playlist_manager
+		.read_playlist(name, &authorization.username)
+		.await
+		.map_err(|e| match e {
+			app::Error::PlaylistNotFound => APIError::IncorrectCredentials,
+			e => e.into(),
+		})?;
+	// Tokens of revoked feeds, or of deleted playlists which were created again, no longer match
+	if playlist.feed_id.is_none() || playlist.feed_id != authorization.feed_id {
+		return Err(APIError::IncorrectCredentials);
+	}
+	Ok((playlist, authorization))
+}
+
+#[utoipa::path(
+	get,
+	path = "/feed/{token}",
+	tag = "Playlists",
+	description = "Serves a playlist as an RSS podcast feed. Feed URLs are created with the `/playlist/{name}/feed` endpoint.",
+	params(("token", example = "875ifYC7bWnRyHG2ju3fMlORRQYzLN")),
+	responses(
+		(status = 200, content_type = "application/rss+xml", body = String),
+	)
+)]
+async fn get_playlist_feed(
+	State(config_manager): State<config::Manager>,
+	State(index_manager): State<index::Manager>,
+	State(playlist_manager): State<playlist::Manager>,
+	Path(token): Path<String>,
+	uri: Uri,
+	headers: HeaderMap,
+) -> Result<Response, APIError> {
+	let (playlist, authorization) =
+		authenticate_feed(&config_manager, &playlist_manager, token.clone()).await?;
+
+	let mut episodes = vec![];
+	for song in index_manager.get_songs(playlist.songs).await {
+		let Ok(song) = song else {
+			continue;
+		};
+		let size = match tokio::fs::metadata(&song.real_path).await {
+			Ok(m) => m.len(),
+			Err(_) => continue,
+		};
+		episodes.push(feed::Episode { song, size });
+	}
+
+	let base_url = get_base_url(&config_manager, &uri, &headers).await;
+	let feed_url = format!("{base_url}/api/v{API_MAJOR_VERSION}/feed/{token}");
+	let body = feed::render_playlist(
+		&playlist.header.name,
+		&authorization.username,
+		&episodes,
+		&feed_url,
+	);
+
+	Ok((
+		[(
+			header::CONTENT_TYPE,
+			HeaderValue::from_static("application/rss+xml; charset=utf-8"),
+		)],
+		body,
+	)
+		.into_response())
+}
+
+#[utoipa::path(
+	get,
+	path = "/feed/{token}/audio/{*path}",
+	tag = "Playlists",
+	description = "Serves a music file from a playlist podcast feed.\n\nThis endpoint supports HTTP range requests to facilitate streaming. When the owner of the playlist has a `stream_quality` limit, or used up their monthly transfer quota, audio is transcoded on the fly instead, without range support.",
 	params(
-		("Accept-Version" = Option<i32>, Header, minimum = 7, maximum = 8),
+		("token", example = "875ifYC7bWnRyHG2ju3fMlORRQYzLN"),
+		("path", allow_reserved, example = "my_music/beethoven/moonlight_sonata.mp3"),
 	),
 	responses(
-		(status = 200, body = dto::SongList),
+		(status = 206, body = [u8]),
+		(status = 200, body = [u8]),
+		(status = 304),
 	)
 )]
-async fn get_flatten_root(
-	_auth: Auth,
-	api_version: APIMajorVersion,
-	State(index_manager): State<index::Manager>,
-) -> Response {
-	let paths = match index_manager.flatten(PathBuf::new()).await {
-		Ok(s) => s,
-		Err(e) => return APIError::from(e).into_response(),
+async fn get_playlist_feed_audio(
+	State(config_manager): State<config::Manager>,
+	State(playlist_manager): State<playlist::Manager>,
+	Extension(stream_limiter): Extension<limits::StreamLimiter>,
+	Path((token, path)): Path<(String, PathBuf)>,
+	client_ip: Option<Extension<ClientIp>>,
+	headers: HeaderMap,
+) -> Result<Response, APIError> {
+	let (playlist, authorization) =
+		authenticate_feed(&config_manager, &playlist_manager, token).await?;
+	if !playlist.songs.contains(&path) {
+		return Err(APIError::SongNotFound);
+	}
+	let client_ip = client_ip.map(|Extension(ClientIp(ip))| ip);
+	let permit = stream_limiter
+		.acquire(
+			&authorization.username,
+			limits::StreamSource::Song(path.clone()),
+			client_ip,
+		)
+		.await?;
+
+	let audio_path = config_manager.resolve_virtual_path(&path).await?;
+	let quality = stream_limiter
+		.get_stream_quality(&authorization.username, client_ip)
+		.await?;
+	if let Some(quality) = quality {
+		let input = transcode::Input::File(audio_path);
+		let stream = transcode::open_stream(input, None, Some(&quality))?;
+		return Ok(permit.attach(serve_transcoded(stream)));
+	}
+
+	let Ok(file) = tokio::fs::File::open(&audio_path).await else {
+		return Err(APIError::AudioFileIOError);
 	};
-	let song_list = make_song_list(paths, &index_manager).await;
-	song_list_to_response(song_list, api_version)
+
+	let mut response = conditional::serve_file(file, &headers)
+		.await
+		.or(Err(APIError::AudioFileIOError))?;
+	if let Some(format) = get_audio_format(&audio_path) {
+		response.headers_mut().insert(
+			header::CONTENT_TYPE,
+			HeaderValue::from_static(format.mime_type()),
+		);
+	}
+	Ok(permit.attach(response))
 }
 
 #[utoipa::path(
 	get,
-	path = "/flatten/{*path}",
-	tag = "File Browser",
-	description = "Recursively lists all the songs within a directory of the music collection.",
+	path = "/audio/{*path}",
+	tag = "Media",
+	description = "Serves a music file.\n\nThis endpoint supports HTTP range requests to facilitate streaming, and `If-Range` to safely resume interrupted downloads. Downloads (`download=true`) require the `download` permission.\n\nFor users with a `stream_quality` limit, or who used up their monthly transfer quota, audio is transcoded on the fly instead, without range support. Songs split from a CUE sheet are also cut from their file on the fly, and served as FLAC unless a `stream_quality` limit applies.",
 	security(
 		("auth_token" = []),
 		("auth_query_param" = []),
 	),
 	params(
-		("Accept-Version" = Option<i32>, Header, minimum = 7, maximum = 8),
-		("path", allow_reserved, example = "my_music/classical/beethoven"),
+		("path", allow_reserved, example = "my_music/beethoven/moonlight_sonata.mp3"),
+		dto::GetAudioParameters,
 	),
 	responses(
-		(status = 200, body = dto::SongList),
+		(status = 206, body = [u8]),
+		(status = 200, body = [u8]),
+		(status = 304),
 	)
 )]
-async fn get_flatten(
-	_auth: Auth,
-	api_version: APIMajorVersion,
+#[allow(clippy::too_many_arguments)]
+async fn get_audio(
+	auth: Auth,
+	State(config_manager): State<config::Manager>,
 	State(index_manager): State<index::Manager>,
+	Extension(stream_limiter): Extension<limits::StreamLimiter>,
 	Path(path): Path<PathBuf>,
-) -> Response {
-	let paths = match index_manager.flatten(path).await {
-		Ok(s) => s,
-		Err(e) => return APIError::from(e).into_response(),
+	Query(options): Query<dto::GetAudioParameters>,
+	client_ip: Option<Extension<ClientIp>>,
+	headers: HeaderMap,
+) -> Result<Response, APIError> {
+	// Songs split from a CUE sheet are not files of their own
+	let remote = config_manager.get_remote(&path).await;
+	let (audio_path, segment) = match config_manager.resolve_virtual_path(&path).await? {
+		p if remote.is_none() && tokio::fs::try_exists(&p).await.unwrap_or(false) => (p, None),
+		p => match index_manager.get_songs(vec![path.clone()]).await.pop() {
+			Some(Ok(song)) if song.segment().is_some() => (song.real_path.clone(), song.segment()),
+			_ => (p, None),
+		},
+	};
+	let user = config_manager.get_user(auth.get_username()).await?;
+	if options.download == Some(true) && !user.has_permission(auth::Permission::Download) {
+		return Err(APIError::PermissionRequired);
+	}
+	let client_ip = client_ip.map(|Extension(ClientIp(ip))| ip);
+	let permit = stream_limiter
+		.acquire(
+			auth.get_username(),
+			limits::StreamSource::Song(path),
+			client_ip,
+		)
+		.await?;
+
+	let quality = stream_limiter
+		.get_stream_quality(auth.get_username(), client_ip)
+		.await?;
+	// Files from remote servers are fed to ffmpeg by Polaris, which holds the credentials
+	let transcode_input = match &remote {
+		_ if quality.is_none() && segment.is_none() => None,
+		Some(remote) => {
+			let (remote, real_path) = (remote.clone(), audio_path.clone());
+			let body = spawn_blocking(move || remote::read(&remote, &real_path))
+				.await
+				.unwrap()?;
+			Some(transcode::Input::Pipe(body))
+		}
+		None => Some(transcode::Input::File(audio_path.clone())),
+	};
+	if let Some(input) = transcode_input {
+		let stream = transcode::open_stream(input, segment, quality.as_ref())?;
+		return Ok(permit.attach(serve_transcoded(stream)));
+	}
+
+	let download_name = match options.download {
+		Some(true) => audio_path
+			.file_name()
+			.map(|n| n.to_string_lossy().to_string()),
+		_ => None,
+	};
+
+	if let Some(remote) = remote {
+		let response =
+			conditional::serve_remote(remote, audio_path, download_name.as_deref(), &headers)
+				.await?;
+		return Ok(permit.attach(response));
+	}
+
+	let Ok(file) = tokio::fs::File::open(&audio_path).await else {
+		return Err(APIError::AudioFileIOError);
+	};
+
+	let response = match download_name {
+		Some(file_name) => conditional::serve_download(file, &file_name, &headers).await,
+		None => conditional::serve_file(file, &headers).await,
 	};
-	let song_list = make_song_list(paths, &index_manager).await;
-	song_list_to_response(song_list, api_version)
+
+	response
+		.map(|r| permit.attach(r))
+		.or(Err(APIError::AudioFileIOError))
+}
+
+// Transcoded audio has no known length, so it is served without range support
+fn serve_transcoded(stream: transcode::Stream) -> Response {
+	let mut headers = HeaderMap::new();
+	headers.insert(
+		header::CONTENT_TYPE,
+		HeaderValue::from_static(stream.content_type),
+	);
+	headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+	let body = Body::from_stream(ReceiverStream::new(stream.chunks));
+	(headers, body).into_response()
+}
+
+#[utoipa::path(
+	put,
+	path = "/playback",
+	tag = "Media",
+	description = "Reports the playback position of the current user. When `lyrics` is set, time-synced lyrics for the song being played are pushed to the `/events` stream as playback progresses.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	request_body = dto::PlaybackReport,
+)]
+async fn put_playback(
+	auth: Auth,
+	State(events_manager): State<events::Manager>,
+	State(listenbrainz_manager): State<listenbrainz::Manager>,
+	Json(report): Json<dto::PlaybackReport>,
+) -> Result<(), APIError> {
+	listenbrainz_manager
+		.report_playback(auth.get_username(), &report.path)
+		.await;
+	events_manager
+		.report_playback(
+			auth.get_username(),
+			&report.path,
+			report.position,
+			report.lyrics,
+		)
+		.await?;
+	Ok(())
 }
 
 #[utoipa::path(
 	get,
-	path = "/albums",
-	tag = "Collection",
-	description = "Lists all albums in the music collection.",
+	path = "/history",
+	tag = "Media",
+	description = "Lists songs played to completion by the current user, most recent first.",
 	security(
 		("auth_token" = []),
 		("auth_query_param" = []),
 	),
+	params(dto::PaginationParameters),
 	responses(
-		(status = 200, body = Vec<dto::AlbumHeader>),
+		(status = 200, body = Vec<dto::Play>, headers(("X-Total-Count" = usize))),
 	)
 )]
-async fn get_albums(
-	_auth: Auth,
+async fn get_history(
+	auth: Auth,
+	State(history_manager): State<history::Manager>,
+	Query(pagination): Query<dto::PaginationParameters>,
+) -> Result<Response, APIError> {
+	let plays = history_manager.get_history(auth.get_username()).await?;
+	let (plays, total) = paginate(plays, &pagination);
+	let plays = plays
+		.into_iter()
+		.map(|p| p.into())
+		.collect::<Vec<dto::Play>>();
+	Ok(with_total_count(Json(plays).into_response(), total))
+}
+
+#[utoipa::path(
+	post,
+	path = "/history",
+	tag = "Media",
+	description = "Records that the current user played a song to completion. Clients should call this once per playback, when it ends.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	request_body = dto::NewPlay,
+	responses(
+		(status = 200, body = dto::Play),
+		(status = 404),
+	)
+)]
+async fn post_history(
+	auth: Auth,
+	headers: HeaderMap,
+	State(history_manager): State<history::Manager>,
 	State(index_manager): State<index::Manager>,
-) -> Result<Json<Vec<dto::AlbumHeader>>, APIError> {
-	Ok(Json(
-		index_manager
-			.get_albums()
-			.await
-			.into_iter()
-			.map(|a| a.into())
-			.collect::<Vec<_>>(),
-	))
+	State(listenbrainz_manager): State<listenbrainz::Manager>,
+	Json(play): Json<dto::NewPlay>,
+) -> Result<Json<dto::Play>, APIError> {
+	let song = index_manager
+		.get_songs(vec![play.path])
+		.await
+		.into_iter()
+		.next()
+		.ok_or(APIError::SongNotFound)??;
+	let client = play.client.or_else(|| {
+		headers
+			.get(http::header::USER_AGENT)
+			.and_then(|h| h.to_str().ok())
+			.map(str::to_owned)
+	});
+	let play = history_manager
+		.record_play(auth.get_username(), song.virtual_path.clone(), client)
+		.await?;
+	listenbrainz_manager
+		.submit_listen(auth.get_username(), &song, play.played_at)
+		.await;
+	Ok(Json(play.into()))
 }
 
 #[utoipa::path(
 	get,
-	path = "/artists",
-	tag = "Collection",
-	description = "Lists all artists in the music collection.",
+	path = "/recently_played/songs",
+	tag = "Media",
+	description = "Lists songs recently played to completion by the current user, most recent first. Songs played several times are only listed once.",
 	security(
 		("auth_token" = []),
 		("auth_query_param" = []),
 	),
+	params(dto::PaginationParameters),
 	responses(
-		(status = 200, body = Vec<dto::ArtistHeader>),
+		(status = 200, body = dto::SongList, headers(("X-Total-Count" = usize))),
 	)
 )]
-async fn get_artists(
-	_auth: Auth,
-	State(index_manager): State<index::Manager>,
-) -> Result<Json<Vec<dto::ArtistHeader>>, APIError> {
-	Ok(Json(
-		index_manager
-			.get_artists()
-			.await
-			.into_iter()
-			.map(|a| a.into())
-			.collect::<Vec<_>>(),
-	))
+async fn get_recently_played_songs(
+	auth: Auth,
+	State(favorites_manager): State<favorites::Manager>,
+	State(history_manager): State<history::Manager>,
+	State(index_manager): State<index::Manager>,
+	State(ratings_manager): State<ratings::Manager>,
+	Query(pagination): Query<dto::PaginationParameters>,
+) -> Result<Response, APIError> {
+	let paths = history_manager
+		.get_recently_played(auth.get_username())
+		.await?;
+	let (paths, total) = paginate(paths, &pagination);
+	let mut song_list = make_song_list(paths, &index_manager).await;
+	SongAnnotations::fetch(
+		auth.get_username(),
+		&favorites_manager,
+		&history_manager,
+		&ratings_manager,
+	)
+	.await?
+	.apply(&mut song_list.first_songs);
+	Ok(with_total_count(Json(song_list).into_response(), total))
 }
 
 #[utoipa::path(
 	get,
-	path = "/artist/{name}",
-	tag = "Collection",
-	description = "Returns detailed information about a single artist.",
+	path = "/recently_played/albums",
+	tag = "Media",
+	description = "Lists albums with songs recently played to completion by the current user, most recent first.",
 	security(
 		("auth_token" = []),
 		("auth_query_param" = []),
 	),
-	params(("name", example = "Claude Frank")),
+	params(dto::PaginationParameters),
 	responses(
-		(status = 200, body = dto::Artist),
+		(status = 200, body = Vec<dto::AlbumHeader>, headers(("X-Total-Count" = usize))),
 	)
 )]
-async fn get_artist(
-	_auth: Auth,
+async fn get_recently_played_albums(
+	auth: Auth,
+	State(history_manager): State<history::Manager>,
 	State(index_manager): State<index::Manager>,
-	Path(name): Path<String>,
-) -> Result<Json<dto::Artist>, APIError> {
-	Ok(Json(index_manager.get_artist(name).await?.into()))
+	Query(pagination): Query<dto::PaginationParameters>,
+) -> Result<Response, APIError> {
+	let paths = history_manager
+		.get_recently_played(auth.get_username())
+		.await?;
+
+	let mut seen = HashSet::new();
+	let album_ids = index_manager
+		.get_songs(paths)
+		.await
+		.into_iter()
+		.filter_map(Result::ok)
+		.filter_map(|s| index::AlbumId::from_song(&s))
+		.filter(|id| seen.insert(id.clone()))
+		.collect::<Vec<_>>();
+
+	let (album_ids, total) = paginate(album_ids, &pagination);
+	let mut albums = vec![];
+	for id in album_ids {
+		if let Ok(album) = index_manager.get_album(id.artists, id.name).await {
+			albums.push(dto::AlbumHeader::from(album.header));
+		}
+	}
+	Ok(with_total_count(Json(albums).into_response(), total))
 }
 
 #[utoipa::path(
 	get,
-	path = "/album/{name}/by/{artists}",
-	tag = "Collection",
-	description = "Returns detailed information about a single album.",
+	path = "/never_played/songs",
+	tag = "Media",
+	description = "Lists songs in the collection which the current user never played to completion.",
 	security(
 		("auth_token" = []),
 		("auth_query_param" = []),
 	),
-	params(
-		("name", example = "The Piano Sonatas"),
-		("artists", example = "Claude Frank", description = "Artists the album is attributed to, separated by unicode \\u{000C} characters."),
-	),
+	params(dto::GetNeverPlayedParameters, dto::PaginationParameters),
 	responses(
-		(status = 200, body = dto::Album),
+		(status = 200, body = dto::SongList, headers(("X-Total-Count" = usize))),
 	)
 )]
-async fn get_album(
-	_auth: Auth,
+async fn get_never_played_songs(
+	auth: Auth,
+	State(favorites_manager): State<favorites::Manager>,
+	State(history_manager): State<history::Manager>,
 	State(index_manager): State<index::Manager>,
-	Path((name, artists)): Path<(String, String)>,
-) -> Result<Json<dto::Album>, APIError> {
-	let artists = artists
-		.split(API_ARRAY_SEPARATOR)
-		.map(str::to_owned)
+	State(ratings_manager): State<ratings::Manager>,
+	Query(options): Query<dto::GetNeverPlayedParameters>,
+	Query(pagination): Query<dto::PaginationParameters>,
+) -> Result<Response, APIError> {
+	let play_counts = history_manager.get_play_counts(auth.get_username()).await?;
+	// Flattening fails while the collection is empty
+	let mut paths = index_manager
+		.flatten(PathBuf::new())
+		.await
+		.unwrap_or_default()
+		.into_iter()
+		.filter(|p| !play_counts.contains_key(p))
 		.collect::<Vec<_>>();
-	Ok(Json(index_manager.get_album(artists, name).await?.into()))
+	if let Some(seed) = options.seed {
+		let order = index::ListingOrder::Random(seed);
+		index::sort_listing(&mut paths, order, |_| index::ListingKey::default());
+	}
+	let (paths, total) = paginate(paths, &pagination);
+	let mut song_list = make_song_list(paths, &index_manager).await;
+	SongAnnotations::fetch(
+		auth.get_username(),
+		&favorites_manager,
+		&history_manager,
+		&ratings_manager,
+	)
+	.await?
+	.apply(&mut song_list.first_songs);
+	Ok(with_total_count(Json(song_list).into_response(), total))
 }
 
+// Albums whose songs were last played longer ago than this are considered forgotten
+const FORGOTTEN_ALBUM_AGE: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
 #[utoipa::path(
-	post, // post because of https://github.com/whatwg/fetch/issues/551
-	path = "/songs",
-	tag = "Collection",
-	description = "Returns detailed information about specific songs.\n\nEven though it is a read operation, this endpoint uses the `POST` method in order to facilitate usage of a request body (which is not standard for `GET` requests).",
+	get,
+	path = "/forgotten/albums",
+	tag = "Media",
+	description = "Lists albums which the current user played in the past, but not in over a year. Albums played the longest time ago are listed first.",
 	security(
 		("auth_token" = []),
 		("auth_query_param" = []),
 	),
-	request_body = dto::GetSongsBulkInput,
+	params(dto::PaginationParameters),
 	responses(
-		(status = 200, body = dto::GetSongsBulkOutput),
+		(status = 200, body = Vec<dto::AlbumHeader>, headers(("X-Total-Count" = usize))),
 	)
 )]
-async fn get_songs(
-	_auth: Auth,
+async fn get_forgotten_albums(
+	auth: Auth,
+	State(history_manager): State<history::Manager>,
 	State(index_manager): State<index::Manager>,
-	songs: Json<dto::GetSongsBulkInput>,
-) -> Result<Json<dto::GetSongsBulkOutput>, APIError> {
-	let results = index_manager
-		.get_songs(songs.0.paths.clone())
-		.await
+	Query(pagination): Query<dto::PaginationParameters>,
+) -> Result<Response, APIError> {
+	let last_played = history_manager.get_last_played(auth.get_username()).await?;
+
+	let mut album_last_played = HashMap::<index::AlbumId, i64>::new();
+	let songs = index_manager
+		.get_songs(last_played.keys().cloned().collect())
+		.await;
+	for song in songs.into_iter().filter_map(Result::ok) {
+		let Some(id) = index::AlbumId::from_song(&song) else {
+			continue;
+		};
+		let played_at = last_played
+			.get(&song.virtual_path)
+			.copied()
+			.unwrap_or_default();
+		let entry = album_last_played.entry(id).or_insert(played_at);
+		*entry = (*entry).max(played_at);
+	}
+
+	let cutoff = SystemTime::now()
+		.checked_sub(FORGOTTEN_ALBUM_AGE)
+		.and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+		.map(|d| d.as_secs() as i64)
+		.unwrap_or_default();
+	let mut album_ids = album_last_played
 		.into_iter()
+		.filter(|(_, played_at)| *played_at < cutoff)
 		.collect::<Vec<_>>();
+	album_ids.sort_by_key(|(_, played_at)| *played_at);
 
-	let mut output = dto::GetSongsBulkOutput::default();
-	for (i, r) in results.into_iter().enumerate() {
-		match r {
-			Ok(s) => output.songs.push(s.into()),
-			Err(_) => output.not_found.push(songs.0.paths[i].clone()),
+	let (album_ids, total) = paginate(album_ids, &pagination);
+	let mut albums = vec![];
+	for (id, _) in album_ids {
+		if let Ok(album) = index_manager.get_album(id.artists, id.name).await {
+			albums.push(dto::AlbumHeader::from(album.header));
 		}
 	}
-
-	Ok(Json(output))
+	Ok(with_total_count(Json(albums).into_response(), total))
 }
 
 #[utoipa::path(
 	get,
-	path = "/albums/random",
-	tag = "Collection",
-	description = "Returns a random selection of albums from the collection.\n\nRe-using the same seed will return the same albums only as long as the collection does not change.",
+	path = "/stats",
+	tag = "Media",
+	description = "Returns listening statistics of the current user, computed from the songs they played to completion.",
 	security(
 		("auth_token" = []),
 		("auth_query_param" = []),
 	),
-	params(
-		("Accept-Version" = Option<i32>, Header, minimum = 7, maximum = 8),
-		dto::GetRandomAlbumsParameters,
-	),
+	params(dto::GetStatsParameters),
 	responses(
-		(status = 200, body = Vec<dto::AlbumHeader>),
+		(status = 200, body = dto::Stats),
 	)
 )]
-async fn get_random_albums(
-	_auth: Auth,
-	api_version: APIMajorVersion,
-	State(index_manager): State<index::Manager>,
-	Query(options): Query<dto::GetRandomAlbumsParameters>,
-) -> Response {
-	let offset = options.offset.unwrap_or(0);
-	let count = options.count.unwrap_or(20);
-	let albums = match index_manager
-		.get_random_albums(options.seed, offset, count)
-		.await
-	{
-		Ok(d) => d,
-		Err(e) => return APIError::from(e).into_response(),
+async fn get_stats(
+	auth: Auth,
+	State(stats_manager): State<stats::Manager>,
+	Query(options): Query<dto::GetStatsParameters>,
+) -> Result<Json<dto::Stats>, APIError> {
+	let filter = stats::Filter {
+		since: options.since,
+		until: options.until,
+		utc_offset_minutes: options.utc_offset.unwrap_or(0),
+		top_count: options.count.unwrap_or(stats::DEFAULT_TOP_COUNT),
 	};
-	albums_to_response(albums, api_version)
+	let report = stats_manager
+		.get_report(auth.get_username(), filter)
+		.await?;
+	Ok(Json(report.into()))
 }
 
 #[utoipa::path(
 	get,
-	path = "/albums/recent",
-	tag = "Collection",
-	description = "Returns the albums most recently added to the collection.",
+	path = "/play_queue",
+	tag = "Playlists",
+	description = "Returns the play queue last saved by the current user, from any device. Users who never saved a queue get an empty one.",
 	security(
 		("auth_token" = []),
 		("auth_query_param" = []),
 	),
-	params(
-		("Accept-Version" = Option<i32>, Header, minimum = 7, maximum = 8),
-		dto::GetRecentAlbumsParameters
-	),
 	responses(
-		(status = 200, body = Vec<dto::AlbumHeader>),
+		(status = 200, body = dto::PlayQueue),
 	)
 )]
-async fn get_recent_albums(
-	_auth: Auth,
-	api_version: APIMajorVersion,
-	State(index_manager): State<index::Manager>,
-	Query(options): Query<dto::GetRecentAlbumsParameters>,
-) -> Response {
-	let offset = options.offset.unwrap_or(0);
-	let count = options.count.unwrap_or(20);
-	let albums = match index_manager.get_recent_albums(offset, count).await {
-		Ok(d) => d,
-		Err(e) => return APIError::from(e).into_response(),
-	};
-	albums_to_response(albums, api_version)
+async fn get_play_queue(
+	auth: Auth,
+	State(queue_manager): State<queue::Manager>,
+) -> Result<Json<dto::PlayQueue>, APIError> {
+	let play_queue = queue_manager.get_play_queue(auth.get_username()).await?;
+	Ok(Json(play_queue.into()))
 }
 
 #[utoipa::path(
-	get,
-	path = "/genres",
-	tag = "Collection",
-	description = "Lists all music genres in the collection.",
+	put,
+	path = "/play_queue",
+	tag = "Playlists",
+	description = "Saves the play queue of the current user, along with the song being played and the playback position within it. This lets users resume listening from the same spot on another device.\n\nSaving a queue replaces the previously saved one.",
 	security(
 		("auth_token" = []),
 		("auth_query_param" = []),
 	),
+	request_body = dto::SavePlayQueueInput,
 	responses(
-		(status = 200, body = Vec<dto::GenreHeader>),
+		(status = 200, body = dto::PlayQueue),
+		(status = 400, description = "Position is out of range"),
 	)
 )]
-async fn get_genres(
-	_auth: Auth,
-	State(index_manager): State<index::Manager>,
-) -> Result<Json<Vec<dto::GenreHeader>>, APIError> {
-	Ok(Json(
-		index_manager
-			.get_genres()
-			.await
-			.into_iter()
-			.map(|g| g.into())
-			.collect(),
-	))
+async fn put_play_queue(
+	auth: Auth,
+	State(queue_manager): State<queue::Manager>,
+	Json(input): Json<dto::SavePlayQueueInput>,
+) -> Result<Json<dto::PlayQueue>, APIError> {
+	let play_queue = queue_manager
+		.save_play_queue(
+			auth.get_username(),
+			input.songs,
+			input.position,
+			input.offset,
+		)
+		.await?;
+	Ok(Json(play_queue.into()))
 }
 
 #[utoipa::path(
 	get,
-	path = "/genre/{name}",
-	tag = "Collection",
-	description = "Returns detailed information about a music genre.",
+	path = "/radio_stations",
+	tag = "Radio",
+	description = "Lists the internet radio stations registered on this server.",
 	security(
 		("auth_token" = []),
 		("auth_query_param" = []),
 	),
-	params(("name", example = "Classical")),
 	responses(
-		(status = 200, body = Vec<dto::Genre>),
-	)
+		(status = 200, body = Vec<dto::RadioStation>),
+	),
 )]
-async fn get_genre(
+async fn get_radio_stations(
 	_auth: Auth,
-	State(index_manager): State<index::Manager>,
-	Path(name): Path<String>,
-) -> Result<Json<dto::Genre>, APIError> {
-	Ok(Json(index_manager.get_genre(name).await?.into()))
+	State(radio_manager): State<radio::Manager>,
+) -> Result<Json<Vec<dto::RadioStation>>, APIError> {
+	let stations = radio_manager.list_stations().await?;
+	Ok(Json(stations.into_iter().map(|s| s.into()).collect()))
 }
 
 #[utoipa::path(
-	get,
-	path = "/genre/{name}/albums",
-	tag = "Collection",
-	description = "Returns all albums associated with a music genre.",
+	post,
+	path = "/radio_stations",
+	tag = "Radio",
+	description = "Registers a new internet radio station.",
 	security(
 		("auth_token" = []),
 		("auth_query_param" = []),
 	),
-	params(("name", example = "Classical")),
+	request_body = dto::RadioStationInput,
 	responses(
-		(status = 200, body = Vec<dto::AlbumHeader>),
-	)
+		(status = 200, body = dto::RadioStation),
+	),
 )]
-async fn get_genre_albums(
-	_auth: Auth,
-	State(index_manager): State<index::Manager>,
-	Path(name): Path<String>,
-) -> Result<Json<Vec<dto::AlbumHeader>>, APIError> {
-	let albums = index_manager
-		.get_genre(name)
-		.await?
-		.albums
-		.into_iter()
-		.map(|a| a.into())
-		.collect();
-	Ok(Json(albums))
+async fn post_radio_station(
+	_admin_rights: AdminRights,
+	State(radio_manager): State<radio::Manager>,
+	Json(station): Json<dto::RadioStationInput>,
+) -> Result<Json<dto::RadioStation>, APIError> {
+	let station = radio_manager.create_station(station.into()).await?;
+	Ok(Json(station.into()))
+}
+
+#[utoipa::path(
+	put,
+	path = "/radio_station/{id}",
+	tag = "Radio",
+	description = "Updates an internet radio station.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(("id", example = "k2OeWsqxBWmpRtKJ")),
+	request_body = dto::RadioStationInput,
+)]
+async fn put_radio_station(
+	_admin_rights: AdminRights,
+	State(radio_manager): State<radio::Manager>,
+	Path(id): Path<String>,
+	Json(station): Json<dto::RadioStationInput>,
+) -> Result<(), APIError> {
+	radio_manager.update_station(&id, station.into()).await?;
+	Ok(())
+}
+
+#[utoipa::path(
+	delete,
+	path = "/radio_station/{id}",
+	tag = "Radio",
+	description = "Removes an internet radio station.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(("id", example = "k2OeWsqxBWmpRtKJ")),
+)]
+async fn delete_radio_station(
+	_admin_rights: AdminRights,
+	State(radio_manager): State<radio::Manager>,
+	Path(id): Path<String>,
+) -> Result<(), APIError> {
+	radio_manager.delete_station(&id).await?;
+	Ok(())
 }
 
 #[utoipa::path(
 	get,
-	path = "/genre/{name}/artists",
-	tag = "Collection",
-	description = "Returns all artists associated with a music genre.",
+	path = "/radio_station/{id}/stream",
+	tag = "Radio",
+	description = "Relays the audio stream of an internet radio station. Clients can use this endpoint to play stations which are not served over HTTPS, or which do not allow cross-origin requests.",
 	security(
 		("auth_token" = []),
 		("auth_query_param" = []),
 	),
-	params(("name", example = "Classical")),
+	params(("id", example = "k2OeWsqxBWmpRtKJ")),
 	responses(
-		(status = 200, body = Vec<dto::ArtistHeader>),
-	)
+		(status = 200, content_type = "audio/*"),
+	),
 )]
-async fn get_genre_artists(
-	_auth: Auth,
-	State(index_manager): State<index::Manager>,
-	Path(name): Path<String>,
-) -> Result<Json<Vec<dto::ArtistHeader>>, APIError> {
-	let artists = index_manager
-		.get_genre(name)
-		.await?
-		.artists
-		.into_iter()
-		.map(|a| a.into())
-		.collect();
-	Ok(Json(artists))
+async fn get_radio_station_stream(
+	auth: Auth,
+	State(radio_manager): State<radio::Manager>,
+	Extension(stream_limiter): Extension<limits::StreamLimiter>,
+	Path(id): Path<String>,
+	client_ip: Option<Extension<ClientIp>>,
+) -> Result<Response, APIError> {
+	let permit = stream_limiter
+		.acquire(
+			auth.get_username(),
+			limits::StreamSource::RadioStation(id.clone()),
+			client_ip.map(|Extension(ClientIp(ip))| ip),
+		)
+		.await?;
+	let stream = radio_manager.open_stream(&id).await?;
+
+	let mut headers = HeaderMap::new();
+	if let Some(content_type) = stream
+		.content_type
+		.and_then(|t| HeaderValue::from_str(&t).ok())
+	{
+		headers.insert(header::CONTENT_TYPE, content_type);
+	}
+	headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+
+	let body = Body::from_stream(ReceiverStream::new(stream.chunks));
+	Ok(permit.attach((headers, body).into_response()))
 }
 
 #[utoipa::path(
 	get,
-	path = "/genre/{name}/songs",
-	tag = "Collection",
-	description = "Returns all songs associated with a music genre.",
+	path = "/streams",
+	tag = "Media",
+	description = "Lists the audio streams currently being served, oldest first. This includes songs, transcoded audio and relayed internet radio stations.",
 	security(
 		("auth_token" = []),
 		("auth_query_param" = []),
 	),
-	params(("name", example = "Classical")),
 	responses(
-		(status = 200, body = dto::SongList),
-	)
+		(status = 200, body = Vec<dto::ActiveStream>),
+	),
 )]
-async fn get_genre_songs(
-	_auth: Auth,
-	State(index_manager): State<index::Manager>,
-	Path(name): Path<String>,
-) -> Result<Json<dto::SongList>, APIError> {
-	let songs = index_manager.get_genre(name).await?.songs;
-	let song_list = dto::SongList {
-		paths: songs.iter().map(|s| s.virtual_path.clone()).collect(),
-		first_songs: songs
-			.into_iter()
-			.take(SONG_LIST_CAPACITY)
-			.map(|s| s.into())
-			.collect(),
-	};
-	Ok(Json(song_list))
+async fn get_streams(
+	_admin_rights: AdminRights,
+	Extension(stream_limiter): Extension<limits::StreamLimiter>,
+) -> Json<Vec<dto::ActiveStream>> {
+	let streams = stream_limiter
+		.get_active_streams()
+		.into_iter()
+		.map(|s| {
+			let (path, radio_station) = match s.source.clone() {
+				limits::StreamSource::Song(path) => (Some(path), None),
+				limits::StreamSource::RadioStation(id) => (None, Some(id)),
+			};
+			dto::ActiveStream {
+				username: s.username.clone(),
+				path,
+				radio_station,
+				client_ip: s.client_ip.map(|ip| ip.to_string()),
+				started_at: s
+					.started_at
+					.duration_since(UNIX_EPOCH)
+					.map(|d| d.as_secs() as i64)
+					.unwrap_or_default(),
+				bytes_sent: s.bytes_sent,
+				bitrate: s.bitrate_kbps(),
+				progress: s.progress(),
+			}
+		})
+		.collect();
+	Json(streams)
+}
+
+#[utoipa::path(
+	get,
+	path = "/jukebox",
+	tag = "Jukebox",
+	description = "Returns the queue and playback state of the jukebox, which plays songs through the audio output of the server.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	responses(
+		(status = 200, body = dto::JukeboxStatus),
+	),
+)]
+async fn get_jukebox(
+	_auth: Auth,
+	State(jukebox_manager): State<jukebox::Manager>,
+) -> Result<Json<dto::JukeboxStatus>, APIError> {
+	let status = jukebox_manager.get_status().await?;
+	Ok(Json(status.into()))
 }
 
 #[utoipa::path(
-	get,
-	path = "/search/{*query}",
-	tag = "Collection",
-	description = "Returns songs matching a search query. The query syntax is documented in the search section of the Polaris web UI.",
+	put,
+	path = "/jukebox/queue",
+	tag = "Jukebox",
+	description = "Replaces the jukebox queue and stops playback.",
 	security(
 		("auth_token" = []),
 		("auth_query_param" = []),
 	),
-	params(
-		("Accept-Version" = Option<i32>, Header, minimum = 7, maximum = 8),
-		("query", allow_reserved, example = "sonata && moonlight"),
-	),
-	responses(
-		(status = 200, body = dto::SongList),
-	)
+	request_body = dto::JukeboxQueueInput,
 )]
-async fn get_search(
+async fn put_jukebox_queue(
 	_auth: Auth,
-	api_version: APIMajorVersion,
-	State(index_manager): State<index::Manager>,
-	Path(query): Path<String>,
-) -> Response {
-	let songs = match index_manager.search(query).await {
-		Ok(f) => f,
-		Err(e) => return APIError::from(e).into_response(),
-	};
-
-	let song_list = dto::SongList {
-		paths: songs.iter().map(|s| s.virtual_path.clone()).collect(),
-		first_songs: songs
-			.into_iter()
-			.take(SONG_LIST_CAPACITY)
-			.map(|s| s.into())
-			.collect(),
-	};
-
-	match api_version {
-		APIMajorVersion::V7 => Json(
-			song_list
-				.paths
-				.iter()
-				.map(|p| dto::v7::CollectionFile::Song(p.into()))
-				.collect::<Vec<_>>(),
-		)
-		.into_response(),
-		APIMajorVersion::V8 => Json(song_list).into_response(),
-	}
+	State(jukebox_manager): State<jukebox::Manager>,
+	Json(input): Json<dto::JukeboxQueueInput>,
+) -> Result<(), APIError> {
+	jukebox_manager.set_queue(input.tracks).await?;
+	Ok(())
 }
 
 #[utoipa::path(
-	get,
-	path = "/playlists",
-	tag = "Playlists",
-	description = "Lists playlists owned by the current user.",
+	post,
+	path = "/jukebox/queue",
+	tag = "Jukebox",
+	description = "Adds songs to the end of the jukebox queue.",
 	security(
 		("auth_token" = []),
 		("auth_query_param" = []),
 	),
-	responses(
-		(status = 200, body = Vec<dto::PlaylistHeader>),
-	)
+	request_body = dto::JukeboxQueueInput,
 )]
-async fn get_playlists(
-	auth: Auth,
-	State(playlist_manager): State<playlist::Manager>,
-) -> Result<Json<Vec<dto::PlaylistHeader>>, APIError> {
-	let playlists = playlist_manager.list_playlists(auth.get_username()).await?;
-	let playlists = playlists.into_iter().map(|p| p.into()).collect();
-
-	Ok(Json(playlists))
+async fn post_jukebox_queue(
+	_auth: Auth,
+	State(jukebox_manager): State<jukebox::Manager>,
+	Json(input): Json<dto::JukeboxQueueInput>,
+) -> Result<(), APIError> {
+	jukebox_manager.add_to_queue(input.tracks).await?;
+	Ok(())
 }
 
 #[utoipa::path(
 	put,
-	path = "/playlist/{name}",
-	tag = "Playlists",
-	description = "Creates or updates a playlist for the current user.",
+	path = "/jukebox/player",
+	tag = "Jukebox",
+	description = "Controls jukebox playback. All fields are optional: a song to play is selected before seeking, and seeking happens before pausing or stopping.",
 	security(
 		("auth_token" = []),
 		("auth_query_param" = []),
 	),
-	params(("name", example = "Chill Jazz")),
-	request_body = dto::SavePlaylistInput,
+	request_body = dto::JukeboxControl,
 )]
-async fn put_playlist(
-	auth: Auth,
-	State(playlist_manager): State<playlist::Manager>,
-	State(index_manager): State<index::Manager>,
-	Path(name): Path<String>,
-	playlist: Json<dto::SavePlaylistInput>,
+async fn put_jukebox_player(
+	_auth: Auth,
+	State(jukebox_manager): State<jukebox::Manager>,
+	Json(control): Json<dto::JukeboxControl>,
 ) -> Result<(), APIError> {
-	let songs = index_manager
-		.get_songs(playlist.tracks.clone())
-		.await
-		.into_iter()
-		.filter_map(|s| s.ok())
-		.collect();
-	playlist_manager
-		.save_playlist(&name, auth.get_username(), songs)
-		.await?;
+	if let Some(volume) = control.volume {
+		jukebox_manager.set_volume(volume).await?;
+	}
+	if let Some(current) = control.current {
+		jukebox_manager.play(Some(current)).await?;
+	}
+	if let Some(position) = control.position {
+		jukebox_manager
+			.seek(Duration::from_millis(position))
+			.await?;
+	}
+	match control.playback {
+		Some(dto::JukeboxPlayback::Playing) => jukebox_manager.play(None).await?,
+		Some(dto::JukeboxPlayback::Paused) => jukebox_manager.pause().await?,
+		Some(dto::JukeboxPlayback::Stopped) => jukebox_manager.stop().await?,
+		None => (),
+	}
 	Ok(())
 }
 
 #[utoipa::path(
-	get,
-	path = "/playlist/{name}",
-	tag = "Playlists",
-	description = "Retrieves a playlist owned by the current user.",
+	post,
+	path = "/jukebox/next",
+	tag = "Jukebox",
+	description = "Skips to the next song in the jukebox queue.",
 	security(
 		("auth_token" = []),
 		("auth_query_param" = []),
 	),
-	params(
-		("Accept-Version" = Option<i32>, Header, minimum = 7, maximum = 8),
-		("name", example = "Chill Jazz"),
-	),
-	responses(
-		(status = 200, body = dto::Playlist),
-	)
 )]
-async fn get_playlist(
-	auth: Auth,
-	api_version: APIMajorVersion,
-	State(index_manager): State<index::Manager>,
-	State(playlist_manager): State<playlist::Manager>,
-	Path(name): Path<String>,
-) -> Response {
-	let playlist = match playlist_manager
-		.read_playlist(&name, auth.get_username())
-		.await
-	{
-		Ok(s) => s,
-		Err(e) => return APIError::from(e).into_response(),
-	};
-
-	match api_version {
-		APIMajorVersion::V7 => Json(playlist.songs).into_response(),
-		APIMajorVersion::V8 => Json(dto::Playlist {
-			header: playlist.header.into(),
-			songs: make_song_list(playlist.songs, &index_manager).await,
-		})
-		.into_response(),
-	}
+async fn post_jukebox_next(
+	_auth: Auth,
+	State(jukebox_manager): State<jukebox::Manager>,
+) -> Result<(), APIError> {
+	jukebox_manager.next().await?;
+	Ok(())
 }
 
 #[utoipa::path(
-	delete,
-	path = "/playlist/{name}",
-	tag = "Playlists",
-	description = "Deletes a playlist owned by the current user.",
+	post,
+	path = "/jukebox/previous",
+	tag = "Jukebox",
+	description = "Goes back to the previous song in the jukebox queue.",
 	security(
 		("auth_token" = []),
 		("auth_query_param" = []),
 	),
-	params(("name", example = "Chill Jazz")),
 )]
-async fn delete_playlist(
-	auth: Auth,
-	State(playlist_manager): State<playlist::Manager>,
-	Path(name): Path<String>,
+async fn post_jukebox_previous(
+	_auth: Auth,
+	State(jukebox_manager): State<jukebox::Manager>,
 ) -> Result<(), APIError> {
-	playlist_manager
-		.delete_playlist(&name, auth.get_username())
-		.await?;
+	jukebox_manager.previous().await?;
 	Ok(())
 }
 
 #[utoipa::path(
 	get,
-	path = "/audio/{*path}",
+	path = "/events",
 	tag = "Media",
-	description = "Serves a music file.\n\nThis endpoint supports HTTP range requests to facilitate streaming.",
+	description = "Opens a stream of server-sent events for the current user.\n\n`lyric_line` events are sent while playback is reported via the `/playback` endpoint with lyrics enabled.",
 	security(
 		("auth_token" = []),
 		("auth_query_param" = []),
 	),
-	params(("path", allow_reserved, example = "my_music/beethoven/moonlight_sonata.mp3")),
 	responses(
-		(status = 206, body = [u8]),
-		(status = 200, body = [u8]),
+		(status = 200, content_type = "text/event-stream", body = dto::LyricLineEvent),
 	)
 )]
-async fn get_audio(
-	_auth: Auth,
-	State(config_manager): State<config::Manager>,
-	Path(path): Path<PathBuf>,
-	range: Option<TypedHeader<Range>>,
-) -> Result<impl IntoResponse, APIError> {
-	let audio_path = config_manager.resolve_virtual_path(&path).await?;
-
-	let Ok(file) = tokio::fs::File::open(audio_path).await else {
-		return Err(APIError::AudioFileIOError);
-	};
-
-	let Ok(body) = KnownSize::file(file).await else {
-		return Err(APIError::AudioFileIOError);
-	};
-
-	let range = range.map(|TypedHeader(r)| r);
-	Ok(Ranged::new(range, body))
+async fn get_events(
+	auth: Auth,
+	State(events_manager): State<events::Manager>,
+) -> Sse<impl Stream<Item = Result<sse::Event, Infallible>>> {
+	let receiver = events_manager.subscribe(auth.get_username()).await;
+	let stream = BroadcastStream::new(receiver).filter_map(|event| {
+		let event = dto::LyricLineEvent::from(event.ok()?);
+		sse::Event::default()
+			.event("lyric_line")
+			.json_data(event)
+			.ok()
+			.map(Ok)
+	});
+	Sse::new(stream).keep_alive(KeepAlive::default())
 }
 
 #[utoipa::path(
@@ -1168,6 +4956,53 @@ async fn get_peaks(
 	Ok(peaks.interleaved)
 }
 
+#[utoipa::path(
+	get,
+	path = "/lyrics/{*path}",
+	tag = "Media",
+	description = "Returns the lyrics of the specified song, from a `.lrc` file next to it or from its metadata. Time-synced lyrics are included when available.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(("path", allow_reserved, example = "my_music/destiny.mp3")),
+	responses(
+		(status = 200, body = dto::Lyrics),
+		(status = 404, description = "The song has no lyrics"),
+	)
+)]
+async fn get_lyrics(
+	_auth: Auth,
+	State(config_manager): State<config::Manager>,
+	Path(path): Path<PathBuf>,
+) -> Result<Json<dto::Lyrics>, APIError> {
+	let audio_path = config_manager.resolve_virtual_path(&path).await?;
+	Ok(Json(lyrics::get_lyrics(audio_path).await?.into()))
+}
+
+#[utoipa::path(
+	get,
+	path = "/fingerprint/{*path}",
+	tag = "Media",
+	description = "Returns the Chromaprint fingerprint of the specified song, for identification with AcoustID. Fingerprints are only computed when the `acoustid_fingerprints` configuration setting is enabled.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(("path", allow_reserved, example = "my_music/destiny.mp3")),
+	responses(
+		(status = 200, body = dto::Fingerprint),
+		(status = 404, description = "No fingerprint has been computed for this song"),
+	)
+)]
+async fn get_fingerprint(
+	_auth: Auth,
+	State(acoustid_manager): State<acoustid::Manager>,
+	Path(path): Path<PathBuf>,
+) -> Result<Json<dto::Fingerprint>, APIError> {
+	Ok(Json(acoustid_manager.get_fingerprint(&path).await?.into()))
+}
+
 #[utoipa::path(
 	get,
 	path = "/thumbnail/{*path}",
@@ -1184,18 +5019,77 @@ async fn get_peaks(
 	responses(
 		(status = 206, body = [u8]),
 		(status = 200, body = [u8]),
+		(status = 304),
 	)
 )]
 async fn get_thumbnail(
 	_auth: Auth,
+	State(artwork_manager): State<artwork::Manager>,
 	State(config_manager): State<config::Manager>,
 	State(thumbnails_manager): State<thumbnail::Manager>,
 	Path(path): Path<PathBuf>,
 	Query(options_input): Query<dto::ThumbnailOptions>,
-	range: Option<TypedHeader<Range>>,
-) -> Result<impl IntoResponse, APIError> {
+	headers: HeaderMap,
+) -> Result<Response, APIError> {
+	let options = thumbnail::Options::from(options_input);
+	let thumbnail_path = match artwork_manager.get_override(&path).await? {
+		Some(p) => thumbnails_manager.get_thumbnail(&p, &options).await?,
+		None => {
+			let image_path = config_manager.resolve_virtual_path(&path).await?;
+			match config_manager.get_remote(&path).await {
+				Some(remote) => {
+					thumbnails_manager
+						.get_remote_thumbnail(&remote, &image_path, &options)
+						.await?
+				}
+				None => {
+					thumbnails_manager
+						.get_thumbnail(&image_path, &options)
+						.await?
+				}
+			}
+		}
+	};
+
+	let Ok(file) = tokio::fs::File::open(thumbnail_path).await else {
+		return Err(APIError::ThumbnailFileIOError);
+	};
+
+	conditional::serve_file(file, &headers)
+		.await
+		.or(Err(APIError::ThumbnailFileIOError))
+}
+
+#[utoipa::path(
+	get,
+	path = "/artist/{name}/image",
+	tag = "Media",
+	description = "Serves a portrait of an artist.\n\nPortraits are read from `artist.jpg` files next to the artist's albums, or downloaded from the online providers listed in the `[artist_images]` configuration section.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(
+		("name", example = "Claude Frank"),
+		dto::ThumbnailOptions
+	),
+	responses(
+		(status = 206, body = [u8]),
+		(status = 200, body = [u8]),
+		(status = 304),
+		(status = 404, description = "No portrait of this artist could be found"),
+	)
+)]
+async fn get_artist_image(
+	_auth: Auth,
+	State(artist_image_manager): State<artist_image::Manager>,
+	State(thumbnails_manager): State<thumbnail::Manager>,
+	Path(name): Path<String>,
+	Query(options_input): Query<dto::ThumbnailOptions>,
+	headers: HeaderMap,
+) -> Result<Response, APIError> {
 	let options = thumbnail::Options::from(options_input);
-	let image_path = config_manager.resolve_virtual_path(&path).await?;
+	let image_path = artist_image_manager.get_image(&name).await?;
 
 	let thumbnail_path = thumbnails_manager
 		.get_thumbnail(&image_path, &options)
@@ -1205,10 +5099,58 @@ async fn get_thumbnail(
 		return Err(APIError::ThumbnailFileIOError);
 	};
 
-	let Ok(body) = KnownSize::file(file).await else {
-		return Err(APIError::ThumbnailFileIOError);
-	};
+	conditional::serve_file(file, &headers)
+		.await
+		.or(Err(APIError::ThumbnailFileIOError))
+}
+
+#[utoipa::path(
+	put,
+	path = "/artwork/{*path}",
+	tag = "Media",
+	description = "Replaces the artwork of a collection directory with an uploaded image.\n\nThe image is stored outside of music folders, and served by the `/thumbnail` endpoint instead of artwork found in the directory. Uploading again replaces the previous image.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(("path", allow_reserved, example = "my_music/stratovarius/destiny")),
+	request_body(content = Vec<u8>, content_type = "application/octet-stream"),
+	responses(
+		(status = 200),
+		(status = 400),
+		(status = 404),
+	)
+)]
+async fn put_artwork(
+	_admin_rights: AdminRights,
+	State(artwork_manager): State<artwork::Manager>,
+	Path(path): Path<PathBuf>,
+	body: Bytes,
+) -> Result<(), APIError> {
+	artwork_manager.set_override(&path, body.to_vec()).await?;
+	Ok(())
+}
 
-	let range = range.map(|TypedHeader(r)| r);
-	Ok(Ranged::new(range, body))
+#[utoipa::path(
+	delete,
+	path = "/artwork/{*path}",
+	tag = "Media",
+	description = "Removes artwork uploaded for a collection directory, restoring the artwork found in the directory.",
+	security(
+		("auth_token" = []),
+		("auth_query_param" = []),
+	),
+	params(("path", allow_reserved, example = "my_music/stratovarius/destiny")),
+	responses(
+		(status = 200),
+		(status = 404),
+	)
+)]
+async fn delete_artwork(
+	_admin_rights: AdminRights,
+	State(artwork_manager): State<artwork::Manager>,
+	Path(path): Path<PathBuf>,
+) -> Result<(), APIError> {
+	artwork_manager.delete_override(&path).await?;
+	Ok(())
 }
@@ -0,0 +1,596 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use std::io::SeekFrom;
+use std::ops::{Range, RangeInclusive};
+use std::time::SystemTime;
+
+use axum::response::Response;
+use axum_extra::headers::ETag;
+use http::{header, HeaderMap, HeaderValue, StatusCode};
+use log::error;
+use ring::digest;
+use time::OffsetDateTime;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, DuplexStream};
+
+use crate::app::{config, index};
+
+use super::{conditional, range};
+
+const BUFFER_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Entry {
+	pub name: String,
+	pub real_path: PathBuf,
+}
+
+// Deepest directory containing all the given paths
+pub fn common_ancestor<'a>(mut paths: impl Iterator<Item = &'a Path>) -> PathBuf {
+	let Some(mut ancestor) = paths.next().and_then(|p| p.parent()).map(Path::to_owned) else {
+		return PathBuf::new();
+	};
+	for path in paths {
+		while !path.starts_with(&ancestor) {
+			if !ancestor.pop() {
+				break;
+			}
+		}
+	}
+	ancestor
+}
+
+// Zip entries are named after their location within the archived directory, which is itself
+// the top-level folder of the archive. Album art found in that directory is included.
+pub async fn list_entries(
+	config_manager: &config::Manager,
+	root: &Path,
+	songs: Vec<index::Song>,
+) -> Vec<Entry> {
+	let prefix = root.file_name().map(PathBuf::from).unwrap_or_default();
+
+	let name = |virtual_path: &Path| {
+		let relative = virtual_path.strip_prefix(root).ok()?;
+		let name = prefix
+			.join(relative)
+			.iter()
+			.map(|s| s.to_string_lossy())
+			.collect::<Vec<_>>()
+			.join("/");
+		Some(name)
+	};
+
+	let mut entries = vec![];
+	let mut seen = HashSet::new();
+	for song in &songs {
+		if let Some(name) = name(&song.virtual_path) {
+			seen.insert(song.virtual_path.clone());
+			entries.push(Entry {
+				name,
+				real_path: song.real_path.clone(),
+			});
+		}
+	}
+
+	for artwork in songs.iter().filter_map(|s| s.artwork.as_ref()) {
+		if !seen.insert(artwork.clone()) {
+			continue;
+		}
+		let (Some(name), Ok(real_path)) = (
+			name(artwork),
+			config_manager.resolve_virtual_path(artwork).await,
+		) else {
+			continue;
+		};
+		entries.push(Entry { name, real_path });
+	}
+
+	entries
+}
+
+// Zips are assembled from file sizes and modification dates alone, so their length and ETag are
+// known before any file is read. Audio files are already compressed, so entries are stored as-is.
+// Archives describe the sizes and CRC of their entries after the content, since CRCs are only
+// computed while the files are streamed.
+pub struct Zip {
+	entries: Vec<ZipEntry>,
+	crcs: Vec<Option<u32>>,
+	central_directory_offset: u64,
+	central_directory: Vec<u8>,
+	length: u64,
+}
+
+struct ZipEntry {
+	name: String,
+	real_path: PathBuf,
+	size: u64,
+	modified: (u16, u16),
+	offset: u64,
+	local_header: Vec<u8>,
+}
+
+impl ZipEntry {
+	fn zip64(&self) -> bool {
+		self.size >= u32::MAX as u64 || self.offset >= u32::MAX as u64
+	}
+
+	fn version(&self) -> u16 {
+		match self.zip64() {
+			true => ZIP64_VERSION,
+			false => ZIP_VERSION,
+		}
+	}
+
+	fn data_offset(&self) -> u64 {
+		self.offset + self.local_header.len() as u64
+	}
+
+	fn descriptor_offset(&self) -> u64 {
+		self.data_offset() + self.size
+	}
+
+	fn end(&self) -> u64 {
+		self.descriptor_offset() + self.descriptor_length()
+	}
+
+	fn descriptor_length(&self) -> u64 {
+		match self.zip64() {
+			true => 24,
+			false => 16,
+		}
+	}
+
+	fn make_local_header(&self) -> Vec<u8> {
+		let mut header = vec![];
+		put_u32(&mut header, LOCAL_HEADER_SIGNATURE);
+		put_u16(&mut header, self.version());
+		put_u16(&mut header, FLAGS);
+		put_u16(&mut header, 0);
+		put_u16(&mut header, self.modified.0);
+		put_u16(&mut header, self.modified.1);
+		put_u32(&mut header, 0);
+		let size = if self.zip64() { u32::MAX } else { 0 };
+		put_u32(&mut header, size);
+		put_u32(&mut header, size);
+		put_u16(&mut header, self.name.len() as u16);
+		put_u16(&mut header, if self.zip64() { 20 } else { 0 });
+		header.extend_from_slice(self.name.as_bytes());
+		if self.zip64() {
+			put_u16(&mut header, ZIP64_EXTRA_ID);
+			put_u16(&mut header, 16);
+			put_u64(&mut header, 0);
+			put_u64(&mut header, 0);
+		}
+		header
+	}
+
+	fn make_descriptor(&self, crc: u32) -> Vec<u8> {
+		let mut descriptor = vec![];
+		put_u32(&mut descriptor, DESCRIPTOR_SIGNATURE);
+		put_u32(&mut descriptor, crc);
+		if self.zip64() {
+			put_u64(&mut descriptor, self.size);
+			put_u64(&mut descriptor, self.size);
+		} else {
+			put_u32(&mut descriptor, self.size as u32);
+			put_u32(&mut descriptor, self.size as u32);
+		}
+		descriptor
+	}
+
+	fn write_central_header(&self, crc: u32, header: &mut Vec<u8>) {
+		let mut extra = vec![];
+		if self.zip64() {
+			put_u16(&mut extra, ZIP64_EXTRA_ID);
+			put_u16(&mut extra, 24);
+			put_u64(&mut extra, self.size);
+			put_u64(&mut extra, self.size);
+			put_u64(&mut extra, self.offset);
+		}
+		let (size, offset) = match self.zip64() {
+			true => (u32::MAX, u32::MAX),
+			false => (self.size as u32, self.offset as u32),
+		};
+		put_u32(header, CENTRAL_HEADER_SIGNATURE);
+		put_u16(header, self.version());
+		put_u16(header, self.version());
+		put_u16(header, FLAGS);
+		put_u16(header, 0);
+		put_u16(header, self.modified.0);
+		put_u16(header, self.modified.1);
+		put_u32(header, crc);
+		put_u32(header, size);
+		put_u32(header, size);
+		put_u16(header, self.name.len() as u16);
+		put_u16(header, extra.len() as u16);
+		put_u16(header, 0);
+		put_u16(header, 0);
+		put_u16(header, 0);
+		put_u32(header, 0);
+		put_u32(header, offset);
+		header.extend_from_slice(self.name.as_bytes());
+		header.extend_from_slice(&extra);
+	}
+
+	fn central_header_length(&self) -> u64 {
+		46 + self.name.len() as u64 + if self.zip64() { 28 } else { 0 }
+	}
+}
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x04034b50;
+const DESCRIPTOR_SIGNATURE: u32 = 0x08074b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x02014b50;
+const ZIP64_END_SIGNATURE: u32 = 0x06064b50;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x07064b50;
+const END_SIGNATURE: u32 = 0x06054b50;
+const ZIP64_EXTRA_ID: u16 = 0x0001;
+const ZIP_VERSION: u16 = 20;
+const ZIP64_VERSION: u16 = 45;
+// Sizes and CRC follow the content, names are UTF-8
+const FLAGS: u16 = 0x0008 | 0x0800;
+
+fn put_u16(buffer: &mut Vec<u8>, value: u16) {
+	buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buffer: &mut Vec<u8>, value: u32) {
+	buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(buffer: &mut Vec<u8>, value: u64) {
+	buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+// MS-DOS time and date, which cannot represent dates before 1980
+fn make_dos_date(modified: Option<SystemTime>) -> (u16, u16) {
+	let Some(date) = modified.map(OffsetDateTime::from) else {
+		return (0, 0x21);
+	};
+	if date.year() < 1980 {
+		return (0, 0x21);
+	}
+	let time =
+		((date.hour() as u16) << 11) | ((date.minute() as u16) << 5) | (date.second() as u16 / 2);
+	let day = (((date.year() - 1980).min(127) as u16) << 9)
+		| ((u8::from(date.month()) as u16) << 5)
+		| date.day() as u16;
+	(time, day)
+}
+
+impl Zip {
+	pub async fn new(entries: Vec<Entry>) -> Self {
+		let mut zip_entries: Vec<ZipEntry> = vec![];
+		let mut offset = 0;
+		for entry in entries {
+			let metadata = match tokio::fs::metadata(&entry.real_path).await {
+				Ok(m) if m.is_file() => m,
+				Ok(_) => continue,
+				Err(e) => {
+					error!("Could not add `{}` to zip: {e}", entry.real_path.display());
+					continue;
+				}
+			};
+			let mut zip_entry = ZipEntry {
+				name: entry.name,
+				real_path: entry.real_path,
+				size: metadata.len(),
+				modified: make_dos_date(metadata.modified().ok()),
+				offset,
+				local_header: vec![],
+			};
+			zip_entry.local_header = zip_entry.make_local_header();
+			offset = zip_entry.end();
+			zip_entries.push(zip_entry);
+		}
+
+		let central_directory_offset = offset;
+		let central_directory_length = zip_entries
+			.iter()
+			.map(ZipEntry::central_header_length)
+			.sum::<u64>();
+		let zip64 = zip_entries.len() >= u16::MAX as usize
+			|| central_directory_offset >= u32::MAX as u64
+			|| central_directory_length >= u32::MAX as u64;
+		let end_length = if zip64 { 56 + 20 + 22 } else { 22 };
+
+		Self {
+			crcs: vec![None; zip_entries.len()],
+			entries: zip_entries,
+			central_directory_offset,
+			central_directory: vec![],
+			length: central_directory_offset + central_directory_length + end_length,
+		}
+	}
+
+	pub fn length(&self) -> u64 {
+		self.length
+	}
+
+	// Changes whenever an entry is renamed, resized or modified
+	pub fn etag(&self) -> ETag {
+		let mut context = digest::Context::new(&digest::SHA256);
+		for entry in &self.entries {
+			context.update(entry.name.as_bytes());
+			context.update(&[0]);
+			context.update(&entry.size.to_le_bytes());
+			context.update(&entry.modified.0.to_le_bytes());
+			context.update(&entry.modified.1.to_le_bytes());
+		}
+		let digest = context.finish();
+		let hash = digest.as_ref()[..16]
+			.iter()
+			.map(|b| format!("{b:02x}"))
+			.collect::<String>();
+		format!("\"{hash}\"").parse().unwrap()
+	}
+
+	async fn get_crc(&mut self, index: usize) -> std::io::Result<u32> {
+		if let Some(crc) = self.crcs[index] {
+			return Ok(crc);
+		}
+		let entry = &self.entries[index];
+		let crc = copy_file(&entry.real_path, 0, entry.size, entry.size, None).await?;
+		self.crcs[index] = Some(crc);
+		Ok(crc)
+	}
+
+	async fn make_central_directory(&mut self) -> std::io::Result<Vec<u8>> {
+		let mut central_directory = vec![];
+		for index in 0..self.entries.len() {
+			let crc = self.get_crc(index).await?;
+			self.entries[index].write_central_header(crc, &mut central_directory);
+		}
+
+		let offset = self.central_directory_offset;
+		let length = central_directory.len() as u64;
+		let count = self.entries.len() as u64;
+		let zip64 =
+			count >= u16::MAX as u64 || offset >= u32::MAX as u64 || length >= u32::MAX as u64;
+		if zip64 {
+			put_u32(&mut central_directory, ZIP64_END_SIGNATURE);
+			put_u64(&mut central_directory, 44);
+			put_u16(&mut central_directory, ZIP64_VERSION);
+			put_u16(&mut central_directory, ZIP64_VERSION);
+			put_u32(&mut central_directory, 0);
+			put_u32(&mut central_directory, 0);
+			put_u64(&mut central_directory, count);
+			put_u64(&mut central_directory, count);
+			put_u64(&mut central_directory, length);
+			put_u64(&mut central_directory, offset);
+			put_u32(&mut central_directory, ZIP64_LOCATOR_SIGNATURE);
+			put_u32(&mut central_directory, 0);
+			put_u64(&mut central_directory, offset + length);
+			put_u32(&mut central_directory, 1);
+		}
+		put_u32(&mut central_directory, END_SIGNATURE);
+		put_u16(&mut central_directory, 0);
+		put_u16(&mut central_directory, 0);
+		put_u16(&mut central_directory, count.min(u16::MAX as u64) as u16);
+		put_u16(&mut central_directory, count.min(u16::MAX as u64) as u16);
+		put_u32(&mut central_directory, length.min(u32::MAX as u64) as u32);
+		put_u32(&mut central_directory, offset.min(u32::MAX as u64) as u32);
+		put_u16(&mut central_directory, 0);
+		Ok(central_directory)
+	}
+}
+
+// Writes the part of `bytes`, located at `offset` within the archive, which falls in `range`
+async fn write_slice(
+	bytes: &[u8],
+	offset: u64,
+	range: &RangeInclusive<u64>,
+	writer: &mut DuplexStream,
+) -> std::io::Result<()> {
+	if let Some(overlap) = overlap(offset, bytes.len() as u64, range) {
+		writer
+			.write_all(&bytes[overlap.start as usize..overlap.end as usize])
+			.await?;
+	}
+	Ok(())
+}
+
+// Part of the span starting at `offset`, relative to `offset`, which falls in `range`
+fn overlap(offset: u64, length: u64, range: &RangeInclusive<u64>) -> Option<Range<u64>> {
+	let start = (*range.start()).max(offset);
+	let end = range.end().saturating_add(1).min(offset + length);
+	(start < end).then(|| start - offset..end - offset)
+}
+
+// Copies part of a file while computing its CRC, or only computes the CRC without a writer
+async fn copy_file(
+	path: &Path,
+	start: u64,
+	length: u64,
+	expected_size: u64,
+	mut writer: Option<&mut DuplexStream>,
+) -> std::io::Result<u32> {
+	let mut file = tokio::fs::File::open(path).await?;
+	if file.metadata().await?.len() != expected_size {
+		return Err(std::io::Error::other(format!(
+			"`{}` changed while being archived",
+			path.display()
+		)));
+	}
+	file.seek(SeekFrom::Start(start)).await?;
+	let mut hasher = crc32fast::Hasher::new();
+	let mut buffer = vec![0; BUFFER_SIZE];
+	let mut remaining = length;
+	while remaining > 0 {
+		let chunk = &mut buffer[..remaining.min(BUFFER_SIZE as u64) as usize];
+		let read = file.read(chunk).await?;
+		if read == 0 {
+			return Err(std::io::ErrorKind::UnexpectedEof.into());
+		}
+		hasher.update(&chunk[..read]);
+		if let Some(writer) = writer.as_mut() {
+			writer.write_all(&chunk[..read]).await?;
+		}
+		remaining -= read as u64;
+	}
+	Ok(hasher.finalize())
+}
+
+impl range::Source for Zip {
+	async fn write_range(
+		&mut self,
+		range: RangeInclusive<u64>,
+		writer: &mut DuplexStream,
+	) -> std::io::Result<()> {
+		for index in 0..self.entries.len() {
+			let entry = &self.entries[index];
+			if entry.offset > *range.end() {
+				return Ok(());
+			}
+			if entry.end() <= *range.start() {
+				continue;
+			}
+
+			write_slice(&entry.local_header, entry.offset, &range, writer).await?;
+
+			if let Some(overlap) = overlap(entry.data_offset(), entry.size, &range) {
+				let whole_file = overlap.start == 0 && overlap.end == entry.size;
+				let crc = copy_file(
+					&entry.real_path,
+					overlap.start,
+					overlap.end - overlap.start,
+					entry.size,
+					Some(&mut *writer),
+				)
+				.await?;
+				if whole_file {
+					self.crcs[index] = Some(crc);
+				}
+			}
+
+			let entry = &self.entries[index];
+			if overlap(entry.descriptor_offset(), entry.descriptor_length(), &range).is_some() {
+				let crc = self.get_crc(index).await?;
+				let entry = &self.entries[index];
+				let descriptor = entry.make_descriptor(crc);
+				write_slice(&descriptor, entry.descriptor_offset(), &range, writer).await?;
+			}
+		}
+
+		if *range.end() >= self.central_directory_offset {
+			if self.central_directory.is_empty() {
+				self.central_directory = self.make_central_directory().await?;
+			}
+			let offset = self.central_directory_offset;
+			write_slice(&self.central_directory, offset, &range, writer).await?;
+		}
+
+		Ok(())
+	}
+}
+
+// Serves a zip archive while it is being written, so large directories can be downloaded
+// without holding the archive in memory or on disk. Archives can be cached and interrupted
+// downloads resumed like regular files.
+pub async fn serve_zip(
+	entries: Vec<Entry>,
+	file_name: &str,
+	request_headers: &HeaderMap,
+) -> Response {
+	let zip = Zip::new(entries).await;
+	let length = zip.length();
+	let etag = zip.etag();
+	let mut response =
+		conditional::serve_generated_download(zip, length, etag, file_name, request_headers).await;
+	// Multipart range responses carry their own content type
+	if response.status() != StatusCode::NOT_MODIFIED {
+		response
+			.headers_mut()
+			.entry(header::CONTENT_TYPE)
+			.or_insert(HeaderValue::from_static("application/zip"));
+	}
+	response
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::server::axum::range::Source;
+
+	async fn read_range(zip: &mut Zip, range: RangeInclusive<u64>) -> Vec<u8> {
+		let (mut writer, mut reader) = tokio::io::duplex(1024);
+		let mut bytes = vec![];
+		let (_, read) = tokio::join!(
+			async {
+				zip.write_range(range, &mut writer).await.unwrap();
+				drop(writer);
+			},
+			reader.read_to_end(&mut bytes)
+		);
+		read.unwrap();
+		bytes
+	}
+
+	async fn make_zip() -> Zip {
+		let directory = Path::new("test-data/small-collection/Khemmis/Hunted");
+		let entries = [
+			"01 - Above The Water.mp3",
+			"02 - Candlelight.mp3",
+			"Folder.jpg",
+		]
+		.into_iter()
+		.map(|name| Entry {
+			name: format!("Hunted/{name}"),
+			real_path: directory.join(name),
+		})
+		.collect();
+		Zip::new(entries).await
+	}
+
+	#[tokio::test]
+	async fn zip_length_is_known_in_advance() {
+		let mut zip = make_zip().await;
+		let length = zip.length();
+		let bytes = read_range(&mut zip, 0..=length - 1).await;
+		assert_eq!(bytes.len() as u64, length);
+		assert!(bytes.starts_with(b"PK\x03\x04"));
+		assert!(bytes[bytes.len() - 22..].starts_with(b"PK\x05\x06"));
+	}
+
+	#[tokio::test]
+	async fn zip_ranges_match_full_archive() {
+		let mut zip = make_zip().await;
+		let length = zip.length();
+		let full = read_range(&mut zip, 0..=length - 1).await;
+
+		// Start from scratch so CRCs are not known yet
+		let mut zip = make_zip().await;
+		for range in [
+			0..=10,
+			50..=5000,
+			length - 100..=length - 1,
+			1000..=length - 1,
+		] {
+			let bytes = read_range(&mut zip, range.clone()).await;
+			let expected = &full[*range.start() as usize..=*range.end() as usize];
+			assert_eq!(bytes, expected);
+		}
+	}
+
+	#[tokio::test]
+	async fn zip_etag_is_deterministic() {
+		assert_eq!(make_zip().await.etag(), make_zip().await.etag());
+	}
+
+	#[test]
+	fn can_find_common_ancestor() {
+		let paths = [
+			Path::new("root/Tobokegao/Picnic/CD 1/01.mp3"),
+			Path::new("root/Tobokegao/Picnic/CD 2/01.mp3"),
+			Path::new("root/Tobokegao/Picnic/CD 1/02.mp3"),
+		];
+		assert_eq!(
+			common_ancestor(paths.into_iter()),
+			PathBuf::from("root/Tobokegao/Picnic")
+		);
+		assert_eq!(
+			common_ancestor([Path::new("root/Khemmis/Hunted/01.mp3")].into_iter()),
+			PathBuf::from("root/Khemmis/Hunted")
+		);
+		assert_eq!(common_ancestor(std::iter::empty()), PathBuf::new());
+	}
+}
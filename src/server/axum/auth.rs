@@ -1,32 +1,95 @@
+use std::marker::PhantomData;
+
 use axum::extract::{FromRef, FromRequestParts, Query};
 use headers::authorization::{Bearer, Credentials};
-use http::request::Parts;
+use http::{request::Parts, Method};
 
 use crate::{
-	app::{auth, config},
-	server::{dto, error::APIError},
+	app::{api_key, auth, config, session},
+	server::{
+		axum::{forwarded::ClientIp, logger},
+		dto,
+		error::APIError,
+	},
 };
 
 #[derive(Debug)]
 pub struct Auth {
 	username: String,
+	session_id: Option<String>,
+	/// Scopes granted by the API key used to authenticate, if any
+	api_key_scopes: Option<Vec<api_key::Scope>>,
 }
 
 impl Auth {
 	pub fn get_username(&self) -> &String {
 		&self.username
 	}
+
+	pub fn get_session_id(&self) -> Option<&str> {
+		self.session_id.as_deref()
+	}
+
+	pub fn is_api_key(&self) -> bool {
+		self.api_key_scopes.is_some()
+	}
+
+	// Requests authenticated with login tokens are not restricted by scopes
+	pub fn allows(&self, scope: api_key::Scope) -> bool {
+		self.api_key_scopes
+			.as_ref()
+			.is_none_or(|scopes| scopes.contains(&scope))
+	}
 }
 
+// Guests can only send requests which do not modify anything
 impl<S> FromRequestParts<S> for Auth
 where
+	api_key::Manager: FromRef<S>,
 	config::Manager: FromRef<S>,
+	session::Manager: FromRef<S>,
 	S: Send + Sync,
 {
 	type Rejection = APIError;
 
 	async fn from_request_parts(parts: &mut Parts, app: &S) -> Result<Self, Self::Rejection> {
-		let config_manager = config::Manager::from_ref(app);
+		let auth = Auth::authenticate(parts, app).await?;
+		if !matches!(parts.method, Method::GET | Method::HEAD) {
+			let config_manager = config::Manager::from_ref(app);
+			if config_manager.get_user(&auth.username).await?.is_guest() {
+				return Err(APIError::GuestPermissionDenied);
+			}
+		}
+		Ok(auth)
+	}
+}
+
+// Authenticates requests managing the sessions of the caller, which guests are allowed to send
+#[derive(Debug)]
+pub struct SessionAuth(pub Auth);
+
+impl<S> FromRequestParts<S> for SessionAuth
+where
+	api_key::Manager: FromRef<S>,
+	session::Manager: FromRef<S>,
+	S: Send + Sync,
+{
+	type Rejection = APIError;
+
+	async fn from_request_parts(parts: &mut Parts, app: &S) -> Result<Self, Self::Rejection> {
+		Ok(SessionAuth(Auth::authenticate(parts, app).await?))
+	}
+}
+
+impl Auth {
+	async fn authenticate<S>(parts: &mut Parts, app: &S) -> Result<Self, APIError>
+	where
+		api_key::Manager: FromRef<S>,
+		session::Manager: FromRef<S>,
+		S: Send + Sync,
+	{
+		let api_key_manager = api_key::Manager::from_ref(app);
+		let session_manager = session::Manager::from_ref(app);
 
 		let header_token = parts
 			.headers
@@ -42,12 +105,34 @@ where
 			return Err(APIError::AuthenticationRequired);
 		};
 
-		let authorization = config_manager
-			.authenticate(&auth::Token(token), auth::Scope::PolarisAuth)
+		if token.starts_with(api_key::KEY_PREFIX) {
+			let api_key = api_key_manager.authenticate(&token).await?;
+			let required_scope = match parts.method {
+				Method::GET | Method::HEAD => api_key::Scope::Read,
+				_ => api_key::Scope::Write,
+			};
+			if !api_key.scopes.contains(&required_scope) {
+				return Err(APIError::ApiKeyScopeRequired);
+			}
+			logger::set_current_user(&api_key.username);
+			return Ok(Auth {
+				username: api_key.username,
+				session_id: None,
+				api_key_scopes: Some(api_key.scopes),
+			});
+		}
+
+		let client_ip = parts.extensions.get::<ClientIp>().map(|c| c.0);
+		let authorization = session_manager
+			.authenticate(&auth::Token(token), auth::Scope::PolarisAuth, client_ip)
 			.await?;
 
+		logger::set_current_user(&authorization.username);
+
 		Ok(Auth {
 			username: authorization.username,
+			session_id: authorization.session_id,
+			api_key_scopes: None,
 		})
 	}
 }
@@ -65,7 +150,9 @@ impl AdminRights {
 
 impl<S> FromRequestParts<S> for AdminRights
 where
+	api_key::Manager: FromRef<S>,
 	config::Manager: FromRef<S>,
+	session::Manager: FromRef<S>,
 	S: Send + Sync,
 {
 	type Rejection = APIError;
@@ -79,6 +166,9 @@ where
 		}
 
 		let auth = Auth::from_request_parts(parts, app).await?;
+		if !auth.allows(api_key::Scope::Admin) {
+			return Err(APIError::ApiKeyScopeRequired);
+		}
 		if config_manager.get_user(&auth.username).await?.is_admin() {
 			Ok(AdminRights { auth: Some(auth) })
 		} else {
@@ -86,3 +176,94 @@ where
 		}
 	}
 }
+
+// Marks endpoints restricted to users holding a permission, see `Permitted`
+pub trait RequiredPermission {
+	const PERMISSION: auth::Permission;
+}
+
+pub mod permission {
+	use super::*;
+
+	pub struct ManageUsers;
+	pub struct ManageMounts;
+	pub struct TriggerScan;
+	pub struct Download;
+	pub struct Share;
+
+	impl RequiredPermission for ManageUsers {
+		const PERMISSION: auth::Permission = auth::Permission::ManageUsers;
+	}
+
+	impl RequiredPermission for ManageMounts {
+		const PERMISSION: auth::Permission = auth::Permission::ManageMounts;
+	}
+
+	impl RequiredPermission for TriggerScan {
+		const PERMISSION: auth::Permission = auth::Permission::TriggerScan;
+	}
+
+	impl RequiredPermission for Download {
+		const PERMISSION: auth::Permission = auth::Permission::Download;
+	}
+
+	impl RequiredPermission for Share {
+		const PERMISSION: auth::Permission = auth::Permission::Share;
+	}
+}
+
+// Like `AdminRights`, for users who were delegated a single responsibility
+#[derive(Debug)]
+pub struct Permitted<P> {
+	auth: Option<Auth>,
+	is_admin: bool,
+	permission: PhantomData<P>,
+}
+
+impl<P> Permitted<P> {
+	pub fn get_auth(&self) -> &Option<Auth> {
+		&self.auth
+	}
+
+	pub fn is_admin(&self) -> bool {
+		self.is_admin
+	}
+}
+
+impl<S, P> FromRequestParts<S> for Permitted<P>
+where
+	api_key::Manager: FromRef<S>,
+	config::Manager: FromRef<S>,
+	session::Manager: FromRef<S>,
+	S: Send + Sync,
+	P: RequiredPermission,
+{
+	type Rejection = APIError;
+
+	async fn from_request_parts(parts: &mut Parts, app: &S) -> Result<Self, Self::Rejection> {
+		let config_manager = config::Manager::from_ref(app);
+		let administrative = P::PERMISSION.is_administrative();
+
+		if administrative && config_manager.get_users().await.is_empty() {
+			return Ok(Permitted {
+				auth: None,
+				is_admin: true,
+				permission: PhantomData,
+			});
+		}
+
+		let auth = Auth::from_request_parts(parts, app).await?;
+		if administrative && !auth.allows(api_key::Scope::Admin) {
+			return Err(APIError::ApiKeyScopeRequired);
+		}
+		let user = config_manager.get_user(&auth.username).await?;
+		if !user.has_permission(P::PERMISSION) {
+			return Err(APIError::PermissionRequired);
+		}
+		Ok(Permitted {
+			auth: Some(auth),
+			is_admin: user.is_admin(),
+			permission: PhantomData,
+		})
+	}
+}
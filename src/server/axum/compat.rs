@@ -0,0 +1,111 @@
+use axum::{extract::Request, response::Response, Extension, Router};
+use http::{header, HeaderName, HeaderValue};
+use std::{
+	future::Future,
+	pin::Pin,
+	task::{Context, Poll},
+};
+use tower::{Layer, Service};
+
+use crate::app::App;
+use crate::server::APIMajorVersion;
+
+use super::version::{self, PinnedVersion};
+
+const DEPRECATION: HeaderName = HeaderName::from_static("deprecation");
+
+// Newer API version which clients of a deprecated version should migrate to.
+// Deprecated versions keep being served until they are removed from `APIMajorVersion`.
+fn successor(version: APIMajorVersion) -> Option<APIMajorVersion> {
+	match version {
+		APIMajorVersion::V7 => Some(APIMajorVersion::V8),
+		APIMajorVersion::V8 => None,
+	}
+}
+
+// Serves the API under a versioned prefix like `/api/v7`, regardless of the `Accept-Version` header
+pub fn pinned(
+	router: Router<App>,
+	version: APIMajorVersion,
+	base_path: &Option<String>,
+) -> Router<App> {
+	router
+		.layer(DeprecationLayer::new(base_path))
+		.layer(Extension(PinnedVersion(version)))
+}
+
+// Serves the API without a version prefix, for clients predating versioned paths
+pub fn unversioned(router: Router<App>, base_path: &Option<String>) -> Router<App> {
+	router.layer(DeprecationLayer::new(base_path))
+}
+
+// Marks responses to requests made against a deprecated API version with a `Deprecation` header,
+// and a `Link` header pointing to the version clients should migrate to.
+#[derive(Clone)]
+pub struct DeprecationLayer {
+	base_path: String,
+}
+
+impl DeprecationLayer {
+	pub fn new(base_path: &Option<String>) -> Self {
+		Self {
+			base_path: base_path.clone().unwrap_or_default(),
+		}
+	}
+}
+
+impl<S> Layer<S> for DeprecationLayer {
+	type Service = DeprecationMiddleware<S>;
+
+	fn layer(&self, inner: S) -> Self::Service {
+		DeprecationMiddleware {
+			inner,
+			base_path: self.base_path.clone(),
+		}
+	}
+}
+
+#[derive(Clone)]
+pub struct DeprecationMiddleware<S> {
+	inner: S,
+	base_path: String,
+}
+
+impl<S> Service<Request> for DeprecationMiddleware<S>
+where
+	S: Service<Request, Response = Response> + Send + 'static,
+	S::Future: Send + 'static,
+{
+	type Response = S::Response;
+	type Error = S::Error;
+	type Future =
+		Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send + 'static>>;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		self.inner.poll_ready(cx)
+	}
+
+	fn call(&mut self, request: Request) -> Self::Future {
+		let successor = version::requested_version(request.extensions(), request.headers())
+			.ok()
+			.and_then(successor);
+		let link = successor.and_then(|v| {
+			let link = format!(
+				"<{}/api/v{}>; rel=\"successor-version\"",
+				self.base_path,
+				i32::from(v)
+			);
+			HeaderValue::from_str(&link).ok()
+		});
+		let future = self.inner.call(request);
+		Box::pin(async move {
+			let mut response = future.await?;
+			if let Some(link) = link {
+				let headers = response.headers_mut();
+				headers.insert(DEPRECATION, HeaderValue::from_static("true"));
+				headers.insert(header::LINK, link);
+			}
+			Ok(response)
+		})
+	}
+}
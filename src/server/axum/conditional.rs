@@ -0,0 +1,220 @@
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::body::Body;
+use axum::response::{IntoResponse, Response};
+use axum_extra::headers::{
+	ETag, HeaderMapExt, IfModifiedSince, IfNoneMatch, IfRange, LastModified,
+};
+use http::{header, HeaderMap, HeaderValue, StatusCode};
+use tokio::task::spawn_blocking;
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::app::{config, remote, Error};
+
+use super::range;
+
+// Serves a file along with its cache validators. Responds with `304 Not Modified`
+// when the copy held by the client is still current.
+pub async fn serve_file(
+	file: tokio::fs::File,
+	request_headers: &HeaderMap,
+) -> std::io::Result<Response> {
+	let metadata = file.metadata().await?;
+	let modified = metadata.modified().ok();
+	let etag = modified.and_then(|m| make_etag(metadata.len(), m));
+	let headers = make_validator_headers(etag.as_ref(), modified);
+
+	Ok(match evaluate(etag.as_ref(), modified, request_headers) {
+		Precondition::NotModified => (StatusCode::NOT_MODIFIED, headers).into_response(),
+		Precondition::IgnoreRange => {
+			let request_headers = without_range(request_headers);
+			range::respond(file, metadata.len(), &request_headers, headers).await?
+		}
+		Precondition::HonorRange => {
+			range::respond(file, metadata.len(), request_headers, headers).await?
+		}
+	})
+}
+
+// Serves content generated on the fly, such as an archive, meant to be saved by the client.
+// Its length and validators must be known in advance, so downloads can be resumed.
+pub async fn serve_generated_download<S: range::Source>(
+	source: S,
+	length: u64,
+	etag: ETag,
+	file_name: &str,
+	request_headers: &HeaderMap,
+) -> Response {
+	let mut headers = make_validator_headers(Some(&etag), None);
+	let precondition = evaluate(Some(&etag), None, request_headers);
+	if precondition == Precondition::NotModified {
+		return (StatusCode::NOT_MODIFIED, headers).into_response();
+	}
+	if let Ok(value) = HeaderValue::from_str(&make_content_disposition(file_name)) {
+		headers.insert(header::CONTENT_DISPOSITION, value);
+	}
+	match precondition {
+		Precondition::IgnoreRange => {
+			range::respond_streamed(source, length, &without_range(request_headers), headers)
+		}
+		_ => range::respond_streamed(source, length, request_headers, headers),
+	}
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Precondition {
+	NotModified,
+	// The content changed since the client started downloading it
+	IgnoreRange,
+	HonorRange,
+}
+
+fn evaluate(
+	etag: Option<&ETag>,
+	modified: Option<SystemTime>,
+	request_headers: &HeaderMap,
+) -> Precondition {
+	// If-None-Match takes precedence over If-Modified-Since (RFC 9110 §13.2.2)
+	let not_modified = if request_headers.contains_key(header::IF_NONE_MATCH) {
+		let if_none_match = request_headers.typed_get::<IfNoneMatch>();
+		etag.zip(if_none_match)
+			.is_some_and(|(e, i)| !i.precondition_passes(e))
+	} else if request_headers.contains_key(header::IF_MODIFIED_SINCE) {
+		let if_modified_since = request_headers.typed_get::<IfModifiedSince>();
+		modified
+			.zip(if_modified_since)
+			.is_some_and(|(m, i)| !i.is_modified(m))
+	} else {
+		false
+	};
+	if not_modified {
+		return Precondition::NotModified;
+	}
+
+	// Ranges are only honored if the file did not change since the client started downloading it
+	let last_modified = modified.map(LastModified::from);
+	let if_range = request_headers.typed_get::<IfRange>();
+	match if_range.is_some_and(|i| i.is_modified(etag, last_modified.as_ref())) {
+		true => Precondition::IgnoreRange,
+		false => Precondition::HonorRange,
+	}
+}
+
+fn make_validator_headers(etag: Option<&ETag>, modified: Option<SystemTime>) -> HeaderMap {
+	let mut headers = HeaderMap::new();
+	if let Some(etag) = etag {
+		headers.typed_insert(etag.clone());
+	}
+	if let Some(modified) = modified {
+		headers.typed_insert(LastModified::from(modified));
+	}
+	headers
+}
+
+fn without_range(request_headers: &HeaderMap) -> HeaderMap {
+	let mut request_headers = request_headers.clone();
+	request_headers.remove(header::RANGE);
+	request_headers
+}
+
+// Serves a file meant to be saved by the client rather than displayed or played.
+// Interrupted downloads can be resumed using range requests along with `If-Range`.
+pub async fn serve_download(
+	file: tokio::fs::File,
+	file_name: &str,
+	request_headers: &HeaderMap,
+) -> std::io::Result<Response> {
+	let mut response = serve_file(file, request_headers).await?;
+	if let Ok(value) = HeaderValue::from_str(&make_content_disposition(file_name)) {
+		response
+			.headers_mut()
+			.insert(header::CONTENT_DISPOSITION, value);
+	}
+	Ok(response)
+}
+
+// Relays a file from a remote server, which handles range and conditional requests itself
+pub async fn serve_remote(
+	remote: config::Remote,
+	real_path: PathBuf,
+	download_name: Option<&str>,
+	request_headers: &HeaderMap,
+) -> Result<Response, Error> {
+	let relayed_headers = remote::RELAYED_REQUEST_HEADERS
+		.iter()
+		.filter_map(|&name| Some((name, request_headers.get(name)?.to_str().ok()?.to_owned())))
+		.collect::<Vec<_>>();
+
+	let remote_response = spawn_blocking(move || {
+		let relayed_headers = relayed_headers
+			.iter()
+			.map(|(name, value)| (*name, value.as_str()))
+			.collect::<Vec<_>>();
+		remote::open(&remote, &real_path, &relayed_headers)
+	})
+	.await
+	.unwrap()?;
+
+	let status = StatusCode::from_u16(remote_response.status).unwrap_or(StatusCode::BAD_GATEWAY);
+	let mut headers = HeaderMap::new();
+	for (name, value) in &remote_response.headers {
+		if let Ok(value) = HeaderValue::from_str(value) {
+			headers.insert(*name, value);
+		}
+	}
+	if let Some(value) =
+		download_name.and_then(|n| HeaderValue::from_str(&make_content_disposition(n)).ok())
+	{
+		headers.insert(header::CONTENT_DISPOSITION, value);
+	}
+
+	let body = Body::from_stream(ReceiverStream::new(remote_response.into_chunks()));
+	Ok((status, headers, body).into_response())
+}
+
+// Follows RFC 6266, with an ASCII fallback for clients which do not support `filename*`
+pub fn make_content_disposition(file_name: &str) -> String {
+	let fallback = file_name
+		.chars()
+		.map(|c| match c {
+			' '..='~' if c != '"' && c != '\\' => c,
+			_ => '_',
+		})
+		.collect::<String>();
+
+	let mut encoded = String::new();
+	for byte in file_name.bytes() {
+		match byte {
+			b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' => encoded.push(byte as char),
+			b'!' | b'#' | b'$' | b'&' | b'+' | b'-' | b'.' | b'^' | b'_' | b'`' | b'|' | b'~' => {
+				encoded.push(byte as char)
+			}
+			_ => encoded.push_str(&format!("%{byte:02X}")),
+		}
+	}
+
+	format!("attachment; filename=\"{fallback}\"; filename*=UTF-8''{encoded}")
+}
+
+fn make_etag(length: u64, modified: SystemTime) -> Option<ETag> {
+	let modified = modified.duration_since(UNIX_EPOCH).ok()?.as_nanos();
+	format!("\"{length:x}-{modified:x}\"").parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn can_make_content_disposition() {
+		assert_eq!(
+			make_content_disposition("Hunted.mp3"),
+			"attachment; filename=\"Hunted.mp3\"; filename*=UTF-8''Hunted.mp3"
+		);
+		assert_eq!(
+			make_content_disposition("Sigur Rós \"Live\".zip"),
+			"attachment; filename=\"Sigur R_s _Live_.zip\"; filename*=UTF-8''Sigur%20R%C3%B3s%20%22Live%22.zip"
+		);
+	}
+}
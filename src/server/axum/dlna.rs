@@ -0,0 +1,693 @@
+use std::{
+	fmt::Write,
+	path::{Component, Path, PathBuf},
+};
+
+use axum::{
+	extract::{self, FromRef, FromRequestParts, State},
+	response::{IntoResponse, Response},
+	routing::{get, post},
+	Extension, Router,
+};
+use http::{header, request::Parts, HeaderMap, HeaderName, HeaderValue, StatusCode, Uri};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+
+use crate::app::{
+	auth, config,
+	dlna::{self, CONNECTION_MANAGER, CONTENT_DIRECTORY, DEVICE_TYPE},
+	index, App,
+};
+use crate::utils::get_audio_format;
+
+use super::{
+	conditional,
+	forwarded::{get_base_url, is_local, ClientIp},
+	limits,
+};
+
+const SOAP_ACTION: HeaderName = HeaderName::from_static("soapaction");
+const TRANSFER_MODE: HeaderName = HeaderName::from_static("transfermode.dlna.org");
+const CONTENT_FEATURES: HeaderName = HeaderName::from_static("contentfeatures.dlna.org");
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+const X_REAL_IP: HeaderName = HeaderName::from_static("x-real-ip");
+
+// UPnP MediaServer endpoints, for DLNA devices found through `app::dlna` announcements
+pub fn router(stream_limiter: limits::StreamLimiter) -> Router<App> {
+	Router::new()
+		.route("/description.xml", get(get_description))
+		.route("/content_directory.xml", get(get_content_directory))
+		.route("/connection_manager.xml", get(get_connection_manager))
+		.route("/control/content_directory", post(post_content_directory))
+		.route("/control/connection_manager", post(post_connection_manager))
+		.route("/media/{*path}", get(get_media))
+		.layer(Extension(stream_limiter))
+}
+
+// DLNA devices cannot authenticate, so these endpoints are only available when DLNA is enabled,
+// and only to clients on the local network. Requests relayed by a reverse proxy are refused, as
+// the proxy itself usually is on the local network even when clients are not.
+pub struct LocalNetwork(config::Dlna);
+
+impl<S> FromRequestParts<S> for LocalNetwork
+where
+	dlna::Manager: FromRef<S>,
+	S: Send + Sync,
+{
+	type Rejection = StatusCode;
+
+	async fn from_request_parts(parts: &mut Parts, app: &S) -> Result<Self, Self::Rejection> {
+		let dlna_manager = dlna::Manager::from_ref(app);
+		let Some(dlna) = dlna_manager.get_dlna().await else {
+			return Err(StatusCode::NOT_FOUND);
+		};
+		let is_proxied = [header::FORWARDED, X_FORWARDED_FOR, X_REAL_IP]
+			.iter()
+			.any(|h| parts.headers.contains_key(h));
+		if is_proxied {
+			return Err(StatusCode::FORBIDDEN);
+		}
+		// Requests which did not come through a network connection (eg. tests) have no address
+		match parts.extensions.get::<ClientIp>() {
+			Some(ClientIp(ip)) if !is_local(*ip) => Err(StatusCode::FORBIDDEN),
+			_ => Ok(LocalNetwork(dlna)),
+		}
+	}
+}
+
+fn xml(body: String) -> Response {
+	(
+		[(
+			header::CONTENT_TYPE,
+			HeaderValue::from_static("text/xml; charset=\"utf-8\""),
+		)],
+		body,
+	)
+		.into_response()
+}
+
+fn escape(text: &str) -> String {
+	text.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+		.replace('"', "&quot;")
+		.replace('\'', "&apos;")
+}
+
+fn unescape(text: &str) -> String {
+	text.replace("&lt;", "<")
+		.replace("&gt;", ">")
+		.replace("&quot;", "\"")
+		.replace("&apos;", "'")
+		.replace("&amp;", "&")
+}
+
+async fn get_description(
+	LocalNetwork(dlna): LocalNetwork,
+	State(dlna_manager): State<dlna::Manager>,
+	State(config_manager): State<config::Manager>,
+) -> Response {
+	let base_path = config_manager.get_base_path().await.unwrap_or_default();
+	// No state variable is evented, so there is nothing to subscribe to
+	let service = |service_type: &str, name: &str| {
+		let id = service_type.split(':').nth(3).unwrap_or_default();
+		format!(
+			"<service><serviceType>{service_type}</serviceType><serviceId>urn:upnp-org:serviceId:{id}</serviceId><SCPDURL>{base_path}/dlna/{name}.xml</SCPDURL><controlURL>{base_path}/dlna/control/{name}</controlURL><eventSubURL></eventSubURL></service>"
+		)
+	};
+	xml(format!(
+		r#"<?xml version="1.0" encoding="utf-8"?><root xmlns="urn:schemas-upnp-org:device-1-0"><specVersion><major>1</major><minor>0</minor></specVersion><device><deviceType>{DEVICE_TYPE}</deviceType><friendlyName>{}</friendlyName><manufacturer>Polaris</manufacturer><manufacturerURL>https://github.com/agersant/polaris</manufacturerURL><modelName>Polaris</modelName><modelNumber>{}</modelNumber><UDN>uuid:{}</UDN><serviceList>{}{}</serviceList></device></root>"#,
+		escape(&dlna.friendly_name),
+		env!("CARGO_PKG_VERSION"),
+		dlna_manager.get_device_id(),
+		service(CONTENT_DIRECTORY, "content_directory"),
+		service(CONNECTION_MANAGER, "connection_manager"),
+	))
+}
+
+type Argument<'a> = (&'a str, &'a str, &'a str);
+
+// Service description, listing actions along with their (name, direction, state variable) arguments
+fn make_scpd(actions: &[(&str, &[Argument])], variables: &[(&str, &str)]) -> String {
+	let mut scpd = String::from(
+		r#"<?xml version="1.0" encoding="utf-8"?><scpd xmlns="urn:schemas-upnp-org:service-1-0"><specVersion><major>1</major><minor>0</minor></specVersion><actionList>"#,
+	);
+	for (action, arguments) in actions {
+		write!(scpd, "<action><name>{action}</name><argumentList>").ok();
+		for (name, direction, variable) in *arguments {
+			write!(scpd, "<argument><name>{name}</name><direction>{direction}</direction><relatedStateVariable>{variable}</relatedStateVariable></argument>").ok();
+		}
+		scpd.push_str("</argumentList></action>");
+	}
+	scpd.push_str("</actionList><serviceStateTable>");
+	for (name, data_type) in variables {
+		write!(scpd, r#"<stateVariable sendEvents="no"><name>{name}</name><dataType>{data_type}</dataType></stateVariable>"#).ok();
+	}
+	scpd.push_str("</serviceStateTable></scpd>");
+	scpd
+}
+
+async fn get_content_directory(_local_network: LocalNetwork) -> Response {
+	xml(make_scpd(
+		&[
+			(
+				"Browse",
+				&[
+					("ObjectID", "in", "A_ARG_TYPE_ObjectID"),
+					("BrowseFlag", "in", "A_ARG_TYPE_BrowseFlag"),
+					("Filter", "in", "A_ARG_TYPE_Filter"),
+					("StartingIndex", "in", "A_ARG_TYPE_Index"),
+					("RequestedCount", "in", "A_ARG_TYPE_Count"),
+					("SortCriteria", "in", "A_ARG_TYPE_SortCriteria"),
+					("Result", "out", "A_ARG_TYPE_Result"),
+					("NumberReturned", "out", "A_ARG_TYPE_Count"),
+					("TotalMatches", "out", "A_ARG_TYPE_Count"),
+					("UpdateID", "out", "A_ARG_TYPE_UpdateID"),
+				],
+			),
+			(
+				"GetSearchCapabilities",
+				&[("SearchCaps", "out", "SearchCapabilities")],
+			),
+			(
+				"GetSortCapabilities",
+				&[("SortCaps", "out", "SortCapabilities")],
+			),
+			("GetSystemUpdateID", &[("Id", "out", "SystemUpdateID")]),
+		],
+		&[
+			("A_ARG_TYPE_ObjectID", "string"),
+			("A_ARG_TYPE_BrowseFlag", "string"),
+			("A_ARG_TYPE_Filter", "string"),
+			("A_ARG_TYPE_Index", "ui4"),
+			("A_ARG_TYPE_Count", "ui4"),
+			("A_ARG_TYPE_SortCriteria", "string"),
+			("A_ARG_TYPE_Result", "string"),
+			("A_ARG_TYPE_UpdateID", "ui4"),
+			("SearchCapabilities", "string"),
+			("SortCapabilities", "string"),
+			("SystemUpdateID", "ui4"),
+		],
+	))
+}
+
+async fn get_connection_manager(_local_network: LocalNetwork) -> Response {
+	xml(make_scpd(
+		&[
+			(
+				"GetProtocolInfo",
+				&[
+					("Source", "out", "SourceProtocolInfo"),
+					("Sink", "out", "SinkProtocolInfo"),
+				],
+			),
+			(
+				"GetCurrentConnectionIDs",
+				&[("ConnectionIDs", "out", "CurrentConnectionIDs")],
+			),
+		],
+		&[
+			("SourceProtocolInfo", "string"),
+			("SinkProtocolInfo", "string"),
+			("CurrentConnectionIDs", "string"),
+		],
+	))
+}
+
+// Reads the value of an argument from a SOAP request
+fn get_soap_argument(body: &str, name: &str) -> Option<String> {
+	if body.contains(&format!("<{name}/>")) {
+		return Some(String::new());
+	}
+	let start = body.find(&format!("<{name}>"))? + name.len() + 2;
+	let end = start + body[start..].find(&format!("</{name}>"))?;
+	Some(unescape(&body[start..end]))
+}
+
+fn get_soap_action(headers: &HeaderMap) -> Option<String> {
+	let value = headers.get(SOAP_ACTION)?.to_str().ok()?;
+	let (_, action) = value.trim_matches('"').split_once('#')?;
+	Some(action.to_owned())
+}
+
+fn soap_response(service: &str, action: &str, arguments: &[(&str, String)]) -> Response {
+	let mut body = format!(
+		r#"<?xml version="1.0" encoding="utf-8"?><s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/"><s:Body><u:{action}Response xmlns:u="{service}">"#
+	);
+	for (name, value) in arguments {
+		write!(body, "<{name}>{}</{name}>", escape(value)).ok();
+	}
+	write!(body, "</u:{action}Response></s:Body></s:Envelope>").ok();
+	xml(body)
+}
+
+fn soap_fault(code: u16, description: &str) -> Response {
+	let body = format!(
+		r#"<?xml version="1.0" encoding="utf-8"?><s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/"><s:Body><s:Fault><faultcode>s:Client</faultcode><faultstring>UPnPError</faultstring><detail><UPnPError xmlns="urn:schemas-upnp-org:control-1-0"><errorCode>{code}</errorCode><errorDescription>{description}</errorDescription></UPnPError></detail></s:Fault></s:Body></s:Envelope>"#
+	);
+	let mut response = xml(body);
+	*response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+	response
+}
+
+fn invalid_action() -> Response {
+	soap_fault(401, "Invalid Action")
+}
+
+fn no_such_object() -> Response {
+	soap_fault(701, "No such object")
+}
+
+async fn post_connection_manager(_local_network: LocalNetwork, headers: HeaderMap) -> Response {
+	match get_soap_action(&headers).as_deref() {
+		Some("GetProtocolInfo") => {
+			let source = [
+				"audio/mpeg",
+				"audio/flac",
+				"audio/mp4",
+				"audio/ogg",
+				"audio/wav",
+			]
+			.iter()
+			.map(|m| format!("http-get:*:{m}:*"))
+			.collect::<Vec<_>>()
+			.join(",");
+			soap_response(
+				CONNECTION_MANAGER,
+				"GetProtocolInfo",
+				&[("Source", source), ("Sink", String::new())],
+			)
+		}
+		Some("GetCurrentConnectionIDs") => soap_response(
+			CONNECTION_MANAGER,
+			"GetCurrentConnectionIDs",
+			&[("ConnectionIDs", "0".to_owned())],
+		),
+		_ => invalid_action(),
+	}
+}
+
+async fn post_content_directory(
+	LocalNetwork(dlna): LocalNetwork,
+	State(config_manager): State<config::Manager>,
+	State(index_manager): State<index::Manager>,
+	uri: Uri,
+	headers: HeaderMap,
+	body: String,
+) -> Response {
+	match get_soap_action(&headers).as_deref() {
+		Some("Browse") => {
+			let base_url = get_base_url(&config_manager, &uri, &headers).await;
+			let didl = Didl {
+				base_url,
+				root_title: dlna.friendly_name,
+			};
+			browse(&index_manager, &didl, &body).await
+		}
+		Some("GetSearchCapabilities") => soap_response(
+			CONTENT_DIRECTORY,
+			"GetSearchCapabilities",
+			&[("SearchCaps", String::new())],
+		),
+		Some("GetSortCapabilities") => soap_response(
+			CONTENT_DIRECTORY,
+			"GetSortCapabilities",
+			&[("SortCaps", String::new())],
+		),
+		Some("GetSystemUpdateID") => soap_response(
+			CONTENT_DIRECTORY,
+			"GetSystemUpdateID",
+			&[("Id", "1".to_owned())],
+		),
+		_ => invalid_action(),
+	}
+}
+
+async fn browse(index_manager: &index::Manager, didl: &Didl, body: &str) -> Response {
+	let object_id = get_soap_argument(body, "ObjectID").unwrap_or_else(|| "0".to_owned());
+	let browse_flag = get_soap_argument(body, "BrowseFlag").unwrap_or_default();
+	let starting_index = get_soap_argument(body, "StartingIndex")
+		.and_then(|i| i.parse::<usize>().ok())
+		.unwrap_or(0);
+	let requested_count = get_soap_argument(body, "RequestedCount")
+		.and_then(|c| c.parse::<usize>().ok())
+		.unwrap_or(0);
+
+	// Object IDs are virtual paths, except for the root container
+	let path = match object_id.as_str() {
+		"0" => PathBuf::new(),
+		id => PathBuf::from(id),
+	};
+
+	let (objects, total) = match browse_flag.as_str() {
+		"BrowseMetadata" => {
+			if let Ok(children) = index_manager.browse(path.clone()).await {
+				(vec![didl.container(&path, Some(children.len()))], 1)
+			} else {
+				match index_manager.get_songs(vec![path]).await.pop() {
+					Some(Ok(song)) => (vec![didl.item(&song)], 1),
+					_ => return no_such_object(),
+				}
+			}
+		}
+		"BrowseDirectChildren" => {
+			let Ok(children) = index_manager.browse(path).await else {
+				return no_such_object();
+			};
+			let total = children.len();
+			let count = match requested_count {
+				0 => total,
+				n => n,
+			};
+			let page = children
+				.into_iter()
+				.skip(starting_index)
+				.take(count)
+				.collect::<Vec<_>>();
+
+			let song_paths = page
+				.iter()
+				.filter_map(|f| match f {
+					index::File::Song(p) => Some(p.clone()),
+					index::File::Directory(_) => None,
+				})
+				.collect::<Vec<_>>();
+			let mut songs = index_manager.get_songs(song_paths).await.into_iter();
+
+			let mut objects = vec![];
+			for file in page {
+				match file {
+					index::File::Directory(p) => objects.push(didl.container(&p, None)),
+					index::File::Song(_) => {
+						if let Some(Ok(song)) = songs.next() {
+							objects.push(didl.item(&song));
+						}
+					}
+				}
+			}
+			(objects, total)
+		}
+		_ => return soap_fault(402, "Invalid Args"),
+	};
+
+	let result = format!(
+		r#"<DIDL-Lite xmlns="urn:schemas-upnp-org:metadata-1-0/DIDL-Lite/" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:upnp="urn:schemas-upnp-org:metadata-1-0/upnp/">{}</DIDL-Lite>"#,
+		objects.concat()
+	);
+
+	soap_response(
+		CONTENT_DIRECTORY,
+		"Browse",
+		&[
+			("Result", result),
+			("NumberReturned", objects.len().to_string()),
+			("TotalMatches", total.to_string()),
+			("UpdateID", "1".to_owned()),
+		],
+	)
+}
+
+// Describes collection content as DIDL-Lite objects
+struct Didl {
+	base_url: String,
+	root_title: String,
+}
+
+impl Didl {
+	fn object_id(path: &Path) -> String {
+		match path.components().count() {
+			0 => "0".to_owned(),
+			_ => path.to_string_lossy().to_string(),
+		}
+	}
+
+	fn parent_id(path: &Path) -> String {
+		match path.components().count() {
+			0 => "-1".to_owned(),
+			_ => Self::object_id(path.parent().unwrap_or(Path::new(""))),
+		}
+	}
+
+	fn media_url(&self, path: &Path) -> String {
+		let segments = path
+			.iter()
+			.map(|s| utf8_percent_encode(&s.to_string_lossy(), NON_ALPHANUMERIC).to_string())
+			.collect::<Vec<_>>();
+		format!("{}/dlna/media/{}", self.base_url, segments.join("/"))
+	}
+
+	fn container(&self, path: &Path, child_count: Option<usize>) -> String {
+		let title = match path.file_name() {
+			Some(name) => name.to_string_lossy().to_string(),
+			None => self.root_title.clone(),
+		};
+		let child_count = child_count
+			.map(|c| format!(r#" childCount="{c}""#))
+			.unwrap_or_default();
+		format!(
+			r#"<container id="{}" parentID="{}" restricted="1"{child_count}><dc:title>{}</dc:title><upnp:class>object.container.storageFolder</upnp:class></container>"#,
+			escape(&Self::object_id(path)),
+			escape(&Self::parent_id(path)),
+			escape(&title),
+		)
+	}
+
+	fn item(&self, song: &index::Song) -> String {
+		let path = &song.virtual_path;
+		let title = song.title.clone().unwrap_or_else(|| {
+			path.file_stem()
+				.map(|s| s.to_string_lossy().to_string())
+				.unwrap_or_default()
+		});
+
+		let mut item = format!(
+			r#"<item id="{}" parentID="{}" restricted="1"><dc:title>{}</dc:title><upnp:class>object.item.audioItem.musicTrack</upnp:class>"#,
+			escape(&Self::object_id(path)),
+			escape(&Self::parent_id(path)),
+			escape(&title),
+		);
+		for artist in &song.artists {
+			write!(
+				item,
+				"<upnp:artist>{0}</upnp:artist><dc:creator>{0}</dc:creator>",
+				escape(artist)
+			)
+			.ok();
+		}
+		if let Some(album) = &song.album {
+			write!(item, "<upnp:album>{}</upnp:album>", escape(album)).ok();
+		}
+		for genre in &song.genres {
+			write!(item, "<upnp:genre>{}</upnp:genre>", escape(genre)).ok();
+		}
+		if let Some(track_number) = song.track_number {
+			write!(
+				item,
+				"<upnp:originalTrackNumber>{track_number}</upnp:originalTrackNumber>"
+			)
+			.ok();
+		}
+		// Artwork embedded in audio files cannot be served as an image
+		if let Some(artwork) = song.artwork.as_ref().filter(|a| *a != path) {
+			write!(
+				item,
+				"<upnp:albumArtURI>{}</upnp:albumArtURI>",
+				escape(&self.media_url(artwork))
+			)
+			.ok();
+		}
+
+		let mime_type = get_audio_format(path)
+			.map(|f| f.mime_type())
+			.unwrap_or("application/octet-stream");
+		let duration = song
+			.duration
+			.map(|d| {
+				format!(
+					r#" duration="{}:{:02}:{:02}.000""#,
+					d / 3600,
+					(d / 60) % 60,
+					d % 60
+				)
+			})
+			.unwrap_or_default();
+		write!(
+			item,
+			r#"<res protocolInfo="http-get:*:{mime_type}:*"{duration}>{}</res></item>"#,
+			escape(&self.media_url(path))
+		)
+		.ok();
+		item
+	}
+}
+
+// DLNA devices get the permissions of the configured `user`, or all of them when there is none
+async fn check_permission(
+	config_manager: &config::Manager,
+	dlna: &config::Dlna,
+	permission: auth::Permission,
+) -> Result<(), StatusCode> {
+	let Some(username) = &dlna.user else {
+		return Ok(());
+	};
+	match config_manager.get_user(username).await {
+		Ok(user) if user.has_permission(permission) => Ok(()),
+		_ => Err(StatusCode::FORBIDDEN),
+	}
+}
+
+async fn get_media(
+	LocalNetwork(dlna): LocalNetwork,
+	State(config_manager): State<config::Manager>,
+	Extension(stream_limiter): Extension<limits::StreamLimiter>,
+	extract::Path(virtual_path): extract::Path<PathBuf>,
+	headers: HeaderMap,
+	client_ip: Option<Extension<ClientIp>>,
+) -> Response {
+	// Renderers receive whole files, which they could keep
+	if let Err(status) = check_permission(&config_manager, &dlna, auth::Permission::Download).await
+	{
+		return status.into_response();
+	}
+	// Browse results describe the original files, so capped audio cannot be substituted. Users
+	// over their transfer quota are capped too.
+	if let Some(username) = &dlna.user {
+		let client_ip = client_ip.map(|Extension(ClientIp(ip))| ip);
+		if let Err(e) = stream_limiter
+			.check_original_quality(username, client_ip)
+			.await
+		{
+			return e.into_response();
+		}
+	}
+
+	let is_normal = virtual_path
+		.components()
+		.all(|c| matches!(c, Component::Normal(_)));
+	let real_path = match config_manager.resolve_virtual_path(&virtual_path).await {
+		Ok(p) if is_normal => p,
+		_ => return StatusCode::NOT_FOUND.into_response(),
+	};
+	let Ok(file) = tokio::fs::File::open(&real_path).await else {
+		return StatusCode::NOT_FOUND.into_response();
+	};
+
+	let Ok(mut response) = conditional::serve_file(file, &headers).await else {
+		return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+	};
+
+	let extension = real_path
+		.extension()
+		.map(|e| e.to_string_lossy().to_lowercase());
+	let content_type = match (get_audio_format(&real_path), extension.as_deref()) {
+		(Some(format), _) => Some(format.mime_type()),
+		(None, Some("jpg" | "jpeg")) => Some("image/jpeg"),
+		(None, Some("png")) => Some("image/png"),
+		(None, Some("gif")) => Some("image/gif"),
+		(None, Some("bmp")) => Some("image/bmp"),
+		_ => None,
+	};
+
+	let response_headers = response.headers_mut();
+	if let Some(content_type) = content_type {
+		response_headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+	}
+	let transfer_mode = match content_type {
+		Some(t) if t.starts_with("image/") => "Interactive",
+		_ => "Streaming",
+	};
+	response_headers.insert(TRANSFER_MODE, HeaderValue::from_static(transfer_mode));
+	response_headers.insert(
+		CONTENT_FEATURES,
+		HeaderValue::from_static("DLNA.ORG_OP=01;DLNA.ORG_FLAGS=01700000000000000000000000000000"),
+	);
+	match &dlna.user {
+		Some(username) => stream_limiter.record_transfer(username, response),
+		None => response,
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn can_read_soap_arguments() {
+		let body = r#"<?xml version="1.0"?><s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/"><s:Body><u:Browse xmlns:u="urn:schemas-upnp-org:service:ContentDirectory:1"><ObjectID>root/Tom &amp; Jerry</ObjectID><BrowseFlag>BrowseDirectChildren</BrowseFlag><Filter>*</Filter><StartingIndex>0</StartingIndex><RequestedCount>50</RequestedCount><SortCriteria/></u:Browse></s:Body></s:Envelope>"#;
+		assert_eq!(
+			get_soap_argument(body, "ObjectID"),
+			Some("root/Tom & Jerry".to_owned())
+		);
+		assert_eq!(
+			get_soap_argument(body, "RequestedCount"),
+			Some("50".to_owned())
+		);
+		assert_eq!(get_soap_argument(body, "SortCriteria"), Some(String::new()));
+		assert_eq!(get_soap_argument(body, "ContainerID"), None);
+	}
+
+	#[test]
+	fn can_read_soap_action() {
+		let mut headers = HeaderMap::new();
+		headers.insert(
+			SOAP_ACTION,
+			HeaderValue::from_static(r#""urn:schemas-upnp-org:service:ContentDirectory:1#Browse""#),
+		);
+		assert_eq!(get_soap_action(&headers), Some("Browse".to_owned()));
+	}
+
+	#[test]
+	fn only_local_addresses_are_allowed() {
+		let local = [
+			"192.168.1.20",
+			"10.0.0.3",
+			"127.0.0.1",
+			"::1",
+			"fd00::1",
+			"fe80::1",
+			"::ffff:192.168.1.20",
+		];
+		for ip in local {
+			assert!(is_local(ip.parse().unwrap()), "{ip}");
+		}
+		let remote = ["8.8.8.8", "2001:db8::1", "::ffff:8.8.8.8"];
+		for ip in remote {
+			assert!(!is_local(ip.parse().unwrap()), "{ip}");
+		}
+	}
+
+	#[test]
+	fn describes_songs() {
+		let didl = Didl {
+			base_url: "http://192.168.1.2:5050".to_owned(),
+			root_title: "Polaris".to_owned(),
+		};
+		let song = index::Song {
+			virtual_path: PathBuf::from_iter(["root", "Khemmis", "Hunted", "02 - Candlelight.mp3"]),
+			title: Some("Candlelight".to_owned()),
+			artists: vec!["Khemmis".to_owned()],
+			album: Some("Hunted".to_owned()),
+			duration: Some(245),
+			artwork: Some(PathBuf::from_iter([
+				"root",
+				"Khemmis",
+				"Hunted",
+				"Folder.jpg",
+			])),
+			..Default::default()
+		};
+		let item = didl.item(&song);
+		let parent = Path::new("root").join("Khemmis").join("Hunted");
+		assert!(item.contains(&format!(r#"parentID="{}""#, parent.to_string_lossy())));
+		assert!(item.contains("<dc:title>Candlelight</dc:title>"));
+		assert!(item.contains("<upnp:artist>Khemmis</upnp:artist>"));
+		assert!(item.contains(r#"protocolInfo="http-get:*:audio/mpeg:*" duration="0:04:05.000""#));
+		assert!(item.contains(
+			"http://192.168.1.2:5050/dlna/media/root/Khemmis/Hunted/02%20%2D%20Candlelight%2Emp3"
+		));
+		assert!(item.contains("<upnp:albumArtURI>"));
+
+		let container = didl.container(Path::new(""), Some(1));
+		assert!(container.contains(r#"id="0" parentID="-1""#));
+		assert!(container.contains("<dc:title>Polaris</dc:title>"));
+	}
+}
@@ -1,18 +1,29 @@
-use axum::http::StatusCode;
+use axum::http::{header, StatusCode};
 use axum::response::{IntoResponse, Response};
+use log::error;
 
+use crate::server::axum::logger;
 use crate::server::error::APIError;
 
 impl IntoResponse for APIError {
 	fn into_response(self) -> Response {
 		let message = self.to_string();
+		let retry_after = match &self {
+			APIError::LoginThrottled(d) => Some(d.as_secs_f64().ceil() as u64),
+			_ => None,
+		};
 		let status_code = match self {
 			APIError::InvalidAPIVersionHeader => StatusCode::BAD_REQUEST,
 			APIError::APIVersionHeaderParseError => StatusCode::BAD_REQUEST,
 			APIError::UnsupportedAPIVersion => StatusCode::NOT_ACCEPTABLE,
 			APIError::AuthorizationTokenEncoding => StatusCode::INTERNAL_SERVER_ERROR,
 			APIError::AdminPermissionRequired => StatusCode::FORBIDDEN,
+			APIError::GuestPermissionDenied => StatusCode::FORBIDDEN,
+			APIError::PermissionRequired => StatusCode::FORBIDDEN,
+			APIError::FamilyMixNotAllowed(_) => StatusCode::FORBIDDEN,
 			APIError::AudioFileIOError => StatusCode::NOT_FOUND,
+			APIError::TooManyStreams => StatusCode::TOO_MANY_REQUESTS,
+			APIError::StreamQualityCapped => StatusCode::FORBIDDEN,
 			APIError::AuthenticationRequired => StatusCode::UNAUTHORIZED,
 			APIError::BrancaTokenEncoding => StatusCode::INTERNAL_SERVER_ERROR,
 			APIError::DdnsUpdateQueryFailed(s) => {
@@ -20,6 +31,23 @@ impl IntoResponse for APIError {
 			}
 			APIError::NativeDatabase(_) => StatusCode::INTERNAL_SERVER_ERROR,
 			APIError::DeletingOwnAccount => StatusCode::CONFLICT,
+			APIError::SessionNotFound => StatusCode::NOT_FOUND,
+			APIError::PasskeyNotFound => StatusCode::NOT_FOUND,
+			APIError::PasskeyAlreadyRegistered => StatusCode::CONFLICT,
+			APIError::PasskeyChallengeExpired => StatusCode::BAD_REQUEST,
+			APIError::InvalidPasskeyRegistration(_) => StatusCode::BAD_REQUEST,
+			APIError::ApiKeyNotFound => StatusCode::NOT_FOUND,
+			APIError::InviteNotFound => StatusCode::NOT_FOUND,
+			APIError::InvalidPreferenceKey(_) => StatusCode::BAD_REQUEST,
+			APIError::PreferencesTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+			APIError::InvalidRating => StatusCode::BAD_REQUEST,
+			APIError::ListenBrainzNotLinked => StatusCode::NOT_FOUND,
+			APIError::InvalidListenBrainzToken => StatusCode::BAD_REQUEST,
+			APIError::ListenBrainzError(_) => StatusCode::BAD_GATEWAY,
+			APIError::InvalidInvite => StatusCode::UNAUTHORIZED,
+			APIError::InvalidInviteValidity => StatusCode::BAD_REQUEST,
+			APIError::EmptyApiKeyName => StatusCode::BAD_REQUEST,
+			APIError::ApiKeyScopeRequired => StatusCode::FORBIDDEN,
 			APIError::DirectoryNotFound(_) => StatusCode::NOT_FOUND,
 			APIError::DuplicateUsername => StatusCode::CONFLICT,
 			APIError::ArtistNotFound => StatusCode::NOT_FOUND,
@@ -27,16 +55,65 @@ impl IntoResponse for APIError {
 			APIError::GenreNotFound => StatusCode::NOT_FOUND,
 			APIError::SongNotFound => StatusCode::NOT_FOUND,
 			APIError::EmbeddedArtworkNotFound => StatusCode::NOT_FOUND,
+			APIError::LyricsNotFound => StatusCode::NOT_FOUND,
+			APIError::AudioFingerprintNotFound => StatusCode::NOT_FOUND,
+			APIError::ArtistImageNotFound => StatusCode::NOT_FOUND,
 			APIError::EmptyPassword => StatusCode::BAD_REQUEST,
+			APIError::WeakPassword(_) => StatusCode::BAD_REQUEST,
+			APIError::PasswordChangeRequired => StatusCode::FORBIDDEN,
 			APIError::EmptyUsername => StatusCode::BAD_REQUEST,
 			APIError::IncorrectCredentials => StatusCode::UNAUTHORIZED,
+			APIError::AccountLocked => StatusCode::LOCKED,
+			APIError::LoginThrottled(_) => StatusCode::TOO_MANY_REQUESTS,
 			APIError::Internal => StatusCode::INTERNAL_SERVER_ERROR,
 			APIError::InvalidAlbumArtPattern => StatusCode::BAD_REQUEST,
+			APIError::InvalidFilenamePattern => StatusCode::BAD_REQUEST,
+			APIError::InvalidAlias => StatusCode::BAD_REQUEST,
+			APIError::InvalidPostScanWebhookURL => StatusCode::BAD_REQUEST,
+			APIError::InvalidWebhookURL => StatusCode::BAD_REQUEST,
+			APIError::InvalidLimit => StatusCode::BAD_REQUEST,
+			APIError::InvalidTokenLifetime => StatusCode::BAD_REQUEST,
+			APIError::InvalidLockout => StatusCode::BAD_REQUEST,
+			APIError::InvalidOidcIssuerURL => StatusCode::BAD_REQUEST,
+			APIError::InvalidPasskeyOrigin => StatusCode::BAD_REQUEST,
+			APIError::InvalidPasskeyRelyingParty => StatusCode::BAD_REQUEST,
+			APIError::OidcNotConfigured => StatusCode::NOT_FOUND,
+			APIError::DeviceLoginNotFound => StatusCode::NOT_FOUND,
+			APIError::DeviceLoginExpired => StatusCode::BAD_REQUEST,
+			APIError::DeviceLoginPending => StatusCode::ACCEPTED,
+			APIError::DeviceLoginSlowDown => StatusCode::TOO_MANY_REQUESTS,
+			APIError::TooManyPendingLogins => StatusCode::SERVICE_UNAVAILABLE,
+			APIError::OidcLoginExpired => StatusCode::BAD_REQUEST,
+			APIError::OidcAccountNotLinked => StatusCode::FORBIDDEN,
+			APIError::InvalidOidcReturnPath => StatusCode::BAD_REQUEST,
+			APIError::OidcProviderError(_) => StatusCode::BAD_GATEWAY,
+			APIError::InvalidTrustedProxy => StatusCode::BAD_REQUEST,
+			APIError::InvalidScanSchedule => StatusCode::BAD_REQUEST,
+			APIError::InvalidExcludePattern => StatusCode::BAD_REQUEST,
+			APIError::InvalidRemoteStorage => StatusCode::BAD_REQUEST,
+			APIError::RemoteStorageUnavailable => StatusCode::BAD_GATEWAY,
+			APIError::InvalidArtistSeparator => StatusCode::BAD_REQUEST,
+			APIError::FanartTvApiKeyMissing => StatusCode::BAD_REQUEST,
 			APIError::InvalidDDNSURL => StatusCode::BAD_REQUEST,
+			APIError::InvalidBasePath => StatusCode::BAD_REQUEST,
+			APIError::InvalidAcmeDomain => StatusCode::BAD_REQUEST,
 			APIError::Io(_, _) => StatusCode::INTERNAL_SERVER_ERROR,
 			APIError::OwnAdminPrivilegeRemoval => StatusCode::CONFLICT,
+			APIError::NoScanInProgress => StatusCode::CONFLICT,
+			APIError::ScanPaused => StatusCode::CONFLICT,
+			APIError::JukeboxUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+			APIError::JukeboxPositionInvalid => StatusCode::BAD_REQUEST,
 			APIError::PasswordHashing => StatusCode::INTERNAL_SERVER_ERROR,
 			APIError::PlaylistNotFound => StatusCode::NOT_FOUND,
+			APIError::PlayQueuePositionInvalid => StatusCode::BAD_REQUEST,
+			APIError::RadioStationNotFound => StatusCode::NOT_FOUND,
+			APIError::ArtworkInvalid => StatusCode::BAD_REQUEST,
+			APIError::ArtworkOverrideNotFound => StatusCode::NOT_FOUND,
+			APIError::EmptyRadioStationName => StatusCode::BAD_REQUEST,
+			APIError::RadioStationUrlInvalid => StatusCode::BAD_REQUEST,
+			APIError::RadioStreamUnavailable => StatusCode::BAD_GATEWAY,
+			APIError::TranscodingFailed => StatusCode::INTERNAL_SERVER_ERROR,
+			APIError::InvalidStreamBitrate => StatusCode::BAD_REQUEST,
 			APIError::SearchQueryParseError => StatusCode::BAD_REQUEST,
 			APIError::ThumbnailFlacDecoding(_, _) => StatusCode::INTERNAL_SERVER_ERROR,
 			APIError::ThumbnailFileIOError => StatusCode::NOT_FOUND,
@@ -50,6 +127,19 @@ impl IntoResponse for APIError {
 			APIError::VFSPathNotFound => StatusCode::NOT_FOUND,
 		};
 
-		(status_code, message).into_response()
+		if status_code.is_server_error() {
+			match logger::current_request_id() {
+				Some(request_id) => error!("Request {request_id} failed: {message}"),
+				None => error!("Request failed: {message}"),
+			}
+		}
+
+		let mut response = (status_code, message).into_response();
+		if let Some(seconds) = retry_after {
+			response
+				.headers_mut()
+				.insert(header::RETRY_AFTER, seconds.into());
+		}
+		response
 	}
 }
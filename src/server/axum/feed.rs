@@ -0,0 +1,105 @@
+use std::fmt::Write;
+
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use time::{format_description::well_known::Rfc2822, Duration, OffsetDateTime};
+
+use crate::app::index;
+use crate::utils::get_audio_format;
+
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+	.remove(b'-')
+	.remove(b'.')
+	.remove(b'_')
+	.remove(b'~');
+
+pub struct Episode {
+	pub song: index::Song,
+	pub size: u64,
+}
+
+fn escape(text: &str) -> String {
+	text.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+		.replace('"', "&quot;")
+		.replace('\'', "&apos;")
+}
+
+pub fn enclosure_url(feed_url: &str, song: &index::Song) -> String {
+	let segments = song
+		.virtual_path
+		.iter()
+		.map(|s| utf8_percent_encode(&s.to_string_lossy(), PATH_SEGMENT).to_string())
+		.collect::<Vec<_>>();
+	format!("{feed_url}/audio/{}", segments.join("/"))
+}
+
+// Renders a playlist as an RSS podcast feed, with one episode per song.
+// Podcast apps sort episodes by date, so songs are given made-up publication dates one minute
+// apart, in playlist order. These dates stay the same as long as the playlist is not edited.
+pub fn render_playlist(name: &str, owner: &str, episodes: &[Episode], feed_url: &str) -> String {
+	let mut feed = String::from(
+		r#"<?xml version="1.0" encoding="UTF-8"?><rss version="2.0" xmlns:itunes="http://www.itunes.com/dtds/podcast-1.0.dtd" xmlns:atom="http://www.w3.org/2005/Atom"><channel>"#,
+	);
+	write!(
+		feed,
+		r#"<title>{0}</title><link>{1}</link><atom:link href="{1}" rel="self" type="application/rss+xml"/><description>Polaris playlist by {2}</description><itunes:author>{2}</itunes:author><itunes:type>serial</itunes:type>"#,
+		escape(name),
+		escape(feed_url),
+		escape(owner),
+	)
+	.ok();
+
+	let first_date = OffsetDateTime::UNIX_EPOCH + Duration::days(365 * 30);
+	for (index, episode) in episodes.iter().enumerate() {
+		let song = &episode.song;
+		let title = song.title.clone().unwrap_or_else(|| {
+			song.virtual_path
+				.file_stem()
+				.map(|s| s.to_string_lossy().to_string())
+				.unwrap_or_default()
+		});
+		let artists = song.artists.join(", ");
+		let mime_type = get_audio_format(&song.virtual_path)
+			.map(|f| f.mime_type())
+			.unwrap_or("application/octet-stream");
+		let date = (first_date + Duration::minutes(index as i64))
+			.format(&Rfc2822)
+			.unwrap_or_default();
+
+		feed.push_str("<item>");
+		write!(feed, "<title>{}</title>", escape(&title)).ok();
+		if !artists.is_empty() {
+			write!(feed, "<itunes:author>{}</itunes:author>", escape(&artists)).ok();
+		}
+		let description = [Some(artists), song.album.clone()]
+			.into_iter()
+			.flatten()
+			.filter(|s| !s.is_empty())
+			.collect::<Vec<_>>()
+			.join(" - ");
+		write!(feed, "<description>{}</description>", escape(&description)).ok();
+		write!(
+			feed,
+			r#"<enclosure url="{}" length="{}" type="{mime_type}"/>"#,
+			escape(&enclosure_url(feed_url, song)),
+			episode.size,
+		)
+		.ok();
+		write!(
+			feed,
+			r#"<guid isPermaLink="false">{}</guid>"#,
+			escape(&song.virtual_path.to_string_lossy())
+		)
+		.ok();
+		write!(feed, "<pubDate>{date}</pubDate>").ok();
+		if let Some(duration) = song.duration {
+			write!(feed, "<itunes:duration>{duration}</itunes:duration>").ok();
+		}
+		write!(feed, "<itunes:episode>{}</itunes:episode>", index + 1).ok();
+		feed.push_str("</item>");
+	}
+
+	feed.push_str("</channel></rss>");
+	feed
+}
@@ -0,0 +1,404 @@
+use axum::{
+	extract::{connect_info::Connected, ConnectInfo, Request},
+	response::Response,
+	serve::IncomingStream,
+};
+use http::{
+	header,
+	uri::{Authority, Scheme},
+	HeaderMap, HeaderName, HeaderValue, Uri,
+};
+use std::{
+	future::Future,
+	net::{IpAddr, SocketAddr},
+	pin::Pin,
+	str::FromStr,
+	task::{Context, Poll},
+};
+use tower::{Layer, Service};
+
+use crate::app::config;
+
+use super::tls::AcmeListener;
+
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+const X_FORWARDED_PROTO: HeaderName = HeaderName::from_static("x-forwarded-proto");
+const X_FORWARDED_HOST: HeaderName = HeaderName::from_static("x-forwarded-host");
+
+// Address of the peer which opened the connection, available as `ConnectInfo<ClientAddress>`.
+// This may be a reverse proxy, see `ClientIp` for the address of the actual client.
+#[derive(Clone, Copy, Debug)]
+pub struct ClientAddress(pub SocketAddr);
+
+impl Connected<IncomingStream<'_, tokio::net::TcpListener>> for ClientAddress {
+	fn connect_info(stream: IncomingStream<'_, tokio::net::TcpListener>) -> Self {
+		Self(*stream.remote_addr())
+	}
+}
+
+impl Connected<IncomingStream<'_, AcmeListener>> for ClientAddress {
+	fn connect_info(stream: IncomingStream<'_, AcmeListener>) -> Self {
+		Self(*stream.remote_addr())
+	}
+}
+
+// Address of the client which sent a request. For requests relayed by trusted reverse proxies,
+// this is the address the proxies received the request from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+// Whether an address belongs to a private, loopback or link-local network
+pub fn is_local(ip: IpAddr) -> bool {
+	match ip {
+		IpAddr::V4(ip) => ip.is_private() || ip.is_loopback() || ip.is_link_local(),
+		IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+			Some(ip) => is_local(IpAddr::V4(ip)),
+			None => {
+				let unique_local = (ip.segments()[0] & 0xfe00) == 0xfc00;
+				let link_local = (ip.segments()[0] & 0xffc0) == 0xfe80;
+				ip.is_loopback() || unique_local || link_local
+			}
+		},
+	}
+}
+
+// What a proxy reported about the request it received. Addresses of proxies which did not
+// report one, or reported an obfuscated one, are unknown.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Hop {
+	address: Option<IpAddr>,
+	proto: Option<Scheme>,
+	host: Option<Authority>,
+}
+
+// Absolute URL prefix under which clients can reach this server, as seen from their side of any
+// trusted reverse proxy
+pub async fn get_base_url(
+	config_manager: &config::Manager,
+	uri: &Uri,
+	headers: &HeaderMap,
+) -> String {
+	let scheme = match (uri.scheme_str(), config_manager.get_acme().await) {
+		(Some(scheme), _) => scheme,
+		(None, Some(_)) => "https",
+		(None, None) => "http",
+	};
+	let host = headers
+		.get(header::HOST)
+		.and_then(|h| h.to_str().ok())
+		.unwrap_or("localhost");
+	let base_path = config_manager.get_base_path().await.unwrap_or_default();
+	format!("{scheme}://{host}{base_path}")
+}
+
+// Reads client details from `Forwarded` (RFC 7239), or from the `X-Forwarded-*` headers if absent.
+// Hops are listed from furthest to closest. `X-Forwarded-*` values are matched from the closest
+// proxy, as each proxy appends to them.
+fn parse_forwarded(headers: &HeaderMap) -> Vec<Hop> {
+	let values = |name: HeaderName| {
+		headers
+			.get_all(name)
+			.iter()
+			.filter_map(|v| v.to_str().ok())
+			.flat_map(|v| v.split(','))
+			.map(|v| v.trim())
+			.filter(|v| !v.is_empty())
+			.collect::<Vec<_>>()
+	};
+
+	let forwarded = values(header::FORWARDED);
+	if !forwarded.is_empty() {
+		return forwarded
+			.iter()
+			.map(|element| {
+				let mut hop = Hop::default();
+				for pair in element.split(';') {
+					let Some((key, value)) = pair.split_once('=') else {
+						continue;
+					};
+					let value = value.trim().trim_matches('"');
+					match key.trim().to_lowercase().as_str() {
+						"for" => hop.address = parse_node(value),
+						"proto" => hop.proto = Scheme::from_str(value).ok(),
+						"host" => hop.host = Authority::from_str(value).ok(),
+						_ => (),
+					}
+				}
+				hop
+			})
+			.collect();
+	}
+
+	let addresses = values(X_FORWARDED_FOR);
+	let protos = values(X_FORWARDED_PROTO);
+	let hosts = values(X_FORWARDED_HOST);
+	let from_closest = |list: &[&str], distance: usize| {
+		list.len()
+			.checked_sub(distance + 1)
+			.map(|index| list[index].to_owned())
+	};
+	let num_hops = addresses.len().max(protos.len()).max(hosts.len());
+	(0..num_hops)
+		.rev()
+		.map(|distance| Hop {
+			address: from_closest(&addresses, distance).and_then(|a| parse_node(&a)),
+			proto: from_closest(&protos, distance).and_then(|p| Scheme::from_str(&p).ok()),
+			host: from_closest(&hosts, distance).and_then(|h| Authority::from_str(&h).ok()),
+		})
+		.collect()
+}
+
+// Parses addresses like `192.0.2.43`, `192.0.2.43:47011` or `[2001:db8::17]:47011`
+fn parse_node(node: &str) -> Option<IpAddr> {
+	IpAddr::from_str(node)
+		.or_else(|_| SocketAddr::from_str(node).map(|a| a.ip()))
+		.or_else(|_| IpAddr::from_str(node.trim_start_matches('[').trim_end_matches(']')))
+		.ok()
+}
+
+fn apply_forwarded(request: &mut Request, trusted_proxies: &[config::TrustedProxy]) {
+	let Some(peer) = request
+		.extensions()
+		.get::<ConnectInfo<ClientAddress>>()
+		.map(|c| c.0 .0.ip())
+	else {
+		return;
+	};
+
+	let is_trusted = |ip: IpAddr| trusted_proxies.iter().any(|p| p.contains(ip));
+	if !is_trusted(peer) {
+		request.extensions_mut().insert(ClientIp(peer));
+		return;
+	}
+
+	let hops = parse_forwarded(request.headers());
+
+	// Walk back from the closest proxy until reaching an address we don't trust
+	let mut client = peer;
+	let mut resolved = hops.len();
+	for (index, hop) in hops.iter().enumerate().rev() {
+		resolved = index;
+		let Some(ip) = hop.address else {
+			break;
+		};
+		client = ip;
+		if !is_trusted(ip) {
+			break;
+		}
+	}
+	request.extensions_mut().insert(ClientIp(client));
+
+	// Scheme and host are only read from hops reported by trusted proxies, preferring the one
+	// which received the request from the client
+	let trusted_hops = &hops[resolved..];
+	let proto = trusted_hops.iter().find_map(|h| h.proto.clone());
+	let host = trusted_hops.iter().find_map(|h| h.host.clone());
+
+	if let Some(host) = &host {
+		if let Ok(value) = HeaderValue::from_str(host.as_str()) {
+			request.headers_mut().insert(header::HOST, value);
+		}
+	}
+
+	if let Some(proto) = proto {
+		let mut parts = request.uri().clone().into_parts();
+		parts.scheme = Some(proto);
+		parts.authority = parts.authority.or(host).or_else(|| {
+			let host = request.headers().get(header::HOST)?.to_str().ok()?;
+			Authority::from_str(host).ok()
+		});
+		if let Ok(uri) = Uri::from_parts(parts) {
+			*request.uri_mut() = uri;
+		}
+	}
+}
+
+// Determines the client address, scheme and host of requests relayed by trusted reverse proxies.
+// The client address is made available to downstream services as a `ClientIp` extension.
+#[derive(Clone)]
+pub struct ForwardedLayer {
+	config_manager: config::Manager,
+}
+
+impl ForwardedLayer {
+	pub fn new(config_manager: config::Manager) -> Self {
+		Self { config_manager }
+	}
+}
+
+impl<S> Layer<S> for ForwardedLayer {
+	type Service = ForwardedMiddleware<S>;
+
+	fn layer(&self, inner: S) -> Self::Service {
+		ForwardedMiddleware {
+			inner,
+			config_manager: self.config_manager.clone(),
+		}
+	}
+}
+
+#[derive(Clone)]
+pub struct ForwardedMiddleware<S> {
+	inner: S,
+	config_manager: config::Manager,
+}
+
+impl<S> Service<Request> for ForwardedMiddleware<S>
+where
+	S: Service<Request, Response = Response> + Clone + Send + 'static,
+	S::Future: Send + 'static,
+{
+	type Response = S::Response;
+	type Error = S::Error;
+	type Future =
+		Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send + 'static>>;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		self.inner.poll_ready(cx)
+	}
+
+	fn call(&mut self, mut request: Request) -> Self::Future {
+		// Take the service which was driven to readiness
+		let clone = self.inner.clone();
+		let mut inner = std::mem::replace(&mut self.inner, clone);
+		let config_manager = self.config_manager.clone();
+
+		Box::pin(async move {
+			let trusted_proxies = config_manager.get_trusted_proxies().await;
+			apply_forwarded(&mut request, &trusted_proxies);
+			inner.call(request).await
+		})
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use axum::body::Body;
+
+	use super::*;
+
+	fn make_request(peer: &str, headers: &[(&str, &str)]) -> Request {
+		let mut builder = Request::builder()
+			.uri("/api/version")
+			.header("host", "polaris");
+		for (name, value) in headers {
+			builder = builder.header(*name, *value);
+		}
+		let mut request = builder.body(Body::empty()).unwrap();
+		let peer = SocketAddr::new(IpAddr::from_str(peer).unwrap(), 1234);
+		request
+			.extensions_mut()
+			.insert(ConnectInfo(ClientAddress(peer)));
+		request
+	}
+
+	fn client_ip(request: &Request) -> Option<String> {
+		request
+			.extensions()
+			.get::<ClientIp>()
+			.map(|c| c.0.to_string())
+	}
+
+	fn trusted(proxies: &[&str]) -> Vec<config::TrustedProxy> {
+		proxies.iter().map(|p| p.parse().unwrap()).collect()
+	}
+
+	#[test]
+	fn ignores_headers_from_untrusted_peers() {
+		let mut request = make_request(
+			"203.0.113.1",
+			&[
+				("x-forwarded-for", "1.2.3.4"),
+				("x-forwarded-proto", "https"),
+			],
+		);
+		apply_forwarded(&mut request, &trusted(&["10.0.0.0/8"]));
+		assert_eq!(client_ip(&request), Some("203.0.113.1".to_owned()));
+		assert_eq!(request.uri().scheme(), None);
+	}
+
+	#[test]
+	fn reads_x_forwarded_headers() {
+		let mut request = make_request(
+			"10.0.0.2",
+			&[
+				("x-forwarded-for", "6.6.6.6, 1.2.3.4, 10.0.0.1"),
+				("x-forwarded-proto", "https"),
+				("x-forwarded-host", "music.example.com"),
+			],
+		);
+		apply_forwarded(&mut request, &trusted(&["10.0.0.0/8"]));
+		assert_eq!(client_ip(&request), Some("1.2.3.4".to_owned()));
+		assert_eq!(request.uri().scheme_str(), Some("https"));
+		assert_eq!(request.uri().host(), Some("music.example.com"));
+		assert_eq!(
+			request.headers().get(header::HOST).unwrap(),
+			"music.example.com"
+		);
+		assert_eq!(request.uri().path(), "/api/version");
+	}
+
+	#[test]
+	fn reads_forwarded_header() {
+		let mut request = make_request(
+			"::1",
+			&[(
+				"forwarded",
+				r#"for="[2001:db8:cafe::17]:4711";proto=https;host=music.example.com, for=127.0.0.1"#,
+			)],
+		);
+		apply_forwarded(&mut request, &trusted(&["::1", "127.0.0.1"]));
+		assert_eq!(client_ip(&request), Some("2001:db8:cafe::17".to_owned()));
+		assert_eq!(request.uri().scheme_str(), Some("https"));
+		assert_eq!(request.uri().host(), Some("music.example.com"));
+	}
+
+	#[test]
+	fn ignores_scheme_and_host_injected_by_client() {
+		let mut request = make_request(
+			"10.0.0.2",
+			&[
+				("x-forwarded-for", "6.6.6.6, 1.2.3.4"),
+				("x-forwarded-proto", "http, https"),
+				("x-forwarded-host", "evil.example.com, music.example.com"),
+			],
+		);
+		apply_forwarded(&mut request, &trusted(&["10.0.0.0/8"]));
+		assert_eq!(client_ip(&request), Some("1.2.3.4".to_owned()));
+		assert_eq!(request.uri().scheme_str(), Some("https"));
+		assert_eq!(request.uri().host(), Some("music.example.com"));
+
+		let mut request = make_request(
+			"::1",
+			&[(
+				"forwarded",
+				r#"for=6.6.6.6;proto=http;host=evil.example.com, for="[2001:db8:cafe::17]";proto=https;host=music.example.com"#,
+			)],
+		);
+		apply_forwarded(&mut request, &trusted(&["::1"]));
+		assert_eq!(client_ip(&request), Some("2001:db8:cafe::17".to_owned()));
+		assert_eq!(request.uri().scheme_str(), Some("https"));
+		assert_eq!(request.uri().host(), Some("music.example.com"));
+	}
+
+	#[test]
+	fn reads_scheme_set_by_closest_proxy() {
+		let mut request = make_request(
+			"10.0.0.2",
+			&[
+				("x-forwarded-for", "1.2.3.4, 10.0.0.1"),
+				("x-forwarded-proto", "https"),
+			],
+		);
+		apply_forwarded(&mut request, &trusted(&["10.0.0.0/8"]));
+		assert_eq!(client_ip(&request), Some("1.2.3.4".to_owned()));
+		assert_eq!(request.uri().scheme_str(), Some("https"));
+	}
+
+	#[test]
+	fn falls_back_to_furthest_address() {
+		let mut request = make_request("10.0.0.2", &[("x-forwarded-for", "10.0.0.3, 10.0.0.1")]);
+		apply_forwarded(&mut request, &trusted(&["10.0.0.0/8"]));
+		assert_eq!(client_ip(&request), Some("10.0.0.3".to_owned()));
+	}
+}
@@ -0,0 +1,741 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant, SystemTime};
+
+use axum::body::{Body, Bytes};
+use axum::extract::connect_info::Connected;
+use axum::response::Response;
+use axum::serve::{IncomingStream, Listener};
+use http_body::{Frame, SizeHint};
+use log::error;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::Sleep;
+use tokio_util::sync::PollSemaphore;
+
+use crate::app::config::storage::StreamQuality;
+use crate::app::{config, transfers};
+use crate::server::error::APIError;
+
+use super::forwarded::{is_local, ClientAddress};
+
+// Connections which received part of a request must get an answer started within this delay,
+// so clients sending requests very slowly cannot hold connections open
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+// Connections which stay silent for this long between requests are closed
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+// Silent connections give their slot back after this long, and wait for a new one before the
+// server reads their next request
+const SLOT_RELEASE_DELAY: Duration = Duration::from_secs(1);
+
+// Connection limits shared by all listeners
+#[derive(Clone, Default)]
+pub struct ConnectionLimits {
+	slots: Option<Arc<Semaphore>>,
+	max_per_address: Option<usize>,
+	per_address: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+impl ConnectionLimits {
+	pub fn new(max_connections: Option<usize>, max_connections_per_ip: Option<usize>) -> Self {
+		Self {
+			slots: max_connections.map(|n| Arc::new(Semaphore::new(n))),
+			max_per_address: max_connections_per_ip,
+			per_address: Arc::default(),
+		}
+	}
+
+	fn register(&self, address: IpAddr) -> Option<AddressGuard> {
+		let mut per_address = self.per_address.lock().unwrap();
+		let count = per_address.entry(address).or_default();
+		if self.max_per_address.is_some_and(|m| *count >= m) {
+			return None;
+		}
+		*count += 1;
+		Some(AddressGuard {
+			address,
+			per_address: self.per_address.clone(),
+		})
+	}
+}
+
+// Counts a connection against the limit of its address while alive
+struct AddressGuard {
+	address: IpAddr,
+	per_address: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+impl Drop for AddressGuard {
+	fn drop(&mut self) {
+		let mut per_address = self.per_address.lock().unwrap();
+		if let Some(count) = per_address.get_mut(&self.address) {
+			*count -= 1;
+			if *count == 0 {
+				per_address.remove(&self.address);
+			}
+		}
+	}
+}
+
+// Stops accepting connections while too many are busy. Pending connections wait in the
+// listen backlog until a slot frees up. Connections over the limit of their address are closed.
+pub struct LimitedListener<L> {
+	inner: L,
+	limits: ConnectionLimits,
+}
+
+impl<L> LimitedListener<L> {
+	pub fn new(inner: L, limits: ConnectionLimits) -> Self {
+		Self { inner, limits }
+	}
+}
+
+impl<L: Listener<Addr = SocketAddr>> Listener for LimitedListener<L> {
+	type Io = LimitedIo<L::Io>;
+	type Addr = SocketAddr;
+
+	async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+		let mut permit = None;
+		loop {
+			if permit.is_none() {
+				if let Some(slots) = &self.limits.slots {
+					permit = slots.clone().acquire_owned().await.ok();
+				}
+			}
+			let (io, address) = self.inner.accept().await;
+			let Some(address_guard) = self.limits.register(address.ip()) else {
+				continue;
+			};
+			let now = Instant::now();
+			return (
+				LimitedIo {
+					inner: io,
+					slots: self.limits.slots.clone().map(PollSemaphore::new),
+					permit,
+					_address_guard: address_guard,
+					request_started: Some(now),
+					last_activity: now,
+					write_pending: false,
+					stash: vec![],
+					timer: Box::pin(tokio::time::sleep_until(now.into())),
+				},
+				address,
+			);
+		}
+	}
+
+	fn local_addr(&self) -> tokio::io::Result<Self::Addr> {
+		self.inner.local_addr()
+	}
+}
+
+impl<L: Listener<Addr = SocketAddr>> Connected<IncomingStream<'_, LimitedListener<L>>>
+	for ClientAddress
+{
+	fn connect_info(stream: IncomingStream<'_, LimitedListener<L>>) -> Self {
+		Self(*stream.remote_addr())
+	}
+}
+
+// Connection which holds a slot while it is busy, and enforces request and idle timeouts
+pub struct LimitedIo<T> {
+	inner: T,
+	slots: Option<PollSemaphore>,
+	permit: Option<OwnedSemaphorePermit>,
+	_address_guard: AddressGuard,
+	// Set from the first byte of a request until the first byte of the answer
+	request_started: Option<Instant>,
+	last_activity: Instant,
+	write_pending: bool,
+	// Data received while waiting for a slot
+	stash: Vec<u8>,
+	timer: Pin<Box<Sleep>>,
+}
+
+impl<T> LimitedIo<T> {
+	fn on_read(&mut self) {
+		let now = Instant::now();
+		self.request_started.get_or_insert(now);
+		self.last_activity = now;
+	}
+
+	fn on_write(&mut self, result: &Poll<std::io::Result<usize>>) {
+		self.write_pending = result.is_pending();
+		if let Poll::Ready(Ok(n)) = result {
+			if *n > 0 {
+				self.request_started = None;
+				self.last_activity = Instant::now();
+			}
+		}
+	}
+
+	fn poll_slot(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+		match (&self.permit, &mut self.slots) {
+			(None, Some(slots)) => slots.poll_acquire(cx).map(|permit| self.permit = permit),
+			_ => Poll::Ready(()),
+		}
+	}
+
+	// Called while waiting for data, to close connections which take too long
+	fn poll_timeouts(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+		loop {
+			// Clients not reading answers fast enough are not idle
+			if self.write_pending {
+				return Poll::Pending;
+			}
+			let now = Instant::now();
+			let deadline = match self.request_started {
+				Some(started) => started + REQUEST_TIMEOUT,
+				None => self.last_activity + IDLE_TIMEOUT,
+			};
+			if now >= deadline {
+				return Poll::Ready(match self.request_started {
+					Some(_) => Err(std::io::ErrorKind::TimedOut.into()),
+					// Closing connections between requests is expected by HTTP clients
+					None => Ok(()),
+				});
+			}
+			let release_at = self.last_activity + SLOT_RELEASE_DELAY;
+			if self.permit.is_some() && now >= release_at {
+				self.permit = None;
+			}
+			let deadline = match self.permit {
+				Some(_) => deadline.min(release_at),
+				None => deadline,
+			};
+			self.timer.as_mut().reset(deadline.into());
+			if self.timer.as_mut().poll(cx).is_pending() {
+				return Poll::Pending;
+			}
+		}
+	}
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for LimitedIo<T> {
+	fn poll_read(
+		mut self: Pin<&mut Self>,
+		cx: &mut Context<'_>,
+		buf: &mut ReadBuf<'_>,
+	) -> Poll<std::io::Result<()>> {
+		if !self.stash.is_empty() {
+			ready!(self.poll_slot(cx));
+			let length = self.stash.len().min(buf.remaining());
+			buf.put_slice(&self.stash[..length]);
+			self.stash.drain(..length);
+			return Poll::Ready(Ok(()));
+		}
+
+		let filled = buf.filled().len();
+		match Pin::new(&mut self.inner).poll_read(cx, buf) {
+			Poll::Ready(Ok(())) if buf.filled().len() > filled => {
+				self.on_read();
+				if self.poll_slot(cx).is_pending() {
+					self.stash.extend_from_slice(&buf.filled()[filled..]);
+					buf.set_filled(filled);
+					return Poll::Pending;
+				}
+				Poll::Ready(Ok(()))
+			}
+			Poll::Ready(result) => Poll::Ready(result),
+			Poll::Pending => self.poll_timeouts(cx),
+		}
+	}
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for LimitedIo<T> {
+	fn poll_write(
+		mut self: Pin<&mut Self>,
+		cx: &mut Context<'_>,
+		buf: &[u8],
+	) -> Poll<std::io::Result<usize>> {
+		let result = Pin::new(&mut self.inner).poll_write(cx, buf);
+		self.on_write(&result);
+		result
+	}
+
+	fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+		let result = Pin::new(&mut self.inner).poll_flush(cx);
+		self.write_pending = result.is_pending();
+		result
+	}
+
+	fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+		Pin::new(&mut self.inner).poll_shutdown(cx)
+	}
+
+	fn poll_write_vectored(
+		mut self: Pin<&mut Self>,
+		cx: &mut Context<'_>,
+		bufs: &[std::io::IoSlice<'_>],
+	) -> Poll<std::io::Result<usize>> {
+		let result = Pin::new(&mut self.inner).poll_write_vectored(cx, bufs);
+		self.on_write(&result);
+		result
+	}
+
+	fn is_write_vectored(&self) -> bool {
+		self.inner.is_write_vectored()
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StreamSource {
+	Song(PathBuf),
+	RadioStation(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct ActiveStream {
+	pub username: String,
+	pub source: StreamSource,
+	pub client_ip: Option<IpAddr>,
+	pub started_at: SystemTime,
+	pub bytes_sent: u64,
+	// Size of the response, unknown for transcoded audio and radio stations
+	pub total_bytes: Option<u64>,
+}
+
+impl ActiveStream {
+	// Average transfer rate since the stream started
+	pub fn bitrate_kbps(&self) -> u64 {
+		let elapsed = self.started_at.elapsed().unwrap_or_default().as_millis() as u64;
+		(self.bytes_sent * 8)
+			.checked_div(elapsed)
+			.unwrap_or_default()
+	}
+
+	pub fn progress(&self) -> Option<f64> {
+		self.total_bytes
+			.filter(|t| *t > 0)
+			.map(|t| self.bytes_sent as f64 / t as f64)
+	}
+}
+
+struct StreamEntry {
+	username: String,
+	source: StreamSource,
+	client_ip: Option<IpAddr>,
+	started_at: SystemTime,
+	bytes_sent: Arc<AtomicU64>,
+	total_bytes: Option<u64>,
+}
+
+#[derive(Default)]
+struct Streams {
+	next_id: u64,
+	entries: HashMap<u64, StreamEntry>,
+}
+
+// Tracks audio streams in progress, to limit how many each user can open. The limit is read
+// from the configuration for every new stream, so changes apply immediately.
+#[derive(Clone)]
+pub struct StreamLimiter {
+	config_manager: config::Manager,
+	transfers_manager: transfers::Manager,
+	streams: Arc<Mutex<Streams>>,
+}
+
+impl StreamLimiter {
+	pub fn new(config_manager: config::Manager, transfers_manager: transfers::Manager) -> Self {
+		Self {
+			config_manager,
+			transfers_manager,
+			streams: Arc::default(),
+		}
+	}
+
+	pub async fn acquire(
+		&self,
+		username: &str,
+		source: StreamSource,
+		client_ip: Option<IpAddr>,
+	) -> Result<StreamPermit, APIError> {
+		let limit = self.config_manager.get_limits().await.max_streams_per_user;
+		let mut streams = self.streams.lock().unwrap();
+		let count = streams
+			.entries
+			.values()
+			.filter(|e| e.username == username)
+			.count();
+		if limit.is_some_and(|l| count >= l) {
+			return Err(APIError::TooManyStreams);
+		}
+		let id = streams.next_id;
+		streams.next_id += 1;
+		let bytes_sent = Arc::new(AtomicU64::new(0));
+		streams.entries.insert(
+			id,
+			StreamEntry {
+				username: username.to_owned(),
+				source,
+				client_ip,
+				started_at: SystemTime::now(),
+				bytes_sent: bytes_sent.clone(),
+				total_bytes: None,
+			},
+		);
+		Ok(StreamPermit {
+			id,
+			streams: self.streams.clone(),
+			recorder: TransferRecorder {
+				username: username.to_owned(),
+				bytes_sent,
+				transfers_manager: self.transfers_manager.clone(),
+			},
+		})
+	}
+
+	// Quality the audio served to a user is capped to, if any. Requests which did not go through
+	// the network, eg. in tests, count as local.
+	pub async fn get_stream_quality(
+		&self,
+		username: &str,
+		client_ip: Option<IpAddr>,
+	) -> Result<Option<StreamQuality>, APIError> {
+		let over_quota = self
+			.transfers_manager
+			.get_over_quota_stream_quality(username)
+			.await?;
+		if over_quota.is_some() {
+			return Ok(over_quota);
+		}
+		let user = self.config_manager.get_user(username).await?;
+		let local_client = client_ip.is_none_or(is_local);
+		Ok(user.get_stream_quality(local_client).cloned())
+	}
+
+	// For responses which cannot be transcoded, like archives or WebDAV files
+	pub async fn check_original_quality(
+		&self,
+		username: &str,
+		client_ip: Option<IpAddr>,
+	) -> Result<(), APIError> {
+		match self.get_stream_quality(username, client_ip).await? {
+			Some(_) => Err(APIError::StreamQualityCapped),
+			None => Ok(()),
+		}
+	}
+
+	// Counts the bytes of a response which is not an audio stream, like an archive, in the
+	// transfer statistics of a user
+	pub fn record_transfer(&self, username: &str, response: Response) -> Response {
+		let recorder = TransferRecorder {
+			username: username.to_owned(),
+			bytes_sent: Arc::default(),
+			transfers_manager: self.transfers_manager.clone(),
+		};
+		response.map(|body| {
+			Body::new(CountingBody {
+				body,
+				bytes_sent: recorder.bytes_sent.clone(),
+				_guard: recorder,
+			})
+		})
+	}
+
+	// Oldest first
+	pub fn get_active_streams(&self) -> Vec<ActiveStream> {
+		let streams = self.streams.lock().unwrap();
+		let mut active_streams = streams.entries.iter().collect::<Vec<_>>();
+		active_streams.sort_by_key(|(id, _)| **id);
+		active_streams
+			.into_iter()
+			.map(|(_, e)| ActiveStream {
+				username: e.username.clone(),
+				source: e.source.clone(),
+				client_ip: e.client_ip,
+				started_at: e.started_at,
+				bytes_sent: e.bytes_sent.load(Ordering::Relaxed),
+				total_bytes: e.total_bytes,
+			})
+			.collect()
+	}
+}
+
+pub struct StreamPermit {
+	id: u64,
+	streams: Arc<Mutex<Streams>>,
+	recorder: TransferRecorder,
+}
+
+impl StreamPermit {
+	// Keeps the permit until the response body is fully sent, or the client goes away
+	pub fn attach(self, response: Response) -> Response {
+		let total_bytes = http_body::Body::size_hint(response.body()).exact();
+		if let Some(entry) = self.streams.lock().unwrap().entries.get_mut(&self.id) {
+			entry.total_bytes = total_bytes;
+		}
+		response.map(|body| {
+			Body::new(CountingBody {
+				body,
+				bytes_sent: self.recorder.bytes_sent.clone(),
+				_guard: self,
+			})
+		})
+	}
+}
+
+impl Drop for StreamPermit {
+	fn drop(&mut self) {
+		self.streams.lock().unwrap().entries.remove(&self.id);
+	}
+}
+
+// Adds the bytes sent to a user to their transfer statistics once the response is over
+struct TransferRecorder {
+	username: String,
+	bytes_sent: Arc<AtomicU64>,
+	transfers_manager: transfers::Manager,
+}
+
+impl Drop for TransferRecorder {
+	fn drop(&mut self) {
+		let bytes = self.bytes_sent.load(Ordering::Relaxed);
+		if bytes == 0 {
+			return;
+		}
+		let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+			return;
+		};
+		let username = std::mem::take(&mut self.username);
+		let transfers_manager = self.transfers_manager.clone();
+		runtime.spawn(async move {
+			if let Err(e) = transfers_manager.record_transfer(&username, bytes).await {
+				error!("Could not record transfer statistics: {e}");
+			}
+		});
+	}
+}
+
+// Body which keeps `_guard` alive until it is fully sent, or the client goes away
+struct CountingBody<G> {
+	body: Body,
+	bytes_sent: Arc<AtomicU64>,
+	_guard: G,
+}
+
+impl<G: Send + Unpin + 'static> http_body::Body for CountingBody<G> {
+	type Data = Bytes;
+	type Error = axum::Error;
+
+	fn poll_frame(
+		mut self: Pin<&mut Self>,
+		cx: &mut Context<'_>,
+	) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+		let poll = Pin::new(&mut self.body).poll_frame(cx);
+		if let Poll::Ready(Some(Ok(frame))) = &poll {
+			if let Some(data) = frame.data_ref() {
+				self.bytes_sent
+					.fetch_add(data.len() as u64, Ordering::Relaxed);
+			}
+		}
+		poll
+	}
+
+	fn is_end_stream(&self) -> bool {
+		self.body.is_end_stream()
+	}
+
+	fn size_hint(&self) -> SizeHint {
+		self.body.size_hint()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::app::config::storage;
+	use crate::app::test;
+	use crate::test_name;
+
+	#[tokio::test]
+	async fn stream_limit_applies_per_user() {
+		let ctx = test::ContextBuilder::new(test_name!()).build().await;
+		ctx.config_manager
+			.apply_config(storage::Config {
+				limits: Some(storage::Limits {
+					max_streams_per_user: Some(2),
+					..Default::default()
+				}),
+				..Default::default()
+			})
+			.await
+			.unwrap();
+		let limiter = StreamLimiter::new(ctx.config_manager.clone(), ctx.transfers_manager.clone());
+		let source = StreamSource::Song(PathBuf::from("my_music/destiny.mp3"));
+
+		let first = limiter
+			.acquire("walter", source.clone(), None)
+			.await
+			.unwrap();
+		let _second = limiter
+			.acquire("walter", source.clone(), None)
+			.await
+			.unwrap();
+		assert!(matches!(
+			limiter.acquire("walter", source.clone(), None).await,
+			Err(APIError::TooManyStreams)
+		));
+		assert!(limiter.acquire("jesse", source.clone(), None).await.is_ok());
+
+		drop(first);
+		assert!(limiter.acquire("walter", source, None).await.is_ok());
+	}
+
+	#[tokio::test]
+	async fn transfer_quota_caps_quality() {
+		let ctx = test::ContextBuilder::new(test_name!()).build().await;
+		ctx.config_manager
+			.apply_config(storage::Config {
+				users: vec![storage::User {
+					name: "walter".to_owned(),
+					initial_password: Some("secret".to_owned()),
+					transfer_quota: Some(storage::TransferQuota {
+						monthly_mb: 1,
+						max_bitrate_kbps: 64,
+						codec: None,
+					}),
+					..Default::default()
+				}],
+				..Default::default()
+			})
+			.await
+			.unwrap();
+		let limiter = StreamLimiter::new(ctx.config_manager.clone(), ctx.transfers_manager.clone());
+
+		assert!(limiter.check_original_quality("walter", None).await.is_ok());
+
+		ctx.transfers_manager
+			.record_transfer("walter", 1024 * 1024)
+			.await
+			.unwrap();
+		let quality = limiter.get_stream_quality("walter", None).await.unwrap();
+		assert_eq!(quality.map(|q| q.max_bitrate_kbps), Some(64));
+		assert!(matches!(
+			limiter.check_original_quality("walter", None).await,
+			Err(APIError::StreamQualityCapped)
+		));
+	}
+
+	#[tokio::test]
+	async fn lists_active_streams() {
+		let ctx = test::ContextBuilder::new(test_name!()).build().await;
+		let limiter = StreamLimiter::new(ctx.config_manager.clone(), ctx.transfers_manager.clone());
+		let source = StreamSource::Song(PathBuf::from("my_music/destiny.mp3"));
+		let client_ip = Some(IpAddr::from([192, 168, 1, 20]));
+
+		let permit = limiter
+			.acquire("walter", source.clone(), client_ip)
+			.await
+			.unwrap();
+		let response = permit.attach(Response::new(Body::from(vec![0u8; 100])));
+
+		let streams = limiter.get_active_streams();
+		assert_eq!(streams.len(), 1);
+		assert_eq!(streams[0].username, "walter");
+		assert_eq!(streams[0].source, source);
+		assert_eq!(streams[0].client_ip, client_ip);
+		assert_eq!(streams[0].total_bytes, Some(100));
+		assert_eq!(streams[0].progress(), Some(0.0));
+
+		let mut body = response.into_body().into_data_stream();
+		assert!(tokio_stream::StreamExt::next(&mut body).await.is_some());
+		assert_eq!(limiter.get_active_streams()[0].progress(), Some(1.0));
+
+		drop(body);
+		assert!(limiter.get_active_streams().is_empty());
+	}
+
+	#[tokio::test]
+	async fn connection_limit_defers_new_connections() {
+		let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let address = listener.local_addr().unwrap();
+		let mut listener = LimitedListener::new(listener, ConnectionLimits::new(Some(1), None));
+
+		let _client_a = tokio::net::TcpStream::connect(address).await.unwrap();
+		let _client_b = tokio::net::TcpStream::connect(address).await.unwrap();
+
+		let (connection_a, _) = listener.accept().await;
+		let second_accept =
+			tokio::time::timeout(std::time::Duration::from_millis(200), listener.accept()).await;
+		assert!(second_accept.is_err());
+
+		drop(connection_a);
+		let second_accept =
+			tokio::time::timeout(std::time::Duration::from_secs(5), listener.accept()).await;
+		assert!(second_accept.is_ok());
+	}
+
+	#[tokio::test]
+	async fn connection_limit_applies_per_address() {
+		let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let address = listener.local_addr().unwrap();
+		let mut listener = LimitedListener::new(listener, ConnectionLimits::new(None, Some(1)));
+
+		let _client_a = tokio::net::TcpStream::connect(address).await.unwrap();
+		let (connection_a, _) = listener.accept().await;
+
+		let mut client_b = tokio::net::TcpStream::connect(address).await.unwrap();
+		let second_accept =
+			tokio::time::timeout(std::time::Duration::from_millis(200), listener.accept()).await;
+		assert!(second_accept.is_err());
+		let mut buffer = [0u8; 1];
+		let read = tokio::io::AsyncReadExt::read(&mut client_b, &mut buffer).await;
+		assert!(matches!(read, Ok(0) | Err(_)));
+
+		drop(connection_a);
+		let _client_c = tokio::net::TcpStream::connect(address).await.unwrap();
+		let third_accept =
+			tokio::time::timeout(std::time::Duration::from_secs(5), listener.accept()).await;
+		assert!(third_accept.is_ok());
+	}
+
+	#[tokio::test]
+	async fn idle_connections_release_their_slot() {
+		let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let address = listener.local_addr().unwrap();
+		let mut listener = LimitedListener::new(listener, ConnectionLimits::new(Some(1), None));
+
+		let mut client_a = tokio::net::TcpStream::connect(address).await.unwrap();
+		let _client_b = tokio::net::TcpStream::connect(address).await.unwrap();
+
+		let (mut connection_a, _) = listener.accept().await;
+		tokio::io::AsyncWriteExt::write_all(&mut client_a, b"GET / HTTP/1.1\r\n\r\n")
+			.await
+			.unwrap();
+		let mut buffer = [0u8; 64];
+		tokio::io::AsyncReadExt::read(&mut connection_a, &mut buffer)
+			.await
+			.unwrap();
+		tokio::io::AsyncWriteExt::write_all(&mut connection_a, b"HTTP/1.1 204 No Content\r\n\r\n")
+			.await
+			.unwrap();
+
+		// Connection A waits for its next request without holding a slot
+		let reader = tokio::spawn(async move {
+			let mut buffer = [0u8; 64];
+			let read = tokio::io::AsyncReadExt::read(&mut connection_a, &mut buffer).await;
+			(connection_a, read)
+		});
+		let second_accept =
+			tokio::time::timeout(std::time::Duration::from_secs(5), listener.accept()).await;
+		assert!(second_accept.is_ok());
+
+		// Its next request waits for a slot
+		tokio::io::AsyncWriteExt::write_all(&mut client_a, b"GET / HTTP/1.1\r\n\r\n")
+			.await
+			.unwrap();
+		tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+		assert!(!reader.is_finished());
+
+		drop(second_accept);
+		let (_, read) = reader.await.unwrap();
+		assert!(read.unwrap() > 0);
+	}
+}
@@ -1,12 +1,56 @@
 use axum::{extract::Request, response::Response};
+use http::{HeaderName, HeaderValue};
 use log::{log, Level};
 use std::{
 	future::Future,
 	pin::Pin,
+	sync::{Arc, Mutex},
 	task::{Context, Poll},
+	time::Instant,
 };
 use tower::{Layer, Service};
 
+use super::forwarded::ClientIp;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+struct RequestContext {
+	id: String,
+	user: Mutex<Option<String>>,
+}
+
+tokio::task_local! {
+	static REQUEST: Arc<RequestContext>;
+}
+
+// Identifier of the request currently being handled, for use in downstream log lines
+pub fn current_request_id() -> Option<String> {
+	REQUEST.try_with(|r| r.id.clone()).ok()
+}
+
+// Records which user sent the request currently being handled
+pub fn set_current_user(username: &str) {
+	REQUEST
+		.try_with(|r| *r.user.lock().unwrap() = Some(username.to_owned()))
+		.ok();
+}
+
+// Formats 128 random bits as a version 4 UUID
+fn generate_request_id() -> String {
+	let mut bytes: [u8; 16] = rand::random();
+	bytes[6] = (bytes[6] & 0x0f) | 0x40;
+	bytes[8] = (bytes[8] & 0x3f) | 0x80;
+	let n = u128::from_be_bytes(bytes);
+	format!(
+		"{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+		n >> 96,
+		(n >> 80) & 0xffff,
+		(n >> 64) & 0xffff,
+		(n >> 48) & 0xffff,
+		n & 0xffff_ffff_ffff
+	)
+}
+
 #[derive(Clone)]
 pub struct LogLayer;
 
@@ -44,19 +88,61 @@ where
 	}
 
 	fn call(&mut self, request: Request) -> Self::Future {
+		let start = Instant::now();
 		let path = request.uri().path().to_owned();
 		let method = request.method().clone();
-		let future = self.inner.call(request);
+		let client_ip = request.extensions().get::<ClientIp>().map(|c| c.0);
+		let context = Arc::new(RequestContext {
+			id: generate_request_id(),
+			user: Mutex::default(),
+		});
+		let future = REQUEST.sync_scope(context.clone(), || self.inner.call(request));
 		Box::pin(async move {
-			let response: Response = future.await?;
+			let mut response: Response = REQUEST.scope(context.clone(), future).await?;
 			let status = response.status();
 			let level = if status.is_client_error() || status.is_server_error() {
 				Level::Error
 			} else {
 				Level::Info
 			};
-			log!(level, "[{}] {} {}", response.status(), method, path);
+			let latency_ms = start.elapsed().as_millis() as u64;
+			let user = context.user.lock().unwrap().clone();
+			let request_id = &context.id;
+			log!(
+				level,
+				status = status.as_u16(),
+				method = method.as_str(),
+				path = path.as_str(),
+				client_ip = client_ip.map(|ip| ip.to_string()),
+				latency_ms = latency_ms,
+				user = user.as_deref(),
+				request_id = request_id.as_str();
+				"[{}] {} {} from {} ({})",
+				status,
+				method,
+				path,
+				client_ip.map_or("unknown address".to_owned(), |ip| ip.to_string()),
+				request_id
+			);
+			if let Ok(value) = HeaderValue::from_str(request_id) {
+				response.headers_mut().insert(REQUEST_ID_HEADER, value);
+			}
 			Ok(response)
 		})
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn request_ids_are_uuids() {
+		let id = generate_request_id();
+		let groups = id.split('-').map(|g| g.len()).collect::<Vec<_>>();
+		assert_eq!(groups, vec![8, 4, 4, 4, 12]);
+		assert!(id.chars().all(|c| c == '-' || c.is_ascii_hexdigit()));
+		assert_eq!(id.chars().nth(14), Some('4'));
+		assert_ne!(id, generate_request_id());
+	}
+}
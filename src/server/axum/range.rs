@@ -0,0 +1,334 @@
+use std::future::Future;
+use std::io::SeekFrom;
+use std::ops::RangeInclusive;
+
+use axum::{
+	body::Body,
+	response::{IntoResponse, Response},
+};
+use axum_extra::headers;
+use axum_range::{KnownSize, Ranged};
+use http::{header, HeaderMap, HeaderValue, StatusCode};
+use log::error;
+use rand::{distributions::Alphanumeric, Rng};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, DuplexStream};
+use tokio_util::io::ReaderStream;
+
+// Requests for more ranges than this are answered with the full file
+const MAX_RANGES: usize = 64;
+
+// Size of the buffer between the task reading multipart ranges and the response body
+const PIPE_SIZE: usize = 64 * 1024;
+
+// Content which can write any of its byte ranges to a response body
+pub trait Source: Send + 'static {
+	fn write_range(
+		&mut self,
+		range: RangeInclusive<u64>,
+		writer: &mut DuplexStream,
+	) -> impl Future<Output = std::io::Result<()>> + Send;
+}
+
+impl Source for tokio::fs::File {
+	async fn write_range(
+		&mut self,
+		range: RangeInclusive<u64>,
+		writer: &mut DuplexStream,
+	) -> std::io::Result<()> {
+		self.seek(SeekFrom::Start(*range.start())).await?;
+		let length = range.end() - range.start() + 1;
+		let copied = tokio::io::copy(&mut (&mut *self).take(length), writer).await?;
+		if copied != length {
+			return Err(std::io::ErrorKind::UnexpectedEof.into());
+		}
+		Ok(())
+	}
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ByteRanges {
+	Full,
+	Unsatisfiable,
+	Partial(Vec<RangeInclusive<u64>>),
+}
+
+// Interprets a `Range` header against a file of the given length, following RFC 7233.
+// Malformed headers are ignored, as required by section 3.1.
+pub fn parse(header: Option<&HeaderValue>, length: u64) -> ByteRanges {
+	let Some(specs) = header
+		.and_then(|h| h.to_str().ok())
+		.and_then(|h| h.trim().strip_prefix("bytes="))
+	else {
+		return ByteRanges::Full;
+	};
+
+	let mut ranges = vec![];
+	for spec in specs.split(',') {
+		let spec = spec.trim();
+		if spec.is_empty() {
+			continue;
+		}
+		let Some((first, last)) = spec.split_once('-') else {
+			return ByteRanges::Full;
+		};
+		let first = match first.trim() {
+			"" => None,
+			f => match f.parse::<u64>() {
+				Ok(f) => Some(f),
+				Err(_) => return ByteRanges::Full,
+			},
+		};
+		let last = match last.trim() {
+			"" => None,
+			l => match l.parse::<u64>() {
+				Ok(l) => Some(l),
+				Err(_) => return ByteRanges::Full,
+			},
+		};
+		let range = match (first, last) {
+			(Some(first), Some(last)) if last < first => return ByteRanges::Full,
+			(Some(first), last) if first < length => {
+				first..=last.map_or(length - 1, |l| l.min(length - 1))
+			}
+			// Suffix ranges longer than the file select the whole file
+			(None, Some(suffix)) if suffix > 0 && length > 0 => {
+				length.saturating_sub(suffix)..=length - 1
+			}
+			(None, None) => return ByteRanges::Full,
+			_ => continue,
+		};
+		ranges.push(range);
+	}
+
+	if ranges.len() > MAX_RANGES {
+		return ByteRanges::Full;
+	}
+
+	match coalesce(ranges) {
+		r if r.is_empty() => ByteRanges::Unsatisfiable,
+		r => ByteRanges::Partial(r),
+	}
+}
+
+// Merges overlapping or adjacent ranges
+fn coalesce(mut ranges: Vec<RangeInclusive<u64>>) -> Vec<RangeInclusive<u64>> {
+	ranges.sort_by_key(|r| *r.start());
+	let mut merged: Vec<RangeInclusive<u64>> = vec![];
+	for range in ranges {
+		match merged.last_mut() {
+			Some(last) if *range.start() <= last.end().saturating_add(1) => {
+				*last = *last.start()..=*last.end().max(range.end());
+			}
+			_ => merged.push(range),
+		}
+	}
+	merged
+}
+
+pub async fn respond(
+	file: tokio::fs::File,
+	length: u64,
+	request_headers: &HeaderMap,
+	response_headers: HeaderMap,
+) -> std::io::Result<Response> {
+	let ranges = match parse(request_headers.get(header::RANGE), length) {
+		ByteRanges::Full => {
+			let body = KnownSize::file(file).await?;
+			return Ok((response_headers, Ranged::new(None, body)).into_response());
+		}
+		ByteRanges::Unsatisfiable => return Ok(unsatisfiable(length, response_headers)),
+		ByteRanges::Partial(ranges) => ranges,
+	};
+
+	if let [range] = ranges.as_slice() {
+		let range = headers::Range::bytes(range.clone()).ok();
+		let body = KnownSize::file(file).await?;
+		return Ok((response_headers, Ranged::new(range, body)).into_response());
+	}
+
+	Ok(respond_multipart(file, length, ranges, response_headers))
+}
+
+// Serves content which is produced while it is being sent, such as archives
+pub fn respond_streamed<S: Source>(
+	source: S,
+	length: u64,
+	request_headers: &HeaderMap,
+	mut response_headers: HeaderMap,
+) -> Response {
+	response_headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+	let (status, range) = match parse(request_headers.get(header::RANGE), length) {
+		ByteRanges::Full if length == 0 => {
+			response_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(0));
+			return (response_headers, Body::empty()).into_response();
+		}
+		ByteRanges::Full => (StatusCode::OK, 0..=length - 1),
+		ByteRanges::Unsatisfiable => return unsatisfiable(length, response_headers),
+		ByteRanges::Partial(ranges) if ranges.len() > 1 => {
+			return respond_multipart(source, length, ranges, response_headers)
+		}
+		ByteRanges::Partial(mut ranges) => {
+			let range = ranges.remove(0);
+			response_headers.insert(
+				header::CONTENT_RANGE,
+				HeaderValue::from_str(&format!("bytes {}-{}/{length}", range.start(), range.end()))
+					.unwrap(),
+			);
+			(StatusCode::PARTIAL_CONTENT, range)
+		}
+	};
+
+	let content_length = range.end() - range.start() + 1;
+	response_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(content_length));
+	let parts = vec![(String::new(), range)];
+	let body = spawn_parts(source, parts, None);
+	(status, response_headers, body).into_response()
+}
+
+fn unsatisfiable(length: u64, mut response_headers: HeaderMap) -> Response {
+	response_headers.insert(
+		header::CONTENT_RANGE,
+		HeaderValue::from_str(&format!("bytes */{length}")).unwrap(),
+	);
+	(StatusCode::RANGE_NOT_SATISFIABLE, response_headers).into_response()
+}
+
+fn respond_multipart<S: Source>(
+	source: S,
+	length: u64,
+	ranges: Vec<RangeInclusive<u64>>,
+	mut response_headers: HeaderMap,
+) -> Response {
+	let boundary: String = rand::thread_rng()
+		.sample_iter(&Alphanumeric)
+		.take(32)
+		.map(char::from)
+		.collect();
+
+	// Parts are streamed from the source, so requests for many large ranges do not buffer them
+	let parts = ranges
+		.into_iter()
+		.map(|range| {
+			let header = format!(
+				"--{boundary}\r\nContent-Type: application/octet-stream\r\nContent-Range: bytes {}-{}/{length}\r\n\r\n",
+				range.start(),
+				range.end()
+			);
+			(header, range)
+		})
+		.collect::<Vec<_>>();
+	let trailer = format!("--{boundary}--\r\n");
+	let content_length = parts
+		.iter()
+		.map(|(header, range)| header.len() as u64 + (range.end() - range.start() + 1) + 2)
+		.sum::<u64>()
+		+ trailer.len() as u64;
+
+	response_headers.insert(
+		header::CONTENT_TYPE,
+		HeaderValue::from_str(&format!("multipart/byteranges; boundary={boundary}")).unwrap(),
+	);
+	response_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(content_length));
+	response_headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+	let body = spawn_parts(source, parts, Some(trailer));
+	(StatusCode::PARTIAL_CONTENT, response_headers, body).into_response()
+}
+
+// Parts are followed by a line break when they are delimited by a multipart trailer
+fn spawn_parts<S: Source>(
+	source: S,
+	parts: Vec<(String, RangeInclusive<u64>)>,
+	trailer: Option<String>,
+) -> Body {
+	let (writer, reader) = tokio::io::duplex(PIPE_SIZE);
+	tokio::spawn(async move {
+		if let Err(e) = write_parts(source, parts, trailer, writer).await {
+			error!("Could not write response body: {e}");
+		}
+	});
+	Body::from_stream(ReaderStream::new(reader))
+}
+
+async fn write_parts<S: Source>(
+	mut source: S,
+	parts: Vec<(String, RangeInclusive<u64>)>,
+	trailer: Option<String>,
+	mut writer: DuplexStream,
+) -> std::io::Result<()> {
+	for (header, range) in parts {
+		writer.write_all(header.as_bytes()).await?;
+		source.write_range(range, &mut writer).await?;
+		if trailer.is_some() {
+			writer.write_all(b"\r\n").await?;
+		}
+	}
+	if let Some(trailer) = trailer {
+		writer.write_all(trailer.as_bytes()).await?;
+	}
+	writer.shutdown().await
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn parse_str(header: &str, length: u64) -> ByteRanges {
+		parse(Some(&HeaderValue::from_str(header).unwrap()), length)
+	}
+
+	#[test]
+	fn can_parse_single_range() {
+		assert_eq!(
+			parse_str("bytes=100-299", 1000),
+			ByteRanges::Partial(vec![RangeInclusive::new(100, 299)])
+		);
+		assert_eq!(
+			parse_str("bytes=900-", 1000),
+			ByteRanges::Partial(vec![RangeInclusive::new(900, 999)])
+		);
+		assert_eq!(
+			parse_str("bytes=900-5000", 1000),
+			ByteRanges::Partial(vec![RangeInclusive::new(900, 999)])
+		);
+	}
+
+	#[test]
+	fn can_parse_suffix_range() {
+		assert_eq!(
+			parse_str("bytes=-100", 1000),
+			ByteRanges::Partial(vec![RangeInclusive::new(900, 999)])
+		);
+		assert_eq!(
+			parse_str("bytes=-5000", 1000),
+			ByteRanges::Partial(vec![RangeInclusive::new(0, 999)])
+		);
+		assert_eq!(parse_str("bytes=-0", 1000), ByteRanges::Unsatisfiable);
+	}
+
+	#[test]
+	fn can_parse_multiple_ranges() {
+		assert_eq!(
+			parse_str("bytes=0-9, -10", 1000),
+			ByteRanges::Partial(vec![0..=9, 990..=999])
+		);
+		assert_eq!(
+			parse_str("bytes=0-9,5-20,21-30,2000-", 1000),
+			ByteRanges::Partial(vec![RangeInclusive::new(0, 30)])
+		);
+	}
+
+	#[test]
+	fn rejects_unsatisfiable_ranges() {
+		assert_eq!(parse_str("bytes=1000-", 1000), ByteRanges::Unsatisfiable);
+		assert_eq!(parse_str("bytes=-10", 0), ByteRanges::Unsatisfiable);
+	}
+
+	#[test]
+	fn ignores_malformed_ranges() {
+		assert_eq!(parse(None, 1000), ByteRanges::Full);
+		assert_eq!(parse_str("items=0-10", 1000), ByteRanges::Full);
+		assert_eq!(parse_str("bytes=10-0", 1000), ByteRanges::Full);
+		assert_eq!(parse_str("bytes=abc", 1000), ByteRanges::Full);
+		assert_eq!(parse_str("bytes=-", 1000), ByteRanges::Full);
+	}
+}
@@ -1,3 +1,5 @@
+use std::any::Any;
+
 use axum::body::Bytes;
 use axum_test::TestServer;
 use http::{response::Builder, Method, Request, Response};
@@ -7,6 +9,7 @@ use crate::app::App;
 use crate::paths::Paths;
 use crate::server::axum::*;
 use crate::server::dto;
+use crate::server::test::protocol::RawBody;
 use crate::server::test::TestService;
 use crate::test::*;
 
@@ -19,7 +22,12 @@ pub type ServiceType = AxumTestService;
 
 impl TestService for AxumTestService {
 	async fn new(test_name: &str) -> Self {
+		Self::new_with_config(test_name, "").await
+	}
+
+	async fn new_with_config(test_name: &str, config: &str) -> Self {
 		let output_dir = prepare_test_directory(test_name);
+		std::fs::write(output_dir.join("polaris.toml"), config).unwrap();
 
 		let paths = Paths {
 			cache_dir_path: ["test-output", test_name].iter().collect(),
@@ -33,7 +41,7 @@ impl TestService for AxumTestService {
 		};
 
 		let app = App::new(5050, paths).await.unwrap();
-		let router = make_router(app);
+		let router = make_router(app).await;
 		let make_service = ServiceExt::<axum::extract::Request>::into_make_service(router);
 		let server = TestServer::new(make_service).unwrap();
 
@@ -55,7 +63,7 @@ impl TestService for AxumTestService {
 			Method::POST => self.server.post(&url),
 			Method::PUT => self.server.put(&url),
 			Method::DELETE => self.server.delete(&url),
-			_ => unimplemented!(),
+			ref method => self.server.method(method.clone(), &url),
 		};
 
 		for (name, value) in request.headers() {
@@ -66,7 +74,10 @@ impl TestService for AxumTestService {
 			axum_request = axum_request.authorization_bearer(authorization.token.clone());
 		}
 
-		let axum_response = axum_request.json(&body).await;
+		let axum_response = match (&body as &dyn Any).downcast_ref::<RawBody>() {
+			Some(RawBody(bytes)) => axum_request.bytes(Bytes::from(bytes.clone())).await,
+			None => axum_request.json(&body).await,
+		};
 
 		let mut response_builder = Response::builder().status(axum_response.status_code());
 		let headers = response_builder.headers_mut().unwrap();
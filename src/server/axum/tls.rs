@@ -0,0 +1,46 @@
+use std::net::SocketAddr;
+
+use axum::serve::Listener;
+use log::error;
+use tokio_stream::StreamExt;
+
+use crate::app::acme;
+
+pub struct AcmeListener {
+	incoming: acme::Incoming,
+	local_addr: SocketAddr,
+}
+
+impl AcmeListener {
+	pub fn new(incoming: acme::Incoming, local_addr: SocketAddr) -> Self {
+		Self {
+			incoming,
+			local_addr,
+		}
+	}
+}
+
+impl Listener for AcmeListener {
+	type Io = acme::TlsStream;
+	type Addr = SocketAddr;
+
+	async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+		loop {
+			match self.incoming.next().await {
+				Some(Ok(stream)) => {
+					let (tcp, _) = stream.get_ref().get_ref();
+					match tcp.get_ref().peer_addr() {
+						Ok(address) => return (stream, address),
+						Err(e) => error!("Could not read TLS connection address: {e}"),
+					}
+				}
+				Some(Err(e)) => error!("TLS connection error: {e}"),
+				None => std::future::pending().await,
+			}
+		}
+	}
+
+	fn local_addr(&self) -> tokio::io::Result<Self::Addr> {
+		Ok(self.local_addr)
+	}
+}
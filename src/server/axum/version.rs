@@ -1,8 +1,35 @@
 use axum::extract::FromRequestParts;
-use http::request::Parts;
+use http::{request::Parts, Extensions, HeaderMap};
 
 use crate::server::{error::APIError, APIMajorVersion};
 
+// API version of requests made under a versioned path prefix like `/api/v8`
+#[derive(Clone, Copy)]
+pub struct PinnedVersion(pub APIMajorVersion);
+
+// Determines which API version a request expects, from its path prefix or `Accept-Version` header
+pub fn requested_version(
+	extensions: &Extensions,
+	headers: &HeaderMap,
+) -> Result<APIMajorVersion, APIError> {
+	if let Some(PinnedVersion(version)) = extensions.get::<PinnedVersion>() {
+		return Ok(*version);
+	}
+
+	let version_header = match headers.get("Accept-Version").map(|h| h.to_str()) {
+		Some(Ok(h)) => h,
+		Some(Err(_)) => return Err(APIError::InvalidAPIVersionHeader),
+		None => return Ok(APIMajorVersion::V7), // TODO Drop support for implicit version in future release
+	};
+
+	let version = match str::parse::<i32>(version_header) {
+		Ok(v) => v,
+		Err(_) => return Err(APIError::APIVersionHeaderParseError),
+	};
+
+	APIMajorVersion::try_from(version)
+}
+
 impl<S> FromRequestParts<S> for APIMajorVersion
 where
 	S: Send + Sync,
@@ -10,17 +37,6 @@ where
 	type Rejection = APIError;
 
 	async fn from_request_parts(parts: &mut Parts, _app: &S) -> Result<Self, Self::Rejection> {
-		let version_header = match parts.headers.get("Accept-Version").map(|h| h.to_str()) {
-			Some(Ok(h)) => h,
-			Some(Err(_)) => return Err(APIError::InvalidAPIVersionHeader),
-			None => return Ok(APIMajorVersion::V7), // TODO Drop support for implicit version in future release
-		};
-
-		let version = match str::parse::<i32>(version_header) {
-			Ok(v) => v,
-			Err(_) => return Err(APIError::APIVersionHeaderParseError),
-		};
-
-		APIMajorVersion::try_from(version)
+		requested_version(&parts.extensions, &parts.headers)
 	}
 }
@@ -0,0 +1,323 @@
+use std::{
+	fmt::Write,
+	path::{Component, Path, PathBuf},
+	time::SystemTime,
+};
+
+use axum::{
+	extract::{self, FromRef, FromRequestParts, OriginalUri, State},
+	response::{IntoResponse, Response},
+	routing::any,
+	Extension, Router,
+};
+use axum_extra::headers::{authorization::Basic, Authorization, HeaderMapExt, LastModified};
+use http::{header, request::Parts, HeaderMap, HeaderName, HeaderValue, Method, StatusCode};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+use crate::app::{self, api_key, auth, config, session, App};
+use crate::server::error::APIError;
+
+use super::{auth::Auth, conditional, forwarded::ClientIp, limits, logger};
+
+const DAV: HeaderName = HeaderName::from_static("dav");
+const DEPTH: HeaderName = HeaderName::from_static("depth");
+const ALLOWED_METHODS: &str = "OPTIONS, GET, HEAD, PROPFIND";
+
+// Characters left as-is in hrefs, as recommended by RFC 3986 §2.3
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+	.remove(b'-')
+	.remove(b'.')
+	.remove(b'_')
+	.remove(b'~');
+
+// Read-only view of the mount directories, for file managers and WebDAV-capable players
+pub fn router(stream_limiter: limits::StreamLimiter) -> Router<App> {
+	Router::new()
+		.route("/", any(handle_root))
+		.route("/{*path}", any(handle_path))
+		.layer(Extension(stream_limiter))
+}
+
+// WebDAV clients generally only support HTTP authentication schemes, so Basic credentials are
+// accepted alongside regular Polaris auth tokens.
+pub struct DavAuth {
+	username: String,
+}
+
+impl<S> FromRequestParts<S> for DavAuth
+where
+	api_key::Manager: FromRef<S>,
+	config::Manager: FromRef<S>,
+	session::Manager: FromRef<S>,
+	S: Send + Sync,
+{
+	type Rejection = Response;
+
+	async fn from_request_parts(parts: &mut Parts, app: &S) -> Result<Self, Self::Rejection> {
+		let basic = parts
+			.headers
+			.typed_get::<Authorization<Basic>>()
+			.map(|a| a.0);
+
+		let username = match basic {
+			Some(basic) => {
+				let session_manager = session::Manager::from_ref(app);
+				let address = parts.extensions.get::<ClientIp>().map(|c| c.0);
+				match session_manager
+					.check_password(basic.username(), basic.password(), address)
+					.await
+				{
+					Ok(()) => {
+						logger::set_current_user(basic.username());
+						Some(basic.username().to_owned())
+					}
+					Err(e @ app::Error::LoginThrottled(_)) => {
+						return Err(APIError::from(e).into_response())
+					}
+					Err(_) => None,
+				}
+			}
+			None => Auth::from_request_parts(parts, app)
+				.await
+				.ok()
+				.map(|a| a.get_username().clone()),
+		};
+
+		match username {
+			Some(username) => Ok(DavAuth { username }),
+			None => Err((
+				StatusCode::UNAUTHORIZED,
+				[(header::WWW_AUTHENTICATE, r#"Basic realm="Polaris""#)],
+			)
+				.into_response()),
+		}
+	}
+}
+
+struct Entry {
+	href: String,
+	name: String,
+	is_collection: bool,
+	length: Option<u64>,
+	modified: Option<SystemTime>,
+}
+
+impl Entry {
+	fn new(href: String, name: String, metadata: Option<&std::fs::Metadata>) -> Self {
+		Self {
+			href,
+			name,
+			is_collection: metadata.is_none_or(|m| m.is_dir()),
+			length: metadata.filter(|m| m.is_file()).map(|m| m.len()),
+			modified: metadata.and_then(|m| m.modified().ok()),
+		}
+	}
+}
+
+async fn handle_root(
+	_auth: DavAuth,
+	State(config_manager): State<config::Manager>,
+	OriginalUri(uri): OriginalUri,
+	method: Method,
+	headers: HeaderMap,
+) -> Response {
+	let href = uri.path().trim_end_matches('/');
+	match method.as_str() {
+		"OPTIONS" => options(),
+		"PROPFIND" => {
+			let mut entries = vec![Entry::new(format!("{href}/"), String::new(), None)];
+			if depth(&headers) > 0 {
+				for mount in config_manager.get_mounts().await {
+					let metadata = tokio::fs::metadata(&mount.source).await.ok();
+					let href = format!("{href}/{}/", encode(&mount.name));
+					entries.push(Entry::new(href, mount.name, metadata.as_ref()));
+				}
+			}
+			multi_status(&entries)
+		}
+		_ => method_not_allowed(),
+	}
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn handle_path(
+	auth: DavAuth,
+	State(config_manager): State<config::Manager>,
+	Extension(stream_limiter): Extension<limits::StreamLimiter>,
+	OriginalUri(uri): OriginalUri,
+	extract::Path(virtual_path): extract::Path<PathBuf>,
+	method: Method,
+	headers: HeaderMap,
+	client_ip: Option<Extension<ClientIp>>,
+) -> Response {
+	if method == Method::OPTIONS {
+		return options();
+	}
+
+	// Paths escaping their mount directory are treated as missing
+	let is_normal = virtual_path
+		.components()
+		.all(|c| matches!(c, Component::Normal(_)));
+	let real_path = match config_manager.resolve_virtual_path(&virtual_path).await {
+		Ok(p) if is_normal => p,
+		_ => return StatusCode::NOT_FOUND.into_response(),
+	};
+	let Ok(metadata) = tokio::fs::metadata(&real_path).await else {
+		return StatusCode::NOT_FOUND.into_response();
+	};
+
+	match method.as_str() {
+		"PROPFIND" => {
+			let href = uri.path().trim_end_matches('/');
+			let name = file_name(&virtual_path);
+			if !metadata.is_dir() {
+				let entry = Entry::new(href.to_owned(), name, Some(&metadata));
+				return multi_status(&[entry]);
+			}
+			let mut entries = vec![Entry::new(format!("{href}/"), name, Some(&metadata))];
+			if depth(&headers) > 0 {
+				match list_directory(&real_path, href).await {
+					Ok(children) => entries.extend(children),
+					Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+				}
+			}
+			multi_status(&entries)
+		}
+		"GET" | "HEAD" if metadata.is_file() => {
+			// Files read over WebDAV can be copied as they are
+			let can_download = config_manager
+				.get_user(&auth.username)
+				.await
+				.is_ok_and(|u| u.has_permission(auth::Permission::Download));
+			if !can_download {
+				return StatusCode::FORBIDDEN.into_response();
+			}
+			// Transcoding would not match the length of files listed by PROPFIND
+			let client_ip = client_ip.map(|Extension(ClientIp(ip))| ip);
+			if let Err(e) = stream_limiter
+				.check_original_quality(&auth.username, client_ip)
+				.await
+			{
+				return e.into_response();
+			}
+			let Ok(file) = tokio::fs::File::open(&real_path).await else {
+				return StatusCode::NOT_FOUND.into_response();
+			};
+			match conditional::serve_file(file, &headers).await {
+				Ok(response) => stream_limiter.record_transfer(&auth.username, response),
+				Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+			}
+		}
+		_ => method_not_allowed(),
+	}
+}
+
+async fn list_directory(real_path: &Path, href: &str) -> std::io::Result<Vec<Entry>> {
+	let mut entries = vec![];
+	let mut read_dir = tokio::fs::read_dir(real_path).await?;
+	while let Some(child) = read_dir.next_entry().await? {
+		let Ok(metadata) = tokio::fs::metadata(child.path()).await else {
+			continue;
+		};
+		let name = child.file_name().to_string_lossy().to_string();
+		let separator = if metadata.is_dir() { "/" } else { "" };
+		let href = format!("{href}/{}{separator}", encode(&name));
+		entries.push(Entry::new(href, name, Some(&metadata)));
+	}
+	entries.sort_by(|a, b| a.href.cmp(&b.href));
+	Ok(entries)
+}
+
+// Listings are limited to immediate children, requests for an infinite depth are answered as if
+// they asked for a depth of 1.
+fn depth(headers: &HeaderMap) -> u8 {
+	match headers.get(DEPTH).and_then(|d| d.to_str().ok()) {
+		Some("0") => 0,
+		_ => 1,
+	}
+}
+
+fn file_name(virtual_path: &Path) -> String {
+	virtual_path
+		.file_name()
+		.map(|n| n.to_string_lossy().to_string())
+		.unwrap_or_default()
+}
+
+fn encode(segment: &str) -> String {
+	utf8_percent_encode(segment, PATH_SEGMENT).to_string()
+}
+
+fn escape(text: &str) -> String {
+	text.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+		.replace('"', "&quot;")
+		.replace('\'', "&apos;")
+}
+
+fn http_date(time: SystemTime) -> Option<String> {
+	let mut headers = HeaderMap::new();
+	headers.typed_insert(LastModified::from(time));
+	let value = headers.get(header::LAST_MODIFIED)?.to_str().ok()?;
+	Some(value.to_owned())
+}
+
+fn multi_status(entries: &[Entry]) -> Response {
+	let mut body =
+		String::from(r#"<?xml version="1.0" encoding="utf-8"?><D:multistatus xmlns:D="DAV:">"#);
+	for entry in entries {
+		body.push_str("<D:response>");
+		write!(body, "<D:href>{}</D:href>", escape(&entry.href)).ok();
+		body.push_str("<D:propstat><D:prop>");
+		write!(
+			body,
+			"<D:displayname>{}</D:displayname>",
+			escape(&entry.name)
+		)
+		.ok();
+		if entry.is_collection {
+			body.push_str("<D:resourcetype><D:collection/></D:resourcetype>");
+		} else {
+			body.push_str("<D:resourcetype/>");
+		}
+		if let Some(length) = entry.length {
+			write!(body, "<D:getcontentlength>{length}</D:getcontentlength>").ok();
+		}
+		if let Some(modified) = entry.modified.and_then(http_date) {
+			write!(body, "<D:getlastmodified>{modified}</D:getlastmodified>").ok();
+		}
+		body.push_str("</D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat>");
+		body.push_str("</D:response>");
+	}
+	body.push_str("</D:multistatus>");
+
+	(
+		StatusCode::MULTI_STATUS,
+		[(
+			header::CONTENT_TYPE,
+			HeaderValue::from_static("application/xml; charset=utf-8"),
+		)],
+		body,
+	)
+		.into_response()
+}
+
+fn options() -> Response {
+	(
+		StatusCode::OK,
+		[
+			(DAV, HeaderValue::from_static("1")),
+			(header::ALLOW, HeaderValue::from_static(ALLOWED_METHODS)),
+		],
+	)
+		.into_response()
+}
+
+fn method_not_allowed() -> Response {
+	(
+		StatusCode::METHOD_NOT_ALLOWED,
+		[(header::ALLOW, ALLOWED_METHODS)],
+	)
+		.into_response()
+}
@@ -43,6 +43,14 @@ pub fn open_api() -> OpenApi {
 			.name("Playlists")
 			.description(Some("These endpoints allow users to create, retrieve, update or delete playlists."))
 			.build(),
+            TagBuilder::new()
+			.name("Radio")
+			.description(Some("These endpoints list internet radio stations and relay their audio. Stations can only be added, edited or removed by administrators."))
+			.build(),
+            TagBuilder::new()
+			.name("Jukebox")
+			.description(Some("These endpoints control songs played through the audio output of the server. They are only available when the jukebox is enabled in the configuration file."))
+			.build(),
         ]))
 		.components(Some(
 			ComponentsBuilder::new()
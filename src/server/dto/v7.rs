@@ -106,6 +106,10 @@ impl From<MountDir> for config::storage::MountDir {
 		Self {
 			name: m.name,
 			source: m.source,
+			exclude_patterns: vec![],
+			symlinks: None,
+			split_artists: None,
+			remote: None,
 		}
 	}
 }
@@ -1,7 +1,11 @@
 use serde::{Deserialize, Serialize};
 use utoipa::{IntoParams, ToSchema};
 
-use crate::app::{config, index, peaks, playlist, scanner, thumbnail};
+use crate::app::{
+	acoustid, api_key, audit, auth, config, device_login, duplicates, events, history, index,
+	invite, jukebox, listenbrainz, lyrics, peaks, playlist, preferences, quality, queue, radio,
+	scanner, session, stats, thumbnail, transfers, webauthn,
+};
 use std::{collections::HashMap, convert::From, path::PathBuf, time::UNIX_EPOCH};
 
 #[derive(PartialEq, Eq, Debug, Serialize, Deserialize, ToSchema)]
@@ -12,6 +16,24 @@ pub struct Version {
 	pub minor: i32,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Health {
+	#[schema(examples(true, false))]
+	pub healthy: bool,
+	pub components: Vec<HealthComponent>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct HealthComponent {
+	#[schema(examples("config_file", "mount_dir:my_music", "index"))]
+	pub name: String,
+	#[schema(examples(true, false))]
+	pub healthy: bool,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples("Directory is not reachable"))]
+	pub error: Option<String>,
+}
+
 #[derive(PartialEq, Eq, Debug, Serialize, Deserialize, ToSchema)]
 pub struct InitialSetup {
 	#[schema(examples(true, false))]
@@ -24,6 +46,30 @@ pub struct Credentials {
 	pub username: String,
 	#[schema(examples("secret_password!!"))]
 	pub password: String,
+	/// Replaces the current password before signing in
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples("new_secret_password!!"))]
+	pub new_password: Option<String>,
+	/// Name of the device signing in, listed alongside its session
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples("Living room speakers"))]
+	pub client_name: Option<String>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, IntoParams, ToSchema)]
+pub struct OidcLoginParameters {
+	/// Path within the Polaris server to send users back to after signing in
+	#[schema(examples("/"))]
+	pub return_to: Option<String>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, IntoParams, ToSchema)]
+pub struct OidcCallbackParameters {
+	pub code: Option<String>,
+	pub state: Option<String>,
+	/// Error code reported by the identity provider
+	pub error: Option<String>,
+	pub error_description: Option<String>,
 }
 
 #[derive(Clone, Serialize, Deserialize, ToSchema)]
@@ -38,6 +84,54 @@ pub struct Authorization {
 	pub is_admin: bool,
 }
 
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct DeviceLoginInput {
+	/// Name of the device, listed alongside the session once it is signed in
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples("Living room TV"))]
+	pub client_name: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct DeviceLogin {
+	/// Secret the device uses to retrieve its token. It should not be displayed.
+	#[schema(examples("XKfgVbfQ4uW9A0QHXmBKhYx7kYQpzhCu"))]
+	pub device_code: String,
+	/// Code the device displays, for users to approve it from a client where they are signed in
+	#[schema(examples("BKTM-QWRZ"))]
+	pub user_code: String,
+	/// Number of seconds after which the codes expire
+	#[schema(examples(600))]
+	pub expires_in: u64,
+	/// Minimum number of seconds devices should wait between two token requests
+	#[schema(examples(5))]
+	pub interval: u64,
+}
+
+impl From<device_login::DeviceLogin> for DeviceLogin {
+	fn from(l: device_login::DeviceLogin) -> Self {
+		Self {
+			device_code: l.device_code,
+			user_code: l.user_code,
+			expires_in: device_login::LOGIN_TIMEOUT.as_secs(),
+			interval: device_login::POLL_INTERVAL.as_secs(),
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct DeviceLoginApproval {
+	/// Code displayed by the device. Case, spaces and dashes are ignored.
+	#[schema(examples("BKTM-QWRZ"))]
+	pub user_code: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct DeviceTokenInput {
+	#[schema(examples("XKfgVbfQ4uW9A0QHXmBKhYx7kYQpzhCu"))]
+	pub device_code: String,
+}
+
 #[derive(Clone, Serialize, Deserialize, ToSchema)]
 pub struct AuthQueryParameters {
 	#[schema(
@@ -120,25 +214,678 @@ pub struct Playlist {
 	pub songs: SongList,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Session {
+	#[schema(examples("Qx8bz0a1Ee9tTKkUzu1qgPjRsFkXyV3m"))]
+	pub id: String,
+	/// Name given by the client when signing in
+	#[schema(examples("Living room speakers"))]
+	pub client_name: Option<String>,
+	#[schema(examples("Mozilla/5.0 (X11; Linux x86_64; rv:131.0) Gecko/20100101 Firefox/131.0"))]
+	pub user_agent: Option<String>,
+	/// Address the session was last used from
+	#[schema(examples("192.168.1.20"))]
+	pub ip_address: Option<String>,
+	/// Time at which the session was created, as a UNIX timestamp
+	#[schema(examples(1729099000))]
+	pub created_at: i64,
+	/// Time at which the session was last used, as a UNIX timestamp
+	#[schema(examples(1729185400))]
+	pub last_seen_at: i64,
+	/// Whether this is the session making the request
+	pub current: bool,
+}
+
+impl From<session::Session> for Session {
+	fn from(s: session::Session) -> Self {
+		Self {
+			id: s.id,
+			client_name: s.client_name,
+			user_agent: s.user_agent,
+			ip_address: s.ip_address,
+			created_at: s.created_at,
+			last_seen_at: s.last_seen_at,
+			current: false,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiKeyScope {
+	/// Allows GET and HEAD requests
+	Read,
+	/// Allows all other requests
+	Write,
+	/// Allows requests requiring administrator permission
+	Admin,
+}
+
+impl From<api_key::Scope> for ApiKeyScope {
+	fn from(s: api_key::Scope) -> Self {
+		match s {
+			api_key::Scope::Read => Self::Read,
+			api_key::Scope::Write => Self::Write,
+			api_key::Scope::Admin => Self::Admin,
+		}
+	}
+}
+
+impl From<ApiKeyScope> for api_key::Scope {
+	fn from(s: ApiKeyScope) -> Self {
+		match s {
+			ApiKeyScope::Read => Self::Read,
+			ApiKeyScope::Write => Self::Write,
+			ApiKeyScope::Admin => Self::Admin,
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ApiKey {
+	#[schema(examples("mV3kq8PzR1cXa9Lw"))]
+	pub id: String,
+	#[schema(examples("Scrobbling bridge"))]
+	pub name: String,
+	pub scopes: Vec<ApiKeyScope>,
+	/// Time at which the key was created, as a UNIX timestamp
+	#[schema(examples(1729099000))]
+	pub created_at: i64,
+	/// Time at which the key was last used, as a UNIX timestamp
+	#[schema(examples(1729185400))]
+	pub last_used_at: Option<i64>,
+}
+
+impl From<api_key::ApiKey> for ApiKey {
+	fn from(k: api_key::ApiKey) -> Self {
+		Self {
+			id: k.id,
+			name: k.name,
+			scopes: k.scopes.into_iter().map(ApiKeyScope::from).collect(),
+			created_at: k.created_at,
+			last_used_at: k.last_used_at,
+		}
+	}
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct NewApiKey {
+	#[schema(examples("Scrobbling bridge"))]
+	pub name: String,
+	pub scopes: Vec<ApiKeyScope>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Passkey {
+	/// Credential ID, encoded as unpadded base64url
+	#[schema(examples("dGVzdC1jcmVkZW50aWFs"))]
+	pub id: String,
+	#[schema(examples("Laptop"))]
+	pub name: String,
+	/// Time at which the passkey was registered, as a UNIX timestamp
+	#[schema(examples(1729099000))]
+	pub created_at: u64,
+}
+
+impl From<config::storage::Passkey> for Passkey {
+	fn from(p: config::storage::Passkey) -> Self {
+		Self {
+			id: p.id,
+			name: p.name,
+			created_at: p.created_at,
+		}
+	}
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct PasskeyRelyingParty {
+	#[schema(examples("polaris.example.com"))]
+	pub id: String,
+	#[schema(examples("Polaris"))]
+	pub name: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PasskeyUser {
+	#[schema(examples("YWxpY2U"))]
+	pub id: String,
+	#[schema(examples("alice"))]
+	pub name: String,
+	#[schema(examples("alice"))]
+	pub display_name: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct PasskeyCredentialParameters {
+	#[serde(rename = "type")]
+	#[schema(examples("public-key"))]
+	pub credential_type: String,
+	#[schema(examples(-7, -8, -257))]
+	pub alg: i64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct PasskeyCredentialDescriptor {
+	#[serde(rename = "type")]
+	#[schema(examples("public-key"))]
+	pub credential_type: String,
+	#[schema(examples("dGVzdC1jcmVkZW50aWFs"))]
+	pub id: String,
+}
+
+impl PasskeyCredentialDescriptor {
+	fn new(id: String) -> Self {
+		Self {
+			credential_type: "public-key".to_owned(),
+			id,
+		}
+	}
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PasskeyAuthenticatorSelection {
+	#[schema(examples("preferred"))]
+	pub resident_key: String,
+	#[schema(examples("preferred"))]
+	pub user_verification: String,
+}
+
+/// Options for `navigator.credentials.create()`, in the format accepted by `PublicKeyCredential.parseCreationOptionsFromJSON()`
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PasskeyCreationOptions {
+	#[schema(examples("aDNkN0JiZlF4T0I2Wk1zY0pUN2xGUmpWcjlkS3E4QWs"))]
+	pub challenge: String,
+	pub rp: PasskeyRelyingParty,
+	pub user: PasskeyUser,
+	pub pub_key_cred_params: Vec<PasskeyCredentialParameters>,
+	/// Milliseconds
+	#[schema(examples(300000))]
+	pub timeout: u64,
+	pub exclude_credentials: Vec<PasskeyCredentialDescriptor>,
+	pub authenticator_selection: PasskeyAuthenticatorSelection,
+	#[schema(examples("none"))]
+	pub attestation: String,
+}
+
+impl From<webauthn::RegistrationOptions> for PasskeyCreationOptions {
+	fn from(o: webauthn::RegistrationOptions) -> Self {
+		let algorithms = [
+			webauthn::ALGORITHM_ES256,
+			webauthn::ALGORITHM_EDDSA,
+			webauthn::ALGORITHM_RS256,
+		];
+		Self {
+			challenge: o.challenge,
+			rp: PasskeyRelyingParty {
+				id: o.rp_id,
+				name: "Polaris".to_owned(),
+			},
+			user: PasskeyUser {
+				id: o.user_id,
+				name: o.username.clone(),
+				display_name: o.username,
+			},
+			pub_key_cred_params: algorithms
+				.into_iter()
+				.map(|alg| PasskeyCredentialParameters {
+					credential_type: "public-key".to_owned(),
+					alg,
+				})
+				.collect(),
+			timeout: webauthn::CHALLENGE_TIMEOUT.as_millis() as u64,
+			exclude_credentials: o
+				.exclude_credentials
+				.into_iter()
+				.map(PasskeyCredentialDescriptor::new)
+				.collect(),
+			authenticator_selection: PasskeyAuthenticatorSelection {
+				resident_key: "preferred".to_owned(),
+				user_verification: "preferred".to_owned(),
+			},
+			attestation: "none".to_owned(),
+		}
+	}
+}
+
+/// Options for `navigator.credentials.get()`, in the format accepted by `PublicKeyCredential.parseRequestOptionsFromJSON()`
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PasskeyRequestOptions {
+	#[schema(examples("aDNkN0JiZlF4T0I2Wk1zY0pUN2xGUmpWcjlkS3E4QWs"))]
+	pub challenge: String,
+	#[schema(examples("polaris.example.com"))]
+	pub rp_id: String,
+	pub allow_credentials: Vec<PasskeyCredentialDescriptor>,
+	/// Milliseconds
+	#[schema(examples(300000))]
+	pub timeout: u64,
+	#[schema(examples("preferred"))]
+	pub user_verification: String,
+}
+
+impl From<webauthn::AuthenticationOptions> for PasskeyRequestOptions {
+	fn from(o: webauthn::AuthenticationOptions) -> Self {
+		Self {
+			challenge: o.challenge,
+			rp_id: o.rp_id,
+			allow_credentials: o
+				.allow_credentials
+				.into_iter()
+				.map(PasskeyCredentialDescriptor::new)
+				.collect(),
+			timeout: webauthn::CHALLENGE_TIMEOUT.as_millis() as u64,
+			user_verification: "preferred".to_owned(),
+		}
+	}
+}
+
+/// Response of the authenticator to `navigator.credentials.create()`. Binary fields are encoded as unpadded base64url.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct NewPasskey {
+	#[schema(examples("Laptop"))]
+	pub name: Option<String>,
+	pub client_data_json: String,
+	pub attestation_object: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct PasskeyLoginOptions {
+	/// Restricts the login to passkeys of this user. When absent, authenticators offer their discoverable passkeys.
+	#[schema(examples("alice"))]
+	pub username: Option<String>,
+}
+
+/// Response of the authenticator to `navigator.credentials.get()`. Binary fields are encoded as unpadded base64url.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct PasskeyAssertion {
+	#[schema(examples("dGVzdC1jcmVkZW50aWFs"))]
+	pub credential_id: String,
+	pub client_data_json: String,
+	pub authenticator_data: String,
+	pub signature: String,
+	/// Name of the device signing in, listed alongside its session
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples("Living room speakers"))]
+	pub client_name: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct CreatedApiKey {
+	pub api_key: ApiKey,
+	/// Secret value to authenticate with, in place of an auth token. It cannot be retrieved again.
+	#[schema(examples("polaris_mV3kq8PzR1cXa9Lw_0Oq1QnDdS7yK2bTfWm4hJx6EcRgZ8uVl"))]
+	pub key: String,
+}
+
+/// Client settings of a user, keyed by name. Values can be any JSON value.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[schema(example = json!({"theme": "dark", "language": "fr", "web.default_sort": "year"}))]
+pub struct Preferences(pub HashMap<String, serde_json::Value>);
+
+impl From<preferences::Preferences> for Preferences {
+	fn from(p: preferences::Preferences) -> Self {
+		Self(p.into_iter().collect())
+	}
+}
+
+impl From<Preferences> for preferences::Preferences {
+	fn from(p: Preferences) -> Self {
+		p.0.into_iter().collect()
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ListenBrainzLink {
+	/// User token, listed in the ListenBrainz account settings
+	pub token: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ListenBrainzAccount {
+	/// Name of the linked ListenBrainz user
+	#[schema(examples("rob"))]
+	pub user_name: String,
+	/// Listens which could not be submitted yet because ListenBrainz was unavailable. They are retried periodically.
+	#[schema(examples(0))]
+	pub pending_listens: usize,
+}
+
+impl From<listenbrainz::Account> for ListenBrainzAccount {
+	fn from(a: listenbrainz::Account) -> Self {
+		Self {
+			user_name: a.user_name,
+			pending_listens: a.pending_listens,
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct MonthlyTransfer {
+	#[schema(examples("2024-06"))]
+	pub month: String,
+	#[schema(examples(1073741824))]
+	pub bytes: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Transfers {
+	/// Amount of data the user can receive each month before audio is served at a lower bitrate, in bytes
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples(10737418240u64))]
+	pub monthly_quota: Option<u64>,
+	/// Whether the quota for the current month is used up
+	pub over_quota: bool,
+	/// Audio and archives sent to the user each month (UTC), most recent first
+	pub months: Vec<MonthlyTransfer>,
+}
+
+impl From<transfers::Usage> for Transfers {
+	fn from(u: transfers::Usage) -> Self {
+		Self {
+			monthly_quota: u.monthly_quota_bytes,
+			over_quota: u.is_over_quota(),
+			months: u
+				.months
+				.into_iter()
+				.map(|m| MonthlyTransfer {
+					month: m.month,
+					bytes: m.bytes,
+				})
+				.collect(),
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AuditEvent {
+	LoginSucceeded,
+	LoginFailed,
+	UserCreated { name: String, admin: bool },
+	UserUpdated { name: String },
+	UserDeleted { name: String },
+	MountDirsChanged,
+	SettingsChanged,
+	PlaylistDeleted { name: String },
+	AliasesChanged,
+	InviteCreated { id: String },
+	InviteRevoked { id: String },
+	UserLoggedOut { name: String },
+	UserUnlocked { name: String },
+	ApiKeyCreated { id: String, name: String },
+	ApiKeyRevoked { id: String },
+}
+
+impl From<audit::Event> for AuditEvent {
+	fn from(e: audit::Event) -> Self {
+		match e {
+			audit::Event::LoginSucceeded => Self::LoginSucceeded,
+			audit::Event::LoginFailed => Self::LoginFailed,
+			audit::Event::UserCreated { name, admin } => Self::UserCreated { name, admin },
+			audit::Event::UserUpdated { name } => Self::UserUpdated { name },
+			audit::Event::UserDeleted { name } => Self::UserDeleted { name },
+			audit::Event::MountDirsChanged => Self::MountDirsChanged,
+			audit::Event::SettingsChanged => Self::SettingsChanged,
+			audit::Event::PlaylistDeleted { name } => Self::PlaylistDeleted { name },
+			audit::Event::AliasesChanged => Self::AliasesChanged,
+			audit::Event::InviteCreated { id } => Self::InviteCreated { id },
+			audit::Event::InviteRevoked { id } => Self::InviteRevoked { id },
+			audit::Event::UserLoggedOut { name } => Self::UserLoggedOut { name },
+			audit::Event::UserUnlocked { name } => Self::UserUnlocked { name },
+			audit::Event::ApiKeyCreated { id, name } => Self::ApiKeyCreated { id, name },
+			audit::Event::ApiKeyRevoked { id } => Self::ApiKeyRevoked { id },
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct AuditEntry {
+	#[schema(examples(42))]
+	pub id: u64,
+	/// Time at which the event happened, as a UNIX timestamp
+	#[schema(examples(1729099000))]
+	pub timestamp: i64,
+	/// User who performed the action. For login events, this is the username which was submitted.
+	#[schema(examples("alice"))]
+	pub actor: Option<String>,
+	/// Address of the client which made the request
+	#[schema(examples("192.168.1.20"))]
+	pub address: Option<String>,
+	pub event: AuditEvent,
+}
+
+impl From<audit::Entry> for AuditEntry {
+	fn from(e: audit::Entry) -> Self {
+		Self {
+			id: e.id,
+			timestamp: e.timestamp,
+			actor: e.actor,
+			address: e.address.map(|a| a.to_string()),
+			event: e.event.into(),
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Invite {
+	#[schema(examples("Qw7rT2xLp0sVb4Nc"))]
+	pub id: String,
+	/// User who created the invite
+	#[schema(examples("alice"))]
+	pub created_by: String,
+	/// Time at which the invite was created, as a UNIX timestamp
+	#[schema(examples(1729099000))]
+	pub created_at: i64,
+	/// Time after which the invite can no longer be used, as a UNIX timestamp
+	#[schema(examples(1729703800))]
+	pub expires_at: i64,
+}
+
+impl From<invite::Invite> for Invite {
+	fn from(i: invite::Invite) -> Self {
+		Self {
+			id: i.id,
+			created_by: i.created_by,
+			created_at: i.created_at,
+			expires_at: i.expires_at,
+		}
+	}
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct NewInvite {
+	/// Number of days the invite can be used for, 7 when unset
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples(7))]
+	pub valid_for_days: Option<u32>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct CreatedInvite {
+	pub invite: Invite,
+	/// Secret value to register with. It cannot be retrieved again.
+	#[schema(examples("Qw7rT2xLp0sVb4Nc_0Oq1QnDdS7yK2bTfWm4hJx6EcRgZ8uVl"))]
+	pub token: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct Registration {
+	/// Token of the invite being used
+	#[schema(examples("Qw7rT2xLp0sVb4Nc_0Oq1QnDdS7yK2bTfWm4hJx6EcRgZ8uVl"))]
+	pub token: String,
+	#[schema(examples("bob"))]
+	pub username: String,
+	#[schema(examples("secret_password!!"))]
+	pub password: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct PlaylistFeed {
+	/// Address of the podcast feed, which podcast apps can subscribe to without signing in
+	#[schema(examples("https://polaris.example.com/api/v8/feed/875ifYC7bWnRyHG2ju3fMlORRQYzLN"))]
+	pub url: String,
+}
+
 #[derive(Clone, Serialize, Deserialize, ToSchema)]
 pub struct SavePlaylistInput {
 	#[schema(value_type = Vec<String>, examples(json!(["my_music/destiny.mp3", "my_music/dancing_all_night.mp3"])))]
 	pub tracks: Vec<PathBuf>,
 }
 
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct PlayQueue {
+	#[schema(value_type = Vec<String>, examples(json!(["my_music/destiny.mp3", "my_music/dancing_all_night.mp3"])))]
+	pub songs: Vec<PathBuf>,
+	/// Index of the current song within `songs`
+	#[schema(examples(1))]
+	pub position: usize,
+	/// Playback position within the current song, in milliseconds
+	#[schema(examples(42000))]
+	pub offset: u64,
+	/// Time when the queue was last saved, in seconds since the Unix epoch
+	#[schema(examples(1736034281))]
+	pub updated_at: i64,
+}
+
+impl From<queue::PlayQueue> for PlayQueue {
+	fn from(q: queue::PlayQueue) -> Self {
+		Self {
+			songs: q.songs,
+			position: q.position,
+			offset: q.offset,
+			updated_at: q.updated_at,
+		}
+	}
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct SavePlayQueueInput {
+	#[schema(value_type = Vec<String>, examples(json!(["my_music/destiny.mp3", "my_music/dancing_all_night.mp3"])))]
+	pub songs: Vec<PathBuf>,
+	/// Index of the current song within `songs`
+	#[serde(default)]
+	#[schema(examples(1))]
+	pub position: usize,
+	/// Playback position within the current song, in milliseconds
+	#[serde(default)]
+	#[schema(examples(42000))]
+	pub offset: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FamilyMixMode {
+	/// Only include songs found in the playlists of every listener
+	#[default]
+	Intersection,
+	/// Include songs found in the playlists of any listener, favoring songs shared by many (or heavily weighted) listeners
+	WeightedUnion,
+}
+
+impl From<FamilyMixMode> for playlist::BlendMode {
+	fn from(m: FamilyMixMode) -> Self {
+		match m {
+			FamilyMixMode::Intersection => Self::Intersection,
+			FamilyMixMode::WeightedUnion => Self::WeightedUnion,
+		}
+	}
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct FamilyMixListener {
+	#[schema(examples("alice"))]
+	pub username: String,
+	/// Relative importance of this listener in `weighted_union` mode. Defaults to 1.
+	#[schema(examples(1.0, 2.5))]
+	pub weight: Option<f64>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct FamilyMixInput {
+	/// Name of the playlist to save the mix as
+	#[schema(examples("Sunday Dinner"))]
+	pub name: String,
+	/// Users whose playlists are blended into the mix. The current user is always included.
+	pub listeners: Vec<FamilyMixListener>,
+	#[serde(default)]
+	pub mode: FamilyMixMode,
+	/// Maximum number of songs in the mix
+	#[schema(examples(50))]
+	pub length: Option<usize>,
+	#[schema(examples(976878))]
+	pub seed: Option<u64>,
+}
+
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct User {
 	#[schema(examples("alice"))]
 	pub name: String,
 	#[schema(examples(true, false))]
 	pub is_admin: bool,
+	/// Guests can browse and stream music, but not change anything
+	#[schema(examples(true, false))]
+	pub is_guest: bool,
+	/// Permissions held by the user, administrators hold all of them
+	pub permissions: Vec<Permission>,
+	/// Whether `permissions` were configured explicitly, rather than defaulted
+	#[schema(examples(true, false))]
+	pub has_custom_permissions: bool,
 }
 
 impl From<config::User> for User {
 	fn from(u: config::User) -> Self {
 		Self {
-			name: u.name,
 			is_admin: u.admin == Some(true),
+			is_guest: u.is_guest(),
+			permissions: auth::Permission::ALL
+				.into_iter()
+				.filter(|p| u.has_permission(*p))
+				.map(Permission::from)
+				.collect(),
+			has_custom_permissions: u.permissions.is_some(),
+			name: u.name,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+	/// Allows creating, editing and deleting users who are not administrators
+	ManageUsers,
+	/// Allows editing mount directories
+	ManageMounts,
+	/// Allows starting and controlling collection scans
+	TriggerScan,
+	/// Allows downloading zip archives of the collection
+	Download,
+	/// Allows creating podcast feeds for playlists
+	Share,
+}
+
+impl From<auth::Permission> for Permission {
+	fn from(p: auth::Permission) -> Self {
+		match p {
+			auth::Permission::ManageUsers => Self::ManageUsers,
+			auth::Permission::ManageMounts => Self::ManageMounts,
+			auth::Permission::TriggerScan => Self::TriggerScan,
+			auth::Permission::Download => Self::Download,
+			auth::Permission::Share => Self::Share,
+		}
+	}
+}
+
+impl From<Permission> for auth::Permission {
+	fn from(p: Permission) -> Self {
+		match p {
+			Permission::ManageUsers => Self::ManageUsers,
+			Permission::ManageMounts => Self::ManageMounts,
+			Permission::TriggerScan => Self::TriggerScan,
+			Permission::Download => Self::Download,
+			Permission::Share => Self::Share,
 		}
 	}
 }
@@ -151,6 +898,12 @@ pub struct NewUser {
 	pub password: String,
 	#[schema(examples(true, false))]
 	pub admin: bool,
+	#[serde(default)]
+	#[schema(examples(true, false))]
+	pub guest: bool,
+	/// Defaults to `download` and `share` when absent. Only administrators can set this field.
+	#[serde(default)]
+	pub permissions: Option<Vec<Permission>>,
 }
 
 #[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
@@ -159,6 +912,12 @@ pub struct UserUpdate {
 	pub new_password: Option<String>,
 	#[schema(examples(true, false))]
 	pub new_is_admin: Option<bool>,
+	#[serde(default)]
+	#[schema(examples(true, false))]
+	pub new_is_guest: Option<bool>,
+	/// Only administrators can change permissions
+	#[serde(default)]
+	pub new_permissions: Option<Vec<Permission>>,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize, ToSchema)]
@@ -167,6 +926,19 @@ pub struct MountDir {
 	pub source: PathBuf,
 	#[schema(examples("my_music", "root"))]
 	pub name: String,
+	/// Glob patterns for files and directories to leave out of the index
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	#[schema(examples(json!(["@eaDir", ".stfolder"])))]
+	pub exclude_patterns: Vec<String>,
+	#[serde(default)]
+	pub symlinks: SymlinkPolicy,
+	/// Whether artist tags are split into multiple artists using the `artist_separators` setting. Defaults to `true`.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples(true, false))]
+	pub split_artists: Option<bool>,
+	/// Kind of server holding the files of this directory, when they are not on the local file system. Remote servers are set up in the configuration file, and are left unchanged when updating mount directories.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub remote: Option<RemoteStorage>,
 }
 
 impl From<MountDir> for config::storage::MountDir {
@@ -174,6 +946,10 @@ impl From<MountDir> for config::storage::MountDir {
 		Self {
 			name: m.name,
 			source: m.source,
+			exclude_patterns: m.exclude_patterns,
+			symlinks: Some(m.symlinks.into()),
+			split_artists: m.split_artists,
+			remote: None,
 		}
 	}
 }
@@ -183,16 +959,119 @@ impl From<config::MountDir> for MountDir {
 		Self {
 			name: m.name,
 			source: m.source,
+			exclude_patterns: m
+				.exclude_patterns
+				.iter()
+				.map(|p| p.as_str().to_owned())
+				.collect(),
+			symlinks: m.symlinks.into(),
+			split_artists: Some(m.split_artists),
+			remote: m.remote.map(|r| r.into()),
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RemoteStorage {
+	/// S3-compatible object storage, like AWS S3 or MinIO
+	S3,
+	/// WebDAV server, like Nextcloud
+	Webdav,
+	/// SMB share, like Windows file sharing or Samba
+	Smb,
+}
+
+impl From<config::Remote> for RemoteStorage {
+	fn from(r: config::Remote) -> Self {
+		match r {
+			config::Remote::S3(_) => Self::S3,
+			config::Remote::Webdav(_) => Self::Webdav,
+			config::Remote::Smb(_) => Self::Smb,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SymlinkPolicy {
+	/// Symlinks to files are indexed, symlinks to directories are skipped
+	#[default]
+	Files,
+	/// Symlinks to files and directories are both followed
+	Follow,
+	/// Symlinks are skipped
+	Ignore,
+}
+
+impl From<config::SymlinkPolicy> for SymlinkPolicy {
+	fn from(p: config::SymlinkPolicy) -> Self {
+		match p {
+			config::SymlinkPolicy::Files => Self::Files,
+			config::SymlinkPolicy::Follow => Self::Follow,
+			config::SymlinkPolicy::Ignore => Self::Ignore,
+		}
+	}
+}
+
+impl From<SymlinkPolicy> for config::SymlinkPolicy {
+	fn from(p: SymlinkPolicy) -> Self {
+		match p {
+			SymlinkPolicy::Files => Self::Files,
+			SymlinkPolicy::Follow => Self::Follow,
+			SymlinkPolicy::Ignore => Self::Ignore,
+		}
+	}
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Alias {
+	/// Preferred spelling
+	#[schema(examples("Sigur Rós"))]
+	pub name: String,
+	/// Alternative spellings which are merged into the preferred one
+	#[schema(examples(json!(["Sigur Ros"])))]
+	pub aliases: Vec<String>,
+}
+
+impl From<Alias> for config::storage::Alias {
+	fn from(a: Alias) -> Self {
+		Self {
+			name: a.name,
+			aliases: a.aliases,
+		}
+	}
+}
+
+impl From<config::Alias> for Alias {
+	fn from(a: config::Alias) -> Self {
+		Self {
+			name: a.name,
+			aliases: a.aliases,
 		}
 	}
 }
 
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Aliases {
+	pub artists: Vec<Alias>,
+	pub albums: Vec<Alias>,
+	#[serde(default)]
+	pub genres: Vec<Alias>,
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 pub struct NewSettings {
 	#[schema(examples("Folder.(jpeg|jpg|png)"))]
 	pub album_art_pattern: Option<String>,
 	#[schema(examples("https://myddnsprovider.com?token=abcdef"))]
 	pub ddns_update_url: Option<String>,
+	#[schema(examples(true, false))]
+	pub detect_silence: Option<bool>,
+	#[schema(examples("%artist%/%album%/%track% - %title%"))]
+	pub filename_pattern: Option<String>,
+	#[schema(examples(json!([";", "feat.", "／"])))]
+	pub artist_separators: Option<Vec<String>>,
 }
 
 #[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
@@ -201,12 +1080,20 @@ pub struct Settings {
 	pub album_art_pattern: String,
 	#[schema(examples("https://myddnsprovider.com?token=abcdef"))]
 	pub ddns_update_url: String,
+	#[schema(examples(true, false))]
+	pub detect_silence: bool,
+	#[schema(examples("%artist%/%album%/%track% - %title%"))]
+	pub filename_pattern: String,
+	/// Separators used to split artist tags into multiple artists
+	#[schema(examples(json!([";", "feat.", "／"])))]
+	pub artist_separators: Vec<String>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 pub enum IndexState {
 	OutOfDate,
 	InProgress,
+	Paused,
 	UpToDate,
 }
 
@@ -216,26 +1103,101 @@ impl From<scanner::State> for IndexState {
 			scanner::State::Initial => Self::OutOfDate,
 			scanner::State::Pending => Self::OutOfDate,
 			scanner::State::InProgress => Self::InProgress,
+			scanner::State::Paused => Self::Paused,
+			scanner::State::Cancelled => Self::OutOfDate,
 			scanner::State::UpToDate => Self::UpToDate,
 		}
 	}
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexPhase {
+	/// Reading the content of mount directories
+	Scanning,
+	/// Writing the updated collection to disk
+	Saving,
+}
+
+impl From<scanner::Phase> for IndexPhase {
+	fn from(phase: scanner::Phase) -> Self {
+		match phase {
+			scanner::Phase::Scanning => Self::Scanning,
+			scanner::Phase::Saving => Self::Saving,
+		}
+	}
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 pub struct IndexStatus {
-	state: IndexState,
+	pub state: IndexState,
+	/// Step of the scan in progress
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub phase: Option<IndexPhase>,
 	#[schema(examples(1736929092))]
-	last_start_time: Option<u64>,
+	pub last_start_time: Option<u64>,
 	#[schema(examples(1736929992))]
-	last_end_time: Option<u64>,
+	pub last_end_time: Option<u64>,
+	/// Time spent on the scan in progress or on the last completed scan, in milliseconds
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples(900000))]
+	pub elapsed: Option<u64>,
 	#[schema(examples(289))]
-	num_songs_indexed: u32,
+	pub num_songs_indexed: u32,
+	#[serde(default)]
+	#[schema(examples(31))]
+	pub num_directories_scanned: u32,
+	/// Songs which were not in the collection before this scan
+	#[serde(default)]
+	#[schema(examples(12))]
+	pub num_songs_added: u32,
+	/// Songs whose files changed since the previous scan
+	#[serde(default)]
+	#[schema(examples(3))]
+	pub num_songs_updated: u32,
+	/// Songs which are no longer in the collection. Only known once the scan completes.
+	#[serde(default)]
+	#[schema(examples(1))]
+	pub num_songs_removed: u32,
+	/// Number of files or directories which could not be read
+	#[serde(default)]
+	#[schema(examples(1))]
+	pub num_errors: u32,
+	/// Files or directories which could not be read (up to 1000)
+	#[serde(default)]
+	pub errors: Vec<IndexError>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct IndexError {
+	#[schema(value_type = String, examples("my_music/destiny/broken.mp3"))]
+	pub path: PathBuf,
+	#[schema(examples("Could not read metadata: MP3 metadata error"))]
+	pub message: String,
+}
+
+impl From<scanner::ScanError> for IndexError {
+	fn from(e: scanner::ScanError) -> Self {
+		Self {
+			path: e.virtual_path,
+			message: e.message,
+		}
+	}
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct IndexRefreshInput {
+	/// Directory of the music collection to scan
+	#[schema(value_type = String, examples("my_music/stratovarius/destiny"))]
+	pub path: PathBuf,
 }
 
 impl From<scanner::Status> for IndexStatus {
 	fn from(s: scanner::Status) -> Self {
 		Self {
+			elapsed: s.elapsed().map(|d| d.as_millis() as u64),
 			state: s.state.into(),
+			phase: s.phase.map(IndexPhase::from),
 			last_start_time: s
 				.last_start_time
 				.and_then(|t| t.duration_since(UNIX_EPOCH).ok())
@@ -245,6 +1207,12 @@ impl From<scanner::Status> for IndexStatus {
 				.and_then(|t| t.duration_since(UNIX_EPOCH).ok())
 				.map(|d| d.as_millis() as u64),
 			num_songs_indexed: s.num_songs_indexed,
+			num_directories_scanned: s.num_directories_scanned,
+			num_songs_added: s.num_songs_added,
+			num_songs_updated: s.num_songs_updated,
+			num_songs_removed: s.num_songs_removed,
+			num_errors: s.num_errors,
+			errors: s.errors.into_iter().map(IndexError::from).collect(),
 		}
 	}
 }
@@ -260,6 +1228,9 @@ pub struct Song {
 	#[schema(examples(1))]
 	pub disc_number: Option<i64>,
 	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples("The Studio Recordings"))]
+	pub disc_subtitle: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
 	#[schema(examples("Destiny", "Dancing All Night"))]
 	pub title: Option<String>,
 	#[serde(default, skip_serializing_if = "Vec::is_empty")]
@@ -278,6 +1249,10 @@ pub struct Song {
 	#[serde(default, skip_serializing_if = "Option::is_none")]
 	#[schema(examples("/my_music/destiny.jpg"))]
 	pub artwork: Option<PathBuf>,
+	/// Whether lyrics for this song can be retrieved from the `/lyrics` endpoint
+	#[serde(default)]
+	#[schema(examples(true, false))]
+	pub has_lyrics: bool,
 	#[serde(default, skip_serializing_if = "Option::is_none")]
 	/// Duration in seconds
 	#[schema(examples(192))]
@@ -294,6 +1269,73 @@ pub struct Song {
 	#[serde(default, skip_serializing_if = "Vec::is_empty")]
 	#[schema(examples(json!(["Ninja Tuna"])))]
 	pub labels: Vec<String>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	#[schema(examples(json!(["Herbert von Karajan"])))]
+	pub conductors: Vec<String>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	#[schema(examples(json!(["Berliner Philharmoniker"])))]
+	pub performers: Vec<String>,
+	/// Classical work this song is part of
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples("Symphony No. 9 in D minor, Op. 125"))]
+	pub work: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples("II. Molto vivace"))]
+	pub movement: Option<String>,
+	/// Tempo in beats per minute
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples(128))]
+	pub bpm: Option<i64>,
+	/// Musical key, in the notation used by the tagging software
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples("Am", "8A"))]
+	pub initial_key: Option<String>,
+	/// ReplayGain track gain in hundredths of a decibel, also derived from R128 tags
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples(-652))]
+	pub track_gain: Option<i32>,
+	/// ReplayGain track peak in millionths of full scale
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples(988525))]
+	pub track_peak: Option<u32>,
+	/// ReplayGain album gain in hundredths of a decibel, also derived from R128 tags
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples(-598))]
+	pub album_gain: Option<i32>,
+	/// ReplayGain album peak in millionths of full scale
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples(999969))]
+	pub album_peak: Option<u32>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples("0b8f4c6e-3d2a-4e1b-a7c9-5f6d8e9a0b1c"))]
+	pub musicbrainz_recording_id: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples("f5a3b6d0-8e8a-4c4e-9d1c-2f6b1d0e7c3a"))]
+	pub musicbrainz_release_id: Option<String>,
+	/// MusicBrainz identifiers of the song artists
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	#[schema(examples(json!(["c2a2d2a1-c2c1-4b73-9e6a-8b0a1d1e7d4f"])))]
+	pub musicbrainz_artist_ids: Vec<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	/// Offset in milliseconds where audible content begins (only available when silence detection is enabled)
+	#[schema(examples(250))]
+	pub audible_start: Option<i64>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	/// Offset in milliseconds where audible content ends (only available when silence detection is enabled)
+	#[schema(examples(191400))]
+	pub audible_end: Option<i64>,
+	/// Whether the current user starred this song
+	#[serde(default)]
+	#[schema(examples(true, false))]
+	pub starred: bool,
+	/// Rating given to this song by the current user, from 0 to 5
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples(4))]
+	pub rating: Option<u8>,
+	/// Number of times the current user played this song to completion
+	#[serde(default)]
+	#[schema(examples(12))]
+	pub play_count: u32,
 }
 
 impl From<index::Song> for Song {
@@ -302,17 +1344,37 @@ impl From<index::Song> for Song {
 			path: s.virtual_path,
 			track_number: s.track_number,
 			disc_number: s.disc_number,
+			disc_subtitle: s.disc_subtitle,
 			title: s.title,
 			artists: s.artists,
 			album_artists: s.album_artists,
 			year: s.year,
 			album: s.album,
 			artwork: s.artwork,
+			has_lyrics: s.lyrics.is_some(),
 			duration: s.duration,
 			lyricists: s.lyricists,
 			composers: s.composers,
 			genres: s.genres,
 			labels: s.labels,
+			conductors: s.conductors,
+			performers: s.performers,
+			work: s.work,
+			movement: s.movement,
+			bpm: s.bpm,
+			initial_key: s.initial_key,
+			track_gain: s.track_gain,
+			track_peak: s.track_peak,
+			album_gain: s.album_gain,
+			album_peak: s.album_peak,
+			musicbrainz_recording_id: s.musicbrainz_recording_id,
+			musicbrainz_release_id: s.musicbrainz_release_id,
+			musicbrainz_artist_ids: s.musicbrainz_artist_ids,
+			audible_start: s.audible_start,
+			audible_end: s.audible_end,
+			starred: false,
+			rating: None,
+			play_count: 0,
 		}
 	}
 }
@@ -325,6 +1387,16 @@ pub struct SongList {
 	pub first_songs: Vec<Song>,
 }
 
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Starred {
+	/// Starred songs, most recently starred first
+	pub songs: SongList,
+	/// Starred albums which are in the collection, most recently starred first
+	pub albums: Vec<AlbumHeader>,
+	/// Starred artists which are in the collection, most recently starred first
+	pub artists: Vec<ArtistHeader>,
+}
+
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 pub struct BrowserEntry {
 	#[schema(value_type = String, examples("my_music/stratovarius/destiny"))]
@@ -352,12 +1424,24 @@ impl From<index::File> for BrowserEntry {
 pub struct GenreHeader {
 	#[schema(examples("Jazz", "Classical"))]
 	pub name: String,
+	#[serde(default)]
+	#[schema(examples(12, 40))]
+	pub num_albums: u32,
+	#[serde(default)]
+	#[schema(examples(8, 25))]
+	pub num_artists: u32,
+	#[serde(default)]
+	#[schema(examples(150, 520))]
+	pub num_songs: u32,
 }
 
 impl From<index::GenreHeader> for GenreHeader {
 	fn from(g: index::GenreHeader) -> Self {
 		Self {
 			name: g.name.to_string(),
+			num_albums: g.num_albums,
+			num_artists: g.num_artists,
+			num_songs: g.num_songs,
 		}
 	}
 }
@@ -424,6 +1508,9 @@ pub struct ArtistHeader {
 	pub num_songs_by_genre: HashMap<String, u32>,
 	#[schema(examples(12))]
 	pub num_songs: u32,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples("c2a2d2a1-c2c1-4b73-9e6a-8b0a1d1e7d4f"))]
+	pub musicbrainz_id: Option<String>,
 }
 
 impl From<index::ArtistHeader> for ArtistHeader {
@@ -436,6 +1523,7 @@ impl From<index::ArtistHeader> for ArtistHeader {
 			num_albums_as_lyricist: a.num_albums_as_lyricist,
 			num_songs_by_genre: a.num_songs_by_genre,
 			num_songs: a.num_songs,
+			musicbrainz_id: a.musicbrainz_id,
 		}
 	}
 }
@@ -487,6 +1575,40 @@ impl From<index::Artist> for Artist {
 	}
 }
 
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Composer {
+	#[serde(flatten)]
+	pub header: ArtistHeader,
+	pub works: Vec<Work>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Work {
+	/// Songs which are not part of a work are listed last, without a name
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples("Symphony No. 9 in D minor, Op. 125"))]
+	pub name: Option<String>,
+	pub songs: Vec<Song>,
+}
+
+impl From<index::Composer> for Composer {
+	fn from(composer: index::Composer) -> Self {
+		Self {
+			header: ArtistHeader::from(composer.header),
+			works: composer.works.into_iter().map(|w| w.into()).collect(),
+		}
+	}
+}
+
+impl From<index::Work> for Work {
+	fn from(work: index::Work) -> Self {
+		Self {
+			name: work.name,
+			songs: work.songs.into_iter().map(|s| s.into()).collect(),
+		}
+	}
+}
+
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 pub struct AlbumHeader {
 	#[schema(examples("Destiny", "Swing Tunes"))]
@@ -500,6 +1622,13 @@ pub struct AlbumHeader {
 	#[serde(default, skip_serializing_if = "Option::is_none")]
 	#[schema(examples(2010, 2024))]
 	pub year: Option<i64>,
+	/// Whether songs of this album are tagged as part of a compilation
+	#[serde(default)]
+	#[schema(examples(true, false))]
+	pub compilation: bool,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples("f5a3b6d0-8e8a-4c4e-9d1c-2f6b1d0e7c3a"))]
+	pub musicbrainz_release_id: Option<String>,
 }
 
 impl From<index::AlbumHeader> for AlbumHeader {
@@ -509,15 +1638,32 @@ impl From<index::AlbumHeader> for AlbumHeader {
 			artwork: a.artwork,
 			main_artists: a.artists,
 			year: a.year,
+			compilation: a.compilation,
+			musicbrainz_release_id: a.musicbrainz_release_id,
 		}
 	}
 }
 
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct Album {
 	#[serde(flatten)]
 	pub header: AlbumHeader,
+	/// Sections of `songs` belonging to each disc, in the same order as the songs
+	#[serde(default)]
+	pub discs: Vec<Disc>,
 	pub songs: Vec<Song>,
+	/// Rating given to this album by the current user, from 0 to 5
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples(4))]
+	pub rating: Option<u8>,
+	/// Average of the ratings given to this album by all users
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples(3.5))]
+	pub average_rating: Option<f64>,
+	/// Number of times the current user played songs from this album to completion
+	#[serde(default)]
+	#[schema(examples(40))]
+	pub play_count: u32,
 }
 
 impl From<index::Album> for Album {
@@ -525,7 +1671,33 @@ impl From<index::Album> for Album {
 		let songs = a.songs.drain(..).map(|s| s.into()).collect();
 		Self {
 			header: a.header.into(),
+			discs: a.discs.drain(..).map(|d| d.into()).collect(),
 			songs: songs,
+			rating: None,
+			average_rating: None,
+			play_count: 0,
+		}
+	}
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Disc {
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples(1, 2))]
+	pub number: Option<i64>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples("The Studio Recordings"))]
+	pub subtitle: Option<String>,
+	#[schema(examples(12))]
+	pub num_songs: u32,
+}
+
+impl From<index::Disc> for Disc {
+	fn from(d: index::Disc) -> Self {
+		Self {
+			number: d.number,
+			subtitle: d.subtitle,
+			num_songs: d.num_songs,
 		}
 	}
 }
@@ -544,6 +1716,84 @@ pub struct GetSongsBulkOutput {
 	pub not_found: Vec<PathBuf>,
 }
 
+#[derive(Clone, Default, Serialize, Deserialize, IntoParams, ToSchema)]
+pub struct GetChangesParameters {
+	/// Token returned by a previous call to this endpoint
+	#[schema(examples("3f9a0c5e1b7d2468-12"))]
+	pub since: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct AlbumId {
+	#[schema(examples("Destiny"))]
+	pub name: String,
+	#[schema(examples(json!(["Stratovarius"])))]
+	pub main_artists: Vec<String>,
+}
+
+impl From<index::AlbumId> for AlbumId {
+	fn from(a: index::AlbumId) -> Self {
+		Self {
+			name: a.name,
+			main_artists: a.artists,
+		}
+	}
+}
+
+#[derive(Default, Serialize, Deserialize, ToSchema)]
+pub struct SongChanges {
+	pub added: Vec<Song>,
+	pub modified: Vec<Song>,
+	#[schema(value_type = Vec<String>, examples(json!(["my_music/destiny.mp3"])))]
+	pub removed: Vec<PathBuf>,
+}
+
+#[derive(Default, Serialize, Deserialize, ToSchema)]
+pub struct AlbumChanges {
+	pub added: Vec<AlbumHeader>,
+	pub modified: Vec<AlbumHeader>,
+	pub removed: Vec<AlbumId>,
+}
+
+#[derive(Default, Serialize, Deserialize, ToSchema)]
+pub struct Changes {
+	/// Token to send on the next call to this endpoint
+	#[schema(examples("3f9a0c5e1b7d2468-15"))]
+	pub token: String,
+	/// When true, the client should discard its copy of the collection and download it again.
+	/// Song and album changes are empty in this case.
+	#[schema(examples(false))]
+	pub full_sync_required: bool,
+	pub songs: SongChanges,
+	pub albums: AlbumChanges,
+}
+
+impl From<index::Changes> for Changes {
+	fn from(c: index::Changes) -> Self {
+		Self {
+			token: c.token,
+			full_sync_required: c.full_sync_required,
+			songs: SongChanges {
+				added: c.added_songs.into_iter().map(|s| s.into()).collect(),
+				modified: c.modified_songs.into_iter().map(|s| s.into()).collect(),
+				removed: c.removed_songs,
+			},
+			albums: AlbumChanges {
+				added: c.added_albums.into_iter().map(|a| a.into()).collect(),
+				modified: c.modified_albums.into_iter().map(|a| a.into()).collect(),
+				removed: c.removed_albums.into_iter().map(|a| a.into()).collect(),
+			},
+		}
+	}
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, IntoParams, ToSchema)]
+pub struct GetAlbumsParameters {
+	/// Only list compilations when `true`, or leave them out when `false`
+	#[schema(examples(true, false))]
+	pub compilation: Option<bool>,
+}
+
 #[derive(Clone, Serialize, Deserialize, IntoParams, ToSchema)]
 pub struct GetRandomAlbumsParameters {
 	#[schema(examples(976878))]
@@ -554,6 +1804,268 @@ pub struct GetRandomAlbumsParameters {
 	pub count: Option<usize>,
 }
 
+#[derive(Clone, Default, Serialize, Deserialize, IntoParams, ToSchema)]
+pub struct PaginationParameters {
+	/// Number of entries to skip
+	#[schema(examples(0, 100))]
+	pub offset: Option<usize>,
+	/// Maximum number of entries to return. All remaining entries are returned when omitted.
+	#[schema(examples(100, 1000))]
+	pub count: Option<usize>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, IntoParams, ToSchema)]
+pub struct GetAuditLogParameters {
+	/// Only include events performed by this user
+	#[schema(examples("alice"))]
+	pub actor: Option<String>,
+	/// Only include events which happened at or after this UNIX timestamp
+	#[schema(examples(1729099000))]
+	pub since: Option<i64>,
+	/// Only include events which happened at or before this UNIX timestamp
+	#[schema(examples(1729185400))]
+	pub until: Option<i64>,
+	/// Maximum number of events to return, 100 when omitted
+	#[schema(examples(100))]
+	pub count: Option<usize>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, IntoParams, ToSchema)]
+pub struct GetStatsParameters {
+	/// Only include songs played at or after this UNIX timestamp
+	#[schema(examples(1704067200))]
+	pub since: Option<i64>,
+	/// Only include songs played at or before this UNIX timestamp
+	#[schema(examples(1735689599))]
+	pub until: Option<i64>,
+	/// Offset of the time zone of the user from UTC, in minutes, used to bucket plays by hour of the day
+	#[schema(examples(-300, 60))]
+	pub utc_offset: Option<i64>,
+	/// Number of entries in each top list, 10 when omitted
+	#[schema(examples(10))]
+	pub count: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct TopSong {
+	#[schema(value_type = String, examples("my_music/destiny.mp3"))]
+	pub path: PathBuf,
+	#[schema(examples(12))]
+	pub play_count: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct TopAlbum {
+	#[schema(examples("Destiny"))]
+	pub name: String,
+	#[schema(examples(json!(["Stratovarius"])))]
+	pub artists: Vec<String>,
+	#[schema(examples(40))]
+	pub play_count: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct TopArtist {
+	#[schema(examples("Stratovarius"))]
+	pub name: String,
+	#[schema(examples(75))]
+	pub play_count: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Stats {
+	/// Number of songs played to completion
+	#[schema(examples(1250))]
+	pub play_count: u32,
+	/// Total duration of the songs played, in seconds
+	#[schema(examples(302400))]
+	pub listening_time: i64,
+	pub top_songs: Vec<TopSong>,
+	pub top_albums: Vec<TopAlbum>,
+	pub top_artists: Vec<TopArtist>,
+	/// Number of songs played during each hour of the day, starting at midnight
+	pub hourly_play_counts: Vec<u32>,
+}
+
+impl From<stats::Report> for Stats {
+	fn from(r: stats::Report) -> Self {
+		Self {
+			play_count: r.play_count,
+			listening_time: r.listening_time,
+			top_songs: r
+				.top_songs
+				.into_iter()
+				.map(|(path, play_count)| TopSong { path, play_count })
+				.collect(),
+			top_albums: r
+				.top_albums
+				.into_iter()
+				.map(|(album, play_count)| TopAlbum {
+					name: album.name,
+					artists: album.artists,
+					play_count,
+				})
+				.collect(),
+			top_artists: r
+				.top_artists
+				.into_iter()
+				.map(|(name, play_count)| TopArtist { name, play_count })
+				.collect(),
+			hourly_play_counts: r.hourly_play_counts.to_vec(),
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct NewPlay {
+	#[schema(value_type = String, examples("my_music/destiny.mp3"))]
+	pub path: PathBuf,
+	/// Name of the application or device which played the song. Defaults to the `User-Agent` of the request.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples("Living room speakers"))]
+	pub client: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Play {
+	#[schema(value_type = String, examples("my_music/destiny.mp3"))]
+	pub path: PathBuf,
+	/// Unix timestamp, in seconds
+	#[schema(examples(1718000000))]
+	pub played_at: i64,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples("Living room speakers"))]
+	pub client: Option<String>,
+}
+
+impl From<history::Play> for Play {
+	fn from(p: history::Play) -> Self {
+		Self {
+			path: p.path,
+			played_at: p.played_at,
+			client: p.client,
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ActiveStream {
+	#[schema(examples("alice"))]
+	pub username: String,
+	/// Song being streamed. Not set for internet radio stations.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(value_type = Option<String>, examples("my_music/destiny.mp3"))]
+	pub path: Option<PathBuf>,
+	/// Internet radio station being relayed
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples("k2OeWsqxBWmpRtKJ"))]
+	pub radio_station: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples("192.168.1.20"))]
+	pub client_ip: Option<String>,
+	/// Unix timestamp, in seconds
+	#[schema(examples(1718000000))]
+	pub started_at: i64,
+	#[schema(examples(2500000))]
+	pub bytes_sent: u64,
+	/// Average transfer rate since the stream started, in kilobits per second
+	#[schema(examples(320))]
+	pub bitrate: u64,
+	/// Fraction of the response sent so far, between 0 and 1. Not set when the size of the response is unknown, eg. for transcoded audio.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples(0.42))]
+	pub progress: Option<f64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Rating {
+	/// From 0 to 5
+	#[schema(examples(4))]
+	pub rating: u8,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[schema(example = "rating")]
+pub enum SongSort {
+	/// Highest rated by the current user first, unrated songs last
+	Rating,
+	/// Slowest songs first, songs without a BPM tag last
+	Bpm,
+	/// Grouped by musical key, songs without a key tag last
+	Key,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, IntoParams, ToSchema)]
+pub struct SongSortParameters {
+	/// Order in which to list songs, instead of their default order
+	pub sort: Option<SongSort>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[schema(example = "year")]
+pub enum ListingSort {
+	/// Alphabetical order
+	Name,
+	/// Oldest first, entries without a year last
+	Year,
+	/// Most recently added first
+	DateAdded,
+	/// Alphabetical order of album artists
+	AlbumArtist,
+	/// Shuffled order
+	Random,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, IntoParams, ToSchema)]
+pub struct ListingSortParameters {
+	/// Order in which to list entries. When omitted, the `browse_sort` or `album_sort` preference of the current user applies, or alphabetical order if it is not set.
+	pub sort: Option<ListingSort>,
+	/// Seed for the `random` order. Listings requested with the same seed are shuffled identically, which allows paginating through them.
+	#[schema(examples(976878))]
+	pub seed: Option<u64>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, IntoParams, ToSchema)]
+pub struct StarredParameters {
+	/// When set, only include songs whose starred state for the current user matches this value
+	#[schema(examples(true, false))]
+	pub starred: Option<bool>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, IntoParams, ToSchema)]
+pub struct GetAudioParameters {
+	/// Serve the file as an attachment, to be saved by the client
+	#[schema(examples(true, false))]
+	pub download: Option<bool>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, IntoParams, ToSchema)]
+pub struct LogoutUserParameters {
+	/// Leave the user's API keys working, instead of revoking them along with their sessions
+	#[schema(examples(false))]
+	pub keep_api_keys: Option<bool>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, IntoParams, ToSchema)]
+pub struct GetMixParameters {
+	/// Only include songs from this genre
+	#[schema(examples("Jazz"))]
+	pub genre: Option<String>,
+	/// Only include songs released during the decade starting on this year
+	#[schema(examples(1990))]
+	pub decade: Option<i64>,
+	/// Only include songs the current user rated at least this high
+	#[schema(examples(4))]
+	pub min_rating: Option<u8>,
+	/// Maximum number of songs in the mix
+	#[schema(examples(50))]
+	pub length: Option<usize>,
+	#[schema(examples(976878))]
+	pub seed: Option<u64>,
+}
+
 #[derive(Clone, Serialize, Deserialize, IntoParams, ToSchema)]
 pub struct GetRecentAlbumsParameters {
 	#[schema(examples(0, 100))]
@@ -561,3 +2073,320 @@ pub struct GetRecentAlbumsParameters {
 	#[schema(examples(100, 1000))]
 	pub count: Option<usize>,
 }
+
+#[derive(Clone, Default, Serialize, Deserialize, IntoParams, ToSchema)]
+pub struct GetNeverPlayedParameters {
+	/// When set, songs are shuffled using this seed. Otherwise, they are listed in collection order.
+	#[schema(examples(976878))]
+	pub seed: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct PlaybackReport {
+	#[schema(value_type = String, examples("my_music/destiny.mp3"))]
+	pub path: PathBuf,
+	/// Playback position in milliseconds
+	#[schema(examples(42000))]
+	pub position: i64,
+	/// Whether the server should push time-synced lyrics for this song over the `/events` stream
+	#[serde(default)]
+	#[schema(examples(true, false))]
+	pub lyrics: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Lyrics {
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples("Destiny, let me know my destiny\nWhere will you lead me"))]
+	pub plain: Option<String>,
+	/// Time-synced lines, ordered by start time
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub synced: Option<Vec<LyricLine>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Fingerprint {
+	/// Duration of the song in seconds
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples(192))]
+	pub duration: Option<i64>,
+	/// Raw Chromaprint fingerprint of the first two minutes of the song
+	#[schema(examples(json!([3649163520u32, 3649098016u32, 3657486624u32])))]
+	pub fingerprint: Vec<u32>,
+}
+
+impl From<acoustid::Fingerprint> for Fingerprint {
+	fn from(f: acoustid::Fingerprint) -> Self {
+		Self {
+			duration: f.duration,
+			fingerprint: f.data,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateReason {
+	/// Songs have the same title and artists, and a similar duration
+	Tags,
+	/// Songs have matching audio fingerprints
+	Fingerprint,
+}
+
+impl From<duplicates::Reason> for DuplicateReason {
+	fn from(r: duplicates::Reason) -> Self {
+		match r {
+			duplicates::Reason::Tags => Self::Tags,
+			duplicates::Reason::Fingerprint => Self::Fingerprint,
+		}
+	}
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct DuplicateGroup {
+	pub reason: DuplicateReason,
+	pub songs: Vec<Song>,
+}
+
+impl From<duplicates::Duplicates> for DuplicateGroup {
+	fn from(d: duplicates::Duplicates) -> Self {
+		Self {
+			reason: d.reason.into(),
+			songs: d.songs.into_iter().map(|s| s.into()).collect(),
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataIssue {
+	MissingTitle,
+	MissingArtwork,
+	MissingTrackNumber,
+	/// Year before 1877 or in the future
+	SuspiciousYear,
+}
+
+impl From<quality::Issue> for MetadataIssue {
+	fn from(i: quality::Issue) -> Self {
+		match i {
+			quality::Issue::MissingTitle => Self::MissingTitle,
+			quality::Issue::MissingArtwork => Self::MissingArtwork,
+			quality::Issue::MissingTrackNumber => Self::MissingTrackNumber,
+			quality::Issue::SuspiciousYear => Self::SuspiciousYear,
+		}
+	}
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct SongMetadataIssues {
+	pub song: Song,
+	pub issues: Vec<MetadataIssue>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct MetadataReport {
+	#[schema(examples(12))]
+	pub missing_title: u32,
+	#[schema(examples(340))]
+	pub missing_artwork: u32,
+	#[schema(examples(57))]
+	pub missing_track_number: u32,
+	#[schema(examples(3))]
+	pub suspicious_year: u32,
+	/// Songs with at least one issue, the ones with most issues first
+	pub songs: Vec<SongMetadataIssues>,
+}
+
+impl From<quality::Report> for MetadataReport {
+	fn from(r: quality::Report) -> Self {
+		Self {
+			missing_title: r.missing_title_count,
+			missing_artwork: r.missing_artwork_count,
+			missing_track_number: r.missing_track_number_count,
+			suspicious_year: r.suspicious_year_count,
+			songs: r
+				.songs
+				.into_iter()
+				.map(|s| SongMetadataIssues {
+					song: s.song.into(),
+					issues: s.issues.into_iter().map(|i| i.into()).collect(),
+				})
+				.collect(),
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct LyricLine {
+	/// Time at which this line starts, in milliseconds
+	#[schema(examples(41500))]
+	pub start: i64,
+	#[schema(examples("Destiny, let me know my destiny"))]
+	pub text: String,
+}
+
+impl From<lyrics::Lyrics> for Lyrics {
+	fn from(l: lyrics::Lyrics) -> Self {
+		Self {
+			plain: l.plain,
+			synced: l.synced.map(|s| {
+				s.lines
+					.into_iter()
+					.map(|l| LyricLine {
+						start: l.start,
+						text: l.text,
+					})
+					.collect()
+			}),
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct LyricLineEvent {
+	#[schema(value_type = String, examples("my_music/destiny.mp3"))]
+	pub path: PathBuf,
+	/// Time at which this line starts, in milliseconds
+	#[schema(examples(41500))]
+	pub start: i64,
+	/// Time at which the following line starts, in milliseconds
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples(45250))]
+	pub next_line_start: Option<i64>,
+	#[schema(examples("Destiny, let me know my destiny"))]
+	pub text: String,
+}
+
+impl From<events::Event> for LyricLineEvent {
+	fn from(e: events::Event) -> Self {
+		match e {
+			events::Event::LyricLine {
+				path,
+				line,
+				next_line_start,
+			} => Self {
+				path,
+				start: line.start,
+				next_line_start,
+				text: line.text,
+			},
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct RadioStation {
+	#[schema(examples("k2OeWsqxBWmpRtKJ"))]
+	pub id: String,
+	#[schema(examples("Jazz FM"))]
+	pub name: String,
+	#[schema(examples("https://radio.example.com/jazz.mp3"))]
+	pub stream_url: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples("https://radio.example.com"))]
+	pub homepage_url: Option<String>,
+}
+
+impl From<radio::Station> for RadioStation {
+	fn from(s: radio::Station) -> Self {
+		Self {
+			id: s.id,
+			name: s.name,
+			stream_url: s.stream_url,
+			homepage_url: s.homepage_url,
+		}
+	}
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct RadioStationInput {
+	#[schema(examples("Jazz FM"))]
+	pub name: String,
+	#[schema(examples("https://radio.example.com/jazz.mp3"))]
+	pub stream_url: String,
+	#[serde(default)]
+	#[schema(examples("https://radio.example.com"))]
+	pub homepage_url: Option<String>,
+}
+
+impl From<RadioStationInput> for radio::NewStation {
+	fn from(s: RadioStationInput) -> Self {
+		Self {
+			name: s.name,
+			stream_url: s.stream_url,
+			homepage_url: s.homepage_url,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JukeboxPlayback {
+	Stopped,
+	Playing,
+	Paused,
+}
+
+impl From<jukebox::Playback> for JukeboxPlayback {
+	fn from(p: jukebox::Playback) -> Self {
+		match p {
+			jukebox::Playback::Stopped => Self::Stopped,
+			jukebox::Playback::Playing => Self::Playing,
+			jukebox::Playback::Paused => Self::Paused,
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct JukeboxStatus {
+	pub playback: JukeboxPlayback,
+	#[schema(value_type = Vec<String>, examples(json!(["my_music/destiny.mp3", "my_music/dancing_all_night.mp3"])))]
+	pub queue: Vec<PathBuf>,
+	/// Position of the current song within the queue
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(examples(0))]
+	pub current: Option<usize>,
+	/// Playback position within the current song, in milliseconds
+	#[schema(examples(42000))]
+	pub position: u64,
+	#[schema(examples(1.0, 0.5))]
+	pub volume: f32,
+}
+
+impl From<jukebox::Status> for JukeboxStatus {
+	fn from(s: jukebox::Status) -> Self {
+		Self {
+			playback: s.playback.into(),
+			queue: s.queue,
+			current: s.current,
+			position: s.position.as_millis() as u64,
+			volume: s.volume,
+		}
+	}
+}
+
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct JukeboxQueueInput {
+	#[schema(value_type = Vec<String>, examples(json!(["my_music/destiny.mp3", "my_music/dancing_all_night.mp3"])))]
+	pub tracks: Vec<PathBuf>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct JukeboxControl {
+	/// Playing from a stopped state starts at the current song, or at the start of the queue
+	#[serde(default)]
+	pub playback: Option<JukeboxPlayback>,
+	/// Queue position of a song to play
+	#[serde(default)]
+	#[schema(examples(3))]
+	pub current: Option<usize>,
+	/// Playback position to seek to within the current song, in milliseconds
+	#[serde(default)]
+	#[schema(examples(42000))]
+	pub position: Option<u64>,
+	/// Volume between 0 and 1
+	#[serde(default)]
+	#[schema(examples(0.8))]
+	pub volume: Option<f32>,
+}
@@ -15,8 +15,18 @@ pub enum APIError {
 	AuthorizationTokenEncoding,
 	#[error("Administrator permission is required")]
 	AdminPermissionRequired,
+	#[error("Guests cannot make changes")]
+	GuestPermissionDenied,
+	#[error("The user does not have the permission required for this action")]
+	PermissionRequired,
+	#[error("User `{0}` does not share their playlists in family mixes")]
+	FamilyMixNotAllowed(String),
 	#[error("Audio file could not be opened")]
 	AudioFileIOError,
+	#[error("Too many streams in progress for this user")]
+	TooManyStreams,
+	#[error("Files cannot be served as they are to this user, whose audio quality is capped")]
+	StreamQualityCapped,
 	#[error("Authentication is required")]
 	AuthenticationRequired,
 	#[error("Could not encode Branca token")]
@@ -37,30 +47,164 @@ pub enum APIError {
 	DdnsUpdateQueryFailed(u16),
 	#[error("Cannot delete your own account")]
 	DeletingOwnAccount,
+	#[error("Session not found")]
+	SessionNotFound,
+	#[error("Passkey not found")]
+	PasskeyNotFound,
+	#[error("Passkey is already registered")]
+	PasskeyAlreadyRegistered,
+	#[error("Passkey challenge expired or was not issued by this server")]
+	PasskeyChallengeExpired,
+	#[error("Passkey registration is invalid:\n\n{0}")]
+	InvalidPasskeyRegistration(String),
+	#[error("API key not found")]
+	ApiKeyNotFound,
+	#[error("Invite not found")]
+	InviteNotFound,
+	#[error("Invalid preference key `{0}`")]
+	InvalidPreferenceKey(String),
+	#[error("Preferences exceed the maximum size")]
+	PreferencesTooLarge,
+	#[error("Ratings must be between 0 and 5")]
+	InvalidRating,
+	#[error("ListenBrainz account is not linked")]
+	ListenBrainzNotLinked,
+	#[error("ListenBrainz token is invalid")]
+	InvalidListenBrainzToken,
+	#[error("ListenBrainz request failed:\n\n{0}")]
+	ListenBrainzError(String),
+	#[error("Invite is invalid or expired")]
+	InvalidInvite,
+	#[error("Invites must be valid for at least one day")]
+	InvalidInviteValidity,
+	#[error("Cannot use empty API key name")]
+	EmptyApiKeyName,
+	#[error("API key does not allow this request")]
+	ApiKeyScopeRequired,
 	#[error("Username already exists")]
 	DuplicateUsername,
 	#[error("EmbeddedArtworkNotFound")]
 	EmbeddedArtworkNotFound,
+	#[error("LyricsNotFound")]
+	LyricsNotFound,
+	#[error("AudioFingerprintNotFound")]
+	AudioFingerprintNotFound,
+	#[error("ArtistImageNotFound")]
+	ArtistImageNotFound,
 	#[error("EmptyUsername")]
 	EmptyUsername,
 	#[error("EmptyPassword")]
 	EmptyPassword,
+	#[error("Password does not meet the password policy: {0}")]
+	WeakPassword(String),
+	#[error("Password must be changed before signing in")]
+	PasswordChangeRequired,
 	#[error("Incorrect Credentials")]
 	IncorrectCredentials,
+	#[error("Account is temporarily locked after too many failed logins")]
+	AccountLocked,
+	#[error("Too many failed attempts, retry in {}s", .0.as_secs_f64().ceil())]
+	LoginThrottled(std::time::Duration),
 	#[error("Internal server error")]
 	Internal,
 	#[error("Could not parse album art pattern")]
 	InvalidAlbumArtPattern,
 	#[error("Could not parse DDNS update URL")]
 	InvalidDDNSURL,
+	#[error("Could not parse filename pattern")]
+	InvalidFilenamePattern,
+	#[error("Invalid base path")]
+	InvalidBasePath,
+	#[error("Invalid ACME domain")]
+	InvalidAcmeDomain,
+	#[error("Alias name cannot be blank")]
+	InvalidAlias,
+	#[error("Could not parse post-scan webhook URL")]
+	InvalidPostScanWebhookURL,
+	#[error("Webhook URL must be an http or https URL")]
+	InvalidWebhookURL,
+	#[error("Limits must be greater than zero")]
+	InvalidLimit,
+	#[error("Token lifetime must be greater than zero")]
+	InvalidTokenLifetime,
+	#[error("Lockout threshold and duration must be greater than zero")]
+	InvalidLockout,
+	#[error("OpenID Connect issuer URL must be an http or https URL")]
+	InvalidOidcIssuerURL,
+	#[error("Passkey origin must be an http or https URL without a path")]
+	InvalidPasskeyOrigin,
+	#[error(
+		"Passkey relying party ID must be the origin's host name or one of its parent domains"
+	)]
+	InvalidPasskeyRelyingParty,
+	#[error("OpenID Connect login is not configured")]
+	OidcNotConfigured,
+	#[error("Device login code not found or expired")]
+	DeviceLoginNotFound,
+	#[error("Device login expired or was not started by this server")]
+	DeviceLoginExpired,
+	#[error("Device login was not approved yet")]
+	DeviceLoginPending,
+	#[error("Device login was polled too often, slow down")]
+	DeviceLoginSlowDown,
+	#[error("Too many logins are pending")]
+	TooManyPendingLogins,
+	#[error("OpenID Connect login expired or was not started by this server")]
+	OidcLoginExpired,
+	#[error("This account of the identity provider is not linked to a Polaris user")]
+	OidcAccountNotLinked,
+	#[error("OpenID Connect return path must be a path on this server")]
+	InvalidOidcReturnPath,
+	#[error("OpenID Connect provider request failed:\n\n{0}")]
+	OidcProviderError(String),
+	#[error("Could not parse trusted proxy address")]
+	InvalidTrustedProxy,
+	#[error("Could not parse scan schedule")]
+	InvalidScanSchedule,
+	#[error("Could not parse exclude pattern")]
+	InvalidExcludePattern,
+	#[error("Remote storage settings are invalid")]
+	InvalidRemoteStorage,
+	#[error("Remote storage could not be reached")]
+	RemoteStorageUnavailable,
+	#[error("Artist separators cannot be empty")]
+	InvalidArtistSeparator,
+	#[error("The fanart.tv artist image provider requires an API key")]
+	FanartTvApiKeyMissing,
 	#[error("File I/O error for `{0}`:\n\n{1}")]
 	Io(PathBuf, std::io::Error),
 	#[error("Cannot remove your own admin privilege")]
 	OwnAdminPrivilegeRemoval,
+	#[error("No collection scan is in progress")]
+	NoScanInProgress,
+	#[error("Collection scan is paused")]
+	ScanPaused,
+	#[error("Jukebox is not available")]
+	JukeboxUnavailable,
+	#[error("Jukebox queue position is out of range")]
+	JukeboxPositionInvalid,
 	#[error("Could not hash password")]
 	PasswordHashing,
 	#[error("Playlist not found")]
 	PlaylistNotFound,
+	#[error("Play queue position is out of range")]
+	PlayQueuePositionInvalid,
+	#[error("Radio station not found")]
+	RadioStationNotFound,
+	#[error("Cannot use empty radio station name")]
+	EmptyRadioStationName,
+	#[error("Radio station URL must be an http or https URL")]
+	RadioStationUrlInvalid,
+	#[error("Could not connect to radio station")]
+	RadioStreamUnavailable,
+	#[error("Could not transcode audio")]
+	TranscodingFailed,
+	#[error("Stream bitrate must be between 8 and 320 kbps")]
+	InvalidStreamBitrate,
+	#[error("Uploaded artwork is not a supported image")]
+	ArtworkInvalid,
+	#[error("Artwork override not found")]
+	ArtworkOverrideNotFound,
 	#[error("Could not parse search query")]
 	SearchQueryParseError,
 	#[error("Could not decode thumbnail from flac file `{0}`:\n\n{1}")]
@@ -97,6 +241,7 @@ impl From<app::Error> for APIError {
 			app::Error::FileWatch(_) => APIError::Internal,
 			app::Error::Ape(_) => APIError::Internal,
 			app::Error::Id3(p, e) => APIError::ThumbnailId3Decoding(p, e),
+			app::Error::CueSheetInvalid(_) => APIError::Internal,
 			app::Error::Metaflac(p, e) => APIError::ThumbnailFlacDecoding(p, e),
 			app::Error::Mp4aMeta(p, e) => APIError::ThumbnailMp4Decoding(p, e),
 			app::Error::Opus(_) => APIError::Internal,
@@ -110,6 +255,7 @@ impl From<app::Error> for APIError {
 			app::Error::MediaDecoderError(e) => APIError::AudioDecoding(e),
 			app::Error::MediaPacketError(e) => APIError::AudioDecoding(e),
 			app::Error::MediaProbeError(e) => APIError::AudioDecoding(e),
+			app::Error::AudioFingerprint(_) => APIError::Internal,
 
 			app::Error::PeaksSerialization(_) => APIError::Internal,
 			app::Error::PeaksDeserialization(_) => APIError::Internal,
@@ -124,31 +270,103 @@ impl From<app::Error> for APIError {
 			app::Error::AuthenticationSecretInvalid => APIError::Internal,
 			app::Error::MiscSettingsNotFound => APIError::Internal,
 			app::Error::DDNSUpdateURLInvalid => APIError::InvalidDDNSURL,
+			app::Error::BasePathInvalid => APIError::InvalidBasePath,
+			app::Error::AcmeDomainInvalid => APIError::InvalidAcmeDomain,
 			app::Error::IndexAlbumArtPatternInvalid => APIError::InvalidAlbumArtPattern,
+			app::Error::FilenamePatternInvalid => APIError::InvalidFilenamePattern,
+			app::Error::AliasInvalid => APIError::InvalidAlias,
+			app::Error::PostScanWebhookURLInvalid => APIError::InvalidPostScanWebhookURL,
+			app::Error::WebhookURLInvalid => APIError::InvalidWebhookURL,
+			app::Error::LimitInvalid => APIError::InvalidLimit,
+			app::Error::TokenLifetimeInvalid => APIError::InvalidTokenLifetime,
+			app::Error::LockoutInvalid => APIError::InvalidLockout,
+			app::Error::OidcIssuerURLInvalid => APIError::InvalidOidcIssuerURL,
+			app::Error::PasskeyOriginInvalid => APIError::InvalidPasskeyOrigin,
+			app::Error::PasskeyRelyingPartyInvalid => APIError::InvalidPasskeyRelyingParty,
+			app::Error::TrustedProxyInvalid => APIError::InvalidTrustedProxy,
+			app::Error::ScanScheduleInvalid => APIError::InvalidScanSchedule,
+			app::Error::ExcludePatternInvalid => APIError::InvalidExcludePattern,
+			app::Error::S3EndpointInvalid => APIError::InvalidRemoteStorage,
+			app::Error::S3BucketMissing => APIError::InvalidRemoteStorage,
+			app::Error::WebdavURLInvalid => APIError::InvalidRemoteStorage,
+			app::Error::SmbServerInvalid => APIError::InvalidRemoteStorage,
+			app::Error::SmbShareMissing => APIError::InvalidRemoteStorage,
+			app::Error::ArtistSeparatorInvalid => APIError::InvalidArtistSeparator,
+			app::Error::FanartTvApiKeyMissing => APIError::FanartTvApiKeyMissing,
 
 			app::Error::ConfigDeserialization(_) => APIError::Internal,
 			app::Error::ConfigSerialization(_) => APIError::Internal,
 			app::Error::IndexDeserializationError => APIError::Internal,
 			app::Error::IndexSerializationError => APIError::Internal,
+			app::Error::SearchIndex(_) => APIError::Internal,
 
 			app::Error::CouldNotMapToRealPath(_) => APIError::VFSPathNotFound,
 			app::Error::CouldNotMapToVirtualPath(_) => APIError::Internal,
 			app::Error::UserNotFound => APIError::UserNotFound,
 			app::Error::DirectoryNotFound(d) => APIError::DirectoryNotFound(d),
+			app::Error::NoScanInProgress => APIError::NoScanInProgress,
+			app::Error::ScanPaused => APIError::ScanPaused,
+			app::Error::JukeboxUnavailable => APIError::JukeboxUnavailable,
+			app::Error::JukeboxPositionInvalid => APIError::JukeboxPositionInvalid,
+			app::Error::AudioOutput(_) => APIError::JukeboxUnavailable,
 			app::Error::ArtistNotFound => APIError::ArtistNotFound,
 			app::Error::AlbumNotFound => APIError::AlbumNotFound,
 			app::Error::GenreNotFound => APIError::GenreNotFound,
 			app::Error::SongNotFound => APIError::SongNotFound,
 			app::Error::PlaylistNotFound => APIError::PlaylistNotFound,
+			app::Error::PlayQueuePositionInvalid => APIError::PlayQueuePositionInvalid,
+			app::Error::RadioStationNotFound => APIError::RadioStationNotFound,
+			app::Error::ArtworkInvalid => APIError::ArtworkInvalid,
+			app::Error::ArtworkOverrideNotFound => APIError::ArtworkOverrideNotFound,
+			app::Error::EmptyRadioStationName => APIError::EmptyRadioStationName,
+			app::Error::RadioStationUrlInvalid => APIError::RadioStationUrlInvalid,
+			app::Error::RadioStreamUnavailable => APIError::RadioStreamUnavailable,
+			app::Error::TranscodingFailed(_) => APIError::TranscodingFailed,
+			app::Error::RemoteStorage(_, _) => APIError::RemoteStorageUnavailable,
+			app::Error::StreamBitrateInvalid => APIError::InvalidStreamBitrate,
 			app::Error::SearchQueryParseError => APIError::SearchQueryParseError,
 			app::Error::EmbeddedArtworkNotFound(_) => APIError::EmbeddedArtworkNotFound,
+			app::Error::LyricsNotFound(_) => APIError::LyricsNotFound,
+			app::Error::AudioFingerprintNotFound(_) => APIError::AudioFingerprintNotFound,
+			app::Error::ArtistImageNotFound => APIError::ArtistImageNotFound,
 
 			app::Error::DuplicateUsername => APIError::DuplicateUsername,
 			app::Error::EmptyUsername => APIError::EmptyUsername,
 			app::Error::EmptyPassword => APIError::EmptyPassword,
+			app::Error::PasswordTooWeak(s) => APIError::WeakPassword(s),
+			app::Error::PasswordChangeRequired => APIError::PasswordChangeRequired,
 			app::Error::IncorrectUsername => APIError::IncorrectCredentials,
 			app::Error::IncorrectPassword => APIError::IncorrectCredentials,
+			app::Error::AccountLocked => APIError::AccountLocked,
+			app::Error::LoginThrottled(d) => APIError::LoginThrottled(d),
 			app::Error::InvalidAuthToken => APIError::IncorrectCredentials,
+			app::Error::SessionNotFound => APIError::SessionNotFound,
+			app::Error::PasskeyNotFound => APIError::PasskeyNotFound,
+			app::Error::PasskeyAlreadyRegistered => APIError::PasskeyAlreadyRegistered,
+			app::Error::PasskeyChallengeExpired => APIError::PasskeyChallengeExpired,
+			app::Error::PasskeyRegistrationInvalid(e) => APIError::InvalidPasskeyRegistration(e),
+			app::Error::PasskeyAuthenticationFailed(_) => APIError::IncorrectCredentials,
+			app::Error::ApiKeyNotFound => APIError::ApiKeyNotFound,
+			app::Error::InviteNotFound => APIError::InviteNotFound,
+			app::Error::PreferenceKeyInvalid(k) => APIError::InvalidPreferenceKey(k),
+			app::Error::PreferencesTooLarge => APIError::PreferencesTooLarge,
+			app::Error::RatingInvalid => APIError::InvalidRating,
+			app::Error::ListenBrainzNotLinked => APIError::ListenBrainzNotLinked,
+			app::Error::ListenBrainzTokenInvalid => APIError::InvalidListenBrainzToken,
+			app::Error::ListenBrainzFailed(e) => APIError::ListenBrainzError(e),
+			app::Error::InviteInvalid => APIError::InvalidInvite,
+			app::Error::InviteValidityInvalid => APIError::InvalidInviteValidity,
+			app::Error::EmptyApiKeyName => APIError::EmptyApiKeyName,
+			app::Error::OidcNotConfigured => APIError::OidcNotConfigured,
+			app::Error::DeviceLoginNotFound => APIError::DeviceLoginNotFound,
+			app::Error::DeviceLoginExpired => APIError::DeviceLoginExpired,
+			app::Error::DeviceLoginSlowDown => APIError::DeviceLoginSlowDown,
+			app::Error::TooManyPendingLogins => APIError::TooManyPendingLogins,
+			app::Error::OidcLoginExpired => APIError::OidcLoginExpired,
+			app::Error::OidcAccountNotLinked => APIError::OidcAccountNotLinked,
+			app::Error::OidcReturnPathInvalid => APIError::InvalidOidcReturnPath,
+			app::Error::OidcProviderFailed(e) => APIError::OidcProviderError(e),
+			app::Error::OidcTokenInvalid(_) => APIError::IncorrectCredentials,
 			app::Error::IncorrectAuthorizationScope => APIError::IncorrectCredentials,
 			app::Error::PasswordHashing => APIError::PasswordHashing,
 			app::Error::AuthorizationTokenEncoding => APIError::AuthorizationTokenEncoding,
@@ -14,13 +14,18 @@ mod admin;
 mod auth;
 mod browser;
 mod collection;
+mod dlna;
 mod docs;
+mod health;
+mod jukebox;
 mod media;
 mod playlist;
+mod radio;
 mod search;
 mod settings;
 mod user;
 mod web;
+mod webdav;
 
 use crate::server::dto;
 use crate::server::test::constants::*;
@@ -31,6 +36,9 @@ pub use crate::server::axum::test::ServiceType;
 pub trait TestService {
 	async fn new(test_name: &str) -> Self;
 
+	// Starts with the given content in the configuration file
+	async fn new_with_config(test_name: &str, config: &str) -> Self;
+
 	async fn execute_request<T: Serialize + Clone + 'static>(
 		&mut self,
 		request: &Request<T>,
@@ -68,6 +76,10 @@ pub trait TestService {
 			self.fetch(&protocol::put_mount_dirs(vec![dto::MountDir {
 				name: TEST_MOUNT_NAME.into(),
 				source: TEST_MOUNT_SOURCE.into(),
+				exclude_patterns: vec![],
+				symlinks: Default::default(),
+				split_artists: None,
+				remote: None,
 			}]))
 			.await
 			.status(),
@@ -79,6 +91,8 @@ pub trait TestService {
 				name: TEST_USERNAME_ADMIN.into(),
 				password: TEST_PASSWORD_ADMIN.into(),
 				admin: true,
+				guest: false,
+				permissions: None,
 			}))
 			.await
 			.status(),
@@ -92,6 +106,8 @@ pub trait TestService {
 				name: TEST_USERNAME.into(),
 				password: TEST_PASSWORD.into(),
 				admin: false,
+				guest: false,
+				permissions: None,
 			}))
 			.await
 			.status(),
@@ -140,6 +156,32 @@ pub trait TestService {
 	}
 }
 
+fn add_version_prefix<T>(request: &mut Request<T>, version: i32) {
+	*request.uri_mut() = request
+		.uri()
+		.to_string()
+		.replacen("/api/", &format!("/api/v{version}/"), 1)
+		.parse()
+		.unwrap();
+}
+
+fn add_pagination<T>(request: &mut Request<T>, offset: usize, count: usize) {
+	*request.uri_mut() = format!("{}?offset={offset}&count={count}", request.uri())
+		.parse()
+		.unwrap();
+}
+
+fn add_listing_sort<T>(request: &mut Request<T>, sort: Option<&str>, seed: Option<u64>) {
+	let parameters = [
+		sort.map(|s| format!("sort={s}")),
+		seed.map(|s| format!("seed={s}")),
+	];
+	let parameters = parameters.into_iter().flatten().collect::<Vec<_>>();
+	*request.uri_mut() = format!("{}?{}", request.uri(), parameters.join("&"))
+		.parse()
+		.unwrap();
+}
+
 fn add_trailing_slash<T>(request: &mut Request<T>) {
 	*request.uri_mut() = (request.uri().to_string().trim_end_matches('/').to_string() + "/")
 		.parse()
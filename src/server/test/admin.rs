@@ -1,8 +1,11 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
 use http::StatusCode;
 
 use crate::server::dto;
 use crate::server::test::protocol::V8;
-use crate::server::test::{protocol, ServiceType, TestService};
+use crate::server::test::{constants::*, protocol, ServiceType, TestService};
 use crate::test_name;
 
 #[tokio::test]
@@ -13,6 +16,18 @@ async fn returns_api_version() {
 	assert_eq!(response.status(), StatusCode::OK);
 }
 
+#[tokio::test]
+async fn responses_include_request_id() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	let request = protocol::version();
+	let first = service.fetch(&request).await;
+	let second = service.fetch(&request).await;
+	let first_id = first.headers().get("x-request-id").unwrap();
+	let second_id = second.headers().get("x-request-id").unwrap();
+	assert_eq!(first_id.len(), 36);
+	assert_ne!(first_id, second_id);
+}
+
 #[tokio::test]
 async fn initial_setup_golden_path() {
 	let mut service = ServiceType::new(&test_name!()).await;
@@ -83,3 +98,86 @@ async fn trigger_index_requires_admin() {
 	let response = service.fetch(&request).await;
 	assert_eq!(response.status(), StatusCode::FORBIDDEN);
 }
+
+#[tokio::test]
+async fn index_refresh_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+
+	let path = PathBuf::from_iter([TEST_MOUNT_NAME, "Khemmis"]);
+	let request = protocol::index_refresh(&path);
+	let response = service.fetch_json::<_, dto::SongList>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().paths.len(), 5);
+
+	let request = protocol::flatten::<V8>(&PathBuf::from(TEST_MOUNT_NAME));
+	let response = service.fetch_json::<_, dto::SongList>(&request).await;
+	assert_eq!(response.body().paths.len(), 5);
+}
+
+#[tokio::test]
+async fn index_refresh_rejects_unknown_directory() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+
+	let path = PathBuf::from_iter([TEST_MOUNT_NAME, "oink"]);
+	let request = protocol::index_refresh(&path);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn index_refresh_requires_admin() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+
+	let path = PathBuf::from(TEST_MOUNT_NAME);
+	let request = protocol::index_refresh(&path);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn index_controls_require_admin() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+
+	for request in [
+		protocol::index_pause(),
+		protocol::index_resume(),
+		protocol::index_cancel(),
+	] {
+		let response = service.fetch(&request).await;
+		assert_eq!(response.status(), StatusCode::FORBIDDEN);
+	}
+}
+
+#[tokio::test]
+async fn index_controls_require_scan_in_progress() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+
+	loop {
+		let request = protocol::index_status();
+		let response = service.fetch_json::<_, dto::IndexStatus>(&request).await;
+		if response.body().state == dto::IndexState::UpToDate {
+			break;
+		}
+		tokio::time::sleep(Duration::from_millis(100)).await;
+	}
+
+	for request in [
+		protocol::index_pause(),
+		protocol::index_resume(),
+		protocol::index_cancel(),
+	] {
+		let response = service.fetch(&request).await;
+		assert_eq!(response.status(), StatusCode::CONFLICT);
+	}
+}
@@ -26,6 +26,47 @@ async fn login_rejects_bad_password() {
 	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
 }
 
+#[tokio::test]
+async fn login_is_throttled_after_repeated_failures() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+
+	for _ in 0..4 {
+		let request = protocol::login(TEST_USERNAME, "garbage");
+		let response = service.fetch(&request).await;
+		assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+	}
+
+	let request = protocol::login(TEST_USERNAME, TEST_PASSWORD);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+	assert!(response.headers().contains_key(http::header::RETRY_AFTER));
+
+	let request = protocol::login(TEST_USERNAME_ADMIN, TEST_PASSWORD_ADMIN);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+}
+
+const LOCKOUT_CONFIG: &str = "[auth]\nlockout_threshold = 1\n";
+
+#[tokio::test]
+async fn failed_password_changes_lock_account() {
+	let mut service = ServiceType::new_with_config(&test_name!(), LOCKOUT_CONFIG).await;
+	service.complete_initial_setup().await;
+
+	let request = protocol::login_with_new_password(TEST_USERNAME, "garbage", "new password");
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+	let request = protocol::login_with_new_password(TEST_USERNAME, TEST_PASSWORD, "new password");
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::LOCKED);
+
+	let request = protocol::login(TEST_USERNAME, TEST_PASSWORD);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::LOCKED);
+}
+
 #[tokio::test]
 async fn login_golden_path() {
 	let mut service = ServiceType::new(&test_name!()).await;
@@ -111,3 +152,422 @@ async fn authentication_via_query_param_golden_path() {
 	let response = service.fetch(&request).await;
 	assert_eq!(response.status(), StatusCode::OK);
 }
+
+#[tokio::test]
+async fn sessions_requires_auth() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	let request = protocol::sessions();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn sessions_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+
+	let mut request = protocol::login(TEST_USERNAME, TEST_PASSWORD);
+	request
+		.headers_mut()
+		.insert(http::header::USER_AGENT, "Polaris Test".parse().unwrap());
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	service.login().await;
+
+	let request = protocol::sessions();
+	let response = service.fetch_json::<_, Vec<dto::Session>>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let sessions = response.body();
+	assert_eq!(sessions.len(), 2);
+	assert_eq!(sessions.iter().filter(|s| s.current).count(), 1);
+	assert!(sessions
+		.iter()
+		.any(|s| s.user_agent.as_deref() == Some("Polaris Test")));
+}
+
+#[tokio::test]
+async fn session_can_be_revoked() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+
+	let mut request = protocol::login(TEST_USERNAME, TEST_PASSWORD);
+	request.body_mut().client_name = Some("Kitchen".to_owned());
+	let response = service.fetch_json::<_, dto::Authorization>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let kitchen_authorization = response.into_body();
+
+	service.login().await;
+
+	let request = protocol::sessions();
+	let response = service.fetch_json::<_, Vec<dto::Session>>(&request).await;
+	let kitchen_session = response
+		.body()
+		.iter()
+		.find(|s| s.client_name.as_deref() == Some("Kitchen"))
+		.unwrap()
+		.clone();
+	assert!(!kitchen_session.current);
+
+	let request = protocol::delete_session(&kitchen_session.id);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::delete_session(&kitchen_session.id);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+	service.set_authorization(Some(kitchen_authorization));
+	let request = protocol::sessions();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn other_sessions_can_be_revoked() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+	service.login().await;
+	service.login().await;
+
+	let request = protocol::delete_sessions();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::sessions();
+	let response = service.fetch_json::<_, Vec<dto::Session>>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let sessions = response.body();
+	assert_eq!(sessions.len(), 1);
+	assert!(sessions[0].current);
+}
+
+#[tokio::test]
+async fn oidc_login_requires_configuration() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+
+	let request = protocol::oidc_login(None);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn oidc_login_rejects_foreign_return_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+
+	let request = protocol::oidc_login(Some("https://evil.example.com"));
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn oidc_callback_rejects_unknown_login() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+
+	let request = protocol::oidc_callback("garbage", "garbage");
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn api_keys_requires_auth() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	let request = protocol::get_api_keys();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn api_key_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+
+	let request = protocol::post_api_key(dto::NewApiKey {
+		name: "Scrobbler".to_owned(),
+		scopes: vec![dto::ApiKeyScope::Read],
+	});
+	let response = service.fetch_json::<_, dto::CreatedApiKey>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let created = response.into_body();
+	assert_eq!(created.api_key.name, "Scrobbler");
+
+	let request = protocol::get_api_keys();
+	let response = service.fetch_json::<_, Vec<dto::ApiKey>>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body(), &vec![created.api_key.clone()]);
+
+	service.set_authorization(Some(dto::Authorization {
+		username: TEST_USERNAME.to_owned(),
+		token: created.key.clone(),
+		is_admin: false,
+	}));
+
+	let request = protocol::get_play_queue();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::put_play_queue(dto::SavePlayQueueInput {
+		songs: vec![],
+		position: 0,
+		offset: 0,
+	});
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+	let request = protocol::post_api_key(dto::NewApiKey {
+		name: "Escalation".to_owned(),
+		scopes: vec![dto::ApiKeyScope::Admin],
+	});
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+	service.login().await;
+	let request = protocol::delete_api_key(&created.api_key.id);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	service.set_authorization(Some(dto::Authorization {
+		username: TEST_USERNAME.to_owned(),
+		token: created.key,
+		is_admin: false,
+	}));
+	let request = protocol::get_play_queue();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn api_key_without_admin_scope_cannot_administer() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+
+	let request = protocol::post_api_key(dto::NewApiKey {
+		name: "Backup script".to_owned(),
+		scopes: vec![dto::ApiKeyScope::Read],
+	});
+	let response = service.fetch_json::<_, dto::CreatedApiKey>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let created = response.into_body();
+
+	service.set_authorization(Some(dto::Authorization {
+		username: TEST_USERNAME_ADMIN.to_owned(),
+		token: created.key,
+		is_admin: true,
+	}));
+	let request = protocol::get_settings();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn passkeys_requires_auth() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+
+	let request = protocol::get_passkeys();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+	let request = protocol::passkey_register_start();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn passkey_registration_start_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+
+	let request = protocol::passkey_register_start();
+	let response = service
+		.fetch_json::<_, dto::PasskeyCreationOptions>(&request)
+		.await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let options = response.body();
+	assert_eq!(options.user.name, TEST_USERNAME);
+	assert!(!options.challenge.is_empty());
+	assert!(options.exclude_credentials.is_empty());
+
+	let request = protocol::get_passkeys();
+	let response = service.fetch_json::<_, Vec<dto::Passkey>>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(response.body().is_empty());
+
+	let request = protocol::delete_passkey("garbage");
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+const PASSKEYS_CONFIG: &str =
+	"[passkeys]\norigin = \"https://music.example.com\"\nrp_id = \"example.com\"\n";
+
+#[tokio::test]
+async fn passkeys_use_configured_relying_party() {
+	let mut service = ServiceType::new_with_config(&test_name!(), PASSKEYS_CONFIG).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+
+	let mut request = protocol::passkey_register_start();
+	request
+		.headers_mut()
+		.typed_insert(headers::Host::from(http::uri::Authority::from_static(
+			"evil.example.org",
+		)));
+	let response = service
+		.fetch_json::<_, dto::PasskeyCreationOptions>(&request)
+		.await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().rp.id, "example.com");
+
+	let mut request = protocol::passkey_login_start(dto::PasskeyLoginOptions::default());
+	request
+		.headers_mut()
+		.typed_insert(headers::Host::from(http::uri::Authority::from_static(
+			"evil.example.org",
+		)));
+	let response = service
+		.fetch_json::<_, dto::PasskeyRequestOptions>(&request)
+		.await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().rp_id, "example.com");
+}
+
+#[tokio::test]
+async fn passkey_registration_rejects_garbage() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+
+	let request = protocol::passkey_register_finish(dto::NewPasskey {
+		name: Some("Laptop".to_owned()),
+		client_data_json: "Z2FyYmFnZQ".to_owned(),
+		attestation_object: "Z2FyYmFnZQ".to_owned(),
+	});
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn passkey_login_rejects_unknown_challenge() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+
+	let request = protocol::passkey_login_start(dto::PasskeyLoginOptions::default());
+	let response = service
+		.fetch_json::<_, dto::PasskeyRequestOptions>(&request)
+		.await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(response.body().allow_credentials.is_empty());
+
+	let request = protocol::passkey_login_finish(dto::PasskeyAssertion {
+		credential_id: "garbage".to_owned(),
+		client_data_json: "Z2FyYmFnZQ".to_owned(),
+		authenticator_data: "Z2FyYmFnZQ".to_owned(),
+		signature: "Z2FyYmFnZQ".to_owned(),
+		client_name: None,
+	});
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn refresh_requires_auth() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+
+	let request = protocol::refresh_auth();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn refresh_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+
+	let request = protocol::refresh_auth();
+	let response = service.fetch_json::<_, dto::Authorization>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let authorization = response.into_body();
+	assert_eq!(authorization.username, TEST_USERNAME);
+
+	service.set_authorization(Some(authorization));
+	let request = protocol::sessions();
+	let response = service.fetch_json::<_, Vec<dto::Session>>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let sessions = response.body();
+	assert_eq!(sessions.len(), 1);
+	assert!(sessions[0].current);
+}
+
+#[tokio::test]
+async fn device_login_approval_requires_auth() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	let request = protocol::approve_device_login("BBBB-BBBB");
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn device_login_rejects_unknown_codes() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+
+	let request = protocol::approve_device_login("BBBB-BBBB");
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+	let request = protocol::device_login_token("not a device code");
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn device_login_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+
+	let request = protocol::device_login(Some("Living room TV"));
+	let response = service.fetch_json::<_, dto::DeviceLogin>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let device_login = response.into_body();
+
+	let request = protocol::device_login_token(&device_login.device_code);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+	service.login().await;
+	let request = protocol::approve_device_login(&device_login.user_code);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	service.logout().await;
+	let request = protocol::device_login_token(&device_login.device_code);
+	let response = service.fetch_json::<_, dto::Authorization>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let authorization = response.into_body();
+	assert_eq!(authorization.username, TEST_USERNAME);
+	assert!(!authorization.is_admin);
+
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+	service.set_authorization(Some(authorization));
+	let request = protocol::sessions();
+	let response = service.fetch_json::<_, Vec<dto::Session>>(&request).await;
+	assert!(response
+		.body()
+		.iter()
+		.any(|s| s.current && s.client_name.as_deref() == Some("Living room TV")));
+}
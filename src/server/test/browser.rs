@@ -1,9 +1,12 @@
-use http::StatusCode;
+use http::{header, HeaderValue, StatusCode};
 use std::path::{Path, PathBuf};
 
 use crate::server::dto;
 use crate::server::test::protocol::{V7, V8};
-use crate::server::test::{constants::*, protocol, ServiceType, TestService};
+use crate::server::test::{
+	add_listing_sort, add_pagination, add_version_prefix, constants::*, protocol, ServiceType,
+	TestService,
+};
 use crate::test_name;
 
 #[tokio::test]
@@ -49,6 +52,57 @@ async fn browse_directory() {
 	assert_eq!(entries.len(), 5);
 }
 
+#[tokio::test]
+async fn browse_directory_paginated() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let mut request = protocol::browse::<V8>(&path);
+	add_pagination(&mut request, 1, 2);
+	let response = service
+		.fetch_json::<_, Vec<dto::BrowserEntry>>(&request)
+		.await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.headers().get("X-Total-Count").unwrap(), "5");
+	let entries = response.body();
+	assert_eq!(entries.len(), 2);
+	assert_eq!(entries[0].path, path.join("02 - Candlelight.mp3"));
+}
+
+#[tokio::test]
+async fn browse_directory_sorted() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Tobokegao"].iter().collect();
+	let mut request = protocol::browse::<V8>(&path);
+	add_listing_sort(&mut request, Some("year"), None);
+	let response = service
+		.fetch_json::<_, Vec<dto::BrowserEntry>>(&request)
+		.await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let entries = response.body();
+	assert_eq!(entries.len(), 2);
+
+	let mut request = protocol::browse::<V8>(&path);
+	add_listing_sort(&mut request, Some("random"), Some(976878));
+	let first = service
+		.fetch_json::<_, Vec<dto::BrowserEntry>>(&request)
+		.await;
+	let second = service
+		.fetch_json::<_, Vec<dto::BrowserEntry>>(&request)
+		.await;
+	assert_eq!(first.status(), StatusCode::OK);
+	assert_eq!(first.body(), second.body());
+}
+
 #[tokio::test]
 async fn browse_missing_directory() {
 	let mut service = ServiceType::new(&test_name!()).await;
@@ -85,6 +139,71 @@ async fn browse_directory_api_v7() {
 	}
 }
 
+#[tokio::test]
+async fn browse_directory_versioned_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	// Version in path takes precedence over the header
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let mut request = protocol::browse::<V7>(&path);
+	add_version_prefix(&mut request, 8);
+	let response = service
+		.fetch_json::<_, Vec<dto::BrowserEntry>>(&request)
+		.await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(response.headers().get("deprecation").is_none());
+	let entries = response.body();
+	assert_eq!(entries.len(), 5);
+}
+
+#[tokio::test]
+async fn browse_directory_deprecated_version_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let mut request = protocol::browse::<V8>(&path);
+	add_version_prefix(&mut request, 7);
+	let response = service
+		.fetch_json::<_, Vec<dto::v7::CollectionFile>>(&request)
+		.await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.headers().get("deprecation").unwrap(), "true");
+	assert_eq!(
+		response.headers().get(header::LINK).unwrap(),
+		"</api/v8>; rel=\"successor-version\""
+	);
+	assert_eq!(response.body().len(), 5);
+}
+
+#[tokio::test]
+async fn browse_directory_unversioned_path_deprecation() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+
+	let request = protocol::browse::<V7>(&path);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.headers().get("deprecation").unwrap(), "true");
+
+	let request = protocol::browse::<V8>(&path);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(response.headers().get("deprecation").is_none());
+}
+
 #[tokio::test]
 async fn flatten_requires_auth() {
 	let mut service = ServiceType::new(&test_name!()).await;
@@ -123,6 +242,47 @@ async fn flatten_directory() {
 	assert_eq!(song_list.paths.len(), 13);
 }
 
+#[tokio::test]
+async fn flatten_paginated() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let mut request = protocol::flatten::<V8>(&PathBuf::new());
+	add_pagination(&mut request, 10, 100);
+	let response = service.fetch_json::<_, dto::SongList>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.headers().get("X-Total-Count").unwrap(), "13");
+	let song_list = response.body();
+	assert_eq!(song_list.paths.len(), 3);
+	assert_eq!(song_list.first_songs.len(), 3);
+}
+
+#[tokio::test]
+async fn flatten_compresses_response() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	for encoding in ["br", "gzip"] {
+		let mut request = protocol::flatten::<V8>(&PathBuf::new());
+		request.headers_mut().append(
+			header::ACCEPT_ENCODING,
+			HeaderValue::from_str(encoding).unwrap(),
+		);
+		let response = service.fetch(&request).await;
+		assert_eq!(response.status(), StatusCode::OK);
+		assert_eq!(
+			response.headers().get(header::CONTENT_ENCODING).unwrap(),
+			encoding
+		);
+	}
+}
+
 #[tokio::test]
 async fn flatten_missing_directory() {
 	let mut service = ServiceType::new(&test_name!()).await;
@@ -152,3 +312,102 @@ async fn flatten_directory_api_v7() {
 
 	assert_eq!(entries[0].path, path.join("01 - Above The Water.mp3"));
 }
+
+#[tokio::test]
+async fn zip_requires_auth() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	let request = protocol::zip(Path::new(TEST_MOUNT_NAME));
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn zip_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let request = protocol::zip(&path);
+	let response = service.fetch_bytes(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(
+		response.headers().get(header::CONTENT_TYPE),
+		Some(&HeaderValue::from_static("application/zip"))
+	);
+	assert_eq!(
+		response.headers().get(header::CONTENT_DISPOSITION),
+		Some(&HeaderValue::from_static(
+			"attachment; filename=\"Hunted.zip\"; filename*=UTF-8''Hunted.zip"
+		))
+	);
+
+	let archive = String::from_utf8_lossy(response.body());
+	assert!(response.body().starts_with(b"PK\x03\x04"));
+	assert!(archive.contains("Hunted/01 - Above The Water.mp3"));
+	assert!(archive.contains("Hunted/05 - Hunted.mp3"));
+	assert!(archive.contains("Hunted/Folder.jpg"));
+}
+
+#[tokio::test]
+async fn zip_can_be_resumed() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let request = protocol::zip(&path);
+	let response = service.fetch_bytes(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let archive = response.body().clone();
+	assert_eq!(
+		response.headers().get(header::CONTENT_LENGTH).unwrap(),
+		&HeaderValue::from(archive.len())
+	);
+	let etag = response.headers().get(header::ETAG).unwrap().clone();
+
+	let mut request = protocol::zip(&path);
+	request
+		.headers_mut()
+		.append(header::IF_NONE_MATCH, etag.clone());
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+	let mut request = protocol::zip(&path);
+	let headers = request.headers_mut();
+	headers.append(header::RANGE, HeaderValue::from_static("bytes=1000-"));
+	headers.append(header::IF_RANGE, etag);
+	let response = service.fetch_bytes(&request).await;
+	assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+	assert_eq!(response.body()[..], archive[1000..]);
+}
+
+#[tokio::test]
+async fn zip_is_refused_to_capped_users() {
+	let config = format!(
+		"[[mount_dirs]]\nname = \"{TEST_MOUNT_NAME}\"\nsource = \"{TEST_MOUNT_SOURCE}\"\n\n[[users]]\nname = \"{TEST_USERNAME}\"\ninitial_password = \"{TEST_PASSWORD}\"\n\n[users.stream_quality]\nmax_bitrate_kbps = 64\nremote_only = false\n"
+	);
+	let mut service = ServiceType::new_with_config(&test_name!(), &config).await;
+	service.login().await;
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let request = protocol::zip(&path);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn zip_bad_directory() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+
+	let path: PathBuf = ["not_my_collection"].iter().collect();
+	let request = protocol::zip(&path);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
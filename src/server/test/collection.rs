@@ -1,10 +1,13 @@
+use std::path::{Path, PathBuf};
+
 use http::StatusCode;
 
 use crate::{
 	server::{
 		dto,
 		test::{
-			add_trailing_slash,
+			add_listing_sort, add_pagination, add_trailing_slash,
+			constants::TEST_MOUNT_NAME,
 			protocol::{self, V7, V8},
 			ServiceType, TestService,
 		},
@@ -134,6 +137,96 @@ async fn recent_golden_path_api_v7() {
 	assert!(entries[0].path.starts_with("collection/"));
 }
 
+#[tokio::test]
+async fn albums_sort_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let mut request = protocol::albums();
+	add_listing_sort(&mut request, Some("album_artist"), None);
+	let response = service
+		.fetch_json::<_, Vec<dto::AlbumHeader>>(&request)
+		.await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let entries = response.body();
+	assert_eq!(entries.len(), 3);
+	assert_eq!(entries[0].main_artists, vec!["Khemmis".to_owned()]);
+
+	let mut request = protocol::albums();
+	add_listing_sort(&mut request, Some("random"), Some(976878));
+	let first = service
+		.fetch_json::<_, Vec<dto::AlbumHeader>>(&request)
+		.await;
+	let second = service
+		.fetch_json::<_, Vec<dto::AlbumHeader>>(&request)
+		.await;
+	assert_eq!(first.status(), StatusCode::OK);
+	assert_eq!(first.body(), second.body());
+
+	let mut request = protocol::albums();
+	add_listing_sort(&mut request, Some("color"), None);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn albums_sort_uses_preference() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let preferences =
+		dto::Preferences([("album_sort".to_owned(), serde_json::json!("random"))].into());
+	let request = protocol::put_preferences(preferences);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	for seed in 0..5 {
+		let mut request = protocol::albums();
+		add_listing_sort(&mut request, None, Some(seed));
+		let by_preference = service
+			.fetch_json::<_, Vec<dto::AlbumHeader>>(&request)
+			.await;
+		assert_eq!(by_preference.status(), StatusCode::OK);
+
+		let mut request = protocol::albums();
+		add_listing_sort(&mut request, Some("random"), Some(seed));
+		let by_parameter = service
+			.fetch_json::<_, Vec<dto::AlbumHeader>>(&request)
+			.await;
+		assert_eq!(by_preference.body(), by_parameter.body());
+	}
+}
+
+#[tokio::test]
+async fn recently_added_songs_requires_auth() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	let request = protocol::recently_added_songs();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn recently_added_songs_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let mut request = protocol::recently_added_songs();
+	add_pagination(&mut request, 0, 5);
+	let response = service.fetch_json::<_, dto::SongList>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.headers().get("X-Total-Count").unwrap(), "13");
+	assert_eq!(response.body().paths.len(), 5);
+}
+
 #[tokio::test]
 async fn genres_requires_auth() {
 	let mut service = ServiceType::new(&test_name!()).await;
@@ -254,3 +347,278 @@ async fn genre_songs_golden_path() {
 	let song_list = response.body();
 	assert_eq!(song_list.paths.len(), 5);
 }
+
+#[tokio::test]
+async fn mix_requires_auth() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	let request = protocol::mix(None, None, None);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn mix_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let request = protocol::mix(Some("Metal"), None, Some(3));
+	let response = service.fetch_json::<_, dto::SongList>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let song_list = response.body();
+	assert_eq!(song_list.paths.len(), 3);
+	assert_eq!(song_list.first_songs.len(), 3);
+	assert!(song_list
+		.first_songs
+		.iter()
+		.all(|s| s.genres.contains(&"Metal".to_owned())));
+}
+
+#[tokio::test]
+async fn mix_bad_genre() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+
+	let request = protocol::mix(Some("Not a genre"), None, None);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn album_zip_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let request = protocol::album_zip("Picnic", &["Tobokegao"]);
+	let response = service.fetch_bytes(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let archive = String::from_utf8_lossy(response.body());
+	assert!(archive.contains("Picnic/01 - ピクニック (Picnic).mp3"));
+	assert!(archive.contains("Picnic/Folder.png"));
+	assert!(!archive.contains("Remix"));
+}
+
+#[tokio::test]
+async fn album_zip_bad_album() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+
+	let request = protocol::album_zip("Not an album", &["Tobokegao"]);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn changes_requires_auth() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	let request = protocol::changes(None);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn changes_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let request = protocol::changes(None);
+	let response = service.fetch_json::<_, dto::Changes>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let changes = response.body();
+	assert!(changes.full_sync_required);
+	assert!(changes.songs.added.is_empty());
+
+	let request = protocol::changes(Some(&changes.token));
+	let response = service.fetch_json::<_, dto::Changes>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let next_changes = response.body();
+	assert!(!next_changes.full_sync_required);
+	assert_eq!(next_changes.token, changes.token);
+	assert!(next_changes.songs.added.is_empty());
+	assert!(next_changes.albums.removed.is_empty());
+
+	let request = protocol::changes(Some("not-a-token"));
+	let response = service.fetch_json::<_, dto::Changes>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(response.body().full_sync_required);
+}
+
+#[tokio::test]
+async fn starred_requires_auth() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	let request = protocol::starred();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn star_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let song: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+
+	let request = protocol::star_song(&song);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::star_artist("Khemmis");
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::star_song(Path::new("not/a/song.mp3"));
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+	let request = protocol::starred();
+	let response = service.fetch_json::<_, dto::Starred>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let starred = response.body();
+	assert_eq!(starred.songs.paths, vec![song.clone()]);
+	assert!(starred.songs.first_songs[0].starred);
+	assert_eq!(starred.artists.len(), 1);
+	assert!(starred.albums.is_empty());
+
+	let mut request = protocol::flatten::<V8>(Path::new(TEST_MOUNT_NAME));
+	*request.uri_mut() = format!("{}?starred=true", request.uri()).parse().unwrap();
+	let response = service.fetch_json::<_, dto::SongList>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().paths, vec![song.clone()]);
+
+	let request = protocol::unstar_song(&song);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::starred();
+	let response = service.fetch_json::<_, dto::Starred>(&request).await;
+	assert!(response.body().songs.paths.is_empty());
+}
+
+#[tokio::test]
+async fn rating_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let hunted: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let candlelight = hunted.join("02 - Candlelight.mp3");
+	let three_gates = hunted.join("03 - Three Gates.mp3");
+
+	let request = protocol::rate_song(&candlelight, 3);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::rate_song(&three_gates, 5);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::rate_song(&three_gates, 6);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+	let request = protocol::rate_album("Hunted", &["Khemmis"], 4);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::album("Hunted", &["Khemmis"]);
+	let response = service.fetch_json::<_, dto::Album>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let album = response.body();
+	assert_eq!(album.rating, Some(4));
+	assert_eq!(album.average_rating, Some(4.0));
+	let song = album.songs.iter().find(|s| s.path == candlelight).unwrap();
+	assert_eq!(song.rating, Some(3));
+
+	let mut request = protocol::flatten::<V8>(&hunted);
+	*request.uri_mut() = format!("{}?sort=rating", request.uri()).parse().unwrap();
+	let response = service.fetch_json::<_, dto::SongList>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let song_list = response.body();
+	assert_eq!(
+		song_list.paths[..2],
+		[three_gates.clone(), candlelight.clone()]
+	);
+
+	let mut request = protocol::mix(None, None, None);
+	*request.uri_mut() = "/api/mix?min_rating=4".parse().unwrap();
+	let response = service.fetch_json::<_, dto::SongList>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().paths, vec![three_gates]);
+
+	let request = protocol::clear_album_rating("Hunted", &["Khemmis"]);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::album("Hunted", &["Khemmis"]);
+	let response = service.fetch_json::<_, dto::Album>(&request).await;
+	assert_eq!(response.body().average_rating, None);
+}
+
+#[tokio::test]
+async fn duplicates_requires_admin() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+
+	let request = protocol::duplicates();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn duplicates_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+
+	let request = protocol::duplicates();
+	let response = service
+		.fetch_json::<_, Vec<dto::DuplicateGroup>>(&request)
+		.await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(response.body().is_empty());
+}
+
+#[tokio::test]
+async fn metadata_report_requires_admin() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+
+	let request = protocol::metadata_report();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn metadata_report_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+
+	let request = protocol::metadata_report();
+	let response = service.fetch_json::<_, dto::MetadataReport>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let report = response.body();
+	assert!(report.songs.iter().all(|s| !s.issues.is_empty()));
+}
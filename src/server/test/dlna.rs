@@ -0,0 +1,91 @@
+use http::StatusCode;
+use std::path::PathBuf;
+
+use crate::server::test::{constants::*, protocol, ServiceType, TestService};
+use crate::test_name;
+
+const DLNA_CONFIG: &str = "[dlna]\nfriendly_name = \"Living Room Music\"\n";
+
+fn song_path() -> PathBuf {
+	[TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect()
+}
+
+#[tokio::test]
+async fn dlna_is_disabled_by_default() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+
+	let request = protocol::dlna_description();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+	let request = protocol::dlna_browse("0");
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+	let request = protocol::dlna_media(&song_path());
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn dlna_browse_golden_path() {
+	let mut service = ServiceType::new_with_config(&test_name!(), DLNA_CONFIG).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.logout().await;
+
+	let request = protocol::dlna_description();
+	let response = service.fetch_bytes(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let body = String::from_utf8(response.into_body()).unwrap();
+	assert!(body.contains("<friendlyName>Living Room Music</friendlyName>"));
+	assert!(body.contains("<eventSubURL></eventSubURL>"));
+
+	let request = protocol::dlna_browse("0");
+	let response = service.fetch_bytes(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let body = String::from_utf8(response.into_body()).unwrap();
+	assert!(body.contains(&format!(
+		"&lt;dc:title&gt;{TEST_MOUNT_NAME}&lt;/dc:title&gt;"
+	)));
+}
+
+#[tokio::test]
+async fn dlna_media_golden_path() {
+	let mut service = ServiceType::new_with_config(&test_name!(), DLNA_CONFIG).await;
+	service.complete_initial_setup().await;
+
+	let request = protocol::dlna_media(&song_path());
+	let response = service.fetch_bytes(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(
+		response.headers().get("transferMode.dlna.org").unwrap(),
+		"Streaming"
+	);
+}
+
+#[tokio::test]
+async fn dlna_refuses_proxied_requests() {
+	let mut service = ServiceType::new_with_config(&test_name!(), DLNA_CONFIG).await;
+	service.complete_initial_setup().await;
+
+	for header in ["forwarded", "x-forwarded-for", "x-real-ip"] {
+		let mut request = protocol::dlna_browse("0");
+		request
+			.headers_mut()
+			.insert(header, "203.0.113.7".parse().unwrap());
+		let response = service.fetch(&request).await;
+		assert_eq!(response.status(), StatusCode::FORBIDDEN, "{header}");
+
+		let mut request = protocol::dlna_media(&song_path());
+		request
+			.headers_mut()
+			.insert(header, "203.0.113.7".parse().unwrap());
+		let response = service.fetch(&request).await;
+		assert_eq!(response.status(), StatusCode::FORBIDDEN, "{header}");
+	}
+}
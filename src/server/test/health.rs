@@ -0,0 +1,136 @@
+use http::StatusCode;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::server::dto;
+use crate::server::test::{constants::*, protocol, ServiceType, TestService};
+use crate::test_name;
+
+#[tokio::test]
+async fn health_live_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	let request = protocol::health_live();
+	let response = service.fetch_json::<_, dto::Health>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(response.body().healthy);
+}
+
+#[tokio::test]
+async fn health_ready_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.logout().await;
+
+	let request = protocol::health_ready();
+	let response = service.fetch_json::<_, dto::Health>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let health = response.body();
+	assert!(health.healthy);
+	let names = health
+		.components
+		.iter()
+		.map(|c| c.name.as_str())
+		.collect::<Vec<_>>();
+	assert_eq!(
+		names,
+		vec![
+			"config_file",
+			&format!("mount_dir:{TEST_MOUNT_NAME}"),
+			"index"
+		]
+	);
+}
+
+#[tokio::test]
+async fn health_ready_reports_unreachable_mount() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+
+	let request = protocol::put_mount_dirs(vec![dto::MountDir {
+		name: "missing".into(),
+		source: "test-data/does-not-exist".into(),
+		exclude_patterns: vec![],
+		symlinks: Default::default(),
+		split_artists: None,
+		remote: None,
+	}]);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::health_ready();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+// Answers every request with the given response, like a remote storage server would
+async fn serve_remote_storage(status: &'static str, body: &'static str) -> String {
+	let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let address = listener.local_addr().unwrap();
+	tokio::spawn(async move {
+		while let Ok((mut stream, _)) = listener.accept().await {
+			let mut buffer = vec![0; 16 * 1024];
+			stream.read(&mut buffer).await.ok();
+			let response = format!(
+				"HTTP/1.1 {status}\r\nContent-Type: application/xml\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+				body.len()
+			);
+			stream.write_all(response.as_bytes()).await.ok();
+		}
+	});
+	format!("http://{address}")
+}
+
+#[tokio::test]
+async fn health_ready_checks_s3_mounts() {
+	let endpoint = serve_remote_storage(
+		"200 OK",
+		"<ListBucketResult><IsTruncated>false</IsTruncated></ListBucketResult>",
+	)
+	.await;
+	let config = format!(
+		"[[mount_dirs]]\nname = \"bucket\"\nsource = \"\"\n[mount_dirs.remote]\ntype = \"s3\"\nendpoint = \"{endpoint}\"\nbucket = \"music\"\naccess_key_id = \"key\"\nsecret_access_key = \"secret\"\npath_style = true\n"
+	);
+	let mut service = ServiceType::new_with_config(&test_name!(), &config).await;
+
+	let request = protocol::health_ready();
+	let response = service.fetch_json::<_, dto::Health>(&request).await;
+	let mount = response
+		.body()
+		.components
+		.iter()
+		.find(|c| c.name == "mount_dir:bucket")
+		.unwrap();
+	assert!(mount.healthy);
+}
+
+#[tokio::test]
+async fn health_ready_checks_webdav_mounts() {
+	let reachable = serve_remote_storage(
+		"207 Multi-Status",
+		r#"<?xml version="1.0"?><d:multistatus xmlns:d="DAV:"></d:multistatus>"#,
+	)
+	.await;
+	let unreachable = serve_remote_storage("500 Internal Server Error", "").await;
+	let config = format!(
+		"[[mount_dirs]]\nname = \"nextcloud\"\nsource = \"\"\n[mount_dirs.remote]\ntype = \"webdav\"\nurl = \"{reachable}\"\n\n[[mount_dirs]]\nname = \"broken\"\nsource = \"\"\n[mount_dirs.remote]\ntype = \"webdav\"\nurl = \"{unreachable}\"\n"
+	);
+	let mut service = ServiceType::new_with_config(&test_name!(), &config).await;
+
+	let request = protocol::health_ready();
+	let response = service.fetch_json::<_, dto::Health>(&request).await;
+	assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+	let is_healthy = |name: &str| {
+		response
+			.body()
+			.components
+			.iter()
+			.find(|c| c.name == name)
+			.unwrap()
+			.healthy
+	};
+	assert!(is_healthy("mount_dir:nextcloud"));
+	assert!(!is_healthy("mount_dir:broken"));
+}
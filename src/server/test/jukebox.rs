@@ -0,0 +1,27 @@
+use http::StatusCode;
+
+use crate::server::test::{protocol, ServiceType, TestService};
+use crate::test_name;
+
+#[tokio::test]
+async fn jukebox_requires_auth() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	let request = protocol::jukebox_status();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn jukebox_is_unavailable_by_default() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+
+	let request = protocol::jukebox_status();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+	let request = protocol::jukebox_next();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
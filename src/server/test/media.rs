@@ -2,6 +2,7 @@ use http::{header, HeaderValue, StatusCode};
 use std::path::PathBuf;
 
 use crate::server::dto::{self, ThumbnailSize};
+use crate::server::test::protocol::V8;
 use crate::server::test::{constants::*, protocol, ServiceType, TestService};
 use crate::test_name;
 
@@ -39,6 +40,39 @@ async fn songs_golden_path() {
 	assert_eq!(payload.not_found, vec![invalid_path]);
 }
 
+#[tokio::test]
+async fn songs_preserves_request_order() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let request = protocol::flatten::<V8>(&PathBuf::new());
+	let response = service.fetch_json::<_, dto::SongList>(&request).await;
+	let mut paths = response.into_body().paths;
+	paths.reverse();
+
+	let request = protocol::songs(dto::GetSongsBulkInput {
+		paths: paths.clone(),
+	});
+	let response = service
+		.fetch_json::<_, dto::GetSongsBulkOutput>(&request)
+		.await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let payload = response.body();
+	assert!(payload.not_found.is_empty());
+	assert_eq!(
+		payload
+			.songs
+			.iter()
+			.map(|s| s.path.clone())
+			.collect::<Vec<_>>(),
+		paths
+	);
+}
+
 #[tokio::test]
 async fn audio_requires_auth() {
 	let mut service = ServiceType::new(&test_name!()).await;
@@ -131,6 +165,204 @@ async fn audio_partial_content() {
 	);
 }
 
+#[tokio::test]
+async fn audio_suffix_range() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+
+	let mut request = protocol::audio(&path);
+	let headers = request.headers_mut();
+	headers.append(header::RANGE, HeaderValue::from_str("bytes=-100").unwrap());
+
+	let response = service.fetch_bytes(&request).await;
+	assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+	assert_eq!(response.body().len(), 100);
+	assert_eq!(
+		response.headers().get(header::CONTENT_RANGE).unwrap(),
+		"bytes 24042-24141/24142"
+	);
+}
+
+#[tokio::test]
+async fn audio_download_can_resume() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+
+	let request = protocol::download_audio(&path);
+	let response = service.fetch_bytes(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(
+		response.headers().get(header::CONTENT_DISPOSITION).unwrap(),
+		"attachment; filename=\"02 - Candlelight.mp3\"; filename*=UTF-8''02%20-%20Candlelight.mp3"
+	);
+	let etag = response.headers().get(header::ETAG).unwrap().clone();
+
+	let mut request = protocol::download_audio(&path);
+	let headers = request.headers_mut();
+	headers.append(
+		header::RANGE,
+		HeaderValue::from_str("bytes=24000-").unwrap(),
+	);
+	headers.append(header::IF_RANGE, etag);
+	let response = service.fetch_bytes(&request).await;
+	assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+	assert_eq!(response.body().len(), 142);
+
+	let mut request = protocol::download_audio(&path);
+	let headers = request.headers_mut();
+	headers.append(
+		header::RANGE,
+		HeaderValue::from_str("bytes=24000-").unwrap(),
+	);
+	headers.append(header::IF_RANGE, HeaderValue::from_static("\"stale\""));
+	let response = service.fetch_bytes(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().len(), 24142);
+}
+
+#[tokio::test]
+async fn download_requires_permission() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+
+	let request = protocol::create_user(dto::NewUser {
+		name: "Walter".into(),
+		password: "secret".into(),
+		admin: false,
+		guest: false,
+		permissions: Some(vec![]),
+	});
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::login("Walter", "secret");
+	let response = service.fetch_json::<_, dto::Authorization>(&request).await;
+	service.set_authorization(Some(response.into_body()));
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+
+	let request = protocol::audio(&path);
+	let response = service.fetch_bytes(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::download_audio(&path);
+	let response = service.fetch_bytes(&request).await;
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn audio_multiple_ranges() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+
+	let mut request = protocol::audio(&path);
+	let headers = request.headers_mut();
+	headers.append(
+		header::RANGE,
+		HeaderValue::from_str("bytes=0-99, -100").unwrap(),
+	);
+
+	let response = service.fetch_bytes(&request).await;
+	assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+	let content_type = response.headers().get(header::CONTENT_TYPE).unwrap();
+	assert!(content_type
+		.to_str()
+		.unwrap()
+		.starts_with("multipart/byteranges; boundary="));
+	let content_length = response.headers().get(header::CONTENT_LENGTH).unwrap();
+	assert_eq!(
+		content_length.to_str().unwrap(),
+		response.body().len().to_string()
+	);
+	let body = String::from_utf8_lossy(response.body());
+	assert!(body.contains("Content-Range: bytes 0-99/24142"));
+	assert!(body.contains("Content-Range: bytes 24042-24141/24142"));
+}
+
+#[tokio::test]
+async fn audio_unsatisfiable_range() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+
+	let mut request = protocol::audio(&path);
+	let headers = request.headers_mut();
+	headers.append(
+		header::RANGE,
+		HeaderValue::from_str("bytes=50000-").unwrap(),
+	);
+
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+	assert_eq!(
+		response.headers().get(header::CONTENT_RANGE).unwrap(),
+		"bytes */24142"
+	);
+}
+
+#[tokio::test]
+async fn audio_honors_if_none_match() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+
+	let request = protocol::audio(&path);
+	let response = service.fetch_bytes(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let etag = response.headers().get(header::ETAG).unwrap().clone();
+
+	let mut request = protocol::audio(&path);
+	request.headers_mut().append(header::IF_NONE_MATCH, etag);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+	let mut request = protocol::audio(&path);
+	request.headers_mut().append(
+		header::IF_NONE_MATCH,
+		HeaderValue::from_str("\"oink\"").unwrap(),
+	);
+	let response = service.fetch_bytes(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().len(), 24_142);
+}
+
 #[tokio::test]
 async fn audio_bad_path_returns_not_found() {
 	let mut service = ServiceType::new(&test_name!()).await;
@@ -189,6 +421,66 @@ async fn peaks_bad_path_returns_not_found() {
 	assert_eq!(response.status(), StatusCode::NOT_FOUND);
 }
 
+#[tokio::test]
+async fn lyrics_requires_auth() {
+	let mut service = ServiceType::new(&test_name!()).await;
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+
+	let request = protocol::lyrics(&path);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn lyrics_missing_returns_not_found() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+
+	let request = protocol::lyrics(&path);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn fingerprint_requires_auth() {
+	let mut service = ServiceType::new(&test_name!()).await;
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+
+	let request = protocol::fingerprint(&path);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn fingerprint_not_computed_returns_not_found() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+
+	let request = protocol::fingerprint(&path);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
 #[tokio::test]
 async fn thumbnail_requires_auth() {
 	let mut service = ServiceType::new(&test_name!()).await;
@@ -223,6 +515,132 @@ async fn thumbnail_golden_path() {
 	assert_eq!(response.status(), StatusCode::OK);
 }
 
+#[tokio::test]
+async fn artist_image_requires_auth() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	let request = protocol::artist_image("Khemmis");
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn artist_image_not_found() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let request = protocol::artist_image("Khemmis");
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn artwork_upload_requires_admin() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+
+	let path = PathBuf::from_iter([TEST_MOUNT_NAME, "Khemmis", "Hunted"]);
+	let image = std::fs::read(PathBuf::from_iter(["test-data", "artwork", "Folder.png"])).unwrap();
+
+	let request = protocol::put_artwork(&path, image);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+	let request = protocol::delete_artwork(&path);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn artwork_upload_overrides_thumbnail() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+
+	let directory = PathBuf::from_iter([TEST_MOUNT_NAME, "Khemmis", "Hunted"]);
+	let artwork_path = directory.join("Folder.jpg");
+	let thumbnail_dimensions = |bytes: &[u8]| {
+		let image = image::load_from_memory(bytes).unwrap();
+		(image.width(), image.height())
+	};
+
+	let request = protocol::thumbnail(&artwork_path, Some(ThumbnailSize::Native), Some(false));
+	let response = service.fetch_bytes(&request).await;
+	let original = thumbnail_dimensions(response.body());
+
+	let image = std::fs::read(PathBuf::from_iter(["test-data", "artwork", "Folder.png"])).unwrap();
+	let request = protocol::put_artwork(&directory, image);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::thumbnail(&artwork_path, Some(ThumbnailSize::Native), Some(false));
+	let response = service.fetch_bytes(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(thumbnail_dimensions(response.body()), (4, 4));
+
+	let request = protocol::delete_artwork(&directory);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::thumbnail(&artwork_path, Some(ThumbnailSize::Native), Some(false));
+	let response = service.fetch_bytes(&request).await;
+	assert_eq!(thumbnail_dimensions(response.body()), original);
+}
+
+#[tokio::test]
+async fn artwork_upload_rejects_invalid_image() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+
+	let path = PathBuf::from_iter([TEST_MOUNT_NAME, "Khemmis", "Hunted"]);
+	let request = protocol::put_artwork(&path, b"not an image".to_vec());
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn thumbnail_honors_if_modified_since() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "Folder.jpg"]
+		.iter()
+		.collect();
+
+	let request = protocol::thumbnail(&path, None, None);
+	let response = service.fetch_bytes(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(response.headers().contains_key(header::ETAG));
+	let last_modified = response
+		.headers()
+		.get(header::LAST_MODIFIED)
+		.unwrap()
+		.clone();
+
+	let mut request = protocol::thumbnail(&path, None, None);
+	request
+		.headers_mut()
+		.append(header::IF_MODIFIED_SINCE, last_modified);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+	let mut request = protocol::thumbnail(&path, None, None);
+	request.headers_mut().append(
+		header::IF_MODIFIED_SINCE,
+		HeaderValue::from_str("Thu, 01 Jan 1970 00:00:00 GMT").unwrap(),
+	);
+	let response = service.fetch_bytes(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+}
+
 #[tokio::test]
 async fn thumbnail_bad_path_returns_not_found() {
 	let mut service = ServiceType::new(&test_name!()).await;
@@ -276,3 +694,323 @@ async fn thumbnail_size(name: &str, size: Option<ThumbnailSize>, pad: Option<boo
 	assert_eq!(thumbnail.width(), expected);
 	assert_eq!(thumbnail.height(), expected);
 }
+
+#[tokio::test]
+async fn events_requires_auth() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	let request = protocol::events();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn playback_requires_auth() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	let request = protocol::playback(dto::PlaybackReport::default());
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn playback_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let request = protocol::playback(dto::PlaybackReport {
+		path: PathBuf::from_iter([TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]),
+		position: 1000,
+		lyrics: true,
+	});
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn playback_bad_path_returns_not_found() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+
+	let request = protocol::playback(dto::PlaybackReport {
+		path: PathBuf::from_iter(["not_my_mount", "song.mp3"]),
+		position: 1000,
+		lyrics: true,
+	});
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn history_requires_auth() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	let request = protocol::get_history();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn history_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+
+	for client in [Some("Phone".to_owned()), None] {
+		let request = protocol::post_history(dto::NewPlay {
+			path: path.clone(),
+			client,
+		});
+		let response = service.fetch_json::<_, dto::Play>(&request).await;
+		assert_eq!(response.status(), StatusCode::OK);
+	}
+
+	let request = protocol::post_history(dto::NewPlay {
+		path: PathBuf::from("not/a/song.mp3"),
+		client: None,
+	});
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+	let request = protocol::get_history();
+	let response = service.fetch_json::<_, Vec<dto::Play>>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.headers().get("X-Total-Count").unwrap(), "2");
+	let plays = response.body();
+	assert_eq!(plays.len(), 2);
+	assert_eq!(plays[1].client.as_deref(), Some("Phone"));
+
+	let request = protocol::songs(dto::GetSongsBulkInput { paths: vec![path] });
+	let response = service
+		.fetch_json::<_, dto::GetSongsBulkOutput>(&request)
+		.await;
+	assert_eq!(response.body().songs[0].play_count, 2);
+}
+
+#[tokio::test]
+async fn recently_played_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let hunted: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let candlelight = hunted.join("02 - Candlelight.mp3");
+	let three_gates = hunted.join("03 - Three Gates.mp3");
+
+	for path in [&candlelight, &three_gates, &candlelight] {
+		let request = protocol::post_history(dto::NewPlay {
+			path: path.clone(),
+			client: None,
+		});
+		let response = service.fetch(&request).await;
+		assert_eq!(response.status(), StatusCode::OK);
+	}
+
+	let request = protocol::recently_played_songs();
+	let response = service.fetch_json::<_, dto::SongList>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().paths, vec![candlelight, three_gates]);
+
+	let request = protocol::recently_played_albums();
+	let response = service
+		.fetch_json::<_, Vec<dto::AlbumHeader>>(&request)
+		.await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let albums = response.body();
+	assert_eq!(albums.len(), 1);
+	assert_eq!(albums[0].name, "Hunted");
+}
+
+#[tokio::test]
+async fn never_played_requires_auth() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	let request = protocol::never_played_songs();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn never_played_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let request = protocol::never_played_songs();
+	let response = service.fetch_json::<_, dto::SongList>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let num_songs = response.body().paths.len();
+	assert!(num_songs > 0);
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+	let request = protocol::post_history(dto::NewPlay {
+		path: path.clone(),
+		client: None,
+	});
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::never_played_songs();
+	let response = service.fetch_json::<_, dto::SongList>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let paths = &response.body().paths;
+	assert_eq!(paths.len(), num_songs - 1);
+	assert!(!paths.contains(&path));
+}
+
+#[tokio::test]
+async fn forgotten_albums_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+	let request = protocol::post_history(dto::NewPlay { path, client: None });
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	// Albums played recently are not forgotten
+	let request = protocol::forgotten_albums();
+	let response = service
+		.fetch_json::<_, Vec<dto::AlbumHeader>>(&request)
+		.await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(response.body().is_empty());
+}
+
+#[tokio::test]
+async fn stats_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let hunted: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let candlelight = hunted.join("02 - Candlelight.mp3");
+	let three_gates = hunted.join("03 - Three Gates.mp3");
+
+	for path in [&candlelight, &three_gates, &candlelight] {
+		let request = protocol::post_history(dto::NewPlay {
+			path: path.clone(),
+			client: None,
+		});
+		let response = service.fetch(&request).await;
+		assert_eq!(response.status(), StatusCode::OK);
+	}
+
+	let request = protocol::stats();
+	let response = service.fetch_json::<_, dto::Stats>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let stats = response.body();
+	assert_eq!(stats.play_count, 3);
+	assert_eq!(stats.top_songs[0].path, candlelight);
+	assert_eq!(stats.top_songs[0].play_count, 2);
+	assert_eq!(stats.top_albums.len(), 1);
+	assert_eq!(stats.top_albums[0].play_count, 3);
+	assert_eq!(stats.top_artists[0].name, "Khemmis");
+	assert_eq!(stats.hourly_play_counts.len(), 24);
+	assert_eq!(stats.hourly_play_counts.iter().sum::<u32>(), 3);
+}
+
+#[tokio::test]
+async fn streams_requires_admin() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	let request = protocol::streams();
+
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+	service.login().await;
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn streams_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+	let request = protocol::audio(&path);
+	let response = service.fetch_bytes(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	// Streams are no longer listed once fully sent
+	let request = protocol::streams();
+	let response = service
+		.fetch_json::<_, Vec<dto::ActiveStream>>(&request)
+		.await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(response.body().is_empty());
+}
+
+#[tokio::test]
+async fn user_transfers_requires_permission() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+	let request = protocol::user_transfers(TEST_USERNAME_ADMIN);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn transfers_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let request = protocol::transfers();
+	let response = service.fetch_json::<_, dto::Transfers>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(response.body().months.is_empty());
+	assert!(!response.body().over_quota);
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+	let request = protocol::audio(&path);
+	let response = service.fetch_bytes(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let size = response.body().len() as u64;
+
+	// Transfers are recorded in the background once responses are sent
+	service.login_admin().await;
+	let request = protocol::user_transfers(TEST_USERNAME);
+	let mut transfers = Vec::new();
+	for _ in 0..50 {
+		let response = service.fetch_json::<_, dto::Transfers>(&request).await;
+		assert_eq!(response.status(), StatusCode::OK);
+		transfers = response.into_body().months;
+		if !transfers.is_empty() {
+			break;
+		}
+		tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+	}
+	assert_eq!(transfers.len(), 1);
+	assert_eq!(transfers[0].bytes, size);
+}
@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use http::StatusCode;
 
@@ -143,3 +143,294 @@ async fn delete_playlist_golden_path() {
 	let response = service.fetch(&request).await;
 	assert_eq!(response.status(), StatusCode::OK);
 }
+
+#[tokio::test]
+async fn playlist_feed_requires_auth() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	let request = protocol::create_playlist_feed(TEST_PLAYLIST_NAME);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn playlist_feed_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+	service.login().await;
+
+	let in_playlist: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+	let not_in_playlist: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "03 - Three Gates.mp3"]
+		.iter()
+		.collect();
+
+	let my_playlist = dto::SavePlaylistInput {
+		tracks: vec![in_playlist.clone()],
+	};
+	let request = protocol::save_playlist(TEST_PLAYLIST_NAME, my_playlist);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::create_playlist_feed(TEST_PLAYLIST_NAME);
+	let response = service.fetch_json::<_, dto::PlaylistFeed>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let url = response.into_body().url;
+	let feed_path = &url[url.find("/api/v8/feed/").unwrap()..];
+
+	// Podcast apps use the feed without signing in
+	service.logout().await;
+
+	let request = protocol::playlist_feed(feed_path);
+	let response = service.fetch_bytes(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let body = String::from_utf8(response.into_body()).unwrap();
+	assert_eq!(body.matches("<item>").count(), 1);
+	assert!(body.contains("<title>Candlelight</title>"));
+	assert!(body.contains("/audio/"));
+
+	let request = protocol::playlist_feed_audio(feed_path, &in_playlist);
+	let response = service.fetch_bytes(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::playlist_feed_audio(feed_path, &not_in_playlist);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+	let request = protocol::playlist_feed("/api/v8/feed/not_a_token");
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn playlist_feed_can_be_revoked() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+
+	let request = protocol::save_playlist(
+		TEST_PLAYLIST_NAME,
+		dto::SavePlaylistInput { tracks: vec![] },
+	);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::create_playlist_feed(TEST_PLAYLIST_NAME);
+	let response = service.fetch_json::<_, dto::PlaylistFeed>(&request).await;
+	let url = response.into_body().url;
+	let feed_path = url[url.find("/api/v8/feed/").unwrap()..].to_owned();
+
+	let request = protocol::revoke_playlist_feed(TEST_PLAYLIST_NAME);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::playlist_feed(&feed_path);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+	let request = protocol::create_playlist_feed(TEST_PLAYLIST_NAME);
+	let response = service.fetch_json::<_, dto::PlaylistFeed>(&request).await;
+	assert_ne!(response.into_body().url, url);
+}
+
+#[tokio::test]
+async fn playlist_feed_does_not_survive_playlist_deletion() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+
+	let request = protocol::save_playlist(
+		TEST_PLAYLIST_NAME,
+		dto::SavePlaylistInput { tracks: vec![] },
+	);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::create_playlist_feed(TEST_PLAYLIST_NAME);
+	let response = service.fetch_json::<_, dto::PlaylistFeed>(&request).await;
+	let url = response.into_body().url;
+	let feed_path = url[url.find("/api/v8/feed/").unwrap()..].to_owned();
+
+	let request = protocol::delete_playlist(TEST_PLAYLIST_NAME);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::save_playlist(
+		TEST_PLAYLIST_NAME,
+		dto::SavePlaylistInput { tracks: vec![] },
+	);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::playlist_feed(&feed_path);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn family_mix_requires_auth() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	let request = protocol::family_mix(dto::FamilyMixInput::default());
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn family_mix_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	service.index().await;
+
+	let shared_song =
+		PathBuf::from_iter([TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]);
+	let other_song =
+		PathBuf::from_iter([TEST_MOUNT_NAME, "Khemmis", "Hunted", "03 - Three Gates.mp3"]);
+
+	let my_playlist = dto::SavePlaylistInput {
+		tracks: vec![shared_song.clone()],
+	};
+	let request = protocol::save_playlist(TEST_PLAYLIST_NAME, my_playlist);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::put_preferences(dto::Preferences(
+		[("family_mix".to_owned(), serde_json::Value::Bool(true))].into(),
+	));
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	service.login().await;
+	let my_playlist = dto::SavePlaylistInput {
+		tracks: vec![shared_song.clone(), other_song],
+	};
+	let request = protocol::save_playlist(TEST_PLAYLIST_NAME, my_playlist);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::family_mix(dto::FamilyMixInput {
+		name: "Family".to_owned(),
+		listeners: vec![dto::FamilyMixListener {
+			username: TEST_USERNAME_ADMIN.to_owned(),
+			weight: None,
+		}],
+		mode: dto::FamilyMixMode::Intersection,
+		..Default::default()
+	});
+	let response = service.fetch_json::<_, dto::SongList>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().paths, vec![shared_song.clone()]);
+
+	let request = protocol::read_playlist::<V8>("Family");
+	let response = service.fetch_json::<_, dto::Playlist>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().songs.paths, vec![shared_song]);
+
+	service.login_admin().await;
+	let request = protocol::read_playlist::<V8>("Family");
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn family_mix_requires_consent() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+
+	let request = protocol::family_mix(dto::FamilyMixInput {
+		name: "Family".to_owned(),
+		listeners: vec![dto::FamilyMixListener {
+			username: TEST_USERNAME_ADMIN.to_owned(),
+			weight: None,
+		}],
+		..Default::default()
+	});
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+	let request = protocol::read_playlist::<V8>("Family");
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn family_mix_unknown_user() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+
+	let request = protocol::family_mix(dto::FamilyMixInput {
+		name: "Family".to_owned(),
+		listeners: vec![dto::FamilyMixListener {
+			username: "not_a_user".to_owned(),
+			weight: None,
+		}],
+		..Default::default()
+	});
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn play_queue_requires_auth() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	let request = protocol::get_play_queue();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+	let request = protocol::put_play_queue(dto::SavePlayQueueInput::default());
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn play_queue_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+
+	let request = protocol::get_play_queue();
+	let response = service.fetch_json::<_, dto::PlayQueue>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body(), &dto::PlayQueue::default());
+
+	let songs = vec![
+		PathBuf::from_iter([TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]),
+		PathBuf::from_iter([TEST_MOUNT_NAME, "Khemmis", "Hunted", "03 - Three Gates.mp3"]),
+	];
+	let request = protocol::put_play_queue(dto::SavePlayQueueInput {
+		songs: songs.clone(),
+		position: 1,
+		offset: 42000,
+	});
+	let response = service.fetch_json::<_, dto::PlayQueue>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let saved = response.into_body();
+
+	let request = protocol::get_play_queue();
+	let response = service.fetch_json::<_, dto::PlayQueue>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let restored = response.into_body();
+	assert_eq!(restored, saved);
+	assert_eq!(restored.songs, songs);
+	assert_eq!(restored.position, 1);
+	assert_eq!(restored.offset, 42000);
+}
+
+#[tokio::test]
+async fn play_queue_rejects_bad_position() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+
+	let request = protocol::put_play_queue(dto::SavePlayQueueInput {
+		songs: vec![PathBuf::from("collection/song.mp3")],
+		position: 3,
+		offset: 0,
+	});
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
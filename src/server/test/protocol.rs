@@ -1,9 +1,10 @@
 use http::{Method, Request};
 use percent_encoding::{percent_encode, NON_ALPHANUMERIC};
+use serde::Serialize;
 use std::path::Path;
 
-use crate::server::dto;
 use crate::server::dto::ThumbnailSize;
+use crate::server::{dto, API_ARRAY_SEPARATOR};
 
 pub trait ProtocolVersion {
 	fn header_value() -> i32;
@@ -12,6 +13,10 @@ pub trait ProtocolVersion {
 pub struct V7;
 pub struct V8;
 
+// Request body sent as-is, instead of being encoded as JSON
+#[derive(Clone, Serialize)]
+pub struct RawBody(pub Vec<u8>);
+
 impl ProtocolVersion for V7 {
 	fn header_value() -> i32 {
 		7
@@ -35,213 +40,867 @@ pub fn web_index() -> Request<()> {
 pub fn docs_index() -> Request<()> {
 	Request::builder()
 		.method(Method::GET)
-		.uri("/api-docs")
+		.uri("/api-docs")
+		.body(())
+		.unwrap()
+}
+
+pub fn version() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/version")
+		.body(())
+		.unwrap()
+}
+
+pub fn health_live() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/health/live")
+		.body(())
+		.unwrap()
+}
+
+pub fn health_ready() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/health/ready")
+		.body(())
+		.unwrap()
+}
+
+pub fn initial_setup() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/initial_setup")
+		.body(())
+		.unwrap()
+}
+
+pub fn login(username: &str, password: &str) -> Request<dto::Credentials> {
+	let credentials = dto::Credentials {
+		username: username.into(),
+		password: password.into(),
+		new_password: None,
+		client_name: None,
+	};
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/auth")
+		.body(credentials)
+		.unwrap()
+}
+
+pub fn login_with_new_password(
+	username: &str,
+	password: &str,
+	new_password: &str,
+) -> Request<dto::Credentials> {
+	let credentials = dto::Credentials {
+		username: username.into(),
+		password: password.into(),
+		new_password: Some(new_password.into()),
+		client_name: None,
+	};
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/auth")
+		.body(credentials)
+		.unwrap()
+}
+
+pub fn sessions() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/sessions")
+		.body(())
+		.unwrap()
+}
+
+pub fn refresh_auth() -> Request<()> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/auth/refresh")
+		.body(())
+		.unwrap()
+}
+
+pub fn delete_sessions() -> Request<()> {
+	Request::builder()
+		.method(Method::DELETE)
+		.uri("/api/sessions")
+		.body(())
+		.unwrap()
+}
+
+pub fn delete_session(id: &str) -> Request<()> {
+	let endpoint = format!("/api/session/{}", url_encode(id));
+	Request::builder()
+		.method(Method::DELETE)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn get_api_keys() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/api_keys")
+		.body(())
+		.unwrap()
+}
+
+pub fn post_api_key(new_api_key: dto::NewApiKey) -> Request<dto::NewApiKey> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/api_keys")
+		.body(new_api_key)
+		.unwrap()
+}
+
+pub fn delete_api_key(id: &str) -> Request<()> {
+	let endpoint = format!("/api/api_key/{}", url_encode(id));
+	Request::builder()
+		.method(Method::DELETE)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn get_passkeys() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/passkeys")
+		.body(())
+		.unwrap()
+}
+
+pub fn delete_passkey(id: &str) -> Request<()> {
+	let endpoint = format!("/api/passkey/{}", url_encode(id));
+	Request::builder()
+		.method(Method::DELETE)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn passkey_register_start() -> Request<()> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/passkeys/register/start")
+		.body(())
+		.unwrap()
+}
+
+pub fn passkey_register_finish(new_passkey: dto::NewPasskey) -> Request<dto::NewPasskey> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/passkeys/register/finish")
+		.body(new_passkey)
+		.unwrap()
+}
+
+pub fn passkey_login_start(options: dto::PasskeyLoginOptions) -> Request<dto::PasskeyLoginOptions> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/passkeys/login/start")
+		.body(options)
+		.unwrap()
+}
+
+pub fn passkey_login_finish(assertion: dto::PasskeyAssertion) -> Request<dto::PasskeyAssertion> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/passkeys/login/finish")
+		.body(assertion)
+		.unwrap()
+}
+
+pub fn device_login(client_name: Option<&str>) -> Request<dto::DeviceLoginInput> {
+	let input = dto::DeviceLoginInput {
+		client_name: client_name.map(str::to_owned),
+	};
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/auth/device")
+		.body(input)
+		.unwrap()
+}
+
+pub fn approve_device_login(user_code: &str) -> Request<dto::DeviceLoginApproval> {
+	let approval = dto::DeviceLoginApproval {
+		user_code: user_code.to_owned(),
+	};
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/auth/device/approve")
+		.body(approval)
+		.unwrap()
+}
+
+pub fn device_login_token(device_code: &str) -> Request<dto::DeviceTokenInput> {
+	let input = dto::DeviceTokenInput {
+		device_code: device_code.to_owned(),
+	};
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/auth/device/token")
+		.body(input)
+		.unwrap()
+}
+
+pub fn oidc_login(return_to: Option<&str>) -> Request<()> {
+	let endpoint = match return_to {
+		Some(path) => format!("/api/oidc/login?return_to={}", url_encode(path)),
+		None => "/api/oidc/login".to_owned(),
+	};
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn oidc_callback(state: &str, code: &str) -> Request<()> {
+	let endpoint = format!(
+		"/api/oidc/callback?state={}&code={}",
+		url_encode(state),
+		url_encode(code)
+	);
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn put_mount_dirs(dirs: Vec<dto::MountDir>) -> Request<Vec<dto::MountDir>> {
+	Request::builder()
+		.method(Method::PUT)
+		.uri("/api/mount_dirs")
+		.body(dirs)
+		.unwrap()
+}
+
+pub fn get_aliases() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/aliases")
+		.body(())
+		.unwrap()
+}
+
+pub fn put_aliases(aliases: dto::Aliases) -> Request<dto::Aliases> {
+	Request::builder()
+		.method(Method::PUT)
+		.uri("/api/aliases")
+		.body(aliases)
+		.unwrap()
+}
+
+pub fn get_settings() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/settings")
+		.body(())
+		.unwrap()
+}
+
+pub fn put_settings(settings: dto::NewSettings) -> Request<dto::NewSettings> {
+	Request::builder()
+		.method(Method::PUT)
+		.uri("/api/settings")
+		.body(settings)
+		.unwrap()
+}
+
+pub fn list_users() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/users")
+		.body(())
+		.unwrap()
+}
+
+pub fn create_user(new_user: dto::NewUser) -> Request<dto::NewUser> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/user")
+		.body(new_user)
+		.unwrap()
+}
+
+pub fn update_user(username: &str, user_update: dto::UserUpdate) -> Request<dto::UserUpdate> {
+	Request::builder()
+		.method(Method::PUT)
+		.uri(format!("/api/user/{}", username))
+		.body(user_update)
+		.unwrap()
+}
+
+pub fn delete_user(username: &str) -> Request<()> {
+	Request::builder()
+		.method(Method::DELETE)
+		.uri(format!("/api/user/{}", username))
+		.body(())
+		.unwrap()
+}
+
+pub fn logout_user(username: &str) -> Request<()> {
+	Request::builder()
+		.method(Method::POST)
+		.uri(format!("/api/user/{}/logout", username))
+		.body(())
+		.unwrap()
+}
+
+pub fn logout_user_keeping_api_keys(username: &str) -> Request<()> {
+	Request::builder()
+		.method(Method::POST)
+		.uri(format!("/api/user/{}/logout?keep_api_keys=true", username))
+		.body(())
+		.unwrap()
+}
+
+pub fn get_invites() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/invites")
+		.body(())
+		.unwrap()
+}
+
+pub fn post_invite(new_invite: dto::NewInvite) -> Request<dto::NewInvite> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/invites")
+		.body(new_invite)
+		.unwrap()
+}
+
+pub fn delete_invite(id: &str) -> Request<()> {
+	let endpoint = format!("/api/invite/{}", url_encode(id));
+	Request::builder()
+		.method(Method::DELETE)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn register(registration: dto::Registration) -> Request<dto::Registration> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/register")
+		.body(registration)
+		.unwrap()
+}
+
+pub fn get_preferences() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/preferences")
+		.body(())
+		.unwrap()
+}
+
+pub fn put_preferences(preferences: dto::Preferences) -> Request<dto::Preferences> {
+	Request::builder()
+		.method(Method::PUT)
+		.uri("/api/preferences")
+		.body(preferences)
+		.unwrap()
+}
+
+pub fn transfers() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/transfers")
+		.body(())
+		.unwrap()
+}
+
+pub fn user_transfers(username: &str) -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri(format!("/api/user/{}/transfers", url_encode(username)))
+		.body(())
+		.unwrap()
+}
+
+pub fn get_listenbrainz() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/listenbrainz")
+		.body(())
+		.unwrap()
+}
+
+pub fn delete_listenbrainz() -> Request<()> {
+	Request::builder()
+		.method(Method::DELETE)
+		.uri("/api/listenbrainz")
+		.body(())
+		.unwrap()
+}
+
+pub fn get_audit_log() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/audit_log")
+		.body(())
+		.unwrap()
+}
+
+pub fn unlock_user(username: &str) -> Request<()> {
+	Request::builder()
+		.method(Method::POST)
+		.uri(format!("/api/user/{}/unlock", username))
+		.body(())
+		.unwrap()
+}
+
+pub fn trigger_index() -> Request<()> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/trigger_index")
+		.body(())
+		.unwrap()
+}
+
+pub fn index_status() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/index_status")
+		.body(())
+		.unwrap()
+}
+
+pub fn index_pause() -> Request<()> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/index/pause")
+		.body(())
+		.unwrap()
+}
+
+pub fn index_resume() -> Request<()> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/index/resume")
+		.body(())
+		.unwrap()
+}
+
+pub fn index_cancel() -> Request<()> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/index/cancel")
+		.body(())
+		.unwrap()
+}
+
+pub fn index_refresh(path: &Path) -> Request<dto::IndexRefreshInput> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/index/refresh")
+		.body(dto::IndexRefreshInput {
+			path: path.to_owned(),
+		})
+		.unwrap()
+}
+
+pub fn browse<VERSION: ProtocolVersion>(path: &Path) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!("/api/browse/{}", url_encode(path.as_ref()));
+	Request::builder()
+		.header("Accept-Version", VERSION::header_value())
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn flatten<VERSION: ProtocolVersion>(path: &Path) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!("/api/flatten/{}", url_encode(path.as_ref()));
+	Request::builder()
+		.header("Accept-Version", VERSION::header_value())
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn zip(path: &Path) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!("/api/zip/{}", url_encode(path.as_ref()));
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn album_zip(name: &str, artists: &[&str]) -> Request<()> {
+	let endpoint = format!(
+		"/api/album/{}/by/{}/zip",
+		url_encode(name),
+		url_encode(&artists.join(API_ARRAY_SEPARATOR))
+	);
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn album(name: &str, artists: &[&str]) -> Request<()> {
+	let endpoint = format!(
+		"/api/album/{}/by/{}",
+		url_encode(name),
+		url_encode(&artists.join(API_ARRAY_SEPARATOR))
+	);
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn genres<VERSION: ProtocolVersion>() -> Request<()> {
+	Request::builder()
+		.header("Accept-Version", VERSION::header_value())
+		.method(Method::GET)
+		.uri("/api/genres")
+		.body(())
+		.unwrap()
+}
+
+pub fn genre<VERSION: ProtocolVersion>(genre: &str) -> Request<()> {
+	let endpoint = format!("/api/genre/{}", url_encode(genre));
+	Request::builder()
+		.header("Accept-Version", VERSION::header_value())
+		.method(Method::GET)
+		.uri(endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn genre_albums<VERSION: ProtocolVersion>(genre: &str) -> Request<()> {
+	let endpoint = format!("/api/genre/{}/albums", url_encode(genre));
+	Request::builder()
+		.header("Accept-Version", VERSION::header_value())
+		.method(Method::GET)
+		.uri(endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn genre_artists<VERSION: ProtocolVersion>(genre: &str) -> Request<()> {
+	let endpoint = format!("/api/genre/{}/artists", url_encode(genre));
+	Request::builder()
+		.header("Accept-Version", VERSION::header_value())
+		.method(Method::GET)
+		.uri(endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn genre_songs<VERSION: ProtocolVersion>(genre: &str) -> Request<()> {
+	let endpoint = format!("/api/genre/{}/songs", url_encode(genre));
+	Request::builder()
+		.header("Accept-Version", VERSION::header_value())
+		.method(Method::GET)
+		.uri(endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn albums() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/albums")
+		.body(())
+		.unwrap()
+}
+
+pub fn mix(genre: Option<&str>, decade: Option<i64>, length: Option<usize>) -> Request<()> {
+	let mut parameters = vec![];
+	if let Some(genre) = genre {
+		parameters.push(format!("genre={}", url_encode(genre)));
+	}
+	if let Some(decade) = decade {
+		parameters.push(format!("decade={decade}"));
+	}
+	if let Some(length) = length {
+		parameters.push(format!("length={length}"));
+	}
+	let endpoint = format!("/api/mix?{}", parameters.join("&"));
+	Request::builder()
+		.method(Method::GET)
+		.uri(endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn random<VERSION: ProtocolVersion>() -> Request<()> {
+	Request::builder()
+		.header("Accept-Version", VERSION::header_value())
+		.method(Method::GET)
+		.uri("/api/albums/random")
+		.body(())
+		.unwrap()
+}
+
+pub fn recent<VERSION: ProtocolVersion>() -> Request<()> {
+	Request::builder()
+		.header("Accept-Version", VERSION::header_value())
+		.method(Method::GET)
+		.uri("/api/albums/recent")
+		.body(())
+		.unwrap()
+}
+
+pub fn recently_added_songs() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/recently_added/songs")
+		.body(())
+		.unwrap()
+}
+
+pub fn search<VERSION: ProtocolVersion>(query: &str) -> Request<()> {
+	let endpoint = format!("/api/search/{}", url_encode(query));
+	Request::builder()
+		.header("Accept-Version", VERSION::header_value())
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn songs(songs: dto::GetSongsBulkInput) -> Request<dto::GetSongsBulkInput> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/songs")
+		.body(songs)
+		.unwrap()
+}
+
+pub fn starred() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/starred")
 		.body(())
 		.unwrap()
 }
 
-pub fn version() -> Request<()> {
+pub fn star_song(path: &Path) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!("/api/star/song/{}", url_encode(path.as_ref()));
 	Request::builder()
-		.method(Method::GET)
-		.uri("/api/version")
+		.method(Method::PUT)
+		.uri(&endpoint)
 		.body(())
 		.unwrap()
 }
 
-pub fn initial_setup() -> Request<()> {
+pub fn unstar_song(path: &Path) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!("/api/star/song/{}", url_encode(path.as_ref()));
 	Request::builder()
-		.method(Method::GET)
-		.uri("/api/initial_setup")
+		.method(Method::DELETE)
+		.uri(&endpoint)
 		.body(())
 		.unwrap()
 }
 
-pub fn login(username: &str, password: &str) -> Request<dto::Credentials> {
-	let credentials = dto::Credentials {
-		username: username.into(),
-		password: password.into(),
-	};
+pub fn star_artist(name: &str) -> Request<()> {
+	let endpoint = format!("/api/star/artist/{}", url_encode(name));
 	Request::builder()
-		.method(Method::POST)
-		.uri("/api/auth")
-		.body(credentials)
+		.method(Method::PUT)
+		.uri(&endpoint)
+		.body(())
 		.unwrap()
 }
 
-pub fn put_mount_dirs(dirs: Vec<dto::MountDir>) -> Request<Vec<dto::MountDir>> {
+pub fn rate_song(path: &Path, rating: u8) -> Request<dto::Rating> {
+	let path = path.to_string_lossy();
+	let endpoint = format!("/api/rating/song/{}", url_encode(path.as_ref()));
 	Request::builder()
 		.method(Method::PUT)
-		.uri("/api/mount_dirs")
-		.body(dirs)
+		.uri(&endpoint)
+		.body(dto::Rating { rating })
 		.unwrap()
 }
 
-pub fn get_settings() -> Request<()> {
+pub fn rate_album(name: &str, artists: &[&str], rating: u8) -> Request<dto::Rating> {
+	let endpoint = format!(
+		"/api/rating/album/{}/by/{}",
+		url_encode(name),
+		url_encode(&artists.join(API_ARRAY_SEPARATOR))
+	);
 	Request::builder()
-		.method(Method::GET)
-		.uri("/api/settings")
-		.body(())
+		.method(Method::PUT)
+		.uri(&endpoint)
+		.body(dto::Rating { rating })
 		.unwrap()
 }
 
-pub fn put_settings(settings: dto::NewSettings) -> Request<dto::NewSettings> {
+pub fn clear_album_rating(name: &str, artists: &[&str]) -> Request<()> {
+	let endpoint = format!(
+		"/api/rating/album/{}/by/{}",
+		url_encode(name),
+		url_encode(&artists.join(API_ARRAY_SEPARATOR))
+	);
 	Request::builder()
-		.method(Method::PUT)
-		.uri("/api/settings")
-		.body(settings)
+		.method(Method::DELETE)
+		.uri(&endpoint)
+		.body(())
 		.unwrap()
 }
 
-pub fn list_users() -> Request<()> {
+pub fn changes(since: Option<&str>) -> Request<()> {
+	let endpoint = match since {
+		Some(token) => format!("/api/changes?since={}", url_encode(token)),
+		None => "/api/changes".to_owned(),
+	};
 	Request::builder()
 		.method(Method::GET)
-		.uri("/api/users")
+		.uri(&endpoint)
 		.body(())
 		.unwrap()
 }
 
-pub fn create_user(new_user: dto::NewUser) -> Request<dto::NewUser> {
+pub fn audio(path: &Path) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!("/api/audio/{}", url_encode(path.as_ref()));
 	Request::builder()
-		.method(Method::POST)
-		.uri("/api/user")
-		.body(new_user)
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
 		.unwrap()
 }
 
-pub fn update_user(username: &str, user_update: dto::UserUpdate) -> Request<dto::UserUpdate> {
+pub fn download_audio(path: &Path) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!("/api/audio/{}?download=true", url_encode(path.as_ref()));
 	Request::builder()
-		.method(Method::PUT)
-		.uri(format!("/api/user/{}", username))
-		.body(user_update)
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
 		.unwrap()
 }
 
-pub fn delete_user(username: &str) -> Request<()> {
+pub fn events() -> Request<()> {
 	Request::builder()
-		.method(Method::DELETE)
-		.uri(format!("/api/user/{}", username))
+		.method(Method::GET)
+		.uri("/api/events")
 		.body(())
 		.unwrap()
 }
 
-pub fn trigger_index() -> Request<()> {
+pub fn playback(report: dto::PlaybackReport) -> Request<dto::PlaybackReport> {
 	Request::builder()
-		.method(Method::POST)
-		.uri("/api/trigger_index")
-		.body(())
+		.method(Method::PUT)
+		.uri("/api/playback")
+		.body(report)
 		.unwrap()
 }
 
-pub fn browse<VERSION: ProtocolVersion>(path: &Path) -> Request<()> {
-	let path = path.to_string_lossy();
-	let endpoint = format!("/api/browse/{}", url_encode(path.as_ref()));
+pub fn get_history() -> Request<()> {
 	Request::builder()
-		.header("Accept-Version", VERSION::header_value())
 		.method(Method::GET)
-		.uri(&endpoint)
+		.uri("/api/history")
 		.body(())
 		.unwrap()
 }
 
-pub fn flatten<VERSION: ProtocolVersion>(path: &Path) -> Request<()> {
-	let path = path.to_string_lossy();
-	let endpoint = format!("/api/flatten/{}", url_encode(path.as_ref()));
+pub fn post_history(play: dto::NewPlay) -> Request<dto::NewPlay> {
 	Request::builder()
-		.header("Accept-Version", VERSION::header_value())
-		.method(Method::GET)
-		.uri(&endpoint)
-		.body(())
+		.method(Method::POST)
+		.uri("/api/history")
+		.body(play)
 		.unwrap()
 }
 
-pub fn genres<VERSION: ProtocolVersion>() -> Request<()> {
+pub fn recently_played_songs() -> Request<()> {
 	Request::builder()
-		.header("Accept-Version", VERSION::header_value())
 		.method(Method::GET)
-		.uri("/api/genres")
+		.uri("/api/recently_played/songs")
 		.body(())
 		.unwrap()
 }
 
-pub fn genre<VERSION: ProtocolVersion>(genre: &str) -> Request<()> {
-	let endpoint = format!("/api/genre/{}", url_encode(genre));
+pub fn recently_played_albums() -> Request<()> {
 	Request::builder()
-		.header("Accept-Version", VERSION::header_value())
 		.method(Method::GET)
-		.uri(endpoint)
+		.uri("/api/recently_played/albums")
 		.body(())
 		.unwrap()
 }
 
-pub fn genre_albums<VERSION: ProtocolVersion>(genre: &str) -> Request<()> {
-	let endpoint = format!("/api/genre/{}/albums", url_encode(genre));
+pub fn never_played_songs() -> Request<()> {
 	Request::builder()
-		.header("Accept-Version", VERSION::header_value())
 		.method(Method::GET)
-		.uri(endpoint)
+		.uri("/api/never_played/songs")
 		.body(())
 		.unwrap()
 }
 
-pub fn genre_artists<VERSION: ProtocolVersion>(genre: &str) -> Request<()> {
-	let endpoint = format!("/api/genre/{}/artists", url_encode(genre));
+pub fn forgotten_albums() -> Request<()> {
 	Request::builder()
-		.header("Accept-Version", VERSION::header_value())
 		.method(Method::GET)
-		.uri(endpoint)
+		.uri("/api/forgotten/albums")
 		.body(())
 		.unwrap()
 }
 
-pub fn genre_songs<VERSION: ProtocolVersion>(genre: &str) -> Request<()> {
-	let endpoint = format!("/api/genre/{}/songs", url_encode(genre));
+pub fn stats() -> Request<()> {
 	Request::builder()
-		.header("Accept-Version", VERSION::header_value())
 		.method(Method::GET)
-		.uri(endpoint)
+		.uri("/api/stats")
 		.body(())
 		.unwrap()
 }
 
-pub fn random<VERSION: ProtocolVersion>() -> Request<()> {
+pub fn duplicates() -> Request<()> {
 	Request::builder()
-		.header("Accept-Version", VERSION::header_value())
 		.method(Method::GET)
-		.uri("/api/albums/random")
+		.uri("/api/duplicates")
 		.body(())
 		.unwrap()
 }
 
-pub fn recent<VERSION: ProtocolVersion>() -> Request<()> {
+pub fn metadata_report() -> Request<()> {
 	Request::builder()
-		.header("Accept-Version", VERSION::header_value())
 		.method(Method::GET)
-		.uri("/api/albums/recent")
+		.uri("/api/metadata_report")
 		.body(())
 		.unwrap()
 }
 
-pub fn search<VERSION: ProtocolVersion>(query: &str) -> Request<()> {
-	let endpoint = format!("/api/search/{}", url_encode(query));
+pub fn streams() -> Request<()> {
 	Request::builder()
-		.header("Accept-Version", VERSION::header_value())
 		.method(Method::GET)
-		.uri(&endpoint)
+		.uri("/api/streams")
 		.body(())
 		.unwrap()
 }
 
-pub fn songs(songs: dto::GetSongsBulkInput) -> Request<dto::GetSongsBulkInput> {
+pub fn peaks(path: &Path) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!("/api/peaks/{}", url_encode(path.as_ref()));
 	Request::builder()
-		.method(Method::POST)
-		.uri("/api/songs")
-		.body(songs)
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
 		.unwrap()
 }
 
-pub fn audio(path: &Path) -> Request<()> {
+pub fn lyrics(path: &Path) -> Request<()> {
 	let path = path.to_string_lossy();
-	let endpoint = format!("/api/audio/{}", url_encode(path.as_ref()));
+	let endpoint = format!("/api/lyrics/{}", url_encode(path.as_ref()));
 	Request::builder()
 		.method(Method::GET)
 		.uri(&endpoint)
@@ -249,9 +908,9 @@ pub fn audio(path: &Path) -> Request<()> {
 		.unwrap()
 }
 
-pub fn peaks(path: &Path) -> Request<()> {
+pub fn fingerprint(path: &Path) -> Request<()> {
 	let path = path.to_string_lossy();
-	let endpoint = format!("/api/peaks/{}", url_encode(path.as_ref()));
+	let endpoint = format!("/api/fingerprint/{}", url_encode(path.as_ref()));
 	Request::builder()
 		.method(Method::GET)
 		.uri(&endpoint)
@@ -293,6 +952,43 @@ pub fn thumbnail(path: &Path, size: Option<ThumbnailSize>, pad: Option<bool>) ->
 		.unwrap()
 }
 
+pub fn artist_image(name: &str) -> Request<()> {
+	let endpoint = format!("/api/artist/{}/image", url_encode(name));
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn put_artwork(path: &Path, image: Vec<u8>) -> Request<RawBody> {
+	let path = path.to_string_lossy();
+	let endpoint = format!("/api/artwork/{}", url_encode(path.as_ref()));
+	Request::builder()
+		.method(Method::PUT)
+		.uri(&endpoint)
+		.body(RawBody(image))
+		.unwrap()
+}
+
+pub fn delete_artwork(path: &Path) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!("/api/artwork/{}", url_encode(path.as_ref()));
+	Request::builder()
+		.method(Method::DELETE)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn family_mix(input: dto::FamilyMixInput) -> Request<dto::FamilyMixInput> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/family_mix")
+		.body(input)
+		.unwrap()
+}
+
 pub fn playlists() -> Request<()> {
 	Request::builder()
 		.method(Method::GET)
@@ -313,6 +1009,22 @@ pub fn save_playlist(
 		.unwrap()
 }
 
+pub fn get_play_queue() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/play_queue")
+		.body(())
+		.unwrap()
+}
+
+pub fn put_play_queue(play_queue: dto::SavePlayQueueInput) -> Request<dto::SavePlayQueueInput> {
+	Request::builder()
+		.method(Method::PUT)
+		.uri("/api/play_queue")
+		.body(play_queue)
+		.unwrap()
+}
+
 pub fn read_playlist<VERSION: ProtocolVersion>(name: &str) -> Request<()> {
 	let endpoint = format!("/api/playlist/{}", url_encode(name));
 	Request::builder()
@@ -332,6 +1044,141 @@ pub fn delete_playlist(name: &str) -> Request<()> {
 		.unwrap()
 }
 
+pub fn create_playlist_feed(name: &str) -> Request<()> {
+	let endpoint = format!("/api/playlist/{}/feed", url_encode(name));
+	Request::builder()
+		.method(Method::POST)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn revoke_playlist_feed(name: &str) -> Request<()> {
+	let endpoint = format!("/api/playlist/{}/feed", url_encode(name));
+	Request::builder()
+		.method(Method::DELETE)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn playlist_feed(feed_path: &str) -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri(feed_path)
+		.body(())
+		.unwrap()
+}
+
+pub fn playlist_feed_audio(feed_path: &str, path: &Path) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!("{feed_path}/audio/{}", url_encode(path.as_ref()));
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn webdav(method: &str, path: &Path) -> Request<()> {
+	let segments = path
+		.iter()
+		.map(|s| url_encode(&s.to_string_lossy()))
+		.collect::<Vec<_>>();
+	let endpoint = format!("/dav/{}", segments.join("/"));
+	Request::builder()
+		.method(Method::from_bytes(method.as_bytes()).unwrap())
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
 fn url_encode(input: &str) -> String {
 	percent_encode(input.as_bytes(), NON_ALPHANUMERIC).to_string()
 }
+
+pub fn dlna_description() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/dlna/description.xml")
+		.body(())
+		.unwrap()
+}
+
+pub fn dlna_browse(object_id: &str) -> Request<RawBody> {
+	let body = format!(
+		r#"<?xml version="1.0"?><s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/"><s:Body><u:Browse xmlns:u="urn:schemas-upnp-org:service:ContentDirectory:1"><ObjectID>{object_id}</ObjectID><BrowseFlag>BrowseDirectChildren</BrowseFlag><Filter>*</Filter><StartingIndex>0</StartingIndex><RequestedCount>0</RequestedCount><SortCriteria/></u:Browse></s:Body></s:Envelope>"#
+	);
+	Request::builder()
+		.method(Method::POST)
+		.uri("/dlna/control/content_directory")
+		.header(
+			"soapaction",
+			r#""urn:schemas-upnp-org:service:ContentDirectory:1#Browse""#,
+		)
+		.body(RawBody(body.into_bytes()))
+		.unwrap()
+}
+
+pub fn dlna_media(path: &Path) -> Request<()> {
+	let path = path.to_string_lossy();
+	let endpoint = format!("/dlna/media/{}", url_encode(path.as_ref()));
+	Request::builder()
+		.method(Method::GET)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn radio_stations() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/radio_stations")
+		.body(())
+		.unwrap()
+}
+
+pub fn create_radio_station(station: dto::RadioStationInput) -> Request<dto::RadioStationInput> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/radio_stations")
+		.body(station)
+		.unwrap()
+}
+
+pub fn update_radio_station(
+	id: &str,
+	station: dto::RadioStationInput,
+) -> Request<dto::RadioStationInput> {
+	let endpoint = format!("/api/radio_station/{}", url_encode(id));
+	Request::builder()
+		.method(Method::PUT)
+		.uri(&endpoint)
+		.body(station)
+		.unwrap()
+}
+
+pub fn delete_radio_station(id: &str) -> Request<()> {
+	let endpoint = format!("/api/radio_station/{}", url_encode(id));
+	Request::builder()
+		.method(Method::DELETE)
+		.uri(&endpoint)
+		.body(())
+		.unwrap()
+}
+
+pub fn jukebox_status() -> Request<()> {
+	Request::builder()
+		.method(Method::GET)
+		.uri("/api/jukebox")
+		.body(())
+		.unwrap()
+}
+
+pub fn jukebox_next() -> Request<()> {
+	Request::builder()
+		.method(Method::POST)
+		.uri("/api/jukebox/next")
+		.body(())
+		.unwrap()
+}
@@ -0,0 +1,93 @@
+use http::StatusCode;
+
+use crate::server::dto;
+use crate::server::test::{protocol, ServiceType, TestService};
+use crate::test_name;
+
+fn jazz_fm() -> dto::RadioStationInput {
+	dto::RadioStationInput {
+		name: "Jazz FM".to_owned(),
+		stream_url: "https://radio.example.com/jazz.mp3".to_owned(),
+		homepage_url: Some("https://radio.example.com".to_owned()),
+	}
+}
+
+#[tokio::test]
+async fn list_radio_stations_requires_auth() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	let request = protocol::radio_stations();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn create_radio_station_requires_admin() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+	let request = protocol::create_radio_station(jazz_fm());
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn create_radio_station_rejects_bad_url() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+	let request = protocol::create_radio_station(dto::RadioStationInput {
+		stream_url: "not a url".to_owned(),
+		..jazz_fm()
+	});
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn radio_stations_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+
+	let request = protocol::create_radio_station(jazz_fm());
+	let response = service.fetch_json::<_, dto::RadioStation>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let station = response.into_body();
+	assert_eq!(station.name, "Jazz FM");
+
+	let request = protocol::update_radio_station(
+		&station.id,
+		dto::RadioStationInput {
+			homepage_url: None,
+			..jazz_fm()
+		},
+	);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	service.logout().await;
+	service.login().await;
+	let request = protocol::radio_stations();
+	let response = service
+		.fetch_json::<_, Vec<dto::RadioStation>>(&request)
+		.await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(
+		response.into_body(),
+		vec![dto::RadioStation {
+			homepage_url: None,
+			..station.clone()
+		}]
+	);
+
+	let request = protocol::delete_radio_station(&station.id);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+	service.logout().await;
+	service.login_admin().await;
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
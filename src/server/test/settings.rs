@@ -64,6 +64,9 @@ async fn put_settings_golden_path() {
 	let request = protocol::put_settings(dto::NewSettings {
 		album_art_pattern: Some("test_pattern".to_owned()),
 		ddns_update_url: Some("http://example.com/".to_owned()),
+		detect_silence: Some(true),
+		filename_pattern: Some("%artist%/%title%".to_owned()),
+		artist_separators: Some(vec![" & ".to_owned()]),
 	});
 	let response = service.fetch(&request).await;
 	assert_eq!(response.status(), StatusCode::OK);
@@ -76,6 +79,126 @@ async fn put_settings_golden_path() {
 		&Settings {
 			album_art_pattern: "test_pattern".to_owned(),
 			ddns_update_url: "http://example.com/".to_owned(),
+			detect_silence: true,
+			filename_pattern: "%artist%/%title%".to_owned(),
+			artist_separators: vec![" & ".to_owned()],
 		},
 	);
 }
+
+#[tokio::test]
+async fn put_settings_rejects_empty_artist_separator() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+
+	let request = protocol::put_settings(dto::NewSettings {
+		artist_separators: Some(vec![";".to_owned(), "".to_owned()]),
+		..Default::default()
+	});
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn put_settings_rejects_bad_filename_pattern() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+
+	let request = protocol::put_settings(dto::NewSettings {
+		filename_pattern: Some("%artist%/%oink%".to_owned()),
+		..Default::default()
+	});
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn aliases_require_admin() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+
+	let request = protocol::get_aliases();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+	let request = protocol::put_aliases(dto::Aliases::default());
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn aliases_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+
+	let aliases = dto::Aliases {
+		artists: vec![dto::Alias {
+			name: "Sigur Rós".to_owned(),
+			aliases: vec!["Sigur Ros".to_owned()],
+		}],
+		albums: vec![dto::Alias {
+			name: "Ágætis byrjun".to_owned(),
+			aliases: vec!["Agaetis Byrjun".to_owned()],
+		}],
+		genres: vec![dto::Alias {
+			name: "Hip-Hop".to_owned(),
+			aliases: vec!["Rap".to_owned()],
+		}],
+	};
+	let request = protocol::put_aliases(aliases.clone());
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::get_aliases();
+	let response = service.fetch_json::<_, dto::Aliases>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body(), &aliases);
+}
+
+#[tokio::test]
+async fn put_aliases_rejects_blank_name() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+
+	let request = protocol::put_aliases(dto::Aliases {
+		artists: vec![dto::Alias {
+			name: "".to_owned(),
+			aliases: vec!["Sigur Ros".to_owned()],
+		}],
+		albums: vec![],
+		genres: vec![],
+	});
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn put_aliases_is_all_or_nothing() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+
+	let request = protocol::put_aliases(dto::Aliases {
+		artists: vec![dto::Alias {
+			name: "Sigur Rós".to_owned(),
+			aliases: vec!["Sigur Ros".to_owned()],
+		}],
+		albums: vec![],
+		genres: vec![dto::Alias {
+			name: "".to_owned(),
+			aliases: vec!["Rap".to_owned()],
+		}],
+	});
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+	let request = protocol::get_aliases();
+	let response = service.fetch_json::<_, dto::Aliases>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body(), &dto::Aliases::default());
+}
@@ -37,6 +37,8 @@ async fn create_user_requires_admin() {
 		name: "Walter".into(),
 		password: "secret".into(),
 		admin: false,
+		guest: false,
+		permissions: None,
 	});
 
 	let response = service.fetch(&request).await;
@@ -57,6 +59,8 @@ async fn create_user_golden_path() {
 		name: "Walter".into(),
 		password: "secret".into(),
 		admin: false,
+		guest: false,
+		permissions: None,
 	};
 	let request = protocol::create_user(new_user);
 	let response = service.fetch(&request).await;
@@ -130,6 +134,54 @@ async fn delete_user_golden_path() {
 	assert_eq!(response.status(), StatusCode::OK);
 }
 
+#[tokio::test]
+async fn delete_user_revokes_sessions_and_api_keys() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	let new_user = dto::NewUser {
+		name: "Walter".into(),
+		password: "secret".into(),
+		admin: false,
+		guest: false,
+		permissions: None,
+	};
+
+	service.login_admin().await;
+	let response = service
+		.fetch(&protocol::create_user(new_user.clone()))
+		.await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::login("Walter", "secret");
+	let response = service.fetch_json::<_, dto::Authorization>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let session = response.into_body();
+	service.set_authorization(Some(session.clone()));
+	let request = protocol::post_api_key(dto::NewApiKey {
+		name: "Scrobbler".to_owned(),
+		scopes: vec![dto::ApiKeyScope::Read],
+	});
+	let response = service.fetch_json::<_, dto::CreatedApiKey>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let api_key = dto::Authorization {
+		token: response.into_body().key,
+		..session.clone()
+	};
+
+	service.login_admin().await;
+	let response = service.fetch(&protocol::delete_user("Walter")).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let response = service.fetch(&protocol::create_user(new_user)).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	for authorization in [session, api_key] {
+		service.set_authorization(Some(authorization));
+		let request = protocol::get_play_queue();
+		let response = service.fetch(&request).await;
+		assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+	}
+}
+
 #[tokio::test]
 async fn delete_user_cannot_delete_self() {
 	let mut service = ServiceType::new(&test_name!()).await;
@@ -139,3 +191,439 @@ async fn delete_user_cannot_delete_self() {
 	let response = service.fetch(&request).await;
 	assert_eq!(response.status(), StatusCode::CONFLICT);
 }
+
+#[tokio::test]
+async fn logout_user_requires_admin() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	let request = protocol::logout_user(TEST_USERNAME);
+
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+	service.login().await;
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn logout_user_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+
+	let request = protocol::login(TEST_USERNAME, TEST_PASSWORD);
+	let response = service.fetch_json::<_, dto::Authorization>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let user_authorization = response.into_body();
+
+	service.login_admin().await;
+	let request = protocol::logout_user(TEST_USERNAME);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::logout_user("garbage");
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+	service.set_authorization(Some(user_authorization));
+	let request = protocol::sessions();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+	service.login().await;
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn logout_user_revokes_api_keys_by_default() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+
+	service.login().await;
+	let request = protocol::post_api_key(dto::NewApiKey {
+		name: "Scrobbler".to_owned(),
+		scopes: vec![dto::ApiKeyScope::Read],
+	});
+	let response = service.fetch_json::<_, dto::CreatedApiKey>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let api_key = dto::Authorization {
+		username: TEST_USERNAME.to_owned(),
+		token: response.into_body().key,
+		is_admin: false,
+	};
+
+	service.login_admin().await;
+	let request = protocol::logout_user_keeping_api_keys(TEST_USERNAME);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	service.set_authorization(Some(api_key.clone()));
+	let request = protocol::get_play_queue();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	service.login_admin().await;
+	let request = protocol::logout_user(TEST_USERNAME);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	service.set_authorization(Some(api_key));
+	let request = protocol::get_play_queue();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn unlock_user_requires_admin() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	let request = protocol::unlock_user(TEST_USERNAME);
+
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+	service.login().await;
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn unlock_user_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+
+	let request = protocol::unlock_user(TEST_USERNAME);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::unlock_user("garbage");
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn guest_cannot_make_changes() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+
+	let request = protocol::create_user(dto::NewUser {
+		name: "Walter".into(),
+		password: "secret".into(),
+		admin: false,
+		guest: true,
+		permissions: None,
+	});
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::list_users();
+	let response = service.fetch_json::<_, Vec<dto::User>>(&request).await;
+	assert!(response
+		.body()
+		.iter()
+		.any(|u| u.name == "Walter" && u.is_guest));
+
+	let request = protocol::login("Walter", "secret");
+	let response = service.fetch_json::<_, dto::Authorization>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	service.set_authorization(Some(response.into_body()));
+
+	let request = protocol::get_play_queue();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::put_play_queue(dto::SavePlayQueueInput {
+		songs: vec![],
+		position: 0,
+		offset: 0,
+	});
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+	let request = protocol::refresh_auth();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn user_management_can_be_delegated() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+
+	let request = protocol::create_user(dto::NewUser {
+		name: "Walter".into(),
+		password: "secret".into(),
+		admin: false,
+		guest: false,
+		permissions: Some(vec![dto::Permission::ManageUsers]),
+	});
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::login("Walter", "secret");
+	let response = service.fetch_json::<_, dto::Authorization>(&request).await;
+	service.set_authorization(Some(response.into_body()));
+
+	let request = protocol::list_users();
+	let response = service.fetch_json::<_, Vec<dto::User>>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let walter = response.body().iter().find(|u| u.name == "Walter").unwrap();
+	assert_eq!(walter.permissions, vec![dto::Permission::ManageUsers]);
+
+	let new_user = |admin: bool| dto::NewUser {
+		name: "Jesse".into(),
+		password: "secret".into(),
+		admin,
+		guest: false,
+		permissions: None,
+	};
+	let response = service.fetch(&protocol::create_user(new_user(true))).await;
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+	let response = service.fetch(&protocol::create_user(new_user(false))).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::delete_user(TEST_USERNAME_ADMIN);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+	let request = protocol::delete_user("Jesse");
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::trigger_index();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn invites_require_admin() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	let request = protocol::post_invite(dto::NewInvite::default());
+
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+	service.login().await;
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn invite_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+
+	let request = protocol::post_invite(dto::NewInvite::default());
+	let response = service.fetch_json::<_, dto::CreatedInvite>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let created = response.into_body();
+
+	let request = protocol::get_invites();
+	let response = service.fetch_json::<_, Vec<dto::Invite>>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body(), &vec![created.invite.clone()]);
+
+	service.logout().await;
+	let registration = |token: &str| dto::Registration {
+		token: token.to_owned(),
+		username: "Walter".into(),
+		password: "secret".into(),
+	};
+	let request = protocol::register(registration(&created.token));
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+	let request = protocol::login("Walter", "secret");
+	let response = service.fetch_json::<_, dto::Authorization>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert!(!response.body().is_admin);
+
+	service.login_admin().await;
+	let request = protocol::post_invite(dto::NewInvite::default());
+	let response = service.fetch_json::<_, dto::CreatedInvite>(&request).await;
+	let created = response.into_body();
+	let request = protocol::delete_invite(&created.invite.id);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::register(registration(&created.token));
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn audit_log_requires_admin() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	let request = protocol::get_audit_log();
+
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+	service.login().await;
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn audit_log_records_logins_and_user_changes() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+
+	let request = protocol::login(TEST_USERNAME, "not the password");
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+	service.login_admin().await;
+	let request = protocol::delete_user(TEST_USERNAME);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::get_audit_log();
+	let response = service
+		.fetch_json::<_, Vec<dto::AuditEntry>>(&request)
+		.await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let entries = response.into_body();
+	let events = entries
+		.iter()
+		.map(|e| (e.actor.as_deref(), e.event.clone()))
+		.take(3)
+		.collect::<Vec<_>>();
+	assert_eq!(
+		events,
+		vec![
+			(
+				Some(TEST_USERNAME_ADMIN),
+				dto::AuditEvent::UserDeleted {
+					name: TEST_USERNAME.to_owned()
+				}
+			),
+			(Some(TEST_USERNAME_ADMIN), dto::AuditEvent::LoginSucceeded),
+			(Some(TEST_USERNAME), dto::AuditEvent::LoginFailed),
+		]
+	);
+}
+
+#[tokio::test]
+async fn audit_log_records_account_management() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+
+	let request = protocol::post_invite(dto::NewInvite::default());
+	let response = service.fetch_json::<_, dto::CreatedInvite>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let invite_id = response.into_body().invite.id;
+	let request = protocol::delete_invite(&invite_id);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::unlock_user(TEST_USERNAME);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let request = protocol::logout_user(TEST_USERNAME);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::get_audit_log();
+	let response = service
+		.fetch_json::<_, Vec<dto::AuditEntry>>(&request)
+		.await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let events = response
+		.into_body()
+		.into_iter()
+		.map(|e| e.event)
+		.take(4)
+		.collect::<Vec<_>>();
+	assert_eq!(
+		events,
+		vec![
+			dto::AuditEvent::UserLoggedOut {
+				name: TEST_USERNAME.to_owned()
+			},
+			dto::AuditEvent::UserUnlocked {
+				name: TEST_USERNAME.to_owned()
+			},
+			dto::AuditEvent::InviteRevoked {
+				id: invite_id.clone()
+			},
+			dto::AuditEvent::InviteCreated { id: invite_id },
+		]
+	);
+}
+
+#[tokio::test]
+async fn preferences_golden_path() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+
+	let update = dto::Preferences(
+		[
+			("theme".to_owned(), serde_json::json!("dark")),
+			("language".to_owned(), serde_json::json!("fr")),
+		]
+		.into(),
+	);
+	let request = protocol::put_preferences(update);
+	let response = service.fetch_json::<_, dto::Preferences>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let update = dto::Preferences([("theme".to_owned(), serde_json::Value::Null)].into());
+	let request = protocol::put_preferences(update);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let request = protocol::get_preferences();
+	let response = service.fetch_json::<_, dto::Preferences>(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(
+		response.body(),
+		&dto::Preferences([("language".to_owned(), serde_json::json!("fr"))].into())
+	);
+
+	let update = dto::Preferences([("bad key".to_owned(), serde_json::json!(1))].into());
+	let request = protocol::put_preferences(update);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+	service.login_admin().await;
+	let request = protocol::get_preferences();
+	let response = service.fetch_json::<_, dto::Preferences>(&request).await;
+	assert!(response.body().0.is_empty());
+}
+
+#[tokio::test]
+async fn listenbrainz_requires_auth() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	let request = protocol::get_listenbrainz();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn listenbrainz_not_linked() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+
+	let request = protocol::get_listenbrainz();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+	let request = protocol::delete_listenbrainz();
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
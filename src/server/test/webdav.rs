@@ -0,0 +1,176 @@
+use headers::{Authorization, HeaderMapExt};
+use http::{header, StatusCode};
+use std::path::PathBuf;
+
+use crate::server::dto;
+use crate::server::test::{constants::*, protocol, ServiceType, TestService};
+use crate::test_name;
+
+#[tokio::test]
+async fn webdav_requires_auth() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+
+	let request = protocol::webdav("PROPFIND", &PathBuf::new());
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+	assert!(response.headers().contains_key(header::WWW_AUTHENTICATE));
+
+	let mut request = protocol::webdav("PROPFIND", &PathBuf::new());
+	request
+		.headers_mut()
+		.typed_insert(Authorization::basic(TEST_USERNAME, "not the password"));
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn webdav_respects_lockout() {
+	let config = "[auth]\nlockout_threshold = 1\n";
+	let mut service = ServiceType::new_with_config(&test_name!(), config).await;
+	service.complete_initial_setup().await;
+
+	let mut request = protocol::webdav("PROPFIND", &PathBuf::new());
+	request
+		.headers_mut()
+		.typed_insert(Authorization::basic(TEST_USERNAME, "not the password"));
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+	let mut request = protocol::webdav("PROPFIND", &PathBuf::new());
+	request
+		.headers_mut()
+		.typed_insert(Authorization::basic(TEST_USERNAME, TEST_PASSWORD));
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+	let request = protocol::login(TEST_USERNAME, TEST_PASSWORD);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::LOCKED);
+}
+
+#[tokio::test]
+async fn webdav_accepts_basic_credentials() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+
+	let mut request = protocol::webdav("PROPFIND", &PathBuf::new());
+	request
+		.headers_mut()
+		.typed_insert(Authorization::basic(TEST_USERNAME, TEST_PASSWORD));
+	let response = service.fetch_bytes(&request).await;
+	assert_eq!(response.status(), StatusCode::MULTI_STATUS);
+	let body = String::from_utf8(response.into_body()).unwrap();
+	assert!(body.contains(&format!("<D:href>/dav/{TEST_MOUNT_NAME}/</D:href>")));
+}
+
+#[tokio::test]
+async fn webdav_lists_directory() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted"].iter().collect();
+	let mut request = protocol::webdav("PROPFIND", &path);
+	request.headers_mut().insert("Depth", "1".parse().unwrap());
+	let response = service.fetch_bytes(&request).await;
+	assert_eq!(response.status(), StatusCode::MULTI_STATUS);
+	let body = String::from_utf8(response.into_body()).unwrap();
+	assert_eq!(body.matches("<D:response>").count(), 7);
+	assert!(body.contains(&format!(
+		"<D:href>/dav/{TEST_MOUNT_NAME}/Khemmis/Hunted/01%20-%20Above%20The%20Water.mp3</D:href>"
+	)));
+	assert!(body.contains("<D:getcontentlength>"));
+
+	let mut request = protocol::webdav("PROPFIND", &path);
+	request.headers_mut().insert("Depth", "0".parse().unwrap());
+	let response = service.fetch_bytes(&request).await;
+	let body = String::from_utf8(response.into_body()).unwrap();
+	assert_eq!(body.matches("<D:response>").count(), 1);
+}
+
+#[tokio::test]
+async fn webdav_serves_files() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+	let request = protocol::webdav("GET", &path);
+	let response = service.fetch_bytes(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.body().len(), 24_142);
+}
+
+#[tokio::test]
+async fn webdav_files_require_download_permission() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login_admin().await;
+
+	let request = protocol::create_user(dto::NewUser {
+		name: "Walter".into(),
+		password: "secret".into(),
+		admin: false,
+		guest: false,
+		permissions: Some(vec![]),
+	});
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+	let mut request = protocol::webdav("GET", &path);
+	request
+		.headers_mut()
+		.typed_insert(Authorization::basic("Walter", "secret"));
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn webdav_files_are_refused_to_capped_users() {
+	let config = format!(
+		"[[mount_dirs]]\nname = \"{TEST_MOUNT_NAME}\"\nsource = \"{TEST_MOUNT_SOURCE}\"\n\n[[users]]\nname = \"{TEST_USERNAME}\"\ninitial_password = \"{TEST_PASSWORD}\"\n\n[users.stream_quality]\nmax_bitrate_kbps = 64\nremote_only = false\n"
+	);
+	let mut service = ServiceType::new_with_config(&test_name!(), &config).await;
+	service.login().await;
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+	let request = protocol::webdav("GET", &path);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn webdav_is_read_only() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "Khemmis", "Hunted", "02 - Candlelight.mp3"]
+		.iter()
+		.collect();
+	for method in ["PUT", "DELETE", "MKCOL", "MOVE"] {
+		let request = protocol::webdav(method, &path);
+		let response = service.fetch(&request).await;
+		assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+	}
+}
+
+#[tokio::test]
+async fn webdav_rejects_parent_directories() {
+	let mut service = ServiceType::new(&test_name!()).await;
+	service.complete_initial_setup().await;
+	service.login().await;
+
+	let path: PathBuf = [TEST_MOUNT_NAME, "..", "..", "Cargo.toml"].iter().collect();
+	let request = protocol::webdav("GET", &path);
+	let response = service.fetch(&request).await;
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
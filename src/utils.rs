@@ -17,6 +17,8 @@ pub use crate::match_ignore_case;
 pub enum AudioFormat {
 	AIFF,
 	APE,
+	DFF,
+	DSF,
 	FLAC,
 	MP3,
 	MP4,
@@ -24,9 +26,30 @@ pub enum AudioFormat {
 	OGG,
 	OPUS,
 	WAVE,
+	WAVPACK,
 	M4B,
 }
 
+impl AudioFormat {
+	pub fn mime_type(&self) -> &'static str {
+		match self {
+			AudioFormat::AIFF => "audio/aiff",
+			AudioFormat::APE => "audio/x-ape",
+			AudioFormat::DFF => "audio/x-dff",
+			AudioFormat::DSF => "audio/x-dsf",
+			AudioFormat::FLAC => "audio/flac",
+			AudioFormat::MP3 => "audio/mpeg",
+			AudioFormat::MP4 => "audio/mp4",
+			AudioFormat::MPC => "audio/x-musepack",
+			AudioFormat::OGG => "audio/ogg",
+			AudioFormat::OPUS => "audio/ogg",
+			AudioFormat::WAVE => "audio/wav",
+			AudioFormat::WAVPACK => "audio/x-wavpack",
+			AudioFormat::M4B => "audio/mp4",
+		}
+	}
+}
+
 pub fn get_audio_format<P: AsRef<Path>>(path: P) -> Option<AudioFormat> {
 	let extension = match path.as_ref().extension() {
 		Some(e) => e,
@@ -40,13 +63,18 @@ pub fn get_audio_format<P: AsRef<Path>>(path: P) -> Option<AudioFormat> {
 		"aif" => Some(AudioFormat::AIFF),
 		"aiff" => Some(AudioFormat::AIFF),
 		"ape" => Some(AudioFormat::APE),
+		"dff" => Some(AudioFormat::DFF),
+		"dsf" => Some(AudioFormat::DSF),
 		"flac" => Some(AudioFormat::FLAC),
 		"mp3" => Some(AudioFormat::MP3),
 		"m4a" => Some(AudioFormat::MP4),
 		"mpc" => Some(AudioFormat::MPC),
+		"mp+" => Some(AudioFormat::MPC),
+		"mpp" => Some(AudioFormat::MPC),
 		"ogg" => Some(AudioFormat::OGG),
 		"opus" => Some(AudioFormat::OPUS),
 		"wav" => Some(AudioFormat::WAVE),
+		"wv" => Some(AudioFormat::WAVPACK),
 		"m4b" => Some(AudioFormat::M4B),
 		_ => None,
 	}
@@ -71,4 +99,20 @@ fn can_guess_audio_format() {
 		get_audio_format(Path::new("animals/🐷/my🐖file.wav")),
 		Some(AudioFormat::WAVE)
 	);
+	assert_eq!(
+		get_audio_format(Path::new("animals/🐷/my🐖file.wv")),
+		Some(AudioFormat::WAVPACK)
+	);
+	assert_eq!(
+		get_audio_format(Path::new("animals/🐷/my🐖file.dsf")),
+		Some(AudioFormat::DSF)
+	);
+	assert_eq!(
+		get_audio_format(Path::new("animals/🐷/my🐖file.dff")),
+		Some(AudioFormat::DFF)
+	);
+	assert_eq!(
+		get_audio_format(Path::new("animals/🐷/my🐖file.mpp")),
+		Some(AudioFormat::MPC)
+	);
 }